@@ -1,8 +1,10 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use core::arch::x86_64::{CpuidResult, __cpuid};
+
 use ostd::cpu::UserContext;
 
-use crate::cpu::LinuxAbi;
+use crate::{cpu::LinuxAbi, prelude::*};
 
 impl LinuxAbi for UserContext {
     fn syscall_num(&self) -> usize {
@@ -36,3 +38,124 @@ impl LinuxAbi for UserContext {
         self.fsbase()
     }
 }
+
+/// The names of the feature flags in EDX of CPUID leaf 1, as shown in `/proc/cpuinfo`.
+const EDX_FLAGS: [&str; 32] = [
+    "fpu", "vme", "de", "pse", "tsc", "msr", "pae", "mce", "cx8", "apic", "", "sep", "mtrr", "pge",
+    "mca", "cmov", "pat", "pse36", "pn", "clflush", "", "dts", "acpi", "mmx", "fxsr", "sse",
+    "sse2", "ss", "ht", "tm", "ia64", "pbe",
+];
+
+/// The names of the feature flags in ECX of CPUID leaf 1, as shown in `/proc/cpuinfo`.
+const ECX_FLAGS: [&str; 32] = [
+    "pni",
+    "pclmulqdq",
+    "dtes64",
+    "monitor",
+    "ds_cpl",
+    "vmx",
+    "smx",
+    "est",
+    "tm2",
+    "ssse3",
+    "cid",
+    "sdbg",
+    "fma",
+    "cx16",
+    "xtpr",
+    "pdcm",
+    "",
+    "pcid",
+    "dca",
+    "sse4_1",
+    "sse4_2",
+    "x2apic",
+    "movbe",
+    "popcnt",
+    "tsc_deadline_timer",
+    "aes",
+    "xsave",
+    "",
+    "avx",
+    "f16c",
+    "rdrand",
+    "hypervisor",
+];
+
+/// The information of the CPU, which is read from CPUID.
+#[derive(Debug, Clone)]
+pub struct CpuInformation {
+    pub vendor_id: String,
+    pub cpu_family: u32,
+    pub model: u32,
+    pub stepping: u32,
+    pub model_name: String,
+    pub flags: Vec<&'static str>,
+}
+
+impl CpuInformation {
+    /// Reads the information of the current CPU.
+    pub fn read() -> Self {
+        let cpuid = |leaf: u32| -> CpuidResult {
+            // SAFETY: CPUID is always available on x86-64.
+            unsafe { __cpuid(leaf) }
+        };
+
+        let leaf0 = cpuid(0);
+        let vendor_id = [leaf0.ebx, leaf0.edx, leaf0.ecx]
+            .iter()
+            .flat_map(|reg| reg.to_le_bytes())
+            .map(char::from)
+            .collect();
+
+        let leaf1 = cpuid(1);
+        let base_family = (leaf1.eax >> 8) & 0xf;
+        let base_model = (leaf1.eax >> 4) & 0xf;
+        let cpu_family = if base_family == 0xf {
+            base_family + ((leaf1.eax >> 20) & 0xff)
+        } else {
+            base_family
+        };
+        let model = if base_family == 0x6 || base_family == 0xf {
+            base_model | (((leaf1.eax >> 16) & 0xf) << 4)
+        } else {
+            base_model
+        };
+        let stepping = leaf1.eax & 0xf;
+
+        let model_name = if cpuid(0x8000_0000).eax >= 0x8000_0004 {
+            let bytes: Vec<u8> = (0x8000_0002..=0x8000_0004)
+                .map(cpuid)
+                .flat_map(|res| [res.eax, res.ebx, res.ecx, res.edx])
+                .flat_map(|reg| reg.to_le_bytes())
+                .take_while(|byte| *byte != 0)
+                .collect();
+            String::from_utf8_lossy(&bytes).trim().to_string()
+        } else {
+            String::from("unknown")
+        };
+
+        let flags = EDX_FLAGS
+            .iter()
+            .enumerate()
+            .filter(|(bit, _)| leaf1.edx & (1 << bit) != 0)
+            .chain(
+                ECX_FLAGS
+                    .iter()
+                    .enumerate()
+                    .filter(|(bit, _)| leaf1.ecx & (1 << bit) != 0),
+            )
+            .map(|(_, name)| *name)
+            .filter(|name| !name.is_empty())
+            .collect();
+
+        Self {
+            vendor_id,
+            cpu_family,
+            model,
+            stepping,
+            model_name,
+            flags,
+        }
+    }
+}
@@ -0,0 +1,172 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::format;
+use core::{
+    sync::atomic::{AtomicU32, AtomicU64, Ordering},
+    time::Duration,
+};
+
+use super::{format_limit, parse_limit, ResourceCounter};
+use crate::prelude::*;
+
+/// The `cpu` controller, which distributes the CPU time among the cgroups.
+///
+/// The cgroups that compete for the CPU share the CPU time in proportion to their
+/// weights, i.e., `cpu.weight`. A cgroup can also be limited to use at most a quota of
+/// CPU time in each period, i.e., `cpu.max`, after which the cgroup is throttled until
+/// the next period.
+pub struct CpuController {
+    weight: AtomicU32,
+    /// The quota in microseconds, or `ResourceCounter::UNLIMITED`
+    quota_us: AtomicU64,
+    period_us: AtomicU64,
+    /// The CPU time used by the cgroup and its descendants, in nanoseconds
+    usage_ns: AtomicU64,
+    bandwidth: SpinLock<Bandwidth>,
+}
+
+/// The CPU time used in the current period.
+struct Bandwidth {
+    /// The start of the current period, in nanoseconds
+    period_start_ns: u64,
+    /// The CPU time used in the current period, in nanoseconds
+    runtime_ns: u64,
+    is_throttled: bool,
+    nr_periods: u64,
+    nr_throttled: u64,
+}
+
+impl CpuController {
+    pub const DEFAULT_WEIGHT: u32 = 100;
+    const MIN_WEIGHT: u32 = 1;
+    const MAX_WEIGHT: u32 = 10000;
+
+    const DEFAULT_PERIOD_US: u64 = 100_000;
+    const MIN_PERIOD_US: u64 = 1_000;
+    const MAX_PERIOD_US: u64 = 1_000_000;
+    const MIN_QUOTA_US: u64 = 1_000;
+
+    pub(super) const fn new() -> Self {
+        Self {
+            weight: AtomicU32::new(Self::DEFAULT_WEIGHT),
+            quota_us: AtomicU64::new(ResourceCounter::UNLIMITED),
+            period_us: AtomicU64::new(Self::DEFAULT_PERIOD_US),
+            usage_ns: AtomicU64::new(0),
+            bandwidth: SpinLock::new(Bandwidth {
+                period_start_ns: 0,
+                runtime_ns: 0,
+                is_throttled: false,
+                nr_periods: 0,
+                nr_throttled: 0,
+            }),
+        }
+    }
+
+    pub fn weight(&self) -> u32 {
+        self.weight.load(Ordering::Relaxed)
+    }
+
+    /// Adds the CPU time used by the cgroup.
+    pub(super) fn charge(&self, now: Duration, runtime: Duration) {
+        let runtime_ns = runtime.as_nanos() as u64;
+        self.usage_ns.fetch_add(runtime_ns, Ordering::Relaxed);
+
+        let mut bandwidth = self.bandwidth.lock_irq_disabled();
+        bandwidth.refresh(now, self.period_us.load(Ordering::Relaxed));
+        bandwidth.runtime_ns += runtime_ns;
+    }
+
+    /// Returns whether the cgroup has used up its quota in the current period.
+    pub(super) fn is_throttled(&self, now: Duration) -> bool {
+        let quota_us = self.quota_us.load(Ordering::Relaxed);
+        if quota_us == ResourceCounter::UNLIMITED {
+            return false;
+        }
+
+        let mut bandwidth = self.bandwidth.lock_irq_disabled();
+        bandwidth.refresh(now, self.period_us.load(Ordering::Relaxed));
+        let is_throttled = bandwidth.runtime_ns >= quota_us * 1000;
+        if is_throttled && !bandwidth.is_throttled {
+            bandwidth.nr_throttled += 1;
+        }
+        bandwidth.is_throttled = is_throttled;
+        is_throttled
+    }
+
+    /// Returns the content of `cpu.weight`.
+    pub fn show_weight(&self) -> String {
+        self.weight().to_string()
+    }
+
+    /// Parses and stores the content of `cpu.weight`, which is in `1..=10000`.
+    pub fn store_weight(&self, content: &str) -> Result<()> {
+        let weight = content
+            .trim()
+            .parse::<u32>()
+            .map_err(|_| Error::with_message(Errno::EINVAL, "invalid weight"))?;
+        if !(Self::MIN_WEIGHT..=Self::MAX_WEIGHT).contains(&weight) {
+            return_errno_with_message!(Errno::ERANGE, "the weight is out of range");
+        }
+        self.weight.store(weight, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Returns the content of `cpu.max`, i.e., `$MAX $PERIOD` in microseconds.
+    pub fn show_max(&self) -> String {
+        format!(
+            "{} {}",
+            format_limit(self.quota_us.load(Ordering::Relaxed)),
+            self.period_us.load(Ordering::Relaxed)
+        )
+    }
+
+    /// Parses and stores the content of `cpu.max`, i.e., `$MAX [$PERIOD]`, where `$MAX`
+    /// is either `max` or the quota in microseconds.
+    pub fn store_max(&self, content: &str) -> Result<()> {
+        let mut fields = content.split_whitespace();
+        let quota_us = parse_limit(fields.next().unwrap_or_default())?;
+        let period_us = match fields.next() {
+            Some(period) => period
+                .parse::<u64>()
+                .map_err(|_| Error::with_message(Errno::EINVAL, "invalid period"))?,
+            None => self.period_us.load(Ordering::Relaxed),
+        };
+        if fields.next().is_some()
+            || !(Self::MIN_PERIOD_US..=Self::MAX_PERIOD_US).contains(&period_us)
+            || quota_us < Self::MIN_QUOTA_US
+        {
+            return_errno_with_message!(Errno::EINVAL, "invalid quota or period");
+        }
+
+        self.quota_us.store(quota_us, Ordering::Relaxed);
+        self.period_us.store(period_us, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Returns the content of `cpu.stat`.
+    pub fn show_stat(&self) -> String {
+        let bandwidth = self.bandwidth.lock_irq_disabled();
+        format!(
+            "usage_usec {}\nnr_periods {}\nnr_throttled {}\n",
+            self.usage_ns.load(Ordering::Relaxed) / 1000,
+            bandwidth.nr_periods,
+            bandwidth.nr_throttled
+        )
+    }
+}
+
+impl Bandwidth {
+    /// Starts a new period if the current period has ended.
+    fn refresh(&mut self, now: Duration, period_us: u64) {
+        let now_ns = now.as_nanos() as u64;
+        let period_ns = period_us * 1000;
+        if now_ns < self.period_start_ns + period_ns {
+            return;
+        }
+
+        self.period_start_ns = now_ns - (now_ns - self.period_start_ns) % period_ns;
+        self.runtime_ns = 0;
+        self.is_throttled = false;
+        self.nr_periods += 1;
+    }
+}
@@ -0,0 +1,137 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::format;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use super::{current_cgroup, format_limit, parse_limit, Cgroup, ResourceCounter};
+use crate::prelude::*;
+
+/// The `memory` controller, which limits the pages used by a cgroup.
+///
+/// The pages allocated for the VMOs, e.g., the anonymous memory of the processes, and
+/// for the page cache are charged. The memory is counted in pages, while the interface
+/// files show it in bytes.
+pub struct MemoryController {
+    counter: ResourceCounter,
+    /// The number of the allocations that failed because of `memory.max`
+    nr_max_events: AtomicU64,
+}
+
+impl MemoryController {
+    pub(super) const fn new() -> Self {
+        Self {
+            counter: ResourceCounter::new(),
+            nr_max_events: AtomicU64::new(0),
+        }
+    }
+
+    pub(super) fn counter(&self) -> &ResourceCounter {
+        &self.counter
+    }
+
+    pub(super) fn record_max_event(&self) {
+        self.nr_max_events.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the content of `memory.current`.
+    pub fn show_current(&self) -> String {
+        (self.counter.usage() * PAGE_SIZE as u64).to_string()
+    }
+
+    /// Returns the content of `memory.max`.
+    pub fn show_max(&self) -> String {
+        match self.counter.max() {
+            ResourceCounter::UNLIMITED => format_limit(ResourceCounter::UNLIMITED),
+            max_pages => (max_pages * PAGE_SIZE as u64).to_string(),
+        }
+    }
+
+    /// Parses and stores the content of `memory.max`, which is the bytes rounded down
+    /// to pages. The bytes may have a suffix of `K`, `M` or `G`.
+    pub fn store_max(&self, content: &str) -> Result<()> {
+        let content = content.trim();
+        let (number, shift) = match content.as_bytes().last() {
+            Some(b'k' | b'K') => (&content[..content.len() - 1], 10),
+            Some(b'm' | b'M') => (&content[..content.len() - 1], 20),
+            Some(b'g' | b'G') => (&content[..content.len() - 1], 30),
+            _ => (content, 0),
+        };
+        let max_pages = match parse_limit(number)? {
+            ResourceCounter::UNLIMITED => ResourceCounter::UNLIMITED,
+            max => max.saturating_mul(1 << shift) / PAGE_SIZE as u64,
+        };
+        self.counter.set_max(max_pages);
+        Ok(())
+    }
+
+    /// Returns the content of `memory.events`.
+    pub fn show_events(&self) -> String {
+        format!("max {}\n", self.nr_max_events.load(Ordering::Relaxed))
+    }
+}
+
+/// The pages charged to a cgroup by an owner of the pages, e.g., a VMO.
+///
+/// The cgroup is the one of the task that creates the charge, and does not change when
+/// the task moves to another cgroup. The charged pages are uncharged when the charge
+/// is dropped.
+pub struct MemoryCharge {
+    cgroup: Arc<Cgroup>,
+    nr_pages: AtomicUsize,
+}
+
+impl MemoryCharge {
+    /// Creates an empty charge to the cgroup of the current task.
+    pub fn new() -> Self {
+        Self {
+            cgroup: current_cgroup(),
+            nr_pages: AtomicUsize::new(0),
+        }
+    }
+
+    /// Charges the pages, unless the `memory` limit would be exceeded.
+    pub fn try_charge(&self, nr_pages: usize) -> Result<()> {
+        self.cgroup.try_charge_memory(nr_pages as u64)?;
+        self.nr_pages.fetch_add(nr_pages, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Uncharges the pages, which are at most the pages that have been charged.
+    pub fn uncharge(&self, nr_pages: usize) {
+        let old_nr_pages = self
+            .nr_pages
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |charged| {
+                Some(charged.saturating_sub(nr_pages))
+            })
+            .unwrap();
+        let nr_uncharged = old_nr_pages.min(nr_pages);
+        self.cgroup.uncharge_memory(nr_uncharged as u64);
+    }
+
+    /// Returns the number of the charged pages.
+    pub fn nr_pages(&self) -> usize {
+        self.nr_pages.load(Ordering::Relaxed)
+    }
+}
+
+impl Default for MemoryCharge {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for MemoryCharge {
+    fn drop(&mut self) {
+        let nr_pages = *self.nr_pages.get_mut();
+        self.cgroup.uncharge_memory(nr_pages as u64);
+    }
+}
+
+impl Debug for MemoryCharge {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("MemoryCharge")
+            .field("cgroup", &self.cgroup)
+            .field("nr_pages", &self.nr_pages())
+            .finish()
+    }
+}
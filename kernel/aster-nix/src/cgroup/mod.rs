@@ -0,0 +1,527 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Control groups (cgroups) of the version 2, which control the resources used by
+//! groups of processes.
+//!
+//! The cgroups form a single hierarchy, which is exposed by the cgroup2 file system.
+//! Each process belongs to a cgroup, and a child process inherits the cgroup of its
+//! parent. A controller distributes one kind of resource among the cgroups, and is
+//! enabled for the children of a cgroup by `cgroup.subtree_control` of the cgroup:
+//!
+//! - `pids` limits the number of the tasks, which is enforced when a task is cloned;
+//! - `memory` limits the pages allocated for the VMOs and the page cache;
+//! - `cpu` shares the CPU time by the weights of the cgroups, and limits the CPU time
+//!   that a cgroup can use in a period.
+//!
+//! A resource used by a cgroup is also charged to all its ancestors, so the limit of
+//! a cgroup applies to its whole subtree.
+
+use alloc::format;
+use core::{
+    sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
+    time::Duration,
+};
+
+use ostd::task::{current_task, Task};
+use spin::Once;
+
+pub use self::{
+    cpu::CpuController,
+    memory::{MemoryCharge, MemoryController},
+    pids::PidsController,
+};
+use crate::{
+    prelude::*,
+    process::{posix_thread::PosixThreadExt, Pid, Process},
+    thread::Thread,
+};
+
+mod cpu;
+mod memory;
+mod pids;
+
+bitflags! {
+    /// The controllers of the cgroups.
+    pub struct Controllers: u32 {
+        const CPU    = 1 << 0;
+        const MEMORY = 1 << 1;
+        const PIDS   = 1 << 2;
+    }
+}
+
+impl Controllers {
+    /// The names of the controllers, in the order that they are shown.
+    const NAMES: [(Controllers, &'static str); 3] = [
+        (Controllers::CPU, "cpu"),
+        (Controllers::MEMORY, "memory"),
+        (Controllers::PIDS, "pids"),
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::NAMES
+            .iter()
+            .find(|(_, controller_name)| *controller_name == name)
+            .map(|(controller, _)| *controller)
+    }
+
+    /// Returns the names of the controllers separated by spaces, e.g., `cpu pids`.
+    pub fn names(&self) -> String {
+        Self::NAMES
+            .iter()
+            .filter(|(controller, _)| self.contains(*controller))
+            .map(|(_, name)| *name)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+static ROOT_CGROUP: Once<Arc<Cgroup>> = Once::new();
+
+/// The ID of the next cgroup, where 1 is the ID of the root cgroup.
+static NEXT_CGROUP_ID: AtomicU64 = AtomicU64::new(2);
+
+/// Returns the root cgroup, which all processes belong to at first.
+pub fn root() -> &'static Arc<Cgroup> {
+    ROOT_CGROUP.call_once(|| Cgroup::new(1, String::new(), None))
+}
+
+/// Returns the cgroup of the task.
+///
+/// A task that does not belong to a process, e.g., a kernel thread, is in the root cgroup.
+pub fn cgroup_of_task(task: &Task) -> Arc<Cgroup> {
+    task.data()
+        .downcast_ref::<Weak<Thread>>()
+        .and_then(Weak::upgrade)
+        .and_then(|thread| thread.as_posix_thread()?.weak_process().upgrade())
+        .map(|process| process.cgroup())
+        .unwrap_or_else(|| root().clone())
+}
+
+/// Returns the cgroup of the current task, or the root cgroup if no task is running yet.
+pub fn current_cgroup() -> Arc<Cgroup> {
+    match current_task() {
+        Some(task) => cgroup_of_task(&task),
+        None => root().clone(),
+    }
+}
+
+/// A control group.
+pub struct Cgroup {
+    id: u64,
+    name: String,
+    parent: Option<Arc<Cgroup>>,
+    children: Mutex<BTreeMap<String, Arc<Cgroup>>>,
+    /// The processes in the cgroup
+    processes: SpinLock<BTreeMap<Pid, Weak<Process>>>,
+    /// The controllers that are enabled for the children
+    subtree_control: AtomicU32,
+    is_removed: AtomicBool,
+    pids: PidsController,
+    memory: MemoryController,
+    cpu: CpuController,
+}
+
+impl Cgroup {
+    fn new(id: u64, name: String, parent: Option<Arc<Cgroup>>) -> Arc<Self> {
+        Arc::new(Self {
+            id,
+            name,
+            parent,
+            children: Mutex::new(BTreeMap::new()),
+            processes: SpinLock::new(BTreeMap::new()),
+            subtree_control: AtomicU32::new(0),
+            is_removed: AtomicBool::new(false),
+            pids: PidsController::new(),
+            memory: MemoryController::new(),
+            cpu: CpuController::new(),
+        })
+    }
+
+    /// Returns the ID of the cgroup, which is unique during the lifetime of the system.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Returns the name of the cgroup, which is empty for the root cgroup.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn parent(&self) -> Option<&Arc<Cgroup>> {
+        self.parent.as_ref()
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.parent.is_none()
+    }
+
+    /// Returns whether the cgroup has been removed from the hierarchy.
+    pub fn is_removed(&self) -> bool {
+        self.is_removed.load(Ordering::Relaxed)
+    }
+
+    /// Returns the path of the cgroup in the hierarchy, e.g., `/a/b`.
+    pub fn path(&self) -> String {
+        match self.parent.as_ref() {
+            Some(parent) if parent.is_root() => format!("/{}", self.name),
+            Some(parent) => format!("{}/{}", parent.path(), self.name),
+            None => String::from("/"),
+        }
+    }
+
+    /// Returns the cgroup itself and its ancestors, from the cgroup to the root.
+    pub fn ancestors(&self) -> impl Iterator<Item = &Cgroup> {
+        core::iter::successors(Some(self), |cgroup| cgroup.parent.as_deref())
+    }
+
+    pub fn pids(&self) -> &PidsController {
+        &self.pids
+    }
+
+    pub fn memory(&self) -> &MemoryController {
+        &self.memory
+    }
+
+    pub fn cpu(&self) -> &CpuController {
+        &self.cpu
+    }
+
+    // *********** Hierarchy ***********
+
+    pub fn child(&self, name: &str) -> Option<Arc<Cgroup>> {
+        self.children.lock().get(name).cloned()
+    }
+
+    pub fn children(&self) -> Vec<Arc<Cgroup>> {
+        self.children.lock().values().cloned().collect()
+    }
+
+    /// Creates a child cgroup, which inherits no controllers for its own children.
+    pub fn create_child(self: &Arc<Self>, name: &str) -> Result<Arc<Cgroup>> {
+        if self.is_removed() {
+            return_errno_with_message!(Errno::ENOENT, "the cgroup has been removed");
+        }
+        // The names of the interface files are reserved.
+        if name.starts_with("cgroup.")
+            || Controllers::NAMES
+                .iter()
+                .any(|(_, controller)| name.starts_with(&format!("{}.", controller)))
+        {
+            return_errno_with_message!(Errno::EINVAL, "the name of the cgroup is reserved");
+        }
+
+        let mut children = self.children.lock();
+        if children.contains_key(name) {
+            return_errno_with_message!(Errno::EEXIST, "the cgroup already exists");
+        }
+        let id = NEXT_CGROUP_ID.fetch_add(1, Ordering::Relaxed);
+        let child = Cgroup::new(id, String::from(name), Some(self.clone()));
+        children.insert(String::from(name), child.clone());
+        Ok(child)
+    }
+
+    /// Removes a child cgroup, which must have neither children nor processes.
+    pub fn remove_child(&self, name: &str) -> Result<()> {
+        let mut children = self.children.lock();
+        let Some(child) = children.get(name) else {
+            return_errno_with_message!(Errno::ENOENT, "the cgroup does not exist");
+        };
+        if !child.children.lock().is_empty() || child.is_populated() {
+            return_errno_with_message!(Errno::EBUSY, "the cgroup is in use");
+        }
+        child.is_removed.store(true, Ordering::Relaxed);
+        children.remove(name);
+        Ok(())
+    }
+
+    // *********** Controllers ***********
+
+    /// Returns the controllers available to the cgroup, which are enabled by its parent.
+    pub fn controllers(&self) -> Controllers {
+        match self.parent.as_ref() {
+            Some(parent) => parent.subtree_control(),
+            None => Controllers::all(),
+        }
+    }
+
+    /// Returns the controllers enabled for the children.
+    pub fn subtree_control(&self) -> Controllers {
+        Controllers::from_bits_truncate(self.subtree_control.load(Ordering::Relaxed))
+    }
+
+    /// Enables and disables the controllers for the children.
+    ///
+    /// Like Linux, a controller can be enabled only if it is available to the cgroup
+    /// and a non-root cgroup has no processes, and can be disabled only if no child
+    /// enables it for its own children.
+    pub fn update_subtree_control(&self, enable: Controllers, disable: Controllers) -> Result<()> {
+        let children = self.children.lock();
+        if !self.controllers().contains(enable) {
+            return_errno_with_message!(Errno::ENOENT, "the controller is not available");
+        }
+        if !enable.is_empty() && !self.is_root() && self.is_populated() {
+            return_errno_with_message!(Errno::EBUSY, "the cgroup has processes");
+        }
+        if children
+            .values()
+            .any(|child| child.subtree_control().intersects(disable))
+        {
+            return_errno_with_message!(Errno::EBUSY, "the controller is used by the children");
+        }
+
+        let subtree_control = (self.subtree_control() | enable) - disable;
+        self.subtree_control
+            .store(subtree_control.bits(), Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Returns whether the controller takes effect on the cgroup.
+    fn is_enabled(&self, controller: Controllers) -> bool {
+        !self.is_root() && self.controllers().contains(controller)
+    }
+
+    // *********** Processes ***********
+
+    /// Returns the processes in the cgroup, which are sorted by their PIDs.
+    pub fn processes(&self) -> Vec<Arc<Process>> {
+        self.processes
+            .lock()
+            .values()
+            .filter_map(Weak::upgrade)
+            .collect()
+    }
+
+    /// Returns whether there are processes in the cgroup.
+    pub fn is_populated(&self) -> bool {
+        !self.processes.lock().is_empty()
+    }
+
+    /// Moves the process into the cgroup.
+    ///
+    /// The tasks of the process are moved together, even if the `pids` limit is exceeded.
+    pub fn attach(self: &Arc<Self>, process: &Arc<Process>) -> Result<()> {
+        if self.is_removed() {
+            return_errno_with_message!(Errno::ENOENT, "the cgroup has been removed");
+        }
+        // The processes are in the leaf cgroups, unless the controllers are not used.
+        if !self.is_root() && !self.subtree_control().is_empty() {
+            return_errno_with_message!(Errno::EBUSY, "the cgroup has enabled controllers");
+        }
+        process.move_to_cgroup(self);
+        Ok(())
+    }
+
+    pub(crate) fn add_process(&self, process: &Arc<Process>) {
+        self.processes
+            .lock()
+            .insert(process.pid(), Arc::downgrade(process));
+    }
+
+    pub(crate) fn remove_process(&self, pid: Pid) {
+        self.processes.lock().remove(&pid);
+    }
+
+    // *********** Charges ***********
+
+    /// Charges the tasks to the cgroup, unless the `pids` limit would be exceeded.
+    pub fn try_charge_pids(&self, nr_tasks: u64) -> Result<()> {
+        self.try_charge(nr_tasks, Controllers::PIDS, |cgroup| cgroup.pids.counter())
+            .map_err(|_| Error::with_message(Errno::EAGAIN, "the pids limit is reached"))
+    }
+
+    /// Charges the tasks to the cgroup regardless of the `pids` limit.
+    pub fn charge_pids(&self, nr_tasks: u64) {
+        for cgroup in self.ancestors() {
+            cgroup.pids.counter().charge(nr_tasks);
+        }
+    }
+
+    pub fn uncharge_pids(&self, nr_tasks: u64) {
+        for cgroup in self.ancestors() {
+            cgroup.pids.counter().uncharge(nr_tasks);
+        }
+    }
+
+    /// Charges the pages to the cgroup, unless the `memory` limit would be exceeded.
+    pub fn try_charge_memory(&self, nr_pages: u64) -> Result<()> {
+        self.try_charge(nr_pages, Controllers::MEMORY, |cgroup| {
+            cgroup.memory.counter()
+        })
+        .map_err(|cgroup| {
+            cgroup.memory.record_max_event();
+            Error::with_message(Errno::ENOMEM, "the memory limit is reached")
+        })
+    }
+
+    pub fn uncharge_memory(&self, nr_pages: u64) {
+        for cgroup in self.ancestors() {
+            cgroup.memory.counter().uncharge(nr_pages);
+        }
+    }
+
+    /// Charges the CPU time used by a task of the cgroup.
+    pub fn charge_cpu_time(&self, now: Duration, runtime: Duration) {
+        for cgroup in self.ancestors() {
+            cgroup.cpu.charge(now, runtime);
+        }
+    }
+
+    /// Returns whether the cgroup or one of its ancestors has used up its CPU quota.
+    pub fn is_cpu_throttled(&self, now: Duration) -> bool {
+        self.ancestors()
+            .any(|cgroup| cgroup.is_enabled(Controllers::CPU) && cgroup.cpu.is_throttled(now))
+    }
+
+    /// Returns the weight of the cgroup to share the CPU time.
+    pub fn cpu_weight(&self) -> u32 {
+        if self.is_enabled(Controllers::CPU) {
+            self.cpu.weight()
+        } else {
+            CpuController::DEFAULT_WEIGHT
+        }
+    }
+
+    /// Charges the amount to the counters of the cgroup and its ancestors.
+    ///
+    /// If the limit of a cgroup would be exceeded, the charges are rolled back and the
+    /// cgroup is returned.
+    fn try_charge(
+        &self,
+        amount: u64,
+        controller: Controllers,
+        counter_of: impl Fn(&Cgroup) -> &ResourceCounter,
+    ) -> core::result::Result<(), &Cgroup> {
+        for (nr_charged, cgroup) in self.ancestors().enumerate() {
+            let is_enforced = cgroup.is_enabled(controller);
+            if counter_of(cgroup).try_charge(amount, is_enforced) {
+                continue;
+            }
+            for charged in self.ancestors().take(nr_charged) {
+                counter_of(charged).uncharge(amount);
+            }
+            return Err(cgroup);
+        }
+        Ok(())
+    }
+}
+
+impl Debug for Cgroup {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Cgroup")
+            .field("id", &self.id)
+            .field("path", &self.path())
+            .finish()
+    }
+}
+
+/// A counter of the usage of a resource, with the limit of the usage.
+struct ResourceCounter {
+    usage: AtomicU64,
+    max: AtomicU64,
+}
+
+impl ResourceCounter {
+    /// The limit that means no limit, i.e., `max` in the interface files.
+    const UNLIMITED: u64 = u64::MAX;
+
+    const fn new() -> Self {
+        Self {
+            usage: AtomicU64::new(0),
+            max: AtomicU64::new(Self::UNLIMITED),
+        }
+    }
+
+    fn usage(&self) -> u64 {
+        self.usage.load(Ordering::Relaxed)
+    }
+
+    fn max(&self) -> u64 {
+        self.max.load(Ordering::Relaxed)
+    }
+
+    fn set_max(&self, max: u64) {
+        self.max.store(max, Ordering::Relaxed);
+    }
+
+    /// Adds the amount to the usage, unless the limit is enforced and would be exceeded.
+    fn try_charge(&self, amount: u64, is_enforced: bool) -> bool {
+        let max = if is_enforced {
+            self.max()
+        } else {
+            Self::UNLIMITED
+        };
+        self.usage
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |usage| {
+                usage
+                    .checked_add(amount)
+                    .filter(|new_usage| *new_usage <= max)
+            })
+            .is_ok()
+    }
+
+    fn charge(&self, amount: u64) {
+        self.usage.fetch_add(amount, Ordering::Relaxed);
+    }
+
+    fn uncharge(&self, amount: u64) {
+        let _ = self
+            .usage
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |usage| {
+                Some(usage.saturating_sub(amount))
+            });
+    }
+}
+
+/// Parses a limit in the interface files, which is either a number or `max`.
+fn parse_limit(value: &str) -> Result<u64> {
+    match value.trim() {
+        "max" => Ok(ResourceCounter::UNLIMITED),
+        value => value
+            .parse::<u64>()
+            .map_err(|_| Error::with_message(Errno::EINVAL, "invalid limit")),
+    }
+}
+
+/// Formats a limit in the interface files.
+fn format_limit(limit: u64) -> String {
+    if limit == ResourceCounter::UNLIMITED {
+        String::from("max")
+    } else {
+        limit.to_string()
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn hierarchical_charges() {
+        let parent = root().create_child("ktest-charges").unwrap();
+        root()
+            .update_subtree_control(Controllers::PIDS, Controllers::empty())
+            .unwrap();
+        parent
+            .update_subtree_control(Controllers::PIDS, Controllers::empty())
+            .unwrap();
+        let child = parent.create_child("child").unwrap();
+        parent.pids().set_max(2);
+
+        child.try_charge_pids(2).unwrap();
+        assert_eq!(parent.pids().current(), 2);
+        assert!(child.try_charge_pids(1).is_err());
+        // A failed charge is rolled back.
+        assert_eq!(child.pids().current(), 2);
+
+        child.uncharge_pids(2);
+        assert_eq!(parent.pids().current(), 0);
+        assert!(root().remove_child("ktest-charges").is_err());
+        parent.remove_child("child").unwrap();
+        root().remove_child("ktest-charges").unwrap();
+        root()
+            .update_subtree_control(Controllers::empty(), Controllers::PIDS)
+            .unwrap();
+    }
+}
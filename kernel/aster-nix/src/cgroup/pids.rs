@@ -0,0 +1,49 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::{format_limit, parse_limit, ResourceCounter};
+use crate::prelude::*;
+
+/// The `pids` controller, which limits the number of the tasks in a cgroup.
+///
+/// A task is charged when it is cloned and uncharged when it exits. The limit is not
+/// enforced when the processes are moved between the cgroups, so `pids.current` may
+/// exceed `pids.max`, like Linux.
+pub struct PidsController {
+    counter: ResourceCounter,
+}
+
+impl PidsController {
+    pub(super) const fn new() -> Self {
+        Self {
+            counter: ResourceCounter::new(),
+        }
+    }
+
+    pub(super) fn counter(&self) -> &ResourceCounter {
+        &self.counter
+    }
+
+    /// Returns the number of the tasks in the cgroup and its descendants.
+    pub fn current(&self) -> u64 {
+        self.counter.usage()
+    }
+
+    pub fn max(&self) -> u64 {
+        self.counter.max()
+    }
+
+    pub fn set_max(&self, max: u64) {
+        self.counter.set_max(max);
+    }
+
+    /// Returns the content of `pids.max`.
+    pub fn show_max(&self) -> String {
+        format_limit(self.max())
+    }
+
+    /// Parses and stores the content of `pids.max`.
+    pub fn store_max(&self, content: &str) -> Result<()> {
+        self.set_max(parse_limit(content)?);
+        Ok(())
+    }
+}
@@ -0,0 +1,464 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#![allow(unused_variables)]
+
+//! The device nodes of block devices, e.g., `/dev/vda`.
+//!
+//! Each block device registered in `aster_block` is exposed as a block device node,
+//! whose data are accessed through a page cache. The cached data are written back
+//! by `fsync` or the `BLKFLSBUF` ioctl.
+//!
+//! The partitions found on a disk are registered in `aster_block` and exposed as
+//! block device nodes as well, e.g., `/dev/vda1`. So are the md arrays assembled
+//! from the disks and the partitions, e.g., `/dev/md0`. Then the mapped devices
+//! specified by the kernel command line are created on them, e.g., `/dev/dm-0`.
+//!
+//! The partition table of a disk is scanned when the disk is added, and again when
+//! its contents are replaced, e.g., when a loop device is bound to a file.
+
+use alloc::format;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use aster_block::{
+    bio::{Bio, BioSegment, BioType, BioWaiter},
+    id::Sid,
+    md,
+    partition::{self, Partition},
+    BlockDevice, SECTOR_SIZE,
+};
+use aster_virtio::device::block::device::BlockDevice as VirtIoBlockDevice;
+use ostd::mm::{Frame, VmIo};
+
+use super::{loop_device::LoopDevice, mapper, *};
+use crate::{
+    events::IoEvents,
+    fs::{
+        inode_handle::FileIo,
+        utils::{IoctlCmd, PageCache, PageCacheBackend},
+    },
+    prelude::*,
+    process::signal::Poller,
+    util::write_val_to_user,
+};
+
+/// The major device number of md arrays.
+const MD_MAJOR: u32 = 9;
+/// The major device number of the partitions that do not fit in the minors of their disks.
+pub(super) const BLOCK_EXT_MAJOR: u32 = 259;
+/// The number of minor device numbers of a disk, which are reserved for its partitions.
+const MINORS_PER_DISK: u32 = 16;
+/// The number of minor device numbers of a major.
+const MAX_MINORS: u32 = 1 << 20;
+
+static BLOCK_DEVICE_NODES: Mutex<BTreeMap<(u32, u32), Arc<BlockDeviceNode>>> =
+    Mutex::new(BTreeMap::new());
+
+/// The disks added by `add_disk`, keyed by their device numbers.
+static DISKS: Mutex<BTreeMap<(u32, u32), Disk>> = Mutex::new(BTreeMap::new());
+
+/// A disk, whose partitions are exposed as block devices.
+struct Disk {
+    name: String,
+    device: Arc<dyn BlockDevice>,
+    /// The number of minors reserved for the disk and its partitions
+    nr_minors: u32,
+    /// The names and the device numbers of the partitions
+    partitions: Vec<(String, DeviceId)>,
+}
+
+/// Creates a device node for each virtio block device, which are named as `vda`, `vdb`, etc.
+/// Then the md arrays found on the devices are assembled, which are named as `md0`, `md1`, etc.
+///
+/// The virtio block devices have a dynamic major, like Linux.
+///
+/// The partition tables and the md superblocks are read from the devices, so this function
+/// must be called after the devices are ready to handle requests.
+pub fn init() -> Result<()> {
+    let virtio_blk_major = registry::register_major(DeviceType::BlockDevice, None, "virtblk")?;
+    let virtio_devices = aster_block::all_devices()
+        .into_iter()
+        .filter(|(_, device)| device.downcast_ref::<VirtIoBlockDevice>().is_some());
+    for (index, (_, device)) in virtio_devices.enumerate() {
+        let id = DeviceId::new(virtio_blk_major, index as u32 * MINORS_PER_DISK);
+        add_disk(&disk_name(index), id, MINORS_PER_DISK, device)?;
+    }
+
+    registry::register_major(DeviceType::BlockDevice, Some(MD_MAJOR), "md")?;
+
+    // The members of the arrays can be either the disks or their partitions.
+    let devices = aster_block::all_devices()
+        .into_iter()
+        .map(|(_, device)| device);
+    for (index, array) in md::assemble(devices).into_iter().enumerate() {
+        let name = format!("md{}", index);
+        aster_block::register_device(name.clone(), array.clone());
+        let id = DeviceId::new(MD_MAJOR, index as u32 * MINORS_PER_DISK);
+        add_disk(&name, id, MINORS_PER_DISK, array)?;
+    }
+
+    mapper::create_from_kcmdline();
+    Ok(())
+}
+
+/// Registers the disk, creates its device node and scans its partitions.
+///
+/// The minors from `id.minor()` to `id.minor() + nr_minors - 1` are reserved for the disk
+/// and its partitions, e.g., `/dev/vda1` has the minor number of `/dev/vda` plus 1.
+/// The partitions that do not fit are numbered with `BLOCK_EXT_MAJOR` instead.
+pub fn add_disk(
+    name: &str,
+    id: DeviceId,
+    nr_minors: u32,
+    device: Arc<dyn BlockDevice>,
+) -> Result<Arc<BlockDeviceNode>> {
+    let node = add_block_device_node(name, id, device.clone())?;
+    let disk = Disk {
+        name: String::from(name),
+        device,
+        nr_minors,
+        partitions: Vec::new(),
+    };
+    DISKS.lock().insert((id.major(), id.minor()), disk);
+    rescan_partitions(id)?;
+    Ok(node)
+}
+
+/// Removes the partitions of the disk, then unregisters the disk and removes its device node.
+pub(super) fn remove_disk(id: DeviceId) -> Result<()> {
+    if let Some(mut disk) = DISKS.lock().remove(&(id.major(), id.minor())) {
+        remove_partitions(&mut disk)?;
+    }
+    remove_block_device_node(id)
+}
+
+/// Removes the partitions of the disk, then scans the partition table again.
+///
+/// This is called whenever the contents of the disk are replaced, e.g., when
+/// a loop device is bound to a file. A disk without a valid partition table
+/// has no partitions.
+///
+/// The old partitions should be removed by `clear_partitions` before the contents
+/// are replaced, so that their cached data are written back to the old contents.
+pub(super) fn rescan_partitions(id: DeviceId) -> Result<()> {
+    let mut disks = DISKS.lock();
+    let Some(disk) = disks.get_mut(&(id.major(), id.minor())) else {
+        return_errno_with_message!(Errno::ENXIO, "the device is not a disk");
+    };
+    remove_partitions(disk)?;
+
+    let partitions = match partition::scan(disk.device.as_ref()) {
+        Ok(partitions) => partitions,
+        Err(e) => {
+            warn!("failed to scan the partitions of {}: {:?}", disk.name, e);
+            return Ok(());
+        }
+    };
+    for info in partitions {
+        let partition_id = if info.number < disk.nr_minors {
+            DeviceId::new(id.major(), id.minor() + info.number)
+        } else {
+            let Some(minor) = alloc_ext_minor() else {
+                warn!("no free minor for {} partition {}", disk.name, info.number);
+                continue;
+            };
+            DeviceId::new(BLOCK_EXT_MAJOR, minor)
+        };
+        let partition_name = partition_name(&disk.name, info.number);
+        let partition: Arc<dyn BlockDevice> = Arc::new(Partition::new(disk.device.clone(), info));
+        aster_block::register_device(partition_name.clone(), partition.clone());
+        if let Err(e) = add_block_device_node(&partition_name, partition_id, partition) {
+            aster_block::unregister_device(&partition_name);
+            return Err(e);
+        }
+        disk.partitions.push((partition_name, partition_id));
+    }
+    Ok(())
+}
+
+/// Removes the partitions of the disk, e.g., before its contents are replaced.
+///
+/// The cached data of the partitions are written back before they are removed.
+pub(super) fn clear_partitions(id: DeviceId) -> Result<()> {
+    let mut disks = DISKS.lock();
+    match disks.get_mut(&(id.major(), id.minor())) {
+        Some(disk) => remove_partitions(disk),
+        None => Ok(()),
+    }
+}
+
+/// Returns the partitions of the disk.
+pub(super) fn partitions(id: DeviceId) -> Vec<Arc<dyn BlockDevice>> {
+    let disks = DISKS.lock();
+    let Some(disk) = disks.get(&(id.major(), id.minor())) else {
+        return Vec::new();
+    };
+    disk.partitions
+        .iter()
+        .filter_map(|(name, _)| aster_block::get_device(name))
+        .collect()
+}
+
+/// Allocates the lowest unused minor of `BLOCK_EXT_MAJOR`.
+fn alloc_ext_minor() -> Option<u32> {
+    (0..MAX_MINORS).find(|minor| get_device(DeviceId::new(BLOCK_EXT_MAJOR, *minor)).is_none())
+}
+
+fn remove_partitions(disk: &mut Disk) -> Result<()> {
+    while let Some((name, id)) = disk.partitions.pop() {
+        aster_block::unregister_device(&name);
+        remove_block_device_node(id)?;
+    }
+    Ok(())
+}
+
+/// Registers the block device and creates its device node `/dev/<name>`.
+fn add_block_device_node(
+    name: &str,
+    id: DeviceId,
+    device: Arc<dyn BlockDevice>,
+) -> Result<Arc<BlockDeviceNode>> {
+    let node = BlockDeviceNode::new(id, device);
+    // The node is found by its number once it is announced.
+    BLOCK_DEVICE_NODES
+        .lock()
+        .insert((id.major(), id.minor()), node.clone());
+    if let Err(e) = registry::register_device(node.clone(), name) {
+        BLOCK_DEVICE_NODES.lock().remove(&(id.major(), id.minor()));
+        return Err(e);
+    }
+    Ok(node)
+}
+
+/// Unregisters the block device and removes its device node.
+///
+/// The cached data are written back before the node is removed.
+fn remove_block_device_node(id: DeviceId) -> Result<()> {
+    if let Some(node) = get_device(id) {
+        node.sync()?;
+    }
+    // The node is unregistered first, so that it is still found when it is announced.
+    let result = registry::unregister_device(DeviceType::BlockDevice, id);
+    BLOCK_DEVICE_NODES.lock().remove(&(id.major(), id.minor()));
+    result
+}
+
+/// Returns the block device node of the device ID.
+pub fn get_device(id: DeviceId) -> Option<Arc<BlockDeviceNode>> {
+    BLOCK_DEVICE_NODES
+        .lock()
+        .get(&(id.major(), id.minor()))
+        .cloned()
+}
+
+/// Returns the name of the `index`-th disk, e.g., `vda`, `vdz`, `vdaa`.
+fn disk_name(index: usize) -> String {
+    let mut suffix = Vec::new();
+    let mut index = index + 1;
+    while index > 0 {
+        index -= 1;
+        suffix.push(b'a' + (index % 26) as u8);
+        index /= 26;
+    }
+    suffix.reverse();
+    String::from("vd") + core::str::from_utf8(&suffix).unwrap()
+}
+
+/// Returns the name of a partition on the disk, e.g., `vda1`, or `loop0p1`
+/// if the disk name ends with a digit.
+fn partition_name(disk_name: &str, number: u32) -> String {
+    if disk_name.ends_with(|c: char| c.is_ascii_digit()) {
+        format!("{}p{}", disk_name, number)
+    } else {
+        format!("{}{}", disk_name, number)
+    }
+}
+
+/// A block device node.
+///
+/// The node is shared by all the opened files of the device, so are the cached pages.
+pub struct BlockDeviceNode {
+    id: DeviceId,
+    device: Arc<dyn BlockDevice>,
+    /// The size in bytes, which is a multiple of the sector size
+    size: AtomicUsize,
+    page_cache: PageCache,
+    this: Weak<Self>,
+}
+
+impl BlockDeviceNode {
+    pub fn new(id: DeviceId, device: Arc<dyn BlockDevice>) -> Arc<Self> {
+        let size = device.nr_sectors() * SECTOR_SIZE;
+        Arc::new_cyclic(|weak_self| Self {
+            id,
+            device,
+            size: AtomicUsize::new(size),
+            page_cache: PageCache::with_capacity(size, weak_self.clone() as _).unwrap(),
+            this: weak_self.clone(),
+        })
+    }
+
+    /// Returns the underlying block device.
+    pub fn block_device(&self) -> &Arc<dyn BlockDevice> {
+        &self.device
+    }
+
+    /// Writes back the dirty pages and drops all the cached pages,
+    /// so that the following reads get the data from the device.
+    ///
+    /// The size is updated as well, since the device may be resized, e.g., a loop device.
+    pub fn invalidate(&self) -> Result<()> {
+        self.page_cache.evict_range(0..self.size())?;
+        self.page_cache.pages().resize(0)?;
+        let new_size = self.device.nr_sectors() * SECTOR_SIZE;
+        self.size.store(new_size, Ordering::Relaxed);
+        self.page_cache.pages().resize(new_size)?;
+        Ok(())
+    }
+
+    /// Creates a bio that transfers the valid part of the page at `idx`.
+    fn page_bio(&self, type_: BioType, idx: usize, frame: &Frame) -> Result<Bio> {
+        let offset = idx * PAGE_SIZE;
+        let size = self.size();
+        if offset >= size {
+            return_errno_with_message!(Errno::EINVAL, "the page is beyond the device");
+        }
+        let len = (size - offset).min(PAGE_SIZE);
+        let segment = BioSegment::from_frame(frame.clone(), 0, len);
+        Ok(Bio::new(
+            type_,
+            Sid::from_offset(offset),
+            vec![segment],
+            None,
+        ))
+    }
+}
+
+impl PageCacheBackend for BlockDeviceNode {
+    fn read_page(&self, idx: usize, frame: &Frame) -> Result<BioWaiter> {
+        let bio = self.page_bio(BioType::Read, idx, frame)?;
+        // The last page may be partially covered by the device.
+        let len = bio.segments()[0].nbytes();
+        if len < PAGE_SIZE {
+            frame.write_bytes(len, &vec![0u8; PAGE_SIZE - len])?;
+        }
+        Ok(bio.submit(self.device.as_ref())?)
+    }
+
+    fn write_page(&self, idx: usize, frame: &Frame) -> Result<BioWaiter> {
+        let bio = self.page_bio(BioType::Write, idx, frame)?;
+        Ok(bio.submit(self.device.as_ref())?)
+    }
+
+    fn npages(&self) -> usize {
+        self.size().div_ceil(PAGE_SIZE)
+    }
+}
+
+impl Device for BlockDeviceNode {
+    fn type_(&self) -> DeviceType {
+        DeviceType::BlockDevice
+    }
+
+    fn id(&self) -> DeviceId {
+        self.id
+    }
+
+    fn open(&self) -> Result<Option<Arc<dyn FileIo>>> {
+        Ok(Some(self.this.upgrade().unwrap()))
+    }
+
+    fn sync(&self) -> Result<()> {
+        self.page_cache.evict_range(0..self.size())
+    }
+}
+
+impl FileIo for BlockDeviceNode {
+    // The file handle calls `read_at` and `write_at` with its offset instead.
+    fn read(&self, buf: &mut [u8]) -> Result<usize> {
+        return_errno_with_message!(
+            Errno::EINVAL,
+            "the block device must be read with an offset"
+        );
+    }
+
+    fn write(&self, buf: &[u8]) -> Result<usize> {
+        return_errno_with_message!(
+            Errno::EINVAL,
+            "the block device must be written with an offset"
+        );
+    }
+
+    fn poll(&self, mask: IoEvents, poller: Option<&Poller>) -> IoEvents {
+        let events = IoEvents::IN | IoEvents::OUT;
+        events & mask
+    }
+
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        match cmd {
+            IoctlCmd::BLKGETSIZE => {
+                let nr_sectors = (self.size() / SECTOR_SIZE) as u64;
+                write_val_to_user(arg, &nr_sectors)?;
+                Ok(0)
+            }
+            IoctlCmd::BLKGETSIZE64 => {
+                write_val_to_user(arg, &(self.size() as u64))?;
+                Ok(0)
+            }
+            IoctlCmd::BLKSSZGET => {
+                write_val_to_user(arg, &(SECTOR_SIZE as i32))?;
+                Ok(0)
+            }
+            IoctlCmd::BLKFLSBUF => {
+                self.invalidate()?;
+                Ok(0)
+            }
+            _ => {
+                if let Some(loop_device) = self.device.downcast_ref::<LoopDevice>() {
+                    return loop_device.ioctl(self, cmd, arg);
+                }
+                return_errno_with_message!(Errno::EINVAL, "unsupported ioctl command")
+            }
+        }
+    }
+
+    fn is_seekable(&self) -> bool {
+        true
+    }
+
+    fn size(&self) -> usize {
+        self.size.load(Ordering::Relaxed)
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        let size = self.size();
+        let start = offset.min(size);
+        let end = offset.saturating_add(buf.len()).min(size);
+        self.page_cache
+            .pages()
+            .read_bytes(start, &mut buf[..end - start])?;
+        Ok(end - start)
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let size = self.size();
+        if offset >= size {
+            return_errno_with_message!(Errno::ENOSPC, "write beyond the end of the device");
+        }
+        let len = buf.len().min(size - offset);
+        self.page_cache.pages().write_bytes(offset, &buf[..len])?;
+        Ok(len)
+    }
+}
+
+impl Debug for BlockDeviceNode {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("BlockDeviceNode")
+            .field("id", &self.id)
+            .field("device", &self.device)
+            .field("size", &self.size())
+            .finish()
+    }
+}
@@ -0,0 +1,44 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#![allow(unused_variables)]
+
+use super::*;
+use crate::{
+    events::IoEvents,
+    fs::{fuse::FuseDevFile, inode_handle::FileIo},
+    prelude::*,
+    process::signal::Poller,
+};
+
+/// Corresponds to `/dev/fuse` in the file system. Each open of the device creates
+/// a new connection, which is served by a FUSE daemon and used by a FUSE mount.
+pub struct FuseDevice;
+
+impl Device for FuseDevice {
+    fn open(&self) -> Result<Option<Arc<dyn FileIo>>> {
+        Ok(Some(FuseDevFile::new()))
+    }
+
+    fn type_(&self) -> DeviceType {
+        DeviceType::CharDevice
+    }
+
+    fn id(&self) -> DeviceId {
+        // Same value with Linux
+        DeviceId::new(10, 229)
+    }
+}
+
+impl FileIo for FuseDevice {
+    fn read(&self, buf: &mut [u8]) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "cannot read fuse device");
+    }
+
+    fn write(&self, buf: &[u8]) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "cannot write fuse device");
+    }
+
+    fn poll(&self, mask: IoEvents, poller: Option<&Poller>) -> IoEvents {
+        IoEvents::empty()
+    }
+}
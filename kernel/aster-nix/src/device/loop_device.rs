@@ -0,0 +1,551 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#![allow(unused_variables)]
+
+//! Loop devices, i.e., `/dev/loopN`, which are block devices backed by regular files.
+//!
+//! A loop device is associated with a file by the `LOOP_SET_FD` ioctl, after which
+//! the bios submitted to the device are served by reading or writing the file.
+//! Free loop devices are found or allocated through `/dev/loop-control`.
+
+use alloc::format;
+
+use aster_block::{
+    bio::{BioEnqueueError, BioStatus, BioType, SubmittedBio},
+    BlockDevice, SECTOR_SIZE,
+};
+
+use super::{block, *};
+use crate::{
+    events::IoEvents,
+    fs::{
+        file_table::FileDesc,
+        inode_handle::{FileIo, InodeHandle},
+        path::Dentry,
+        utils::{InodeType, IoctlCmd},
+    },
+    prelude::*,
+    process::signal::Poller,
+    util::{read_val_from_user, write_val_to_user},
+};
+
+/// The major device number of loop devices.
+const LOOP_MAJOR: u32 = 7;
+/// The number of loop devices created at boot.
+const NR_INITIAL_LOOP_DEVICES: u32 = 8;
+/// The length of the file name in `LoopInfo64`.
+const LO_NAME_SIZE: usize = 64;
+
+static LOOP_DEVICES: Mutex<Vec<Arc<LoopDevice>>> = Mutex::new(Vec::new());
+
+/// Creates `/dev/loop-control` and the initial loop devices.
+pub(super) fn init() -> Result<()> {
+    registry::register_major(DeviceType::BlockDevice, Some(LOOP_MAJOR), "loop")?;
+    registry::register_device(Arc::new(LoopControl), "loop-control")?;
+    for _ in 0..NR_INITIAL_LOOP_DEVICES {
+        add_loop_device()?;
+    }
+    Ok(())
+}
+
+/// Creates a new loop device, whose number follows the existing ones.
+fn add_loop_device() -> Result<Arc<LoopDevice>> {
+    let mut loop_devices = LOOP_DEVICES.lock();
+    let number = loop_devices.len() as u32;
+    let loop_device = Arc::new(LoopDevice {
+        number,
+        backing: RwLock::new(None),
+    });
+    block::add_disk(
+        &format!("loop{}", number),
+        DeviceId::new(LOOP_MAJOR, number),
+        1,
+        loop_device.clone(),
+    )?;
+    loop_devices.push(loop_device.clone());
+    Ok(loop_device)
+}
+
+/// A loop device.
+pub struct LoopDevice {
+    number: u32,
+    backing: RwLock<Option<BackingFile>>,
+}
+
+/// The file associated with a loop device.
+#[derive(Clone)]
+struct BackingFile {
+    dentry: Arc<Dentry>,
+    /// The offset in bytes of the device data in the file
+    offset: usize,
+    /// The maximum size in bytes of the device, or zero if unlimited
+    size_limit: usize,
+    flags: LoopFlags,
+    file_name: [u8; LO_NAME_SIZE],
+}
+
+bitflags! {
+    struct LoopFlags: u32 {
+        const READ_ONLY = 1 << 0;
+        const AUTOCLEAR = 1 << 2;
+        const PARTSCAN  = 1 << 3;
+        const DIRECT_IO = 1 << 4;
+    }
+}
+
+/// The flags that can be changed by `LOOP_SET_STATUS64` in Linux.
+const LOOP_SETTABLE_FLAGS: LoopFlags = LoopFlags::AUTOCLEAR.union(LoopFlags::PARTSCAN);
+
+/// The status of a loop device, i.e., `struct loop_info64` in Linux.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct LoopInfo64 {
+    lo_device: u64,
+    lo_inode: u64,
+    lo_rdevice: u64,
+    lo_offset: u64,
+    lo_sizelimit: u64,
+    lo_number: u32,
+    lo_encrypt_type: u32,
+    lo_encrypt_key_size: u32,
+    lo_flags: u32,
+    lo_file_name: [u8; LO_NAME_SIZE],
+    lo_crypt_name: [u8; LO_NAME_SIZE],
+    lo_encrypt_key: [u8; 32],
+    lo_init: [u64; 2],
+}
+
+impl LoopDevice {
+    /// Handles the loop ioctls issued on the device node.
+    pub(super) fn ioctl(&self, node: &BlockDeviceNode, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        match cmd {
+            IoctlCmd::LOOP_SET_FD => {
+                self.set_fd(arg as FileDesc)?;
+                node.invalidate()?;
+                block::rescan_partitions(self.id())?;
+            }
+            IoctlCmd::LOOP_CLR_FD => {
+                if self.backing.read().is_none() {
+                    return_errno_with_message!(Errno::ENXIO, "the loop device is not bound");
+                }
+                if self.is_in_use() {
+                    return_errno_with_message!(Errno::EBUSY, "the loop device is in use");
+                }
+                // Write back the cached data before the file is detached.
+                block::clear_partitions(self.id())?;
+                node.invalidate()?;
+                *self.backing.write() = None;
+                node.invalidate()?;
+            }
+            IoctlCmd::LOOP_SET_STATUS64 => {
+                let info: LoopInfo64 = read_val_from_user(arg)?;
+                block::clear_partitions(self.id())?;
+                node.invalidate()?;
+                self.set_status(&info)?;
+                node.invalidate()?;
+                block::rescan_partitions(self.id())?;
+            }
+            IoctlCmd::LOOP_GET_STATUS64 => {
+                let info = self.status()?;
+                write_val_to_user(arg, &info)?;
+            }
+            _ => return_errno_with_message!(Errno::EINVAL, "unsupported ioctl command"),
+        }
+        Ok(0)
+    }
+
+    fn set_fd(&self, fd: FileDesc) -> Result<()> {
+        if self.backing.read().is_some() {
+            return_errno_with_message!(Errno::EBUSY, "the loop device is already bound");
+        }
+
+        let (dentry, is_writable) = {
+            let current = current!();
+            let file_table = current.file_table().lock();
+            let file = file_table.get_file(fd)?;
+            let inode_handle = file
+                .downcast_ref::<InodeHandle>()
+                .ok_or(Error::with_message(Errno::EBADF, "not inode"))?;
+            (
+                inode_handle.dentry().clone(),
+                file.access_mode().is_writable(),
+            )
+        };
+        if dentry.type_() != InodeType::File {
+            return_errno_with_message!(Errno::EINVAL, "the backing file is not a regular file");
+        }
+
+        let mut file_name = [0u8; LO_NAME_SIZE];
+        let path = dentry.abs_path();
+        let len = path.len().min(LO_NAME_SIZE - 1);
+        file_name[..len].copy_from_slice(&path.as_bytes()[..len]);
+        let flags = if is_writable {
+            LoopFlags::empty()
+        } else {
+            LoopFlags::READ_ONLY
+        };
+        let mut backing = self.backing.write();
+        if backing.is_some() {
+            return_errno_with_message!(Errno::EBUSY, "the loop device is already bound");
+        }
+        *backing = Some(BackingFile {
+            dentry,
+            offset: 0,
+            size_limit: 0,
+            flags,
+            file_name,
+        });
+        Ok(())
+    }
+
+    fn set_status(&self, info: &LoopInfo64) -> Result<()> {
+        let mut backing = self.backing.write();
+        let Some(backing) = backing.as_mut() else {
+            return_errno_with_message!(Errno::ENXIO, "the loop device is not bound");
+        };
+        if info.lo_encrypt_type != 0 {
+            return_errno_with_message!(Errno::EINVAL, "loop encryption is not supported");
+        }
+        // TODO: support detaching the device once it is no longer used, and scanning
+        // the partitions on the device.
+        if LoopFlags::from_bits_truncate(info.lo_flags).intersects(LOOP_SETTABLE_FLAGS) {
+            return_errno_with_message!(
+                Errno::EINVAL,
+                "autoclear and partition scanning are not supported"
+            );
+        }
+        backing.offset = info.lo_offset as usize;
+        backing.size_limit = info.lo_sizelimit as usize;
+        backing.file_name = info.lo_file_name;
+        backing.file_name[LO_NAME_SIZE - 1] = 0;
+        Ok(())
+    }
+
+    /// Returns whether the device or one of its partitions is used, e.g., by a mounted
+    /// filesystem.
+    ///
+    /// A user holds the device itself, while the list of the loop devices, the device node
+    /// and each partition hold one reference each. A partition is held by `aster_block`
+    /// and its device node.
+    fn is_in_use(&self) -> bool {
+        let partitions = block::partitions(self.id());
+        // The `partitions` holds one more reference to each partition.
+        if partitions
+            .iter()
+            .any(|partition| Arc::strong_count(partition) > 3)
+        {
+            return true;
+        }
+        let loop_devices = LOOP_DEVICES.lock();
+        Arc::strong_count(&loop_devices[self.number as usize]) > 2 + partitions.len()
+    }
+
+    fn id(&self) -> DeviceId {
+        DeviceId::new(LOOP_MAJOR, self.number)
+    }
+
+    /// Returns a copy of the backing file.
+    ///
+    /// The file is accessed with the copy since `backing` is a spin lock, which must not
+    /// be held while the file system sleeps.
+    fn backing(&self) -> Option<BackingFile> {
+        self.backing.read().clone()
+    }
+
+    fn status(&self) -> Result<LoopInfo64> {
+        let Some(backing) = self.backing() else {
+            return_errno_with_message!(Errno::ENXIO, "the loop device is not bound");
+        };
+        let metadata = backing.dentry.inode().metadata();
+        Ok(LoopInfo64 {
+            lo_device: metadata.dev,
+            lo_inode: metadata.ino,
+            lo_offset: backing.offset as u64,
+            lo_sizelimit: backing.size_limit as u64,
+            lo_number: self.number,
+            lo_flags: backing.flags.bits(),
+            lo_file_name: backing.file_name,
+            ..LoopInfo64::new_zeroed()
+        })
+    }
+}
+
+impl BackingFile {
+    /// Returns the size in bytes of the device.
+    fn size(&self) -> usize {
+        let mut size = self.dentry.inode().size().saturating_sub(self.offset);
+        if self.size_limit != 0 {
+            size = size.min(self.size_limit);
+        }
+        size / SECTOR_SIZE * SECTOR_SIZE
+    }
+
+    /// Serves the bio by reading or writing the file.
+    fn handle_bio(&self, bio: &SubmittedBio) -> Result<()> {
+        // The backing file is not extended by the bios beyond the end of the device,
+        // e.g., the bios on a device whose size is limited by `lo_sizelimit`.
+        if bio.sid_range().end.to_offset() > self.size() {
+            return_errno_with_message!(Errno::EIO, "the bio is beyond the end of the device");
+        }
+        let inode = self.dentry.inode();
+        let mut offset = self.offset + bio.sid_range().start.to_offset();
+        match bio.type_() {
+            BioType::Read => {
+                for segment in bio.segments() {
+                    // The part beyond the end of the file is read as zeroes.
+                    let mut buf = vec![0u8; segment.nbytes()];
+                    inode.read_at(offset, &mut buf)?;
+                    segment.writer().write(&mut buf.as_slice().into());
+                    offset += buf.len();
+                }
+            }
+            BioType::Write => {
+                self.check_writable()?;
+                for segment in bio.segments() {
+                    let mut buf = vec![0u8; segment.nbytes()];
+                    segment.reader().read(&mut buf.as_mut_slice().into());
+                    inode.write_at(offset, &buf)?;
+                    offset += buf.len();
+                }
+            }
+            BioType::WriteZeroes => {
+                self.check_writable()?;
+                // The zeroes are written page by page to bound the size of the buffer.
+                let zeroes = vec![0u8; PAGE_SIZE];
+                let end = self.offset + bio.sid_range().end.to_offset();
+                while offset < end {
+                    let len = (end - offset).min(PAGE_SIZE);
+                    inode.write_at(offset, &zeroes[..len])?;
+                    offset += len;
+                }
+            }
+            BioType::Flush => inode.sync_data()?,
+            // Discarding is a hint, which is safe to ignore.
+            BioType::Discard => (),
+        }
+        Ok(())
+    }
+
+    fn check_writable(&self) -> Result<()> {
+        if self.flags.contains(LoopFlags::READ_ONLY) {
+            return_errno_with_message!(Errno::EROFS, "the loop device is read-only");
+        }
+        Ok(())
+    }
+}
+
+impl BlockDevice for LoopDevice {
+    /// Serves the bio synchronously with the backing file.
+    fn enqueue(&self, bio: SubmittedBio) -> core::result::Result<(), BioEnqueueError> {
+        let status = match self.backing() {
+            Some(backing) => match backing.handle_bio(&bio) {
+                Ok(()) => BioStatus::Complete,
+                Err(e) => {
+                    warn!(
+                        "loop{}: failed to do {:?}: {:?}",
+                        self.number,
+                        bio.type_(),
+                        e
+                    );
+                    BioStatus::IoError
+                }
+            },
+            None => BioStatus::IoError,
+        };
+        bio.complete(status);
+        Ok(())
+    }
+
+    fn max_nr_segments_per_bio(&self) -> usize {
+        usize::MAX
+    }
+
+    fn nr_sectors(&self) -> usize {
+        self.backing()
+            .map_or(0, |backing| backing.size() / SECTOR_SIZE)
+    }
+}
+
+impl Debug for LoopDevice {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("LoopDevice")
+            .field("number", &self.number)
+            .finish()
+    }
+}
+
+/// Corresponds to `/dev/loop-control` in the file system.
+pub struct LoopControl;
+
+impl Device for LoopControl {
+    fn type_(&self) -> DeviceType {
+        DeviceType::CharDevice
+    }
+
+    fn id(&self) -> DeviceId {
+        // Same value with Linux
+        DeviceId::new(10, 237)
+    }
+}
+
+impl FileIo for LoopControl {
+    fn read(&self, buf: &mut [u8]) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "cannot read loop-control");
+    }
+
+    fn write(&self, buf: &[u8]) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "cannot write loop-control");
+    }
+
+    fn poll(&self, mask: IoEvents, poller: Option<&Poller>) -> IoEvents {
+        IoEvents::empty()
+    }
+
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        match cmd {
+            IoctlCmd::LOOP_CTL_GET_FREE => {
+                let free_device = LOOP_DEVICES
+                    .lock()
+                    .iter()
+                    .find(|device| device.backing.read().is_none())
+                    .cloned();
+                let loop_device = match free_device {
+                    Some(loop_device) => loop_device,
+                    None => add_loop_device()?,
+                };
+                Ok(loop_device.number as i32)
+            }
+            _ => return_errno_with_message!(Errno::EINVAL, "unsupported ioctl command"),
+        }
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use aster_block::bio::BioEnqueueError;
+    use ostd::{
+        mm::{FrameAllocOptions, Segment, VmIo},
+        prelude::*,
+    };
+
+    use super::*;
+    use crate::fs::{
+        ext2::{Ext2, Ext2MountOptions},
+        path::MountNode,
+        utils::InodeMode,
+    };
+
+    /// A block device in memory, which is initialized with the ext2 disk image.
+    struct Ext2MemoryDisk(Segment);
+
+    impl Debug for Ext2MemoryDisk {
+        fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+            f.debug_struct("Ext2MemoryDisk")
+                .field("sectors_count", &self.nr_sectors())
+                .finish()
+        }
+    }
+
+    impl BlockDevice for Ext2MemoryDisk {
+        fn enqueue(&self, bio: SubmittedBio) -> core::result::Result<(), BioEnqueueError> {
+            let mut cur_device_ofs = bio.sid_range().start.to_offset();
+            for seg in bio.segments() {
+                let size = match bio.type_() {
+                    BioType::Read => seg
+                        .writer()
+                        .write(&mut self.0.reader().skip(cur_device_ofs)),
+                    BioType::Write => self
+                        .0
+                        .writer()
+                        .skip(cur_device_ofs)
+                        .write(&mut seg.reader()),
+                    _ => 0,
+                };
+                cur_device_ofs += size;
+            }
+            bio.complete(BioStatus::Complete);
+            Ok(())
+        }
+
+        fn max_nr_segments_per_bio(&self) -> usize {
+            usize::MAX
+        }
+
+        fn nr_sectors(&self) -> usize {
+            self.0.nframes() * (PAGE_SIZE / SECTOR_SIZE)
+        }
+    }
+
+    /// A small ext2 disk image
+    static EXT2_IMAGE: &[u8] = include_bytes!("../../../../test/build/ext2_small.img");
+
+    /// Creates a loop device backed by a file of `size` bytes in an ext2.
+    fn new_ext2_loop_device(size: usize) -> (Arc<dyn BlockDevice>, Arc<Dentry>) {
+        let segment = FrameAllocOptions::new(EXT2_IMAGE.len() / PAGE_SIZE)
+            .is_contiguous(true)
+            .uninit(true)
+            .alloc_contiguous()
+            .unwrap();
+        segment.write_bytes(0, EXT2_IMAGE).unwrap();
+        let ext2 = Ext2::open(
+            Arc::new(Ext2MemoryDisk(segment)),
+            Ext2MountOptions::default(),
+        )
+        .unwrap();
+
+        let root = Dentry::new_fs_root(MountNode::new_root(ext2));
+        let dentry = root
+            .new_fs_child(
+                "backing",
+                InodeType::File,
+                InodeMode::from_bits_truncate(0o644),
+            )
+            .unwrap();
+        dentry.inode().resize(size).unwrap();
+        let loop_device = LoopDevice {
+            number: 0,
+            backing: RwLock::new(Some(BackingFile {
+                dentry: dentry.clone(),
+                offset: 0,
+                size_limit: 0,
+                flags: LoopFlags::empty(),
+                file_name: [0; LO_NAME_SIZE],
+            })),
+        };
+        (Arc::new(loop_device), dentry)
+    }
+
+    #[ktest]
+    fn ext2_backed_read_write() {
+        let (loop_device, dentry) = new_ext2_loop_device(4 * PAGE_SIZE);
+        assert_eq!(loop_device.nr_sectors(), 4 * PAGE_SIZE / SECTOR_SIZE);
+
+        let expected = [0x5Au8; SECTOR_SIZE];
+        loop_device
+            .write_bytes(PAGE_SIZE + SECTOR_SIZE, &expected)
+            .unwrap();
+        let mut buf = [0u8; SECTOR_SIZE];
+        dentry
+            .inode()
+            .read_at(PAGE_SIZE + SECTOR_SIZE, &mut buf)
+            .unwrap();
+        assert_eq!(buf, expected);
+
+        buf.fill(0);
+        loop_device
+            .read_bytes(PAGE_SIZE + SECTOR_SIZE, &mut buf)
+            .unwrap();
+        assert_eq!(buf, expected);
+    }
+
+    #[ktest]
+    fn ext2_backed_bio_beyond_device() {
+        let (loop_device, dentry) = new_ext2_loop_device(PAGE_SIZE);
+
+        let mut buf = [0u8; PAGE_SIZE];
+        assert!(loop_device.read_bytes(PAGE_SIZE, &mut buf).is_err());
+        assert!(loop_device.write_bytes(PAGE_SIZE, &buf).is_err());
+        // The backing file is not extended by the rejected write.
+        assert_eq!(dentry.inode().size(), PAGE_SIZE);
+    }
+}
@@ -0,0 +1,805 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#![allow(unused_variables)]
+
+//! The device mapper, which creates the block devices mapped onto other block devices.
+//!
+//! The mapped devices are managed by the ioctls on `/dev/mapper/control`, which are
+//! compatible with Linux, so that `dmsetup` and `cryptsetup` work. A mapped device
+//! is created without a table, and it is activated by resuming it after a table
+//! is loaded. Only the `crypt` target is supported, which must cover the whole device.
+//! A mapped device is exposed as both `/dev/dm-N` and `/dev/mapper/<name>`.
+//!
+//! The mapped devices can also be created at boot by the kernel command line:
+//! - `dm-mod.create="<name>,<uuid>,<minor>,<flags>,<table>[;<name>,...]"` as Linux,
+//!   where the table is `<start> <length> crypt <params>`;
+//! - `dm-crypt.luks="<device>,<name>,<passphrase>"`, which opens the LUKS2 volume
+//!   on the device with the passphrase, which cannot contain `=` or spaces.
+//!
+//! Suspending a device does not hold the I/O. It only marks the device, and the loaded
+//! table replaces the active one when the device is resumed.
+
+use alloc::format;
+use core::{
+    mem::size_of,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use aster_block::{
+    bio::{BioEnqueueError, SubmittedBio},
+    crypt::{CryptConfig, CryptDevice},
+    id::Sid,
+    BlockDevice, SECTOR_SIZE,
+};
+use ostd::{
+    boot::kcmdline::ModuleArg,
+    mm::{VmReader, VmWriter},
+};
+
+use super::{
+    block::{self, BlockDeviceNode},
+    *,
+};
+use crate::{
+    events::IoEvents,
+    fs::{
+        device::{add_node, delete_node},
+        fs_resolver::{FsPath, FsResolver, AT_FDCWD},
+        inode_handle::FileIo,
+        utils::{InodeType, IoctlCmd},
+    },
+    prelude::*,
+    process::signal::Poller,
+    util::{read_bytes_from_user, write_bytes_to_user},
+};
+
+/// The major device number of mapped devices.
+const DM_MAJOR: u32 = 253;
+/// The version of the ioctl interface, which is that of Linux 6.x.
+const DM_VERSION: [u32; 3] = [4, 48, 0];
+/// The version of the crypt target.
+const CRYPT_TARGET_VERSION: [u32; 3] = [1, 24, 0];
+/// The maximum size of the data passed by an ioctl.
+const MAX_DATA_SIZE: usize = 64 * 1024;
+const DM_NAME_LEN: usize = 128;
+const DM_UUID_LEN: usize = 129;
+const DM_MAX_TYPE_NAME: usize = 16;
+
+static MAPPED_DEVICES: Mutex<Vec<Arc<MappedDevice>>> = Mutex::new(Vec::new());
+
+/// Creates `/dev/mapper/control`.
+pub(super) fn init() -> Result<()> {
+    registry::register_major(DeviceType::BlockDevice, Some(DM_MAJOR), "device-mapper")?;
+    registry::register_device(Arc::new(MapperControl), "mapper/control")?;
+    Ok(())
+}
+
+/// Creates the mapped devices specified by the kernel command line.
+///
+/// The devices are created in the order of `dm-mod.create` and then `dm-crypt.luks`,
+/// and those that fail are skipped with warnings.
+pub(super) fn create_from_kcmdline() {
+    // The specs contain the keys or the passphrases, so only the names are logged.
+    for value in kcmdline_values("dm-mod", "create") {
+        for spec in value.split(';').filter(|spec| !spec.is_empty()) {
+            if let Err(e) = create_from_spec(spec) {
+                let name = spec.split(',').next().unwrap_or_default();
+                warn!("dm-mod.create: failed to create {:?}: {:?}", name, e);
+            }
+        }
+        zeroize(value.into_bytes());
+    }
+    for value in kcmdline_values("dm-crypt", "luks") {
+        if let Err(e) = open_luks(&value) {
+            let name = value.split(',').nth(1).unwrap_or_default();
+            warn!("dm-crypt.luks: failed to open {:?}: {:?}", name, e);
+        }
+        zeroize(value.into_bytes());
+    }
+}
+
+/// Overwrites the key material with zeroes before it is freed.
+fn zeroize(mut bytes: Vec<u8>) {
+    bytes.fill(0);
+    core::hint::black_box(&bytes);
+}
+
+/// Returns the values of the module option on the kernel command line, without the quotes.
+fn kcmdline_values(module: &str, option: &str) -> Vec<String> {
+    let Some(args) = ostd::boot::kernel_cmdline().get_module_args(module) else {
+        return Vec::new();
+    };
+    args.iter()
+        .filter_map(|arg| match arg {
+            ModuleArg::KeyVal(name, value) if name.as_bytes() == option.as_bytes() => {
+                value.to_str().ok()
+            }
+            _ => None,
+        })
+        .map(|value| value.trim_matches('"').to_string())
+        .collect()
+}
+
+/// Creates a mapped device by `<name>,<uuid>,<minor>,<flags>,<table>`.
+fn create_from_spec(spec: &str) -> Result<()> {
+    let fields: Vec<&str> = spec.splitn(5, ',').collect();
+    let [name, uuid, minor, flags, table] = fields[..] else {
+        return_errno_with_message!(Errno::EINVAL, "the device spec is incomplete");
+    };
+    let minor = match minor {
+        "" => None,
+        minor => Some(
+            minor
+                .parse()
+                .map_err(|_| Error::with_message(Errno::EINVAL, "invalid minor number"))?,
+        ),
+    };
+    if flags != "ro" && flags != "rw" {
+        return_errno_with_message!(Errno::EINVAL, "the flags must be ro or rw");
+    }
+    if table.contains(',') {
+        return_errno_with_message!(Errno::EINVAL, "only one target is supported");
+    }
+
+    let mut table_fields = table.splitn(4, ' ');
+    let (Some(start), Some(length), Some(target_type), Some(params)) = (
+        table_fields.next(),
+        table_fields.next(),
+        table_fields.next(),
+        table_fields.next(),
+    ) else {
+        return_errno_with_message!(Errno::EINVAL, "the table is incomplete");
+    };
+    let (Ok(start), Ok(length)) = (start.parse::<u64>(), length.parse::<u64>()) else {
+        return_errno_with_message!(Errno::EINVAL, "invalid target range");
+    };
+    let target = Target::new(start, length, target_type, params, &FsResolver::new())?;
+
+    let device = MappedDevice::create(name, uuid, minor)?;
+    *device.inactive.write() = Some(target);
+    device.resume()?;
+    info!("dm-mod.create: created {}", name);
+    Ok(())
+}
+
+/// Opens a LUKS2 volume by `<device>,<name>,<passphrase>`.
+fn open_luks(spec: &str) -> Result<()> {
+    let fields: Vec<&str> = spec.splitn(3, ',').collect();
+    let [path, name, passphrase] = fields[..] else {
+        return_errno_with_message!(Errno::EINVAL, "the LUKS spec is incomplete");
+    };
+    let node = lookup_block_device(path, &FsResolver::new())?;
+    let crypt_device =
+        match CryptDevice::open_luks2(node.block_device().clone(), passphrase.as_bytes()) {
+            Ok(crypt_device) => crypt_device,
+            Err(ostd::Error::AccessDenied) => {
+                return_errno_with_message!(Errno::EPERM, "no keyslot is unlocked by the passphrase")
+            }
+            Err(e) => return Err(e.into()),
+        };
+    // The key is not reported, as Linux does for the keys in the kernel keyring.
+    let config = crypt_device.config();
+    let mut params = format!(
+        "{} - {} {}:{} {}",
+        config.cipher,
+        config.iv_offset,
+        node.id().major(),
+        node.id().minor(),
+        config.start_sid.to_raw()
+    );
+    if config.sector_size != SECTOR_SIZE {
+        params += &format!(" 2 sector_size:{} iv_large_sectors", config.sector_size);
+    }
+    let target = Target {
+        length: crypt_device.nr_sectors() as u64,
+        params,
+        device: Arc::new(crypt_device),
+    };
+
+    let device = MappedDevice::create(name, "", None)?;
+    *device.inactive.write() = Some(target);
+    device.resume()?;
+    info!("dm-crypt.luks: opened {} as /dev/mapper/{}", path, name);
+    Ok(())
+}
+
+/// Returns the block device node of a path, or of `<major>:<minor>`.
+fn lookup_block_device(spec: &str, fs_resolver: &FsResolver) -> Result<Arc<BlockDeviceNode>> {
+    let id = match spec.split_once(':') {
+        Some((major, minor)) => {
+            let (Ok(major), Ok(minor)) = (major.parse(), minor.parse()) else {
+                return_errno_with_message!(Errno::EINVAL, "invalid device number");
+            };
+            DeviceId::new(major, minor)
+        }
+        None => {
+            let dentry = fs_resolver.lookup(&FsPath::new(AT_FDCWD, spec)?)?;
+            if dentry.type_() != InodeType::BlockDevice {
+                return_errno_with_message!(Errno::ENOTBLK, "not a block device");
+            }
+            DeviceId::from(dentry.inode().metadata().rdev)
+        }
+    };
+    block::get_device(id)
+        .ok_or_else(|| Error::with_message(Errno::ENXIO, "the block device does not exist"))
+}
+
+/// A mapped device.
+pub struct MappedDevice {
+    name: String,
+    uuid: String,
+    minor: u32,
+    /// The table that serves the I/O
+    active: RwLock<Option<Target>>,
+    /// The table loaded to replace the active one
+    inactive: RwLock<Option<Target>>,
+    is_suspended: AtomicBool,
+}
+
+/// A target of the table, which maps the sectors to another block device.
+#[derive(Clone)]
+struct Target {
+    /// The number of sectors
+    length: u64,
+    /// The parameters to create the target, which are reported by `DM_TABLE_STATUS`
+    params: String,
+    device: Arc<CryptDevice>,
+}
+
+impl Drop for Target {
+    fn drop(&mut self) {
+        zeroize(core::mem::take(&mut self.params).into_bytes());
+    }
+}
+
+impl Target {
+    /// Creates the target of the `target_type` with its parameters.
+    fn new(
+        start: u64,
+        length: u64,
+        target_type: &str,
+        params: &str,
+        fs_resolver: &FsResolver,
+    ) -> Result<Self> {
+        if target_type != "crypt" {
+            return_errno_with_message!(Errno::EINVAL, "only the crypt target is supported");
+        }
+        if start != 0 || length == 0 {
+            return_errno_with_message!(Errno::EINVAL, "the target must cover the whole device");
+        }
+        let device = new_crypt_device(length, params, fs_resolver)?;
+        Ok(Self {
+            length,
+            params: params.trim().to_string(),
+            device: Arc::new(device),
+        })
+    }
+}
+
+/// Creates an encrypted device by the parameters of the crypt target, i.e.,
+/// `<cipher> <key> <iv_offset> <device> <offset> [<#opt_params> <opt_params>...]`.
+fn new_crypt_device(length: u64, params: &str, fs_resolver: &FsResolver) -> Result<CryptDevice> {
+    let params: Vec<&str> = params.split_whitespace().collect();
+    let [cipher, key, iv_offset, device, offset, opt_params @ ..] = &params[..] else {
+        return_errno_with_message!(Errno::EINVAL, "the crypt parameters are incomplete");
+    };
+    let (Ok(iv_offset), Ok(offset)) = (iv_offset.parse::<u64>(), offset.parse::<u64>()) else {
+        return_errno_with_message!(Errno::EINVAL, "invalid offset");
+    };
+
+    let mut config = CryptConfig {
+        cipher: cipher.to_string(),
+        start_sid: Sid::new(offset),
+        nr_sectors: length as usize,
+        iv_offset,
+        sector_size: SECTOR_SIZE,
+        iv_large_sectors: false,
+        allow_discards: false,
+    };
+    if let [nr_opt_params, opt_params @ ..] = opt_params {
+        if nr_opt_params.parse::<usize>().ok() != Some(opt_params.len()) {
+            return_errno_with_message!(Errno::EINVAL, "invalid number of optional parameters");
+        }
+        for opt_param in opt_params {
+            match *opt_param {
+                "allow_discards" => config.allow_discards = true,
+                "iv_large_sectors" => config.iv_large_sectors = true,
+                // The options tuning the workqueues of Linux are irrelevant.
+                "same_cpu_crypt"
+                | "submit_from_crypt_cpus"
+                | "no_read_workqueue"
+                | "no_write_workqueue" => (),
+                opt_param => {
+                    let Some(sector_size) = opt_param.strip_prefix("sector_size:") else {
+                        return_errno_with_message!(Errno::EINVAL, "unsupported optional parameter");
+                    };
+                    config.sector_size = sector_size
+                        .parse()
+                        .map_err(|_| Error::with_message(Errno::EINVAL, "invalid sector size"))?;
+                }
+            }
+        }
+    }
+
+    let node = lookup_block_device(device, fs_resolver)?;
+    // The keys in the kernel keyring, i.e., `:<size>:<type>:<description>`, are not supported.
+    let Some(key) = decode_hex(key) else {
+        return_errno_with_message!(Errno::EINVAL, "the key must be in hexadecimal");
+    };
+    let crypt_device = CryptDevice::new(node.block_device().clone(), config, &key)
+        .map_err(|_| Error::with_message(Errno::EINVAL, "invalid crypt parameters"));
+    zeroize(key);
+    crypt_device
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+impl MappedDevice {
+    /// Creates a mapped device without any table, and its device nodes.
+    ///
+    /// The minor number is allocated if not specified.
+    fn create(name: &str, uuid: &str, minor: Option<u32>) -> Result<Arc<Self>> {
+        if name.is_empty() || name.len() >= DM_NAME_LEN || name.contains('/') || name == "control" {
+            return_errno_with_message!(Errno::EINVAL, "invalid device name");
+        }
+        let mut devices = MAPPED_DEVICES.lock();
+        if devices
+            .iter()
+            .any(|device| device.name == name || (!uuid.is_empty() && device.uuid == uuid))
+        {
+            return_errno_with_message!(Errno::EBUSY, "the device name or UUID is in use");
+        }
+        let is_used = |minor: u32| devices.iter().any(|device| device.minor == minor);
+        let minor = match minor {
+            Some(minor) if is_used(minor) => {
+                return_errno_with_message!(Errno::EBUSY, "the minor number is in use")
+            }
+            Some(minor) => minor,
+            None => (0..).find(|minor| !is_used(*minor)).unwrap(),
+        };
+
+        let device = Arc::new(Self {
+            name: name.to_string(),
+            uuid: uuid.to_string(),
+            minor,
+            active: RwLock::new(None),
+            inactive: RwLock::new(None),
+            is_suspended: AtomicBool::new(false),
+        });
+        let node = block::add_disk(
+            &format!("dm-{}", minor),
+            device.id(),
+            1,
+            device.clone() as Arc<dyn BlockDevice>,
+        )?;
+        // The node named after the device is not registered, since it shares the number
+        // of `/dev/dm-<minor>`.
+        if let Err(e) = add_node(node, &format!("mapper/{}", name)) {
+            block::remove_disk(device.id())?;
+            return Err(e);
+        }
+        devices.push(device.clone());
+        Ok(device)
+    }
+
+    /// Removes the device and its device nodes.
+    fn remove(&self) -> Result<()> {
+        MAPPED_DEVICES
+            .lock()
+            .retain(|device| device.minor != self.minor);
+        delete_node(&format!("mapper/{}", self.name))?;
+        block::remove_disk(self.id())
+    }
+
+    fn id(&self) -> DeviceId {
+        DeviceId::new(DM_MAJOR, self.minor)
+    }
+
+    /// Resumes the device, which activates the loaded table if any.
+    fn resume(&self) -> Result<()> {
+        if let Some(target) = self.inactive.write().take() {
+            block::clear_partitions(self.id())?;
+            *self.active.write() = Some(target);
+            // The cached data of the old table are dropped, and the size is updated.
+            if let Some(node) = block::get_device(self.id()) {
+                node.invalidate()?;
+            }
+            block::rescan_partitions(self.id())?;
+        }
+        self.is_suspended.store(false, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+impl BlockDevice for MappedDevice {
+    fn enqueue(&self, bio: SubmittedBio) -> core::result::Result<(), BioEnqueueError> {
+        let device = self
+            .active
+            .read()
+            .as_ref()
+            .map(|target| target.device.clone());
+        match device {
+            Some(device) => device.enqueue(bio),
+            None => Err(BioEnqueueError::Refused),
+        }
+    }
+
+    fn max_nr_segments_per_bio(&self) -> usize {
+        self.active
+            .read()
+            .as_ref()
+            .map_or(usize::MAX, |target| target.device.max_nr_segments_per_bio())
+    }
+
+    fn nr_sectors(&self) -> usize {
+        self.active
+            .read()
+            .as_ref()
+            .map_or(0, |target| target.length as usize)
+    }
+}
+
+impl Debug for MappedDevice {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("MappedDevice")
+            .field("name", &self.name)
+            .field("minor", &self.minor)
+            .finish()
+    }
+}
+
+/// Corresponds to `/dev/mapper/control` in the file system.
+pub struct MapperControl;
+
+impl Device for MapperControl {
+    fn type_(&self) -> DeviceType {
+        DeviceType::CharDevice
+    }
+
+    fn id(&self) -> DeviceId {
+        // Same value with Linux
+        DeviceId::new(10, 236)
+    }
+}
+
+impl FileIo for MapperControl {
+    fn read(&self, buf: &mut [u8]) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "cannot read mapper/control");
+    }
+
+    fn write(&self, buf: &[u8]) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "cannot write mapper/control");
+    }
+
+    fn poll(&self, mask: IoEvents, poller: Option<&Poller>) -> IoEvents {
+        IoEvents::empty()
+    }
+
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        let mut request = DmRequest::read_from_user(arg)?;
+        match cmd {
+            IoctlCmd::DM_VERSION => (),
+            IoctlCmd::DM_LIST_DEVICES => request.list_devices(),
+            IoctlCmd::DM_DEV_CREATE => request.create_device()?,
+            IoctlCmd::DM_DEV_REMOVE => request.find_device()?.remove()?,
+            IoctlCmd::DM_DEV_SUSPEND => request.suspend_device()?,
+            IoctlCmd::DM_DEV_STATUS => {
+                let device = request.find_device()?;
+                request.fill_status(&device);
+            }
+            IoctlCmd::DM_TABLE_LOAD => request.load_table()?,
+            IoctlCmd::DM_TABLE_CLEAR => {
+                let device = request.find_device()?;
+                *device.inactive.write() = None;
+                request.fill_status(&device);
+            }
+            IoctlCmd::DM_TABLE_STATUS => request.table_status()?,
+            IoctlCmd::DM_LIST_VERSIONS => request.list_versions(),
+            _ => return_errno_with_message!(Errno::EINVAL, "unsupported ioctl command"),
+        }
+        request.write_to_user(arg)?;
+        Ok(0)
+    }
+}
+
+/// The header of the data passed by the ioctls, i.e., `struct dm_ioctl` in Linux.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct DmIoctl {
+    version: [u32; 3],
+    /// The total size of the data, including this header
+    data_size: u32,
+    /// The offset of the payload from the start of this header
+    data_start: u32,
+    target_count: u32,
+    open_count: i32,
+    flags: u32,
+    event_nr: u32,
+    padding: u32,
+    dev: u64,
+    name: [u8; DM_NAME_LEN],
+    uuid: [u8; DM_UUID_LEN],
+    data: [u8; 7],
+}
+
+/// A target of a table in the payload, i.e., `struct dm_target_spec` in Linux,
+/// which is followed by the parameters as a C string.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct DmTargetSpec {
+    sector_start: u64,
+    length: u64,
+    status: i32,
+    /// The offset of the next target
+    next: u32,
+    target_type: [u8; DM_MAX_TYPE_NAME],
+}
+
+bitflags! {
+    struct DmFlags: u32 {
+        const READONLY         = 1 << 0;
+        const SUSPEND          = 1 << 1;
+        const PERSISTENT_DEV   = 1 << 3;
+        const STATUS_TABLE     = 1 << 4;
+        const ACTIVE_PRESENT   = 1 << 5;
+        const INACTIVE_PRESENT = 1 << 6;
+        const BUFFER_FULL      = 1 << 8;
+    }
+}
+
+/// An ioctl request on the control device.
+struct DmRequest {
+    header: DmIoctl,
+    /// The payload following the header
+    payload: Vec<u8>,
+    /// The payload to be returned
+    output: Vec<u8>,
+}
+
+impl Drop for DmRequest {
+    fn drop(&mut self) {
+        // The payload of `DM_TABLE_LOAD` and the output of `DM_TABLE_STATUS` contain the key.
+        zeroize(core::mem::take(&mut self.payload));
+        zeroize(core::mem::take(&mut self.output));
+    }
+}
+
+impl DmRequest {
+    fn read_from_user(addr: Vaddr) -> Result<Self> {
+        let mut header_bytes = [0u8; size_of::<DmIoctl>()];
+        read_bytes_from_user(addr, &mut VmWriter::from(header_bytes.as_mut_slice()))?;
+        let header = DmIoctl::from_bytes(&header_bytes);
+        if header.version[0] != DM_VERSION[0] {
+            return_errno_with_message!(Errno::EINVAL, "unsupported dm ioctl version");
+        }
+        let data_size = header.data_size as usize;
+        let data_start = header.data_start as usize;
+        if data_size < size_of::<DmIoctl>() || data_size > MAX_DATA_SIZE {
+            return_errno_with_message!(Errno::EINVAL, "invalid data size");
+        }
+
+        let mut payload = Vec::new();
+        if data_start < data_size {
+            payload.resize(data_size - data_start, 0);
+            read_bytes_from_user(
+                addr + data_start,
+                &mut VmWriter::from(payload.as_mut_slice()),
+            )?;
+        }
+        Ok(Self {
+            header,
+            payload,
+            output: Vec::new(),
+        })
+    }
+
+    /// Writes back the header and the output payload if the buffer is large enough.
+    fn write_to_user(mut self, addr: Vaddr) -> Result<()> {
+        let header = &mut self.header;
+        header.version = DM_VERSION;
+        let data_start = size_of::<DmIoctl>();
+        let mut flags = DmFlags::from_bits_truncate(header.flags);
+        if data_start + self.output.len() > header.data_size as usize {
+            flags |= DmFlags::BUFFER_FULL;
+        } else {
+            flags -= DmFlags::BUFFER_FULL;
+            header.data_start = data_start as u32;
+            header.data_size = (data_start + self.output.len()) as u32;
+            write_bytes_to_user(
+                addr + data_start,
+                &mut VmReader::from(self.output.as_slice()),
+            )?;
+        }
+        header.flags = (header.flags & !DmFlags::all().bits()) | flags.bits();
+        write_bytes_to_user(addr, &mut VmReader::from(header.as_bytes()))
+    }
+
+    fn flags(&self) -> DmFlags {
+        DmFlags::from_bits_truncate(self.header.flags)
+    }
+
+    /// Finds the device by the UUID, the name, or the device number in turn.
+    fn find_device(&self) -> Result<Arc<MappedDevice>> {
+        let uuid = c_str(&self.header.uuid)?;
+        let name = c_str(&self.header.name)?;
+        let devices = MAPPED_DEVICES.lock();
+        let device = if !uuid.is_empty() {
+            devices.iter().find(|device| device.uuid == uuid)
+        } else if !name.is_empty() {
+            devices.iter().find(|device| device.name == name)
+        } else {
+            let id = DeviceId::from(self.header.dev);
+            devices
+                .iter()
+                .find(|device| id.major() == DM_MAJOR && device.minor == id.minor())
+        };
+        device
+            .cloned()
+            .ok_or_else(|| Error::with_message(Errno::ENXIO, "the mapped device does not exist"))
+    }
+
+    /// Fills the status of the device in the header.
+    fn fill_status(&mut self, device: &MappedDevice) {
+        let mut flags = self.flags()
+            - (DmFlags::SUSPEND
+                | DmFlags::READONLY
+                | DmFlags::ACTIVE_PRESENT
+                | DmFlags::INACTIVE_PRESENT);
+        if device.is_suspended.load(Ordering::Relaxed) {
+            flags |= DmFlags::SUSPEND;
+        }
+        let has_active = device.active.read().is_some();
+        if has_active {
+            flags |= DmFlags::ACTIVE_PRESENT;
+        }
+        if device.inactive.read().is_some() {
+            flags |= DmFlags::INACTIVE_PRESENT;
+        }
+        self.header.flags = (self.header.flags & !DmFlags::all().bits()) | flags.bits();
+        self.header.dev = device.id().into();
+        self.header.target_count = has_active as u32;
+        self.header.open_count = 0;
+        self.header.event_nr = 0;
+        copy_c_str(&mut self.header.name, &device.name);
+        copy_c_str(&mut self.header.uuid, &device.uuid);
+    }
+
+    /// Lists the devices as `struct dm_name_list` in Linux, each of which is followed by
+    /// the name, and then the event number and the flags, which are zeroes.
+    fn list_devices(&mut self) {
+        let devices = MAPPED_DEVICES.lock();
+        if devices.is_empty() {
+            // An empty entry whose device number is zero.
+            self.output.resize(16, 0);
+            return;
+        }
+        for (index, device) in devices.iter().enumerate() {
+            let start = self.output.len();
+            let len = (12 + device.name.len() + 1).next_multiple_of(8) + 8;
+            self.output.resize(start + len, 0);
+            let entry = &mut self.output[start..];
+            entry[..8].copy_from_slice(&u64::from(device.id()).to_ne_bytes());
+            if index + 1 < devices.len() {
+                entry[8..12].copy_from_slice(&(len as u32).to_ne_bytes());
+            }
+            entry[12..12 + device.name.len()].copy_from_slice(device.name.as_bytes());
+        }
+    }
+
+    fn create_device(&mut self) -> Result<()> {
+        let name = c_str(&self.header.name)?.to_string();
+        let uuid = c_str(&self.header.uuid)?.to_string();
+        let minor = self
+            .flags()
+            .contains(DmFlags::PERSISTENT_DEV)
+            .then(|| DeviceId::from(self.header.dev).minor());
+        let device = MappedDevice::create(&name, &uuid, minor)?;
+        self.fill_status(&device);
+        Ok(())
+    }
+
+    fn suspend_device(&mut self) -> Result<()> {
+        let device = self.find_device()?;
+        if self.flags().contains(DmFlags::SUSPEND) {
+            device.is_suspended.store(true, Ordering::Relaxed);
+        } else {
+            device.resume()?;
+        }
+        self.fill_status(&device);
+        Ok(())
+    }
+
+    fn load_table(&mut self) -> Result<()> {
+        let device = self.find_device()?;
+        if self.header.target_count != 1 {
+            return_errno_with_message!(Errno::EINVAL, "only one target is supported");
+        }
+        let spec_size = size_of::<DmTargetSpec>();
+        if self.payload.len() < spec_size {
+            return_errno_with_message!(Errno::EINVAL, "the target is truncated");
+        }
+        let spec = DmTargetSpec::from_bytes(&self.payload[..spec_size]);
+        let target_type = c_str(&spec.target_type)?;
+        let params = c_str(&self.payload[spec_size..])?;
+
+        let fs_resolver = current!().fs().read().clone();
+        let target = Target::new(
+            spec.sector_start,
+            spec.length,
+            target_type,
+            params,
+            &fs_resolver,
+        )?;
+        *device.inactive.write() = Some(target);
+        self.fill_status(&device);
+        Ok(())
+    }
+
+    /// Returns the active table if `STATUS_TABLE` is set, or the status of the targets otherwise.
+    fn table_status(&mut self) -> Result<()> {
+        let device = self.find_device()?;
+        self.fill_status(&device);
+        let Some(target) = device.active.read().clone() else {
+            return Ok(());
+        };
+
+        let params = if self.flags().contains(DmFlags::STATUS_TABLE) {
+            target.params.as_str()
+        } else {
+            // The crypt target reports nothing as its status.
+            ""
+        };
+        let spec_size = size_of::<DmTargetSpec>();
+        let len = (spec_size + params.len() + 1).next_multiple_of(8);
+        let mut target_type = [0u8; DM_MAX_TYPE_NAME];
+        copy_c_str(&mut target_type, "crypt");
+        let spec = DmTargetSpec {
+            sector_start: 0,
+            length: target.length,
+            status: 0,
+            next: len as u32,
+            target_type,
+        };
+        self.output.extend_from_slice(spec.as_bytes());
+        self.output.extend_from_slice(params.as_bytes());
+        self.output.resize(len, 0);
+        Ok(())
+    }
+
+    /// Lists the versions of the targets as `struct dm_target_versions` in Linux.
+    fn list_versions(&mut self) {
+        let name = "crypt";
+        self.output
+            .resize((16 + name.len() + 1).next_multiple_of(8), 0);
+        for (index, version) in CRYPT_TARGET_VERSION.iter().enumerate() {
+            let offset = 4 + index * 4;
+            self.output[offset..offset + 4].copy_from_slice(&version.to_ne_bytes());
+        }
+        self.output[16..16 + name.len()].copy_from_slice(name.as_bytes());
+    }
+}
+
+/// Returns the string before the first NUL byte.
+fn c_str(bytes: &[u8]) -> Result<&str> {
+    let len = bytes
+        .iter()
+        .position(|byte| *byte == 0)
+        .unwrap_or(bytes.len());
+    core::str::from_utf8(&bytes[..len])
+        .map_err(|_| Error::with_message(Errno::EINVAL, "the string is not valid UTF-8"))
+}
+
+/// Copies the string into the buffer as a C string, which is truncated if too long.
+fn copy_c_str(buf: &mut [u8], string: &str) {
+    let len = string.len().min(buf.len() - 1);
+    buf.fill(0);
+    buf[..len].copy_from_slice(&string.as_bytes()[..len]);
+}
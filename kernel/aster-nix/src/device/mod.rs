@@ -1,14 +1,23 @@
 // SPDX-License-Identifier: MPL-2.0
 
+pub mod block;
+mod fuse;
+mod loop_device;
+mod mapper;
 mod null;
 mod pty;
 mod random;
+pub mod registry;
 #[cfg(feature = "intel_tdx")]
 mod tdxguest;
 pub mod tty;
 mod urandom;
+mod whiteout;
 mod zero;
 
+pub use block::BlockDeviceNode;
+pub use fuse::FuseDevice;
+pub use loop_device::{LoopControl, LoopDevice};
 pub use pty::{new_pty_pair, PtyMaster, PtySlave};
 pub use random::Random;
 #[cfg(feature = "intel_tdx")]
@@ -16,57 +25,68 @@ use tdx_guest::tdx_is_enabled;
 #[cfg(feature = "intel_tdx")]
 pub use tdxguest::TdxGuest;
 pub use urandom::Urandom;
+pub use whiteout::Whiteout;
 
-use self::tty::get_n_tty;
+use self::{registry::register_device, tty::get_n_tty};
 use crate::{
-    fs::device::{add_node, Device, DeviceId, DeviceType},
+    fs::device::{Device, DeviceId, DeviceType},
     prelude::*,
 };
 
+/// The major of the memory devices, e.g., `/dev/null`.
+const MEM_MAJOR: u32 = 1;
+/// The major of `/dev/tty` and `/dev/console`.
+const TTYAUX_MAJOR: u32 = 5;
+/// The major of the miscellaneous devices, e.g., `/dev/fuse`.
+const MISC_MAJOR: u32 = 10;
+
 /// Init the device node in fs, must be called after mounting rootfs.
 pub fn init() -> Result<()> {
+    registry::register_major(DeviceType::CharDevice, Some(MEM_MAJOR), "mem")?;
+    registry::register_major(DeviceType::CharDevice, Some(TTYAUX_MAJOR), "tty")?;
+    registry::register_major(DeviceType::CharDevice, Some(MISC_MAJOR), "misc")?;
+    registry::register_major(
+        DeviceType::BlockDevice,
+        Some(block::BLOCK_EXT_MAJOR),
+        "blkext",
+    )?;
+
     let null = Arc::new(null::Null);
-    add_node(null, "null")?;
+    register_device(null, "null")?;
     let zero = Arc::new(zero::Zero);
-    add_node(zero, "zero")?;
+    register_device(zero, "zero")?;
     tty::init();
     let console = get_n_tty().clone();
-    add_node(console, "console")?;
+    register_device(console, "console")?;
     let tty = Arc::new(tty::TtyDevice);
-    add_node(tty, "tty")?;
+    register_device(tty, "tty")?;
     #[cfg(feature = "intel_tdx")]
     let tdx_guest = Arc::new(tdxguest::TdxGuest);
     #[cfg(feature = "intel_tdx")]
     if tdx_is_enabled() {
-        add_node(tdx_guest, "tdx_guest")?;
+        register_device(tdx_guest, "tdx_guest")?;
     }
     let random = Arc::new(random::Random);
-    add_node(random, "random")?;
+    register_device(random, "random")?;
     let urandom = Arc::new(urandom::Urandom);
-    add_node(urandom, "urandom")?;
+    register_device(urandom, "urandom")?;
+    let fuse = Arc::new(fuse::FuseDevice);
+    register_device(fuse, "fuse")?;
     pty::init()?;
+    loop_device::init()?;
+    mapper::init()?;
     Ok(())
 }
 
-// TODO: Implement a more scalable solution for ID-to-device mapping.
-// Instead of hardcoding every device numbers in this function,
-// a registration mechanism should be used to allow each driver to
-// allocate device IDs either statically or dynamically.
-pub fn get_device(dev: usize) -> Result<Arc<dyn Device>> {
-    if dev == 0 {
-        return_errno_with_message!(Errno::EPERM, "whiteout device")
+/// Returns the device of the type and the device number, e.g., for `mknod`.
+///
+/// The character device numbered 0 is the whiteout of overlayfs, and the others
+/// are looked up in the device registry.
+pub fn get_device(type_: DeviceType, dev: usize) -> Result<Arc<dyn Device>> {
+    if type_ != DeviceType::BlockDevice && dev == 0 {
+        return Ok(Arc::new(whiteout::Whiteout));
     }
 
-    let devid = DeviceId::from(dev as u64);
-    let major = devid.major();
-    let minor = devid.minor();
-
-    match (major, minor) {
-        (1, 3) => Ok(Arc::new(null::Null)),
-        (1, 5) => Ok(Arc::new(zero::Zero)),
-        (5, 0) => Ok(Arc::new(tty::TtyDevice)),
-        (1, 8) => Ok(Arc::new(random::Random)),
-        (1, 9) => Ok(Arc::new(urandom::Urandom)),
-        _ => return_errno_with_message!(Errno::EINVAL, "unsupported device"),
-    }
+    registry::lookup_device(type_, DeviceId::from(dev as u64))
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "unsupported device"))
 }
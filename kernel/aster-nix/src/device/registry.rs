@@ -0,0 +1,186 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The registry of the device numbers.
+//!
+//! A driver reserves a major number before registering its devices. The major is either
+//! a static one, which is fixed by convention, e.g., 7 for the loop devices, or a dynamic
+//! one, which is allocated from the unused majors. The character devices and the block
+//! devices have separate major numbers, so do their device numbers.
+//!
+//! A registered device gets a device node in the devtmpfs, and a uevent is sent so that
+//! a device manager in the user space, e.g., mdev, can react. The device can also be
+//! found by its device number, e.g., when a device node is created by `mknod`.
+
+use core::ops::RangeInclusive;
+
+use crate::{
+    fs::{
+        device::{add_node, delete_node, Device, DeviceId, DeviceType},
+        sysfs::uevent::{send_device_uevent, UeventAction},
+    },
+    prelude::*,
+};
+
+/// The majors that are allocated dynamically, which are allocated from the top like Linux.
+const DYNAMIC_MAJORS: RangeInclusive<u32> = 234..=254;
+
+static REGISTRY: Mutex<Registry> = Mutex::new(Registry {
+    majors: BTreeMap::new(),
+    devices: BTreeMap::new(),
+});
+
+struct Registry {
+    majors: BTreeMap<(Namespace, u32), &'static str>,
+    devices: BTreeMap<(Namespace, u32, u32), RegisteredDevice>,
+}
+
+/// The namespace of the device numbers.
+///
+/// The miscellaneous devices are character devices of the `misc` major.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Namespace {
+    Char,
+    Block,
+}
+
+impl From<DeviceType> for Namespace {
+    fn from(type_: DeviceType) -> Self {
+        match type_ {
+            DeviceType::CharDevice | DeviceType::MiscDevice => Namespace::Char,
+            DeviceType::BlockDevice => Namespace::Block,
+        }
+    }
+}
+
+/// A device in the registry.
+#[derive(Debug, Clone)]
+pub struct RegisteredDevice {
+    device: Arc<dyn Device>,
+    name: String,
+    major_name: &'static str,
+}
+
+impl RegisteredDevice {
+    pub fn device(&self) -> &Arc<dyn Device> {
+        &self.device
+    }
+
+    /// Returns the path of the device node relative to `/dev`, e.g., `mapper/control`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the name of the major, e.g., `mem` for `/dev/null`.
+    pub fn major_name(&self) -> &'static str {
+        self.major_name
+    }
+
+    pub fn is_block_device(&self) -> bool {
+        Namespace::from(self.device.type_()) == Namespace::Block
+    }
+}
+
+/// Reserves a major number for the devices of the type.
+///
+/// If `major` is `None`, an unused major is allocated. The name is shown in `/proc/devices`,
+/// and is the class of the character devices in the sysfs.
+pub fn register_major(type_: DeviceType, major: Option<u32>, name: &'static str) -> Result<u32> {
+    let namespace = Namespace::from(type_);
+    let mut registry = REGISTRY.lock();
+    let major = match major {
+        Some(major) if registry.majors.contains_key(&(namespace, major)) => {
+            return_errno_with_message!(Errno::EBUSY, "the major number is in use");
+        }
+        Some(major) => major,
+        None => DYNAMIC_MAJORS
+            .rev()
+            .find(|major| !registry.majors.contains_key(&(namespace, *major)))
+            .ok_or_else(|| Error::with_message(Errno::EBUSY, "no free major number"))?,
+    };
+    registry.majors.insert((namespace, major), name);
+    Ok(major)
+}
+
+/// Registers the device and creates its device node `/dev/<name>`.
+///
+/// The major of the device must have been reserved by `register_major`.
+pub fn register_device(device: Arc<dyn Device>, name: &str) -> Result<()> {
+    let namespace = Namespace::from(device.type_());
+    let id = device.id();
+    let registered = {
+        let mut registry = REGISTRY.lock();
+        let Some(major_name) = registry.majors.get(&(namespace, id.major())).copied() else {
+            return_errno_with_message!(Errno::ENXIO, "the major number is not registered");
+        };
+        let key = (namespace, id.major(), id.minor());
+        if registry.devices.contains_key(&key) {
+            return_errno_with_message!(Errno::EEXIST, "the device number is in use");
+        }
+        let registered = RegisteredDevice {
+            device: device.clone(),
+            name: String::from(name),
+            major_name,
+        };
+        registry.devices.insert(key, registered.clone());
+        registered
+    };
+
+    if let Err(e) = add_node(device, name) {
+        REGISTRY
+            .lock()
+            .devices
+            .remove(&(namespace, id.major(), id.minor()));
+        return Err(e);
+    }
+    send_device_uevent(UeventAction::Add, &registered);
+    Ok(())
+}
+
+/// Unregisters the device and deletes its device node.
+pub fn unregister_device(type_: DeviceType, id: DeviceId) -> Result<()> {
+    let key = (Namespace::from(type_), id.major(), id.minor());
+    let Some(registered) = REGISTRY.lock().devices.remove(&key) else {
+        return_errno_with_message!(Errno::ENXIO, "the device is not registered");
+    };
+
+    // The node may have been deleted by the user space.
+    if let Err(e) = delete_node(&registered.name) {
+        debug!("failed to delete /dev/{}: {:?}", registered.name, e);
+    }
+    send_device_uevent(UeventAction::Remove, &registered);
+    Ok(())
+}
+
+/// Returns the registered device of the type and the device number.
+pub fn lookup_device(type_: DeviceType, id: DeviceId) -> Option<Arc<dyn Device>> {
+    let key = (Namespace::from(type_), id.major(), id.minor());
+    REGISTRY
+        .lock()
+        .devices
+        .get(&key)
+        .map(|registered| registered.device.clone())
+}
+
+/// Returns the registered devices of the type, which are sorted by the device numbers.
+pub fn all_devices(type_: DeviceType) -> Vec<RegisteredDevice> {
+    let namespace = Namespace::from(type_);
+    REGISTRY
+        .lock()
+        .devices
+        .iter()
+        .filter(|((device_namespace, _, _), _)| *device_namespace == namespace)
+        .map(|(_, registered)| registered.clone())
+        .collect()
+}
+
+/// Returns the reserved majors of the type with their names, which are sorted by the majors.
+pub fn all_majors(type_: DeviceType) -> Vec<(u32, &'static str)> {
+    let namespace = Namespace::from(type_);
+    REGISTRY
+        .lock()
+        .majors
+        .iter()
+        .filter(|((major_namespace, _), _)| *major_namespace == namespace)
+        .map(|(&(_, major), &name)| (major, name))
+        .collect()
+}
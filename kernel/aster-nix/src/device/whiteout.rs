@@ -0,0 +1,38 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#![allow(unused_variables)]
+
+use super::*;
+use crate::{events::IoEvents, fs::inode_handle::FileIo, prelude::*, process::signal::Poller};
+
+/// The whiteout device.
+///
+/// A character device with the device number 0/0 is a whiteout, which hides
+/// a file of the same name in the lower layers of an overlay filesystem.
+/// It cannot be opened for I/O.
+pub struct Whiteout;
+
+impl Device for Whiteout {
+    fn type_(&self) -> DeviceType {
+        DeviceType::CharDevice
+    }
+
+    fn id(&self) -> DeviceId {
+        // Same value with Linux
+        DeviceId::new(0, 0)
+    }
+}
+
+impl FileIo for Whiteout {
+    fn read(&self, _buf: &mut [u8]) -> Result<usize> {
+        return_errno_with_message!(Errno::ENXIO, "whiteout device");
+    }
+
+    fn write(&self, _buf: &[u8]) -> Result<usize> {
+        return_errno_with_message!(Errno::ENXIO, "whiteout device");
+    }
+
+    fn poll(&self, mask: IoEvents, poller: Option<&Poller>) -> IoEvents {
+        IoEvents::empty()
+    }
+}
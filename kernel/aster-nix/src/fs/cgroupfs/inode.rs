@@ -0,0 +1,430 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::time::Duration;
+
+use super::{CgroupFs, BLOCK_SIZE};
+use crate::{
+    cgroup::{Cgroup, Controllers},
+    fs::utils::{DirentVisitor, FileSystem, Inode, InodeMode, InodeType, IoctlCmd, Metadata},
+    prelude::*,
+    process::{process_table, Gid, Uid},
+};
+
+/// An inode of the cgroup2, which is a cgroup or an interface file of it.
+///
+/// The inodes are created on lookup and are not cached, since the interface files
+/// come and go with the controllers. The inode number is derived from the ID of the
+/// cgroup, so that a cgroup keeps its inode number across the lookups.
+pub(super) struct CgroupInode {
+    kind: CgroupInodeKind,
+    metadata: Metadata,
+    fs: Weak<CgroupFs>,
+}
+
+enum CgroupInodeKind {
+    Dir(Arc<Cgroup>),
+    File(Arc<Cgroup>, InterfaceFile),
+}
+
+impl CgroupInode {
+    pub fn new_dir(cgroup: Arc<Cgroup>, fs: Weak<CgroupFs>) -> Arc<Self> {
+        let metadata = Metadata::new_dir(
+            cgroup.id() << 8,
+            InodeMode::from_bits_truncate(0o755),
+            BLOCK_SIZE,
+        );
+        Arc::new(Self {
+            kind: CgroupInodeKind::Dir(cgroup),
+            metadata,
+            fs,
+        })
+    }
+
+    fn new_file(cgroup: Arc<Cgroup>, file: InterfaceFile, fs: Weak<CgroupFs>) -> Arc<Self> {
+        let mode = if file.is_writable() { 0o644 } else { 0o444 };
+        let mut metadata = Metadata::new_file(
+            (cgroup.id() << 8) | (file as u64 + 1),
+            InodeMode::from_bits_truncate(mode),
+            BLOCK_SIZE,
+        );
+        // Like the sysfs, the size is reported as a page since the content is generated.
+        metadata.size = PAGE_SIZE;
+        Arc::new(Self {
+            kind: CgroupInodeKind::File(cgroup, file),
+            metadata,
+            fs,
+        })
+    }
+
+    fn cgroup(&self) -> Result<&Arc<Cgroup>> {
+        match &self.kind {
+            CgroupInodeKind::Dir(cgroup) => Ok(cgroup),
+            CgroupInodeKind::File(..) => return_errno!(Errno::ENOTDIR),
+        }
+    }
+
+    fn file(&self) -> Result<(&Arc<Cgroup>, InterfaceFile)> {
+        match &self.kind {
+            CgroupInodeKind::File(cgroup, file) => Ok((cgroup, *file)),
+            CgroupInodeKind::Dir(_) => return_errno!(Errno::EISDIR),
+        }
+    }
+
+    /// Returns the child inodes of the directory, with their names.
+    fn children(&self) -> Result<Vec<(String, Arc<dyn Inode>)>> {
+        let cgroup = self.cgroup()?;
+        let mut children: Vec<(String, Arc<dyn Inode>)> = Vec::new();
+        for file in InterfaceFile::ALL {
+            if file.exists_in(cgroup) {
+                let inode = Self::new_file(cgroup.clone(), file, self.fs.clone());
+                children.push((String::from(file.name()), inode));
+            }
+        }
+        for child in cgroup.children() {
+            let name = String::from(child.name());
+            children.push((name, Self::new_dir(child, self.fs.clone())));
+        }
+        Ok(children)
+    }
+}
+
+impl Inode for CgroupInode {
+    fn size(&self) -> usize {
+        self.metadata.size
+    }
+
+    fn resize(&self, _new_size: usize) -> Result<()> {
+        // Truncating an interface file, e.g., by `echo > cgroup.procs`, is a no-op.
+        self.file().map(|_| ())
+    }
+
+    fn metadata(&self) -> Metadata {
+        self.metadata
+    }
+
+    fn ino(&self) -> u64 {
+        self.metadata.ino
+    }
+
+    fn type_(&self) -> InodeType {
+        self.metadata.type_
+    }
+
+    fn mode(&self) -> Result<InodeMode> {
+        Ok(self.metadata.mode)
+    }
+
+    fn set_mode(&self, _mode: InodeMode) -> Result<()> {
+        return_errno_with_message!(Errno::EPERM, "the mode of cgroup inodes cannot be changed")
+    }
+
+    fn owner(&self) -> Result<Uid> {
+        Ok(self.metadata.uid)
+    }
+
+    fn set_owner(&self, _uid: Uid) -> Result<()> {
+        return_errno_with_message!(Errno::EPERM, "the owner of cgroup inodes cannot be changed")
+    }
+
+    fn group(&self) -> Result<Gid> {
+        Ok(self.metadata.gid)
+    }
+
+    fn set_group(&self, _gid: Gid) -> Result<()> {
+        return_errno_with_message!(Errno::EPERM, "the group of cgroup inodes cannot be changed")
+    }
+
+    fn atime(&self) -> Duration {
+        self.metadata.atime
+    }
+
+    fn set_atime(&self, _time: Duration) {}
+
+    fn mtime(&self) -> Duration {
+        self.metadata.mtime
+    }
+
+    fn set_mtime(&self, _time: Duration) {}
+
+    fn ctime(&self) -> Duration {
+        self.metadata.ctime
+    }
+
+    fn set_ctime(&self, _time: Duration) {}
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        let (cgroup, file) = self.file()?;
+        let content = file.show(cgroup)?;
+        let data = content.as_bytes();
+        let start = data.len().min(offset);
+        let end = data.len().min(offset + buf.len());
+        let len = end - start;
+        buf[0..len].copy_from_slice(&data[start..end]);
+        Ok(len)
+    }
+
+    fn read_direct_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        self.read_at(offset, buf)
+    }
+
+    fn write_at(&self, _offset: usize, buf: &[u8]) -> Result<usize> {
+        let (cgroup, file) = self.file()?;
+        let content = core::str::from_utf8(buf)
+            .map_err(|_| Error::with_message(Errno::EINVAL, "the content is not UTF-8"))?;
+        file.store(cgroup, content)?;
+        Ok(buf.len())
+    }
+
+    fn write_direct_at(&self, offset: usize, buf: &[u8]) -> Result<usize> {
+        self.write_at(offset, buf)
+    }
+
+    fn create(&self, name: &str, type_: InodeType, _mode: InodeMode) -> Result<Arc<dyn Inode>> {
+        let cgroup = self.cgroup()?;
+        if type_ != InodeType::Dir {
+            return_errno_with_message!(Errno::EPERM, "only cgroups can be created");
+        }
+        if InterfaceFile::ALL.iter().any(|file| file.name() == name) {
+            return_errno_with_message!(Errno::EEXIST, "the interface file exists");
+        }
+        let child = cgroup.create_child(name)?;
+        Ok(Self::new_dir(child, self.fs.clone()))
+    }
+
+    fn rmdir(&self, name: &str) -> Result<()> {
+        let cgroup = self.cgroup()?;
+        if cgroup.child(name).is_none() {
+            return_errno_with_message!(Errno::ENOTDIR, "not a cgroup");
+        }
+        cgroup.remove_child(name)
+    }
+
+    fn unlink(&self, _name: &str) -> Result<()> {
+        return_errno_with_message!(Errno::EPERM, "the interface files cannot be removed")
+    }
+
+    fn readdir_at(&self, offset: usize, visitor: &mut dyn DirentVisitor) -> Result<usize> {
+        let cgroup = self.cgroup()?;
+        let try_readdir = |offset: &mut usize, visitor: &mut dyn DirentVisitor| -> Result<()> {
+            // Read the two special entries.
+            if *offset == 0 {
+                visitor.visit(".", self.ino(), InodeType::Dir, *offset)?;
+                *offset += 1;
+            }
+            if *offset == 1 {
+                let parent_ino = cgroup
+                    .parent()
+                    .map(|parent| parent.id() << 8)
+                    .unwrap_or(self.ino());
+                visitor.visit("..", parent_ino, InodeType::Dir, *offset)?;
+                *offset += 1;
+            }
+
+            // Read the normal child entries.
+            let start_offset = *offset;
+            for (idx, (name, child)) in self
+                .children()?
+                .into_iter()
+                .enumerate()
+                .map(|(idx, child)| (idx + 2, child))
+                .skip_while(|(idx, _)| idx < &start_offset)
+            {
+                visitor.visit(&name, child.ino(), child.type_(), idx)?;
+                *offset = idx + 1;
+            }
+            Ok(())
+        };
+
+        let mut iterate_offset = offset;
+        match try_readdir(&mut iterate_offset, visitor) {
+            Err(e) if iterate_offset == offset => Err(e),
+            _ => Ok(iterate_offset - offset),
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>> {
+        let cgroup = self.cgroup()?;
+        let inode: Arc<dyn Inode> = match name {
+            "." => Self::new_dir(cgroup.clone(), self.fs.clone()),
+            ".." => {
+                let parent = cgroup.parent().unwrap_or(cgroup).clone();
+                Self::new_dir(parent, self.fs.clone())
+            }
+            name => self
+                .children()?
+                .into_iter()
+                .find(|(child_name, _)| child_name == name)
+                .map(|(_, child)| child)
+                .ok_or_else(|| Error::new(Errno::ENOENT))?,
+        };
+        Ok(inode)
+    }
+
+    fn ioctl(&self, _cmd: IoctlCmd, _arg: usize) -> Result<i32> {
+        return_errno!(Errno::ENOTTY)
+    }
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        self.fs.upgrade().unwrap()
+    }
+
+    fn is_dentry_cacheable(&self) -> bool {
+        false
+    }
+}
+
+/// An interface file of a cgroup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InterfaceFile {
+    Procs,
+    Controllers,
+    SubtreeControl,
+    CpuStat,
+    CpuWeight,
+    CpuMax,
+    MemoryCurrent,
+    MemoryMax,
+    MemoryEvents,
+    PidsCurrent,
+    PidsMax,
+}
+
+impl InterfaceFile {
+    const ALL: [Self; 11] = [
+        Self::Procs,
+        Self::Controllers,
+        Self::SubtreeControl,
+        Self::CpuStat,
+        Self::CpuWeight,
+        Self::CpuMax,
+        Self::MemoryCurrent,
+        Self::MemoryMax,
+        Self::MemoryEvents,
+        Self::PidsCurrent,
+        Self::PidsMax,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Procs => "cgroup.procs",
+            Self::Controllers => "cgroup.controllers",
+            Self::SubtreeControl => "cgroup.subtree_control",
+            Self::CpuStat => "cpu.stat",
+            Self::CpuWeight => "cpu.weight",
+            Self::CpuMax => "cpu.max",
+            Self::MemoryCurrent => "memory.current",
+            Self::MemoryMax => "memory.max",
+            Self::MemoryEvents => "memory.events",
+            Self::PidsCurrent => "pids.current",
+            Self::PidsMax => "pids.max",
+        }
+    }
+
+    /// Returns the controller of the file, or `None` if the file exists in every cgroup.
+    fn controller(self) -> Option<Controllers> {
+        match self {
+            Self::Procs | Self::Controllers | Self::SubtreeControl | Self::CpuStat => None,
+            Self::CpuWeight | Self::CpuMax => Some(Controllers::CPU),
+            Self::MemoryCurrent | Self::MemoryMax | Self::MemoryEvents => Some(Controllers::MEMORY),
+            Self::PidsCurrent | Self::PidsMax => Some(Controllers::PIDS),
+        }
+    }
+
+    fn exists_in(self, cgroup: &Cgroup) -> bool {
+        match self.controller() {
+            None => true,
+            Some(controller) => !cgroup.is_root() && cgroup.controllers().contains(controller),
+        }
+    }
+
+    fn is_writable(self) -> bool {
+        matches!(
+            self,
+            Self::Procs
+                | Self::SubtreeControl
+                | Self::CpuWeight
+                | Self::CpuMax
+                | Self::MemoryMax
+                | Self::PidsMax
+        )
+    }
+
+    /// Returns the content of the file, which ends with a newline.
+    fn show(self, cgroup: &Cgroup) -> Result<String> {
+        let mut content = match self {
+            Self::Procs => cgroup
+                .processes()
+                .iter()
+                .map(|process| process.pid().to_string())
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Self::Controllers => cgroup.controllers().names(),
+            Self::SubtreeControl => cgroup.subtree_control().names(),
+            Self::CpuStat => cgroup.cpu().show_stat(),
+            Self::CpuWeight => cgroup.cpu().show_weight(),
+            Self::CpuMax => cgroup.cpu().show_max(),
+            Self::MemoryCurrent => cgroup.memory().show_current(),
+            Self::MemoryMax => cgroup.memory().show_max(),
+            Self::MemoryEvents => cgroup.memory().show_events(),
+            Self::PidsCurrent => cgroup.pids().current().to_string(),
+            Self::PidsMax => cgroup.pids().show_max(),
+        };
+        if !content.is_empty() && !content.ends_with('\n') {
+            content.push('\n');
+        }
+        Ok(content)
+    }
+
+    /// Stores the content written to the file.
+    fn store(self, cgroup: &Arc<Cgroup>, content: &str) -> Result<()> {
+        match self {
+            Self::Procs => store_procs(cgroup, content),
+            Self::SubtreeControl => store_subtree_control(cgroup, content),
+            Self::CpuWeight => cgroup.cpu().store_weight(content),
+            Self::CpuMax => cgroup.cpu().store_max(content),
+            Self::MemoryMax => cgroup.memory().store_max(content),
+            Self::PidsMax => cgroup.pids().store_max(content),
+            _ => return_errno_with_message!(Errno::EACCES, "the file is read-only"),
+        }
+    }
+}
+
+/// Moves the processes of the PIDs, one per line, into the cgroup.
+///
+/// The PID 0 means the current process.
+fn store_procs(cgroup: &Arc<Cgroup>, content: &str) -> Result<()> {
+    for pid in content.split_whitespace() {
+        let pid = pid
+            .parse::<u32>()
+            .map_err(|_| Error::with_message(Errno::EINVAL, "invalid PID"))?;
+        let process = if pid == 0 {
+            current!()
+        } else {
+            process_table::get_process(pid)
+                .ok_or_else(|| Error::with_message(Errno::ESRCH, "the process does not exist"))?
+        };
+        cgroup.attach(&process)?;
+    }
+    Ok(())
+}
+
+/// Enables and disables the controllers for the children, e.g., by `+cpu -memory`.
+fn store_subtree_control(cgroup: &Cgroup, content: &str) -> Result<()> {
+    let mut enable = Controllers::empty();
+    let mut disable = Controllers::empty();
+    for token in content.split_whitespace() {
+        let (controllers, name) = match token.split_at(1) {
+            ("+", name) => (&mut enable, name),
+            ("-", name) => (&mut disable, name),
+            _ => return_errno_with_message!(Errno::EINVAL, "invalid controller operation"),
+        };
+        let controller = Controllers::from_name(name)
+            .ok_or_else(|| Error::with_message(Errno::EINVAL, "unknown controller"))?;
+        *controllers |= controller;
+    }
+    // Enabling and disabling the same controller at once is ambiguous.
+    if enable.intersects(disable) {
+        return_errno_with_message!(Errno::EINVAL, "the controller is enabled and disabled");
+    }
+    cgroup.update_subtree_control(enable, disable)
+}
@@ -0,0 +1,76 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The cgroup2 file system, which exposes the hierarchy of the cgroups.
+//!
+//! Each cgroup is a directory, which is created by `mkdir` and removed by `rmdir`.
+//! The regular files in the directory are the interface files of the cgroup:
+//!
+//! - `cgroup.procs` lists the processes in the cgroup, and a process is moved into
+//!   the cgroup by writing its PID;
+//! - `cgroup.controllers` lists the controllers available to the cgroup;
+//! - `cgroup.subtree_control` lists the controllers enabled for the children, which
+//!   are enabled or disabled by writing e.g. `+cpu -memory`;
+//! - `pids.*`, `memory.*` and `cpu.*` are the files of the controllers, which exist
+//!   only if the controllers are available to a non-root cgroup, except `cpu.stat`.
+//!
+//! Like Linux, there is only one hierarchy, so every mount of the cgroup2 shows the
+//! same cgroups. It is mounted at `/sys/fs/cgroup` when the system boots.
+
+use spin::Once;
+
+use self::inode::CgroupInode;
+use crate::{
+    cgroup,
+    fs::utils::{FileSystem, FsFlags, Inode, SuperBlock, NAME_MAX},
+    prelude::*,
+};
+
+mod inode;
+
+/// Magic number.
+const CGROUP2_MAGIC: u64 = 0x63677270;
+/// Block size.
+const BLOCK_SIZE: usize = PAGE_SIZE;
+
+static CGROUPFS: Once<Arc<CgroupFs>> = Once::new();
+
+/// Returns the cgroup2 file system.
+pub fn cgroupfs() -> Arc<dyn FileSystem> {
+    CGROUPFS.call_once(CgroupFs::new).clone()
+}
+
+pub struct CgroupFs {
+    sb: SuperBlock,
+    root: Arc<dyn Inode>,
+}
+
+impl CgroupFs {
+    fn new() -> Arc<Self> {
+        Arc::new_cyclic(|weak_fs| Self {
+            sb: SuperBlock::new(CGROUP2_MAGIC, BLOCK_SIZE, NAME_MAX),
+            root: CgroupInode::new_dir(cgroup::root().clone(), weak_fs.clone()),
+        })
+    }
+}
+
+impl FileSystem for CgroupFs {
+    fn sync(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn root_inode(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+
+    fn sb(&self) -> SuperBlock {
+        self.sb.clone()
+    }
+
+    fn flags(&self) -> FsFlags {
+        FsFlags::empty()
+    }
+
+    fn name(&self) -> &'static str {
+        "cgroup2"
+    }
+}
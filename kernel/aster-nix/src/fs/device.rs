@@ -3,7 +3,7 @@
 use super::inode_handle::FileIo;
 use crate::{
     fs::{
-        fs_resolver::{FsPath, FsResolver},
+        devtmpfs,
         path::Dentry,
         utils::{InodeMode, InodeType},
     },
@@ -22,6 +22,11 @@ pub trait Device: Sync + Send + FileIo {
     fn open(&self) -> Result<Option<Arc<dyn FileIo>>> {
         Ok(None)
     }
+
+    /// Write back the data cached for the device.
+    fn sync(&self) -> Result<()> {
+        Ok(())
+    }
 }
 
 impl Debug for dyn Device {
@@ -33,7 +38,7 @@ impl Debug for dyn Device {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 /// Device type
 pub enum DeviceType {
     CharDevice,
@@ -87,15 +92,13 @@ impl From<u64> for DeviceId {
     }
 }
 
-/// Add a device node to FS for the device.
+/// Add a device node to the devtmpfs for the device.
 ///
+/// The path is relative to the root of the devtmpfs, i.e., `/dev`.
 /// If the parent path is not existing, `mkdir -p` the parent path.
 /// This function is used in registering device.
 pub fn add_node(device: Arc<dyn Device>, path: &str) -> Result<Arc<Dentry>> {
-    let mut dentry = {
-        let fs_resolver = FsResolver::new();
-        fs_resolver.lookup(&FsPath::try_from("/dev").unwrap())?
-    };
+    let mut dentry = devtmpfs::root()?;
     let mut relative_path = {
         let relative_path = path.trim_start_matches('/');
         if relative_path.is_empty() {
@@ -143,23 +146,24 @@ pub fn add_node(device: Arc<dyn Device>, path: &str) -> Result<Arc<Dentry>> {
     Ok(dentry)
 }
 
-/// Delete the device node from FS for the device.
+/// Delete the device node from the devtmpfs for the device.
 ///
 /// This function is used in unregistering device.
 pub fn delete_node(path: &str) -> Result<()> {
-    let abs_path = {
-        let device_path = path.trim_start_matches('/');
-        if device_path.is_empty() {
-            return_errno_with_message!(Errno::EINVAL, "invalid device path");
-        }
-        String::from("/dev") + "/" + device_path
-    };
+    let device_path = path.trim_start_matches('/');
+    if device_path.is_empty() {
+        return_errno_with_message!(Errno::EINVAL, "invalid device path");
+    }
 
-    let (parent_dentry, name) = {
-        let fs_resolver = FsResolver::new();
-        fs_resolver.lookup_dir_and_base_name(&FsPath::try_from(abs_path.as_str()).unwrap())?
+    let (parent_path, name) = match device_path.rsplit_once('/') {
+        Some((parent_path, name)) => (parent_path, name),
+        None => ("", device_path),
     };
+    let mut parent_dentry = devtmpfs::root()?;
+    for dir_name in parent_path.split('/').filter(|name| !name.is_empty()) {
+        parent_dentry = parent_dentry.lookup(dir_name)?;
+    }
 
-    parent_dentry.unlink(&name)?;
+    parent_dentry.unlink(name)?;
     Ok(())
 }
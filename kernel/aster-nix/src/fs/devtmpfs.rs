@@ -0,0 +1,42 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The devtmpfs, which is mounted at `/dev` and populated by the kernel.
+//!
+//! The device nodes are created when the devices are registered, and removed when
+//! they are unregistered, see `crate::device::registry`. The devtmpfs is backed by
+//! RamFS, so the user space can create its own files in it as well, e.g., the symbolic
+//! links made by a device manager.
+//!
+//! Like Linux, there is only one devtmpfs. Mounting it again, e.g., by
+//! `mount -t devtmpfs devtmpfs /dev`, shows the same device nodes.
+
+use spin::Once;
+
+use super::{path::Dentry, ramfs::RamFS, utils::FileSystem};
+use crate::prelude::*;
+
+static DEVTMPFS: Once<Arc<RamFS>> = Once::new();
+
+/// The root of the devtmpfs in its first mount, through which the device nodes are
+/// added and deleted.
+static DEVTMPFS_ROOT: Once<Arc<Dentry>> = Once::new();
+
+/// Returns the devtmpfs.
+pub fn devtmpfs() -> Arc<dyn FileSystem> {
+    DEVTMPFS.call_once(RamFS::new_devtmpfs).clone()
+}
+
+/// Mounts the devtmpfs at `/dev`.
+pub(super) fn init(dev_dentry: &Arc<Dentry>) -> Result<()> {
+    let mount_node = dev_dentry.mount(devtmpfs())?;
+    DEVTMPFS_ROOT.call_once(|| Dentry::new_fs_root(mount_node));
+    Ok(())
+}
+
+/// Returns the root of the devtmpfs.
+pub(super) fn root() -> Result<Arc<Dentry>> {
+    DEVTMPFS_ROOT
+        .get()
+        .cloned()
+        .ok_or_else(|| Error::with_message(Errno::ENODEV, "the devtmpfs is not mounted"))
+}
@@ -0,0 +1,91 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::mem::size_of;
+
+use super::NAME_MAX;
+use crate::{fs::utils::InodeType, prelude::*};
+
+/// An on-disk directory entry.
+///
+/// A directory block starts with the entries, which are followed by their names
+/// in the same order. The names are not terminated, except that the last one may
+/// be padded with zeros.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct RawDirent {
+    nid: u64,
+    /// The offset of the name in the block
+    nameoff: u16,
+    file_type: u8,
+    reserved: u8,
+}
+
+const DIRENT_SIZE: usize = size_of::<RawDirent>();
+
+#[derive(Debug)]
+pub(super) struct DirEntry {
+    pub name: String,
+    pub nid: u64,
+    pub type_: InodeType,
+}
+
+/// Parses the entries in a directory block, which may be shorter than a full block
+/// at the end of the directory.
+pub(super) fn parse_dir_block(block: &[u8], entries: &mut Vec<DirEntry>) -> Result<()> {
+    let nameoff = |i: usize| {
+        let raw = RawDirent::from_bytes(&block[i * DIRENT_SIZE..(i + 1) * DIRENT_SIZE]);
+        raw.nameoff as usize
+    };
+    if block.len() < DIRENT_SIZE {
+        return_errno_with_message!(Errno::EIO, "too short directory block");
+    }
+    let names_start = nameoff(0);
+    if names_start < DIRENT_SIZE || names_start % DIRENT_SIZE != 0 || names_start > block.len() {
+        return_errno_with_message!(Errno::EIO, "bogus name offset in directory");
+    }
+
+    let count = names_start / DIRENT_SIZE;
+    for i in 0..count {
+        let raw = RawDirent::from_bytes(&block[i * DIRENT_SIZE..(i + 1) * DIRENT_SIZE]);
+        let start = raw.nameoff as usize;
+        let end = if i + 1 < count {
+            nameoff(i + 1)
+        } else {
+            block.len()
+        };
+        if start >= end || end > block.len() {
+            return_errno_with_message!(Errno::EIO, "bogus name offset in directory");
+        }
+        let mut name = &block[start..end];
+        if i + 1 == count
+            && let Some(len) = name.iter().position(|byte| *byte == 0)
+        {
+            name = &name[..len];
+        }
+        if name.is_empty() || name.len() > NAME_MAX {
+            return_errno_with_message!(Errno::EIO, "bogus name length in directory");
+        }
+
+        entries.push(DirEntry {
+            name: String::from_utf8(name.to_vec())
+                .map_err(|_| Error::with_message(Errno::EIO, "invalid name in directory"))?,
+            nid: raw.nid,
+            type_: file_type(raw.file_type)?,
+        });
+    }
+    Ok(())
+}
+
+fn file_type(raw: u8) -> Result<InodeType> {
+    let type_ = match raw {
+        1 => InodeType::File,
+        2 => InodeType::Dir,
+        3 => InodeType::CharDevice,
+        4 => InodeType::BlockDevice,
+        5 => InodeType::NamedPipe,
+        6 => InodeType::Socket,
+        7 => InodeType::SymLink,
+        _ => return_errno_with_message!(Errno::EIO, "unknown file type in directory"),
+    };
+    Ok(type_)
+}
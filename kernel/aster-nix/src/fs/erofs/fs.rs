@@ -0,0 +1,105 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::time::Duration;
+
+use aster_block::BlockDevice;
+use spin::Once;
+
+use super::{
+    inode::ErofsInode,
+    super_block::{SuperBlock, EROFS_MAGIC},
+    NAME_MAX,
+};
+use crate::{
+    fs::utils::{FileSystem, FsFlags, Inode, SuperBlock as VfsSuperBlock},
+    prelude::*,
+};
+
+/// The size of the slots locating the inodes in the metadata.
+const INODE_SLOT_SIZE: usize = 32;
+/// The flag of `statfs` for a read-only file system.
+const ST_RDONLY: u64 = 1;
+
+/// EROFS, the enhanced read-only file system.
+pub struct ErofsFS {
+    block_device: Arc<dyn BlockDevice>,
+    super_block: SuperBlock,
+    root: Once<Arc<ErofsInode>>,
+}
+
+impl ErofsFS {
+    /// Opens the EROFS image on the block device.
+    pub fn open(block_device: Arc<dyn BlockDevice>) -> Result<Arc<Self>> {
+        let super_block = SuperBlock::read(block_device.as_ref())?;
+        let erofs = Arc::new(Self {
+            block_device,
+            super_block,
+            root: Once::new(),
+        });
+
+        let root = ErofsInode::read(&erofs, super_block.root_nid as u64)?;
+        if !root.type_().is_directory() {
+            return_errno_with_message!(Errno::EINVAL, "the root inode is not a directory");
+        }
+        erofs.root.call_once(|| root);
+        Ok(erofs)
+    }
+
+    /// Reads the bytes at `pos` of the image, which must be within the image.
+    pub(super) fn read_bytes(&self, pos: usize, buf: &mut [u8]) -> Result<()> {
+        let image_size = (self.super_block.blocks as usize) << self.super_block.blkszbits;
+        if pos.saturating_add(buf.len()) > image_size {
+            return_errno_with_message!(Errno::EIO, "read beyond the image");
+        }
+        self.block_device.read_bytes_unaligned(pos, buf)?;
+        Ok(())
+    }
+
+    /// Returns the position of the inode with `nid` in the image.
+    pub(super) fn inode_pos(&self, nid: u64) -> usize {
+        ((self.super_block.meta_blkaddr as usize) << self.super_block.blkszbits)
+            + nid as usize * INODE_SLOT_SIZE
+    }
+
+    /// Returns the build time, which is the modification time of the compact inodes.
+    pub(super) fn build_time(&self) -> Duration {
+        Duration::new(
+            self.super_block.build_time,
+            self.super_block.build_time_nsec,
+        )
+    }
+
+    pub(super) fn block_size(&self) -> usize {
+        self.super_block.block_size()
+    }
+
+    pub(super) fn block_bits(&self) -> u8 {
+        self.super_block.blkszbits
+    }
+}
+
+impl FileSystem for ErofsFS {
+    fn sync(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn root_inode(&self) -> Arc<dyn Inode> {
+        self.root.get().unwrap().clone()
+    }
+
+    fn sb(&self) -> VfsSuperBlock {
+        let mut sb = VfsSuperBlock::new(EROFS_MAGIC as u64, self.block_size(), NAME_MAX);
+        sb.blocks = self.super_block.blocks as usize;
+        sb.files = self.super_block.inos as usize;
+        sb.flags = ST_RDONLY;
+        sb
+    }
+
+    fn flags(&self) -> FsFlags {
+        FsFlags::empty()
+    }
+
+    fn name(&self) -> &'static str {
+        "erofs"
+    }
+}
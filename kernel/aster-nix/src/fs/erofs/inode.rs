@@ -0,0 +1,531 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::{mem::size_of, time::Duration};
+
+use align_ext::AlignExt;
+use aster_block::bio::BioWaiter;
+use aster_rights::Full;
+use ostd::mm::{Frame, VmIo};
+
+use super::{
+    dir::{parse_dir_block, DirEntry},
+    fs::ErofsFS,
+    NAME_MAX,
+};
+use crate::{
+    fs::{
+        device::Device,
+        utils::{
+            DirentVisitor, FileSystem, Inode, InodeMode, InodeType, IoctlCmd, Metadata, PageCache,
+            PageCacheBackend,
+        },
+    },
+    prelude::*,
+    process::{Gid, Uid},
+    vm::vmo::Vmo,
+};
+
+/// If set in the format of an inode, the inode is in the extended form.
+const FORMAT_EXTENDED: u16 = 1 << 0;
+/// The bits of the format that are known.
+const FORMAT_ALL: u16 = 0xF;
+
+const LAYOUT_FLAT_PLAIN: u16 = 0;
+const LAYOUT_COMPRESSED_FULL: u16 = 1;
+const LAYOUT_FLAT_INLINE: u16 = 2;
+const LAYOUT_COMPRESSED_COMPACT: u16 = 3;
+const LAYOUT_CHUNK_BASED: u16 = 4;
+
+/// The bits of the chunk format giving the chunk size in blocks.
+const CHUNK_BLKBITS_MASK: u32 = 0x1F;
+/// If set in the chunk format, the chunks are located by 8-byte indexes instead of
+/// 4-byte block addresses.
+const CHUNK_INDEXES: u32 = 0x20;
+/// The block address of the holes in the chunk-based files.
+const NULL_ADDR: u32 = u32::MAX;
+
+/// The size of the header of the inline xattrs, which is followed by the entries
+/// of 4-byte slots.
+const XATTR_IBODY_HEADER_SIZE: usize = 12;
+const XATTR_SLOT_SIZE: usize = 4;
+
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct CompactInode {
+    format: u16,
+    xattr_icount: u16,
+    mode: u16,
+    nlink: u16,
+    size: u32,
+    reserved: u32,
+    /// The start block, the device number or the chunk format
+    i_u: u32,
+    ino: u32,
+    uid: u16,
+    gid: u16,
+    reserved2: u32,
+}
+
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct ExtendedInode {
+    format: u16,
+    xattr_icount: u16,
+    mode: u16,
+    reserved: u16,
+    size: u64,
+    /// The start block, the device number or the chunk format
+    i_u: u32,
+    ino: u32,
+    uid: u32,
+    gid: u32,
+    mtime: u64,
+    mtime_nsec: u32,
+    nlink: u32,
+    reserved2: [u8; 16],
+}
+
+/// The layout of the data of an uncompressed inode.
+#[derive(Clone, Copy, Debug)]
+enum DataLayout {
+    /// The data are contiguous from `start`.
+    ///
+    /// The device files, the named pipes and the sockets have no data at all.
+    FlatPlain { start: usize },
+    /// The data are contiguous from `start`, except that the last block is inline
+    /// after the inode.
+    FlatInline { start: usize },
+    /// The data are split into chunks of `1 << chunk_bits` bytes, which are located
+    /// by the indexes after the inode.
+    ChunkBased { chunk_bits: u32, has_indexes: bool },
+}
+
+/// An inode of EROFS, whose inode number is the nid.
+#[derive(Debug)]
+pub struct ErofsInode {
+    nid: u64,
+    type_: InodeType,
+    mode: InodeMode,
+    uid: u32,
+    gid: u32,
+    mtime: Duration,
+    nlinks: usize,
+    size: usize,
+    rdev: u32,
+    layout: DataLayout,
+    /// The position following the inode and its xattrs, where the inline data or
+    /// the chunk indexes are
+    inline_pos: usize,
+    /// The cache of the contents, which only exists for files
+    page_cache: Option<PageCache>,
+    fs: Weak<ErofsFS>,
+}
+
+impl ErofsInode {
+    /// Reads the inode with `nid`.
+    pub(super) fn read(fs: &Arc<ErofsFS>, nid: u64) -> Result<Arc<Self>> {
+        let pos = fs.inode_pos(nid);
+        let mut buf = [0u8; size_of::<ExtendedInode>()];
+        let compact_size = size_of::<CompactInode>();
+        fs.read_bytes(pos, &mut buf[..compact_size])?;
+        let format = u16::from_le_bytes([buf[0], buf[1]]);
+        if format & !FORMAT_ALL != 0 {
+            return_errno_with_message!(Errno::EOPNOTSUPP, "unknown EROFS inode format");
+        }
+
+        let (inode_size, xattr_icount, mode, nlink, size, i_u, uid, gid, mtime) =
+            if format & FORMAT_EXTENDED != 0 {
+                fs.read_bytes(pos + compact_size, &mut buf[compact_size..])?;
+                let raw = ExtendedInode::from_bytes(&buf);
+                (
+                    size_of::<ExtendedInode>(),
+                    raw.xattr_icount,
+                    raw.mode,
+                    raw.nlink,
+                    raw.size,
+                    raw.i_u,
+                    raw.uid,
+                    raw.gid,
+                    Duration::new(raw.mtime, raw.mtime_nsec),
+                )
+            } else {
+                let raw = CompactInode::from_bytes(&buf[..compact_size]);
+                (
+                    compact_size,
+                    raw.xattr_icount,
+                    raw.mode,
+                    raw.nlink as u32,
+                    raw.size as u64,
+                    raw.i_u,
+                    raw.uid as u32,
+                    raw.gid as u32,
+                    fs.build_time(),
+                )
+            };
+
+        let type_ = InodeType::try_from(mode as u32 & 0o170000)
+            .map_err(|_| Error::with_message(Errno::EIO, "unknown EROFS file type"))?;
+        // The xattrs are not supported, but they are skipped to find the inline data.
+        let xattr_size = match xattr_icount as usize {
+            0 => 0,
+            count => XATTR_IBODY_HEADER_SIZE + (count - 1) * XATTR_SLOT_SIZE,
+        };
+        let inline_pos = pos + inode_size + xattr_size;
+
+        let (layout, rdev) = match type_ {
+            InodeType::CharDevice | InodeType::BlockDevice => {
+                (DataLayout::FlatPlain { start: 0 }, i_u)
+            }
+            InodeType::NamedPipe | InodeType::Socket => (DataLayout::FlatPlain { start: 0 }, 0),
+            _ => {
+                let start = (i_u as usize) << fs.block_bits();
+                let layout = match (format >> 1) & 0x7 {
+                    LAYOUT_FLAT_PLAIN => DataLayout::FlatPlain { start },
+                    LAYOUT_FLAT_INLINE => DataLayout::FlatInline { start },
+                    LAYOUT_CHUNK_BASED => {
+                        if i_u & !(CHUNK_BLKBITS_MASK | CHUNK_INDEXES) != 0 {
+                            return_errno_with_message!(Errno::EOPNOTSUPP, "unknown chunk format");
+                        }
+                        DataLayout::ChunkBased {
+                            chunk_bits: fs.block_bits() as u32 + (i_u & CHUNK_BLKBITS_MASK),
+                            has_indexes: i_u & CHUNK_INDEXES != 0,
+                        }
+                    }
+                    LAYOUT_COMPRESSED_FULL | LAYOUT_COMPRESSED_COMPACT => {
+                        return_errno_with_message!(
+                            Errno::EOPNOTSUPP,
+                            "compressed EROFS files are not supported"
+                        );
+                    }
+                    _ => return_errno_with_message!(Errno::EOPNOTSUPP, "unknown data layout"),
+                };
+                (layout, 0)
+            }
+        };
+
+        let size = size as usize;
+        Ok(Arc::new_cyclic(|weak_self| Self {
+            nid,
+            type_,
+            mode: InodeMode::from_bits_truncate(mode),
+            uid,
+            gid,
+            mtime,
+            nlinks: nlink as usize,
+            size,
+            rdev,
+            layout,
+            inline_pos,
+            page_cache: (type_ == InodeType::File)
+                .then(|| PageCache::with_capacity(size, weak_self.clone() as _).unwrap()),
+            fs: Arc::downgrade(fs),
+        }))
+    }
+
+    fn erofs(&self) -> Arc<ErofsFS> {
+        self.fs.upgrade().unwrap()
+    }
+
+    /// Maps `offset`, which must be less than the size, to the position in the image,
+    /// returning `None` for holes, along with the length of the contiguous data.
+    fn map(&self, fs: &ErofsFS, offset: usize) -> Result<(Option<usize>, usize)> {
+        let block_size = fs.block_size();
+        match self.layout {
+            DataLayout::FlatPlain { start } => Ok((Some(start + offset), self.size - offset)),
+            DataLayout::FlatInline { start } => {
+                // The last block is inline, even if it is a full block.
+                let tail_start = (self.size - 1).align_down(block_size);
+                if offset < tail_start {
+                    return Ok((Some(start + offset), tail_start - offset));
+                }
+                if self.inline_pos % block_size + (self.size - tail_start) > block_size {
+                    return_errno_with_message!(Errno::EIO, "the inline data cross a block");
+                }
+                Ok((
+                    Some(self.inline_pos + offset - tail_start),
+                    self.size - offset,
+                ))
+            }
+            DataLayout::ChunkBased {
+                chunk_bits,
+                has_indexes,
+            } => {
+                let chunk_idx = offset >> chunk_bits;
+                let offset_in_chunk = offset & ((1 << chunk_bits) - 1);
+                let len = ((1 << chunk_bits) - offset_in_chunk).min(self.size - offset);
+
+                let mut buf = [0u8; 4];
+                if has_indexes {
+                    // An index is `{ advise: u16, device_id: u16, blkaddr: u32 }`, where
+                    // the device is always the primary one.
+                    let index_pos = self.inline_pos.align_up(8) + chunk_idx * 8;
+                    fs.read_bytes(index_pos + 4, &mut buf)?;
+                } else {
+                    let index_pos = self.inline_pos.align_up(4) + chunk_idx * 4;
+                    fs.read_bytes(index_pos, &mut buf)?;
+                }
+                let blkaddr = u32::from_le_bytes(buf);
+                if blkaddr == NULL_ADDR {
+                    return Ok((None, len));
+                }
+                let pos = ((blkaddr as usize) << fs.block_bits()) + offset_in_chunk;
+                Ok((Some(pos), len))
+            }
+        }
+    }
+
+    /// Reads the data at `offset`, where `buf` must be within the size.
+    fn read_data(&self, fs: &ErofsFS, offset: usize, buf: &mut [u8]) -> Result<()> {
+        let mut done = 0;
+        while done < buf.len() {
+            let (pos, len) = self.map(fs, offset + done)?;
+            let len = len.min(buf.len() - done);
+            match pos {
+                Some(pos) => fs.read_bytes(pos, &mut buf[done..done + len])?,
+                None => buf[done..done + len].fill(0),
+            }
+            done += len;
+        }
+        Ok(())
+    }
+
+    fn dir_entries(&self, fs: &ErofsFS) -> Result<Vec<DirEntry>> {
+        if self.type_ != InodeType::Dir {
+            return_errno!(Errno::ENOTDIR);
+        }
+        let block_size = fs.block_size();
+        let mut block = vec![0u8; block_size];
+        let mut entries = Vec::new();
+        for offset in (0..self.size).step_by(block_size) {
+            let len = (self.size - offset).min(block_size);
+            self.read_data(fs, offset, &mut block[..len])?;
+            parse_dir_block(&block[..len], &mut entries)?;
+        }
+        Ok(entries)
+    }
+}
+
+impl PageCacheBackend for ErofsInode {
+    fn read_page(&self, idx: usize, frame: &Frame) -> Result<BioWaiter> {
+        let offset = idx * PAGE_SIZE;
+        let len = self.size.saturating_sub(offset).min(PAGE_SIZE);
+        let mut buf = vec![0u8; len];
+        self.read_data(&self.erofs(), offset, &mut buf)?;
+        frame.write_bytes(0, &buf)?;
+        frame.writer().skip(len).fill(0u8);
+        // The page is filled synchronously.
+        Ok(BioWaiter::new())
+    }
+
+    fn write_page(&self, _idx: usize, _frame: &Frame) -> Result<BioWaiter> {
+        return_errno_with_message!(Errno::EROFS, "EROFS is read-only")
+    }
+
+    fn npages(&self) -> usize {
+        self.size.div_ceil(PAGE_SIZE)
+    }
+}
+
+impl Inode for ErofsInode {
+    fn size(&self) -> usize {
+        self.size
+    }
+
+    fn resize(&self, _new_size: usize) -> Result<()> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn metadata(&self) -> Metadata {
+        let blk_size = self.erofs().block_size();
+        Metadata {
+            dev: 0,
+            ino: self.nid,
+            size: self.size,
+            blk_size,
+            blocks: self.size.div_ceil(blk_size),
+            atime: self.mtime,
+            mtime: self.mtime,
+            ctime: self.mtime,
+            type_: self.type_,
+            mode: self.mode,
+            nlinks: self.nlinks,
+            uid: Uid::new(self.uid),
+            gid: Gid::new(self.gid),
+            rdev: self.rdev as u64,
+        }
+    }
+
+    fn ino(&self) -> u64 {
+        self.nid
+    }
+
+    fn type_(&self) -> InodeType {
+        self.type_
+    }
+
+    fn mode(&self) -> Result<InodeMode> {
+        Ok(self.mode)
+    }
+
+    fn set_mode(&self, _mode: InodeMode) -> Result<()> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn owner(&self) -> Result<Uid> {
+        Ok(Uid::new(self.uid))
+    }
+
+    fn set_owner(&self, _uid: Uid) -> Result<()> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn group(&self) -> Result<Gid> {
+        Ok(Gid::new(self.gid))
+    }
+
+    fn set_group(&self, _gid: Gid) -> Result<()> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn atime(&self) -> Duration {
+        self.mtime
+    }
+
+    fn set_atime(&self, _time: Duration) {
+        // The access time is not recorded.
+    }
+
+    fn mtime(&self) -> Duration {
+        self.mtime
+    }
+
+    fn set_mtime(&self, _time: Duration) {}
+
+    fn ctime(&self) -> Duration {
+        self.mtime
+    }
+
+    fn set_ctime(&self, _time: Duration) {}
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        self.erofs()
+    }
+
+    fn page_cache(&self) -> Option<Vmo<Full>> {
+        self.page_cache
+            .as_ref()
+            .map(|page_cache| page_cache.pages().dup())
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        let Some(page_cache) = &self.page_cache else {
+            if self.type_ == InodeType::Dir {
+                return_errno!(Errno::EISDIR);
+            }
+            return_errno!(Errno::EINVAL);
+        };
+        let start = offset.min(self.size);
+        let end = offset.saturating_add(buf.len()).min(self.size);
+        page_cache
+            .pages()
+            .read_bytes(start, &mut buf[..end - start])?;
+        Ok(end - start)
+    }
+
+    fn read_direct_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        self.read_at(offset, buf)
+    }
+
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> Result<usize> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn write_direct_at(&self, _offset: usize, _buf: &[u8]) -> Result<usize> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn create(&self, _name: &str, _type_: InodeType, _mode: InodeMode) -> Result<Arc<dyn Inode>> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn mknod(
+        &self,
+        _name: &str,
+        _mode: InodeMode,
+        _dev: Arc<dyn Device>,
+    ) -> Result<Arc<dyn Inode>> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn as_device(&self) -> Option<Arc<dyn Device>> {
+        let type_ = self.type_.device_type()?;
+        crate::device::get_device(type_, self.rdev as usize).ok()
+    }
+
+    fn readdir_at(&self, offset: usize, visitor: &mut dyn DirentVisitor) -> Result<usize> {
+        // The `.` and `..` entries are stored in the directory.
+        let entries = self.dir_entries(&self.erofs())?;
+        let try_visit = |idx: &mut usize, visitor: &mut dyn DirentVisitor| -> Result<()> {
+            for (i, entry) in entries.iter().enumerate().skip(*idx) {
+                visitor.visit(&entry.name, entry.nid, entry.type_, i)?;
+                *idx = i + 1;
+            }
+            Ok(())
+        };
+
+        let mut iterate_idx = offset;
+        match try_visit(&mut iterate_idx, visitor) {
+            Err(e) if iterate_idx == offset => Err(e),
+            _ => Ok(iterate_idx - offset),
+        }
+    }
+
+    fn link(&self, _old: &Arc<dyn Inode>, _name: &str) -> Result<()> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn unlink(&self, _name: &str) -> Result<()> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn rmdir(&self, _name: &str) -> Result<()> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>> {
+        if name.len() > NAME_MAX {
+            return_errno!(Errno::ENAMETOOLONG);
+        }
+        let fs = self.erofs();
+        let entries = self.dir_entries(&fs)?;
+        let Some(entry) = entries.iter().find(|entry| entry.name == name) else {
+            return_errno!(Errno::ENOENT);
+        };
+        Ok(ErofsInode::read(&fs, entry.nid)?)
+    }
+
+    fn rename(&self, _old_name: &str, _target: &Arc<dyn Inode>, _new_name: &str) -> Result<()> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn read_link(&self) -> Result<String> {
+        if self.type_ != InodeType::SymLink {
+            return_errno_with_message!(Errno::EINVAL, "not a symlink");
+        }
+        if self.size > PAGE_SIZE {
+            return_errno_with_message!(Errno::EIO, "too long symlink target");
+        }
+        let mut buf = vec![0u8; self.size];
+        self.read_data(&self.erofs(), 0, &mut buf)?;
+        String::from_utf8(buf)
+            .map_err(|_| Error::with_message(Errno::EIO, "invalid symlink target"))
+    }
+
+    fn write_link(&self, _target: &str) -> Result<()> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn ioctl(&self, _cmd: IoctlCmd, _arg: usize) -> Result<i32> {
+        return_errno_with_message!(Errno::EINVAL, "unsupported operation")
+    }
+}
@@ -0,0 +1,21 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! EROFS, the enhanced read-only file system.
+//!
+//! The inodes are located by their nids, which are their offsets from the start of the
+//! metadata in 32-byte slots. The files with the plain, the inline-tail and the
+//! chunk-based layouts are supported, while the compressed files cannot be opened.
+//! The xattrs and the extra devices are not supported either.
+//!
+//! The data are read from the block device directly, and the file contents are cached
+//! in the page caches of the inodes.
+
+pub use fs::ErofsFS;
+pub use inode::ErofsInode;
+
+mod dir;
+mod fs;
+mod inode;
+mod super_block;
+
+const NAME_MAX: usize = 255;
@@ -0,0 +1,92 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use aster_block::{BlockDevice, SECTOR_SIZE};
+
+use crate::prelude::*;
+
+pub(super) const EROFS_MAGIC: u32 = 0xE0F5_E1E2;
+/// The offset of the super block in the image.
+pub(super) const SUPER_BLOCK_OFFSET: usize = 1024;
+const MIN_BLOCK_BITS: u8 = 9;
+const MAX_BLOCK_BITS: u8 = 12;
+
+/// The on-disk super block of EROFS.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Pod)]
+pub(super) struct SuperBlock {
+    pub magic: u32,
+    pub checksum: u32,
+    pub feature_compat: u32,
+    pub blkszbits: u8,
+    pub sb_extslots: u8,
+    pub root_nid: u16,
+    pub inos: u64,
+    pub build_time: u64,
+    pub build_time_nsec: u32,
+    pub blocks: u32,
+    /// The block address of the metadata, where the inodes are located by their nids
+    pub meta_blkaddr: u32,
+    pub xattr_blkaddr: u32,
+    pub uuid: [u8; 16],
+    pub volume_name: [u8; 16],
+    pub feature_incompat: u32,
+    pub available_compr_algs: u16,
+    pub extra_devices: u16,
+    pub devt_slotoff: u16,
+    pub dirblkbits: u8,
+    pub xattr_prefix_count: u8,
+    pub xattr_prefix_start: u32,
+    pub packed_nid: u64,
+    pub xattr_filter_reserved: u8,
+    pub reserved: [u8; 23],
+}
+
+bitflags! {
+    /// The incompatible features, which must be known to mount the image.
+    ///
+    /// The features only used by the compressed files or the xattrs are accepted, since
+    /// those are rejected or ignored later.
+    pub(super) struct IncompatFeatures: u32 {
+        const ZERO_PADDING = 1 << 0;
+        const COMPR_CFGS = 1 << 1;
+        const CHUNKED_FILE = 1 << 2;
+        const DEVICE_TABLE = 1 << 3;
+        const ZTAILPACKING = 1 << 4;
+        const FRAGMENTS = 1 << 5;
+        const XATTR_PREFIXES = 1 << 6;
+    }
+}
+
+impl SuperBlock {
+    /// Reads the super block and checks it against the device.
+    pub(super) fn read(block_device: &dyn BlockDevice) -> Result<Self> {
+        let mut buf = [0u8; SECTOR_SIZE];
+        block_device.read_bytes(SUPER_BLOCK_OFFSET, &mut buf)?;
+        let super_block = Self::from_bytes(&buf[..core::mem::size_of::<Self>()]);
+
+        if super_block.magic != EROFS_MAGIC {
+            return_errno_with_message!(Errno::EINVAL, "invalid EROFS magic");
+        }
+        if !(MIN_BLOCK_BITS..=MAX_BLOCK_BITS).contains(&super_block.blkszbits) {
+            return_errno_with_message!(Errno::EINVAL, "unsupported EROFS block size");
+        }
+        if super_block.dirblkbits != 0 {
+            return_errno_with_message!(Errno::EINVAL, "unsupported EROFS directory block size");
+        }
+        if IncompatFeatures::from_bits(super_block.feature_incompat).is_none() {
+            return_errno_with_message!(Errno::EOPNOTSUPP, "unknown EROFS features");
+        }
+        if super_block.extra_devices != 0 {
+            return_errno_with_message!(Errno::EOPNOTSUPP, "multi-device EROFS is not supported");
+        }
+        let image_size = (super_block.blocks as usize) << super_block.blkszbits;
+        if image_size > block_device.nr_sectors() * SECTOR_SIZE {
+            return_errno_with_message!(Errno::EINVAL, "the image is larger than the device");
+        }
+        Ok(super_block)
+    }
+
+    pub(super) fn block_size(&self) -> usize {
+        1 << self.blkszbits
+    }
+}
@@ -5,7 +5,11 @@
 
 use core::{num::NonZeroUsize, ops::Range, sync::atomic::AtomicU64};
 
-use aster_block::{bio::BioWaiter, id::BlockId, BlockDevice};
+use aster_block::{
+    bio::{BioStatus, BioWaiter},
+    id::BlockId,
+    BlockDevice,
+};
 use hashbrown::HashMap;
 use lru::LruCache;
 use ostd::mm::Frame;
@@ -304,6 +308,15 @@ impl ExfatFS {
         self.block_device.as_ref()
     }
 
+    /// Flushes the volatile write cache of the block device.
+    pub(super) fn flush_device(&self) -> Result<()> {
+        let status = self.block_device.flush_sync()?;
+        match status {
+            BioStatus::Complete => Ok(()),
+            err_status => Err(Error::from(err_status)),
+        }
+    }
+
     pub(super) fn super_block(&self) -> ExfatSuperBlock {
         self.super_block
     }
@@ -395,6 +408,7 @@ impl FileSystem for ExfatFS {
             inode.sync_all()?;
         }
         self.meta_cache.evict_range(0..self.fs_size())?;
+        self.flush_device()?;
         Ok(())
     }
 
@@ -409,6 +423,10 @@ impl FileSystem for ExfatFS {
     fn flags(&self) -> FsFlags {
         FsFlags::DENTRY_UNEVICTABLE
     }
+
+    fn name(&self) -> &'static str {
+        "exfat"
+    }
 }
 
 #[derive(Clone, Debug, Default)]
@@ -1685,6 +1685,7 @@ impl Inode for ExfatInode {
         let fs = inner.fs();
         let fs_guard = fs.lock();
         inner.sync_all(&fs_guard)?;
+        fs.flush_device()?;
 
         Ok(())
     }
@@ -1694,6 +1695,7 @@ impl Inode for ExfatInode {
         let fs = inner.fs();
         let fs_guard = fs.lock();
         inner.sync_data(&fs_guard)?;
+        fs.flush_device()?;
 
         Ok(())
     }
@@ -12,6 +12,9 @@ mod utils;
 
 pub use fs::{ExfatFS, ExfatMountOptions};
 pub use inode::ExfatInode;
+// The attributes and the timestamps are shared with vfat.
+pub(super) use inode::FatAttr;
+pub(super) use utils::DosTimestamp;
 
 #[cfg(ktest)]
 mod test {
@@ -102,6 +105,10 @@ mod test {
         fn max_nr_segments_per_bio(&self) -> usize {
             usize::MAX
         }
+
+        fn nr_sectors(&self) -> usize {
+            self.sectors_count()
+        }
     }
     /// Exfat disk image
     static EXFAT_IMAGE: &[u8] = include_bytes!("../../../../../test/build/exfat.img");
@@ -48,11 +48,11 @@ const EXFAT_TIME_ZONE_VALID: u8 = 1 << 7;
 #[derive(Default, Debug, Clone, Copy)]
 pub struct DosTimestamp {
     // Timestamp at the precesion of double seconds.
-    pub(super) time: u16,
-    pub(super) date: u16,
+    pub(in crate::fs) time: u16,
+    pub(in crate::fs) date: u16,
     // Precise time in 10ms.
-    pub(super) increament_10ms: u8,
-    pub(super) utc_offset: u8,
+    pub(in crate::fs) increament_10ms: u8,
+    pub(in crate::fs) utc_offset: u8,
 }
 
 impl DosTimestamp {
@@ -172,14 +172,14 @@ impl<'a> DirEntryReader<'a> {
     }
 
     /// Reads one `DirEntry` from the current offset.
+    ///
+    /// The unused entries, whose inode number is zero, are skipped.
     pub fn read_entry(&mut self) -> Result<DirEntry> {
+        self.skip_unused_entries()?;
         let header = self
             .page_cache
             .pages()
             .read_val::<DirEntryHeader>(self.offset)?;
-        if header.ino == 0 {
-            return_errno!(Errno::ENOENT);
-        }
 
         let mut name = vec![0u8; header.name_len as _];
         self.page_cache
@@ -193,12 +193,30 @@ impl<'a> DirEntryReader<'a> {
 
         Ok(entry)
     }
+
+    /// Moves the current offset to the next used entry.
+    fn skip_unused_entries(&mut self) -> Result<()> {
+        loop {
+            let header = self
+                .page_cache
+                .pages()
+                .read_val::<DirEntryHeader>(self.offset)?;
+            if header.ino != 0 {
+                return Ok(());
+            }
+            if header.record_len == 0 {
+                return_errno_with_message!(Errno::EIO, "invalid record length");
+            }
+            self.offset += header.record_len as usize;
+        }
+    }
 }
 
 impl<'a> Iterator for DirEntryReader<'a> {
     type Item = (usize, DirEntry);
 
     fn next(&mut self) -> Option<Self::Item> {
+        self.skip_unused_entries().ok()?;
         let offset = self.offset;
         let entry = match self.read_entry() {
             Ok(entry) => entry,
@@ -239,66 +257,179 @@ impl<'a> DirEntryWriter<'a> {
         Ok(())
     }
 
+    /// Writes an unused entry of `record_len` at the current offset.
+    pub fn write_unused_entry(&mut self, record_len: usize) -> Result<()> {
+        let header = DirEntryHeader {
+            ino: 0,
+            record_len: record_len as u16,
+            name_len: 0,
+            file_type: DirEntryFileType::Unknown as _,
+        };
+        self.page_cache.pages().write_val(self.offset, &header)?;
+        self.offset += record_len;
+        Ok(())
+    }
+
+    /// Writes the `entries` compactly into the block starting from the current offset.
+    ///
+    /// The last entry is extended to the end of the block. If there are no entries,
+    /// the block is filled with an unused entry.
+    pub fn write_block_entries(&mut self, entries: Vec<DirEntry>) -> Result<()> {
+        debug_assert_eq!(self.offset % BLOCK_SIZE, 0);
+
+        let block_end = self.offset + BLOCK_SIZE;
+        let entries_count = entries.len();
+        if entries_count == 0 {
+            return self.write_unused_entry(BLOCK_SIZE);
+        }
+        for (idx, mut entry) in entries.into_iter().enumerate() {
+            if idx == entries_count - 1 {
+                entry.set_record_len(block_end - self.offset);
+            } else {
+                entry.set_record_len(entry.actual_len());
+            }
+            self.write_entry(&entry)?;
+        }
+        Ok(())
+    }
+
+    /// Inserts a new `DirEntry` into the block which contains the current offset.
+    ///
+    /// The new entry is written into an unused entry or into the gap of an existing entry.
+    /// Returns `false` if there is no available space in the block.
+    pub fn insert_entry_in_block(&mut self, new_entry: &DirEntry) -> Result<bool> {
+        let block_start = self.offset.align_down(BLOCK_SIZE);
+        let block_end = block_start + BLOCK_SIZE;
+        let required_len = new_entry.actual_len();
+
+        let mut offset = block_start;
+        while offset < block_end {
+            let mut header = self.page_cache.pages().read_val::<DirEntryHeader>(offset)?;
+            let record_len = header.record_len as usize;
+            if record_len == 0 {
+                return_errno_with_message!(Errno::EIO, "invalid record length");
+            }
+
+            let mut new_entry = new_entry.clone();
+            if header.ino == 0 && record_len >= required_len {
+                // Write in the unused entry.
+                new_entry.set_record_len(record_len);
+                self.offset = offset;
+                self.write_entry(&new_entry)?;
+                return Ok(true);
+            }
+
+            let actual_len = (DirEntry::header_len() + header.name_len as usize).align_up(4);
+            if header.ino != 0 && record_len - actual_len >= required_len {
+                // Write in the gap of the existing entry.
+                header.record_len = actual_len as u16;
+                self.page_cache.pages().write_val(offset, &header)?;
+                new_entry.set_record_len(record_len - actual_len);
+                self.offset = offset + actual_len;
+                self.write_entry(&new_entry)?;
+                return Ok(true);
+            }
+
+            offset += record_len;
+        }
+
+        Ok(false)
+    }
+
     /// Appends a new `DirEntry` starting from the current offset.
     ///
-    /// If there is a gap between existing entries, inserts the new entry into the gap；
+    /// If there is available space in the existing blocks, inserts the new entry into it;
     /// If there is no available space, expands the size and appends the new entry at the end.
     pub fn append_entry(&mut self, mut new_entry: DirEntry) -> Result<()> {
-        let Some((offset, mut entry)) = DirEntryReader::new(self.page_cache, self.offset)
-            .find(|(_, entry)| entry.gap_len() >= new_entry.record_len())
-        else {
-            // Resize and append it at the new block.
-            let old_size = self.page_cache.pages().size();
-            let new_size = old_size + BLOCK_SIZE;
-            self.page_cache.pages().resize(new_size)?;
-            new_entry.set_record_len(BLOCK_SIZE);
-            self.offset = old_size;
-            self.write_entry(&new_entry)?;
-            return Ok(());
-        };
+        let size = self.page_cache.pages().size();
+        let mut block_start = self.offset.align_down(BLOCK_SIZE);
+        while block_start < size {
+            self.offset = block_start;
+            if self.insert_entry_in_block(&new_entry)? {
+                return Ok(());
+            }
+            block_start += BLOCK_SIZE;
+        }
 
-        // Write in the gap between existing entries.
-        new_entry.set_record_len(entry.gap_len());
-        entry.set_record_len(entry.actual_len());
-        self.offset = offset;
-        self.write_entry(&entry)?;
+        // Resize and append it at the new block.
+        let new_size = size + BLOCK_SIZE;
+        self.page_cache.pages().resize(new_size)?;
+        new_entry.set_record_len(BLOCK_SIZE);
+        self.offset = size;
         self.write_entry(&new_entry)?;
         Ok(())
     }
 
     /// Removes and returns an existing `DirEntry` indicated by `name`.
+    ///
+    /// The space of the removed entry is merged into the previous entry in the same block.
+    /// If there is no previous entry, the removed entry is marked as unused instead.
     pub fn remove_entry(&mut self, name: &str) -> Result<DirEntry> {
-        let self_entry_record_len = DirEntry::self_entry(0).record_len();
-        let reader = DirEntryReader::new(self.page_cache, 0);
-        let next_reader = DirEntryReader::new(self.page_cache, self_entry_record_len);
-        let Some(((pre_offset, mut pre_entry), (offset, entry))) = reader
-            .zip(next_reader)
-            .find(|((offset, _), (_, dir_entry))| dir_entry.name() == name)
+        let Some((offset, entry)) = DirEntryReader::new(self.page_cache, self.offset)
+            .find(|(_, entry)| entry.name() == name)
         else {
             return_errno!(Errno::ENOENT);
         };
 
-        if DirEntryReader::new(self.page_cache, offset)
-            .next()
-            .is_none()
-            && Bid::from_offset(pre_offset) != Bid::from_offset(offset)
-        {
-            // Shrink the size.
-            let new_size = pre_offset.align_up(BLOCK_SIZE);
-            self.page_cache.pages().resize(new_size)?;
-            pre_entry.set_record_len(new_size - pre_offset);
-            self.offset = pre_offset;
-            self.write_entry(&pre_entry)?;
-        } else {
-            // Update the previous entry.
-            pre_entry.set_record_len(pre_entry.record_len() + entry.record_len());
-            self.offset = pre_offset;
-            self.write_entry(&pre_entry)?;
+        let pre_offset = {
+            let mut pre_offset = None;
+            let mut current_offset = offset.align_down(BLOCK_SIZE);
+            while current_offset < offset {
+                let header = self
+                    .page_cache
+                    .pages()
+                    .read_val::<DirEntryHeader>(current_offset)?;
+                if header.record_len == 0 {
+                    return_errno_with_message!(Errno::EIO, "invalid record length");
+                }
+                pre_offset = Some(current_offset);
+                current_offset += header.record_len as usize;
+            }
+            pre_offset
+        };
+
+        match pre_offset {
+            Some(pre_offset) => {
+                // Update the previous entry.
+                let mut pre_header = self
+                    .page_cache
+                    .pages()
+                    .read_val::<DirEntryHeader>(pre_offset)?;
+                pre_header.record_len += entry.record_len() as u16;
+                self.page_cache.pages().write_val(pre_offset, &pre_header)?;
+            }
+            None => {
+                self.offset = offset;
+                self.write_unused_entry(entry.record_len())?;
+            }
         }
 
         Ok(entry)
     }
 
+    /// Shrinks the size by removing the trailing blocks that contain no entries.
+    ///
+    /// The first block is always kept.
+    pub fn shrink_unused_blocks(&mut self) -> Result<()> {
+        let mut size = self.page_cache.pages().size();
+        while size > BLOCK_SIZE {
+            let last_block_start = size - BLOCK_SIZE;
+            let header = self
+                .page_cache
+                .pages()
+                .read_val::<DirEntryHeader>(last_block_start)?;
+            if header.ino != 0 || header.record_len as usize != BLOCK_SIZE {
+                break;
+            }
+            size = last_block_start;
+        }
+
+        if size != self.page_cache.pages().size() {
+            self.page_cache.pages().resize(size)?;
+        }
+        Ok(())
+    }
+
     /// Renames the `DirEntry` from `old_name` to the `new_name` from the current offset.
     ///
     /// It will moves the `DirEntry` to another position,
@@ -316,6 +447,7 @@ impl<'a> DirEntryWriter<'a> {
             self.write_entry(&new_entry)?;
         } else {
             // Move to another position.
+            self.offset = offset;
             self.remove_entry(old_name)?;
             self.offset = 0;
             self.append_entry(new_entry)?;
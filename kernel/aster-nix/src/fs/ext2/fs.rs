@@ -24,12 +24,38 @@ pub struct Ext2 {
     inode_size: usize,
     block_size: usize,
     group_descriptors_segment: Segment,
+    options: Ext2MountOptions,
     self_ref: Weak<Self>,
 }
 
+/// The mount options of an Ext2.
+#[derive(Clone, Debug, Default)]
+pub struct Ext2MountOptions {
+    /// Whether to discard the blocks once they are freed
+    pub discard: bool,
+}
+
+impl Ext2MountOptions {
+    /// Parses the comma-separated options from the `data` argument of `mount`.
+    pub fn parse(data: &str) -> Result<Self> {
+        let mut options = Self::default();
+        for option in data.split(',').filter(|option| !option.is_empty()) {
+            match option {
+                "discard" => options.discard = true,
+                "nodiscard" => options.discard = false,
+                _ => return_errno_with_message!(Errno::EINVAL, "unknown ext2 option"),
+            }
+        }
+        Ok(options)
+    }
+}
+
 impl Ext2 {
     /// Opens and loads an Ext2 from the `block_device`.
-    pub fn open(block_device: Arc<dyn BlockDevice>) -> Result<Arc<Self>> {
+    pub fn open(
+        block_device: Arc<dyn BlockDevice>,
+        options: Ext2MountOptions,
+    ) -> Result<Arc<Self>> {
         // Load the superblock
         // TODO: if the main superblock is corrupted, should we load the backup?
         let super_block = {
@@ -88,6 +114,7 @@ impl Ext2 {
             block_device,
             super_block: RwMutex::new(Dirty::new(super_block)),
             group_descriptors_segment,
+            options,
             self_ref: weak_ref.clone(),
         });
         Ok(ext2)
@@ -272,6 +299,12 @@ impl Ext2 {
 
     /// Frees a range of blocks.
     pub(super) fn free_blocks(&self, range: Range<Ext2Bid>) -> Result<()> {
+        // Discards the blocks before they are visible to the allocator,
+        // so that the discard never races with the writes to the reallocated blocks.
+        if self.options.discard {
+            self.discard_blocks(range.clone());
+        }
+
         let mut current_range = range.clone();
         while !current_range.is_empty() {
             let (_, block_group) = self.block_group_of_bid(current_range.start)?;
@@ -348,6 +381,28 @@ impl Ext2 {
         Ok(waiter)
     }
 
+    /// Discards a range of blocks, which tells the block device that
+    /// the blocks are no longer in use.
+    ///
+    /// This is only done with the `discard` mount option, since it waits for the device.
+    ///
+    /// The discard is only a hint, so the failures are ignored.
+    fn discard_blocks(&self, range: Range<Ext2Bid>) {
+        let _ = self
+            .block_device
+            .discard_blocks_sync(Bid::new(range.start as u64)..Bid::new(range.end as u64));
+    }
+
+    /// Flushes the volatile write cache of the block device,
+    /// which makes the completed writes durable.
+    pub(super) fn flush_device(&self) -> Result<()> {
+        let status = self.block_device.flush_sync()?;
+        match status {
+            BioStatus::Complete => Ok(()),
+            err_status => Err(Error::from(err_status)),
+        }
+    }
+
     /// Writes back the metadata to the block device.
     pub fn sync_metadata(&self) -> Result<()> {
         // If the superblock is clean, the block groups must be clean.
@@ -438,3 +493,117 @@ impl Ext2 {
         bid % self.blocks_per_group
     }
 }
+
+#[cfg(ktest)]
+mod test {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use aster_block::{
+        bio::{BioEnqueueError, BioType, SubmittedBio},
+        SECTOR_SIZE,
+    };
+    use ostd::prelude::*;
+
+    use super::*;
+
+    /// A block device in memory, which counts the discarded sectors.
+    #[derive(Debug)]
+    struct MemoryDisk {
+        segment: Segment,
+        nr_discarded_sectors: AtomicUsize,
+    }
+
+    impl BlockDevice for MemoryDisk {
+        fn enqueue(&self, bio: SubmittedBio) -> core::result::Result<(), BioEnqueueError> {
+            let mut cur_device_ofs = bio.sid_range().start.to_offset();
+            match bio.type_() {
+                BioType::Read => {
+                    for seg in bio.segments() {
+                        let mut reader = self.segment.reader().skip(cur_device_ofs);
+                        cur_device_ofs += seg.writer().write(&mut reader);
+                    }
+                }
+                BioType::Write => {
+                    for seg in bio.segments() {
+                        let mut writer = self.segment.writer().skip(cur_device_ofs);
+                        cur_device_ofs += writer.write(&mut seg.reader());
+                    }
+                }
+                BioType::Discard => {
+                    let nr_sectors = bio.sid_range().end.to_raw() - bio.sid_range().start.to_raw();
+                    self.nr_discarded_sectors
+                        .fetch_add(nr_sectors as usize, Ordering::Relaxed);
+                }
+                _ => (),
+            }
+            bio.complete(BioStatus::Complete);
+            Ok(())
+        }
+
+        fn max_nr_segments_per_bio(&self) -> usize {
+            usize::MAX
+        }
+
+        fn nr_sectors(&self) -> usize {
+            self.segment.nbytes() / SECTOR_SIZE
+        }
+    }
+
+    /// A small ext2 disk image
+    static EXT2_IMAGE: &[u8] = include_bytes!("../../../../../test/build/ext2_small.img");
+
+    fn open_ext2(options: &str) -> (Arc<Ext2>, Arc<MemoryDisk>) {
+        crate::time::clocks::init_for_ktest();
+        let segment = FrameAllocOptions::new(EXT2_IMAGE.len() / BLOCK_SIZE)
+            .is_contiguous(true)
+            .uninit(true)
+            .alloc_contiguous()
+            .unwrap();
+        segment.write_bytes(0, EXT2_IMAGE).unwrap();
+        let disk = Arc::new(MemoryDisk {
+            segment,
+            nr_discarded_sectors: AtomicUsize::new(0),
+        });
+        let ext2 = Ext2::open(disk.clone(), Ext2MountOptions::parse(options).unwrap()).unwrap();
+        (ext2, disk)
+    }
+
+    /// Writes a file of four blocks and then truncates it, which frees the blocks.
+    fn write_and_truncate(ext2: &Ext2) {
+        let root = ext2.root_inode().unwrap();
+        let file = root
+            .create("a", FileType::File, FilePerm::from_bits_truncate(0o644))
+            .unwrap();
+        file.write_at(0, &[1u8; 4 * BLOCK_SIZE]).unwrap();
+        file.resize(0).unwrap();
+    }
+
+    #[ktest]
+    fn parse_options() {
+        assert!(!Ext2MountOptions::parse("").unwrap().discard);
+        assert!(Ext2MountOptions::parse("discard").unwrap().discard);
+        assert!(
+            !Ext2MountOptions::parse("discard,nodiscard")
+                .unwrap()
+                .discard
+        );
+        assert_eq!(
+            Ext2MountOptions::parse("discard,foo").unwrap_err().error(),
+            Errno::EINVAL
+        );
+    }
+
+    #[ktest]
+    fn discard_freed_blocks_with_option() {
+        let (ext2, disk) = open_ext2("discard");
+        write_and_truncate(&ext2);
+        assert!(disk.nr_discarded_sectors.load(Ordering::Relaxed) >= 4 * BLOCK_SIZE / SECTOR_SIZE);
+    }
+
+    #[ktest]
+    fn keep_freed_blocks_without_option() {
+        let (ext2, disk) = open_ext2("");
+        write_and_truncate(&ext2);
+        assert_eq!(disk.nr_discarded_sectors.load(Ordering::Relaxed), 0);
+    }
+}
@@ -0,0 +1,826 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The hashed directory index (htree) of Ext2.
+//!
+//! A directory with the `FileFlags::INDEX_DIR` flag keeps a shallow B-tree keyed by
+//! the hash of the entry names, so that an entry can be found without scanning the
+//! whole directory. The layout is compatible with the `DIR_INDEX` feature of Linux.
+//!
+//! The first block of an indexed directory is the root of the tree. It starts with
+//! the "." and ".." entries, where the ".." entry covers the rest of the block, so
+//! that the index is invisible to a linear reader. The index data follows the ".."
+//! entry: a `DxRootInfo` and an array of `DxEntry`s. The interior nodes (if any)
+//! occupy blocks that start with an unused entry covering the whole block, followed
+//! by an array of `DxEntry`s. The leaves are ordinary directory blocks.
+//!
+//! The first `DxEntry` of every node does not store a hash, but the `DxCountLimit`
+//! of the node. Its block covers all the hashes smaller than that of the second entry.
+
+use super::{
+    dir::{DirEntry, DirEntryReader, DirEntryWriter},
+    prelude::*,
+    super_block::{SuperBlock, SuperBlockFlags},
+};
+
+/// The maximum number of the indirect levels of the index.
+///
+/// Zero means that the root points to the leaves directly.
+const MAX_INDIRECT_LEVELS: u8 = 1;
+
+/// The offset of the `DxRootInfo` in the root block,
+/// which is right after the "." entry and the name of the ".." entry.
+const DX_ROOT_INFO_OFFSET: usize = 24;
+
+/// The offset of the entries in an interior node block,
+/// which is right after the header of the unused entry.
+const DX_NODE_ENTRIES_OFFSET: usize = 8;
+
+/// The record length of the "." entry in the root block.
+const DOT_RECORD_LEN: usize = 12;
+
+/// The hash algorithms of the directory index.
+///
+/// The unsigned variants are never stored on disk. They are selected at runtime
+/// if the superblock is flagged with `SuperBlockFlags::UNSIGNED_HASH`.
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, TryFromInt)]
+pub enum DxHashVersion {
+    Legacy = 0,
+    HalfMd4 = 1,
+    Tea = 2,
+    LegacyUnsigned = 3,
+    HalfMd4Unsigned = 4,
+    TeaUnsigned = 5,
+}
+
+impl DxHashVersion {
+    /// Returns the variant that treats the name as unsigned characters.
+    fn to_unsigned(self) -> Self {
+        match self {
+            Self::Legacy => Self::LegacyUnsigned,
+            Self::HalfMd4 => Self::HalfMd4Unsigned,
+            Self::Tea => Self::TeaUnsigned,
+            unsigned => unsigned,
+        }
+    }
+
+    /// Returns the variant stored on disk.
+    fn to_signed(self) -> Self {
+        match self {
+            Self::LegacyUnsigned => Self::Legacy,
+            Self::HalfMd4Unsigned => Self::HalfMd4,
+            Self::TeaUnsigned => Self::Tea,
+            signed => signed,
+        }
+    }
+
+    fn is_unsigned(&self) -> bool {
+        matches!(
+            self,
+            Self::LegacyUnsigned | Self::HalfMd4Unsigned | Self::TeaUnsigned
+        )
+    }
+}
+
+/// The parameters of the hash function, which are shared by all the directories.
+#[derive(Clone, Copy, Debug)]
+pub(super) struct DxHashInfo {
+    seed: [u32; 4],
+    default_version: DxHashVersion,
+    is_unsigned: bool,
+}
+
+impl DxHashInfo {
+    pub fn new(super_block: &SuperBlock) -> Self {
+        Self {
+            seed: super_block.hash_seed(),
+            default_version: super_block.def_hash_version(),
+            is_unsigned: super_block.flags().contains(SuperBlockFlags::UNSIGNED_HASH),
+        }
+    }
+
+    /// Resolves the on-disk hash version to the one used for computing.
+    fn resolve(&self, version: DxHashVersion) -> DxHashVersion {
+        if self.is_unsigned {
+            version.to_unsigned()
+        } else {
+            version
+        }
+    }
+}
+
+/// The hashed index of a directory, which is stored in the page cache of the directory.
+pub(super) struct HTree<'a> {
+    page_cache: &'a PageCache,
+    hash_version: DxHashVersion,
+    seed: [u32; 4],
+    indirect_levels: u8,
+}
+
+impl<'a> HTree<'a> {
+    /// Opens the index of a directory whose first block is the root of the tree.
+    pub fn open(page_cache: &'a PageCache, hash_info: &DxHashInfo) -> Result<Self> {
+        let root_info = page_cache
+            .pages()
+            .read_val::<DxRootInfo>(DX_ROOT_INFO_OFFSET)?;
+        if root_info.reserved_zero != 0
+            || root_info.info_length as usize != core::mem::size_of::<DxRootInfo>()
+        {
+            return_errno_with_message!(Errno::EIO, "invalid dx root info");
+        }
+        if root_info.indirect_levels > MAX_INDIRECT_LEVELS {
+            return_errno_with_message!(Errno::EIO, "unsupported dx indirect levels");
+        }
+        let hash_version = DxHashVersion::try_from(root_info.hash_version)
+            .map_err(|_| Error::with_message(Errno::EIO, "invalid dx hash version"))?;
+
+        let htree = Self {
+            page_cache,
+            hash_version: hash_info.resolve(hash_version),
+            seed: hash_info.seed,
+            indirect_levels: root_info.indirect_levels,
+        };
+        let root = htree.read_node(0)?;
+        if root.limit != DxNode::root_limit() {
+            return_errno_with_message!(Errno::EIO, "invalid dx root limit");
+        }
+        Ok(htree)
+    }
+
+    /// Converts a linear directory of one block into an indexed directory.
+    ///
+    /// The entries except "." and ".." are moved to a new leaf block,
+    /// and the first block is rewritten as the root of the tree.
+    pub fn create(page_cache: &'a PageCache, hash_info: &DxHashInfo) -> Result<Self> {
+        debug_assert_eq!(page_cache.pages().size(), BLOCK_SIZE);
+
+        let mut entries: Vec<DirEntry> = DirEntryReader::new(page_cache, 0)
+            .take_while(|(offset, _)| *offset < BLOCK_SIZE)
+            .map(|(_, entry)| entry)
+            .collect();
+        if entries.len() < 2 || entries[0].name() != "." || entries[1].name() != ".." {
+            return_errno_with_message!(Errno::EIO, "invalid directory");
+        }
+        let leaf_entries = entries.split_off(2);
+        let mut parent_entry = entries.pop().unwrap();
+        let mut self_entry = entries.pop().unwrap();
+
+        // Moves the entries to the new leaf block.
+        page_cache.pages().resize(2 * BLOCK_SIZE)?;
+        DirEntryWriter::new(page_cache, BLOCK_SIZE).write_block_entries(leaf_entries)?;
+
+        // Rewrites the first block as the root.
+        page_cache
+            .pages()
+            .write_bytes(0, &[0u8; DX_ROOT_INFO_OFFSET])?;
+        self_entry.set_record_len(DOT_RECORD_LEN);
+        parent_entry.set_record_len(BLOCK_SIZE - DOT_RECORD_LEN);
+        let mut writer = DirEntryWriter::new(page_cache, 0);
+        writer.write_entry(&self_entry)?;
+        writer.write_entry(&parent_entry)?;
+
+        let hash_version = hash_info.default_version.to_signed();
+        let root_info = DxRootInfo {
+            reserved_zero: 0,
+            hash_version: hash_version as u8,
+            info_length: core::mem::size_of::<DxRootInfo>() as u8,
+            indirect_levels: 0,
+            unused_flags: 0,
+        };
+        page_cache
+            .pages()
+            .write_val(DX_ROOT_INFO_OFFSET, &root_info)?;
+
+        let htree = Self {
+            page_cache,
+            hash_version: hash_info.resolve(hash_version),
+            seed: hash_info.seed,
+            indirect_levels: 0,
+        };
+        let root = DxNode {
+            block: 0,
+            limit: DxNode::root_limit(),
+            entries: vec![DxEntry { hash: 0, block: 1 }],
+        };
+        htree.write_node(&root)?;
+        Ok(htree)
+    }
+
+    /// Finds the entry indicated by `name`, returns the offset of the entry
+    /// within the directory and the entry.
+    pub fn find_entry(&self, name: &str) -> Result<Option<(usize, DirEntry)>> {
+        // The "." and ".." entries are only in the root block.
+        if name == "." || name == ".." {
+            return Ok(self.find_entry_in_block(0, name));
+        }
+
+        let hash = self.hash(name);
+        let mut path = self.probe(hash)?;
+        loop {
+            let leaf = path.last().unwrap().block();
+            if let Some(found) = self.find_entry_in_block(leaf, name) {
+                return Ok(Some(found));
+            }
+
+            // The entries with the same hash may continue in the next leaf.
+            if !self.next_leaf(&mut path, hash)? {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Inserts a new entry into the tree.
+    ///
+    /// If the leaf is full, it is split in two halves by hash,
+    /// which may further split the interior node or grow the tree.
+    pub fn insert_entry(&self, new_entry: DirEntry) -> Result<()> {
+        let hash = self.hash(new_entry.name());
+        let mut path = self.probe(hash)?;
+        let leaf = path.last().unwrap().block();
+        if DirEntryWriter::new(self.page_cache, leaf as usize * BLOCK_SIZE)
+            .insert_entry_in_block(&new_entry)?
+        {
+            return Ok(());
+        }
+
+        self.make_room_in_parent(&mut path)?;
+        let (split_hash, new_leaf) = self.split_leaf(&mut path)?;
+        let target = if hash >= split_hash { new_leaf } else { leaf };
+        if !DirEntryWriter::new(self.page_cache, target as usize * BLOCK_SIZE)
+            .insert_entry_in_block(&new_entry)?
+        {
+            return_errno_with_message!(Errno::ENOSPC, "no space in the split leaf");
+        }
+        Ok(())
+    }
+
+    /// Computes the hash of `name`.
+    fn hash(&self, name: &str) -> u32 {
+        dx_hash(name.as_bytes(), self.hash_version, &self.seed).0
+    }
+
+    /// Looks up the path from the root to the leaf that covers `hash`.
+    fn probe(&self, hash: u32) -> Result<Vec<DxFrame>> {
+        let mut path = Vec::with_capacity(self.indirect_levels as usize + 1);
+        let mut node = self.read_node(0)?;
+        loop {
+            let idx = node.search(hash);
+            let child = node.entries[idx].block;
+            path.push(DxFrame { node, idx });
+            if path.len() > self.indirect_levels as usize {
+                return Ok(path);
+            }
+            node = self.read_node(child)?;
+        }
+    }
+
+    /// Moves the path to the next leaf if the entries with `hash` continue in it.
+    ///
+    /// Returns `false` if there is no such leaf.
+    fn next_leaf(&self, path: &mut Vec<DxFrame>, hash: u32) -> Result<bool> {
+        let Some(level) = path
+            .iter()
+            .rposition(|frame| frame.idx + 1 < frame.node.count())
+        else {
+            return Ok(false);
+        };
+
+        path.truncate(level + 1);
+        let frame = path.last_mut().unwrap();
+        frame.idx += 1;
+        let next_hash = frame.node.entries[frame.idx].hash;
+        if next_hash & !1 != hash {
+            return Ok(false);
+        }
+
+        while path.len() <= self.indirect_levels as usize {
+            let node = self.read_node(path.last().unwrap().block())?;
+            path.push(DxFrame { node, idx: 0 });
+        }
+        Ok(true)
+    }
+
+    /// Finds the entry indicated by `name` in one block.
+    fn find_entry_in_block(&self, block: u32, name: &str) -> Option<(usize, DirEntry)> {
+        let block_range = {
+            let start = block as usize * BLOCK_SIZE;
+            start..start + BLOCK_SIZE
+        };
+        DirEntryReader::new(self.page_cache, block_range.start)
+            .take_while(|(offset, _)| block_range.contains(offset))
+            .find(|(_, entry)| entry.name() == name)
+    }
+
+    /// Ensures that the parent node of the leaf in `path` can hold one more entry.
+    fn make_room_in_parent(&self, path: &mut Vec<DxFrame>) -> Result<()> {
+        if !path.last().unwrap().node.is_full() {
+            return Ok(());
+        }
+
+        if path.len() == 1 {
+            // Grows the tree by moving the entries of the root to a new node.
+            let new_block = self.alloc_block()?;
+            let root_frame = path.pop().unwrap();
+            let child = DxNode {
+                block: new_block,
+                limit: DxNode::node_limit(),
+                entries: root_frame.node.entries,
+            };
+            let root = DxNode {
+                block: 0,
+                limit: DxNode::root_limit(),
+                entries: vec![DxEntry {
+                    hash: 0,
+                    block: new_block,
+                }],
+            };
+            self.write_node(&child)?;
+            self.write_node(&root)?;
+            self.set_indirect_levels(1)?;
+
+            path.push(DxFrame { node: root, idx: 0 });
+            path.push(DxFrame {
+                node: child,
+                idx: root_frame.idx,
+            });
+            return Ok(());
+        }
+
+        // Splits the interior node and inserts the new half into the root.
+        debug_assert_eq!(path.len(), 2);
+        if path[0].node.is_full() {
+            return_errno_with_message!(Errno::ENOSPC, "directory index is full");
+        }
+        let new_block = self.alloc_block()?;
+        let frame = path.pop().unwrap();
+        let mut node = frame.node;
+        let split = node.count() / 2;
+        let new_node = DxNode {
+            block: new_block,
+            limit: DxNode::node_limit(),
+            entries: node.entries.split_off(split),
+        };
+        let split_hash = new_node.entries[0].hash;
+
+        let root_frame = path.last_mut().unwrap();
+        root_frame.node.insert(
+            root_frame.idx + 1,
+            DxEntry {
+                hash: split_hash,
+                block: new_block,
+            },
+        );
+        self.write_node(&node)?;
+        self.write_node(&new_node)?;
+        self.write_node(&root_frame.node)?;
+
+        if frame.idx >= split {
+            root_frame.idx += 1;
+            path.push(DxFrame {
+                node: new_node,
+                idx: frame.idx - split,
+            });
+        } else {
+            path.push(DxFrame {
+                node,
+                idx: frame.idx,
+            });
+        }
+        Ok(())
+    }
+
+    /// Splits the leaf in `path` by moving the upper half of the entries to a new leaf.
+    ///
+    /// Returns the lowest hash in the new leaf and the block of the new leaf.
+    /// The parent node must have room for one more entry.
+    fn split_leaf(&self, path: &mut [DxFrame]) -> Result<(u32, u32)> {
+        let frame = path.last_mut().unwrap();
+        let leaf = frame.block();
+        let leaf_offset = leaf as usize * BLOCK_SIZE;
+
+        let mut entries: Vec<(u32, DirEntry)> = DirEntryReader::new(self.page_cache, leaf_offset)
+            .take_while(|(offset, _)| *offset < leaf_offset + BLOCK_SIZE)
+            .map(|(_, entry)| (self.hash(entry.name()), entry))
+            .collect();
+        if entries.len() < 2 {
+            return_errno_with_message!(Errno::ENOSPC, "can not split the leaf");
+        }
+        entries.sort_by_key(|(hash, _)| *hash);
+
+        // Splits the leaf in the middle, size-wise.
+        let split = {
+            let mut moved_size = 0;
+            let mut split = entries.len();
+            for (_, entry) in entries.iter().rev() {
+                if moved_size + entry.actual_len() / 2 > BLOCK_SIZE / 2 {
+                    break;
+                }
+                moved_size += entry.actual_len();
+                split -= 1;
+            }
+            split.clamp(1, entries.len() - 1)
+        };
+        let split_hash = entries[split].0;
+        let is_continued = split_hash == entries[split - 1].0;
+
+        let new_leaf = self.alloc_block()?;
+        let moved_entries = entries.split_off(split);
+        DirEntryWriter::new(self.page_cache, new_leaf as usize * BLOCK_SIZE)
+            .write_block_entries(moved_entries.into_iter().map(|(_, entry)| entry).collect())?;
+        DirEntryWriter::new(self.page_cache, leaf_offset)
+            .write_block_entries(entries.into_iter().map(|(_, entry)| entry).collect())?;
+
+        frame.node.insert(
+            frame.idx + 1,
+            DxEntry {
+                hash: split_hash | is_continued as u32,
+                block: new_leaf,
+            },
+        );
+        self.write_node(&frame.node)?;
+        Ok((split_hash, new_leaf))
+    }
+
+    /// Appends a new block to the directory, returns the block number.
+    fn alloc_block(&self) -> Result<u32> {
+        let size = self.page_cache.pages().size();
+        self.page_cache.pages().resize(size + BLOCK_SIZE)?;
+        Ok((size / BLOCK_SIZE) as u32)
+    }
+
+    fn set_indirect_levels(&self, levels: u8) -> Result<()> {
+        let mut root_info = self
+            .page_cache
+            .pages()
+            .read_val::<DxRootInfo>(DX_ROOT_INFO_OFFSET)?;
+        root_info.indirect_levels = levels;
+        self.page_cache
+            .pages()
+            .write_val(DX_ROOT_INFO_OFFSET, &root_info)?;
+        Ok(())
+    }
+
+    fn read_node(&self, block: u32) -> Result<DxNode> {
+        let entries_offset = DxNode::entries_offset(block);
+        let count_limit = self
+            .page_cache
+            .pages()
+            .read_val::<DxCountLimit>(entries_offset)?;
+        let (count, limit) = (count_limit.count as usize, count_limit.limit as usize);
+        if count == 0 || count > limit {
+            return_errno_with_message!(Errno::EIO, "invalid dx node count");
+        }
+
+        let entry_size = core::mem::size_of::<DxEntry>();
+        let mut buf = vec![0u8; count * entry_size];
+        self.page_cache
+            .pages()
+            .read_bytes(entries_offset, &mut buf)?;
+        let mut entries: Vec<DxEntry> = buf.chunks(entry_size).map(DxEntry::from_bytes).collect();
+        entries[0].hash = 0;
+        Ok(DxNode {
+            block,
+            limit,
+            entries,
+        })
+    }
+
+    fn write_node(&self, node: &DxNode) -> Result<()> {
+        if node.block != 0 {
+            DirEntryWriter::new(self.page_cache, node.block as usize * BLOCK_SIZE)
+                .write_unused_entry(BLOCK_SIZE)?;
+        }
+
+        let entries_offset = DxNode::entries_offset(node.block);
+        let buf: Vec<u8> = node
+            .entries
+            .iter()
+            .flat_map(|entry| entry.as_bytes())
+            .copied()
+            .collect();
+        self.page_cache.pages().write_bytes(entries_offset, &buf)?;
+        let count_limit = DxCountLimit {
+            limit: node.limit as u16,
+            count: node.count() as u16,
+        };
+        self.page_cache
+            .pages()
+            .write_val(entries_offset, &count_limit)?;
+        Ok(())
+    }
+}
+
+/// A node of the index, which is either the root or an interior node.
+struct DxNode {
+    block: u32,
+    limit: usize,
+    entries: Vec<DxEntry>,
+}
+
+impl DxNode {
+    fn root_limit() -> usize {
+        (BLOCK_SIZE - Self::entries_offset(0)) / core::mem::size_of::<DxEntry>()
+    }
+
+    fn node_limit() -> usize {
+        (BLOCK_SIZE - DX_NODE_ENTRIES_OFFSET) / core::mem::size_of::<DxEntry>()
+    }
+
+    fn entries_offset(block: u32) -> usize {
+        if block == 0 {
+            DX_ROOT_INFO_OFFSET + core::mem::size_of::<DxRootInfo>()
+        } else {
+            block as usize * BLOCK_SIZE + DX_NODE_ENTRIES_OFFSET
+        }
+    }
+
+    fn count(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn is_full(&self) -> bool {
+        self.count() >= self.limit
+    }
+
+    /// Returns the index of the last entry whose hash is not greater than `hash`.
+    fn search(&self, hash: u32) -> usize {
+        self.entries[1..].partition_point(|entry| entry.hash <= hash)
+    }
+
+    fn insert(&mut self, idx: usize, entry: DxEntry) {
+        debug_assert!(!self.is_full());
+        self.entries.insert(idx, entry);
+    }
+}
+
+/// A node on the path from the root to a leaf, with the index of the chosen entry.
+struct DxFrame {
+    node: DxNode,
+    idx: usize,
+}
+
+impl DxFrame {
+    /// Returns the block pointed by the chosen entry.
+    fn block(&self) -> u32 {
+        self.node.entries[self.idx].block
+    }
+}
+
+/// The information about the index, which follows the ".." entry in the root block.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct DxRootInfo {
+    reserved_zero: u32,
+    hash_version: u8,
+    /// The length of this structure.
+    info_length: u8,
+    indirect_levels: u8,
+    unused_flags: u8,
+}
+
+/// The count and limit of the entries, which overlays the hash of the first entry in a node.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct DxCountLimit {
+    limit: u16,
+    count: u16,
+}
+
+/// The index entry which maps hashes starting from `hash` to the `block`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct DxEntry {
+    hash: u32,
+    /// The block number within the directory.
+    block: u32,
+}
+
+/// Computes the major and minor hash of a name.
+///
+/// The lowest bit of the major hash is always cleared, as it is used to mark
+/// the entries whose hash collides with the previous leaf.
+pub(super) fn dx_hash(name: &[u8], version: DxHashVersion, seed: &[u32; 4]) -> (u32, u32) {
+    let mut buf = if seed.iter().any(|word| *word != 0) {
+        *seed
+    } else {
+        [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476]
+    };
+
+    let (hash, minor_hash) = match version {
+        DxHashVersion::Legacy | DxHashVersion::LegacyUnsigned => {
+            (dx_hack_hash(name, version.is_unsigned()), 0)
+        }
+        DxHashVersion::HalfMd4 | DxHashVersion::HalfMd4Unsigned => {
+            let mut input = [0u32; 8];
+            for start in (0..name.len()).step_by(32) {
+                str_to_hash_buf(&name[start..], &mut input, version.is_unsigned());
+                half_md4_transform(&mut buf, &input);
+            }
+            (buf[1], buf[2])
+        }
+        DxHashVersion::Tea | DxHashVersion::TeaUnsigned => {
+            let mut input = [0u32; 4];
+            for start in (0..name.len()).step_by(16) {
+                str_to_hash_buf(&name[start..], &mut input, version.is_unsigned());
+                tea_transform(&mut buf, &input);
+            }
+            (buf[0], buf[1])
+        }
+    };
+
+    // The hash value of 0xfffffffe is reserved as the end-of-directory marker.
+    let hash = match hash & !1 {
+        0xfffffffe => 0xfffffffc,
+        hash => hash,
+    };
+    (hash, minor_hash)
+}
+
+fn char_to_u32(c: u8, is_unsigned: bool) -> u32 {
+    if is_unsigned {
+        c as u32
+    } else {
+        c as i8 as i32 as u32
+    }
+}
+
+fn dx_hack_hash(name: &[u8], is_unsigned: bool) -> u32 {
+    let (mut hash0, mut hash1): (u32, u32) = (0x12a3fe2d, 0x37abe8f9);
+    for c in name {
+        let mut hash =
+            hash1.wrapping_add(hash0 ^ char_to_u32(*c, is_unsigned).wrapping_mul(7152373));
+        if hash & 0x80000000 != 0 {
+            hash = hash.wrapping_sub(0x7fffffff);
+        }
+        hash1 = hash0;
+        hash0 = hash;
+    }
+    hash0 << 1
+}
+
+/// Packs the bytes of `msg` into the words of `buf`, padded with the length of `msg`.
+fn str_to_hash_buf(msg: &[u8], buf: &mut [u32], is_unsigned: bool) {
+    let pad = {
+        let len = msg.len() as u32;
+        let pad = len | (len << 8);
+        pad | (pad << 16)
+    };
+
+    let len = msg.len().min(buf.len() * 4);
+    let mut words = buf.iter_mut();
+    let mut val = pad;
+    for (i, c) in msg[..len].iter().enumerate() {
+        val = char_to_u32(*c, is_unsigned).wrapping_add(val << 8);
+        if i % 4 == 3 {
+            *words.next().unwrap() = val;
+            val = pad;
+        }
+    }
+    if len % 4 != 0 {
+        *words.next().unwrap() = val;
+    }
+    for word in words {
+        *word = pad;
+    }
+}
+
+fn half_md4_transform(buf: &mut [u32; 4], input: &[u32; 8]) {
+    const K1: u32 = 0;
+    const K2: u32 = 0x5a827999;
+    const K3: u32 = 0x6ed9eba1;
+
+    let f = |x: u32, y: u32, z: u32| z ^ (x & (y ^ z));
+    let g = |x: u32, y: u32, z: u32| (x & y).wrapping_add((x ^ y) & z);
+    let h = |x: u32, y: u32, z: u32| x ^ y ^ z;
+
+    macro_rules! round {
+        ($f:ident, $a:ident, $b:ident, $c:ident, $d:ident, $x:expr, $s:expr) => {
+            $a = $a
+                .wrapping_add($f($b, $c, $d))
+                .wrapping_add($x)
+                .rotate_left($s);
+        };
+    }
+
+    let [mut a, mut b, mut c, mut d] = *buf;
+
+    round!(f, a, b, c, d, input[0].wrapping_add(K1), 3);
+    round!(f, d, a, b, c, input[1].wrapping_add(K1), 7);
+    round!(f, c, d, a, b, input[2].wrapping_add(K1), 11);
+    round!(f, b, c, d, a, input[3].wrapping_add(K1), 19);
+    round!(f, a, b, c, d, input[4].wrapping_add(K1), 3);
+    round!(f, d, a, b, c, input[5].wrapping_add(K1), 7);
+    round!(f, c, d, a, b, input[6].wrapping_add(K1), 11);
+    round!(f, b, c, d, a, input[7].wrapping_add(K1), 19);
+
+    round!(g, a, b, c, d, input[1].wrapping_add(K2), 3);
+    round!(g, d, a, b, c, input[3].wrapping_add(K2), 5);
+    round!(g, c, d, a, b, input[5].wrapping_add(K2), 9);
+    round!(g, b, c, d, a, input[7].wrapping_add(K2), 13);
+    round!(g, a, b, c, d, input[0].wrapping_add(K2), 3);
+    round!(g, d, a, b, c, input[2].wrapping_add(K2), 5);
+    round!(g, c, d, a, b, input[4].wrapping_add(K2), 9);
+    round!(g, b, c, d, a, input[6].wrapping_add(K2), 13);
+
+    round!(h, a, b, c, d, input[3].wrapping_add(K3), 3);
+    round!(h, d, a, b, c, input[7].wrapping_add(K3), 9);
+    round!(h, c, d, a, b, input[2].wrapping_add(K3), 11);
+    round!(h, b, c, d, a, input[6].wrapping_add(K3), 15);
+    round!(h, a, b, c, d, input[1].wrapping_add(K3), 3);
+    round!(h, d, a, b, c, input[5].wrapping_add(K3), 9);
+    round!(h, c, d, a, b, input[0].wrapping_add(K3), 11);
+    round!(h, b, c, d, a, input[4].wrapping_add(K3), 15);
+
+    buf[0] = buf[0].wrapping_add(a);
+    buf[1] = buf[1].wrapping_add(b);
+    buf[2] = buf[2].wrapping_add(c);
+    buf[3] = buf[3].wrapping_add(d);
+}
+
+fn tea_transform(buf: &mut [u32; 4], input: &[u32; 4]) {
+    const DELTA: u32 = 0x9e3779b9;
+
+    let (mut b0, mut b1) = (buf[0], buf[1]);
+    let [a, b, c, d] = *input;
+    let mut sum: u32 = 0;
+    for _ in 0..16 {
+        sum = sum.wrapping_add(DELTA);
+        b0 = b0.wrapping_add(
+            ((b1 << 4).wrapping_add(a)) ^ (b1.wrapping_add(sum)) ^ ((b1 >> 5).wrapping_add(b)),
+        );
+        b1 = b1.wrapping_add(
+            ((b0 << 4).wrapping_add(c)) ^ (b0.wrapping_add(sum)) ^ ((b0 >> 5).wrapping_add(d)),
+        );
+    }
+
+    buf[0] = buf[0].wrapping_add(b0);
+    buf[1] = buf[1].wrapping_add(b1);
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::{dx_hash, DxHashVersion};
+
+    /// The expected values are computed by the `dx_hash` command of `debugfs`.
+    #[ktest]
+    fn hash_compatible_with_linux() {
+        const SHORT_NAME: &[u8] = b"hello_world";
+        const LONG_NAME: &[u8] = b"a_much_longer_file_name_exceeding_thirty_two_bytes.txt";
+        const NON_ASCII_NAME: &[u8] = "caf\u{e9}".as_bytes();
+        // The seed of "6d2a3b1c-0000-4000-8000-123456789abc".
+        const SEED: [u32; 4] = [0x1c3b2a6d, 0x00400000, 0x34120080, 0xbc9a7856];
+        const NO_SEED: [u32; 4] = [0; 4];
+
+        let cases = [
+            (SHORT_NAME, DxHashVersion::Legacy, NO_SEED, (0xf42d112e, 0)),
+            (LONG_NAME, DxHashVersion::Legacy, SEED, (0x9beed270, 0)),
+            (
+                NON_ASCII_NAME,
+                DxHashVersion::Legacy,
+                NO_SEED,
+                (0x96ca5a2c, 0),
+            ),
+            (
+                SHORT_NAME,
+                DxHashVersion::HalfMd4,
+                NO_SEED,
+                (0x657dfe96, 0x640376d8),
+            ),
+            (
+                LONG_NAME,
+                DxHashVersion::HalfMd4,
+                SEED,
+                (0xb29e2d40, 0x6bac5d0c),
+            ),
+            (
+                NON_ASCII_NAME,
+                DxHashVersion::HalfMd4,
+                NO_SEED,
+                (0xfb9c5e5c, 0x0573e8b8),
+            ),
+            (
+                SHORT_NAME,
+                DxHashVersion::Tea,
+                NO_SEED,
+                (0x6cf0d90e, 0x5fc3632a),
+            ),
+            (
+                LONG_NAME,
+                DxHashVersion::Tea,
+                SEED,
+                (0xe113cc2a, 0x9deca3a4),
+            ),
+            (
+                NON_ASCII_NAME,
+                DxHashVersion::Tea,
+                NO_SEED,
+                (0x105842ea, 0xfb9165ca),
+            ),
+        ];
+        for (name, version, seed, expected) in cases {
+            assert_eq!(dx_hash(name, version, &seed), expected);
+        }
+    }
+}
@@ -14,6 +14,7 @@ impl FileSystem for Ext2 {
     fn sync(&self) -> Result<()> {
         self.sync_all_inodes()?;
         self.sync_metadata()?;
+        self.flush_device()?;
         Ok(())
     }
 
@@ -28,6 +29,10 @@ impl FileSystem for Ext2 {
     fn flags(&self) -> FsFlags {
         FsFlags::empty()
     }
+
+    fn name(&self) -> &'static str {
+        "ext2"
+    }
 }
 
 impl From<RwMutexReadGuard<'_, Dirty<Ext2SuperBlock>>> for SuperBlock {
@@ -177,11 +177,13 @@ impl Inode for Ext2Inode {
     }
 
     fn sync_all(&self) -> Result<()> {
-        self.sync_all()
+        self.sync_all()?;
+        self.fs().flush_device()
     }
 
     fn sync_data(&self) -> Result<()> {
-        self.sync_data()
+        self.sync_data()?;
+        self.fs().flush_device()
     }
 
     fn fs(&self) -> Arc<dyn FileSystem> {
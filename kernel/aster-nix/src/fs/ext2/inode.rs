@@ -12,8 +12,10 @@ use super::{
     blocks_hole::BlocksHoleDesc,
     dir::{DirEntry, DirEntryReader, DirEntryWriter},
     fs::Ext2,
+    htree::{DxHashInfo, HTree},
     indirect_block_cache::{IndirectBlock, IndirectBlockCache},
     prelude::*,
+    super_block::FeatureCompatSet,
     utils::now,
 };
 
@@ -553,14 +555,14 @@ impl Inode {
 
             let try_readdir = |offset: &mut usize, visitor: &mut dyn DirentVisitor| -> Result<()> {
                 let dir_entry_reader = DirEntryReader::new(&inner.page_cache, *offset);
-                for (_, dir_entry) in dir_entry_reader {
+                for (entry_offset, dir_entry) in dir_entry_reader {
                     visitor.visit(
                         dir_entry.name(),
                         dir_entry.ino() as u64,
                         InodeType::from(dir_entry.type_()),
                         dir_entry.record_len(),
                     )?;
-                    *offset += dir_entry.record_len();
+                    *offset = entry_offset + dir_entry.record_len();
                 }
 
                 Ok(())
@@ -828,6 +830,7 @@ impl Inner {
     pub fn gid(&self) -> u32;
     pub fn set_gid(&mut self, gid: u32);
     pub fn file_flags(&self) -> FileFlags;
+    pub fn set_file_flags(&mut self, flags: FileFlags);
     pub fn hard_links(&self) -> u16;
     pub fn inc_hard_links(&mut self);
     pub fn dec_hard_links(&mut self);
@@ -981,6 +984,13 @@ impl Inner {
     }
 
     pub fn get_entry(&self, name: &str) -> Option<(usize, DirEntry)> {
+        if let Some(htree) = self.htree() {
+            match htree.find_entry(name) {
+                Ok(entry) => return entry,
+                Err(e) => warn!("failed to find entry in the dir index: {:?}", e),
+            }
+        }
+
         DirEntryReader::new(&self.page_cache, 0).find(|(offset, entry)| entry.name() == name)
     }
 
@@ -992,7 +1002,7 @@ impl Inner {
         let is_dir = entry.type_() == FileType::Dir;
         let is_parent = entry.name() == "..";
 
-        DirEntryWriter::new(&self.page_cache, 0).append_entry(entry)?;
+        self.insert_entry(entry)?;
         let file_size = self.inode_impl.file_size();
         let page_cache_size = self.page_cache.pages().size();
         if page_cache_size > file_size {
@@ -1006,6 +1016,10 @@ impl Inner {
 
     pub fn remove_entry_at(&mut self, name: &str, offset: usize) -> Result<()> {
         let entry = DirEntryWriter::new(&self.page_cache, offset).remove_entry(name)?;
+        if self.htree().is_none() {
+            self.clear_index_flag();
+            DirEntryWriter::new(&self.page_cache, 0).shrink_unused_blocks()?;
+        }
         let is_dir = entry.type_() == FileType::Dir;
         let file_size = self.inode_impl.file_size();
         let page_cache_size = self.page_cache.pages().size();
@@ -1019,7 +1033,21 @@ impl Inner {
     }
 
     pub fn rename_entry_at(&mut self, old_name: &str, new_name: &str, offset: usize) -> Result<()> {
-        DirEntryWriter::new(&self.page_cache, offset).rename_entry(old_name, new_name)?;
+        if self.htree().is_some() {
+            // The hash of the name changes, so the entry must be moved.
+            let entry = DirEntryWriter::new(&self.page_cache, offset).remove_entry(old_name)?;
+            if let Err(err) = self.insert_entry(DirEntry::new(entry.ino(), new_name, entry.type_()))
+            {
+                // Put the entry back, which fits in the space that it has just freed.
+                self.insert_entry(entry)?;
+                return Err(err);
+            }
+        } else {
+            self.clear_index_flag();
+            let mut writer = DirEntryWriter::new(&self.page_cache, offset);
+            writer.rename_entry(old_name, new_name)?;
+            writer.shrink_unused_blocks()?;
+        }
         let file_size = self.inode_impl.file_size();
         let page_cache_size = self.page_cache.pages().size();
         if page_cache_size != file_size {
@@ -1028,6 +1056,70 @@ impl Inner {
         Ok(())
     }
 
+    /// Inserts the entry into the directory, via the index if the directory is indexed.
+    ///
+    /// A linear directory of one block is converted into an indexed directory
+    /// if the block is full and the filesystem supports directory indexing.
+    fn insert_entry(&mut self, entry: DirEntry) -> Result<()> {
+        if let Some(htree) = self.htree() {
+            return htree.insert_entry(entry);
+        }
+
+        self.clear_index_flag();
+        let hash_info = {
+            let fs = self.inode_impl.fs();
+            let super_block = fs.super_block();
+            super_block
+                .feature_compat()
+                .contains(FeatureCompatSet::DIR_INDEX)
+                .then(|| DxHashInfo::new(&super_block))
+        };
+        if let Some(hash_info) = hash_info
+            && self.page_cache.pages().size() == BLOCK_SIZE
+        {
+            if DirEntryWriter::new(&self.page_cache, 0).insert_entry_in_block(&entry)? {
+                return Ok(());
+            }
+
+            let htree = HTree::create(&self.page_cache, &hash_info)?;
+            self.inode_impl
+                .set_file_flags(self.inode_impl.file_flags() | FileFlags::INDEX_DIR);
+            return htree.insert_entry(entry);
+        }
+
+        DirEntryWriter::new(&self.page_cache, 0).append_entry(entry)
+    }
+
+    /// Returns the index of the directory,
+    /// or `None` if the directory is not indexed or the index is unusable.
+    fn htree(&self) -> Option<HTree> {
+        if !self.file_flags().contains(FileFlags::INDEX_DIR) {
+            return None;
+        }
+
+        let fs = self.inode_impl.fs();
+        let super_block = fs.super_block();
+        if !super_block
+            .feature_compat()
+            .contains(FeatureCompatSet::DIR_INDEX)
+        {
+            return None;
+        }
+
+        HTree::open(&self.page_cache, &DxHashInfo::new(&super_block))
+            .inspect_err(|e| warn!("failed to open the dir index: {:?}", e))
+            .ok()
+    }
+
+    /// Clears the index flag before modifying the directory linearly,
+    /// since the index is no longer valid after that.
+    fn clear_index_flag(&mut self) {
+        let flags = self.file_flags();
+        if flags.contains(FileFlags::INDEX_DIR) {
+            self.set_file_flags(flags - FileFlags::INDEX_DIR);
+        }
+    }
+
     pub fn set_parent_ino(&mut self, parent_ino: u32) -> Result<()> {
         let (offset, mut entry) = self.get_entry("..").unwrap();
         entry.set_ino(parent_ino);
@@ -1676,6 +1768,10 @@ impl InodeImpl {
         Arc::new(Self(RwMutex::new(inner)))
     }
 
+    pub fn fs(&self) -> Arc<Ext2> {
+        self.0.read().fs()
+    }
+
     pub fn file_size(&self) -> usize {
         self.0.read().desc.size
     }
@@ -1719,6 +1815,11 @@ impl InodeImpl {
         self.0.read().desc.flags
     }
 
+    pub fn set_file_flags(&self, flags: FileFlags) {
+        let mut inner = self.0.write();
+        inner.desc.flags = flags;
+    }
+
     pub fn hard_links(&self) -> u16 {
         self.0.read().desc.hard_links
     }
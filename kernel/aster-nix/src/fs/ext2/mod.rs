@@ -14,12 +14,15 @@
 //!    stored in PageCache, which accelerates the performance of data access.
 //! 3. Compatible with queue-based block device. The filesystem can submits multiple
 //!    BIO requests to be block device at once, thereby enhancing I/O performance.
+//! 4. Hashed directory indexing. Large directories are indexed by the hash of names
+//!    in the same on-disk format as Linux (`dir_index`), so that a lookup does not
+//!    need to scan the whole directory.
 //!
 //! # Example
 //!
 //! ```no_run
 //! // Opens an Ext2 from the block device.
-//! let ext2 = Ext2::open(block_device)?;
+//! let ext2 = Ext2::open(block_device, Ext2MountOptions::default())?;
 //! // Lookup the root inode.
 //! let root = ext2.root_inode()?;
 //! // Create a file inside root directory.
@@ -36,7 +39,7 @@
 //! 1. Supports merging small read/write operations.
 //! 2. Handles the intermediate failure status correctly.
 
-pub use fs::Ext2;
+pub use fs::{Ext2, Ext2MountOptions};
 pub use inode::{FilePerm, FileType, Inode};
 pub use super_block::{SuperBlock, MAGIC_NUM};
 
@@ -45,6 +48,7 @@ mod block_ptr;
 mod blocks_hole;
 mod dir;
 mod fs;
+mod htree;
 mod impl_for_vfs;
 mod indirect_block_cache;
 mod inode;
@@ -1,6 +1,6 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use super::{inode::RawInode, prelude::*};
+use super::{htree::DxHashVersion, inode::RawInode, prelude::*};
 
 /// The magic number of Ext2.
 pub const MAGIC_NUM: u16 = 0xef53;
@@ -91,6 +91,15 @@ pub struct SuperBlock {
     prealloc_file_blocks: u8,
     /// Number of blocks to preallocate for directories.
     prealloc_dir_blocks: u8,
+    ///
+    /// This fields are valid if the FeatureCompatSet::DIR_INDEX is set.
+    ///
+    /// Seed of the hash algorithm for directory indexing.
+    hash_seed: [u32; 4],
+    /// Default hash algorithm for directory indexing.
+    def_hash_version: DxHashVersion,
+    /// Miscellaneous flags.
+    flags: SuperBlockFlags,
 }
 
 impl TryFrom<RawSuperBlock> for SuperBlock {
@@ -172,6 +181,10 @@ impl TryFrom<RawSuperBlock> for SuperBlock {
             last_mounted_dir: sb.last_mounted_dir,
             prealloc_file_blocks: sb.prealloc_file_blocks,
             prealloc_dir_blocks: sb.prealloc_dir_blocks,
+            hash_seed: sb.hash_seed,
+            def_hash_version: DxHashVersion::try_from(sb.def_hash_version)
+                .map_err(|_| Error::with_message(Errno::EINVAL, "invalid default hash version"))?,
+            flags: SuperBlockFlags::from_bits_truncate(sb.flags),
         })
     }
 }
@@ -242,6 +255,21 @@ impl SuperBlock {
         self.feature_ro_compat
     }
 
+    /// Returns the seed of the hash algorithm for directory indexing.
+    pub fn hash_seed(&self) -> [u32; 4] {
+        self.hash_seed
+    }
+
+    /// Returns the default hash algorithm for directory indexing.
+    pub fn def_hash_version(&self) -> DxHashVersion {
+        self.def_hash_version
+    }
+
+    /// Returns the miscellaneous flags.
+    pub fn flags(&self) -> SuperBlockFlags {
+        self.flags
+    }
+
     /// Returns the number of free blocks.
     pub fn free_blocks_count(&self) -> u32 {
         self.free_blocks_count
@@ -368,6 +396,18 @@ bitflags! {
     }
 }
 
+bitflags! {
+    /// Miscellaneous flags of the superblock.
+    pub struct SuperBlockFlags: u32 {
+        /// Signed directory hash in use
+        const SIGNED_HASH = 1 << 0;
+        /// Unsigned directory hash in use
+        const UNSIGNED_HASH = 1 << 1;
+        /// Development code testing
+        const TEST_FILESYS = 1 << 2;
+    }
+}
+
 #[repr(u16)]
 #[derive(Copy, Clone, Debug, Eq, PartialEq, TryFromInt)]
 pub enum FsState {
@@ -485,6 +525,9 @@ pub(super) struct RawSuperBlock {
     pub default_mount_opts: u32,
     /// First metablock block group.
     pub first_meta_bg: u32,
+    reserved1: [u32; 22],
+    /// Miscellaneous flags.
+    pub flags: u32,
     reserved: Reserved,
 }
 
@@ -526,6 +569,9 @@ impl From<&SuperBlock> for RawSuperBlock {
             last_mounted_dir: sb.last_mounted_dir,
             prealloc_file_blocks: sb.prealloc_file_blocks,
             prealloc_dir_blocks: sb.prealloc_dir_blocks,
+            hash_seed: sb.hash_seed,
+            def_hash_version: sb.def_hash_version as u8,
+            flags: sb.flags.bits(),
             ..Default::default()
         }
     }
@@ -533,10 +579,10 @@ impl From<&SuperBlock> for RawSuperBlock {
 
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Pod)]
-struct Reserved([u32; 190]);
+struct Reserved([u32; 167]);
 
 impl Default for Reserved {
     fn default() -> Self {
-        Self([0u32; 190])
+        Self([0u32; 167])
     }
 }
@@ -15,6 +15,7 @@ use crate::{
     events::{Events, Observer, Subject},
     net::socket::Socket,
     prelude::*,
+    process::ResourceType,
 };
 
 pub type FileDesc = i32;
@@ -83,14 +84,24 @@ impl FileTable {
         };
 
         let min_free_fd = get_min_free_fd();
+        check_nofile_limit(min_free_fd)?;
         let entry = FileTableEntry::new(file, flags);
         self.table.put_at(min_free_fd, entry);
         Ok(min_free_fd as FileDesc)
     }
 
-    pub fn insert(&mut self, item: Arc<dyn FileLike>, flags: FdFlags) -> FileDesc {
+    /// Inserts the file at the lowest free file descriptor.
+    ///
+    /// This method fails with `EMFILE` if the file descriptor would exceed the
+    /// `RLIMIT_NOFILE` of the current process.
+    pub fn insert(&mut self, item: Arc<dyn FileLike>, flags: FdFlags) -> Result<FileDesc> {
+        let lowest_free_fd = (0..self.table.slots_len())
+            .find(|idx| self.table.get(*idx).is_none())
+            .unwrap_or(self.table.slots_len());
+        check_nofile_limit(lowest_free_fd)?;
+
         let entry = FileTableEntry::new(item, flags);
-        self.table.put(entry) as FileDesc
+        Ok(self.table.put(entry) as FileDesc)
     }
 
     pub fn insert_at(
@@ -228,6 +239,19 @@ pub enum FdEvents {
 
 impl Events for FdEvents {}
 
+/// Checks whether the file descriptor is below the `RLIMIT_NOFILE` of the current process.
+fn check_nofile_limit(fd: usize) -> Result<()> {
+    let max_nr_fds = current!()
+        .resource_limits()
+        .lock()
+        .get_rlimit(ResourceType::RLIMIT_NOFILE)
+        .get_cur();
+    if fd as u64 >= max_nr_fds {
+        return_errno_with_message!(Errno::EMFILE, "the file descriptor limit is reached");
+    }
+    Ok(())
+}
+
 pub struct FileTableEntry {
     file: Arc<dyn FileLike>,
     flags: AtomicU8,
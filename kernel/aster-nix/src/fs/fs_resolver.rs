@@ -35,8 +35,8 @@ impl Default for FsResolver {
 impl FsResolver {
     pub fn new() -> Self {
         Self {
-            root: Dentry::new_fs_root(root_mount().clone()),
-            cwd: Dentry::new_fs_root(root_mount().clone()),
+            root: Dentry::new_fs_root(root_mount()),
+            cwd: Dentry::new_fs_root(root_mount()),
         }
     }
 
@@ -114,6 +114,12 @@ impl FsResolver {
             Err(e) => return Err(e),
         };
 
+        // Like Linux, the special files can still be written on a read-only mount.
+        if dentry.type_() == InodeType::File
+            && (access_mode.is_writable() || creation_flags.contains(CreationFlags::O_TRUNC))
+        {
+            dentry.check_writable_mount()?;
+        }
         if creation_flags.contains(CreationFlags::O_TRUNC) {
             dentry.resize(0)?;
         }
@@ -0,0 +1,362 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use ostd::sync::WaitQueue;
+
+use super::abi::*;
+use crate::{
+    events::IoEvents,
+    fs::inode_handle::FileIo,
+    prelude::*,
+    process::signal::{Pollee, Poller},
+};
+
+/// A connection between the kernel and a FUSE daemon.
+///
+/// Requests are queued by the filesystem and read by the daemon from `/dev/fuse`,
+/// and the replies written by the daemon wake up the waiting requests.
+pub(super) struct FuseConn {
+    state: Mutex<ConnState>,
+    /// The state of the request queue
+    pollee: Pollee,
+    /// The queue of threads waiting for replies or the initialization
+    wait_queue: WaitQueue,
+    unique_allocator: AtomicU64,
+    /// Whether a filesystem has been mounted with this connection
+    is_mounted: AtomicBool,
+}
+
+struct ConnState {
+    /// The requests that have not been read by the daemon
+    pending: VecDeque<Vec<u8>>,
+    /// The requests that expect replies, indexed by their unique IDs
+    replies: BTreeMap<u64, ReplySlot>,
+    /// The result of INIT, which is `None` before the daemon replies
+    init_out: Option<FuseInitOut>,
+    is_aborted: bool,
+}
+
+enum ReplySlot {
+    /// A request is waiting for the reply.
+    Waiting,
+    /// The reply has arrived.
+    Received(Result<Vec<u8>>),
+    /// Nobody waits for the reply, which is dropped once it arrives.
+    Background,
+    /// The reply of INIT.
+    Init,
+}
+
+impl FuseConn {
+    fn new() -> Arc<Self> {
+        Arc::new(Self {
+            state: Mutex::new(ConnState {
+                pending: VecDeque::new(),
+                replies: BTreeMap::new(),
+                init_out: None,
+                is_aborted: false,
+            }),
+            pollee: Pollee::new(IoEvents::OUT),
+            wait_queue: WaitQueue::new(),
+            unique_allocator: AtomicU64::new(1),
+            is_mounted: AtomicBool::new(false),
+        })
+    }
+
+    /// Marks the connection as used by a mounted filesystem and starts the initialization.
+    ///
+    /// The daemon usually mounts the filesystem before serving requests, so this method
+    /// does not wait for the reply of INIT. Instead, the other requests wait for it.
+    pub(super) fn mount(&self) -> Result<()> {
+        if self.is_mounted.swap(true, Ordering::Relaxed) {
+            return_errno_with_message!(Errno::EINVAL, "the FUSE device is already mounted");
+        }
+        let init_in = FuseInitIn {
+            major: FUSE_KERNEL_VERSION,
+            minor: FUSE_KERNEL_MINOR_VERSION,
+            max_readahead: 0,
+            flags: (FuseInitFlags::ASYNC_READ
+                | FuseInitFlags::ATOMIC_O_TRUNC
+                | FuseInitFlags::BIG_WRITES
+                | FuseInitFlags::DONT_MASK)
+                .bits(),
+        };
+        self.queue_request(FuseOpcode::Init, 0, &[init_in.as_bytes()], ReplySlot::Init)?;
+        Ok(())
+    }
+
+    /// Sends a request and waits for the reply.
+    ///
+    /// The payload of the reply is returned on success.
+    pub(super) fn request(
+        &self,
+        opcode: FuseOpcode,
+        nodeid: u64,
+        args: &[&[u8]],
+    ) -> Result<Vec<u8>> {
+        self.wait_initialized()?;
+        let unique = self.queue_request(opcode, nodeid, args, ReplySlot::Waiting)?;
+        self.wait_queue.wait_until(|| {
+            let mut state = self.state.lock();
+            if state.is_aborted {
+                state.replies.remove(&unique);
+                return Some(Err(Error::with_message(
+                    Errno::ENOTCONN,
+                    "the FUSE connection is aborted",
+                )));
+            }
+            match state.replies.remove(&unique) {
+                Some(ReplySlot::Received(reply)) => Some(reply),
+                Some(slot) => {
+                    state.replies.insert(unique, slot);
+                    None
+                }
+                // The slot is never removed by others, but do not trust that.
+                None => Some(Err(Error::with_message(
+                    Errno::EIO,
+                    "the reply of the FUSE request is lost",
+                ))),
+            }
+        })
+    }
+
+    /// Sends a request whose reply is ignored, without waiting.
+    pub(super) fn request_in_background(&self, opcode: FuseOpcode, nodeid: u64, args: &[&[u8]]) {
+        let is_initialized = {
+            let state = self.state.lock();
+            state.init_out.is_some() && !state.is_aborted
+        };
+        if is_initialized {
+            let _ = self.queue_request(opcode, nodeid, args, ReplySlot::Background);
+        }
+    }
+
+    /// Returns the maximum size of the data in a WRITE request.
+    pub(super) fn max_write(&self) -> usize {
+        let state = self.state.lock();
+        let max_write = state.init_out.map_or(0, |init_out| init_out.max_write) as usize;
+        max_write.max(PAGE_SIZE)
+    }
+
+    /// Aborts the connection, failing all the requests.
+    pub(super) fn abort(&self) {
+        let mut state = self.state.lock();
+        state.is_aborted = true;
+        state.pending.clear();
+        drop(state);
+        self.pollee.add_events(IoEvents::IN | IoEvents::ERR);
+        self.wait_queue.wake_all();
+    }
+
+    fn wait_initialized(&self) -> Result<()> {
+        self.wait_queue.wait_until(|| {
+            let state = self.state.lock();
+            if state.is_aborted {
+                Some(Err(Error::with_message(
+                    Errno::ENOTCONN,
+                    "the FUSE connection is aborted",
+                )))
+            } else if state.init_out.is_some() {
+                Some(Ok(()))
+            } else {
+                None
+            }
+        })
+    }
+
+    fn queue_request(
+        &self,
+        opcode: FuseOpcode,
+        nodeid: u64,
+        args: &[&[u8]],
+        slot: ReplySlot,
+    ) -> Result<u64> {
+        let unique = self.unique_allocator.fetch_add(1, Ordering::Relaxed);
+        let (uid, gid, pid) = caller_ids();
+        let args_len: usize = args.iter().map(|arg| arg.len()).sum();
+        let header = FuseInHeader {
+            len: (core::mem::size_of::<FuseInHeader>() + args_len) as u32,
+            opcode: opcode as u32,
+            unique,
+            nodeid,
+            uid,
+            gid,
+            pid,
+            padding: 0,
+        };
+        let mut request = Vec::with_capacity(header.len as usize);
+        request.extend_from_slice(header.as_bytes());
+        for arg in args {
+            request.extend_from_slice(arg);
+        }
+
+        let mut state = self.state.lock();
+        if state.is_aborted {
+            return_errno_with_message!(Errno::ENOTCONN, "the FUSE connection is aborted");
+        }
+        if opcode.has_reply() {
+            state.replies.insert(unique, slot);
+        }
+        state.pending.push_back(request);
+        self.pollee.add_events(IoEvents::IN);
+        Ok(unique)
+    }
+
+    /// Reads a request for the daemon.
+    fn read_request(&self, buf: &mut [u8]) -> Result<usize> {
+        if buf.len() < FUSE_MIN_READ_BUFFER {
+            return_errno_with_message!(Errno::EINVAL, "the buffer is too small");
+        }
+
+        let poller = Poller::new();
+        loop {
+            let mut state = self.state.lock();
+            if state.is_aborted {
+                return_errno_with_message!(Errno::ENODEV, "the FUSE connection is aborted");
+            }
+            let Some(request) = state.pending.pop_front() else {
+                self.pollee.del_events(IoEvents::IN);
+                drop(state);
+                if self.pollee.poll(IoEvents::IN, Some(&poller)).is_empty() {
+                    poller.wait()?;
+                }
+                continue;
+            };
+            if state.pending.is_empty() {
+                self.pollee.del_events(IoEvents::IN);
+            }
+            if request.len() > buf.len() {
+                // Fail the request instead of leaving the daemon stuck on it.
+                let header = FuseInHeader::from_bytes(&request);
+                if let Some(slot) = state.replies.get_mut(&header.unique) {
+                    *slot = ReplySlot::Received(Err(Error::new(Errno::EIO)));
+                }
+                drop(state);
+                self.wait_queue.wake_all();
+                return_errno_with_message!(Errno::EINVAL, "the buffer is too small");
+            }
+            buf[..request.len()].copy_from_slice(&request);
+            return Ok(request.len());
+        }
+    }
+
+    /// Handles a reply written by the daemon.
+    fn write_reply(&self, buf: &[u8]) -> Result<usize> {
+        const HEADER_LEN: usize = core::mem::size_of::<FuseOutHeader>();
+
+        if buf.len() < HEADER_LEN {
+            return_errno_with_message!(Errno::EINVAL, "the reply is too short");
+        }
+        let header = FuseOutHeader::from_bytes(&buf[..HEADER_LEN]);
+        if header.len as usize != buf.len() || header.error > 0 {
+            return_errno_with_message!(Errno::EINVAL, "invalid reply header");
+        }
+        let payload = &buf[HEADER_LEN..];
+        let reply = if header.error == 0 {
+            Ok(payload.to_vec())
+        } else {
+            let errno = header
+                .error
+                .checked_neg()
+                .and_then(|errno| Errno::try_from(errno).ok())
+                .unwrap_or(Errno::EIO);
+            Err(Error::new(errno))
+        };
+
+        let mut state = self.state.lock();
+        let Some(slot) = state.replies.remove(&header.unique) else {
+            return_errno_with_message!(Errno::ENOENT, "no request with the unique ID");
+        };
+        match slot {
+            ReplySlot::Received(_) => {
+                // The request has been answered, whose waiter will take the reply.
+                state.replies.insert(header.unique, slot);
+                return_errno_with_message!(Errno::EINVAL, "the request is already answered");
+            }
+            ReplySlot::Waiting => {
+                state
+                    .replies
+                    .insert(header.unique, ReplySlot::Received(reply));
+            }
+            ReplySlot::Init => {
+                let init_out = reply.and_then(|payload| parse_init_out(&payload));
+                match init_out {
+                    Ok(init_out) => state.init_out = Some(init_out),
+                    Err(_) => {
+                        drop(state);
+                        self.abort();
+                        return_errno_with_message!(Errno::EPROTO, "FUSE initialization failed");
+                    }
+                }
+            }
+            ReplySlot::Background => {}
+        }
+        drop(state);
+        self.wait_queue.wake_all();
+
+        Ok(buf.len())
+    }
+}
+
+fn parse_init_out(payload: &[u8]) -> Result<FuseInitOut> {
+    // Old daemons reply with a shorter structure.
+    let mut init_out = FuseInitOut::default();
+    let len = payload.len().min(core::mem::size_of::<FuseInitOut>());
+    init_out.as_bytes_mut()[..len].copy_from_slice(&payload[..len]);
+    if init_out.major != FUSE_KERNEL_VERSION {
+        return_errno_with_message!(Errno::EPROTO, "unsupported FUSE protocol version");
+    }
+    Ok(init_out)
+}
+
+/// Returns the user ID, group ID and process ID of the caller.
+fn caller_ids() -> (u32, u32, u32) {
+    let current_thread = current_thread!();
+    let Some(posix_thread) = current_thread.as_posix_thread() else {
+        return (0, 0, 0);
+    };
+    let credentials = posix_thread.credentials();
+    (
+        credentials.fsuid().as_u32(),
+        credentials.fsgid().as_u32(),
+        posix_thread.process().pid(),
+    )
+}
+
+/// A file opened from `/dev/fuse`, through which a daemon serves a FUSE connection.
+pub struct FuseDevFile {
+    conn: Arc<FuseConn>,
+}
+
+impl FuseDevFile {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            conn: FuseConn::new(),
+        })
+    }
+
+    pub(super) fn conn(&self) -> &Arc<FuseConn> {
+        &self.conn
+    }
+}
+
+impl Drop for FuseDevFile {
+    fn drop(&mut self) {
+        self.conn.abort();
+    }
+}
+
+impl FileIo for FuseDevFile {
+    fn read(&self, buf: &mut [u8]) -> Result<usize> {
+        self.conn.read_request(buf)
+    }
+
+    fn write(&self, buf: &[u8]) -> Result<usize> {
+        self.conn.write_reply(buf)
+    }
+
+    fn poll(&self, mask: IoEvents, poller: Option<&Poller>) -> IoEvents {
+        self.conn.pollee.poll(mask, poller)
+    }
+}
@@ -0,0 +1,939 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use super::{abi::*, conn::FuseConn, *};
+use crate::{
+    events::IoEvents,
+    fs::{
+        device::Device,
+        inode_handle::FileIo,
+        utils::{
+            AccessMode, DirentVisitor, FileSystem, FsFlags, Inode, InodeMode, InodeType, Metadata,
+            StatusFlags, SuperBlock,
+        },
+    },
+    prelude::*,
+    process::{signal::Poller, Gid, Uid},
+};
+
+/// A filesystem whose operations are served by a user-space daemon.
+pub struct FuseFS {
+    /// The connection to the daemon
+    conn: Arc<FuseConn>,
+    /// The mount options
+    options: FuseMountOptions,
+    /// Root inode
+    root: Arc<FuseInode>,
+    /// The inodes that have been looked up, indexed by their node IDs
+    inodes: Mutex<BTreeMap<u64, Weak<FuseInode>>>,
+}
+
+impl FuseFS {
+    /// Creates a FUSE filesystem served through `dev_file`.
+    pub fn new(dev_file: &FuseDevFile, options: FuseMountOptions) -> Result<Arc<Self>> {
+        let conn = dev_file.conn().clone();
+        conn.mount()?;
+
+        // The attributes of the root are fetched from the daemon once it is initialized.
+        let root_attr = FuseAttr {
+            ino: FUSE_ROOT_ID,
+            mode: options.root_mode,
+            nlink: 2,
+            uid: options.user_id.as_u32(),
+            gid: options.group_id.as_u32(),
+            blksize: BLOCK_SIZE as u32,
+            ..Default::default()
+        };
+        Ok(Arc::new_cyclic(|weak_fs| Self {
+            conn,
+            options,
+            root: FuseInode::new(FUSE_ROOT_ID, InodeType::Dir, root_attr, weak_fs.clone()),
+            inodes: Mutex::new(BTreeMap::new()),
+        }))
+    }
+
+    /// Returns the inode of a new entry replied by the daemon.
+    ///
+    /// Each replied entry increases the lookup count of the node by one, which
+    /// is given back to the daemon by FORGET when the inode is dropped.
+    fn get_or_insert_inode(self: &Arc<Self>, entry: &FuseEntryOut) -> Result<Arc<FuseInode>> {
+        if entry.nodeid == 0 {
+            return_errno!(Errno::ENOENT);
+        }
+        if entry.nodeid == FUSE_ROOT_ID {
+            return Ok(self.root.clone());
+        }
+        let typ = inode_type_from_mode(entry.attr.mode)?;
+
+        let mut inodes = self.inodes.lock();
+        let stale_inode = match inodes.get(&entry.nodeid).and_then(Weak::upgrade) {
+            Some(inode) if inode.typ == typ => {
+                inode.nlookup.fetch_add(1, Ordering::Relaxed);
+                *inode.attr.lock() = entry.attr;
+                return Ok(inode);
+            }
+            stale_inode => stale_inode,
+        };
+        let inode = FuseInode::new(entry.nodeid, typ, entry.attr, Arc::downgrade(self));
+        inode.nlookup.store(1, Ordering::Relaxed);
+        inodes.insert(entry.nodeid, Arc::downgrade(&inode));
+        // Dropping an inode locks the map, so the stale one must be dropped after unlocking.
+        drop(inodes);
+        drop(stale_inode);
+        Ok(inode)
+    }
+
+    fn max_read(&self) -> usize {
+        self.options
+            .max_read
+            .unwrap_or(FUSE_DEFAULT_MAX_READ)
+            .clamp(PAGE_SIZE, FUSE_DEFAULT_MAX_READ)
+    }
+}
+
+impl Drop for FuseFS {
+    fn drop(&mut self) {
+        // Let the daemon know that the filesystem is unmounted.
+        self.conn.abort();
+    }
+}
+
+impl FileSystem for FuseFS {
+    fn sync(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn root_inode(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+
+    fn sb(&self) -> SuperBlock {
+        let mut sb = SuperBlock::new(FUSE_SUPER_MAGIC, BLOCK_SIZE, NAME_MAX);
+        let Ok(st) = self
+            .conn
+            .request(FuseOpcode::Statfs, FUSE_ROOT_ID, &[])
+            .and_then(|reply| parse_reply::<FuseKstatfs>(&reply))
+        else {
+            return sb;
+        };
+        sb.bsize = st.bsize as usize;
+        sb.blocks = st.blocks as usize;
+        sb.bfree = st.bfree as usize;
+        sb.bavail = st.bavail as usize;
+        sb.files = st.files as usize;
+        sb.ffree = st.ffree as usize;
+        sb.namelen = st.namelen as usize;
+        sb.frsize = st.frsize as usize;
+        sb
+    }
+
+    fn flags(&self) -> FsFlags {
+        FsFlags::empty()
+    }
+
+    fn name(&self) -> &'static str {
+        "fuse"
+    }
+}
+
+/// The file handles of an inode.
+#[derive(Default)]
+struct FileHandles {
+    /// The handle for reading without an open file, e.g., by the kernel itself
+    read: Option<u64>,
+    /// The handle for writing without an open file
+    write: Option<u64>,
+    /// The handles of the open files, which are released when the files are closed
+    open_files: BTreeSet<u64>,
+}
+
+impl FileHandles {
+    /// Returns a handle for the operations that need one but not a specific one,
+    /// e.g., SETATTR and FSYNC.
+    fn any(&self) -> Option<u64> {
+        self.write
+            .or(self.read)
+            .or_else(|| self.open_files.first().copied())
+    }
+}
+
+struct FuseInode {
+    /// The node ID, which is zero for a symlink not yet created by the daemon
+    nodeid: AtomicU64,
+    /// Type of the inode
+    typ: InodeType,
+    /// The attributes last replied by the daemon
+    attr: Mutex<FuseAttr>,
+    /// The number of times the node has been replied by the daemon
+    nlookup: AtomicU64,
+    /// The file handles used for the I/O of a regular file without an open file
+    handles: Mutex<FileHandles>,
+    /// The READDIR offsets of the directory entries read without an open file
+    dir_offsets: Mutex<Vec<u64>>,
+    /// The parent and the name of a symlink before its target is written
+    pending_symlink: Mutex<Option<(Arc<FuseInode>, String)>>,
+    /// Reference to self
+    this: Weak<FuseInode>,
+    /// Reference to fs
+    fs: Weak<FuseFS>,
+}
+
+impl FuseInode {
+    fn new(nodeid: u64, typ: InodeType, attr: FuseAttr, fs: Weak<FuseFS>) -> Arc<Self> {
+        Arc::new_cyclic(|weak_self| FuseInode {
+            nodeid: AtomicU64::new(nodeid),
+            typ,
+            attr: Mutex::new(attr),
+            nlookup: AtomicU64::new(0),
+            handles: Mutex::new(FileHandles::default()),
+            dir_offsets: Mutex::new(Vec::new()),
+            pending_symlink: Mutex::new(None),
+            this: weak_self.clone(),
+            fs,
+        })
+    }
+
+    fn fuse_fs(&self) -> Arc<FuseFS> {
+        self.fs.upgrade().unwrap()
+    }
+
+    fn nodeid(&self) -> u64 {
+        self.nodeid.load(Ordering::Relaxed)
+    }
+
+    fn request(&self, opcode: FuseOpcode, args: &[&[u8]]) -> Result<Vec<u8>> {
+        let nodeid = self.nodeid();
+        if nodeid == 0 {
+            return_errno_with_message!(Errno::ENOENT, "the symlink has no target");
+        }
+        self.fuse_fs().conn.request(opcode, nodeid, args)
+    }
+
+    fn request_entry(&self, opcode: FuseOpcode, args: &[&[u8]]) -> Result<Arc<FuseInode>> {
+        let reply = self.request(opcode, args)?;
+        let entry = parse_reply::<FuseEntryOut>(&reply)?;
+        self.fuse_fs().get_or_insert_inode(&entry)
+    }
+
+    /// Fetches the attributes from the daemon.
+    fn getattr(&self) -> Result<FuseAttr> {
+        if self.nodeid() == 0 {
+            return Ok(*self.attr.lock());
+        }
+        let getattr_in = FuseGetattrIn {
+            getattr_flags: 0,
+            dummy: 0,
+            fh: 0,
+        };
+        let reply = self.request(FuseOpcode::Getattr, &[getattr_in.as_bytes()])?;
+        let attr_out = parse_reply::<FuseAttrOut>(&reply)?;
+        *self.attr.lock() = attr_out.attr;
+        Ok(attr_out.attr)
+    }
+
+    /// Returns the up-to-date attributes, or the cached ones if the daemon fails.
+    fn attr(&self) -> FuseAttr {
+        self.getattr().unwrap_or_else(|_| *self.attr.lock())
+    }
+
+    fn setattr(&self, mut setattr_in: FuseSetattrIn) -> Result<()> {
+        if let Some(fh) = self.handles.lock().any() {
+            setattr_in.valid |= FuseSetattrValid::FH.bits();
+            setattr_in.fh = fh;
+        }
+        let reply = self.request(FuseOpcode::Setattr, &[setattr_in.as_bytes()])?;
+        let attr_out = parse_reply::<FuseAttrOut>(&reply)?;
+        *self.attr.lock() = attr_out.attr;
+        Ok(())
+    }
+
+    fn set_time(&self, valid: FuseSetattrValid, time: Duration) {
+        let mut setattr_in = FuseSetattrIn {
+            valid: valid.bits(),
+            ..Default::default()
+        };
+        let (secs, nsecs) = (time.as_secs(), time.subsec_nanos());
+        if valid.contains(FuseSetattrValid::ATIME) {
+            (setattr_in.atime, setattr_in.atimensec) = (secs, nsecs);
+        } else if valid.contains(FuseSetattrValid::MTIME) {
+            (setattr_in.mtime, setattr_in.mtimensec) = (secs, nsecs);
+        } else {
+            (setattr_in.ctime, setattr_in.ctimensec) = (secs, nsecs);
+        }
+        if let Err(e) = self.setattr(setattr_in) {
+            warn!("failed to set the time of FUSE inode: {:?}", e);
+        }
+    }
+
+    /// Returns the file handle for the I/O without an open file, opening the file
+    /// for reading or for writing if necessary.
+    fn file_handle(&self, need_write: bool) -> Result<u64> {
+        let mut handles = self.handles.lock();
+        let (handle, flags) = if need_write {
+            (&mut handles.write, O_WRONLY)
+        } else {
+            (&mut handles.read, O_RDONLY)
+        };
+        if let Some(fh) = *handle {
+            return Ok(fh);
+        }
+
+        let fh = self.open_handle(flags)?;
+        *handle = Some(fh);
+        Ok(fh)
+    }
+
+    fn open_handle(&self, flags: u32) -> Result<u64> {
+        let opcode = if self.typ == InodeType::Dir {
+            FuseOpcode::Opendir
+        } else {
+            FuseOpcode::Open
+        };
+        let open_in = FuseOpenIn {
+            flags,
+            open_flags: 0,
+        };
+        let reply = self.request(opcode, &[open_in.as_bytes()])?;
+        Ok(parse_reply::<FuseOpenOut>(&reply)?.fh)
+    }
+
+    fn release(&self, opcode: FuseOpcode, fh: u64) {
+        let Some(fs) = self.fs.upgrade() else {
+            return;
+        };
+        let release_in = FuseReleaseIn {
+            fh,
+            flags: 0,
+            release_flags: 0,
+            lock_owner: 0,
+        };
+        fs.conn
+            .request_in_background(opcode, self.nodeid(), &[release_in.as_bytes()]);
+    }
+
+    fn read_with_handle(&self, fh: u64, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        let max_read = self.fuse_fs().max_read();
+        let mut read_len = 0;
+        while read_len < buf.len() {
+            let size = (buf.len() - read_len).min(max_read);
+            let read_in = FuseReadIn {
+                fh,
+                offset: (offset + read_len) as u64,
+                size: size as u32,
+                read_flags: 0,
+                lock_owner: 0,
+                flags: 0,
+                padding: 0,
+            };
+            let reply = self.request(FuseOpcode::Read, &[read_in.as_bytes()])?;
+            let len = reply.len().min(size);
+            buf[read_len..read_len + len].copy_from_slice(&reply[..len]);
+            read_len += len;
+            if len < size {
+                break;
+            }
+        }
+        Ok(read_len)
+    }
+
+    fn write_with_handle(&self, fh: u64, offset: usize, buf: &[u8]) -> Result<usize> {
+        let max_write = self.fuse_fs().conn.max_write();
+        let mut written_len = 0;
+        while written_len < buf.len() {
+            let size = (buf.len() - written_len).min(max_write);
+            let write_in = FuseWriteIn {
+                fh,
+                offset: (offset + written_len) as u64,
+                size: size as u32,
+                write_flags: 0,
+                lock_owner: 0,
+                flags: 0,
+                padding: 0,
+            };
+            let reply = self.request(
+                FuseOpcode::Write,
+                &[write_in.as_bytes(), &buf[written_len..written_len + size]],
+            )?;
+            let len = (parse_reply::<FuseWriteOut>(&reply)?.size as usize).min(size);
+            written_len += len;
+            if len < size {
+                break;
+            }
+        }
+
+        let mut attr = self.attr.lock();
+        attr.size = attr.size.max((offset + written_len) as u64);
+        Ok(written_len)
+    }
+
+    fn readdir_with_handle(
+        &self,
+        fh: u64,
+        dir_offsets: &Mutex<Vec<u64>>,
+        offset: usize,
+        visitor: &mut dyn DirentVisitor,
+    ) -> Result<usize> {
+        // The daemon resumes READDIR from an opaque offset returned along with
+        // the previous entry, so remember the offsets of the visited positions.
+        let mut dir_offsets = dir_offsets.lock();
+        if offset == 0 || dir_offsets.is_empty() {
+            *dir_offsets = vec![0];
+        }
+        let mut idx = offset.min(dir_offsets.len() - 1);
+        let mut read_offset = dir_offsets[idx];
+
+        loop {
+            let read_in = FuseReadIn {
+                fh,
+                offset: read_offset,
+                size: PAGE_SIZE as u32,
+                read_flags: 0,
+                lock_owner: 0,
+                flags: 0,
+                padding: 0,
+            };
+            let reply = self.request(FuseOpcode::Readdir, &[read_in.as_bytes()])?;
+            if reply.is_empty() {
+                break;
+            }
+
+            for (name, dirent) in parse_dirents(&reply)? {
+                if idx >= offset {
+                    let type_ = InodeType::try_from(dirent.type_ << 12).unwrap_or(InodeType::File);
+                    if let Err(e) = visitor.visit(name, dirent.ino, type_, idx) {
+                        if idx == offset {
+                            return Err(e);
+                        }
+                        return Ok(idx - offset);
+                    }
+                }
+                idx += 1;
+                read_offset = dirent.off;
+                dir_offsets.truncate(idx);
+                dir_offsets.push(read_offset);
+            }
+        }
+
+        Ok(idx.saturating_sub(offset))
+    }
+
+    fn create_symlink(&self, target: &str) -> Result<()> {
+        let Some((parent, name)) = self.pending_symlink.lock().take() else {
+            return_errno_with_message!(Errno::EPERM, "cannot change the target of a symlink");
+        };
+        let reply = parent.request(
+            FuseOpcode::Symlink,
+            &[&cstr_bytes(&name), &cstr_bytes(target)],
+        )?;
+        let entry = parse_reply::<FuseEntryOut>(&reply)?;
+        if entry.nodeid == 0 {
+            return_errno!(Errno::EIO);
+        }
+
+        let fs = self.fuse_fs();
+        *self.attr.lock() = entry.attr;
+        self.nlookup.store(1, Ordering::Relaxed);
+        self.nodeid.store(entry.nodeid, Ordering::Relaxed);
+        fs.inodes.lock().insert(entry.nodeid, self.this.clone());
+        Ok(())
+    }
+}
+
+impl Drop for FuseInode {
+    fn drop(&mut self) {
+        let Some(fs) = self.fs.upgrade() else {
+            return;
+        };
+        let nodeid = self.nodeid();
+        if nodeid == 0 {
+            return;
+        }
+        let handles = core::mem::take(&mut *self.handles.lock());
+        for fh in [handles.read, handles.write].into_iter().flatten() {
+            self.release(FuseOpcode::Release, fh);
+        }
+        let nlookup = self.nlookup.load(Ordering::Relaxed);
+        if nlookup > 0 {
+            let forget_in = FuseForgetIn { nlookup };
+            fs.conn
+                .request_in_background(FuseOpcode::Forget, nodeid, &[forget_in.as_bytes()]);
+        }
+        let mut inodes = fs.inodes.lock();
+        if inodes
+            .get(&nodeid)
+            .is_some_and(|inode| inode.ptr_eq(&self.this))
+        {
+            inodes.remove(&nodeid);
+        }
+    }
+}
+
+impl Inode for FuseInode {
+    fn size(&self) -> usize {
+        self.attr().size as usize
+    }
+
+    fn resize(&self, new_size: usize) -> Result<()> {
+        if self.typ != InodeType::File {
+            return_errno_with_message!(Errno::EISDIR, "not regular file");
+        }
+        self.setattr(FuseSetattrIn {
+            valid: FuseSetattrValid::SIZE.bits(),
+            size: new_size as u64,
+            ..Default::default()
+        })
+    }
+
+    fn metadata(&self) -> Metadata {
+        let attr = self.attr();
+        Metadata {
+            dev: 0,
+            ino: attr.ino,
+            size: attr.size as usize,
+            blk_size: if attr.blksize == 0 {
+                BLOCK_SIZE
+            } else {
+                attr.blksize as usize
+            },
+            blocks: attr.blocks as usize,
+            atime: Duration::new(attr.atime, attr.atimensec),
+            mtime: Duration::new(attr.mtime, attr.mtimensec),
+            ctime: Duration::new(attr.ctime, attr.ctimensec),
+            type_: self.typ,
+            mode: InodeMode::from_bits_truncate(attr.mode as u16),
+            nlinks: attr.nlink as usize,
+            uid: Uid::new(attr.uid),
+            gid: Gid::new(attr.gid),
+            rdev: attr.rdev as u64,
+        }
+    }
+
+    fn ino(&self) -> u64 {
+        self.attr.lock().ino
+    }
+
+    fn type_(&self) -> InodeType {
+        self.typ
+    }
+
+    fn mode(&self) -> Result<InodeMode> {
+        Ok(InodeMode::from_bits_truncate(self.getattr()?.mode as u16))
+    }
+
+    fn set_mode(&self, mode: InodeMode) -> Result<()> {
+        self.setattr(FuseSetattrIn {
+            valid: FuseSetattrValid::MODE.bits(),
+            mode: self.typ as u32 | mode.bits() as u32,
+            ..Default::default()
+        })
+    }
+
+    fn owner(&self) -> Result<Uid> {
+        Ok(Uid::new(self.getattr()?.uid))
+    }
+
+    fn set_owner(&self, uid: Uid) -> Result<()> {
+        self.setattr(FuseSetattrIn {
+            valid: FuseSetattrValid::UID.bits(),
+            uid: uid.as_u32(),
+            ..Default::default()
+        })
+    }
+
+    fn group(&self) -> Result<Gid> {
+        Ok(Gid::new(self.getattr()?.gid))
+    }
+
+    fn set_group(&self, gid: Gid) -> Result<()> {
+        self.setattr(FuseSetattrIn {
+            valid: FuseSetattrValid::GID.bits(),
+            gid: gid.as_u32(),
+            ..Default::default()
+        })
+    }
+
+    fn atime(&self) -> Duration {
+        let attr = self.attr();
+        Duration::new(attr.atime, attr.atimensec)
+    }
+
+    fn set_atime(&self, time: Duration) {
+        self.set_time(FuseSetattrValid::ATIME, time)
+    }
+
+    fn mtime(&self) -> Duration {
+        let attr = self.attr();
+        Duration::new(attr.mtime, attr.mtimensec)
+    }
+
+    fn set_mtime(&self, time: Duration) {
+        self.set_time(FuseSetattrValid::MTIME, time)
+    }
+
+    fn ctime(&self) -> Duration {
+        let attr = self.attr();
+        Duration::new(attr.ctime, attr.ctimensec)
+    }
+
+    fn set_ctime(&self, time: Duration) {
+        self.set_time(FuseSetattrValid::CTIME, time)
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        if self.typ != InodeType::File {
+            return_errno_with_message!(Errno::EISDIR, "read is not supported");
+        }
+
+        let fh = self.file_handle(false)?;
+        self.read_with_handle(fh, offset, buf)
+    }
+
+    fn read_direct_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        self.read_at(offset, buf)
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize> {
+        if self.typ != InodeType::File {
+            return_errno_with_message!(Errno::EISDIR, "write is not supported");
+        }
+
+        let fh = self.file_handle(true)?;
+        self.write_with_handle(fh, offset, buf)
+    }
+
+    fn write_direct_at(&self, offset: usize, buf: &[u8]) -> Result<usize> {
+        self.write_at(offset, buf)
+    }
+
+    fn create(&self, name: &str, type_: InodeType, mode: InodeMode) -> Result<Arc<dyn Inode>> {
+        if name.len() > NAME_MAX {
+            return_errno!(Errno::ENAMETOOLONG);
+        }
+        if self.typ != InodeType::Dir {
+            return_errno_with_message!(Errno::ENOTDIR, "self is not dir");
+        }
+
+        let mode_bits = type_ as u32 | mode.bits() as u32;
+        let new_inode = match type_ {
+            InodeType::File => {
+                let create_in = FuseCreateIn {
+                    flags: O_WRONLY | O_CREAT | O_EXCL,
+                    mode: mode_bits,
+                    umask: 0,
+                    open_flags: 0,
+                };
+                let reply = self.request(
+                    FuseOpcode::Create,
+                    &[create_in.as_bytes(), &cstr_bytes(name)],
+                )?;
+                let entry = parse_reply::<FuseEntryOut>(&reply)?;
+                let open_out = parse_reply::<FuseOpenOut>(
+                    reply
+                        .get(core::mem::size_of::<FuseEntryOut>()..)
+                        .unwrap_or(&[]),
+                )?;
+                let new_inode = self.fuse_fs().get_or_insert_inode(&entry)?;
+                let old_fh = new_inode.handles.lock().write.replace(open_out.fh);
+                if let Some(old_fh) = old_fh {
+                    new_inode.release(FuseOpcode::Release, old_fh);
+                }
+                new_inode
+            }
+            InodeType::Dir => {
+                let mkdir_in = FuseMkdirIn {
+                    mode: mode_bits,
+                    umask: 0,
+                };
+                self.request_entry(FuseOpcode::Mkdir, &[mkdir_in.as_bytes(), &cstr_bytes(name)])?
+            }
+            InodeType::SymLink => {
+                // The daemon creates a symlink along with its target, which is
+                // written later by `write_link`.
+                let attr = FuseAttr {
+                    mode: mode_bits,
+                    nlink: 1,
+                    ..Default::default()
+                };
+                let new_inode = FuseInode::new(0, InodeType::SymLink, attr, self.fs.clone());
+                *new_inode.pending_symlink.lock() =
+                    Some((self.this.upgrade().unwrap(), String::from(name)));
+                new_inode
+            }
+            InodeType::Socket | InodeType::NamedPipe => {
+                let mknod_in = FuseMknodIn {
+                    mode: mode_bits,
+                    rdev: 0,
+                    umask: 0,
+                    padding: 0,
+                };
+                self.request_entry(FuseOpcode::Mknod, &[mknod_in.as_bytes(), &cstr_bytes(name)])?
+            }
+            _ => return_errno_with_message!(Errno::EINVAL, "unsupported inode type"),
+        };
+        Ok(new_inode)
+    }
+
+    fn mknod(&self, name: &str, mode: InodeMode, dev: Arc<dyn Device>) -> Result<Arc<dyn Inode>> {
+        if name.len() > NAME_MAX {
+            return_errno!(Errno::ENAMETOOLONG);
+        }
+        let mknod_in = FuseMknodIn {
+            mode: InodeType::from(dev.type_()) as u32 | mode.bits() as u32,
+            rdev: u64::from(dev.id()) as u32,
+            umask: 0,
+            padding: 0,
+        };
+        let new_inode =
+            self.request_entry(FuseOpcode::Mknod, &[mknod_in.as_bytes(), &cstr_bytes(name)])?;
+        Ok(new_inode)
+    }
+
+    fn readdir_at(&self, offset: usize, visitor: &mut dyn DirentVisitor) -> Result<usize> {
+        if self.typ != InodeType::Dir {
+            return_errno_with_message!(Errno::ENOTDIR, "self is not dir");
+        }
+
+        let fh = self.open_handle(O_RDONLY)?;
+        let result = self.readdir_with_handle(fh, &self.dir_offsets, offset, visitor);
+        self.release(FuseOpcode::Releasedir, fh);
+        result
+    }
+
+    fn open(
+        &self,
+        access_mode: AccessMode,
+        status_flags: StatusFlags,
+    ) -> Result<Option<Arc<dyn FileIo>>> {
+        if !matches!(self.typ, InodeType::File | InodeType::Dir) {
+            return Ok(None);
+        }
+
+        let flags = access_mode as u32 | (status_flags & StatusFlags::O_APPEND).bits();
+        let fh = self.open_handle(flags)?;
+        self.handles.lock().open_files.insert(fh);
+        Ok(Some(Arc::new(FuseFile {
+            inode: self.this.upgrade().unwrap(),
+            fh,
+            dir_offsets: Mutex::new(Vec::new()),
+        })))
+    }
+
+    fn link(&self, old: &Arc<dyn Inode>, name: &str) -> Result<()> {
+        let old = old
+            .downcast_ref::<FuseInode>()
+            .ok_or(Error::new(Errno::EXDEV))?;
+        if !Arc::ptr_eq(&self.fuse_fs(), &old.fuse_fs()) {
+            return_errno_with_message!(Errno::EXDEV, "not same fs");
+        }
+        let link_in = FuseLinkIn {
+            oldnodeid: old.nodeid(),
+        };
+        self.request_entry(FuseOpcode::Link, &[link_in.as_bytes(), &cstr_bytes(name)])?;
+        Ok(())
+    }
+
+    fn unlink(&self, name: &str) -> Result<()> {
+        self.request(FuseOpcode::Unlink, &[&cstr_bytes(name)])?;
+        Ok(())
+    }
+
+    fn rmdir(&self, name: &str) -> Result<()> {
+        self.request(FuseOpcode::Rmdir, &[&cstr_bytes(name)])?;
+        Ok(())
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>> {
+        if self.typ != InodeType::Dir {
+            return_errno_with_message!(Errno::ENOTDIR, "self is not dir");
+        }
+        if name == "." {
+            return Ok(self.this.upgrade().unwrap());
+        }
+        let inode = self.request_entry(FuseOpcode::Lookup, &[&cstr_bytes(name)])?;
+        Ok(inode)
+    }
+
+    fn rename(&self, old_name: &str, target: &Arc<dyn Inode>, new_name: &str) -> Result<()> {
+        let target = target
+            .downcast_ref::<FuseInode>()
+            .ok_or(Error::new(Errno::EXDEV))?;
+        if !Arc::ptr_eq(&self.fuse_fs(), &target.fuse_fs()) {
+            return_errno_with_message!(Errno::EXDEV, "not same fs");
+        }
+        let rename_in = FuseRenameIn {
+            newdir: target.nodeid(),
+        };
+        self.request(
+            FuseOpcode::Rename,
+            &[
+                rename_in.as_bytes(),
+                &cstr_bytes(old_name),
+                &cstr_bytes(new_name),
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn read_link(&self) -> Result<String> {
+        if self.typ != InodeType::SymLink {
+            return_errno_with_message!(Errno::EINVAL, "self is not symlink");
+        }
+        let reply = self.request(FuseOpcode::Readlink, &[])?;
+        Ok(String::from_utf8(reply)?)
+    }
+
+    fn write_link(&self, target: &str) -> Result<()> {
+        if self.typ != InodeType::SymLink {
+            return_errno_with_message!(Errno::EINVAL, "self is not symlink");
+        }
+        self.create_symlink(target)
+    }
+
+    fn sync_all(&self) -> Result<()> {
+        self.fsync(0)
+    }
+
+    fn sync_data(&self) -> Result<()> {
+        self.fsync(FUSE_FSYNC_FDATASYNC)
+    }
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        self.fuse_fs()
+    }
+
+    /// The daemon may change the filesystem at any time, so the entries must
+    /// always be looked up from it.
+    fn is_dentry_cacheable(&self) -> bool {
+        false
+    }
+}
+
+impl FuseInode {
+    fn fsync(&self, fsync_flags: u32) -> Result<()> {
+        let Some(fh) = self.handles.lock().any() else {
+            return Ok(());
+        };
+        let fsync_in = FuseFsyncIn {
+            fh,
+            fsync_flags,
+            padding: 0,
+        };
+        match self.request(FuseOpcode::Fsync, &[fsync_in.as_bytes()]) {
+            // The daemon does not need to implement FSYNC.
+            Ok(_) => Ok(()),
+            Err(e) if e.error() == Errno::ENOSYS => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// A file opened on FUSE, which has its own file handle opened with the access
+/// mode of the file.
+struct FuseFile {
+    inode: Arc<FuseInode>,
+    fh: u64,
+    /// The READDIR offsets of the directory entries, indexed by their positions
+    dir_offsets: Mutex<Vec<u64>>,
+}
+
+impl FileIo for FuseFile {
+    // The file handle calls `read_at` and `write_at` with its offset instead.
+    fn read(&self, _buf: &mut [u8]) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the file must be read with an offset");
+    }
+
+    fn write(&self, _buf: &[u8]) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the file must be written with an offset");
+    }
+
+    fn poll(&self, mask: IoEvents, _poller: Option<&Poller>) -> IoEvents {
+        let events = IoEvents::IN | IoEvents::OUT;
+        events & mask
+    }
+
+    fn is_seekable(&self) -> bool {
+        true
+    }
+
+    fn size(&self) -> usize {
+        self.inode.size()
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        if self.inode.typ != InodeType::File {
+            return_errno_with_message!(Errno::EISDIR, "read is not supported");
+        }
+        self.inode.read_with_handle(self.fh, offset, buf)
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize> {
+        if self.inode.typ != InodeType::File {
+            return_errno_with_message!(Errno::EISDIR, "write is not supported");
+        }
+        self.inode.write_with_handle(self.fh, offset, buf)
+    }
+
+    fn readdir_at(&self, offset: usize, visitor: &mut dyn DirentVisitor) -> Result<usize> {
+        if self.inode.typ != InodeType::Dir {
+            return_errno_with_message!(Errno::ENOTDIR, "self is not dir");
+        }
+        self.inode
+            .readdir_with_handle(self.fh, &self.dir_offsets, offset, visitor)
+    }
+}
+
+impl Drop for FuseFile {
+    fn drop(&mut self) {
+        self.inode.handles.lock().open_files.remove(&self.fh);
+        let opcode = if self.inode.typ == InodeType::Dir {
+            FuseOpcode::Releasedir
+        } else {
+            FuseOpcode::Release
+        };
+        self.inode.release(opcode, self.fh);
+    }
+}
+
+fn parse_reply<T: Pod>(reply: &[u8]) -> Result<T> {
+    if reply.len() < core::mem::size_of::<T>() {
+        return_errno_with_message!(Errno::EIO, "the FUSE reply is too short");
+    }
+    Ok(T::from_bytes(reply))
+}
+
+/// Parses the directory entries replied to READDIR.
+fn parse_dirents(reply: &[u8]) -> Result<Vec<(&str, FuseDirent)>> {
+    const DIRENT_LEN: usize = core::mem::size_of::<FuseDirent>();
+
+    let mut dirents = Vec::new();
+    let mut pos = 0;
+    while pos + DIRENT_LEN <= reply.len() {
+        let dirent = FuseDirent::from_bytes(&reply[pos..pos + DIRENT_LEN]);
+        let name_start = pos + DIRENT_LEN;
+        let name_end = name_start + dirent.namelen as usize;
+        if dirent.namelen == 0 || name_end > reply.len() {
+            return_errno_with_message!(Errno::EIO, "invalid FUSE dirent");
+        }
+        let name = core::str::from_utf8(&reply[name_start..name_end])?;
+        dirents.push((name, dirent));
+        pos = name_end.next_multiple_of(8);
+    }
+    Ok(dirents)
+}
+
+fn inode_type_from_mode(mode: u32) -> Result<InodeType> {
+    InodeType::try_from(mode & 0o170000)
+        .map_err(|_| Error::with_message(Errno::EIO, "invalid file type from FUSE daemon"))
+}
+
+/// Returns the bytes of `s` with a trailing NUL, as the strings in FUSE requests.
+fn cstr_bytes(s: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(s.len() + 1);
+    bytes.extend_from_slice(s.as_bytes());
+    bytes.push(0);
+    bytes
+}
@@ -0,0 +1,30 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Filesystem in Userspace (FUSE).
+//!
+//! A FUSE filesystem forwards its operations as requests to a user-space daemon,
+//! which reads the requests from `/dev/fuse` and writes the replies back.
+//!
+//! The daemon opens `/dev/fuse` and mounts the filesystem with the file descriptor
+//! in the mount options, e.g., `fd=3,rootmode=40000,user_id=0,group_id=0`. The
+//! requests follow the protocol version 7.31 of Linux, so daemons built against
+//! libfuse or written from scratch work without changes. Closing the device
+//! aborts the connection, and unmounting the filesystem makes the daemon read
+//! `ENODEV`.
+
+pub use conn::FuseDevFile;
+pub use fs::FuseFS;
+pub use options::FuseMountOptions;
+
+mod abi;
+mod conn;
+mod fs;
+mod options;
+
+const FUSE_SUPER_MAGIC: u64 = 0x6573_7546;
+/// The node ID of the root directory.
+const FUSE_ROOT_ID: u64 = 1;
+const NAME_MAX: usize = 255;
+const BLOCK_SIZE: usize = 4096;
+/// The maximum size of the data in a READ request if `max_read` is not given.
+const FUSE_DEFAULT_MAX_READ: usize = 128 * 1024;
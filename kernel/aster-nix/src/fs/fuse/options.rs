@@ -0,0 +1,84 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use crate::{
+    fs::file_table::FileDesc,
+    prelude::*,
+    process::{Gid, Uid},
+};
+
+/// The mount options of a FUSE filesystem.
+///
+/// The options are given in the `data` argument of `mount`, e.g.,
+/// `fd=3,rootmode=40000,user_id=0,group_id=0`, where `fd` is the file
+/// descriptor of the opened `/dev/fuse`.
+#[derive(Debug, Clone)]
+pub struct FuseMountOptions {
+    /// The file descriptor of the FUSE device.
+    pub fd: FileDesc,
+    /// The file type and permission bits of the root directory.
+    pub root_mode: u32,
+    /// The user who mounts the filesystem.
+    pub user_id: Uid,
+    /// The group of the user who mounts the filesystem.
+    pub group_id: Gid,
+    /// Whether users other than the mounting one can access the filesystem.
+    pub allow_other: bool,
+    /// Whether the kernel checks the permissions instead of the daemon.
+    pub default_permissions: bool,
+    /// The maximum size of the data in a READ request.
+    pub max_read: Option<usize>,
+}
+
+impl FuseMountOptions {
+    /// Parses the options from the `data` argument of `mount`.
+    pub fn parse(data: &str) -> Result<Self> {
+        let mut fd = None;
+        let mut root_mode = None;
+        let mut user_id = None;
+        let mut group_id = None;
+        let mut allow_other = false;
+        let mut default_permissions = false;
+        let mut max_read = None;
+
+        for option in data.split(',').filter(|option| !option.is_empty()) {
+            let (key, value) = option.split_once('=').unwrap_or((option, ""));
+            match key {
+                "fd" => fd = Some(parse_number(value, 10)?),
+                "rootmode" => root_mode = Some(parse_number(value, 8)?),
+                "user_id" => user_id = Some(Uid::new(parse_number(value, 10)?)),
+                "group_id" => group_id = Some(Gid::new(parse_number(value, 10)?)),
+                "allow_other" => allow_other = true,
+                "default_permissions" => default_permissions = true,
+                "max_read" => max_read = Some(parse_number(value, 10)? as usize),
+                _ => return_errno_with_message!(Errno::EINVAL, "unknown FUSE option"),
+            }
+        }
+
+        let (Some(fd), Some(root_mode), Some(user_id), Some(group_id)) =
+            (fd, root_mode, user_id, group_id)
+        else {
+            return_errno_with_message!(
+                Errno::EINVAL,
+                "fd, rootmode, user_id and group_id are required"
+            );
+        };
+        if root_mode & 0o170000 != 0o040000 {
+            return_errno_with_message!(Errno::EINVAL, "the root must be a directory");
+        }
+
+        Ok(Self {
+            fd: fd as FileDesc,
+            root_mode,
+            user_id,
+            group_id,
+            allow_other,
+            default_permissions,
+            max_read,
+        })
+    }
+}
+
+fn parse_number(value: &str, radix: u32) -> Result<u32> {
+    u32::from_str_radix(value, radix)
+        .map_err(|_| Error::with_message(Errno::EINVAL, "invalid number in FUSE option"))
+}
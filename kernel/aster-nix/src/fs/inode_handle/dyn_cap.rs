@@ -22,11 +22,14 @@ impl InodeHandle<Rights> {
         if access_mode.is_writable() && inode.type_() == InodeType::Dir {
             return_errno_with_message!(Errno::EISDIR, "Directory cannot open to write");
         }
+        if access_mode.is_writable() {
+            inode.prepare_write()?;
+        }
 
         let file_io = if let Some(device) = inode.as_device() {
             device.open()?
         } else {
-            None
+            inode.open(access_mode, status_flags)?
         };
 
         let inner = Arc::new(InodeHandle_ {
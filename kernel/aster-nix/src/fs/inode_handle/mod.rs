@@ -13,6 +13,7 @@ use aster_rights::Rights;
 use inherit_methods_macro::inherit_methods;
 
 use crate::{
+    current_thread,
     events::IoEvents,
     fs::{
         device::Device,
@@ -24,7 +25,11 @@ use crate::{
         },
     },
     prelude::*,
-    process::{signal::Poller, Gid, Uid},
+    process::{
+        posix_thread::PosixThreadExt,
+        signal::{constants::SIGXFSZ, signals::kernel::KernelSignal, Poller},
+        Gid, ResourceType, Uid,
+    },
 };
 
 #[derive(Debug)]
@@ -43,7 +48,9 @@ struct InodeHandle_ {
 
 impl InodeHandle_ {
     pub fn read(&self, buf: &mut [u8]) -> Result<usize> {
-        if let Some(ref file_io) = self.file_io {
+        if let Some(ref file_io) = self.file_io
+            && !file_io.is_seekable()
+        {
             return file_io.read(buf);
         }
 
@@ -56,14 +63,16 @@ impl InodeHandle_ {
     }
 
     pub fn write(&self, buf: &[u8]) -> Result<usize> {
-        if let Some(ref file_io) = self.file_io {
+        if let Some(ref file_io) = self.file_io
+            && !file_io.is_seekable()
+        {
             return file_io.write(buf);
         }
 
         let mut offset = self.offset.lock();
 
         if self.status_flags().contains(StatusFlags::O_APPEND) {
-            *offset = self.dentry.size();
+            *offset = self.end_offset();
         }
 
         let len = self.write_at(*offset, buf)?;
@@ -74,7 +83,7 @@ impl InodeHandle_ {
 
     pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
         if let Some(ref file_io) = self.file_io {
-            todo!("support read_at for FileIo");
+            return file_io.read_at(offset, buf);
         }
 
         if self.status_flags().contains(StatusFlags::O_DIRECT) {
@@ -85,13 +94,14 @@ impl InodeHandle_ {
     }
 
     pub fn write_at(&self, mut offset: usize, buf: &[u8]) -> Result<usize> {
-        if let Some(ref file_io) = self.file_io {
-            todo!("support write_at for FileIo");
-        }
-
         if self.status_flags().contains(StatusFlags::O_APPEND) {
             // If the file has the O_APPEND flag, the offset is ignored
-            offset = self.dentry.size();
+            offset = self.end_offset();
+        }
+
+        let buf = self.check_file_size_limit(offset, buf)?;
+        if let Some(ref file_io) = self.file_io {
+            return file_io.write_at(offset, buf);
         }
 
         if self.status_flags().contains(StatusFlags::O_DIRECT) {
@@ -101,9 +111,42 @@ impl InodeHandle_ {
         }
     }
 
+    /// Truncates the write at `offset` of a regular file to the `RLIMIT_FSIZE` of the
+    /// current process.
+    ///
+    /// If the write starts at or beyond the limit, the current thread receives
+    /// `SIGXFSZ` and this method fails with `EFBIG`.
+    fn check_file_size_limit<'a>(&self, offset: usize, buf: &'a [u8]) -> Result<&'a [u8]> {
+        if self.dentry.type_() != InodeType::File || buf.is_empty() {
+            return Ok(buf);
+        }
+
+        let max_file_size = current!()
+            .resource_limits()
+            .lock()
+            .get_rlimit(ResourceType::RLIMIT_FSIZE)
+            .get_cur();
+        if offset as u64 >= max_file_size {
+            let current_thread = current_thread!();
+            let posix_thread = current_thread.as_posix_thread().unwrap();
+            posix_thread.enqueue_signal(Box::new(KernelSignal::new(SIGXFSZ)));
+            return_errno_with_message!(Errno::EFBIG, "the file size limit is reached");
+        }
+
+        let len = buf.len().min((max_file_size - offset as u64) as usize);
+        Ok(&buf[..len])
+    }
+
     pub fn read_to_end(&self, buf: &mut Vec<u8>) -> Result<usize> {
-        if self.file_io.is_some() {
-            return_errno_with_message!(Errno::EINVAL, "file io does not support read to end");
+        if let Some(ref file_io) = self.file_io {
+            if !file_io.is_seekable() {
+                return_errno_with_message!(Errno::EINVAL, "file io does not support read to end");
+            }
+            let start = buf.len();
+            buf.resize(start + file_io.size(), 0);
+            let len = file_io.read_at(0, &mut buf[start..])?;
+            buf.truncate(start + len);
+            return Ok(len);
         }
 
         let len = if self.status_flags().contains(StatusFlags::O_DIRECT) {
@@ -124,7 +167,7 @@ impl InodeHandle_ {
                 off as isize
             }
             SeekFrom::End(off /* as isize */) => {
-                let file_size = self.dentry.size() as isize;
+                let file_size = self.end_offset() as isize;
                 assert!(file_size >= 0);
                 file_size
                     .checked_add(off)
@@ -148,6 +191,17 @@ impl InodeHandle_ {
         *offset
     }
 
+    /// Returns the offset of the end of the file, which is used by `SEEK_END` and `O_APPEND`.
+    fn end_offset(&self) -> usize {
+        if let Some(ref file_io) = self.file_io
+            && file_io.is_seekable()
+        {
+            return file_io.size();
+        }
+
+        self.dentry.size()
+    }
+
     pub fn resize(&self, new_size: usize) -> Result<()> {
         if self.status_flags().contains(StatusFlags::O_APPEND) {
             return_errno_with_message!(Errno::EPERM, "can not resize append-only file");
@@ -171,7 +225,11 @@ impl InodeHandle_ {
 
     pub fn readdir(&self, visitor: &mut dyn DirentVisitor) -> Result<usize> {
         let mut offset = self.offset.lock();
-        let read_cnt = self.dentry.inode().readdir_at(*offset, visitor)?;
+        let read_cnt = if let Some(ref file_io) = self.file_io {
+            file_io.readdir_at(*offset, visitor)?
+        } else {
+            self.dentry.inode().readdir_at(*offset, visitor)?
+        };
         *offset += read_cnt;
         Ok(read_cnt)
     }
@@ -221,9 +279,13 @@ impl<R> InodeHandle<R> {
     pub fn dentry(&self) -> &Arc<Dentry> {
         &self.0.dentry
     }
+
+    pub fn file_io(&self) -> Option<&Arc<dyn FileIo>> {
+        self.0.file_io.as_ref()
+    }
 }
 
-pub trait FileIo: Send + Sync + 'static {
+pub trait FileIo: Any + Send + Sync + 'static {
     fn read(&self, buf: &mut [u8]) -> Result<usize>;
 
     fn write(&self, buf: &[u8]) -> Result<usize>;
@@ -233,4 +295,36 @@ pub trait FileIo: Send + Sync + 'static {
     fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
         return_errno_with_message!(Errno::EINVAL, "ioctl is not supported");
     }
+
+    /// Returns whether the file supports random access, e.g., a block device.
+    ///
+    /// The offset of a seekable file is maintained by the file handle,
+    /// which calls `read_at` and `write_at` instead of `read` and `write`.
+    fn is_seekable(&self) -> bool {
+        false
+    }
+
+    /// Returns the size of a seekable file.
+    fn size(&self) -> usize {
+        0
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        return_errno_with_message!(Errno::ESPIPE, "the file is not seekable");
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize> {
+        return_errno_with_message!(Errno::ESPIPE, "the file is not seekable");
+    }
+
+    /// Reads the entries of a directory opened with its own state, see `Inode::open`.
+    fn readdir_at(&self, offset: usize, visitor: &mut dyn DirentVisitor) -> Result<usize> {
+        return_errno_with_message!(Errno::ENOTDIR, "the file is not a directory");
+    }
+}
+
+impl dyn FileIo {
+    pub fn downcast_ref<T: FileIo>(&self) -> Option<&T> {
+        (self as &dyn Any).downcast_ref::<T>()
+    }
 }
@@ -1,66 +1,112 @@
 // SPDX-License-Identifier: MPL-2.0
+pub mod cgroupfs;
 pub mod device;
 pub mod devpts;
+pub mod devtmpfs;
 pub mod epoll;
+pub mod erofs;
 pub mod exfat;
 pub mod ext2;
 pub mod file_handle;
 pub mod file_table;
 pub mod fs_resolver;
+pub mod fuse;
 pub mod inode_handle;
+pub mod overlayfs;
+pub mod p9fs;
 pub mod path;
 pub mod pipe;
 pub mod procfs;
 pub mod ramfs;
 pub mod rootfs;
+pub mod squashfs;
+pub mod sysfs;
 pub mod utils;
+pub mod vfat;
 
 use aster_block::BlockDevice;
-use aster_virtio::device::block::device::BlockDevice as VirtIoBlockDevice;
 
 use crate::{
     fs::{
+        erofs::ErofsFS,
         exfat::{ExfatFS, ExfatMountOptions},
-        ext2::Ext2,
+        ext2::{Ext2, Ext2MountOptions},
         fs_resolver::FsPath,
+        squashfs::SquashFS,
+        utils::FileSystem,
+        vfat::VfatFS,
     },
     prelude::*,
-    thread::kernel_thread::KernelThreadExt,
 };
 
-fn start_block_device(device_name: &str) -> Result<Arc<dyn BlockDevice>> {
-    if let Some(device) = aster_block::get_device(device_name) {
-        let cloned_device = device.clone();
-        let task_fn = move || {
-            info!("spawn the virt-io-block thread");
-            let virtio_block_device = cloned_device.downcast_ref::<VirtIoBlockDevice>().unwrap();
-            loop {
-                virtio_block_device.handle_requests();
-            }
-        };
-        crate::Thread::spawn_kernel_thread(crate::ThreadOptions::new(task_fn));
-        Ok(device)
-    } else {
-        return_errno_with_message!(Errno::ENOENT, "Device does not exist")
-    }
+fn get_block_device(device_name: &str) -> Result<Arc<dyn BlockDevice>> {
+    aster_block::get_device(device_name)
+        .ok_or_else(|| Error::with_message(Errno::ENOENT, "Device does not exist"))
+}
+
+/// Opens the filesystem of `fs_type` on the block device.
+///
+/// The mount options in `data` are only understood by Ext2.
+pub fn open_block_fs(
+    fs_type: &str,
+    device: Arc<dyn BlockDevice>,
+    data: &str,
+) -> Result<Arc<dyn FileSystem>> {
+    let fs: Arc<dyn FileSystem> = match fs_type {
+        "ext2" => Ext2::open(device, Ext2MountOptions::parse(data)?)?,
+        "exfat" => ExfatFS::open(device, ExfatMountOptions::default())?,
+        "vfat" => VfatFS::open(device)?,
+        "squashfs" => SquashFS::open(device)?,
+        "erofs" => ErofsFS::open(device)?,
+        _ => return_errno_with_message!(Errno::EINVAL, "Invalid fs type"),
+    };
+    Ok(fs)
 }
 
 pub fn lazy_init() {
+    if let Err(e) = crate::device::block::init() {
+        warn!("failed to create the block device nodes: {:?}", e);
+    }
+
+    // The root device is left to the init in the initramfs if `rdinit=` is given.
+    let karg = ostd::boot::kernel_cmdline();
+    if karg.get_rdinit_path().is_none()
+        && let Some(device) = karg.get_root_device()
+    {
+        if let Err(e) = rootfs::mount_root(
+            device,
+            karg.get_root_fstype(),
+            karg.get_root_flags().unwrap_or(""),
+            karg.is_root_read_only(),
+        ) {
+            panic!("failed to mount the root device {}: {:?}", device, e);
+        }
+        return;
+    }
+
     //The device name is specified in qemu args as --serial={device_name}
     let ext2_device_name = "vext2";
     let exfat_device_name = "vexfat";
+    let vfat_device_name = "vvfat";
 
-    if let Ok(block_device_ext2) = start_block_device(ext2_device_name) {
-        let ext2_fs = Ext2::open(block_device_ext2).unwrap();
+    if let Ok(block_device_ext2) = get_block_device(ext2_device_name) {
+        let ext2_fs = Ext2::open(block_device_ext2, Ext2MountOptions::default()).unwrap();
         let target_path = FsPath::try_from("/ext2").unwrap();
         println!("[kernel] Mount Ext2 fs at {:?} ", target_path);
         self::rootfs::mount_fs_at(ext2_fs, &target_path).unwrap();
     }
 
-    if let Ok(block_device_exfat) = start_block_device(exfat_device_name) {
+    if let Ok(block_device_exfat) = get_block_device(exfat_device_name) {
         let exfat_fs = ExfatFS::open(block_device_exfat, ExfatMountOptions::default()).unwrap();
         let target_path = FsPath::try_from("/exfat").unwrap();
         println!("[kernel] Mount ExFat fs at {:?} ", target_path);
         self::rootfs::mount_fs_at(exfat_fs, &target_path).unwrap();
     }
+
+    if let Ok(block_device_vfat) = get_block_device(vfat_device_name) {
+        let vfat_fs = VfatFS::open(block_device_vfat).unwrap();
+        let target_path = FsPath::try_from("/vfat").unwrap();
+        println!("[kernel] Mount Vfat fs at {:?} ", target_path);
+        self::rootfs::mount_fs_at(vfat_fs, &target_path).unwrap();
+    }
 }
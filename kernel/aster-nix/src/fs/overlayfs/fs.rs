@@ -0,0 +1,1158 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::{
+    sync::atomic::{AtomicU32, AtomicU64, Ordering},
+    time::Duration,
+};
+
+use aster_rights::Full;
+
+use super::*;
+use crate::{
+    device::{get_device, Whiteout},
+    events::IoEvents,
+    fs::{
+        device::{Device, DeviceId},
+        inode_handle::FileIo,
+        utils::{
+            AccessMode, DirentVisitor, FileSystem, FsFlags, Inode, InodeMode, InodeType, IoctlCmd,
+            Metadata, StatusFlags, SuperBlock,
+        },
+    },
+    prelude::*,
+    process::{signal::Poller, Gid, Uid},
+    vm::vmo::Vmo,
+};
+
+/// The next minor number of the anonymous devices of the overlays, whose major number is 0.
+static ANON_DEV_MINOR: AtomicU32 = AtomicU32::new(1);
+
+/// The bits of an inode number that hold the inode number in a layer.
+const XINO_SHIFT: u32 = 48;
+
+/// A union filesystem that merges an upper layer and several lower layers.
+pub struct OverlayFS {
+    /// The upper layer, absent if the overlay is read-only
+    upper: Option<UpperLayer>,
+    /// The root directories of the lower layers, from the top-most one
+    lowers: Vec<Arc<dyn Inode>>,
+    /// Root inode
+    root: Arc<OverlayInode>,
+    /// The distinct filesystems of the layers, from the upper one
+    layer_fss: Vec<Arc<dyn FileSystem>>,
+    /// The anonymous device number of the overlay
+    dev: u64,
+    /// An inode allocator for the inodes that cannot be numbered by their origins
+    inode_allocator: AtomicU64,
+    /// A global lock for the operations that change the directory tree
+    mutex: Mutex<()>,
+}
+
+struct UpperLayer {
+    /// The root directory of the upper layer
+    root: Arc<dyn Inode>,
+    /// The directory to prepare copy-ups in, on the same filesystem as `root`
+    work: Arc<dyn Inode>,
+}
+
+impl OverlayFS {
+    /// Creates an overlay filesystem.
+    ///
+    /// `lowers` are the lower directories from the top-most one. If `upper` is
+    /// `None`, the overlay filesystem is read-only; otherwise `work` must be
+    /// given and be on the same filesystem as `upper`.
+    pub fn new(
+        upper: Option<Arc<dyn Inode>>,
+        work: Option<Arc<dyn Inode>>,
+        lowers: Vec<Arc<dyn Inode>>,
+    ) -> Result<Arc<Self>> {
+        if lowers.is_empty() {
+            return_errno_with_message!(Errno::EINVAL, "no lower layer");
+        }
+        if lowers.iter().any(|lower| lower.type_() != InodeType::Dir) {
+            return_errno_with_message!(Errno::ENOTDIR, "lower layer is not dir");
+        }
+        let upper = match (upper, work) {
+            (Some(upper), Some(work)) => {
+                if upper.type_() != InodeType::Dir || work.type_() != InodeType::Dir {
+                    return_errno_with_message!(Errno::ENOTDIR, "upper or work is not dir");
+                }
+                if !Arc::ptr_eq(&upper.fs(), &work.fs()) {
+                    return_errno_with_message!(Errno::EINVAL, "upper and work are not on same fs");
+                }
+                if upper.ino() == work.ino() {
+                    return_errno_with_message!(Errno::EINVAL, "upper and work are the same dir");
+                }
+                let work = prepare_work_dir(&work)?;
+                Some(UpperLayer { root: upper, work })
+            }
+            (None, None) => None,
+            _ => return_errno_with_message!(Errno::EINVAL, "upper and work must be given together"),
+        };
+
+        let mut layer_fss: Vec<Arc<dyn FileSystem>> = Vec::new();
+        for layer in upper.iter().map(|upper| &upper.root).chain(lowers.iter()) {
+            let fs = layer.fs();
+            if !layer_fss.iter().any(|layer_fs| Arc::ptr_eq(layer_fs, &fs)) {
+                layer_fss.push(fs);
+            }
+        }
+        let dev = DeviceId::new(0, ANON_DEV_MINOR.fetch_add(1, Ordering::Relaxed)).into();
+
+        Ok(Arc::new_cyclic(|weak_fs| {
+            let root = Arc::new_cyclic(|weak_root| OverlayInode {
+                ino: ROOT_INO,
+                typ: InodeType::Dir,
+                upper: Mutex::new(upper.as_ref().map(|upper| upper.root.clone())),
+                lowers: lowers.clone(),
+                location: Mutex::new(None),
+                children: Mutex::new(BTreeMap::new()),
+                this: weak_root.clone(),
+                fs: weak_fs.clone(),
+            });
+            Self {
+                upper,
+                lowers,
+                root,
+                layer_fss,
+                dev,
+                inode_allocator: AtomicU64::new(ROOT_INO + 1),
+                mutex: Mutex::new(()),
+            }
+        }))
+    }
+
+    fn alloc_id(&self) -> u64 {
+        self.inode_allocator.fetch_add(1, Ordering::SeqCst)
+    }
+
+    /// Returns the inode number of an overlay inode from the inode it originates from,
+    /// i.e., the top-most lower inode or, if there is none, the upper inode.
+    ///
+    /// Like `xino` of Linux, the number is made of the index of the filesystem of the
+    /// origin and the origin's own number, so it is unique in the overlay and does not
+    /// change when the inode is copied up or looked up again.
+    fn inode_number(&self, origin: &Arc<dyn Inode>) -> u64 {
+        let origin_fs = origin.fs();
+        let layer_index = self
+            .layer_fss
+            .iter()
+            .position(|layer_fs| Arc::ptr_eq(layer_fs, &origin_fs));
+        match layer_index {
+            Some(index) if origin.ino() < 1 << XINO_SHIFT => {
+                ((index as u64 + 1) << XINO_SHIFT) | origin.ino()
+            }
+            _ => self.alloc_id(),
+        }
+    }
+
+    fn work_dir(&self) -> Result<&Arc<dyn Inode>> {
+        match &self.upper {
+            Some(upper) => Ok(&upper.work),
+            None => return_errno_with_message!(Errno::EROFS, "overlay is read-only"),
+        }
+    }
+
+    /// Copies `lower` into the work directory and then moves it to `upper_parent` as `name`.
+    fn copy_up(
+        &self,
+        ino: u64,
+        lower: &Arc<dyn Inode>,
+        upper_parent: &Arc<dyn Inode>,
+        name: &str,
+    ) -> Result<Arc<dyn Inode>> {
+        let work = self.work_dir()?;
+        let tmp_name = format!("#{:x}", ino);
+        let tmp = self.copy_inode(lower, work, &tmp_name);
+        let copied = tmp.and_then(|tmp| {
+            work.rename(&tmp_name, upper_parent, name)?;
+            Ok(tmp)
+        });
+        if copied.is_err() {
+            let _ = remove_all(work, &tmp_name);
+        }
+        copied
+    }
+
+    fn copy_inode(
+        &self,
+        src: &Arc<dyn Inode>,
+        dir: &Arc<dyn Inode>,
+        name: &str,
+    ) -> Result<Arc<dyn Inode>> {
+        let metadata = src.metadata();
+        let dst = match metadata.type_ {
+            InodeType::File => {
+                let dst = dir.create(name, InodeType::File, metadata.mode)?;
+                copy_data(src, &dst, metadata.size)?;
+                dst
+            }
+            InodeType::Dir => dir.create(name, InodeType::Dir, metadata.mode)?,
+            InodeType::SymLink => {
+                let dst = dir.create(name, InodeType::SymLink, metadata.mode)?;
+                dst.write_link(&src.read_link()?)?;
+                dst
+            }
+            InodeType::CharDevice | InodeType::BlockDevice => {
+                let device = match src.as_device() {
+                    Some(device) => device,
+                    None => {
+                        let type_ = metadata.type_.device_type().unwrap();
+                        get_device(type_, metadata.rdev as usize)?
+                    }
+                };
+                dir.mknod(name, metadata.mode, device)?
+            }
+            _ => return_errno_with_message!(Errno::EPERM, "copy-up is not supported"),
+        };
+        dst.set_owner(metadata.uid)?;
+        dst.set_group(metadata.gid)?;
+        dst.set_atime(metadata.atime);
+        dst.set_mtime(metadata.mtime);
+        Ok(dst)
+    }
+}
+
+impl FileSystem for OverlayFS {
+    fn sync(&self) -> Result<()> {
+        match &self.upper {
+            Some(upper) => upper.root.fs().sync(),
+            None => Ok(()),
+        }
+    }
+
+    fn root_inode(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+
+    fn sb(&self) -> SuperBlock {
+        // Report the usage of the layer that takes new files.
+        let layer = match &self.upper {
+            Some(upper) => &upper.root,
+            None => &self.lowers[0],
+        };
+        let mut sb = layer.fs().sb();
+        sb.magic = OVERLAYFS_MAGIC;
+        sb.namelen = sb.namelen.min(NAME_MAX);
+        sb
+    }
+
+    fn flags(&self) -> FsFlags {
+        FsFlags::empty()
+    }
+
+    fn name(&self) -> &'static str {
+        "overlay"
+    }
+}
+
+struct OverlayInode {
+    /// Inode number
+    ino: u64,
+    /// Type of the inode
+    typ: InodeType,
+    /// The inode in the upper layer, which is set by copy-up
+    upper: Mutex<Option<Arc<dyn Inode>>>,
+    /// The inodes in the lower layers, from the top-most one.
+    ///
+    /// A directory has all the merged directories here, while other types of
+    /// inodes have at most one.
+    lowers: Vec<Arc<dyn Inode>>,
+    /// The parent and the name of this inode, which is `None` for the root
+    location: Mutex<Option<(Arc<OverlayInode>, String)>>,
+    /// The children that have been looked up
+    children: Mutex<BTreeMap<String, Weak<OverlayInode>>>,
+    /// Reference to self
+    this: Weak<OverlayInode>,
+    /// Reference to fs
+    fs: Weak<OverlayFS>,
+}
+
+impl OverlayInode {
+    fn new(
+        fs: &Arc<OverlayFS>,
+        upper: Option<Arc<dyn Inode>>,
+        lowers: Vec<Arc<dyn Inode>>,
+        location: (Arc<OverlayInode>, String),
+    ) -> Arc<Self> {
+        let origin = lowers.first().or(upper.as_ref()).unwrap();
+        let typ = upper.as_ref().unwrap_or(origin).type_();
+        let ino = fs.inode_number(origin);
+        Arc::new_cyclic(|weak_self| OverlayInode {
+            ino,
+            typ,
+            upper: Mutex::new(upper),
+            lowers,
+            location: Mutex::new(Some(location)),
+            children: Mutex::new(BTreeMap::new()),
+            this: weak_self.clone(),
+            fs: Arc::downgrade(fs),
+        })
+    }
+
+    fn overlay_fs(&self) -> Arc<OverlayFS> {
+        self.fs.upgrade().unwrap()
+    }
+
+    fn upper(&self) -> Option<Arc<dyn Inode>> {
+        self.upper.lock().clone()
+    }
+
+    /// Returns the inode that currently provides the content.
+    fn real(&self) -> Arc<dyn Inode> {
+        self.upper().unwrap_or_else(|| self.lowers[0].clone())
+    }
+
+    /// Returns the lower directories that are merged into this directory.
+    fn merged_lowers(&self) -> Result<&[Arc<dyn Inode>]> {
+        match self.upper() {
+            Some(upper) if is_opaque(&upper)? => Ok(&[]),
+            _ => Ok(&self.lowers),
+        }
+    }
+
+    /// Copies this inode and all its ancestors up to the upper layer.
+    fn copy_up(&self) -> Result<Arc<dyn Inode>> {
+        let mut upper = self.upper.lock();
+        if let Some(upper) = upper.as_ref() {
+            return Ok(upper.clone());
+        }
+
+        let fs = self.overlay_fs();
+        fs.work_dir()?;
+        let (parent, name) = self.location.lock().clone().unwrap();
+        let upper_parent = parent.copy_up()?;
+        let new_upper = fs.copy_up(self.ino, &self.lowers[0], &upper_parent, &name)?;
+        *upper = Some(new_upper.clone());
+        Ok(new_upper)
+    }
+
+    /// Looks up `name` in all the layers.
+    ///
+    /// Returns the inode in the upper layer and the inodes in the lower layers,
+    /// or `None` if there is no such file.
+    fn lookup_layers(
+        &self,
+        name: &str,
+    ) -> Result<Option<(Option<Arc<dyn Inode>>, Vec<Arc<dyn Inode>>)>> {
+        if name == OPAQUE_MARKER {
+            return Ok(None);
+        }
+
+        let mut upper_child = None;
+        if let Some(upper) = self.upper() {
+            match lookup_or_none(&upper, name)? {
+                Some(child) if is_whiteout(&child) => return Ok(None),
+                Some(child) => {
+                    if child.type_() != InodeType::Dir || is_opaque(&child)? {
+                        return Ok(Some((Some(child), Vec::new())));
+                    }
+                    upper_child = Some(child);
+                }
+                None => {}
+            }
+        }
+
+        let mut lower_children: Vec<Arc<dyn Inode>> = Vec::new();
+        for lower in self.merged_lowers()? {
+            let Some(child) = lookup_or_none(lower, name)? else {
+                continue;
+            };
+            if is_whiteout(&child) {
+                break;
+            }
+            let is_topmost = upper_child.is_none() && lower_children.is_empty();
+            // Only directories are merged, a non-directory hides everything below it.
+            if child.type_() != InodeType::Dir {
+                if is_topmost {
+                    lower_children.push(child);
+                }
+                break;
+            }
+            let is_opaque = is_opaque(&child)?;
+            lower_children.push(child);
+            if is_opaque {
+                break;
+            }
+        }
+
+        if upper_child.is_none() && lower_children.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some((upper_child, lower_children)))
+    }
+
+    fn find(&self, name: &str) -> Result<Arc<Self>> {
+        if self.typ != InodeType::Dir {
+            return_errno_with_message!(Errno::ENOTDIR, "self is not dir");
+        }
+        if name == "." {
+            return Ok(self.this.upgrade().unwrap());
+        }
+        if name == ".." {
+            return Ok(match self.location.lock().as_ref() {
+                Some((parent, _)) => parent.clone(),
+                None => self.this.upgrade().unwrap(),
+            });
+        }
+
+        let mut children = self.children.lock();
+        if let Some(child) = children.get(name).and_then(Weak::upgrade) {
+            return Ok(child);
+        }
+        let Some((upper, lowers)) = self.lookup_layers(name)? else {
+            children.remove(name);
+            return_errno!(Errno::ENOENT);
+        };
+        let child = OverlayInode::new(
+            &self.overlay_fs(),
+            upper,
+            lowers,
+            (self.this.upgrade().unwrap(), String::from(name)),
+        );
+        children.insert(String::from(name), Arc::downgrade(&child));
+        Ok(child)
+    }
+
+    fn find_or_none(&self, name: &str) -> Result<Option<Arc<Self>>> {
+        match self.find(name) {
+            Ok(child) => Ok(Some(child)),
+            Err(e) if e.error() == Errno::ENOENT => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Returns the entries of this directory merged from all the layers,
+    /// excluding "." and "..".
+    fn merged_entries(&self) -> Result<Vec<(String, u64, InodeType)>> {
+        let mut entries = Vec::new();
+        let mut visited_names = BTreeSet::new();
+        let upper = self.upper();
+        for layer in upper.iter().chain(self.merged_lowers()?) {
+            for (name, ino, type_) in read_entries(layer)? {
+                if name == "." || name == ".." || name == OPAQUE_MARKER {
+                    continue;
+                }
+                // An entry hides the entries of the same name in the layers below,
+                // and so does a whiteout.
+                if !visited_names.insert(name.clone()) {
+                    continue;
+                }
+                if type_ == InodeType::CharDevice && is_whiteout(&layer.lookup(&name)?) {
+                    continue;
+                }
+                entries.push((name, ino, type_));
+            }
+        }
+        Ok(entries)
+    }
+
+    fn is_empty_dir(&self) -> Result<bool> {
+        Ok(self.merged_entries()?.is_empty())
+    }
+
+    /// Adds a child created in the upper layer.
+    fn add_child(&self, name: &str, upper: Arc<dyn Inode>) -> Arc<Self> {
+        let child = OverlayInode::new(
+            &self.overlay_fs(),
+            Some(upper),
+            Vec::new(),
+            (self.this.upgrade().unwrap(), String::from(name)),
+        );
+        self.children
+            .lock()
+            .insert(String::from(name), Arc::downgrade(&child));
+        child
+    }
+
+    /// Hides the child named `name` in the lower layers, if it exists there.
+    fn hide_lower(&self, upper_self: &Arc<dyn Inode>, child: &Self, name: &str) -> Result<()> {
+        if !child.lowers.is_empty() {
+            upper_self.mknod(name, InodeMode::empty(), Arc::new(Whiteout))?;
+        }
+        Ok(())
+    }
+}
+
+impl Inode for OverlayInode {
+    fn size(&self) -> usize {
+        self.real().size()
+    }
+
+    fn resize(&self, new_size: usize) -> Result<()> {
+        if self.typ != InodeType::File {
+            return_errno_with_message!(Errno::EISDIR, "not regular file");
+        }
+        if self.upper().is_none() && self.size() == new_size {
+            return Ok(());
+        }
+        self.copy_up()?.resize(new_size)
+    }
+
+    fn metadata(&self) -> Metadata {
+        let mut metadata = self.real().metadata();
+        metadata.dev = self.overlay_fs().dev;
+        metadata.ino = self.ino;
+        metadata
+    }
+
+    fn ino(&self) -> u64 {
+        self.ino
+    }
+
+    fn type_(&self) -> InodeType {
+        self.typ
+    }
+
+    fn mode(&self) -> Result<InodeMode> {
+        self.real().mode()
+    }
+
+    fn set_mode(&self, mode: InodeMode) -> Result<()> {
+        self.copy_up()?.set_mode(mode)
+    }
+
+    fn owner(&self) -> Result<Uid> {
+        self.real().owner()
+    }
+
+    fn set_owner(&self, uid: Uid) -> Result<()> {
+        self.copy_up()?.set_owner(uid)
+    }
+
+    fn group(&self) -> Result<Gid> {
+        self.real().group()
+    }
+
+    fn set_group(&self, gid: Gid) -> Result<()> {
+        self.copy_up()?.set_group(gid)
+    }
+
+    fn atime(&self) -> Duration {
+        self.real().atime()
+    }
+
+    fn set_atime(&self, time: Duration) {
+        match self.copy_up() {
+            Ok(upper) => upper.set_atime(time),
+            Err(e) => warn!("failed to copy up: {:?}", e),
+        }
+    }
+
+    fn mtime(&self) -> Duration {
+        self.real().mtime()
+    }
+
+    fn set_mtime(&self, time: Duration) {
+        match self.copy_up() {
+            Ok(upper) => upper.set_mtime(time),
+            Err(e) => warn!("failed to copy up: {:?}", e),
+        }
+    }
+
+    fn ctime(&self) -> Duration {
+        self.real().ctime()
+    }
+
+    fn set_ctime(&self, time: Duration) {
+        match self.copy_up() {
+            Ok(upper) => upper.set_ctime(time),
+            Err(e) => warn!("failed to copy up: {:?}", e),
+        }
+    }
+
+    fn page_cache(&self) -> Option<Vmo<Full>> {
+        self.real().page_cache()
+    }
+
+    fn prepare_write(&self) -> Result<()> {
+        if self.typ != InodeType::File {
+            return Ok(());
+        }
+        self.copy_up()?;
+        Ok(())
+    }
+
+    fn open(
+        &self,
+        access_mode: AccessMode,
+        status_flags: StatusFlags,
+    ) -> Result<Option<Arc<dyn FileIo>>> {
+        // The entries of a directory are merged from the layers by the overlay itself.
+        if self.typ == InodeType::Dir {
+            return Ok(None);
+        }
+        self.real().open(access_mode, status_flags)
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        self.real().read_at(offset, buf)
+    }
+
+    fn read_direct_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        self.real().read_direct_at(offset, buf)
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize> {
+        self.copy_up()?.write_at(offset, buf)
+    }
+
+    fn write_direct_at(&self, offset: usize, buf: &[u8]) -> Result<usize> {
+        self.copy_up()?.write_direct_at(offset, buf)
+    }
+
+    fn create(&self, name: &str, type_: InodeType, mode: InodeMode) -> Result<Arc<dyn Inode>> {
+        if name.len() > NAME_MAX {
+            return_errno!(Errno::ENAMETOOLONG);
+        }
+        let fs = self.overlay_fs();
+        let _guard = fs.mutex.lock();
+        if self.find_or_none(name)?.is_some() {
+            return_errno_with_message!(Errno::EEXIST, "entry exists");
+        }
+
+        let upper_self = self.copy_up()?;
+        let has_whiteout = remove_whiteout(&upper_self, name)?;
+        let new_upper = upper_self.create(name, type_, mode)?;
+        // The new directory must not be merged with the removed one below.
+        if type_ == InodeType::Dir && has_whiteout {
+            set_opaque(&new_upper)?;
+        }
+        Ok(self.add_child(name, new_upper))
+    }
+
+    fn mknod(&self, name: &str, mode: InodeMode, dev: Arc<dyn Device>) -> Result<Arc<dyn Inode>> {
+        if name.len() > NAME_MAX {
+            return_errno!(Errno::ENAMETOOLONG);
+        }
+        let fs = self.overlay_fs();
+        let _guard = fs.mutex.lock();
+        if self.find_or_none(name)?.is_some() {
+            return_errno_with_message!(Errno::EEXIST, "entry exists");
+        }
+
+        let upper_self = self.copy_up()?;
+        remove_whiteout(&upper_self, name)?;
+        let new_upper = upper_self.mknod(name, mode, dev)?;
+        Ok(self.add_child(name, new_upper))
+    }
+
+    fn as_device(&self) -> Option<Arc<dyn Device>> {
+        self.real().as_device()
+    }
+
+    fn readdir_at(&self, offset: usize, visitor: &mut dyn DirentVisitor) -> Result<usize> {
+        if self.typ != InodeType::Dir {
+            return_errno_with_message!(Errno::ENOTDIR, "self is not dir");
+        }
+
+        // Report the numbers of the overlay inodes, which are the same as those in `stat`.
+        let entries = self
+            .merged_entries()?
+            .into_iter()
+            .map(|(name, _, type_)| {
+                let ino = self.find(&name)?.ino;
+                Ok((name, ino, type_))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let try_visit = |idx: &mut usize, visitor: &mut dyn DirentVisitor| -> Result<()> {
+            // Read the two special entries("." and "..").
+            if *idx == 0 {
+                visitor.visit(".", self.ino, self.typ, *idx)?;
+                *idx += 1;
+            }
+            if *idx == 1 {
+                let parent_ino = match self.location.lock().as_ref() {
+                    Some((parent, _)) => parent.ino,
+                    None => self.ino,
+                };
+                visitor.visit("..", parent_ino, InodeType::Dir, *idx)?;
+                *idx += 1;
+            }
+            // Read the merged entries.
+            for (name, ino, type_) in entries.iter().skip(*idx - 2) {
+                visitor.visit(name, *ino, *type_, *idx)?;
+                *idx += 1;
+            }
+            Ok(())
+        };
+
+        let mut iterate_idx = offset;
+        match try_visit(&mut iterate_idx, visitor) {
+            Err(e) if iterate_idx == offset => Err(e),
+            _ => Ok(iterate_idx - offset),
+        }
+    }
+
+    fn link(&self, old: &Arc<dyn Inode>, name: &str) -> Result<()> {
+        let old = old
+            .downcast_ref::<OverlayInode>()
+            .ok_or(Error::new(Errno::EXDEV))?;
+        if !Arc::ptr_eq(&self.overlay_fs(), &old.overlay_fs()) {
+            return_errno_with_message!(Errno::EXDEV, "not same fs");
+        }
+        if old.typ == InodeType::Dir {
+            return_errno_with_message!(Errno::EPERM, "old is a dir");
+        }
+        let fs = self.overlay_fs();
+        let _guard = fs.mutex.lock();
+        if self.find_or_none(name)?.is_some() {
+            return_errno_with_message!(Errno::EEXIST, "entry exists");
+        }
+
+        let upper_old = old.copy_up()?;
+        let upper_self = self.copy_up()?;
+        remove_whiteout(&upper_self, name)?;
+        upper_self.link(&upper_old, name)
+    }
+
+    fn unlink(&self, name: &str) -> Result<()> {
+        if name == "." || name == ".." {
+            return_errno_with_message!(Errno::EISDIR, "unlink . or ..");
+        }
+        let fs = self.overlay_fs();
+        let _guard = fs.mutex.lock();
+        let target = self.find(name)?;
+        if target.typ == InodeType::Dir {
+            return_errno_with_message!(Errno::EISDIR, "unlink on dir");
+        }
+
+        let upper_self = self.copy_up()?;
+        if target.upper().is_some() {
+            upper_self.unlink(name)?;
+        }
+        self.hide_lower(&upper_self, &target, name)?;
+        self.children.lock().remove(name);
+        Ok(())
+    }
+
+    fn rmdir(&self, name: &str) -> Result<()> {
+        if name == "." {
+            return_errno_with_message!(Errno::EINVAL, "rmdir on .");
+        }
+        if name == ".." {
+            return_errno_with_message!(Errno::ENOTEMPTY, "rmdir on ..");
+        }
+        let fs = self.overlay_fs();
+        let _guard = fs.mutex.lock();
+        let target = self.find(name)?;
+        if target.typ != InodeType::Dir {
+            return_errno_with_message!(Errno::ENOTDIR, "rmdir on not dir");
+        }
+        if !target.is_empty_dir()? {
+            return_errno_with_message!(Errno::ENOTEMPTY, "dir not empty");
+        }
+
+        let upper_self = self.copy_up()?;
+        if let Some(upper_target) = target.upper() {
+            clear_dir(&upper_target)?;
+            upper_self.rmdir(name)?;
+        }
+        self.hide_lower(&upper_self, &target, name)?;
+        self.children.lock().remove(name);
+        Ok(())
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>> {
+        let inode = self.find(name)?;
+        Ok(inode as _)
+    }
+
+    fn rename(&self, old_name: &str, target: &Arc<dyn Inode>, new_name: &str) -> Result<()> {
+        if old_name == "." || old_name == ".." {
+            return_errno_with_message!(Errno::EISDIR, "old_name is . or ..");
+        }
+        if new_name == "." || new_name == ".." {
+            return_errno_with_message!(Errno::EISDIR, "new_name is . or ..");
+        }
+        let target = target
+            .downcast_ref::<OverlayInode>()
+            .ok_or(Error::new(Errno::EXDEV))?;
+        if !Arc::ptr_eq(&self.overlay_fs(), &target.overlay_fs()) {
+            return_errno_with_message!(Errno::EXDEV, "not same fs");
+        }
+        if self.typ != InodeType::Dir || target.typ != InodeType::Dir {
+            return_errno_with_message!(Errno::ENOTDIR, "self or target is not dir");
+        }
+
+        let fs = self.overlay_fs();
+        let _guard = fs.mutex.lock();
+        let src = self.find(old_name)?;
+        // Like Linux without the `redirect_dir` feature, let user space fall
+        // back to copying.
+        if src.typ == InodeType::Dir && !src.lowers.is_empty() {
+            return_errno_with_message!(Errno::EXDEV, "cannot rename merged dir");
+        }
+        let dst = target.find_or_none(new_name)?;
+        if let Some(dst) = dst.as_ref() {
+            if Arc::ptr_eq(&src, dst) {
+                return Ok(());
+            }
+            match (src.typ, dst.typ) {
+                (InodeType::Dir, InodeType::Dir) => {
+                    if !dst.is_empty_dir()? {
+                        return_errno_with_message!(Errno::ENOTEMPTY, "dir not empty");
+                    }
+                }
+                (InodeType::Dir, _) => {
+                    return_errno_with_message!(Errno::ENOTDIR, "old is dir");
+                }
+                (_, InodeType::Dir) => {
+                    return_errno_with_message!(Errno::EISDIR, "new is dir");
+                }
+                _ => {}
+            }
+        }
+
+        src.copy_up()?;
+        let upper_self = self.copy_up()?;
+        let upper_target = target.copy_up()?;
+        let hides_lower = match dst.as_ref() {
+            Some(dst) => {
+                if dst.typ == InodeType::Dir
+                    && let Some(upper_dst) = dst.upper()
+                {
+                    clear_dir(&upper_dst)?;
+                }
+                !dst.lowers.is_empty()
+            }
+            None => remove_whiteout(&upper_target, new_name)?,
+        };
+
+        upper_self.rename(old_name, &upper_target, new_name)?;
+        self.hide_lower(&upper_self, &src, old_name)?;
+        if src.typ == InodeType::Dir && hides_lower {
+            set_opaque(&src.upper().unwrap())?;
+        }
+
+        self.children.lock().remove(old_name);
+        target
+            .children
+            .lock()
+            .insert(String::from(new_name), Arc::downgrade(&src));
+        *src.location.lock() = Some((target.this.upgrade().unwrap(), String::from(new_name)));
+        Ok(())
+    }
+
+    fn read_link(&self) -> Result<String> {
+        self.real().read_link()
+    }
+
+    fn write_link(&self, target: &str) -> Result<()> {
+        self.copy_up()?.write_link(target)
+    }
+
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        self.real().ioctl(cmd, arg)
+    }
+
+    fn sync_all(&self) -> Result<()> {
+        match self.upper() {
+            Some(upper) => upper.sync_all(),
+            None => Ok(()),
+        }
+    }
+
+    fn sync_data(&self) -> Result<()> {
+        match self.upper() {
+            Some(upper) => upper.sync_data(),
+            None => Ok(()),
+        }
+    }
+
+    fn poll(&self, mask: IoEvents, poller: Option<&Poller>) -> IoEvents {
+        self.real().poll(mask, poller)
+    }
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        self.overlay_fs()
+    }
+}
+
+/// A visitor that collects the names, inode numbers and types of dir entries.
+struct DirentCollector(Vec<(String, u64, InodeType)>);
+
+impl DirentVisitor for DirentCollector {
+    fn visit(&mut self, name: &str, ino: u64, type_: InodeType, _offset: usize) -> Result<()> {
+        self.0.push((String::from(name), ino, type_));
+        Ok(())
+    }
+}
+
+fn read_entries(dir: &Arc<dyn Inode>) -> Result<Vec<(String, u64, InodeType)>> {
+    let mut collector = DirentCollector(Vec::new());
+    let mut offset = 0;
+    loop {
+        let read_cnt = dir.readdir_at(offset, &mut collector)?;
+        if read_cnt == 0 {
+            break;
+        }
+        offset += read_cnt;
+    }
+    Ok(collector.0)
+}
+
+fn lookup_or_none(dir: &Arc<dyn Inode>, name: &str) -> Result<Option<Arc<dyn Inode>>> {
+    match dir.lookup(name) {
+        Ok(inode) => Ok(Some(inode)),
+        Err(e) if e.error() == Errno::ENOENT => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn is_whiteout(inode: &Arc<dyn Inode>) -> bool {
+    let metadata = inode.metadata();
+    metadata.type_ == InodeType::CharDevice && metadata.rdev == 0
+}
+
+fn is_opaque(dir: &Arc<dyn Inode>) -> Result<bool> {
+    Ok(lookup_or_none(dir, OPAQUE_MARKER)?.is_some())
+}
+
+fn set_opaque(dir: &Arc<dyn Inode>) -> Result<()> {
+    if !is_opaque(dir)? {
+        dir.create(OPAQUE_MARKER, InodeType::File, InodeMode::empty())?;
+    }
+    Ok(())
+}
+
+/// Removes the whiteout named `name` in `dir`, returns whether it exists.
+fn remove_whiteout(dir: &Arc<dyn Inode>, name: &str) -> Result<bool> {
+    match lookup_or_none(dir, name)? {
+        Some(inode) if is_whiteout(&inode) => {
+            dir.unlink(name)?;
+            Ok(true)
+        }
+        Some(_) => return_errno_with_message!(Errno::EEXIST, "entry exists in upper layer"),
+        None => Ok(false),
+    }
+}
+
+/// Removes all the entries in `dir` of the upper layer.
+fn clear_dir(dir: &Arc<dyn Inode>) -> Result<()> {
+    for (name, _, _) in read_entries(dir)? {
+        if name != "." && name != ".." {
+            remove_all(dir, &name)?;
+        }
+    }
+    Ok(())
+}
+
+/// Removes the entry named `name` in `dir`, including all its descendants.
+fn remove_all(dir: &Arc<dyn Inode>, name: &str) -> Result<()> {
+    let inode = dir.lookup(name)?;
+    if inode.type_() == InodeType::Dir {
+        clear_dir(&inode)?;
+        dir.rmdir(name)
+    } else {
+        dir.unlink(name)
+    }
+}
+
+/// Creates an empty directory inside `work_dir` to prepare copy-ups in.
+fn prepare_work_dir(work_dir: &Arc<dyn Inode>) -> Result<Arc<dyn Inode>> {
+    // Remove the leftovers of the previous mount.
+    if lookup_or_none(work_dir, WORK_DIR_NAME)?.is_some() {
+        remove_all(work_dir, WORK_DIR_NAME)?;
+    }
+    work_dir.create(WORK_DIR_NAME, InodeType::Dir, InodeMode::empty())
+}
+
+fn copy_data(src: &Arc<dyn Inode>, dst: &Arc<dyn Inode>, size: usize) -> Result<()> {
+    let mut buf = vec![0u8; PAGE_SIZE];
+    let mut offset = 0;
+    while offset < size {
+        let len = buf.len().min(size - offset);
+        let read_len = src.read_at(offset, &mut buf[..len])?;
+        if read_len == 0 {
+            break;
+        }
+        dst.write_at(offset, &buf[..read_len])?;
+        offset += read_len;
+    }
+    Ok(())
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+    use crate::fs::ramfs::RamFS;
+
+    fn mode() -> InodeMode {
+        InodeMode::from_bits_truncate(0o755)
+    }
+
+    fn create_dir(dir: &Arc<dyn Inode>, name: &str) -> Arc<dyn Inode> {
+        dir.create(name, InodeType::Dir, mode()).unwrap()
+    }
+
+    fn create_file(dir: &Arc<dyn Inode>, name: &str, content: &[u8]) -> Arc<dyn Inode> {
+        let file = dir.create(name, InodeType::File, mode()).unwrap();
+        file.write_at(0, content).unwrap();
+        file
+    }
+
+    fn read_file(file: &Arc<dyn Inode>) -> Vec<u8> {
+        let mut buf = vec![0u8; file.size()];
+        file.read_at(0, &mut buf).unwrap();
+        buf
+    }
+
+    fn find(dir: &Arc<dyn Inode>, path: &str) -> Result<Arc<dyn Inode>> {
+        let mut inode = dir.clone();
+        for name in path.split('/') {
+            inode = inode.lookup(name)?;
+        }
+        Ok(inode)
+    }
+
+    fn list_dir(dir: &Arc<dyn Inode>) -> Vec<String> {
+        let mut names: Vec<String> = read_entries(dir)
+            .unwrap()
+            .into_iter()
+            .map(|(name, _, _)| name)
+            .filter(|name| name != "." && name != "..")
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Creates an overlay, whose lower layer is
+    ///
+    /// ```text
+    /// /dir/a
+    /// /dir/sub/b
+    /// /x
+    /// ```
+    ///
+    /// Returns the overlay, the upper layer and the lower layer.
+    fn new_overlay() -> (Arc<OverlayFS>, Arc<dyn Inode>, Arc<dyn Inode>) {
+        crate::time::clocks::init_for_ktest();
+        let lower_fs = RamFS::new();
+        let lower = lower_fs.root_inode();
+        let dir = create_dir(&lower, "dir");
+        create_file(&dir, "a", b"lower a");
+        let sub = create_dir(&dir, "sub");
+        create_file(&sub, "b", b"lower b");
+        create_file(&lower, "x", b"lower x");
+
+        let upper_fs = RamFS::new();
+        let upper = create_dir(&upper_fs.root_inode(), "upper");
+        let work = create_dir(&upper_fs.root_inode(), "work");
+        // The overlay holds the filesystems of the layers.
+        let overlay = OverlayFS::new(Some(upper.clone()), Some(work), vec![lower.clone()]).unwrap();
+        (overlay, upper, lower)
+    }
+
+    #[ktest]
+    fn copy_up_on_write() {
+        let (overlay, upper, lower) = new_overlay();
+        let root = overlay.root_inode();
+        let b = find(&root, "dir/sub/b").unwrap();
+        let ino = b.ino();
+        assert_eq!(read_file(&b), b"lower b");
+        assert!(find(&upper, "dir").is_err());
+
+        // The parents are copied up before the file.
+        b.write_at(0, b"upper").unwrap();
+        assert_eq!(read_file(&b), b"upper b");
+        assert_eq!(b.ino(), ino);
+        assert_eq!(read_file(&find(&upper, "dir/sub/b").unwrap()), b"upper b");
+        assert_eq!(read_file(&find(&lower, "dir/sub/b").unwrap()), b"lower b");
+        // Only the written file is copied up in the parents.
+        assert_eq!(list_dir(&find(&upper, "dir").unwrap()), vec!["sub"]);
+        assert_eq!(list_dir(&root.lookup("dir").unwrap()), vec!["a", "sub"]);
+    }
+
+    #[ktest]
+    fn whiteout_hides_lower() {
+        let (overlay, upper, lower) = new_overlay();
+        let root = overlay.root_inode();
+        let dir = root.lookup("dir").unwrap();
+        dir.unlink("a").unwrap();
+        assert_eq!(dir.lookup("a").unwrap_err().error(), Errno::ENOENT);
+        assert_eq!(list_dir(&dir), vec!["sub"]);
+        assert!(is_whiteout(&find(&upper, "dir/a").unwrap()));
+        assert!(find(&lower, "dir/a").is_ok());
+
+        // The whiteout is replaced by a new file.
+        create_file(&dir, "a", b"upper a");
+        assert_eq!(read_file(&dir.lookup("a").unwrap()), b"upper a");
+        assert!(!is_whiteout(&find(&upper, "dir/a").unwrap()));
+        assert_eq!(list_dir(&dir), vec!["a", "sub"]);
+    }
+
+    #[ktest]
+    fn opaque_dir() {
+        let (overlay, upper, _) = new_overlay();
+        let root = overlay.root_inode();
+        let dir = root.lookup("dir").unwrap();
+        let sub = dir.lookup("sub").unwrap();
+        assert_eq!(dir.rmdir("sub").unwrap_err().error(), Errno::ENOTEMPTY);
+        sub.unlink("b").unwrap();
+        dir.rmdir("sub").unwrap();
+        assert_eq!(list_dir(&dir), vec!["a"]);
+
+        // The new directory is not merged with the removed one.
+        let sub = create_dir(&dir, "sub");
+        assert!(is_opaque(&find(&upper, "dir/sub").unwrap()).unwrap());
+        assert_eq!(list_dir(&sub), Vec::<String>::new());
+        assert_eq!(sub.lookup("b").unwrap_err().error(), Errno::ENOENT);
+        assert_eq!(
+            sub.lookup(OPAQUE_MARKER).unwrap_err().error(),
+            Errno::ENOENT
+        );
+    }
+
+    #[ktest]
+    fn merged_readdir() {
+        let (overlay, upper, _) = new_overlay();
+        let root = overlay.root_inode();
+        let dir = root.lookup("dir").unwrap();
+        create_file(&dir, "c", b"upper c");
+        // The upper file hides the lower one of the same name.
+        dir.lookup("a").unwrap().write_at(0, b"upper").unwrap();
+        assert_eq!(list_dir(&dir), vec!["a", "c", "sub"]);
+        assert_eq!(list_dir(&find(&upper, "dir").unwrap()), vec!["a", "c"]);
+
+        // The entries are reported with the overlay inode numbers.
+        let entries = read_entries(&dir).unwrap();
+        for (name, ino, type_) in entries {
+            let inode = dir.lookup(&name).unwrap();
+            assert_eq!(inode.ino(), ino);
+            assert_eq!(inode.type_(), type_);
+        }
+    }
+
+    #[ktest]
+    fn rename_and_unlink_across_layers() {
+        let (overlay, upper, lower) = new_overlay();
+        let root = overlay.root_inode();
+        let dir = root.lookup("dir").unwrap();
+        root.rename("x", &dir, "y").unwrap();
+        assert_eq!(root.lookup("x").unwrap_err().error(), Errno::ENOENT);
+        assert_eq!(read_file(&dir.lookup("y").unwrap()), b"lower x");
+        assert!(is_whiteout(&upper.lookup("x").unwrap()));
+        assert!(lower.lookup("x").is_ok());
+
+        // The renamed file replaces a lower one.
+        dir.rename("y", &dir, "a").unwrap();
+        assert_eq!(read_file(&dir.lookup("a").unwrap()), b"lower x");
+        assert_eq!(list_dir(&dir), vec!["a", "sub"]);
+
+        // A merged directory cannot be renamed.
+        assert_eq!(
+            dir.rename("sub", &root, "sub").unwrap_err().error(),
+            Errno::EXDEV
+        );
+
+        // A file only in the upper layer is removed without a whiteout.
+        dir.unlink("a").unwrap();
+        create_file(&dir, "c", b"upper c");
+        dir.unlink("c").unwrap();
+        assert!(find(&upper, "dir/c").is_err());
+        assert_eq!(list_dir(&dir), vec!["sub"]);
+    }
+}
@@ -0,0 +1,32 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! A union filesystem that overlays directory trees of other filesystems.
+//!
+//! An overlay filesystem merges a writable upper layer and one or more read-only
+//! lower layers into a single directory tree. Any filesystem implementing the
+//! generic `FileSystem` and `Inode` traits can serve as a layer.
+//!
+//! 1. Files are looked up from the upper layer to the lowest layer and the first
+//!    one found wins. Directories of the same name are merged.
+//! 2. A file from a lower layer is copied up to the upper layer before it is
+//!    modified. The copy is prepared in the work directory and then renamed into
+//!    place, so a partially copied file is never visible.
+//! 3. Removing a file that exists in a lower layer leaves a whiteout, i.e., a
+//!    character device with the device number 0/0, in the upper layer.
+//! 4. A directory containing an entry named `.wh..wh..opq` is opaque and hides
+//!    the directories of the same name in the layers below it. Linux marks opaque
+//!    directories with an extended attribute instead, which is not supported yet.
+
+pub use fs::OverlayFS;
+pub use options::OverlayMountOptions;
+
+mod fs;
+mod options;
+
+const OVERLAYFS_MAGIC: u64 = 0x794c_7630;
+const ROOT_INO: u64 = 1;
+const NAME_MAX: usize = 255;
+/// The name of the entry that marks a directory as opaque.
+const OPAQUE_MARKER: &str = ".wh..wh..opq";
+/// The name of the directory inside the work directory that holds the files being copied up.
+const WORK_DIR_NAME: &str = "work";
@@ -0,0 +1,57 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use crate::prelude::*;
+
+/// The mount options of an overlay filesystem.
+///
+/// The options are given in the `data` argument of `mount`, e.g.,
+/// `lowerdir=/lower1:/lower2,upperdir=/upper,workdir=/work`.
+/// The left-most lower directory is the top-most lower layer.
+/// Without `upperdir` and `workdir`, the overlay filesystem is read-only.
+#[derive(Debug, Clone, Default)]
+pub struct OverlayMountOptions {
+    /// The paths of the lower directories, from the top-most one.
+    pub lowerdirs: Vec<String>,
+    /// The path of the upper directory.
+    pub upperdir: Option<String>,
+    /// The path of the work directory, which must be on the same filesystem
+    /// as the upper directory.
+    pub workdir: Option<String>,
+}
+
+impl OverlayMountOptions {
+    /// Parses the options from the `data` argument of `mount`.
+    pub fn parse(data: &str) -> Result<Self> {
+        let mut options = Self::default();
+
+        for option in data.split(',').filter(|option| !option.is_empty()) {
+            let (key, value) = option.split_once('=').unwrap_or((option, ""));
+            if value.is_empty() {
+                return_errno_with_message!(Errno::EINVAL, "overlay option without value");
+            }
+            match key {
+                "lowerdir" => {
+                    options.lowerdirs = value.split(':').map(String::from).collect();
+                    if options.lowerdirs.iter().any(|dir| dir.is_empty()) {
+                        return_errno_with_message!(Errno::EINVAL, "empty lowerdir");
+                    }
+                }
+                "upperdir" => options.upperdir = Some(String::from(value)),
+                "workdir" => options.workdir = Some(String::from(value)),
+                _ => return_errno_with_message!(Errno::EINVAL, "unknown overlay option"),
+            }
+        }
+
+        if options.lowerdirs.is_empty() {
+            return_errno_with_message!(Errno::EINVAL, "missing lowerdir");
+        }
+        if options.upperdir.is_some() != options.workdir.is_some() {
+            return_errno_with_message!(
+                Errno::EINVAL,
+                "upperdir and workdir must be specified together"
+            );
+        }
+
+        Ok(options)
+    }
+}
@@ -0,0 +1,346 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::sync::atomic::{AtomicU16, AtomicU32, Ordering};
+
+use aster_virtio::device::{p9::device::P9Device, VirtioDeviceError};
+
+use super::protocol::*;
+use crate::prelude::*;
+
+/// A 9P2000.L client over a virtio 9P transport.
+///
+/// Every method sends one T-message and waits for the R-message. The errors
+/// replied by Rlerror are converted to the corresponding `Errno`.
+pub(super) struct P9Client {
+    device: Arc<P9Device>,
+    /// The maximum size of a message, negotiated by Tversion
+    msize: usize,
+    tag_allocator: AtomicU16,
+    fid_allocator: AtomicU32,
+}
+
+impl P9Client {
+    /// The size of the reply buffer for the messages without data.
+    const SMALL_REPLY_LEN: usize = 2 * PAGE_SIZE;
+
+    /// Negotiates the protocol version and the message size with the server.
+    pub fn connect(device: Arc<P9Device>, msize: usize) -> Result<Self> {
+        let mut client = Self {
+            device,
+            msize,
+            tag_allocator: AtomicU16::new(0),
+            fid_allocator: AtomicU32::new(0),
+        };
+
+        let mut writer = MsgWriter::new(MsgType::Tversion, NOTAG);
+        writer.put_u32(msize as u32).put_str(P9_VERSION);
+        let reply = client.send(writer.finish(), MsgType::Tversion, Self::SMALL_REPLY_LEN)?;
+        let mut reader = MsgReader::new(&reply);
+        let server_msize = reader.get_u32()? as usize;
+        if reader.get_str()? != P9_VERSION {
+            return_errno_with_message!(
+                Errno::EPROTONOSUPPORT,
+                "the server does not speak 9P2000.L"
+            );
+        }
+        if server_msize <= IO_HEADER_LEN {
+            return_errno_with_message!(Errno::EPROTO, "the message size is too small");
+        }
+        client.msize = client.msize.min(server_msize);
+        Ok(client)
+    }
+
+    /// Returns the maximum size of the data in a Tread or Twrite.
+    pub fn max_io_len(&self) -> usize {
+        self.msize - IO_HEADER_LEN
+    }
+
+    /// Allocates a fid, which is bound to a file by Tattach or Twalk.
+    pub fn alloc_fid(&self) -> u32 {
+        loop {
+            let fid = self.fid_allocator.fetch_add(1, Ordering::Relaxed);
+            if fid != NOFID {
+                return fid;
+            }
+        }
+    }
+
+    pub fn attach(&self, fid: u32, uname: &str, aname: &str, n_uname: u32) -> Result<Qid> {
+        let reply = self.rpc(MsgType::Tattach, |writer| {
+            writer
+                .put_u32(fid)
+                .put_u32(NOFID)
+                .put_str(uname)
+                .put_str(aname)
+                .put_u32(n_uname);
+        })?;
+        MsgReader::new(&reply).get_qid()
+    }
+
+    /// Walks from `fid` through `names`, binding `newfid` to the destination.
+    ///
+    /// Walking through no names clones `fid`.
+    pub fn walk(&self, fid: u32, newfid: u32, names: &[&str]) -> Result<Vec<Qid>> {
+        let reply = self.rpc(MsgType::Twalk, |writer| {
+            writer
+                .put_u32(fid)
+                .put_u32(newfid)
+                .put_u16(names.len() as u16);
+            for name in names {
+                writer.put_str(name);
+            }
+        })?;
+        let mut reader = MsgReader::new(&reply);
+        let nwqid = reader.get_u16()? as usize;
+        if nwqid < names.len() {
+            // The server does not bind `newfid` if the walk stops halfway.
+            return_errno_with_message!(Errno::ENOENT, "the walk stops halfway");
+        }
+        (0..nwqid).map(|_| reader.get_qid()).collect()
+    }
+
+    pub fn clunk(&self, fid: u32) -> Result<()> {
+        self.rpc(MsgType::Tclunk, |writer| {
+            writer.put_u32(fid);
+        })?;
+        Ok(())
+    }
+
+    pub fn getattr(&self, fid: u32) -> Result<Attr> {
+        let reply = self.rpc(MsgType::Tgetattr, |writer| {
+            writer.put_u32(fid).put_u64(GETATTR_BASIC);
+        })?;
+        MsgReader::new(&reply).get_attr()
+    }
+
+    pub fn setattr(&self, fid: u32, valid: SetattrValid, attr: &SetAttr) -> Result<()> {
+        self.rpc(MsgType::Tsetattr, |writer| {
+            writer
+                .put_u32(fid)
+                .put_u32(valid.bits())
+                .put_u32(attr.mode)
+                .put_u32(attr.uid)
+                .put_u32(attr.gid)
+                .put_u64(attr.size)
+                .put_u64(attr.atime.0)
+                .put_u64(attr.atime.1)
+                .put_u64(attr.mtime.0)
+                .put_u64(attr.mtime.1);
+        })?;
+        Ok(())
+    }
+
+    /// Opens the file of `fid`, which can then be used for I/O.
+    pub fn lopen(&self, fid: u32, flags: u32) -> Result<Qid> {
+        let reply = self.rpc(MsgType::Tlopen, |writer| {
+            writer.put_u32(fid).put_u32(flags);
+        })?;
+        MsgReader::new(&reply).get_qid()
+    }
+
+    /// Creates a regular file in the directory of `fid`, which is then
+    /// bound to the opened new file.
+    pub fn lcreate(&self, fid: u32, name: &str, flags: u32, mode: u32, gid: u32) -> Result<Qid> {
+        let reply = self.rpc(MsgType::Tlcreate, |writer| {
+            writer
+                .put_u32(fid)
+                .put_str(name)
+                .put_u32(flags)
+                .put_u32(mode)
+                .put_u32(gid);
+        })?;
+        MsgReader::new(&reply).get_qid()
+    }
+
+    pub fn read(&self, fid: u32, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        let count = buf.len().min(self.max_io_len());
+        let reply = self.rpc_with_reply_len(MsgType::Tread, count + IO_HEADER_LEN, |writer| {
+            writer.put_u32(fid).put_u64(offset).put_u32(count as u32);
+        })?;
+        let mut reader = MsgReader::new(&reply);
+        let len = (reader.get_u32()? as usize).min(count);
+        buf[..len].copy_from_slice(reader.get_bytes(len)?);
+        Ok(len)
+    }
+
+    pub fn write(&self, fid: u32, offset: u64, buf: &[u8]) -> Result<usize> {
+        let count = buf.len().min(self.max_io_len());
+        let reply = self.rpc(MsgType::Twrite, |writer| {
+            writer
+                .put_u32(fid)
+                .put_u64(offset)
+                .put_u32(count as u32)
+                .put_bytes(&buf[..count]);
+        })?;
+        let len = MsgReader::new(&reply).get_u32()? as usize;
+        Ok(len.min(count))
+    }
+
+    /// Reads the raw directory entries of the opened directory of `fid`.
+    pub fn readdir(&self, fid: u32, offset: u64) -> Result<Vec<u8>> {
+        let count = PAGE_SIZE.min(self.max_io_len());
+        let reply =
+            self.rpc_with_reply_len(MsgType::Treaddir, count + IO_HEADER_LEN, |writer| {
+                writer.put_u32(fid).put_u64(offset).put_u32(count as u32);
+            })?;
+        let mut reader = MsgReader::new(&reply);
+        let len = reader.get_u32()? as usize;
+        Ok(reader.get_bytes(len)?.to_vec())
+    }
+
+    pub fn mkdir(&self, dfid: u32, name: &str, mode: u32, gid: u32) -> Result<Qid> {
+        let reply = self.rpc(MsgType::Tmkdir, |writer| {
+            writer
+                .put_u32(dfid)
+                .put_str(name)
+                .put_u32(mode)
+                .put_u32(gid);
+        })?;
+        MsgReader::new(&reply).get_qid()
+    }
+
+    pub fn symlink(&self, dfid: u32, name: &str, target: &str, gid: u32) -> Result<Qid> {
+        let reply = self.rpc(MsgType::Tsymlink, |writer| {
+            writer
+                .put_u32(dfid)
+                .put_str(name)
+                .put_str(target)
+                .put_u32(gid);
+        })?;
+        MsgReader::new(&reply).get_qid()
+    }
+
+    pub fn mknod(
+        &self,
+        dfid: u32,
+        name: &str,
+        mode: u32,
+        (major, minor): (u32, u32),
+        gid: u32,
+    ) -> Result<Qid> {
+        let reply = self.rpc(MsgType::Tmknod, |writer| {
+            writer
+                .put_u32(dfid)
+                .put_str(name)
+                .put_u32(mode)
+                .put_u32(major)
+                .put_u32(minor)
+                .put_u32(gid);
+        })?;
+        MsgReader::new(&reply).get_qid()
+    }
+
+    pub fn readlink(&self, fid: u32) -> Result<String> {
+        let reply = self.rpc(MsgType::Treadlink, |writer| {
+            writer.put_u32(fid);
+        })?;
+        Ok(String::from(MsgReader::new(&reply).get_str()?))
+    }
+
+    pub fn link(&self, dfid: u32, fid: u32, name: &str) -> Result<()> {
+        self.rpc(MsgType::Tlink, |writer| {
+            writer.put_u32(dfid).put_u32(fid).put_str(name);
+        })?;
+        Ok(())
+    }
+
+    pub fn renameat(
+        &self,
+        old_dfid: u32,
+        old_name: &str,
+        new_dfid: u32,
+        new_name: &str,
+    ) -> Result<()> {
+        self.rpc(MsgType::Trenameat, |writer| {
+            writer
+                .put_u32(old_dfid)
+                .put_str(old_name)
+                .put_u32(new_dfid)
+                .put_str(new_name);
+        })?;
+        Ok(())
+    }
+
+    pub fn unlinkat(&self, dfid: u32, name: &str, flags: u32) -> Result<()> {
+        self.rpc(MsgType::Tunlinkat, |writer| {
+            writer.put_u32(dfid).put_str(name).put_u32(flags);
+        })?;
+        Ok(())
+    }
+
+    pub fn statfs(&self, fid: u32) -> Result<StatFs> {
+        let reply = self.rpc(MsgType::Tstatfs, |writer| {
+            writer.put_u32(fid);
+        })?;
+        MsgReader::new(&reply).get_statfs()
+    }
+
+    pub fn fsync(&self, fid: u32, datasync: bool) -> Result<()> {
+        self.rpc(MsgType::Tfsync, |writer| {
+            writer.put_u32(fid).put_u32(datasync as u32);
+        })?;
+        Ok(())
+    }
+
+    fn rpc(&self, type_: MsgType, build: impl FnOnce(&mut MsgWriter)) -> Result<Vec<u8>> {
+        self.rpc_with_reply_len(type_, Self::SMALL_REPLY_LEN, build)
+    }
+
+    fn rpc_with_reply_len(
+        &self,
+        type_: MsgType,
+        reply_len: usize,
+        build: impl FnOnce(&mut MsgWriter),
+    ) -> Result<Vec<u8>> {
+        let mut writer = MsgWriter::new(type_, self.alloc_tag());
+        build(&mut writer);
+        let request = writer.finish();
+        if request.len() > self.msize {
+            return_errno_with_message!(Errno::EINVAL, "the 9P message is too large");
+        }
+        self.send(request, type_, reply_len.min(self.msize))
+    }
+
+    /// Sends a message and returns the fields of the reply.
+    fn send(&self, request: Vec<u8>, type_: MsgType, reply_len: usize) -> Result<Vec<u8>> {
+        let mut reply = vec![0u8; reply_len];
+        let len = self
+            .device
+            .request(&request, &mut reply)
+            .map_err(|err| match err {
+                VirtioDeviceError::NoMemory => {
+                    Error::with_message(Errno::ENOMEM, "no memory for the 9P request")
+                }
+                _ => Error::with_message(Errno::EIO, "the 9P transport fails"),
+            })?;
+        reply.truncate(len);
+
+        let mut reader = MsgReader::new(&reply);
+        let size = reader.get_u32()? as usize;
+        let reply_type = reader.get_u8()?;
+        let tag = reader.get_u16()?;
+        if size != len || tag != u16::from_le_bytes([request[5], request[6]]) {
+            return_errno_with_message!(Errno::EIO, "invalid 9P reply header");
+        }
+        if reply_type == MsgType::Rlerror as u8 {
+            let ecode = reader.get_u32()?;
+            let errno = Errno::try_from(ecode as i32).unwrap_or(Errno::EIO);
+            return Err(Error::new(errno));
+        }
+        if reply_type != type_ as u8 + 1 {
+            return_errno_with_message!(Errno::EIO, "unexpected 9P reply type");
+        }
+
+        reply.drain(..HEADER_LEN);
+        Ok(reply)
+    }
+
+    fn alloc_tag(&self) -> u16 {
+        loop {
+            let tag = self.tag_allocator.fetch_add(1, Ordering::Relaxed);
+            if tag != NOTAG {
+                return tag;
+            }
+        }
+    }
+}
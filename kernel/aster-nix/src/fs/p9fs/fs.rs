@@ -0,0 +1,655 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::{
+    sync::atomic::{AtomicU32, Ordering},
+    time::Duration,
+};
+
+use aster_virtio::device::p9::device::P9Device;
+
+use super::{client::P9Client, protocol::*, *};
+use crate::{
+    fs::{
+        device::Device,
+        utils::{
+            DirentVisitor, FileSystem, FsFlags, Inode, InodeMode, InodeType, Metadata, SuperBlock,
+        },
+    },
+    prelude::*,
+    process::{credentials, Gid, Uid},
+};
+
+/// A filesystem that accesses the files of a 9P2000.L server, e.g., a
+/// directory shared by the host through virtio 9P.
+pub struct P9FS {
+    client: P9Client,
+    /// Root inode
+    root: Arc<P9Inode>,
+}
+
+impl P9FS {
+    /// Attaches to the files exported through `device`.
+    pub fn new(device: Arc<P9Device>, options: P9MountOptions) -> Result<Arc<Self>> {
+        let client = P9Client::connect(device, options.msize)?;
+        let fid = client.alloc_fid();
+        let n_uname = credentials().fsuid().as_u32();
+        let qid = client.attach(fid, &options.uname, &options.aname, n_uname)?;
+        if qid.type_ & Qid::QTDIR == 0 {
+            let _ = client.clunk(fid);
+            return_errno_with_message!(Errno::ENOTDIR, "the attached file is not a directory");
+        }
+
+        Ok(Arc::new_cyclic(|weak_fs| Self {
+            client,
+            root: P9Inode::new(fid, qid.path, InodeType::Dir, weak_fs.clone()),
+        }))
+    }
+}
+
+impl Drop for P9FS {
+    fn drop(&mut self) {
+        let _ = self.client.clunk(self.root.fid());
+    }
+}
+
+impl FileSystem for P9FS {
+    fn sync(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn root_inode(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+
+    fn sb(&self) -> SuperBlock {
+        let mut sb = SuperBlock::new(V9FS_MAGIC, BLOCK_SIZE, NAME_MAX);
+        let Ok(st) = self.client.statfs(self.root.fid()) else {
+            return sb;
+        };
+        sb.bsize = st.bsize as usize;
+        sb.blocks = st.blocks as usize;
+        sb.bfree = st.bfree as usize;
+        sb.bavail = st.bavail as usize;
+        sb.files = st.files as usize;
+        sb.ffree = st.ffree as usize;
+        sb.fsid = st.fsid;
+        sb.namelen = st.namelen as usize;
+        sb.frsize = st.bsize as usize;
+        sb
+    }
+
+    fn flags(&self) -> FsFlags {
+        FsFlags::empty()
+    }
+
+    fn name(&self) -> &'static str {
+        "9p"
+    }
+}
+
+/// A fid opened by Tlopen or Tlcreate.
+struct OpenFid {
+    fid: u32,
+    is_writable: bool,
+}
+
+struct P9Inode {
+    /// The fid bound to the file, which is `NOFID` for a symlink not yet created
+    fid: AtomicU32,
+    /// The path part of the qid, which is unique on the server
+    ino: u64,
+    /// Type of the inode
+    typ: InodeType,
+    /// The fid opened for the I/O of a regular file
+    open_fid: Mutex<Option<OpenFid>>,
+    /// The Treaddir offsets of the directory entries, indexed by their positions
+    dir_offsets: Mutex<Vec<u64>>,
+    /// The parent, the name and the mode of a symlink before its target is written
+    pending_symlink: Mutex<Option<(Arc<P9Inode>, String, InodeMode)>>,
+    /// Reference to self
+    this: Weak<P9Inode>,
+    /// Reference to fs
+    fs: Weak<P9FS>,
+}
+
+impl P9Inode {
+    fn new(fid: u32, ino: u64, typ: InodeType, fs: Weak<P9FS>) -> Arc<Self> {
+        Arc::new_cyclic(|weak_self| P9Inode {
+            fid: AtomicU32::new(fid),
+            ino,
+            typ,
+            open_fid: Mutex::new(None),
+            dir_offsets: Mutex::new(Vec::new()),
+            pending_symlink: Mutex::new(None),
+            this: weak_self.clone(),
+            fs,
+        })
+    }
+
+    fn p9_fs(&self) -> Arc<P9FS> {
+        self.fs.upgrade().unwrap()
+    }
+
+    fn fid(&self) -> u32 {
+        self.fid.load(Ordering::Relaxed)
+    }
+
+    /// Returns the fid bound to the file, failing for a symlink not yet created.
+    fn bound_fid(&self) -> Result<u32> {
+        let fid = self.fid();
+        if fid == NOFID {
+            return_errno_with_message!(Errno::ENOENT, "the symlink has no target");
+        }
+        Ok(fid)
+    }
+
+    /// Walks to the child of `name` and creates its inode.
+    fn walk_child(&self, name: &str) -> Result<Arc<P9Inode>> {
+        let fs = self.p9_fs();
+        let newfid = fs.client.alloc_fid();
+        fs.client.walk(self.bound_fid()?, newfid, &[name])?;
+        let attr = match fs.client.getattr(newfid) {
+            Ok(attr) => attr,
+            Err(e) => {
+                let _ = fs.client.clunk(newfid);
+                return Err(e);
+            }
+        };
+        let Ok(typ) = InodeType::try_from(attr.mode & 0o170000) else {
+            let _ = fs.client.clunk(newfid);
+            return_errno_with_message!(Errno::EIO, "invalid file type from 9P server");
+        };
+        Ok(P9Inode::new(newfid, attr.qid.path, typ, self.fs.clone()))
+    }
+
+    /// Clones the fid bound to the file and opens it with `flags`.
+    fn open(&self, flags: u32) -> Result<u32> {
+        let fs = self.p9_fs();
+        let fid = fs.client.alloc_fid();
+        fs.client.walk(self.bound_fid()?, fid, &[])?;
+        if let Err(e) = fs.client.lopen(fid, flags) {
+            let _ = fs.client.clunk(fid);
+            return Err(e);
+        }
+        Ok(fid)
+    }
+
+    /// Returns the fid for I/O, opening the file if necessary.
+    fn io_fid(&self, need_write: bool) -> Result<u32> {
+        let mut open_fid = self.open_fid.lock();
+        if let Some(open_fid) = open_fid.as_ref()
+            && (open_fid.is_writable || !need_write)
+        {
+            return Ok(open_fid.fid);
+        }
+
+        let (fid, is_writable) = match self.open(O_RDWR) {
+            Ok(fid) => (fid, true),
+            Err(e) if !need_write && matches!(e.error(), Errno::EACCES | Errno::EROFS) => {
+                (self.open(O_RDONLY)?, false)
+            }
+            Err(e) => return Err(e),
+        };
+        if let Some(old_open_fid) = open_fid.replace(OpenFid { fid, is_writable }) {
+            let _ = self.p9_fs().client.clunk(old_open_fid.fid);
+        }
+        Ok(fid)
+    }
+
+    fn getattr(&self) -> Result<Attr> {
+        self.p9_fs().client.getattr(self.bound_fid()?)
+    }
+
+    fn setattr(&self, valid: SetattrValid, attr: SetAttr) -> Result<()> {
+        self.p9_fs().client.setattr(self.bound_fid()?, valid, &attr)
+    }
+
+    fn set_time(&self, valid: SetattrValid, time: Duration) {
+        let time = (time.as_secs(), time.subsec_nanos() as u64);
+        let attr = SetAttr {
+            atime: time,
+            mtime: time,
+            ..Default::default()
+        };
+        if let Err(e) = self.setattr(valid, attr) {
+            warn!("failed to set the time of 9P inode: {:?}", e);
+        }
+    }
+
+    fn readdir_with_fid(
+        &self,
+        fid: u32,
+        offset: usize,
+        visitor: &mut dyn DirentVisitor,
+    ) -> Result<usize> {
+        // The server resumes Treaddir from an opaque offset returned along with
+        // the previous entry, so remember the offsets of the visited positions.
+        let mut dir_offsets = self.dir_offsets.lock();
+        if offset == 0 || dir_offsets.is_empty() {
+            *dir_offsets = vec![0];
+        }
+        let mut idx = offset.min(dir_offsets.len() - 1);
+        let mut read_offset = dir_offsets[idx];
+        let fs = self.p9_fs();
+        let client = &fs.client;
+
+        loop {
+            let entries = client.readdir(fid, read_offset)?;
+            if entries.is_empty() {
+                break;
+            }
+
+            let mut reader = MsgReader::new(&entries);
+            while reader.remaining() > 0 {
+                let qid = reader.get_qid()?;
+                let next_offset = reader.get_u64()?;
+                let dtype = reader.get_u8()?;
+                let name = reader.get_str()?;
+                if idx >= offset {
+                    let type_ =
+                        InodeType::try_from((dtype as u32) << 12).unwrap_or(InodeType::File);
+                    if let Err(e) = visitor.visit(name, qid.path, type_, idx) {
+                        if idx == offset {
+                            return Err(e);
+                        }
+                        return Ok(idx - offset);
+                    }
+                }
+                idx += 1;
+                read_offset = next_offset;
+                dir_offsets.truncate(idx);
+                dir_offsets.push(read_offset);
+            }
+        }
+
+        Ok(idx.saturating_sub(offset))
+    }
+
+    fn create_symlink(&self, target: &str) -> Result<()> {
+        let Some((parent, name, _)) = self.pending_symlink.lock().take() else {
+            return_errno_with_message!(Errno::EPERM, "cannot change the target of a symlink");
+        };
+        let fs = self.p9_fs();
+        let gid = credentials().fsgid().as_u32();
+        fs.client.symlink(parent.bound_fid()?, &name, target, gid)?;
+        let fid = fs.client.alloc_fid();
+        fs.client.walk(parent.bound_fid()?, fid, &[&name])?;
+        self.fid.store(fid, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn fsync(&self, datasync: bool) -> Result<()> {
+        let Some(fid) = self.open_fid.lock().as_ref().map(|open_fid| open_fid.fid) else {
+            return Ok(());
+        };
+        self.p9_fs().client.fsync(fid, datasync)
+    }
+
+    fn same_fs(&self, other: &P9Inode) -> Result<()> {
+        if !Arc::ptr_eq(&self.p9_fs(), &other.p9_fs()) {
+            return_errno_with_message!(Errno::EXDEV, "not same fs");
+        }
+        Ok(())
+    }
+}
+
+impl Drop for P9Inode {
+    fn drop(&mut self) {
+        let Some(fs) = self.fs.upgrade() else {
+            return;
+        };
+        if let Some(open_fid) = self.open_fid.lock().take() {
+            let _ = fs.client.clunk(open_fid.fid);
+        }
+        let fid = self.fid();
+        if fid != NOFID {
+            let _ = fs.client.clunk(fid);
+        }
+    }
+}
+
+impl Inode for P9Inode {
+    fn size(&self) -> usize {
+        self.getattr().map_or(0, |attr| attr.size as usize)
+    }
+
+    fn resize(&self, new_size: usize) -> Result<()> {
+        if self.typ != InodeType::File {
+            return_errno_with_message!(Errno::EISDIR, "not regular file");
+        }
+        let attr = SetAttr {
+            size: new_size as u64,
+            ..Default::default()
+        };
+        self.setattr(SetattrValid::SIZE, attr)
+    }
+
+    fn metadata(&self) -> Metadata {
+        let Ok(attr) = self.getattr() else {
+            let mode = self
+                .pending_symlink
+                .lock()
+                .as_ref()
+                .map_or(InodeMode::empty(), |(_, _, mode)| *mode);
+            return Metadata {
+                dev: 0,
+                ino: self.ino,
+                size: 0,
+                blk_size: BLOCK_SIZE,
+                blocks: 0,
+                atime: Duration::ZERO,
+                mtime: Duration::ZERO,
+                ctime: Duration::ZERO,
+                type_: self.typ,
+                mode,
+                nlinks: 1,
+                uid: Uid::new_root(),
+                gid: Gid::new_root(),
+                rdev: 0,
+            };
+        };
+        Metadata {
+            dev: 0,
+            ino: self.ino,
+            size: attr.size as usize,
+            blk_size: if attr.blksize == 0 {
+                BLOCK_SIZE
+            } else {
+                attr.blksize as usize
+            },
+            blocks: attr.blocks as usize,
+            atime: Duration::new(attr.atime.0, attr.atime.1 as u32),
+            mtime: Duration::new(attr.mtime.0, attr.mtime.1 as u32),
+            ctime: Duration::new(attr.ctime.0, attr.ctime.1 as u32),
+            type_: self.typ,
+            mode: InodeMode::from_bits_truncate(attr.mode as u16),
+            nlinks: attr.nlink as usize,
+            uid: Uid::new(attr.uid),
+            gid: Gid::new(attr.gid),
+            rdev: attr.rdev,
+        }
+    }
+
+    fn ino(&self) -> u64 {
+        self.ino
+    }
+
+    fn type_(&self) -> InodeType {
+        self.typ
+    }
+
+    fn mode(&self) -> Result<InodeMode> {
+        Ok(InodeMode::from_bits_truncate(self.getattr()?.mode as u16))
+    }
+
+    fn set_mode(&self, mode: InodeMode) -> Result<()> {
+        let attr = SetAttr {
+            mode: mode.bits() as u32,
+            ..Default::default()
+        };
+        self.setattr(SetattrValid::MODE, attr)
+    }
+
+    fn owner(&self) -> Result<Uid> {
+        Ok(Uid::new(self.getattr()?.uid))
+    }
+
+    fn set_owner(&self, uid: Uid) -> Result<()> {
+        let attr = SetAttr {
+            uid: uid.as_u32(),
+            ..Default::default()
+        };
+        self.setattr(SetattrValid::UID, attr)
+    }
+
+    fn group(&self) -> Result<Gid> {
+        Ok(Gid::new(self.getattr()?.gid))
+    }
+
+    fn set_group(&self, gid: Gid) -> Result<()> {
+        let attr = SetAttr {
+            gid: gid.as_u32(),
+            ..Default::default()
+        };
+        self.setattr(SetattrValid::GID, attr)
+    }
+
+    fn atime(&self) -> Duration {
+        self.getattr().map_or(Duration::ZERO, |attr| {
+            Duration::new(attr.atime.0, attr.atime.1 as u32)
+        })
+    }
+
+    fn set_atime(&self, time: Duration) {
+        self.set_time(SetattrValid::ATIME | SetattrValid::ATIME_SET, time)
+    }
+
+    fn mtime(&self) -> Duration {
+        self.getattr().map_or(Duration::ZERO, |attr| {
+            Duration::new(attr.mtime.0, attr.mtime.1 as u32)
+        })
+    }
+
+    fn set_mtime(&self, time: Duration) {
+        self.set_time(SetattrValid::MTIME | SetattrValid::MTIME_SET, time)
+    }
+
+    fn ctime(&self) -> Duration {
+        self.getattr().map_or(Duration::ZERO, |attr| {
+            Duration::new(attr.ctime.0, attr.ctime.1 as u32)
+        })
+    }
+
+    /// The server always sets the ctime to the current time.
+    fn set_ctime(&self, time: Duration) {
+        self.set_time(SetattrValid::CTIME, time)
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        if self.typ != InodeType::File {
+            return_errno_with_message!(Errno::EISDIR, "read is not supported");
+        }
+
+        let fid = self.io_fid(false)?;
+        let fs = self.p9_fs();
+        let client = &fs.client;
+        let mut read_len = 0;
+        while read_len < buf.len() {
+            let len = client.read(fid, (offset + read_len) as u64, &mut buf[read_len..])?;
+            if len == 0 {
+                break;
+            }
+            read_len += len;
+        }
+        Ok(read_len)
+    }
+
+    fn read_direct_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        self.read_at(offset, buf)
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize> {
+        if self.typ != InodeType::File {
+            return_errno_with_message!(Errno::EISDIR, "write is not supported");
+        }
+
+        let fid = self.io_fid(true)?;
+        let fs = self.p9_fs();
+        let client = &fs.client;
+        let mut written_len = 0;
+        while written_len < buf.len() {
+            let len = client.write(fid, (offset + written_len) as u64, &buf[written_len..])?;
+            if len == 0 {
+                break;
+            }
+            written_len += len;
+        }
+        Ok(written_len)
+    }
+
+    fn write_direct_at(&self, offset: usize, buf: &[u8]) -> Result<usize> {
+        self.write_at(offset, buf)
+    }
+
+    fn create(&self, name: &str, type_: InodeType, mode: InodeMode) -> Result<Arc<dyn Inode>> {
+        if name.len() > NAME_MAX {
+            return_errno!(Errno::ENAMETOOLONG);
+        }
+        if self.typ != InodeType::Dir {
+            return_errno_with_message!(Errno::ENOTDIR, "self is not dir");
+        }
+
+        let fs = self.p9_fs();
+        let dfid = self.bound_fid()?;
+        let gid = credentials().fsgid().as_u32();
+        let new_inode = match type_ {
+            InodeType::File => {
+                // Tlcreate turns the given fid into the opened new file, so
+                // create through a clone of the directory fid.
+                let open_fid = fs.client.alloc_fid();
+                fs.client.walk(dfid, open_fid, &[])?;
+                let flags = O_RDWR | O_CREAT | O_EXCL;
+                if let Err(e) = fs
+                    .client
+                    .lcreate(open_fid, name, flags, mode.bits() as u32, gid)
+                {
+                    let _ = fs.client.clunk(open_fid);
+                    return Err(e);
+                }
+                let new_inode = match self.walk_child(name) {
+                    Ok(new_inode) => new_inode,
+                    Err(e) => {
+                        let _ = fs.client.clunk(open_fid);
+                        return Err(e);
+                    }
+                };
+                *new_inode.open_fid.lock() = Some(OpenFid {
+                    fid: open_fid,
+                    is_writable: true,
+                });
+                new_inode
+            }
+            InodeType::Dir => {
+                fs.client.mkdir(dfid, name, mode.bits() as u32, gid)?;
+                self.walk_child(name)?
+            }
+            InodeType::SymLink => {
+                // The server creates a symlink along with its target, which is
+                // written later by `write_link`.
+                let new_inode = P9Inode::new(NOFID, 0, InodeType::SymLink, self.fs.clone());
+                *new_inode.pending_symlink.lock() =
+                    Some((self.this.upgrade().unwrap(), String::from(name), mode));
+                new_inode
+            }
+            InodeType::Socket | InodeType::NamedPipe => {
+                let mode = type_ as u32 | mode.bits() as u32;
+                fs.client.mknod(dfid, name, mode, (0, 0), gid)?;
+                self.walk_child(name)?
+            }
+            _ => return_errno_with_message!(Errno::EINVAL, "unsupported inode type"),
+        };
+        Ok(new_inode)
+    }
+
+    fn mknod(&self, name: &str, mode: InodeMode, dev: Arc<dyn Device>) -> Result<Arc<dyn Inode>> {
+        if name.len() > NAME_MAX {
+            return_errno!(Errno::ENAMETOOLONG);
+        }
+        let device_id = dev.id();
+        let mode = InodeType::from(dev.type_()) as u32 | mode.bits() as u32;
+        let gid = credentials().fsgid().as_u32();
+        self.p9_fs().client.mknod(
+            self.bound_fid()?,
+            name,
+            mode,
+            (device_id.major(), device_id.minor()),
+            gid,
+        )?;
+        let new_inode = self.walk_child(name)?;
+        Ok(new_inode)
+    }
+
+    fn readdir_at(&self, offset: usize, visitor: &mut dyn DirentVisitor) -> Result<usize> {
+        if self.typ != InodeType::Dir {
+            return_errno_with_message!(Errno::ENOTDIR, "self is not dir");
+        }
+
+        let fid = self.open(O_RDONLY | O_DIRECTORY)?;
+        let result = self.readdir_with_fid(fid, offset, visitor);
+        let _ = self.p9_fs().client.clunk(fid);
+        result
+    }
+
+    fn link(&self, old: &Arc<dyn Inode>, name: &str) -> Result<()> {
+        let old = old
+            .downcast_ref::<P9Inode>()
+            .ok_or(Error::new(Errno::EXDEV))?;
+        self.same_fs(old)?;
+        self.p9_fs()
+            .client
+            .link(self.bound_fid()?, old.bound_fid()?, name)
+    }
+
+    fn unlink(&self, name: &str) -> Result<()> {
+        self.p9_fs().client.unlinkat(self.bound_fid()?, name, 0)
+    }
+
+    fn rmdir(&self, name: &str) -> Result<()> {
+        self.p9_fs()
+            .client
+            .unlinkat(self.bound_fid()?, name, AT_REMOVEDIR)
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>> {
+        if self.typ != InodeType::Dir {
+            return_errno_with_message!(Errno::ENOTDIR, "self is not dir");
+        }
+        if name == "." {
+            return Ok(self.this.upgrade().unwrap());
+        }
+        let inode = self.walk_child(name)?;
+        Ok(inode)
+    }
+
+    fn rename(&self, old_name: &str, target: &Arc<dyn Inode>, new_name: &str) -> Result<()> {
+        let target = target
+            .downcast_ref::<P9Inode>()
+            .ok_or(Error::new(Errno::EXDEV))?;
+        self.same_fs(target)?;
+        self.p9_fs()
+            .client
+            .renameat(self.bound_fid()?, old_name, target.bound_fid()?, new_name)
+    }
+
+    fn read_link(&self) -> Result<String> {
+        if self.typ != InodeType::SymLink {
+            return_errno_with_message!(Errno::EINVAL, "self is not symlink");
+        }
+        self.p9_fs().client.readlink(self.bound_fid()?)
+    }
+
+    fn write_link(&self, target: &str) -> Result<()> {
+        if self.typ != InodeType::SymLink {
+            return_errno_with_message!(Errno::EINVAL, "self is not symlink");
+        }
+        self.create_symlink(target)
+    }
+
+    fn sync_all(&self) -> Result<()> {
+        self.fsync(false)
+    }
+
+    fn sync_data(&self) -> Result<()> {
+        self.fsync(true)
+    }
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        self.p9_fs()
+    }
+
+    /// The files may be changed by the host at any time, so the entries must
+    /// always be looked up from the server.
+    fn is_dentry_cacheable(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,26 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! A 9P2000.L client filesystem.
+//!
+//! The filesystem accesses the files of a 9P server through a virtio 9P
+//! device, which is how QEMU shares a host directory with `-virtfs`. It is
+//! mounted with the mount tag of the device as the source, e.g.,
+//! `mount -t 9p -o trans=virtio,version=9p2000.L hostshare /mnt`.
+//!
+//! Each inode holds a fid bound to its file, and a regular file is opened with
+//! another fid on its first I/O. Nothing is cached because the files may be
+//! changed by the host at any time.
+
+pub use fs::P9FS;
+pub use options::P9MountOptions;
+
+mod client;
+mod fs;
+mod options;
+mod protocol;
+
+const V9FS_MAGIC: u64 = 0x0102_1997;
+const NAME_MAX: usize = 255;
+const BLOCK_SIZE: usize = 4096;
+/// The maximum size of a 9P message if `msize` is not given.
+const DEFAULT_MSIZE: usize = 128 * 1024 + protocol::IO_HEADER_LEN;
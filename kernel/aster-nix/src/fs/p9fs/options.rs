@@ -0,0 +1,65 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::DEFAULT_MSIZE;
+use crate::prelude::*;
+
+/// The mount options of a 9P filesystem.
+///
+/// The options are given in the `data` argument of `mount`, e.g.,
+/// `trans=virtio,version=9p2000.L,msize=262144`, while the mount tag of the
+/// virtio 9P device is given as the source.
+#[derive(Debug, Clone)]
+pub struct P9MountOptions {
+    /// The user name to attach as.
+    pub uname: String,
+    /// The directory tree exported by the server to attach to.
+    pub aname: String,
+    /// The maximum size of a 9P message.
+    pub msize: usize,
+}
+
+impl Default for P9MountOptions {
+    fn default() -> Self {
+        Self {
+            uname: String::new(),
+            aname: String::new(),
+            msize: DEFAULT_MSIZE,
+        }
+    }
+}
+
+impl P9MountOptions {
+    /// The minimum message size, which leaves room for one page of data.
+    const MIN_MSIZE: usize = 2 * PAGE_SIZE;
+
+    /// Parses the options from the `data` argument of `mount`.
+    pub fn parse(data: &str) -> Result<Self> {
+        let mut options = Self::default();
+
+        for option in data.split(',').filter(|option| !option.is_empty()) {
+            let (key, value) = option.split_once('=').unwrap_or((option, ""));
+            match key {
+                "trans" if value == "virtio" => {}
+                "trans" => return_errno_with_message!(Errno::EINVAL, "only virtio is supported"),
+                "version" if value.eq_ignore_ascii_case("9p2000.L") => {}
+                "version" => {
+                    return_errno_with_message!(Errno::EINVAL, "only 9P2000.L is supported")
+                }
+                "uname" => options.uname = String::from(value),
+                "aname" => options.aname = String::from(value),
+                "msize" => {
+                    let msize = value
+                        .parse::<usize>()
+                        .map_err(|_| Error::with_message(Errno::EINVAL, "invalid msize"))?;
+                    if msize < Self::MIN_MSIZE {
+                        return_errno_with_message!(Errno::EINVAL, "msize is too small");
+                    }
+                    options.msize = msize;
+                }
+                _ => return_errno_with_message!(Errno::EINVAL, "unknown 9P option"),
+            }
+        }
+
+        Ok(options)
+    }
+}
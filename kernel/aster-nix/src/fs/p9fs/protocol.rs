@@ -0,0 +1,320 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The 9P2000.L protocol.
+//!
+//! A message is a little-endian `size[4] type[1] tag[2]` header followed by
+//! the fields of its type. Strings are encoded as `len[2]` followed by the
+//! UTF-8 bytes without a trailing NUL.
+
+#![allow(dead_code)]
+
+use crate::prelude::*;
+
+/// The version string of the protocol.
+pub(super) const P9_VERSION: &str = "9P2000.L";
+/// The tag of Tversion, which is sent when no other request is outstanding.
+pub(super) const NOTAG: u16 = !0;
+/// The fid that represents no file, e.g., as the `afid` of Tattach without authentication.
+pub(super) const NOFID: u32 = !0;
+/// The length of a message header.
+pub(super) const HEADER_LEN: usize = 7;
+/// The length of the header of Twrite and Rread before the data.
+pub(super) const IO_HEADER_LEN: usize = 24;
+
+/// The message types.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromInt)]
+pub(super) enum MsgType {
+    Rlerror = 7,
+    Tstatfs = 8,
+    Rstatfs = 9,
+    Tlopen = 12,
+    Rlopen = 13,
+    Tlcreate = 14,
+    Rlcreate = 15,
+    Tsymlink = 16,
+    Rsymlink = 17,
+    Tmknod = 18,
+    Rmknod = 19,
+    Treadlink = 22,
+    Rreadlink = 23,
+    Tgetattr = 24,
+    Rgetattr = 25,
+    Tsetattr = 26,
+    Rsetattr = 27,
+    Treaddir = 40,
+    Rreaddir = 41,
+    Tfsync = 50,
+    Rfsync = 51,
+    Tlink = 70,
+    Rlink = 71,
+    Tmkdir = 72,
+    Rmkdir = 73,
+    Trenameat = 74,
+    Rrenameat = 75,
+    Tunlinkat = 76,
+    Runlinkat = 77,
+    Tversion = 100,
+    Rversion = 101,
+    Tattach = 104,
+    Rattach = 105,
+    Twalk = 110,
+    Rwalk = 111,
+    Tread = 116,
+    Rread = 117,
+    Twrite = 118,
+    Rwrite = 119,
+    Tclunk = 120,
+    Rclunk = 121,
+}
+
+/// The unique identification of a file on the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Qid {
+    pub type_: u8,
+    pub version: u32,
+    pub path: u64,
+}
+
+impl Qid {
+    pub const QTDIR: u8 = 0x80;
+    pub const QTSYMLINK: u8 = 0x02;
+}
+
+/// The request mask of Tgetattr for the fields in `struct stat`.
+pub(super) const GETATTR_BASIC: u64 = 0x0000_07ff;
+
+/// The attributes replied by Rgetattr.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Attr {
+    pub qid: Qid,
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub nlink: u64,
+    pub rdev: u64,
+    pub size: u64,
+    pub blksize: u64,
+    pub blocks: u64,
+    pub atime: (u64, u64),
+    pub mtime: (u64, u64),
+    pub ctime: (u64, u64),
+}
+
+bitflags! {
+    /// The fields to be changed by Tsetattr.
+    pub(super) struct SetattrValid: u32 {
+        const MODE = 0x1;
+        const UID = 0x2;
+        const GID = 0x4;
+        const SIZE = 0x8;
+        const ATIME = 0x10;
+        const MTIME = 0x20;
+        const CTIME = 0x40;
+        const ATIME_SET = 0x80;
+        const MTIME_SET = 0x100;
+    }
+}
+
+/// The attributes to be changed by Tsetattr.
+#[derive(Debug, Clone, Copy, Default)]
+pub(super) struct SetAttr {
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub size: u64,
+    pub atime: (u64, u64),
+    pub mtime: (u64, u64),
+}
+
+/// The filesystem statistics replied by Rstatfs.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct StatFs {
+    pub type_: u32,
+    pub bsize: u32,
+    pub blocks: u64,
+    pub bfree: u64,
+    pub bavail: u64,
+    pub files: u64,
+    pub ffree: u64,
+    pub fsid: u64,
+    pub namelen: u32,
+}
+
+/// The `O_*` flags of Tlopen and Tlcreate.
+pub(super) const O_RDONLY: u32 = 0;
+pub(super) const O_RDWR: u32 = 2;
+pub(super) const O_CREAT: u32 = 0o100;
+pub(super) const O_EXCL: u32 = 0o200;
+pub(super) const O_DIRECTORY: u32 = 0o200000;
+
+/// The flag of Tunlinkat to remove a directory.
+pub(super) const AT_REMOVEDIR: u32 = 0x200;
+
+/// An encoder of a T-message.
+pub(super) struct MsgWriter {
+    buf: Vec<u8>,
+}
+
+impl MsgWriter {
+    /// Starts a message, whose size is filled by `finish`.
+    pub fn new(type_: MsgType, tag: u16) -> Self {
+        let mut writer = Self {
+            buf: Vec::with_capacity(64),
+        };
+        writer.put_u32(0).put_u8(type_ as u8).put_u16(tag);
+        writer
+    }
+
+    pub fn put_u8(&mut self, val: u8) -> &mut Self {
+        self.buf.push(val);
+        self
+    }
+
+    pub fn put_u16(&mut self, val: u16) -> &mut Self {
+        self.buf.extend_from_slice(&val.to_le_bytes());
+        self
+    }
+
+    pub fn put_u32(&mut self, val: u32) -> &mut Self {
+        self.buf.extend_from_slice(&val.to_le_bytes());
+        self
+    }
+
+    pub fn put_u64(&mut self, val: u64) -> &mut Self {
+        self.buf.extend_from_slice(&val.to_le_bytes());
+        self
+    }
+
+    pub fn put_str(&mut self, val: &str) -> &mut Self {
+        self.put_u16(val.len() as u16);
+        self.buf.extend_from_slice(val.as_bytes());
+        self
+    }
+
+    pub fn put_bytes(&mut self, val: &[u8]) -> &mut Self {
+        self.buf.extend_from_slice(val);
+        self
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        let size = self.buf.len() as u32;
+        self.buf[..4].copy_from_slice(&size.to_le_bytes());
+        self.buf
+    }
+}
+
+/// A decoder of the fields of an R-message.
+pub(super) struct MsgReader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> MsgReader<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.buf.len() < len {
+            return_errno_with_message!(Errno::EIO, "the 9P message is truncated");
+        }
+        let (bytes, rest) = self.buf.split_at(len);
+        self.buf = rest;
+        Ok(bytes)
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn get_u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn get_u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    pub fn get_u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn get_u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub fn get_str(&mut self) -> Result<&'a str> {
+        let len = self.get_u16()? as usize;
+        Ok(core::str::from_utf8(self.take(len)?)?)
+    }
+
+    pub fn get_bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        self.take(len)
+    }
+
+    pub fn get_qid(&mut self) -> Result<Qid> {
+        Ok(Qid {
+            type_: self.get_u8()?,
+            version: self.get_u32()?,
+            path: self.get_u64()?,
+        })
+    }
+
+    pub fn get_attr(&mut self) -> Result<Attr> {
+        let _valid = self.get_u64()?;
+        let attr = Attr {
+            qid: self.get_qid()?,
+            mode: self.get_u32()?,
+            uid: self.get_u32()?,
+            gid: self.get_u32()?,
+            nlink: self.get_u64()?,
+            rdev: self.get_u64()?,
+            size: self.get_u64()?,
+            blksize: self.get_u64()?,
+            blocks: self.get_u64()?,
+            atime: (self.get_u64()?, self.get_u64()?),
+            mtime: (self.get_u64()?, self.get_u64()?),
+            ctime: (self.get_u64()?, self.get_u64()?),
+        };
+        // The birth time, generation and data version are not used.
+        Ok(attr)
+    }
+
+    pub fn get_statfs(&mut self) -> Result<StatFs> {
+        Ok(StatFs {
+            type_: self.get_u32()?,
+            bsize: self.get_u32()?,
+            blocks: self.get_u64()?,
+            bfree: self.get_u64()?,
+            bavail: self.get_u64()?,
+            files: self.get_u64()?,
+            ffree: self.get_u64()?,
+            fsid: self.get_u64()?,
+            namelen: self.get_u32()?,
+        })
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn encode_and_decode() {
+        let mut writer = MsgWriter::new(MsgType::Twalk, 1);
+        writer.put_u32(2).put_u32(3).put_u16(1).put_str("dir");
+        let msg = writer.finish();
+        assert_eq!(msg.len(), HEADER_LEN + 4 + 4 + 2 + 2 + 3);
+        assert_eq!(&msg[..7], &[20, 0, 0, 0, MsgType::Twalk as u8, 1, 0]);
+
+        let mut reader = MsgReader::new(&msg[HEADER_LEN..]);
+        assert_eq!(reader.get_u32().unwrap(), 2);
+        assert_eq!(reader.get_u32().unwrap(), 3);
+        assert_eq!(reader.get_u16().unwrap(), 1);
+        assert_eq!(reader.get_str().unwrap(), "dir");
+        assert_eq!(reader.remaining(), 0);
+        assert!(reader.get_u8().is_err());
+    }
+}
@@ -450,6 +450,7 @@ impl Dentry {
 
     /// Crete a new Dentry to represent the child directory of a file system.
     pub fn new_fs_child(&self, name: &str, type_: InodeType, mode: InodeMode) -> Result<Arc<Self>> {
+        self.check_writable_mount()?;
         let new_child_dentry = self.inner.create(name, type_, mode)?;
         Ok(Self::new(self.mount_node.clone(), new_child_dentry.clone()))
     }
@@ -624,6 +625,7 @@ impl Dentry {
 
     /// Create a Dentry by making a device inode.
     pub fn mknod(&self, name: &str, mode: InodeMode, device: Arc<dyn Device>) -> Result<Arc<Self>> {
+        self.check_writable_mount()?;
         let inner = self.inner.mknod(name, mode, device)?;
         Ok(Self::new(self.mount_node.clone(), inner.clone()))
     }
@@ -633,16 +635,19 @@ impl Dentry {
         if !Arc::ptr_eq(&old.mount_node, &self.mount_node) {
             return_errno_with_message!(Errno::EXDEV, "cannot cross mount");
         }
+        self.check_writable_mount()?;
         self.inner.link(&old.inner, name)
     }
 
     /// Delete a Dentry by unlinking inode.
     pub fn unlink(&self, name: &str) -> Result<()> {
+        self.check_writable_mount()?;
         self.inner.unlink(name)
     }
 
     /// Delete a directory Dentry by rmdiring inode.
     pub fn rmdir(&self, name: &str) -> Result<()> {
+        self.check_writable_mount()?;
         self.inner.rmdir(name)
     }
 
@@ -651,6 +656,7 @@ impl Dentry {
         if !Arc::ptr_eq(&self.mount_node, &new_dir.mount_node) {
             return_errno_with_message!(Errno::EXDEV, "cannot cross mount");
         }
+        self.check_writable_mount()?;
         self.inner.rename(old_name, &new_dir.inner, new_name)
     }
 
@@ -667,6 +673,49 @@ impl Dentry {
         Ok(())
     }
 
+    /// Sets the mode of the inode, which must not be on a read-only mount.
+    pub fn set_mode(&self, mode: InodeMode) -> Result<()> {
+        self.check_writable_mount()?;
+        self.inner.set_mode(mode)
+    }
+
+    /// Sets the owner of the inode, which must not be on a read-only mount.
+    pub fn set_owner(&self, uid: Uid) -> Result<()> {
+        self.check_writable_mount()?;
+        self.inner.set_owner(uid)
+    }
+
+    /// Sets the group of the inode, which must not be on a read-only mount.
+    pub fn set_group(&self, gid: Gid) -> Result<()> {
+        self.check_writable_mount()?;
+        self.inner.set_group(gid)
+    }
+
+    /// Returns an error if the Dentry is on a read-only mount.
+    pub fn check_writable_mount(&self) -> Result<()> {
+        if self.mount_node.is_read_only() {
+            return_errno_with_message!(Errno::EROFS, "the mount is read-only");
+        }
+        Ok(())
+    }
+
+    /// Returns whether the Dentry is `ancestor` or one of its descendants,
+    /// across the mounts.
+    pub fn is_reachable_from(&self, ancestor: &Dentry) -> bool {
+        let mut dentry = self.this();
+        loop {
+            if Arc::ptr_eq(&dentry.mount_node, &ancestor.mount_node)
+                && Arc::ptr_eq(&dentry.inner, &ancestor.inner)
+            {
+                return true;
+            }
+            let Some(parent) = dentry.effective_parent() else {
+                return false;
+            };
+            dentry = parent;
+        }
+    }
+
     /// Get the arc reference to self.
     fn this(&self) -> Arc<Self> {
         self.this.upgrade().unwrap()
@@ -686,13 +735,10 @@ impl Dentry {
     pub fn metadata(&self) -> Metadata;
     pub fn type_(&self) -> InodeType;
     pub fn mode(&self) -> Result<InodeMode>;
-    pub fn set_mode(&self, mode: InodeMode) -> Result<()>;
     pub fn size(&self) -> usize;
     pub fn resize(&self, size: usize) -> Result<()>;
     pub fn owner(&self) -> Result<Uid>;
-    pub fn set_owner(&self, uid: Uid) -> Result<()>;
     pub fn group(&self) -> Result<Gid>;
-    pub fn set_group(&self, gid: Gid) -> Result<()>;
     pub fn atime(&self) -> Duration;
     pub fn set_atime(&self, time: Duration);
     pub fn mtime(&self) -> Duration;
@@ -1,5 +1,7 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use core::sync::atomic::{AtomicBool, Ordering};
+
 use crate::{
     fs::{
         path::dentry::{Dentry, DentryKey, Dentry_},
@@ -21,6 +23,8 @@ pub struct MountNode {
     parent: RwLock<Option<Weak<MountNode>>>,
     /// Child mount nodes which are mounted on one dentry of self.
     children: Mutex<BTreeMap<DentryKey, Arc<Self>>>,
+    /// Whether the files cannot be modified through this mount node.
+    read_only: AtomicBool,
     /// Reference to self.
     this: Weak<Self>,
 }
@@ -51,6 +55,7 @@ impl MountNode {
             mountpoint_dentry: RwLock::new(None),
             parent: RwLock::new(parent_mount),
             children: Mutex::new(BTreeMap::new()),
+            read_only: AtomicBool::new(false),
             fs,
             this: weak_self.clone(),
         })
@@ -107,6 +112,7 @@ impl MountNode {
             mountpoint_dentry: RwLock::new(None),
             parent: RwLock::new(None),
             children: Mutex::new(BTreeMap::new()),
+            read_only: AtomicBool::new(self.is_read_only()),
             fs: self.fs.clone(),
             this: weak_self.clone(),
         })
@@ -190,6 +196,25 @@ impl MountNode {
         Ok(())
     }
 
+    /// Detaches the mount node from its mountpoint, which makes it a root mount node.
+    ///
+    /// The mount node must not be the mount node of the mountpoint, or the mount tree
+    /// would be broken.
+    pub fn detach_as_root(&self) {
+        let Some(mountpoint_dentry) = self.mountpoint_dentry.write().take() else {
+            return;
+        };
+        if let Some(parent) = self
+            .parent
+            .write()
+            .take()
+            .and_then(|parent| parent.upgrade())
+        {
+            parent.children.lock().remove(&mountpoint_dentry.key());
+        }
+        mountpoint_dentry.clear_mountpoint();
+    }
+
     /// Try to get a child mount node from the mountpoint.
     pub fn get(&self, mountpoint: &Dentry) -> Option<Arc<Self>> {
         if !Arc::ptr_eq(mountpoint.mount_node(), &self.this()) {
@@ -198,6 +223,11 @@ impl MountNode {
         self.children.lock().get(&mountpoint.key()).cloned()
     }
 
+    /// Returns the child mount nodes which are mounted on the dentries of this mount node.
+    pub fn children(&self) -> Vec<Arc<Self>> {
+        self.children.lock().values().cloned().collect()
+    }
+
     /// Get the root `Dentry_` of this mount node.
     pub fn root_dentry(&self) -> &Arc<Dentry_> {
         &self.root_dentry
@@ -248,6 +278,16 @@ impl MountNode {
         self.this.upgrade().unwrap()
     }
 
+    /// Returns whether the files cannot be modified through this mount node.
+    pub fn is_read_only(&self) -> bool {
+        self.read_only.load(Ordering::Relaxed)
+    }
+
+    /// Sets whether the files cannot be modified through this mount node.
+    pub fn set_read_only(&self, read_only: bool) {
+        self.read_only.store(read_only, Ordering::Relaxed);
+    }
+
     /// Get the associated fs.
     pub fn fs(&self) -> &Arc<dyn FileSystem> {
         &self.fs
@@ -260,6 +300,7 @@ impl Debug for MountNode {
             .field("root", &self.root_dentry)
             .field("mountpoint", &self.mountpoint_dentry)
             .field("fs", &self.fs)
+            .field("read_only", &self.is_read_only())
             .finish()
     }
 }
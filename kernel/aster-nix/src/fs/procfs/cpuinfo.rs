@@ -0,0 +1,82 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::fmt::Write;
+
+use crate::{
+    arch::cpu::CpuInformation,
+    fs::{
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::Inode,
+    },
+    prelude::*,
+};
+
+/// Represents the inode at `/proc/cpuinfo`.
+pub struct CpuInfoFileOps;
+
+impl CpuInfoFileOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self).parent(parent).build().unwrap()
+    }
+}
+
+impl FileOps for CpuInfoFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        // All the CPUs are assumed to be identical.
+        let info = CpuInformation::read();
+        let tsc_khz = ostd::arch::tsc_freq() / 1000;
+        let num_cpus = ostd::cpu::num_cpus();
+
+        let mut output = String::new();
+        for cpu_id in 0..num_cpus {
+            let _ = write!(
+                output,
+                "processor\t: {}\n\
+                 vendor_id\t: {}\n\
+                 cpu family\t: {}\n\
+                 model\t\t: {}\n\
+                 model name\t: {}\n\
+                 stepping\t: {}\n\
+                 cpu MHz\t\t: {}.{:03}\n\
+                 physical id\t: 0\n\
+                 siblings\t: {}\n\
+                 core id\t\t: {}\n\
+                 cpu cores\t: {}\n\
+                 flags\t\t: {}\n\n",
+                cpu_id,
+                info.vendor_id,
+                info.cpu_family,
+                info.model,
+                info.model_name,
+                info.stepping,
+                tsc_khz / 1000,
+                tsc_khz % 1000,
+                num_cpus,
+                cpu_id,
+                num_cpus,
+                info.flags.join(" "),
+            );
+        }
+        Ok(output.into_bytes())
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn one_entry_per_cpu() {
+        let data = String::from_utf8(CpuInfoFileOps.data().unwrap()).unwrap();
+        let num_cpus = ostd::cpu::num_cpus() as usize;
+        assert_eq!(data.split_terminator("\n\n").count(), num_cpus);
+        let processors = data
+            .lines()
+            .filter(|line| line.starts_with("processor\t: "))
+            .count();
+        assert_eq!(processors, num_cpus);
+        assert!(data.lines().any(|line| line.starts_with("flags\t\t: ")));
+    }
+}
@@ -0,0 +1,36 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::fmt::Write;
+
+use crate::{
+    device::registry,
+    fs::{
+        device::DeviceType,
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::Inode,
+    },
+    prelude::*,
+};
+
+/// Represents the inode at `/proc/devices`, which lists the registered majors.
+pub struct DevicesFileOps;
+
+impl DevicesFileOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self).parent(parent).build().unwrap()
+    }
+}
+
+impl FileOps for DevicesFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let mut output = String::from("Character devices:\n");
+        for (major, name) in registry::all_majors(DeviceType::CharDevice) {
+            let _ = writeln!(output, "{:3} {}", major, name);
+        }
+        output.push_str("\nBlock devices:\n");
+        for (major, name) in registry::all_majors(DeviceType::BlockDevice) {
+            let _ = writeln!(output, "{:3} {}", major, name);
+        }
+        Ok(output.into_bytes())
+    }
+}
@@ -0,0 +1,66 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::fmt::Write;
+
+use crate::{
+    fs::{
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::Inode,
+    },
+    prelude::*,
+};
+
+/// The file system types that can be mounted, and whether they need a device.
+const FILESYSTEMS: &[(&str, bool)] = &[
+    ("ramfs", false),
+    ("tmpfs", false),
+    ("proc", false),
+    ("sysfs", false),
+    ("cgroup2", false),
+    ("devtmpfs", false),
+    ("devpts", false),
+    ("overlay", false),
+    ("fuse", false),
+    ("9p", false),
+    ("ext2", true),
+    ("exfat", true),
+    ("vfat", true),
+    ("squashfs", true),
+    ("erofs", true),
+];
+
+/// Represents the inode at `/proc/filesystems`.
+pub struct FileSystemsFileOps;
+
+impl FileSystemsFileOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self).parent(parent).build().unwrap()
+    }
+}
+
+impl FileOps for FileSystemsFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let mut output = String::new();
+        for (name, requires_dev) in FILESYSTEMS {
+            let nodev = if *requires_dev { "" } else { "nodev" };
+            let _ = writeln!(output, "{}\t{}", nodev, name);
+        }
+        Ok(output.into_bytes())
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn list_filesystems() {
+        let data = String::from_utf8(FileSystemsFileOps.data().unwrap()).unwrap();
+        let lines: Vec<&str> = data.lines().collect();
+        assert_eq!(lines.len(), FILESYSTEMS.len());
+        assert!(lines.contains(&"nodev\tproc"));
+        assert!(lines.contains(&"\text2"));
+    }
+}
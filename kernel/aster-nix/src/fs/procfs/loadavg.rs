@@ -0,0 +1,36 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::format;
+
+use crate::{
+    fs::{
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::Inode,
+    },
+    prelude::*,
+    sched::loadavg::{load_averages, nr_threads},
+    thread::last_allocated_tid,
+};
+
+/// Represents the inode at `/proc/loadavg`.
+pub struct LoadAvgFileOps;
+
+impl LoadAvgFileOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self).parent(parent).build().unwrap()
+    }
+}
+
+impl FileOps for LoadAvgFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let (nr_running, nr_total) = nr_threads();
+        let output = format!(
+            "{} {}/{} {}\n",
+            load_averages(),
+            nr_running,
+            nr_total,
+            last_allocated_tid()
+        );
+        Ok(output.into_bytes())
+    }
+}
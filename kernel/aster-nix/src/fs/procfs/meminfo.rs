@@ -0,0 +1,65 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::format;
+
+use ostd::mm::frame::{free_frames, total_frames};
+
+use crate::{
+    fs::{
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::Inode,
+    },
+    prelude::*,
+};
+
+/// Represents the inode at `/proc/meminfo`.
+pub struct MemInfoFileOps;
+
+impl MemInfoFileOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self).parent(parent).build().unwrap()
+    }
+}
+
+impl FileOps for MemInfoFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let total_kb = total_frames() * PAGE_SIZE / 1024;
+        let free_kb = free_frames() * PAGE_SIZE / 1024;
+        // There are no swap devices, and the page caches are not accounted
+        // separately, so all the free memory is available.
+        let output = format!(
+            "MemTotal:       {:8} kB\n\
+             MemFree:        {:8} kB\n\
+             MemAvailable:   {:8} kB\n\
+             Buffers:        {:8} kB\n\
+             Cached:         {:8} kB\n\
+             SwapCached:     {:8} kB\n\
+             Shmem:          {:8} kB\n\
+             SReclaimable:   {:8} kB\n\
+             SwapTotal:      {:8} kB\n\
+             SwapFree:       {:8} kB\n",
+            total_kb, free_kb, free_kb, 0, 0, 0, 0, 0, 0, 0
+        );
+        Ok(output.into_bytes())
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn report_frame_statistics() {
+        let data = String::from_utf8(MemInfoFileOps.data().unwrap()).unwrap();
+        let field = |name: &str| -> usize {
+            let line = data.lines().find(|line| line.starts_with(name)).unwrap();
+            let value = line[name.len() + 1..].trim().strip_suffix(" kB").unwrap();
+            value.parse().unwrap()
+        };
+        assert_eq!(field("MemTotal"), total_frames() * PAGE_SIZE / 1024);
+        assert!(field("MemFree") <= field("MemTotal"));
+        assert_eq!(field("SwapTotal"), 0);
+    }
+}
@@ -5,9 +5,17 @@ use core::sync::atomic::{AtomicU64, Ordering};
 use sys::SysDirOps;
 
 use self::{
+    cpuinfo::CpuInfoFileOps,
+    devices::DevicesFileOps,
+    filesystems::FileSystemsFileOps,
+    loadavg::LoadAvgFileOps,
+    meminfo::MemInfoFileOps,
+    mounts::MountsFileOps,
     pid::PidDirOps,
     self_::SelfSymOps,
+    stat::StatFileOps,
     template::{DirOps, ProcDir, ProcDirBuilder, ProcSymBuilder, SymOps},
+    uptime::UptimeFileOps,
 };
 use crate::{
     events::Observer,
@@ -16,10 +24,18 @@ use crate::{
     process::{process_table, process_table::PidEvent, Pid},
 };
 
+mod cpuinfo;
+mod devices;
+mod filesystems;
+mod loadavg;
+mod meminfo;
+mod mounts;
 mod pid;
 mod self_;
+mod stat;
 mod sys;
 mod template;
+mod uptime;
 
 /// Magic number.
 const PROC_MAGIC: u64 = 0x9fa0;
@@ -27,6 +43,8 @@ const PROC_MAGIC: u64 = 0x9fa0;
 const PROC_ROOT_INO: u64 = 1;
 /// Block size.
 const BLOCK_SIZE: usize = 1024;
+/// The frequency of the clock ticks in which the CPU times are reported.
+const USER_HZ: u128 = 100;
 
 pub struct ProcFS {
     sb: SuperBlock,
@@ -64,6 +82,10 @@ impl FileSystem for ProcFS {
     fn flags(&self) -> FsFlags {
         FsFlags::empty()
     }
+
+    fn name(&self) -> &'static str {
+        "proc"
+    }
 }
 
 /// Represents the inode at `/proc`.
@@ -92,16 +114,25 @@ impl Observer<PidEvent> for ProcDir<RootDirOps> {
 
 impl DirOps for RootDirOps {
     fn lookup_child(&self, this_ptr: Weak<dyn Inode>, name: &str) -> Result<Arc<dyn Inode>> {
-        let child = if name == "self" {
-            SelfSymOps::new_inode(this_ptr.clone())
-        } else if name == "sys" {
-            SysDirOps::new_inode(this_ptr.clone())
-        } else if let Ok(pid) = name.parse::<Pid>() {
-            let process_ref =
-                process_table::get_process(pid).ok_or_else(|| Error::new(Errno::ENOENT))?;
-            PidDirOps::new_inode(process_ref, this_ptr.clone())
-        } else {
-            return_errno!(Errno::ENOENT);
+        let child = match name {
+            "self" => SelfSymOps::new_inode(this_ptr.clone()),
+            "sys" => SysDirOps::new_inode(this_ptr.clone()),
+            "meminfo" => MemInfoFileOps::new_inode(this_ptr.clone()),
+            "stat" => StatFileOps::new_inode(this_ptr.clone()),
+            "uptime" => UptimeFileOps::new_inode(this_ptr.clone()),
+            "cpuinfo" => CpuInfoFileOps::new_inode(this_ptr.clone()),
+            "loadavg" => LoadAvgFileOps::new_inode(this_ptr.clone()),
+            "mounts" => MountsFileOps::new_inode(this_ptr.clone()),
+            "filesystems" => FileSystemsFileOps::new_inode(this_ptr.clone()),
+            "devices" => DevicesFileOps::new_inode(this_ptr.clone()),
+            _ => {
+                let Ok(pid) = name.parse::<Pid>() else {
+                    return_errno!(Errno::ENOENT);
+                };
+                let process_ref =
+                    process_table::get_process(pid).ok_or_else(|| Error::new(Errno::ENOENT))?;
+                PidDirOps::new_inode(process_ref, this_ptr.clone())
+            }
         };
         Ok(child)
     }
@@ -114,6 +145,22 @@ impl DirOps for RootDirOps {
         let mut cached_children = this.cached_children().write();
         cached_children.put_entry_if_not_found("self", || SelfSymOps::new_inode(this_ptr.clone()));
         cached_children.put_entry_if_not_found("sys", || SysDirOps::new_inode(this_ptr.clone()));
+        cached_children
+            .put_entry_if_not_found("meminfo", || MemInfoFileOps::new_inode(this_ptr.clone()));
+        cached_children.put_entry_if_not_found("stat", || StatFileOps::new_inode(this_ptr.clone()));
+        cached_children
+            .put_entry_if_not_found("uptime", || UptimeFileOps::new_inode(this_ptr.clone()));
+        cached_children
+            .put_entry_if_not_found("cpuinfo", || CpuInfoFileOps::new_inode(this_ptr.clone()));
+        cached_children
+            .put_entry_if_not_found("loadavg", || LoadAvgFileOps::new_inode(this_ptr.clone()));
+        cached_children
+            .put_entry_if_not_found("mounts", || MountsFileOps::new_inode(this_ptr.clone()));
+        cached_children.put_entry_if_not_found("filesystems", || {
+            FileSystemsFileOps::new_inode(this_ptr.clone())
+        });
+        cached_children
+            .put_entry_if_not_found("devices", || DevicesFileOps::new_inode(this_ptr.clone()));
 
         for process in process_table::process_table().iter() {
             let pid = process.pid().to_string();
@@ -0,0 +1,89 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::fmt::Write;
+
+use crate::{
+    fs::{
+        path::{Dentry, MountNode},
+        procfs::template::{FileOps, ProcFileBuilder},
+        rootfs::root_mount,
+        utils::Inode,
+    },
+    prelude::*,
+};
+
+/// The flag of `statfs` for a read-only file system.
+const ST_RDONLY: u64 = 1;
+
+/// Represents the inode at `/proc/mounts`.
+pub struct MountsFileOps;
+
+impl MountsFileOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self).parent(parent).build().unwrap()
+    }
+}
+
+impl FileOps for MountsFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let mut output = String::new();
+        write_mount_tree(&root_mount(), &mut output);
+        Ok(output.into_bytes())
+    }
+}
+
+/// Writes the lines of the mount node and its descendants in the pre-order.
+fn write_mount_tree(mount_node: &Arc<MountNode>, output: &mut String) {
+    let fs = mount_node.fs();
+    let path = Dentry::new_fs_root(mount_node.clone()).abs_path();
+    let mode = if mount_node.is_read_only() || fs.sb().flags & ST_RDONLY != 0 {
+        "ro"
+    } else {
+        "rw"
+    };
+    // The source devices are not recorded, so the names of the file systems are
+    // used instead, as Linux does for the pseudo file systems.
+    let _ = writeln!(output, "{0} {1} {0} {2} 0 0", fs.name(), path, mode);
+
+    for child in mount_node.children() {
+        write_mount_tree(&child, output);
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+    use crate::fs::{
+        ramfs::{RamFS, RamFsMountOptions},
+        utils::{InodeMode, InodeType},
+    };
+
+    #[ktest]
+    fn walk_mount_tree() {
+        crate::time::clocks::init_for_ktest();
+        let root_mount = MountNode::new_root(RamFS::new());
+        let root = Dentry::new_fs_root(root_mount.clone());
+        let mode = InodeMode::from_bits_truncate(0o755);
+        let mnt = root.new_fs_child("mnt", InodeType::Dir, mode).unwrap();
+        let child_mount = mnt
+            .mount(RamFS::new_with_options(RamFsMountOptions::default()))
+            .unwrap();
+        let child_root = Dentry::new_fs_root(child_mount);
+        child_root
+            .new_fs_child("sub", InodeType::Dir, mode)
+            .unwrap()
+            .mount(RamFS::new_devtmpfs())
+            .unwrap();
+
+        let mut output = String::new();
+        write_mount_tree(&root_mount, &mut output);
+        assert_eq!(
+            output,
+            "ramfs / ramfs rw 0 0\n\
+             tmpfs /mnt tmpfs rw 0 0\n\
+             devtmpfs /mnt/sub devtmpfs rw 0 0\n"
+        );
+    }
+}
@@ -0,0 +1,28 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use crate::{
+    fs::{
+        procfs::{ProcSymBuilder, SymOps},
+        utils::Inode,
+    },
+    prelude::*,
+    Process,
+};
+
+/// Represents the inode at `/proc/[pid]/cwd`.
+pub struct CwdSymOps(Arc<Process>);
+
+impl CwdSymOps {
+    pub fn new_inode(process_ref: Arc<Process>, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcSymBuilder::new(Self(process_ref))
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl SymOps for CwdSymOps {
+    fn read_link(&self) -> Result<String> {
+        Ok(self.0.fs().read().cwd().abs_path())
+    }
+}
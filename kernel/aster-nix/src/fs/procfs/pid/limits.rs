@@ -0,0 +1,109 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::fmt::Write;
+
+use crate::{
+    fs::{
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::Inode,
+    },
+    prelude::*,
+    process::ResourceType,
+    Process,
+};
+
+/// The names and the units of the resource limits, in the order of `ResourceType`.
+const LIMITS: [(&str, &str); 16] = [
+    ("Max cpu time", "seconds"),
+    ("Max file size", "bytes"),
+    ("Max data size", "bytes"),
+    ("Max stack size", "bytes"),
+    ("Max core file size", "bytes"),
+    ("Max resident set", "bytes"),
+    ("Max processes", "processes"),
+    ("Max open files", "files"),
+    ("Max locked memory", "bytes"),
+    ("Max address space", "bytes"),
+    ("Max file locks", "locks"),
+    ("Max pending signals", "signals"),
+    ("Max msgqueue size", "bytes"),
+    ("Max nice priority", ""),
+    ("Max realtime priority", ""),
+    ("Max realtime timeout", "us"),
+];
+
+/// Represents the inode at `/proc/[pid]/limits`.
+pub struct LimitsFileOps(Arc<Process>);
+
+impl LimitsFileOps {
+    pub fn new_inode(process_ref: Arc<Process>, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self(process_ref))
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for LimitsFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let format_limit = |limit: u64| {
+            if limit == u64::MAX {
+                String::from("unlimited")
+            } else {
+                limit.to_string()
+            }
+        };
+
+        let mut output = String::new();
+        let _ = writeln!(
+            output,
+            "{:<25} {:<20} {:<20} {:<10}",
+            "Limit", "Soft Limit", "Hard Limit", "Units"
+        );
+        let resource_limits = self.0.resource_limits().lock();
+        for (resource, (name, unit)) in LIMITS.iter().enumerate() {
+            let resource = ResourceType::try_from(resource as u32).unwrap();
+            let rlimit = resource_limits.get_rlimit(resource);
+            let _ = writeln!(
+                output,
+                "{:<25} {:<20} {:<20} {:<10}",
+                name,
+                format_limit(rlimit.get_cur()),
+                format_limit(rlimit.get_max()),
+                unit
+            );
+        }
+        Ok(output.into_bytes())
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+    use crate::process::RLimit64;
+
+    #[ktest]
+    fn report_rlimits() {
+        crate::time::clocks::init_for_ktest();
+        let process = Process::new_for_ktest(None);
+        *process
+            .resource_limits()
+            .lock()
+            .get_rlimit_mut(ResourceType::RLIMIT_CORE) = RLimit64::new(4096);
+
+        let data = String::from_utf8(LimitsFileOps(process).data().unwrap()).unwrap();
+        assert_eq!(data.lines().count(), 1 + LIMITS.len());
+        let fields = |name: &str| -> Vec<String> {
+            let line = data.lines().find(|line| line.starts_with(name)).unwrap();
+            line[name.len()..]
+                .split_whitespace()
+                .map(String::from)
+                .collect()
+        };
+        assert_eq!(fields("Max core file size"), ["4096", "unlimited", "bytes"]);
+        assert_eq!(fields("Max open files"), ["1024", "unlimited", "files"]);
+        assert_eq!(fields("Max nice priority"), ["unlimited", "unlimited"]);
+    }
+}
@@ -0,0 +1,96 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::format;
+use core::fmt::Write;
+
+use crate::{
+    fs::{
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::Inode,
+    },
+    prelude::*,
+    process::USER_HEAP_BASE,
+    vm::{perms::VmPerms, vmar::vm_mapping::VmMapping},
+    Process,
+};
+
+/// Represents the inode at `/proc/[pid]/maps`.
+pub struct MapsFileOps(Arc<Process>);
+
+impl MapsFileOps {
+    pub fn new_inode(process_ref: Arc<Process>, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self(process_ref))
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for MapsFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        if self.0.is_zombie() {
+            return Ok(Vec::new());
+        }
+        let mut output = String::new();
+        let stack_top = self.0.init_stack_reader().user_stack_top();
+        for vm_mapping in self.0.root_vmar().vm_mappings() {
+            write_mapping_line(&vm_mapping, stack_top, &mut output);
+        }
+        Ok(output.into_bytes())
+    }
+}
+
+/// Writes the line that describes the mapping as in `/proc/[pid]/maps`.
+///
+/// The mapping containing `stack_top` is named `[stack]`, and the one at the heap
+/// base is named `[heap]`.
+pub(super) fn write_mapping_line(vm_mapping: &VmMapping, stack_top: Vaddr, output: &mut String) {
+    let range = vm_mapping.range();
+    let perms = vm_mapping.perms();
+    let flag = |perm: VmPerms, ch: char| if perms.contains(perm) { ch } else { '-' };
+    let (offset, ino, name) = match vm_mapping.file() {
+        Some((dentry, offset)) => (offset, dentry.inode().ino(), dentry.abs_path()),
+        None if range.start == USER_HEAP_BASE => (0, 0, String::from("[heap]")),
+        None if range.contains(&stack_top) => (0, 0, String::from("[stack]")),
+        None => (0, 0, String::new()),
+    };
+
+    let line = format!(
+        "{:08x}-{:08x} {}{}{}{} {:08x} 00:00 {}",
+        range.start,
+        range.end,
+        flag(VmPerms::READ, 'r'),
+        flag(VmPerms::WRITE, 'w'),
+        flag(VmPerms::EXEC, 'x'),
+        if vm_mapping.is_shared() { 's' } else { 'p' },
+        offset,
+        ino
+    );
+    if name.is_empty() {
+        let _ = writeln!(output, "{}", line);
+    } else {
+        // The names are aligned at the same column as Linux does.
+        let _ = writeln!(output, "{:<72} {}", line, name);
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn list_mappings() {
+        crate::time::clocks::init_for_ktest();
+        let process = Process::new_for_ktest(None);
+        let nr_mappings = process.root_vmar().vm_mappings().len();
+
+        let data = String::from_utf8(MapsFileOps(process).data().unwrap()).unwrap();
+        assert_eq!(data.lines().count(), nr_mappings);
+        let heap = data.lines().find(|line| line.ends_with("[heap]")).unwrap();
+        let start = usize::from_str_radix(heap.split('-').next().unwrap(), 16).unwrap();
+        assert_eq!(start, USER_HEAP_BASE);
+        assert_eq!(heap.split_whitespace().nth(1), Some("rw-p"));
+    }
+}
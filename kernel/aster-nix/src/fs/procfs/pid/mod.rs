@@ -1,6 +1,11 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use self::{cmdline::CmdlineFileOps, comm::CommFileOps, exe::ExeSymOps, fd::FdDirOps};
+use self::{
+    cgroup::CgroupFileOps, cmdline::CmdlineFileOps, comm::CommFileOps, cwd::CwdSymOps,
+    environ::EnvironFileOps, exe::ExeSymOps, fd::FdDirOps, limits::LimitsFileOps,
+    maps::MapsFileOps, root::RootSymOps, smaps::SmapsFileOps, stat::StatFileOps,
+    statm::StatmFileOps, status::StatusFileOps, task::TaskDirOps,
+};
 use super::template::{DirOps, ProcDir, ProcDirBuilder};
 use crate::{
     events::Observer,
@@ -12,10 +17,21 @@ use crate::{
     process::Process,
 };
 
+mod cgroup;
 mod cmdline;
 mod comm;
+mod cwd;
+mod environ;
 mod exe;
 mod fd;
+mod limits;
+mod maps;
+mod root;
+mod smaps;
+mod stat;
+mod statm;
+mod status;
+mod task;
 
 /// Represents the inode at `/proc/[pid]`.
 pub struct PidDirOps(Arc<Process>);
@@ -49,8 +65,19 @@ impl DirOps for PidDirOps {
         let inode = match name {
             "exe" => ExeSymOps::new_inode(self.0.clone(), this_ptr.clone()),
             "comm" => CommFileOps::new_inode(self.0.clone(), this_ptr.clone()),
+            "cgroup" => CgroupFileOps::new_inode(self.0.clone(), this_ptr.clone()),
             "fd" => FdDirOps::new_inode(self.0.clone(), this_ptr.clone()),
             "cmdline" => CmdlineFileOps::new_inode(self.0.clone(), this_ptr.clone()),
+            "environ" => EnvironFileOps::new_inode(self.0.clone(), this_ptr.clone()),
+            "cwd" => CwdSymOps::new_inode(self.0.clone(), this_ptr.clone()),
+            "root" => RootSymOps::new_inode(self.0.clone(), this_ptr.clone()),
+            "status" => StatusFileOps::new_inode(self.0.clone(), None, this_ptr.clone()),
+            "stat" => StatFileOps::new_inode(self.0.clone(), None, this_ptr.clone()),
+            "statm" => StatmFileOps::new_inode(self.0.clone(), this_ptr.clone()),
+            "maps" => MapsFileOps::new_inode(self.0.clone(), this_ptr.clone()),
+            "smaps" => SmapsFileOps::new_inode(self.0.clone(), this_ptr.clone()),
+            "limits" => LimitsFileOps::new_inode(self.0.clone(), this_ptr.clone()),
+            "task" => TaskDirOps::new_inode(self.0.clone(), this_ptr.clone()),
             _ => return_errno!(Errno::ENOENT),
         };
         Ok(inode)
@@ -68,11 +95,44 @@ impl DirOps for PidDirOps {
         cached_children.put_entry_if_not_found("comm", || {
             CommFileOps::new_inode(self.0.clone(), this_ptr.clone())
         });
+        cached_children.put_entry_if_not_found("cgroup", || {
+            CgroupFileOps::new_inode(self.0.clone(), this_ptr.clone())
+        });
         cached_children.put_entry_if_not_found("fd", || {
             FdDirOps::new_inode(self.0.clone(), this_ptr.clone())
         });
         cached_children.put_entry_if_not_found("cmdline", || {
             CmdlineFileOps::new_inode(self.0.clone(), this_ptr.clone())
         });
+        cached_children.put_entry_if_not_found("environ", || {
+            EnvironFileOps::new_inode(self.0.clone(), this_ptr.clone())
+        });
+        cached_children.put_entry_if_not_found("cwd", || {
+            CwdSymOps::new_inode(self.0.clone(), this_ptr.clone())
+        });
+        cached_children.put_entry_if_not_found("root", || {
+            RootSymOps::new_inode(self.0.clone(), this_ptr.clone())
+        });
+        cached_children.put_entry_if_not_found("status", || {
+            StatusFileOps::new_inode(self.0.clone(), None, this_ptr.clone())
+        });
+        cached_children.put_entry_if_not_found("stat", || {
+            StatFileOps::new_inode(self.0.clone(), None, this_ptr.clone())
+        });
+        cached_children.put_entry_if_not_found("statm", || {
+            StatmFileOps::new_inode(self.0.clone(), this_ptr.clone())
+        });
+        cached_children.put_entry_if_not_found("maps", || {
+            MapsFileOps::new_inode(self.0.clone(), this_ptr.clone())
+        });
+        cached_children.put_entry_if_not_found("smaps", || {
+            SmapsFileOps::new_inode(self.0.clone(), this_ptr.clone())
+        });
+        cached_children.put_entry_if_not_found("limits", || {
+            LimitsFileOps::new_inode(self.0.clone(), this_ptr.clone())
+        });
+        cached_children.put_entry_if_not_found("task", || {
+            TaskDirOps::new_inode(self.0.clone(), this_ptr.clone())
+        });
     }
 }
@@ -0,0 +1,72 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::format;
+use core::fmt::Write;
+
+use super::maps::write_mapping_line;
+use crate::{
+    fs::{
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::Inode,
+    },
+    prelude::*,
+    Process,
+};
+
+/// Represents the inode at `/proc/[pid]/smaps`.
+pub struct SmapsFileOps(Arc<Process>);
+
+impl SmapsFileOps {
+    pub fn new_inode(process_ref: Arc<Process>, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self(process_ref))
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for SmapsFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        if self.0.is_zombie() {
+            return Ok(Vec::new());
+        }
+        let mut output = String::new();
+        let stack_top = self.0.init_stack_reader().user_stack_top();
+        for vm_mapping in self.0.root_vmar().vm_mappings() {
+            write_mapping_line(&vm_mapping, stack_top, &mut output);
+
+            let size_kb = vm_mapping.map_size() / 1024;
+            let rss_kb = vm_mapping.nr_mapped_pages() * PAGE_SIZE / 1024;
+            let is_anonymous = vm_mapping.file().is_none();
+            // The dirtiness of the pages is not tracked, so the resident pages are
+            // regarded as dirty for the anonymous mappings and clean for the file ones.
+            let (shared_clean, shared_dirty, private_clean, private_dirty) =
+                match (vm_mapping.is_shared(), is_anonymous) {
+                    (true, true) => (0, rss_kb, 0, 0),
+                    (true, false) => (rss_kb, 0, 0, 0),
+                    (false, true) => (0, 0, 0, rss_kb),
+                    (false, false) => (0, 0, rss_kb, 0),
+                };
+            let fields = [
+                ("Size", size_kb),
+                ("KernelPageSize", PAGE_SIZE / 1024),
+                ("MMUPageSize", PAGE_SIZE / 1024),
+                ("Rss", rss_kb),
+                ("Pss", rss_kb),
+                ("Shared_Clean", shared_clean),
+                ("Shared_Dirty", shared_dirty),
+                ("Private_Clean", private_clean),
+                ("Private_Dirty", private_dirty),
+                ("Referenced", rss_kb),
+                ("Anonymous", if is_anonymous { rss_kb } else { 0 }),
+                ("Swap", 0),
+                ("SwapPss", 0),
+                ("Locked", 0),
+            ];
+            for (name, value) in fields {
+                let _ = writeln!(output, "{:<16}{:>8} kB", format!("{}:", name), value);
+            }
+        }
+        Ok(output.into_bytes())
+    }
+}
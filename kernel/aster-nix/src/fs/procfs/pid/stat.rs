@@ -0,0 +1,118 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::format;
+use core::{sync::atomic::Ordering, time::Duration};
+
+use super::status::{comm_of, ignored_and_caught_signals, thread_state, MemoryUsage};
+use crate::{
+    fs::{
+        procfs::{
+            template::{FileOps, ProcFileBuilder},
+            USER_HZ,
+        },
+        utils::Inode,
+    },
+    prelude::*,
+    process::{posix_thread::PosixThreadExt, ResourceType},
+    thread::Thread,
+    Process,
+};
+
+/// Represents the inode at `/proc/[pid]/stat` or `/proc/[pid]/task/[tid]/stat`.
+pub struct StatFileOps {
+    process: Arc<Process>,
+    /// The thread to report, or the whole process if it is `None`
+    thread: Option<Arc<Thread>>,
+}
+
+impl StatFileOps {
+    pub fn new_inode(
+        process: Arc<Process>,
+        thread: Option<Arc<Thread>>,
+        parent: Weak<dyn Inode>,
+    ) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self { process, thread })
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for StatFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let process = &self.process;
+        let thread = self.thread.clone().or_else(|| process.main_thread());
+        let posix_thread = thread.as_ref().and_then(|thread| thread.as_posix_thread());
+        let (state, _) = thread_state(process, thread.as_ref());
+        let tid = thread.as_ref().map_or(process.pid(), |thread| thread.tid());
+        let ppid = process.parent().map_or(0, |parent| parent.pid());
+        let sid = process.session().map_or(0, |session| session.sid());
+
+        let to_ticks = |time: Duration| (time.as_millis() * USER_HZ / 1000) as u64;
+        // The usage of a thread only includes its own, while that of a process
+        // includes all its threads.
+        let rusage = match (&self.thread, posix_thread) {
+            (Some(_), Some(posix_thread)) => posix_thread
+                .resource_usage()
+                .snapshot(posix_thread.prof_clock()),
+            _ => process.rusage(),
+        };
+        let children_rusage = *process.children_rusage().lock();
+
+        let nice = process.nice().load(Ordering::Relaxed).to_raw();
+        let nr_threads = process.threads().lock().len();
+        let usage = MemoryUsage::of(process);
+        let rss_limit = process
+            .resource_limits()
+            .lock()
+            .get_rlimit(ResourceType::RLIMIT_RSS)
+            .get_cur();
+        let start_stack = if process.is_zombie() {
+            0
+        } else {
+            process.init_stack_reader().user_stack_top()
+        };
+        let (pending, blocked) = posix_thread.map_or((0, 0), |posix_thread| {
+            (
+                posix_thread.sig_pending().as_u64(),
+                posix_thread.sig_mask().lock().as_u64(),
+            )
+        });
+        let (ignored, caught) = ignored_and_caught_signals(process);
+        let exit_code = process.exit_code().unwrap_or(0);
+
+        // The fields are in the order of `proc_pid_stat(5)`. The unsupported ones are zeros.
+        let output = format!(
+            "{} ({}) {} {} {} {} 0 -1 0 {} {} {} {} {} {} {} {} {} {} {} 0 0 {} {} {} 0 0 {} 0 0 \
+             {} {} {} {} 0 0 0 17 {} 0 0 0 0 0 0 0 0 0 0 0 0 {}\n",
+            tid,
+            comm_of(process),
+            state,
+            ppid,
+            process.pgid(),
+            sid,
+            rusage.minflt,
+            children_rusage.minflt,
+            rusage.majflt,
+            children_rusage.majflt,
+            to_ticks(rusage.utime),
+            to_ticks(rusage.stime),
+            to_ticks(children_rusage.utime),
+            to_ticks(children_rusage.stime),
+            20 + nice as i32,
+            nice,
+            nr_threads,
+            usage.size,
+            usage.resident / PAGE_SIZE,
+            rss_limit,
+            start_stack,
+            pending,
+            blocked,
+            ignored.as_u64(),
+            caught.as_u64(),
+            ostd::cpu::this_cpu(),
+            exit_code,
+        );
+        Ok(output.into_bytes())
+    }
+}
@@ -0,0 +1,74 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::format;
+
+use super::status::MemoryUsage;
+use crate::{
+    fs::{
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::Inode,
+    },
+    prelude::*,
+    Process,
+};
+
+/// Represents the inode at `/proc/[pid]/statm`.
+pub struct StatmFileOps(Arc<Process>);
+
+impl StatmFileOps {
+    pub fn new_inode(process_ref: Arc<Process>, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self(process_ref))
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for StatmFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let usage = MemoryUsage::of(&self.0);
+        // The sizes are in pages: size, resident, shared, text, lib (always 0),
+        // data and dt (always 0).
+        let output = format!(
+            "{} {} {} {} 0 {} 0\n",
+            usage.size / PAGE_SIZE,
+            usage.resident / PAGE_SIZE,
+            usage.shared / PAGE_SIZE,
+            usage.text / PAGE_SIZE,
+            usage.data / PAGE_SIZE
+        );
+        Ok(output.into_bytes())
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn count_pages() {
+        crate::time::clocks::init_for_ktest();
+        let process = Process::new_for_ktest(None);
+        let size: usize = process
+            .root_vmar()
+            .vm_mappings()
+            .iter()
+            .map(|vm_mapping| vm_mapping.map_size())
+            .sum();
+
+        let data = String::from_utf8(StatmFileOps(process).data().unwrap()).unwrap();
+        let fields: Vec<usize> = data
+            .split_whitespace()
+            .map(|field| field.parse().unwrap())
+            .collect();
+        let [total, resident, shared, text, lib, data, dt] = fields[..] else {
+            panic!("invalid statm: {}", data);
+        };
+        assert_eq!(total, size / PAGE_SIZE);
+        assert!(resident <= total);
+        // The heap and the stack are private and writable anonymous mappings.
+        assert_eq!((shared, text, lib, data, dt), (0, 0, 0, total, 0));
+    }
+}
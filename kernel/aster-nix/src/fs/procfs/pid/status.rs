@@ -0,0 +1,300 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::format;
+use core::fmt::Write;
+
+use crate::{
+    fs::{
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::Inode,
+    },
+    prelude::*,
+    process::{
+        posix_thread::PosixThreadExt,
+        signal::{sig_action::SigAction, sig_mask::SigSet, sig_num::SigNum},
+    },
+    thread::{status::ThreadStatus, Thread},
+    vm::perms::VmPerms,
+    Process,
+};
+
+/// Represents the inode at `/proc/[pid]/status` or `/proc/[pid]/task/[tid]/status`.
+pub struct StatusFileOps {
+    process: Arc<Process>,
+    /// The thread to report, or the main thread if it is `None`
+    thread: Option<Arc<Thread>>,
+}
+
+impl StatusFileOps {
+    pub fn new_inode(
+        process: Arc<Process>,
+        thread: Option<Arc<Thread>>,
+        parent: Weak<dyn Inode>,
+    ) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self { process, thread })
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for StatusFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let process = &self.process;
+        let thread = self.thread.clone().or_else(|| process.main_thread());
+        let posix_thread = thread.as_ref().and_then(|thread| thread.as_posix_thread());
+        let (state, state_name) = thread_state(process, thread.as_ref());
+        let tid = thread.as_ref().map_or(process.pid(), |thread| thread.tid());
+        let ppid = process.parent().map_or(0, |parent| parent.pid());
+        let sid = process.session().map_or(0, |session| session.sid());
+
+        let mut output = String::new();
+        let _ = writeln!(output, "Name:\t{}", comm_of(process));
+        let _ = writeln!(output, "Umask:\t{:04o}", process.umask().read().get());
+        let _ = writeln!(output, "State:\t{} ({})", state, state_name);
+        let _ = writeln!(output, "Tgid:\t{}", process.pid());
+        let _ = writeln!(output, "Pid:\t{}", tid);
+        let _ = writeln!(output, "PPid:\t{}", ppid);
+        let _ = writeln!(output, "TracerPid:\t0");
+        let _ = writeln!(output, "NSpgid:\t{}", process.pgid());
+        let _ = writeln!(output, "NSsid:\t{}", sid);
+
+        if let Some(posix_thread) = posix_thread {
+            let credentials = posix_thread.credentials();
+            let _ = writeln!(
+                output,
+                "Uid:\t{}\t{}\t{}\t{}",
+                credentials.ruid().as_u32(),
+                credentials.euid().as_u32(),
+                credentials.suid().as_u32(),
+                credentials.fsuid().as_u32()
+            );
+            let _ = writeln!(
+                output,
+                "Gid:\t{}\t{}\t{}\t{}",
+                credentials.rgid().as_u32(),
+                credentials.egid().as_u32(),
+                credentials.sgid().as_u32(),
+                credentials.fsgid().as_u32()
+            );
+            let groups: Vec<String> = credentials
+                .groups()
+                .iter()
+                .map(|gid| gid.as_u32().to_string())
+                .collect();
+            let _ = writeln!(output, "Groups:\t{}", groups.join(" "));
+        }
+
+        let max_fd = process
+            .file_table()
+            .lock()
+            .fds_and_files()
+            .map(|(fd, _)| fd as usize)
+            .max();
+        // The file table is regarded to grow in the units of 64 slots as Linux does.
+        let fd_size = max_fd.map_or(0, |fd| fd + 1).next_multiple_of(64).max(64);
+        let _ = writeln!(output, "FDSize:\t{}", fd_size);
+
+        let usage = MemoryUsage::of(process);
+        if usage.size > 0 {
+            let _ = writeln!(output, "VmPeak:\t{:8} kB", usage.size / 1024);
+            let _ = writeln!(output, "VmSize:\t{:8} kB", usage.size / 1024);
+            let _ = writeln!(output, "VmRSS:\t{:8} kB", usage.resident / 1024);
+            let _ = writeln!(output, "RssAnon:\t{:8} kB", usage.anonymous / 1024);
+            let _ = writeln!(
+                output,
+                "RssFile:\t{:8} kB",
+                (usage.resident - usage.anonymous) / 1024
+            );
+            let _ = writeln!(output, "VmData:\t{:8} kB", usage.data / 1024);
+            let _ = writeln!(output, "VmExe:\t{:8} kB", usage.text / 1024);
+        }
+
+        let _ = writeln!(output, "Threads:\t{}", process.threads().lock().len());
+        if let Some(posix_thread) = posix_thread {
+            let _ = writeln!(
+                output,
+                "SigPnd:\t{:016x}",
+                posix_thread.sig_pending().as_u64()
+            );
+            let _ = writeln!(output, "ShdPnd:\t{:016x}", 0);
+            let _ = writeln!(
+                output,
+                "SigBlk:\t{:016x}",
+                posix_thread.sig_mask().lock().as_u64()
+            );
+        }
+        let (ignored, caught) = ignored_and_caught_signals(process);
+        let _ = writeln!(output, "SigIgn:\t{:016x}", ignored.as_u64());
+        let _ = writeln!(output, "SigCgt:\t{:016x}", caught.as_u64());
+
+        if let Some(posix_thread) = posix_thread {
+            let credentials = posix_thread.credentials();
+            let _ = writeln!(
+                output,
+                "CapInh:\t{:016x}",
+                credentials.inheritable_capset().bits()
+            );
+            let _ = writeln!(
+                output,
+                "CapPrm:\t{:016x}",
+                credentials.permitted_capset().bits()
+            );
+            let _ = writeln!(
+                output,
+                "CapEff:\t{:016x}",
+                credentials.effective_capset().bits()
+            );
+        }
+        let _ = writeln!(
+            output,
+            "Cpus_allowed_list:\t0-{}",
+            ostd::cpu::num_cpus() - 1
+        );
+        if let Some(posix_thread) = posix_thread {
+            // The context switches of a process are the sum of all its threads, like Linux.
+            let rusage = if self.thread.is_some() {
+                posix_thread
+                    .resource_usage()
+                    .snapshot(posix_thread.prof_clock())
+            } else {
+                process.rusage()
+            };
+            let _ = writeln!(output, "voluntary_ctxt_switches:\t{}", rusage.nvcsw);
+            let _ = writeln!(output, "nonvoluntary_ctxt_switches:\t{}", rusage.nivcsw);
+        }
+        Ok(output.into_bytes())
+    }
+}
+
+/// Returns the state of the thread, in the single-character code and the full name.
+///
+/// The main thread is used if `thread` is `None`. If there are no threads, the process
+/// is regarded as a zombie.
+pub(super) fn thread_state(
+    process: &Process,
+    thread: Option<&Arc<Thread>>,
+) -> (char, &'static str) {
+    if process.is_zombie() {
+        return ('Z', "zombie");
+    }
+    let Some(thread) = thread.cloned().or_else(|| process.main_thread()) else {
+        return ('Z', "zombie");
+    };
+    match thread.status() {
+        ThreadStatus::Stopped => ('T', "stopped"),
+        ThreadStatus::Exited => ('X', "dead"),
+        _ if thread.is_blocked() => ('S', "sleeping"),
+        _ => ('R', "running"),
+    }
+}
+
+/// Returns the command name of the process, which is the file name of the executable.
+pub(super) fn comm_of(process: &Process) -> String {
+    let exe_path = process.executable_path();
+    let name = exe_path.rsplit('/').next().unwrap_or(&exe_path);
+    name.chars().take(TASK_COMM_LEN - 1).collect()
+}
+
+/// Returns the signals that are ignored and caught by the process.
+pub(super) fn ignored_and_caught_signals(process: &Process) -> (SigSet, SigSet) {
+    let mut ignored = SigSet::new_empty();
+    let mut caught = SigSet::new_empty();
+    let sig_dispositions = process.sig_dispositions().lock();
+    for num in (1..=MAX_SIG_NUM).filter_map(|num| SigNum::try_from(num).ok()) {
+        match sig_dispositions.get(num) {
+            SigAction::Ign => ignored.add_signal(num),
+            SigAction::User { .. } => caught.add_signal(num),
+            SigAction::Dfl => (),
+        }
+    }
+    (ignored, caught)
+}
+
+/// The memory usage of a process in bytes.
+pub(super) struct MemoryUsage {
+    /// The total size of the mappings
+    pub size: usize,
+    /// The size of the resident pages
+    pub resident: usize,
+    /// The size of the resident pages that are not backed by files
+    pub anonymous: usize,
+    /// The size of the resident pages in the shared mappings
+    pub shared: usize,
+    /// The size of the executable mappings of files
+    pub text: usize,
+    /// The size of the writable private mappings
+    pub data: usize,
+}
+
+impl MemoryUsage {
+    pub(super) fn of(process: &Process) -> Self {
+        let mut usage = Self {
+            size: 0,
+            resident: 0,
+            anonymous: 0,
+            shared: 0,
+            text: 0,
+            data: 0,
+        };
+        if process.is_zombie() {
+            return usage;
+        }
+
+        for vm_mapping in process.root_vmar().vm_mappings() {
+            let perms = vm_mapping.perms();
+            let resident = vm_mapping.nr_mapped_pages() * PAGE_SIZE;
+            usage.size += vm_mapping.map_size();
+            usage.resident += resident;
+            if vm_mapping.file().is_none() {
+                usage.anonymous += resident;
+            }
+            if vm_mapping.is_shared() {
+                usage.shared += resident;
+            } else if perms.contains(VmPerms::WRITE) {
+                usage.data += vm_mapping.map_size();
+            }
+            if perms.contains(VmPerms::EXEC) && vm_mapping.file().is_some() {
+                usage.text += vm_mapping.map_size();
+            }
+        }
+        usage
+    }
+}
+
+const TASK_COMM_LEN: usize = 16;
+const MAX_SIG_NUM: u8 = 64;
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn process_without_threads() {
+        crate::time::clocks::init_for_ktest();
+        let parent = Process::new_for_ktest(None);
+        let process = Process::new_for_ktest(Some(parent.clone()));
+
+        let status_ops = StatusFileOps {
+            process: process.clone(),
+            thread: None,
+        };
+        let data = String::from_utf8(status_ops.data().unwrap()).unwrap();
+        let field = |name: &str| -> String {
+            data.lines()
+                .find_map(|line| line.strip_prefix(name)?.strip_prefix(":\t"))
+                .map(String::from)
+                .unwrap()
+        };
+        // A process is regarded as a zombie after all its threads exit.
+        assert_eq!(field("State"), "Z (zombie)");
+        assert_eq!(field("Tgid"), process.pid().to_string());
+        assert_eq!(field("Pid"), process.pid().to_string());
+        assert_eq!(field("PPid"), parent.pid().to_string());
+        assert_eq!(field("Threads"), "0");
+        assert_eq!(field("SigIgn"), "0000000000000000");
+        assert!(!data.contains("Uid:"));
+    }
+}
@@ -0,0 +1,129 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::{comm::CommFileOps, stat::StatFileOps, status::StatusFileOps};
+use crate::{
+    fs::{
+        procfs::template::{DirOps, ProcDir, ProcDirBuilder},
+        utils::{DirEntryVecExt, Inode},
+    },
+    prelude::*,
+    process::posix_thread::PosixThreadExt,
+    thread::{Thread, Tid},
+    Process,
+};
+
+/// Represents the inode at `/proc/[pid]/task`.
+pub struct TaskDirOps(Arc<Process>);
+
+impl TaskDirOps {
+    pub fn new_inode(process_ref: Arc<Process>, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcDirBuilder::new(Self(process_ref))
+            .parent(parent)
+            // The threads come and go, so the entries must be looked up every time.
+            .volatile()
+            .build()
+            .unwrap()
+    }
+
+    fn posix_threads(&self) -> Vec<Arc<Thread>> {
+        self.0
+            .threads()
+            .lock()
+            .iter()
+            .filter(|thread| thread.as_posix_thread().is_some())
+            .cloned()
+            .collect()
+    }
+}
+
+impl DirOps for TaskDirOps {
+    fn lookup_child(&self, this_ptr: Weak<dyn Inode>, name: &str) -> Result<Arc<dyn Inode>> {
+        let tid = name.parse::<Tid>().map_err(|_| Error::new(Errno::ENOENT))?;
+        let thread = self
+            .posix_threads()
+            .into_iter()
+            .find(|thread| thread.tid() == tid)
+            .ok_or_else(|| Error::new(Errno::ENOENT))?;
+        Ok(TidDirOps::new_inode(self.0.clone(), thread, this_ptr))
+    }
+
+    fn populate_children(&self, this_ptr: Weak<dyn Inode>) {
+        let this = {
+            let this = this_ptr.upgrade().unwrap();
+            this.downcast_ref::<ProcDir<TaskDirOps>>().unwrap().this()
+        };
+        let threads = self.posix_threads();
+        let mut cached_children = this.cached_children().write();
+
+        // Remove the threads that have exited.
+        let exited: Vec<String> = cached_children
+            .iter()
+            .map(|(name, _)| name.clone())
+            .filter(|name| {
+                !threads
+                    .iter()
+                    .any(|thread| thread.tid().to_string() == *name)
+            })
+            .collect();
+        for name in exited {
+            cached_children.remove_entry_by_name(&name);
+        }
+
+        for thread in threads {
+            cached_children.put_entry_if_not_found(&thread.tid().to_string(), || {
+                TidDirOps::new_inode(self.0.clone(), thread.clone(), this_ptr.clone())
+            });
+        }
+    }
+}
+
+/// Represents the inode at `/proc/[pid]/task/[tid]`.
+struct TidDirOps {
+    process: Arc<Process>,
+    thread: Arc<Thread>,
+}
+
+impl TidDirOps {
+    pub fn new_inode(
+        process: Arc<Process>,
+        thread: Arc<Thread>,
+        parent: Weak<dyn Inode>,
+    ) -> Arc<dyn Inode> {
+        ProcDirBuilder::new(Self { process, thread })
+            .parent(parent)
+            .volatile()
+            .build()
+            .unwrap()
+    }
+
+    fn new_child(&self, name: &str, this_ptr: Weak<dyn Inode>) -> Option<Arc<dyn Inode>> {
+        let process = self.process.clone();
+        let thread = Some(self.thread.clone());
+        let inode = match name {
+            "comm" => CommFileOps::new_inode(process, this_ptr),
+            "stat" => StatFileOps::new_inode(process, thread, this_ptr),
+            "status" => StatusFileOps::new_inode(process, thread, this_ptr),
+            _ => return None,
+        };
+        Some(inode)
+    }
+}
+
+impl DirOps for TidDirOps {
+    fn lookup_child(&self, this_ptr: Weak<dyn Inode>, name: &str) -> Result<Arc<dyn Inode>> {
+        self.new_child(name, this_ptr)
+            .ok_or_else(|| Error::new(Errno::ENOENT))
+    }
+
+    fn populate_children(&self, this_ptr: Weak<dyn Inode>) {
+        let this = {
+            let this = this_ptr.upgrade().unwrap();
+            this.downcast_ref::<ProcDir<TidDirOps>>().unwrap().this()
+        };
+        let mut cached_children = this.cached_children().write();
+        for name in ["comm", "stat", "status"] {
+            cached_children
+                .put_entry_if_not_found(name, || self.new_child(name, this_ptr.clone()).unwrap());
+        }
+    }
+}
@@ -0,0 +1,93 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::format;
+use core::time::Duration;
+
+use crate::{
+    fs::{
+        procfs::{
+            template::{FileOps, ProcFileBuilder},
+            USER_HZ,
+        },
+        utils::Inode,
+    },
+    prelude::*,
+    process::process_table,
+    thread::last_allocated_tid,
+    time::{
+        clocks::{idle_clock, system_prof_clock, BootTimeClock, RealTimeClock},
+        Clock,
+    },
+};
+
+/// Represents the inode at `/proc/stat`.
+pub struct StatFileOps;
+
+impl StatFileOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self).parent(parent).build().unwrap()
+    }
+}
+
+impl FileOps for StatFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let user = to_clock_ticks(system_prof_clock().user_clock().read_time());
+        let system = to_clock_ticks(system_prof_clock().kernel_clock().read_time());
+        let idle = to_clock_ticks(idle_clock().read_time());
+        // The times are not recorded per CPU, so they are all attributed to the first CPU.
+        let cpu_times = format!("{} 0 {} {} 0 0 0 0 0 0", user, system, idle);
+
+        let boot_time =
+            RealTimeClock::get().read_time().as_secs() - BootTimeClock::get().read_time().as_secs();
+
+        let mut nr_running = 0;
+        let mut nr_blocked = 0;
+        for process in process_table::process_table().iter() {
+            for thread in process.threads().lock().iter() {
+                if thread.is_runnable() {
+                    nr_running += 1;
+                } else if thread.is_blocked() {
+                    nr_blocked += 1;
+                }
+            }
+        }
+
+        let output = format!(
+            "cpu  {}\n\
+             cpu0 {}\n\
+             intr 0\n\
+             ctxt 0\n\
+             btime {}\n\
+             processes {}\n\
+             procs_running {}\n\
+             procs_blocked {}\n\
+             softirq 0\n",
+            cpu_times,
+            cpu_times,
+            boot_time,
+            last_allocated_tid(),
+            nr_running,
+            nr_blocked
+        );
+        Ok(output.into_bytes())
+    }
+}
+
+/// Converts the time to the clock ticks of `USER_HZ`.
+fn to_clock_ticks(time: Duration) -> u128 {
+    time.as_millis() * USER_HZ / 1000
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn clock_ticks() {
+        assert_eq!(to_clock_ticks(Duration::from_millis(1_509)), 150);
+        assert_eq!(to_clock_ticks(Duration::from_millis(9)), 0);
+        assert_eq!(to_clock_ticks(Duration::from_secs(60)), 6_000);
+    }
+}
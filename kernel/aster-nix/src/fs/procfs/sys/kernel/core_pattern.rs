@@ -0,0 +1,47 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::format;
+
+use crate::{
+    fs::{
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::Inode,
+    },
+    prelude::*,
+    process::{
+        core_dump::{core_pattern, set_core_pattern},
+        credentials,
+        credentials::capabilities::CapSet,
+    },
+};
+
+/// Represents the inode at `/proc/sys/kernel/core_pattern`.
+pub struct CorePatternFileOps;
+
+impl CorePatternFileOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self).parent(parent).build().unwrap()
+    }
+}
+
+impl FileOps for CorePatternFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let output = format!("{}\n", core_pattern());
+        Ok(output.into_bytes())
+    }
+
+    fn is_writable(&self) -> bool {
+        true
+    }
+
+    fn write(&self, data: &[u8]) -> Result<()> {
+        if !credentials().effective_capset().contains(CapSet::SYS_ADMIN) {
+            return_errno_with_message!(
+                Errno::EPERM,
+                "setting the core pattern requires CAP_SYS_ADMIN"
+            );
+        }
+        let pattern = core::str::from_utf8(data)?;
+        set_core_pattern(pattern)
+    }
+}
@@ -21,9 +21,10 @@ impl<F: FileOps> ProcFile<F> {
         let common = {
             let arc_fs = fs.upgrade().unwrap();
             let procfs = arc_fs.downcast_ref::<ProcFS>().unwrap();
+            let mode = if file.is_writable() { 0o644 } else { 0o444 };
             let metadata = Metadata::new_file(
                 procfs.alloc_id(),
-                InodeMode::from_bits_truncate(0o444),
+                InodeMode::from_bits_truncate(mode),
                 super::BLOCK_SIZE,
             );
             Common::new(metadata, fs, is_volatile)
@@ -55,6 +56,10 @@ impl<F: FileOps + 'static> Inode for ProcFile<F> {
     fn fs(&self) -> Arc<dyn FileSystem>;
 
     fn resize(&self, _new_size: usize) -> Result<()> {
+        // Opening a writable file with `O_TRUNC` is allowed, but its data is not truncated.
+        if self.inner.is_writable() {
+            return Ok(());
+        }
         Err(Error::new(Errno::EPERM))
     }
 
@@ -75,12 +80,16 @@ impl<F: FileOps + 'static> Inode for ProcFile<F> {
         self.read_at(offset, buf)
     }
 
-    fn write_at(&self, _offset: usize, _buf: &[u8]) -> Result<usize> {
-        Err(Error::new(Errno::EPERM))
+    fn write_at(&self, _offset: usize, buf: &[u8]) -> Result<usize> {
+        if !self.inner.is_writable() {
+            return Err(Error::new(Errno::EPERM));
+        }
+        self.inner.write(buf)?;
+        Ok(buf.len())
     }
 
-    fn write_direct_at(&self, _offset: usize, _buf: &[u8]) -> Result<usize> {
-        Err(Error::new(Errno::EPERM))
+    fn write_direct_at(&self, offset: usize, buf: &[u8]) -> Result<usize> {
+        self.write_at(offset, buf)
     }
 
     fn read_link(&self) -> Result<String> {
@@ -102,4 +111,17 @@ impl<F: FileOps + 'static> Inode for ProcFile<F> {
 
 pub trait FileOps: Sync + Send {
     fn data(&self) -> Result<Vec<u8>>;
+
+    /// Returns whether the file can be written, which makes its mode `0o644`.
+    fn is_writable(&self) -> bool {
+        false
+    }
+
+    /// Replaces the data of the file with the data written.
+    ///
+    /// Like the files in `/proc/sys` of Linux, each write is handled as a whole,
+    /// regardless of the offset.
+    fn write(&self, _data: &[u8]) -> Result<()> {
+        Err(Error::new(Errno::EPERM))
+    }
 }
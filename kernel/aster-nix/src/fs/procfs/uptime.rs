@@ -0,0 +1,60 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::format;
+use core::time::Duration;
+
+use crate::{
+    fs::{
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::Inode,
+    },
+    prelude::*,
+    time::{
+        clocks::{idle_clock, BootTimeClock},
+        Clock,
+    },
+};
+
+/// Represents the inode at `/proc/uptime`.
+pub struct UptimeFileOps;
+
+impl UptimeFileOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self).parent(parent).build().unwrap()
+    }
+}
+
+impl FileOps for UptimeFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let uptime = BootTimeClock::get().read_time();
+        let idle = idle_clock().read_time();
+        Ok(format_uptime(uptime, idle).into_bytes())
+    }
+}
+
+/// Formats the uptime and the idle time in seconds with two decimal places.
+fn format_uptime(uptime: Duration, idle: Duration) -> String {
+    format!(
+        "{}.{:02} {}.{:02}\n",
+        uptime.as_secs(),
+        uptime.subsec_millis() / 10,
+        idle.as_secs(),
+        idle.subsec_millis() / 10
+    )
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn uptime_format() {
+        assert_eq!(
+            format_uptime(Duration::from_millis(12_345), Duration::from_millis(6_009)),
+            "12.34 6.00\n"
+        );
+        assert_eq!(format_uptime(Duration::ZERO, Duration::ZERO), "0.00 0.00\n");
+    }
+}
@@ -1,7 +1,7 @@
 // SPDX-License-Identifier: MPL-2.0
 
 use core::{
-    sync::atomic::{AtomicU64, Ordering},
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
     time::Duration,
 };
 
@@ -13,7 +13,7 @@ use ostd::{
     sync::RwMutexWriteGuard,
 };
 
-use super::*;
+use super::{options::RamFsMountOptions, *};
 use crate::{
     events::IoEvents,
     fs::{
@@ -37,17 +37,41 @@ pub struct RamFS {
     root: Arc<RamInode>,
     /// An inode allocator
     inode_allocator: AtomicU64,
+    /// The type of the file system, e.g., `tmpfs`
+    name: &'static str,
+    /// The mount options, of which the limits can be changed by remounting
+    options: RwLock<RamFsMountOptions>,
+    /// The number of pages used by the file contents
+    used_pages: AtomicUsize,
+    /// The number of inodes in use
+    used_inodes: AtomicUsize,
 }
 
 impl RamFS {
     pub fn new() -> Arc<Self> {
+        Self::new_with_name("ramfs", RamFsMountOptions::default())
+    }
+
+    /// Creates a RamFS that enforces the limits in `options`.
+    ///
+    /// This is what backs a `tmpfs` mount.
+    pub fn new_with_options(options: RamFsMountOptions) -> Arc<Self> {
+        Self::new_with_name("tmpfs", options)
+    }
+
+    /// Creates a RamFS that backs the `devtmpfs`.
+    pub fn new_devtmpfs() -> Arc<Self> {
+        Self::new_with_name("devtmpfs", RamFsMountOptions::default())
+    }
+
+    fn new_with_name(name: &'static str, options: RamFsMountOptions) -> Arc<Self> {
         Arc::new_cyclic(|weak_fs| Self {
             sb: SuperBlock::new(RAMFS_MAGIC, BLOCK_SIZE, NAME_MAX),
             root: Arc::new_cyclic(|weak_root| RamInode {
                 node: RwMutex::new(Node::new_dir(
-                    InodeMode::from_bits_truncate(0o755),
-                    Uid::new_root(),
-                    Gid::new_root(),
+                    options.mode,
+                    options.uid,
+                    options.gid,
                     weak_root.clone(),
                     weak_root.clone(),
                 )),
@@ -55,8 +79,14 @@ impl RamFS {
                 typ: InodeType::Dir,
                 this: weak_root.clone(),
                 fs: weak_fs.clone(),
+                nr_pages: AtomicUsize::new(0),
             }),
             inode_allocator: AtomicU64::new(ROOT_INO + 1),
+            name,
+            options: RwLock::new(options),
+            used_pages: AtomicUsize::new(0),
+            // The root inode
+            used_inodes: AtomicUsize::new(1),
         })
     }
 
@@ -67,6 +97,67 @@ impl RamFS {
     fn device_id(&self) -> u64 {
         0
     }
+
+    /// Reserves an inode, fails with `ENOSPC` if the inode limit is reached.
+    ///
+    /// The reservation is released when the `RamInode` is dropped.
+    fn reserve_inode(&self) -> Result<()> {
+        let max_inodes = self.options.read().max_inodes;
+        if !try_reserve(&self.used_inodes, 1, max_inodes) {
+            return_errno_with_message!(Errno::ENOSPC, "no free inodes");
+        }
+        Ok(())
+    }
+
+    fn release_inode(&self) {
+        self.used_inodes.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Reserves `nr_pages` pages, fails with `ENOSPC` if the size limit is reached.
+    fn reserve_pages(&self, nr_pages: usize) -> Result<()> {
+        let max_pages = self.options.read().max_pages;
+        if !try_reserve(&self.used_pages, nr_pages, max_pages) {
+            return_errno_with_message!(Errno::ENOSPC, "no free space");
+        }
+        Ok(())
+    }
+
+    fn release_pages(&self, nr_pages: usize) {
+        self.used_pages.fetch_sub(nr_pages, Ordering::Relaxed);
+    }
+
+    /// Changes the limits with the options in the `data` argument of a remount.
+    ///
+    /// Like Linux, it fails with `EINVAL` if a new limit is lower than the usage.
+    pub fn remount(&self, data: &str) -> Result<()> {
+        let mut options = self.options.write();
+        let new_options = options.update(data)?;
+        let is_within = |used: &AtomicUsize, limit: Option<usize>| {
+            limit.map_or(true, |limit| used.load(Ordering::Relaxed) <= limit)
+        };
+        if !is_within(&self.used_pages, new_options.max_pages)
+            || !is_within(&self.used_inodes, new_options.max_inodes)
+        {
+            return_errno_with_message!(Errno::EINVAL, "the limit is lower than the usage");
+        }
+        *options = new_options;
+        Ok(())
+    }
+}
+
+/// Adds `count` to `used` unless the sum exceeds `limit`.
+///
+/// Returns whether the resource is reserved.
+fn try_reserve(used: &AtomicUsize, count: usize, limit: Option<usize>) -> bool {
+    let Some(limit) = limit else {
+        used.fetch_add(count, Ordering::Relaxed);
+        return true;
+    };
+    used.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+        used.checked_add(count)
+            .filter(|&new_used| new_used <= limit)
+    })
+    .is_ok()
 }
 
 impl FileSystem for RamFS {
@@ -80,12 +171,29 @@ impl FileSystem for RamFS {
     }
 
     fn sb(&self) -> SuperBlock {
-        self.sb.clone()
+        let mut sb = self.sb.clone();
+        let options = self.options.read();
+        // Unlimited resources are reported as zero, as Linux does.
+        if let Some(max_pages) = options.max_pages {
+            let free_pages = max_pages.saturating_sub(self.used_pages.load(Ordering::Relaxed));
+            sb.blocks = max_pages;
+            sb.bfree = free_pages;
+            sb.bavail = free_pages;
+        }
+        if let Some(max_inodes) = options.max_inodes {
+            sb.files = max_inodes;
+            sb.ffree = max_inodes.saturating_sub(self.used_inodes.load(Ordering::Relaxed));
+        }
+        sb
     }
 
     fn flags(&self) -> FsFlags {
         FsFlags::DENTRY_UNEVICTABLE
     }
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
 }
 
 struct RamInode {
@@ -99,6 +207,8 @@ struct RamInode {
     this: Weak<RamInode>,
     /// Reference to fs
     fs: Weak<RamFS>,
+    /// The number of pages in the page cache, which are charged to the fs
+    nr_pages: AtomicUsize,
 }
 
 struct Node {
@@ -415,6 +525,7 @@ impl RamInode {
             typ: InodeType::Dir,
             this: weak_self.clone(),
             fs: Arc::downgrade(fs),
+            nr_pages: AtomicUsize::new(0),
         })
     }
 
@@ -425,6 +536,7 @@ impl RamInode {
             typ: InodeType::File,
             this: weak_self.clone(),
             fs: Arc::downgrade(fs),
+            nr_pages: AtomicUsize::new(0),
         })
     }
 
@@ -435,6 +547,7 @@ impl RamInode {
             typ: InodeType::SymLink,
             this: weak_self.clone(),
             fs: Arc::downgrade(fs),
+            nr_pages: AtomicUsize::new(0),
         })
     }
 
@@ -445,6 +558,7 @@ impl RamInode {
             typ: InodeType::Socket,
             this: weak_self.clone(),
             fs: Arc::downgrade(fs),
+            nr_pages: AtomicUsize::new(0),
         })
     }
 
@@ -461,6 +575,7 @@ impl RamInode {
             typ: InodeType::from(device.type_()),
             this: weak_self.clone(),
             fs: Arc::downgrade(fs),
+            nr_pages: AtomicUsize::new(0),
         })
     }
 
@@ -480,6 +595,18 @@ impl RamInode {
     }
 }
 
+impl Drop for RamInode {
+    fn drop(&mut self) {
+        let Some(fs) = self.fs.upgrade() else {
+            return;
+        };
+        // The pages still in the page cache cannot be uncharged one by one, since
+        // the page cache does not reach the inode being dropped.
+        fs.release_pages(self.nr_pages.load(Ordering::Relaxed));
+        fs.release_inode();
+    }
+}
+
 impl PageCacheBackend for RamInode {
     fn read_page(&self, _idx: usize, frame: &Frame) -> Result<BioWaiter> {
         // Initially, any block/page in a RamFs inode contains all zeros
@@ -495,6 +622,24 @@ impl PageCacheBackend for RamInode {
     fn npages(&self) -> usize {
         self.node.read().metadata.blocks
     }
+
+    // The pages are charged when they are cached rather than when the file is resized,
+    // so a sparse file takes the space it uses only.
+    //
+    // TODO: Pages of a size-limited RamFS are not reclaimable until swapping is supported.
+    fn charge_page(&self) -> Result<()> {
+        let fs = self.fs.upgrade().unwrap();
+        fs.reserve_pages(1)?;
+        self.nr_pages.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn uncharge_page(&self) {
+        if let Some(fs) = self.fs.upgrade() {
+            fs.release_pages(1);
+        }
+        self.nr_pages.fetch_sub(1, Ordering::Relaxed);
+    }
 }
 
 impl Inode for RamInode {
@@ -559,7 +704,19 @@ impl Inode for RamInode {
         if should_expand_size {
             page_cache.pages().resize(new_size)?;
         }
-        page_cache.pages().write_bytes(offset, buf)?;
+        // Commit the pages first, so that an `ENOSPC` from charging the pages is not
+        // converted by `write_bytes`.
+        let result = page_cache
+            .pages()
+            .commit(offset..new_size)
+            .and_then(|_| Ok(page_cache.pages().write_bytes(offset, buf)?));
+        if let Err(e) = result {
+            // Shrink the page cache back to drop the pages beyond the file size.
+            if should_expand_size {
+                let _ = page_cache.pages().resize(file_size);
+            }
+            return Err(e);
+        }
 
         let mut self_inode = self_inode.upgrade();
         let now = now();
@@ -591,16 +748,15 @@ impl Inode for RamInode {
             return Ok(());
         }
 
+        let page_cache = self_inode.inner.as_file().unwrap();
+        page_cache.pages().resize(new_size)?;
+
         let mut self_inode = self_inode.upgrade();
         self_inode.resize(new_size);
         let now = now();
         self_inode.set_mtime(now);
         self_inode.set_ctime(now);
 
-        let self_inode = self_inode.downgrade();
-        let page_cache = self_inode.inner.as_file().unwrap();
-        page_cache.pages().resize(new_size)?;
-
         Ok(())
     }
 
@@ -686,13 +842,10 @@ impl Inode for RamInode {
         if self_inode.inner.as_direntry().unwrap().contains_entry(name) {
             return_errno_with_message!(Errno::EEXIST, "entry exists");
         }
-        let device_inode = RamInode::new_device(
-            &self.fs.upgrade().unwrap(),
-            mode,
-            Uid::new_root(),
-            Gid::new_root(),
-            device,
-        );
+        let fs = self.fs.upgrade().unwrap();
+        fs.reserve_inode()?;
+        let device_inode =
+            RamInode::new_device(&fs, mode, Uid::new_root(), Gid::new_root(), device);
 
         let mut self_inode = self_inode.upgrade();
         self_inode
@@ -721,6 +874,7 @@ impl Inode for RamInode {
             return_errno_with_message!(Errno::EEXIST, "entry exists");
         }
         let fs = self.fs.upgrade().unwrap();
+        fs.reserve_inode()?;
         let new_inode = match type_ {
             InodeType::File => RamInode::new_file(&fs, mode, Uid::new_root(), Gid::new_root()),
             InodeType::SymLink => {
@@ -1111,6 +1265,17 @@ impl Inode for RamInode {
         }
         return_errno_with_message!(Errno::EINVAL, "ioctl is not supported");
     }
+
+    fn sync_all(&self) -> Result<()> {
+        if let Some(device) = self.as_device() {
+            return device.sync();
+        }
+        Ok(())
+    }
+
+    fn sync_data(&self) -> Result<()> {
+        self.sync_all()
+    }
 }
 
 fn write_lock_two_inodes<'a>(
@@ -1131,3 +1296,96 @@ fn write_lock_two_inodes<'a>(
 fn now() -> Duration {
     RealTimeCoarseClock::get().read_time()
 }
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    fn new_tmpfs(options: &str) -> Arc<RamFS> {
+        crate::time::clocks::init_for_ktest();
+        RamFS::new_with_options(RamFsMountOptions::parse(options).unwrap())
+    }
+
+    fn create_file(fs: &RamFS, name: &str) -> Result<Arc<dyn Inode>> {
+        let mode = InodeMode::from_bits_truncate(0o644);
+        fs.root_inode().create(name, InodeType::File, mode)
+    }
+
+    #[ktest]
+    fn size_limit() {
+        let fs = new_tmpfs("size=8k");
+        let file = create_file(&fs, "a").unwrap();
+        file.write_at(0, &[1u8; 2 * BLOCK_SIZE]).unwrap();
+        assert_eq!(fs.sb().bfree, 0);
+        assert_eq!(
+            file.write_at(2 * BLOCK_SIZE, &[1u8]).unwrap_err().error(),
+            Errno::ENOSPC
+        );
+        assert_eq!(file.size(), 2 * BLOCK_SIZE);
+
+        // Truncating returns the pages.
+        file.resize(BLOCK_SIZE).unwrap();
+        assert_eq!(fs.sb().bfree, 1);
+        let other = create_file(&fs, "b").unwrap();
+        other.write_at(0, &[1u8; BLOCK_SIZE]).unwrap();
+        other.resize(2 * BLOCK_SIZE).unwrap();
+        assert_eq!(
+            other.write_at(BLOCK_SIZE, &[1u8]).unwrap_err().error(),
+            Errno::ENOSPC
+        );
+        assert_eq!(other.size(), 2 * BLOCK_SIZE);
+
+        // Unlinking returns the pages once the inode is gone.
+        fs.root_inode().unlink("a").unwrap();
+        drop(file);
+        assert_eq!(fs.sb().bfree, 1);
+        other.write_at(BLOCK_SIZE, &[1u8; BLOCK_SIZE]).unwrap();
+        assert_eq!(fs.sb().bfree, 0);
+    }
+
+    #[ktest]
+    fn sparse_file() {
+        let fs = new_tmpfs("size=8k");
+        let file = create_file(&fs, "a").unwrap();
+        // Only the pages that are written take the space.
+        file.resize(256 * BLOCK_SIZE).unwrap();
+        assert_eq!(fs.sb().bfree, 2);
+        file.write_at(128 * BLOCK_SIZE, &[1u8]).unwrap();
+        assert_eq!(fs.sb().bfree, 1);
+        assert_eq!(file.size(), 256 * BLOCK_SIZE);
+
+        file.resize(BLOCK_SIZE).unwrap();
+        assert_eq!(fs.sb().bfree, 2);
+    }
+
+    #[ktest]
+    fn remount_limits() {
+        let fs = new_tmpfs("size=8k");
+        let file = create_file(&fs, "a").unwrap();
+        file.write_at(0, &[1u8; 2 * BLOCK_SIZE]).unwrap();
+        assert_eq!(fs.remount("size=4k").unwrap_err().error(), Errno::EINVAL);
+        assert_eq!(fs.sb().blocks, 2);
+
+        fs.remount("size=12k").unwrap();
+        assert_eq!(fs.sb().blocks, 3);
+        file.write_at(2 * BLOCK_SIZE, &[1u8; BLOCK_SIZE]).unwrap();
+        assert_eq!(fs.sb().bfree, 0);
+    }
+
+    #[ktest]
+    fn inode_limit() {
+        // The root directory takes one of the inodes.
+        let fs = new_tmpfs("nr_inodes=3");
+        let file = create_file(&fs, "a").unwrap();
+        create_file(&fs, "b").unwrap();
+        assert_eq!(fs.sb().ffree, 0);
+        assert_eq!(create_file(&fs, "c").unwrap_err().error(), Errno::ENOSPC);
+
+        fs.root_inode().unlink("a").unwrap();
+        drop(file);
+        assert_eq!(fs.sb().ffree, 1);
+        create_file(&fs, "c").unwrap();
+    }
+}
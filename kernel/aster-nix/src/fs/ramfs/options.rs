@@ -0,0 +1,144 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::BLOCK_SIZE;
+use crate::{
+    fs::utils::InodeMode,
+    prelude::*,
+    process::{Gid, Uid},
+};
+
+/// The mount options of a RamFS instance.
+///
+/// A RamFS mounted with the `tmpfs` type accepts the comma-separated options
+/// in the `data` argument of `mount`, e.g., `size=64m,nr_inodes=4k,mode=1777`.
+/// Limits that are not given are unlimited, which is how a plain RamFS behaves.
+#[derive(Debug, Clone)]
+pub struct RamFsMountOptions {
+    /// The maximum number of pages for the file contents.
+    pub max_pages: Option<usize>,
+    /// The maximum number of inodes.
+    pub max_inodes: Option<usize>,
+    /// The permission bits of the root directory.
+    pub mode: InodeMode,
+    /// The owner of the root directory.
+    pub uid: Uid,
+    /// The group of the root directory.
+    pub gid: Gid,
+}
+
+impl Default for RamFsMountOptions {
+    fn default() -> Self {
+        Self {
+            max_pages: None,
+            max_inodes: None,
+            mode: InodeMode::from_bits_truncate(0o755),
+            uid: Uid::new_root(),
+            gid: Gid::new_root(),
+        }
+    }
+}
+
+impl RamFsMountOptions {
+    /// Parses the options from the `data` argument of `mount`.
+    pub fn parse(data: &str) -> Result<Self> {
+        let options = Self {
+            mode: InodeMode::from_bits_truncate(0o1777),
+            ..Default::default()
+        };
+        options.update(data)
+    }
+
+    /// Parses the options from the `data` argument of a remount.
+    ///
+    /// The options that are not given in `data` keep their values in `self`.
+    pub fn update(&self, data: &str) -> Result<Self> {
+        let mut options = self.clone();
+
+        for option in data.split(',').filter(|option| !option.is_empty()) {
+            let (key, value) = option.split_once('=').unwrap_or((option, ""));
+            match key {
+                "size" => {
+                    let size = parse_size(value)?;
+                    options.max_pages = Some(size.div_ceil(BLOCK_SIZE));
+                }
+                "nr_blocks" => options.max_pages = Some(parse_size(value)?),
+                "nr_inodes" => options.max_inodes = Some(parse_size(value)?),
+                "mode" => {
+                    let mode = u16::from_str_radix(value, 8)
+                        .map_err(|_| Error::with_message(Errno::EINVAL, "invalid mode"))?;
+                    options.mode = InodeMode::from_bits_truncate(mode & 0o7777);
+                }
+                "uid" => options.uid = Uid::new(parse_id(value)?),
+                "gid" => options.gid = Gid::new(parse_id(value)?),
+                _ => return_errno_with_message!(Errno::EINVAL, "unknown tmpfs option"),
+            }
+        }
+
+        // A limit of zero means unlimited.
+        options.max_pages = options.max_pages.filter(|&pages| pages != 0);
+        options.max_inodes = options.max_inodes.filter(|&inodes| inodes != 0);
+
+        Ok(options)
+    }
+}
+
+/// Parses a number with an optional `k`, `m` or `g` suffix.
+fn parse_size(value: &str) -> Result<usize> {
+    let (digits, shift) = match value.as_bytes().last() {
+        Some(b'k' | b'K') => (&value[..value.len() - 1], 10),
+        Some(b'm' | b'M') => (&value[..value.len() - 1], 20),
+        Some(b'g' | b'G') => (&value[..value.len() - 1], 30),
+        Some(b'%') => {
+            return_errno_with_message!(Errno::EINVAL, "size in percentage is not supported")
+        }
+        _ => (value, 0),
+    };
+    let number = digits
+        .parse::<usize>()
+        .map_err(|_| Error::with_message(Errno::EINVAL, "invalid size"))?;
+    number
+        .checked_mul(1 << shift)
+        .ok_or(Error::with_message(Errno::EINVAL, "size is too large"))
+}
+
+fn parse_id(value: &str) -> Result<u32> {
+    value
+        .parse::<u32>()
+        .map_err(|_| Error::with_message(Errno::EINVAL, "invalid id"))
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn parse_options() {
+        let options =
+            RamFsMountOptions::parse("size=1m,nr_inodes=2k,mode=700,uid=1000,gid=100").unwrap();
+        assert_eq!(options.max_pages, Some(256));
+        assert_eq!(options.max_inodes, Some(2048));
+        assert_eq!(options.mode, InodeMode::from_bits_truncate(0o700));
+        assert_eq!(options.uid, Uid::new(1000));
+        assert_eq!(options.gid, Gid::new(100));
+
+        let options = RamFsMountOptions::parse("").unwrap();
+        assert_eq!(options.max_pages, None);
+        assert_eq!(options.mode, InodeMode::from_bits_truncate(0o1777));
+
+        assert!(RamFsMountOptions::parse("size=12x").is_err());
+        assert!(RamFsMountOptions::parse("noexist").is_err());
+    }
+
+    #[ktest]
+    fn update_options() {
+        let options = RamFsMountOptions::parse("size=1m,nr_inodes=2k").unwrap();
+        let options = options.update("size=2m").unwrap();
+        assert_eq!(options.max_pages, Some(512));
+        assert_eq!(options.max_inodes, Some(2048));
+        let options = options.update("nr_inodes=0").unwrap();
+        assert_eq!(options.max_inodes, None);
+        assert!(options.update("noexist").is_err());
+    }
+}
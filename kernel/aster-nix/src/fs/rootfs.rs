@@ -1,25 +1,84 @@
-// SPDX-License-Identifier: MPL-2.0
-
+use aster_block::{
+    bio::{BioEnqueueError, BioStatus, BioType, SubmittedBio},
+    BlockDevice, SECTOR_SIZE,
+};
 use cpio_decoder::{CpioDecoder, FileType};
 use lending_iterator::LendingIterator;
 use libflate::gzip::Decoder as GZipDecoder;
-use spin::Once;
 
 use super::{
+    cgroupfs,
+    device::DeviceId,
+    devtmpfs,
+    erofs::ErofsFS,
     fs_resolver::{FsPath, FsResolver},
-    path::MountNode,
+    open_block_fs,
+    p9fs::{P9MountOptions, P9FS},
+    path::{Dentry, MountNode},
     procfs::ProcFS,
     ramfs::RamFS,
+    squashfs::SquashFS,
+    sysfs::SysFS,
     utils::{FileSystem, InodeMode, InodeType},
 };
-use crate::prelude::*;
+use crate::{device::block, prelude::*, process::process_table};
+
+/// The magic of SquashFS at the start of the image.
+const SQUASHFS_MAGIC: &[u8] = b"hsqs";
+/// The magic of EROFS, which is at offset 1024 of the image.
+const EROFS_MAGIC: &[u8] = &[0xE2, 0xE1, 0xF5, 0xE0];
+const EROFS_MAGIC_OFFSET: usize = 1024;
+
+/// Prepares the rootfs from the initramfs.
+///
+/// The initramfs is either a gzip-compressed CPIO archive, which is unpacked to RamFS,
+/// or a SquashFS or EROFS image, which is mounted in place as the read-only root, so
+/// that the files are only decompressed or copied when they are read.
+pub fn init(initramfs_buf: &'static [u8]) -> Result<()> {
+    let image_fs: Option<Arc<dyn FileSystem>> = if initramfs_buf.starts_with(SQUASHFS_MAGIC) {
+        println!("[kernel] mounting the SquashFS initramfs as rootfs ...");
+        Some(SquashFS::open(Arc::new(InitramfsDisk(initramfs_buf)))?)
+    } else if initramfs_buf
+        .get(EROFS_MAGIC_OFFSET..)
+        .is_some_and(|buf| buf.starts_with(EROFS_MAGIC))
+    {
+        println!("[kernel] mounting the EROFS initramfs as rootfs ...");
+        Some(ErofsFS::open(Arc::new(InitramfsDisk(initramfs_buf)))?)
+    } else {
+        None
+    };
+
+    let fs = if let Some(image_fs) = image_fs {
+        *ROOT_MOUNT.write() = Some(MountNode::new_root(image_fs));
+        FsResolver::new()
+    } else {
+        init_root_mount();
+        println!("[kernel] unpacking the initramfs.cpio.gz to rootfs ...");
+        let fs = FsResolver::new();
+        unpack_cpio(initramfs_buf, &fs)?;
+        fs
+    };
+
+    // Mount ProcFS
+    let proc_dentry = fs.lookup(&FsPath::try_from("/proc")?)?;
+    proc_dentry.mount(ProcFS::new())?;
+    // Mount SysFS
+    let sys_dentry = fs.lookup(&FsPath::try_from("/sys")?)?;
+    sys_dentry.mount(SysFS::new())?;
+    // Mount the cgroup2
+    let cgroup_dentry = fs.lookup(&FsPath::try_from("/sys/fs/cgroup")?)?;
+    cgroup_dentry.mount(cgroupfs::cgroupfs())?;
+    // Mount devtmpfs
+    let dev_dentry = fs.lookup(&FsPath::try_from("/dev")?)?;
+    devtmpfs::init(&dev_dentry)?;
+
+    println!("[kernel] rootfs is ready");
 
-/// Unpack and prepare the rootfs from the initramfs CPIO buffer.
-pub fn init(initramfs_buf: &[u8]) -> Result<()> {
-    init_root_mount();
+    Ok(())
+}
 
-    println!("[kernel] unpacking the initramfs.cpio.gz to rootfs ...");
-    let fs = FsResolver::new();
+/// Unpacks the gzip-compressed CPIO archive to the root.
+fn unpack_cpio(initramfs_buf: &[u8], fs: &FsResolver) -> Result<()> {
     let mut decoder = CpioDecoder::new(
         GZipDecoder::new(initramfs_buf)
             .map_err(|_| Error::with_message(Errno::EINVAL, "invalid gzip buffer"))?,
@@ -75,16 +134,48 @@ pub fn init(initramfs_buf: &[u8]) -> Result<()> {
             }
         }
     }
-    // Mount ProcFS
-    let proc_dentry = fs.lookup(&FsPath::try_from("/proc")?)?;
-    proc_dentry.mount(ProcFS::new())?;
-    // Mount DevFS
-    let dev_dentry = fs.lookup(&FsPath::try_from("/dev")?)?;
-    dev_dentry.mount(RamFS::new())?;
+    Ok(())
+}
 
-    println!("[kernel] rootfs is ready");
+/// A read-only block device over the initramfs image in memory.
+struct InitramfsDisk(&'static [u8]);
 
-    Ok(())
+impl Debug for InitramfsDisk {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("InitramfsDisk")
+            .field("len", &self.0.len())
+            .finish()
+    }
+}
+
+impl BlockDevice for InitramfsDisk {
+    fn enqueue(&self, bio: SubmittedBio) -> core::result::Result<(), BioEnqueueError> {
+        match bio.type_() {
+            BioType::Read => {
+                let mut offset = bio.sid_range().start.to_raw() as usize * SECTOR_SIZE;
+                for seg in bio.segments() {
+                    let src = self.0.get(offset..).unwrap_or(&[]);
+                    let mut writer = seg.writer();
+                    writer.write(&mut VmReader::from(src));
+                    // The last sector is padded with zeros.
+                    writer.fill(0u8);
+                    offset += seg.nbytes();
+                }
+                bio.complete(BioStatus::Complete);
+            }
+            BioType::Flush => bio.complete(BioStatus::Complete),
+            _ => bio.complete(BioStatus::NotSupported),
+        }
+        Ok(())
+    }
+
+    fn max_nr_segments_per_bio(&self) -> usize {
+        usize::MAX
+    }
+
+    fn nr_sectors(&self) -> usize {
+        self.0.len().div_ceil(SECTOR_SIZE)
+    }
 }
 
 pub fn mount_fs_at(fs: Arc<dyn FileSystem>, fs_path: &FsPath) -> Result<()> {
@@ -93,15 +184,225 @@ pub fn mount_fs_at(fs: Arc<dyn FileSystem>, fs_path: &FsPath) -> Result<()> {
     Ok(())
 }
 
-static ROOT_MOUNT: Once<Arc<MountNode>> = Once::new();
+/// The filesystems that are tried in order if `rootfstype=` is not given.
+const ROOT_FS_TYPES: &[&str] = &["ext2", "exfat", "vfat", "squashfs", "erofs"];
+
+/// The mounts that are moved from the initramfs to the new root, like `switch_root`.
+/// The mounts under them, e.g., the cgroup2 at `/sys/fs/cgroup`, are moved along.
+const MOVED_MOUNTS: &[&str] = &["dev", "proc", "sys"];
+
+/// Mounts the root device given by the kernel command line, and switches the root to it.
+///
+/// The device is `/dev/<name>`, `<major>:<minor>`, the name of a block device in
+/// `aster_block`, or the mount tag if the filesystem type is `9p`. If the filesystem
+/// type is not given, the filesystems in `ROOT_FS_TYPES` are tried in order.
+/// The mount options are only understood by 9P and Ext2.
+pub fn mount_root(device: &str, fs_type: Option<&str>, flags: &str, read_only: bool) -> Result<()> {
+    let fs: Arc<dyn FileSystem> = if fs_type == Some("9p") {
+        let options = P9MountOptions::parse(flags)?;
+        let Some(device) = aster_virtio::device::p9::get_device(device) else {
+            return_errno_with_message!(Errno::ENOENT, "no 9P device with the mount tag");
+        };
+        P9FS::new(device, options)?
+    } else {
+        let block_device = lookup_root_device(device)?;
+        match fs_type {
+            Some(fs_type) => open_block_fs(fs_type, block_device, flags)?,
+            None => ROOT_FS_TYPES
+                .iter()
+                .find_map(|fs_type| open_block_fs(fs_type, block_device.clone(), flags).ok())
+                .ok_or_else(|| {
+                    Error::with_message(Errno::EINVAL, "no known filesystem on the root device")
+                })?,
+        }
+    };
+
+    println!(
+        "[kernel] mounting {} as the {} root ...",
+        device,
+        if read_only { "read-only" } else { "read-write" }
+    );
+    switch_root(fs, read_only)
+}
+
+/// Returns the block device of the root device given by the kernel command line.
+fn lookup_root_device(device: &str) -> Result<Arc<dyn BlockDevice>> {
+    if let Some((major, minor)) = device.split_once(':') {
+        let (Ok(major), Ok(minor)) = (major.parse(), minor.parse()) else {
+            return_errno_with_message!(Errno::EINVAL, "invalid device number");
+        };
+        return block::get_device(DeviceId::new(major, minor))
+            .map(|node| node.block_device().clone())
+            .ok_or_else(|| Error::with_message(Errno::ENODEV, "no block device of the number"));
+    }
+
+    if device.starts_with("/dev/") {
+        let dentry = FsResolver::new().lookup(&FsPath::try_from(device)?)?;
+        if dentry.type_() != InodeType::BlockDevice {
+            return_errno_with_message!(Errno::ENOTBLK, "not a block device");
+        }
+        let id = DeviceId::from(dentry.metadata().rdev);
+        return block::get_device(id)
+            .map(|node| node.block_device().clone())
+            .ok_or_else(|| Error::with_message(Errno::ENODEV, "no block device of the node"));
+    }
+
+    aster_block::get_device(device)
+        .ok_or_else(|| Error::with_message(Errno::ENODEV, "no block device of the name"))
+}
+
+/// Makes the filesystem the root, like `switch_root`.
+///
+/// The mounts in `MOVED_MOUNTS` are moved to the new root if it has the directories.
+/// The initramfs is dropped since no one refers to it.
+fn switch_root(fs: Arc<dyn FileSystem>, read_only: bool) -> Result<()> {
+    let new_root_mount = MountNode::new_root(fs);
+    new_root_mount.set_read_only(read_only);
+    let new_root = Dentry::new_fs_root(new_root_mount.clone());
+
+    let old_fs = FsResolver::new();
+    for name in MOVED_MOUNTS {
+        let Ok(dentry) = old_fs.root().lookup(name) else {
+            continue;
+        };
+        if !dentry.is_root_of_mount() {
+            continue;
+        }
+        match new_root.lookup(name) {
+            Ok(target) => dentry.mount_node().graft_mount_node_tree(&target)?,
+            Err(e) => warn!("cannot move /{} to the new root: {:?}", name, e),
+        }
+    }
+
+    *ROOT_MOUNT.write() = Some(new_root_mount);
+    Ok(())
+}
+
+/// Makes the mount of `new_root` the root mount, and mounts the old root mount at `put_old`.
+///
+/// `new_root` must be the root of a mount, and `put_old` must be at or under `new_root`.
+/// The processes whose root or working directory is the old root are moved to the new
+/// root, like Linux.
+pub fn pivot_root(new_root: &Arc<Dentry>, put_old: &Arc<Dentry>) -> Result<()> {
+    if new_root.type_() != InodeType::Dir || put_old.type_() != InodeType::Dir {
+        return_errno_with_message!(Errno::ENOTDIR, "new_root and put_old must be directories");
+    }
+    if !new_root.is_root_of_mount() {
+        return_errno_with_message!(Errno::EINVAL, "new_root is not a mountpoint");
+    }
+    let old_root_mount = root_mount();
+    let new_root_mount = new_root.mount_node().clone();
+    if Arc::ptr_eq(&new_root_mount, &old_root_mount) {
+        return_errno_with_message!(Errno::EBUSY, "new_root is on the root mount");
+    }
+    if !put_old.is_reachable_from(new_root) {
+        return_errno_with_message!(Errno::EINVAL, "put_old is not under new_root");
+    }
+
+    new_root_mount.detach_as_root();
+    old_root_mount.graft_mount_node_tree(put_old)?;
+    *ROOT_MOUNT.write() = Some(new_root_mount.clone());
+
+    let is_old_root = |dentry: &Arc<Dentry>| {
+        Arc::ptr_eq(dentry.mount_node(), &old_root_mount) && dentry.is_root_of_mount()
+    };
+    for process in process_table::process_table().iter() {
+        let mut fs = process.fs().write();
+        if is_old_root(fs.root()) {
+            fs.set_root(Dentry::new_fs_root(new_root_mount.clone()));
+        }
+        if is_old_root(fs.cwd()) {
+            fs.set_cwd(Dentry::new_fs_root(new_root_mount.clone()));
+        }
+    }
+    Ok(())
+}
+
+/// The root mount, which is changed when the root is switched or pivoted.
+static ROOT_MOUNT: RwLock<Option<Arc<MountNode>>> = RwLock::new(None);
 
 pub fn init_root_mount() {
-    ROOT_MOUNT.call_once(|| -> Arc<MountNode> {
-        let rootfs = RamFS::new();
-        MountNode::new_root(rootfs)
-    });
+    let mut root_mount = ROOT_MOUNT.write();
+    if root_mount.is_none() {
+        *root_mount = Some(MountNode::new_root(RamFS::new()));
+    }
 }
 
-pub fn root_mount() -> &'static Arc<MountNode> {
-    ROOT_MOUNT.get().unwrap()
+pub fn root_mount() -> Arc<MountNode> {
+    ROOT_MOUNT.read().clone().unwrap()
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+    use crate::process::{Gid, Uid};
+
+    #[ktest]
+    fn pivot_root_to_mount() {
+        crate::time::clocks::init_for_ktest();
+        let saved_root_mount = ROOT_MOUNT
+            .write()
+            .replace(MountNode::new_root(RamFS::new()));
+        let old_root_mount = root_mount();
+        let old_root = Dentry::new_fs_root(old_root_mount.clone());
+        let mode = InodeMode::from_bits_truncate(0o755);
+        let mnt = old_root.new_fs_child("mnt", InodeType::Dir, mode).unwrap();
+        let new_root = Dentry::new_fs_root(mnt.mount(RamFS::new()).unwrap());
+        let put_old = new_root.new_fs_child("old", InodeType::Dir, mode).unwrap();
+        let file = new_root
+            .new_fs_child("file", InodeType::File, mode)
+            .unwrap();
+
+        let pivot_errno = |new_root: &Arc<Dentry>, put_old: &Arc<Dentry>| {
+            pivot_root(new_root, put_old).unwrap_err().error()
+        };
+        assert_eq!(pivot_errno(&new_root, &file), Errno::ENOTDIR);
+        assert_eq!(pivot_errno(&mnt, &put_old), Errno::EINVAL);
+        assert_eq!(pivot_errno(&old_root, &put_old), Errno::EBUSY);
+        assert_eq!(pivot_errno(&new_root, &mnt), Errno::EINVAL);
+
+        pivot_root(&new_root, &put_old).unwrap();
+        assert!(Arc::ptr_eq(&root_mount(), new_root.mount_node()));
+        assert!(root_mount().mountpoint_dentry().is_none());
+        assert!(!mnt.is_mountpoint());
+        let old_mnt = FsResolver::new()
+            .lookup(&FsPath::try_from("/old/mnt").unwrap())
+            .unwrap();
+        assert!(Arc::ptr_eq(old_mnt.mount_node(), &old_root_mount));
+        assert!(old_mnt.is_reachable_from(&new_root));
+        assert!(!new_root.is_reachable_from(&old_root));
+
+        *ROOT_MOUNT.write() = saved_root_mount;
+    }
+
+    #[ktest]
+    fn read_only_mount() {
+        crate::time::clocks::init_for_ktest();
+        let mount = MountNode::new_root(RamFS::new());
+        let root = Dentry::new_fs_root(mount.clone());
+        assert!(root.check_writable_mount().is_ok());
+        mount.set_read_only(true);
+        assert_eq!(
+            root.check_writable_mount().unwrap_err().error(),
+            Errno::EROFS
+        );
+        let mode = root.mode().unwrap();
+        let errno = |result: Result<()>| result.unwrap_err().error();
+        assert_eq!(
+            errno(root.set_mode(InodeMode::from_bits_truncate(0o700))),
+            Errno::EROFS
+        );
+        assert_eq!(errno(root.set_owner(Uid::new(1))), Errno::EROFS);
+        assert_eq!(errno(root.set_group(Gid::new(1))), Errno::EROFS);
+        assert_eq!(root.mode().unwrap(), mode);
+    }
+
+    #[ktest]
+    fn invalid_root_device() {
+        let errno = |device: &str| lookup_root_device(device).unwrap_err().error();
+        assert_eq!(errno("vda:1"), Errno::EINVAL);
+        assert_eq!(errno("250:250"), Errno::ENODEV);
+    }
 }
@@ -0,0 +1,61 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core2::io::Read;
+use libflate::zlib::Decoder as ZlibDecoder;
+
+use super::xz;
+use crate::prelude::*;
+
+/// The compression algorithm of the image, which is used for all the blocks.
+///
+/// The options of the compressor in the image, if any, only matter to the compression,
+/// so they are ignored.
+#[derive(Clone, Copy, Debug)]
+pub(super) enum Compressor {
+    /// zlib streams, which are named gzip by SquashFS
+    Gzip,
+    Xz,
+    Lz4,
+    Zstd,
+}
+
+impl Compressor {
+    pub(super) fn new(id: u16) -> Result<Self> {
+        match id {
+            1 => Ok(Self::Gzip),
+            2 => return_errno_with_message!(Errno::EOPNOTSUPP, "LZMA is not supported"),
+            3 => return_errno_with_message!(Errno::EOPNOTSUPP, "LZO is not supported"),
+            4 => Ok(Self::Xz),
+            5 => Ok(Self::Lz4),
+            6 => Ok(Self::Zstd),
+            _ => return_errno_with_message!(Errno::EINVAL, "unknown SquashFS compressor"),
+        }
+    }
+
+    /// Decompresses a block into `dst`, returning the length of the output.
+    pub(super) fn decompress(&self, src: &[u8], dst: &mut [u8]) -> Result<usize> {
+        match self {
+            Self::Gzip => {
+                let mut decoder = ZlibDecoder::new(src)
+                    .map_err(|_| Error::with_message(Errno::EIO, "invalid zlib stream"))?;
+                let mut len = 0;
+                while len < dst.len() {
+                    let read_len = decoder
+                        .read(&mut dst[len..])
+                        .map_err(|_| Error::with_message(Errno::EIO, "corrupted zlib stream"))?;
+                    if read_len == 0 {
+                        break;
+                    }
+                    len += read_len;
+                }
+                Ok(len)
+            }
+            Self::Xz => xz::decompress(src, dst),
+            Self::Lz4 => lz4_flex::block::decompress_into(src, dst)
+                .map_err(|_| Error::with_message(Errno::EIO, "corrupted LZ4 block")),
+            Self::Zstd => ruzstd::FrameDecoder::new()
+                .decode_all(src, dst)
+                .map_err(|_| Error::with_message(Errno::EIO, "corrupted zstd frame")),
+        }
+    }
+}
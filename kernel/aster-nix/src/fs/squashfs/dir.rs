@@ -0,0 +1,100 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::mem::size_of;
+
+use super::{
+    fs::SquashFS,
+    inode::inode_type,
+    metadata::{MetaReader, MetaRef},
+    NAME_MAX,
+};
+use crate::{fs::utils::InodeType, prelude::*};
+
+/// The maximum number of entries following a directory header.
+const MAX_ENTRIES_PER_HEADER: u32 = 256;
+
+/// A header in a directory listing, which is shared by the following entries
+/// whose inodes are in the same metadata block.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct DirHeader {
+    /// The number of the following entries minus one
+    count: u32,
+    /// The position of the metadata block of the inodes, relative to the inode table
+    start: u32,
+    inode_number: u32,
+}
+
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct RawDirEntry {
+    /// The offset of the inode in the metadata block
+    offset: u16,
+    /// The difference of the inode number from the one in the header
+    inode_offset: i16,
+    type_: u16,
+    /// The length of the name minus one
+    name_size: u16,
+}
+
+#[derive(Debug)]
+pub(super) struct DirEntry {
+    pub name: String,
+    pub inode_ref: MetaRef,
+    pub ino: u64,
+    pub type_: InodeType,
+}
+
+/// Reads the entries of a directory, whose listing is at `offset` in the metadata
+/// block at `block_start` of the directory table.
+///
+/// The `size` recorded in the inode is three bytes longer than the listing, as
+/// Linux counts `.` and `..` in it.
+pub(super) fn read_dir(
+    fs: &SquashFS,
+    block_start: u32,
+    offset: usize,
+    size: usize,
+) -> Result<Vec<DirEntry>> {
+    let mut entries = Vec::new();
+    if size <= 3 {
+        return Ok(entries);
+    }
+    let dir_ref = MetaRef::new(block_start as u64, offset);
+    let mut reader = MetaReader::with_ref(fs, fs.super_block().directory_table, dir_ref)?;
+
+    let mut remaining = size - 3;
+    while remaining > 0 {
+        let Some(rest) = remaining.checked_sub(size_of::<DirHeader>()) else {
+            return_errno_with_message!(Errno::EIO, "truncated directory header");
+        };
+        remaining = rest;
+        let header: DirHeader = reader.read_val()?;
+        if header.count >= MAX_ENTRIES_PER_HEADER {
+            return_errno_with_message!(Errno::EIO, "too many entries in directory header");
+        }
+
+        for _ in 0..=header.count {
+            let raw: RawDirEntry = reader.read_val()?;
+            let name_len = raw.name_size as usize + 1;
+            if name_len > NAME_MAX {
+                return_errno_with_message!(Errno::EIO, "too long name in directory");
+            }
+            let Some(rest) = remaining.checked_sub(size_of::<RawDirEntry>() + name_len) else {
+                return_errno_with_message!(Errno::EIO, "truncated directory entry");
+            };
+            remaining = rest;
+            let name = reader.read_vec(name_len)?;
+            let name = String::from_utf8(name)
+                .map_err(|_| Error::with_message(Errno::EIO, "invalid name in directory"))?;
+
+            entries.push(DirEntry {
+                name,
+                inode_ref: MetaRef::new(header.start as u64, raw.offset as usize),
+                ino: (header.inode_number as i64 + raw.inode_offset as i64) as u64,
+                type_: inode_type(raw.type_)?,
+            });
+        }
+    }
+    Ok(entries)
+}
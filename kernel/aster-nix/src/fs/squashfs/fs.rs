@@ -0,0 +1,262 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::{mem::size_of, num::NonZeroUsize};
+
+use aster_block::BlockDevice;
+use lru::LruCache;
+use spin::Once;
+
+use super::{
+    compressor::Compressor,
+    inode::SquashInode,
+    metadata::{MetaBlock, MetaReader, MetaRef, METADATA_SIZE},
+    super_block::{SuperBlock, SuperBlockFlags, SQUASHFS_MAGIC, TABLE_ABSENT},
+    xattr::XattrTable,
+    NAME_MAX,
+};
+use crate::{
+    fs::utils::{FileSystem, FsFlags, Inode, SuperBlock as VfsSuperBlock},
+    prelude::*,
+};
+
+/// If set in the size of a data block or a fragment block, the block is stored uncompressed.
+pub(super) const BLOCK_UNCOMPRESSED: u32 = 1 << 24;
+/// If set in the header of a metadata block, the block is stored uncompressed.
+const METADATA_UNCOMPRESSED: u16 = 1 << 15;
+/// The number of the decompressed metadata blocks to be cached.
+const META_CACHE_SIZE: usize = 64;
+/// The number of the decompressed data and fragment blocks to be cached.
+///
+/// A data block is larger than a page, so it is cached until all its pages are read.
+const BLOCK_CACHE_SIZE: usize = 8;
+/// The flag of `statfs` for a read-only file system.
+const ST_RDONLY: u64 = 1;
+
+/// An entry of the fragment table, which locates the block holding the tails of files.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Pod)]
+pub(super) struct FragmentEntry {
+    pub start: u64,
+    pub size: u32,
+    pub unused: u32,
+}
+
+/// SquashFS, a compressed read-only file system.
+pub struct SquashFS {
+    block_device: Arc<dyn BlockDevice>,
+    super_block: SuperBlock,
+    compressor: Compressor,
+    /// The uids and gids, which are referred by their indices in the inodes
+    ids: Vec<u32>,
+    /// The positions of the metadata blocks of the fragment table
+    fragment_index: Vec<u64>,
+    xattr_table: Option<XattrTable>,
+    meta_cache: Mutex<LruCache<u64, Arc<MetaBlock>>>,
+    block_cache: Mutex<LruCache<u64, Arc<Vec<u8>>>>,
+    root: Once<Arc<SquashInode>>,
+}
+
+impl SquashFS {
+    /// Opens the SquashFS image on the block device.
+    pub fn open(block_device: Arc<dyn BlockDevice>) -> Result<Arc<Self>> {
+        let super_block = SuperBlock::read(block_device.as_ref())?;
+        let compressor = Compressor::new(super_block.compression)?;
+        let mut squash_fs = Self {
+            block_device,
+            super_block,
+            compressor,
+            ids: Vec::new(),
+            fragment_index: Vec::new(),
+            xattr_table: None,
+            meta_cache: Mutex::new(LruCache::new(NonZeroUsize::new(META_CACHE_SIZE).unwrap())),
+            block_cache: Mutex::new(LruCache::new(NonZeroUsize::new(BLOCK_CACHE_SIZE).unwrap())),
+            root: Once::new(),
+        };
+
+        squash_fs.ids = squash_fs.read_ids()?;
+        if !super_block.flags().contains(SuperBlockFlags::NO_FRAGMENTS)
+            && super_block.fragment_table != TABLE_ABSENT
+        {
+            squash_fs.fragment_index = squash_fs.read_table_index(
+                super_block.fragment_table,
+                super_block.fragment_count as usize * size_of::<FragmentEntry>(),
+            )?;
+        }
+        if !super_block.flags().contains(SuperBlockFlags::NO_XATTRS)
+            && super_block.xattr_id_table != TABLE_ABSENT
+        {
+            squash_fs.xattr_table = Some(XattrTable::read(&squash_fs)?);
+        }
+
+        let squash_fs = Arc::new(squash_fs);
+        let root = SquashInode::read(&squash_fs, MetaRef::from_raw(super_block.root_inode))?;
+        if !root.type_().is_directory() {
+            return_errno_with_message!(Errno::EINVAL, "the root inode is not a directory");
+        }
+        squash_fs.root.call_once(|| root);
+        Ok(squash_fs)
+    }
+
+    /// Reads the positions of the metadata blocks of a lookup table, which are
+    /// stored uncompressed at `start`.
+    pub(super) fn read_table_index(&self, start: u64, table_len: usize) -> Result<Vec<u64>> {
+        let num_blocks = table_len.div_ceil(METADATA_SIZE);
+        if start + (num_blocks * size_of::<u64>()) as u64 > self.super_block.bytes_used {
+            return_errno_with_message!(Errno::EIO, "bogus lookup table");
+        }
+        let mut buf = vec![0u8; num_blocks * size_of::<u64>()];
+        self.block_device
+            .read_bytes_unaligned(start as usize, &mut buf)?;
+        Ok(buf
+            .chunks_exact(size_of::<u64>())
+            .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+            .collect())
+    }
+
+    /// Reads the entry at `index` of a lookup table whose metadata blocks are at `table_index`.
+    pub(super) fn read_table_entry<T: Pod>(&self, table_index: &[u64], index: usize) -> Result<T> {
+        let entries_per_block = METADATA_SIZE / size_of::<T>();
+        let Some(pos) = table_index.get(index / entries_per_block) else {
+            return_errno_with_message!(Errno::EIO, "bogus index of lookup table");
+        };
+        let offset = index % entries_per_block * size_of::<T>();
+        MetaReader::new(self, *pos, offset)?.read_val()
+    }
+
+    fn read_ids(&self) -> Result<Vec<u32>> {
+        let id_count = self.super_block.id_count as usize;
+        let table_index = self.read_table_index(self.super_block.id_table, id_count * 4)?;
+        let Some(first_block) = table_index.first() else {
+            return_errno_with_message!(Errno::EIO, "empty id table");
+        };
+        // The metadata blocks of a lookup table are contiguous.
+        let data = MetaReader::new(self, *first_block, 0)?.read_vec(id_count * 4)?;
+        Ok(data
+            .chunks_exact(4)
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect())
+    }
+
+    /// Reads the metadata block at `pos` of the image.
+    pub(super) fn read_meta_block(&self, pos: u64) -> Result<Arc<MetaBlock>> {
+        if let Some(block) = self.meta_cache.lock().get(&pos) {
+            return Ok(block.clone());
+        }
+
+        let bytes_used = self.super_block.bytes_used;
+        if pos + 2 > bytes_used {
+            return_errno_with_message!(Errno::EIO, "metadata block beyond the image");
+        }
+        // The header and the largest block are read together.
+        let read_len = (2 + METADATA_SIZE).min((bytes_used - pos) as usize);
+        let mut buf = vec![0u8; read_len];
+        self.block_device
+            .read_bytes_unaligned(pos as usize, &mut buf)?;
+        let header = u16::from_le_bytes([buf[0], buf[1]]);
+        let size = (header & !METADATA_UNCOMPRESSED) as usize;
+        if size == 0 || 2 + size > read_len {
+            return_errno_with_message!(Errno::EIO, "bogus size of metadata block");
+        }
+
+        let data = if header & METADATA_UNCOMPRESSED != 0 {
+            buf.truncate(2 + size);
+            buf.split_off(2)
+        } else {
+            let mut data = vec![0u8; METADATA_SIZE];
+            let len = self.compressor.decompress(&buf[2..2 + size], &mut data)?;
+            data.truncate(len);
+            data
+        };
+        let block = Arc::new(MetaBlock {
+            data,
+            next: pos + 2 + size as u64,
+        });
+        self.meta_cache.lock().put(pos, block.clone());
+        Ok(block)
+    }
+
+    /// Reads the data block or the fragment block at `pos` of the image, whose on-disk
+    /// size is encoded in `raw_size`.
+    pub(super) fn read_block(&self, pos: u64, raw_size: u32) -> Result<Arc<Vec<u8>>> {
+        if let Some(block) = self.block_cache.lock().get(&pos) {
+            return Ok(block.clone());
+        }
+
+        let size = (raw_size & (BLOCK_UNCOMPRESSED - 1)) as usize;
+        let block_size = self.block_size();
+        if size == 0 || size > block_size || pos + size as u64 > self.super_block.bytes_used {
+            return_errno_with_message!(Errno::EIO, "bogus size of data block");
+        }
+        let mut buf = vec![0u8; size];
+        self.block_device
+            .read_bytes_unaligned(pos as usize, &mut buf)?;
+
+        let data = if raw_size & BLOCK_UNCOMPRESSED != 0 {
+            buf
+        } else {
+            let mut data = vec![0u8; block_size];
+            let len = self.compressor.decompress(&buf, &mut data)?;
+            data.truncate(len);
+            data
+        };
+        let block = Arc::new(data);
+        self.block_cache.lock().put(pos, block.clone());
+        Ok(block)
+    }
+
+    pub(super) fn fragment(&self, index: u32) -> Result<FragmentEntry> {
+        if index >= self.super_block.fragment_count {
+            return_errno_with_message!(Errno::EIO, "bogus fragment index");
+        }
+        self.read_table_entry(&self.fragment_index, index as usize)
+    }
+
+    pub(super) fn id(&self, index: u16) -> Result<u32> {
+        self.ids
+            .get(index as usize)
+            .copied()
+            .ok_or_else(|| Error::with_message(Errno::EIO, "bogus id index"))
+    }
+
+    pub(super) fn xattr_table(&self) -> Option<&XattrTable> {
+        self.xattr_table.as_ref()
+    }
+
+    pub(super) fn block_device(&self) -> &dyn BlockDevice {
+        self.block_device.as_ref()
+    }
+
+    pub(super) fn super_block(&self) -> &SuperBlock {
+        &self.super_block
+    }
+
+    pub(super) fn block_size(&self) -> usize {
+        self.super_block.block_size as usize
+    }
+}
+
+impl FileSystem for SquashFS {
+    fn sync(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn root_inode(&self) -> Arc<dyn Inode> {
+        self.root.get().unwrap().clone()
+    }
+
+    fn sb(&self) -> VfsSuperBlock {
+        let mut sb = VfsSuperBlock::new(SQUASHFS_MAGIC as u64, self.block_size(), NAME_MAX);
+        sb.blocks = (self.super_block.bytes_used as usize).div_ceil(self.block_size());
+        sb.files = self.super_block.inode_count as usize;
+        sb.flags = ST_RDONLY;
+        sb
+    }
+
+    fn flags(&self) -> FsFlags {
+        FsFlags::empty()
+    }
+
+    fn name(&self) -> &'static str {
+        "squashfs"
+    }
+}
@@ -0,0 +1,594 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::time::Duration;
+
+use aster_block::bio::BioWaiter;
+use aster_rights::Full;
+use ostd::mm::{Frame, VmIo};
+
+use super::{
+    dir::{read_dir, DirEntry},
+    fs::{SquashFS, BLOCK_UNCOMPRESSED},
+    metadata::{MetaReader, MetaRef},
+    xattr::NO_XATTR,
+    NAME_MAX,
+};
+use crate::{
+    fs::{
+        device::Device,
+        utils::{
+            DirentVisitor, FileSystem, Inode, InodeMode, InodeType, IoctlCmd, Metadata, PageCache,
+            PageCacheBackend,
+        },
+    },
+    prelude::*,
+    process::{Gid, Uid},
+    vm::vmo::Vmo,
+};
+
+/// The fragment index of the files whose tails are not in fragments.
+const NO_FRAGMENT: u32 = u32::MAX;
+/// The inode types from 8 on are the extended versions of the basic ones.
+const NUM_BASIC_TYPES: u16 = 7;
+
+/// The header shared by all the types of inodes.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct InodeHeader {
+    type_: u16,
+    permissions: u16,
+    uid_idx: u16,
+    gid_idx: u16,
+    mtime: u32,
+    inode_number: u32,
+}
+
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct BasicDirInode {
+    block_start: u32,
+    nlink: u32,
+    file_size: u16,
+    block_offset: u16,
+    parent: u32,
+}
+
+/// The extended directory inode, which is followed by an index of the listing
+/// that is not used here.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct ExtDirInode {
+    nlink: u32,
+    file_size: u32,
+    block_start: u32,
+    parent: u32,
+    index_count: u16,
+    block_offset: u16,
+    xattr: u32,
+}
+
+/// The basic file inode, which is followed by the sizes of the data blocks.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct BasicFileInode {
+    blocks_start: u32,
+    fragment: u32,
+    frag_offset: u32,
+    size: u32,
+}
+
+/// The extended file inode, which is followed by the sizes of the data blocks.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct ExtFileInode {
+    blocks_start: u64,
+    size: u64,
+    sparse: u64,
+    nlink: u32,
+    fragment: u32,
+    frag_offset: u32,
+    xattr: u32,
+}
+
+#[derive(Debug)]
+enum InodeKind {
+    Dir {
+        block_start: u32,
+        offset: usize,
+        size: usize,
+        parent: u32,
+    },
+    File {
+        size: usize,
+        /// The positions in the image and the on-disk sizes of the data blocks
+        blocks: Vec<(u64, u32)>,
+        /// The index of the fragment holding the tail and the offset in it
+        fragment: Option<(u32, u32)>,
+    },
+    Symlink(String),
+    Device(u32),
+    /// A named pipe or a socket
+    Ipc,
+}
+
+/// An inode of SquashFS.
+///
+/// The inodes are immutable, so they are not cached by the file system. The contents of
+/// files are cached in their page caches.
+#[derive(Debug)]
+pub struct SquashInode {
+    ino: u64,
+    type_: InodeType,
+    mode: InodeMode,
+    uid: u32,
+    gid: u32,
+    mtime: Duration,
+    nlinks: usize,
+    /// The index of the xattrs in the xattr id table
+    xattr: u32,
+    kind: InodeKind,
+    /// The cache of the contents, which only exists for files
+    page_cache: Option<PageCache>,
+    fs: Weak<SquashFS>,
+}
+
+impl SquashInode {
+    /// Reads the inode at the reference in the inode table.
+    pub(super) fn read(fs: &Arc<SquashFS>, inode_ref: MetaRef) -> Result<Arc<Self>> {
+        let mut reader = MetaReader::with_ref(fs, fs.super_block().inode_table, inode_ref)?;
+        let header: InodeHeader = reader.read_val()?;
+        let type_ = inode_type(header.type_)?;
+        let is_extended = header.type_ > NUM_BASIC_TYPES;
+
+        let mut xattr = NO_XATTR;
+        let (nlink, kind) = match type_ {
+            InodeType::Dir if is_extended => {
+                let raw: ExtDirInode = reader.read_val()?;
+                xattr = raw.xattr;
+                let kind = InodeKind::Dir {
+                    block_start: raw.block_start,
+                    offset: raw.block_offset as usize,
+                    size: raw.file_size as usize,
+                    parent: raw.parent,
+                };
+                (raw.nlink, kind)
+            }
+            InodeType::Dir => {
+                let raw: BasicDirInode = reader.read_val()?;
+                let kind = InodeKind::Dir {
+                    block_start: raw.block_start,
+                    offset: raw.block_offset as usize,
+                    size: raw.file_size as usize,
+                    parent: raw.parent,
+                };
+                (raw.nlink, kind)
+            }
+            InodeType::File => {
+                let (nlink, blocks_start, size, fragment, frag_offset) = if is_extended {
+                    let raw: ExtFileInode = reader.read_val()?;
+                    xattr = raw.xattr;
+                    (
+                        raw.nlink,
+                        raw.blocks_start,
+                        raw.size,
+                        raw.fragment,
+                        raw.frag_offset,
+                    )
+                } else {
+                    let raw: BasicFileInode = reader.read_val()?;
+                    (
+                        1,
+                        raw.blocks_start as u64,
+                        raw.size as u64,
+                        raw.fragment,
+                        raw.frag_offset,
+                    )
+                };
+                let block_size = fs.block_size() as u64;
+                let fragment = (fragment != NO_FRAGMENT).then_some((fragment, frag_offset));
+                let num_blocks = if fragment.is_some() {
+                    size / block_size
+                } else {
+                    size.div_ceil(block_size)
+                };
+
+                // The blocks are contiguous in the image, except for the sparse ones.
+                let mut blocks = Vec::new();
+                let mut pos = blocks_start;
+                for _ in 0..num_blocks {
+                    let raw_size: u32 = reader.read_val()?;
+                    blocks.push((pos, raw_size));
+                    pos += (raw_size & (BLOCK_UNCOMPRESSED - 1)) as u64;
+                }
+                let kind = InodeKind::File {
+                    size: size as usize,
+                    blocks,
+                    fragment,
+                };
+                (nlink, kind)
+            }
+            InodeType::SymLink => {
+                let nlink: u32 = reader.read_val()?;
+                let target_size = reader.read_val::<u32>()? as usize;
+                if target_size > PAGE_SIZE {
+                    return_errno_with_message!(Errno::EIO, "too long symlink target");
+                }
+                let target = String::from_utf8(reader.read_vec(target_size)?)
+                    .map_err(|_| Error::with_message(Errno::EIO, "invalid symlink target"))?;
+                if is_extended {
+                    xattr = reader.read_val()?;
+                }
+                (nlink, InodeKind::Symlink(target))
+            }
+            InodeType::BlockDevice | InodeType::CharDevice => {
+                let nlink: u32 = reader.read_val()?;
+                let rdev: u32 = reader.read_val()?;
+                if is_extended {
+                    xattr = reader.read_val()?;
+                }
+                (nlink, InodeKind::Device(rdev))
+            }
+            InodeType::NamedPipe | InodeType::Socket => {
+                let nlink: u32 = reader.read_val()?;
+                if is_extended {
+                    xattr = reader.read_val()?;
+                }
+                (nlink, InodeKind::Ipc)
+            }
+        };
+
+        let uid = fs.id(header.uid_idx)?;
+        let gid = fs.id(header.gid_idx)?;
+        Ok(Arc::new_cyclic(|weak_self| {
+            let page_cache = match kind {
+                InodeKind::File { size, .. } => {
+                    Some(PageCache::with_capacity(size, weak_self.clone() as _).unwrap())
+                }
+                _ => None,
+            };
+            Self {
+                ino: header.inode_number as u64,
+                type_,
+                mode: InodeMode::from_bits_truncate(header.permissions),
+                uid,
+                gid,
+                mtime: Duration::from_secs(header.mtime as u64),
+                nlinks: nlink as usize,
+                xattr,
+                kind,
+                page_cache,
+                fs: Arc::downgrade(fs),
+            }
+        }))
+    }
+
+    /// Returns the extended attributes as pairs of the full names and the values.
+    pub fn xattrs(&self) -> Result<Vec<(String, Vec<u8>)>> {
+        let fs = self.squash_fs();
+        match fs.xattr_table() {
+            Some(table) if self.xattr != NO_XATTR => table.read_xattrs(&fs, self.xattr),
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    fn squash_fs(&self) -> Arc<SquashFS> {
+        self.fs.upgrade().unwrap()
+    }
+
+    fn dir_entries(&self, fs: &SquashFS) -> Result<Vec<DirEntry>> {
+        let InodeKind::Dir {
+            block_start,
+            offset,
+            size,
+            ..
+        } = self.kind
+        else {
+            return_errno!(Errno::ENOTDIR);
+        };
+        read_dir(fs, block_start, offset, size)
+    }
+
+    /// Reads the part of the file in the page at `idx` into `buf`, returning the length.
+    fn read_page_data(&self, idx: usize, buf: &mut [u8]) -> Result<usize> {
+        let InodeKind::File {
+            size,
+            ref blocks,
+            fragment,
+        } = self.kind
+        else {
+            return_errno!(Errno::EISDIR);
+        };
+        let offset = idx * PAGE_SIZE;
+        if offset >= size {
+            return Ok(0);
+        }
+        let len = (size - offset).min(PAGE_SIZE);
+
+        let fs = self.squash_fs();
+        let block_size = fs.block_size();
+        let offset_in_block = offset % block_size;
+        let (data, start) = if let Some(&(pos, raw_size)) = blocks.get(offset / block_size) {
+            if raw_size & (BLOCK_UNCOMPRESSED - 1) == 0 {
+                // A sparse block, which reads as zeros.
+                return Ok(0);
+            }
+            (fs.read_block(pos, raw_size)?, offset_in_block)
+        } else {
+            let Some((index, frag_offset)) = fragment else {
+                return_errno_with_message!(Errno::EIO, "missing data block");
+            };
+            let entry = fs.fragment(index)?;
+            let data = fs.read_block(entry.start, entry.size)?;
+            (data, frag_offset as usize + offset_in_block)
+        };
+
+        let Some(src) = data.get(start..start + len) else {
+            return_errno_with_message!(Errno::EIO, "data block is too short");
+        };
+        buf[..len].copy_from_slice(src);
+        Ok(len)
+    }
+}
+
+/// Converts the type of an inode or a directory entry.
+pub(super) fn inode_type(raw: u16) -> Result<InodeType> {
+    let type_ = match raw {
+        1 | 8 => InodeType::Dir,
+        2 | 9 => InodeType::File,
+        3 | 10 => InodeType::SymLink,
+        4 | 11 => InodeType::BlockDevice,
+        5 | 12 => InodeType::CharDevice,
+        6 | 13 => InodeType::NamedPipe,
+        7 | 14 => InodeType::Socket,
+        _ => return_errno_with_message!(Errno::EIO, "unknown SquashFS inode type"),
+    };
+    Ok(type_)
+}
+
+impl PageCacheBackend for SquashInode {
+    fn read_page(&self, idx: usize, frame: &Frame) -> Result<BioWaiter> {
+        let mut buf = vec![0u8; PAGE_SIZE];
+        let len = self.read_page_data(idx, &mut buf)?;
+        frame.write_bytes(0, &buf[..len])?;
+        frame.writer().skip(len).fill(0u8);
+        // The page is filled synchronously.
+        Ok(BioWaiter::new())
+    }
+
+    fn write_page(&self, _idx: usize, _frame: &Frame) -> Result<BioWaiter> {
+        return_errno_with_message!(Errno::EROFS, "SquashFS is read-only")
+    }
+
+    fn npages(&self) -> usize {
+        self.size().div_ceil(PAGE_SIZE)
+    }
+}
+
+impl Inode for SquashInode {
+    fn size(&self) -> usize {
+        match &self.kind {
+            InodeKind::Dir { size, .. } | InodeKind::File { size, .. } => *size,
+            InodeKind::Symlink(target) => target.len(),
+            InodeKind::Device(_) | InodeKind::Ipc => 0,
+        }
+    }
+
+    fn resize(&self, _new_size: usize) -> Result<()> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn metadata(&self) -> Metadata {
+        let blk_size = self.squash_fs().block_size();
+        Metadata {
+            dev: 0,
+            ino: self.ino,
+            size: self.size(),
+            blk_size,
+            blocks: self.size().div_ceil(blk_size),
+            atime: self.mtime,
+            mtime: self.mtime,
+            ctime: self.mtime,
+            type_: self.type_,
+            mode: self.mode,
+            nlinks: self.nlinks,
+            uid: Uid::new(self.uid),
+            gid: Gid::new(self.gid),
+            rdev: match self.kind {
+                InodeKind::Device(rdev) => rdev as u64,
+                _ => 0,
+            },
+        }
+    }
+
+    fn ino(&self) -> u64 {
+        self.ino
+    }
+
+    fn type_(&self) -> InodeType {
+        self.type_
+    }
+
+    fn mode(&self) -> Result<InodeMode> {
+        Ok(self.mode)
+    }
+
+    fn set_mode(&self, _mode: InodeMode) -> Result<()> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn owner(&self) -> Result<Uid> {
+        Ok(Uid::new(self.uid))
+    }
+
+    fn set_owner(&self, _uid: Uid) -> Result<()> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn group(&self) -> Result<Gid> {
+        Ok(Gid::new(self.gid))
+    }
+
+    fn set_group(&self, _gid: Gid) -> Result<()> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn atime(&self) -> Duration {
+        self.mtime
+    }
+
+    fn set_atime(&self, _time: Duration) {
+        // The access time is not recorded.
+    }
+
+    fn mtime(&self) -> Duration {
+        self.mtime
+    }
+
+    fn set_mtime(&self, _time: Duration) {}
+
+    fn ctime(&self) -> Duration {
+        self.mtime
+    }
+
+    fn set_ctime(&self, _time: Duration) {}
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        self.squash_fs()
+    }
+
+    fn page_cache(&self) -> Option<Vmo<Full>> {
+        self.page_cache
+            .as_ref()
+            .map(|page_cache| page_cache.pages().dup())
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        let Some(page_cache) = &self.page_cache else {
+            if self.type_ == InodeType::Dir {
+                return_errno!(Errno::EISDIR);
+            }
+            return_errno!(Errno::EINVAL);
+        };
+        let size = self.size();
+        let start = offset.min(size);
+        let end = offset.saturating_add(buf.len()).min(size);
+        page_cache
+            .pages()
+            .read_bytes(start, &mut buf[..end - start])?;
+        Ok(end - start)
+    }
+
+    fn read_direct_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        self.read_at(offset, buf)
+    }
+
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> Result<usize> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn write_direct_at(&self, _offset: usize, _buf: &[u8]) -> Result<usize> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn create(&self, _name: &str, _type_: InodeType, _mode: InodeMode) -> Result<Arc<dyn Inode>> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn mknod(
+        &self,
+        _name: &str,
+        _mode: InodeMode,
+        _dev: Arc<dyn Device>,
+    ) -> Result<Arc<dyn Inode>> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn as_device(&self) -> Option<Arc<dyn Device>> {
+        match self.kind {
+            InodeKind::Device(rdev) => {
+                let type_ = self.type_.device_type()?;
+                crate::device::get_device(type_, rdev as usize).ok()
+            }
+            _ => None,
+        }
+    }
+
+    fn readdir_at(&self, offset: usize, visitor: &mut dyn DirentVisitor) -> Result<usize> {
+        let InodeKind::Dir { parent, .. } = self.kind else {
+            return_errno!(Errno::ENOTDIR);
+        };
+        let fs = self.squash_fs();
+        // The parent of the root directory is beyond the inode numbers.
+        let parent_ino = if parent > fs.super_block().inode_count {
+            self.ino
+        } else {
+            parent as u64
+        };
+        let entries = self.dir_entries(&fs)?;
+
+        let try_visit = |idx: &mut usize, visitor: &mut dyn DirentVisitor| -> Result<()> {
+            if *idx == 0 {
+                visitor.visit(".", self.ino, InodeType::Dir, *idx)?;
+                *idx += 1;
+            }
+            if *idx == 1 {
+                visitor.visit("..", parent_ino, InodeType::Dir, *idx)?;
+                *idx += 1;
+            }
+            for (i, entry) in entries.iter().enumerate().skip(*idx - 2) {
+                visitor.visit(&entry.name, entry.ino, entry.type_, i + 2)?;
+                *idx = i + 3;
+            }
+            Ok(())
+        };
+
+        let mut iterate_idx = offset;
+        match try_visit(&mut iterate_idx, visitor) {
+            Err(e) if iterate_idx == offset => Err(e),
+            _ => Ok(iterate_idx - offset),
+        }
+    }
+
+    fn link(&self, _old: &Arc<dyn Inode>, _name: &str) -> Result<()> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn unlink(&self, _name: &str) -> Result<()> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn rmdir(&self, _name: &str) -> Result<()> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>> {
+        if name.len() > NAME_MAX {
+            return_errno!(Errno::ENAMETOOLONG);
+        }
+        let fs = self.squash_fs();
+        let entries = self.dir_entries(&fs)?;
+        let Some(entry) = entries.iter().find(|entry| entry.name == name) else {
+            return_errno!(Errno::ENOENT);
+        };
+        Ok(SquashInode::read(&fs, entry.inode_ref)?)
+    }
+
+    fn rename(&self, _old_name: &str, _target: &Arc<dyn Inode>, _new_name: &str) -> Result<()> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn read_link(&self) -> Result<String> {
+        match &self.kind {
+            InodeKind::Symlink(target) => Ok(target.clone()),
+            _ => return_errno_with_message!(Errno::EINVAL, "not a symlink"),
+        }
+    }
+
+    fn write_link(&self, _target: &str) -> Result<()> {
+        return_errno!(Errno::EROFS)
+    }
+
+    fn ioctl(&self, _cmd: IoctlCmd, _arg: usize) -> Result<i32> {
+        return_errno_with_message!(Errno::EINVAL, "unsupported operation")
+    }
+}
@@ -0,0 +1,91 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::fs::SquashFS;
+use crate::prelude::*;
+
+/// The maximum size of the data in a metadata block.
+pub(super) const METADATA_SIZE: usize = 8192;
+
+/// A decompressed metadata block.
+///
+/// The inodes, the directories and the lookup tables are stored in the metadata blocks,
+/// each of which has a 16-bit header with the size and whether it is compressed.
+pub(super) struct MetaBlock {
+    pub data: Vec<u8>,
+    /// The position of the following metadata block in the image
+    pub next: u64,
+}
+
+/// A reference to a position in the metadata, which consists of the position of the
+/// metadata block relative to the start of the table, and the offset in the decompressed
+/// block.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) struct MetaRef(u64);
+
+impl MetaRef {
+    pub(super) fn new(block: u64, offset: usize) -> Self {
+        Self((block << 16) | offset as u64)
+    }
+
+    pub(super) fn from_raw(raw: u64) -> Self {
+        Self(raw)
+    }
+
+    pub(super) fn block(&self) -> u64 {
+        self.0 >> 16
+    }
+
+    pub(super) fn offset(&self) -> usize {
+        (self.0 & 0xFFFF) as usize
+    }
+}
+
+/// A reader of the metadata, which continues into the following blocks.
+pub(super) struct MetaReader<'a> {
+    fs: &'a SquashFS,
+    block: Arc<MetaBlock>,
+    offset: usize,
+}
+
+impl<'a> MetaReader<'a> {
+    /// Creates a reader at `offset` in the metadata block at `pos` of the image.
+    pub(super) fn new(fs: &'a SquashFS, pos: u64, offset: usize) -> Result<Self> {
+        let block = fs.read_meta_block(pos)?;
+        if offset > block.data.len() {
+            return_errno_with_message!(Errno::EIO, "bogus offset in metadata block");
+        }
+        Ok(Self { fs, block, offset })
+    }
+
+    /// Creates a reader at the reference in the table starting at `table`.
+    pub(super) fn with_ref(fs: &'a SquashFS, table: u64, meta_ref: MetaRef) -> Result<Self> {
+        Self::new(fs, table + meta_ref.block(), meta_ref.offset())
+    }
+
+    pub(super) fn read(&mut self, buf: &mut [u8]) -> Result<()> {
+        let mut done = 0;
+        while done < buf.len() {
+            if self.offset == self.block.data.len() {
+                self.block = self.fs.read_meta_block(self.block.next)?;
+                self.offset = 0;
+            }
+            let len = (buf.len() - done).min(self.block.data.len() - self.offset);
+            buf[done..done + len].copy_from_slice(&self.block.data[self.offset..self.offset + len]);
+            self.offset += len;
+            done += len;
+        }
+        Ok(())
+    }
+
+    pub(super) fn read_val<T: Pod>(&mut self) -> Result<T> {
+        let mut val = T::new_zeroed();
+        self.read(val.as_bytes_mut())?;
+        Ok(val)
+    }
+
+    pub(super) fn read_vec(&mut self, len: usize) -> Result<Vec<u8>> {
+        let mut buf = vec![0u8; len];
+        self.read(&mut buf)?;
+        Ok(buf)
+    }
+}
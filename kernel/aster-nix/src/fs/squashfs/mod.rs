@@ -0,0 +1,26 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! SquashFS 4.0, a read-only file system with compressed blocks.
+//!
+//! The inodes, the directories and the lookup tables are packed into metadata blocks
+//! of up to 8 KiB, and the file contents are split into data blocks of the block size
+//! given by the super block. The tails of the files can be packed together into
+//! fragment blocks. Each block may be compressed with gzip, xz, LZ4 or zstd, while
+//! the LZMA and LZO images are rejected.
+//!
+//! The decompressed metadata and data blocks are kept in small LRU caches, and the
+//! file contents are cached in the page caches of the inodes.
+
+pub use fs::SquashFS;
+pub use inode::SquashInode;
+
+mod compressor;
+mod dir;
+mod fs;
+mod inode;
+mod metadata;
+mod super_block;
+mod xattr;
+mod xz;
+
+const NAME_MAX: usize = 256;
@@ -0,0 +1,94 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use aster_block::{BlockDevice, SECTOR_SIZE};
+
+use crate::prelude::*;
+
+pub(super) const SQUASHFS_MAGIC: u32 = 0x7371_7368;
+const MIN_BLOCK_LOG: u16 = 12;
+const MAX_BLOCK_LOG: u16 = 20;
+
+/// The on-disk super block of SquashFS 4.0, which is at the start of the image.
+///
+/// All the `*_table` fields are the byte offsets of the tables in the image.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Pod)]
+pub(super) struct SuperBlock {
+    pub magic: u32,
+    pub inode_count: u32,
+    pub mod_time: u32,
+    pub block_size: u32,
+    pub fragment_count: u32,
+    pub compression: u16,
+    pub block_log: u16,
+    pub flags: u16,
+    pub id_count: u16,
+    pub version_major: u16,
+    pub version_minor: u16,
+    /// The reference to the root inode in the inode table
+    pub root_inode: u64,
+    pub bytes_used: u64,
+    pub id_table: u64,
+    pub xattr_id_table: u64,
+    pub inode_table: u64,
+    pub directory_table: u64,
+    pub fragment_table: u64,
+    pub export_table: u64,
+}
+
+/// The offset of the tables that are absent, e.g., the xattr table of an image without xattrs.
+pub(super) const TABLE_ABSENT: u64 = u64::MAX;
+
+bitflags! {
+    pub(super) struct SuperBlockFlags: u16 {
+        const UNCOMPRESSED_INODES = 1 << 0;
+        const UNCOMPRESSED_DATA = 1 << 1;
+        const UNCOMPRESSED_FRAGMENTS = 1 << 3;
+        const NO_FRAGMENTS = 1 << 4;
+        const ALWAYS_FRAGMENTS = 1 << 5;
+        const DUPLICATES = 1 << 6;
+        const EXPORTABLE = 1 << 7;
+        const UNCOMPRESSED_XATTRS = 1 << 8;
+        const NO_XATTRS = 1 << 9;
+        const COMPRESSOR_OPTIONS = 1 << 10;
+        const UNCOMPRESSED_IDS = 1 << 11;
+    }
+}
+
+impl SuperBlock {
+    /// Reads the super block and checks it against the device.
+    pub(super) fn read(block_device: &dyn BlockDevice) -> Result<Self> {
+        let mut buf = [0u8; SECTOR_SIZE];
+        block_device.read_bytes(0, &mut buf)?;
+        let super_block = Self::from_bytes(&buf[..core::mem::size_of::<Self>()]);
+
+        if super_block.magic != SQUASHFS_MAGIC {
+            return_errno_with_message!(Errno::EINVAL, "invalid SquashFS magic");
+        }
+        if super_block.version_major != 4 || super_block.version_minor != 0 {
+            return_errno_with_message!(Errno::EINVAL, "unsupported SquashFS version");
+        }
+        let block_log = super_block.block_log;
+        if !(MIN_BLOCK_LOG..=MAX_BLOCK_LOG).contains(&block_log)
+            || super_block.block_size != 1 << block_log
+        {
+            return_errno_with_message!(Errno::EINVAL, "bogus SquashFS block size");
+        }
+        if super_block.bytes_used > (block_device.nr_sectors() * SECTOR_SIZE) as u64 {
+            return_errno_with_message!(Errno::EINVAL, "the image is larger than the device");
+        }
+        let tables = [
+            super_block.inode_table,
+            super_block.directory_table,
+            super_block.id_table,
+        ];
+        if tables.iter().any(|table| *table >= super_block.bytes_used) {
+            return_errno_with_message!(Errno::EINVAL, "bogus SquashFS table offsets");
+        }
+        Ok(super_block)
+    }
+
+    pub(super) fn flags(&self) -> SuperBlockFlags {
+        SuperBlockFlags::from_bits_truncate(self.flags)
+    }
+}
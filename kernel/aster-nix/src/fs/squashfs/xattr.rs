@@ -0,0 +1,113 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::format;
+use core::mem::size_of;
+
+use super::{
+    fs::SquashFS,
+    metadata::{MetaReader, MetaRef},
+};
+use crate::prelude::*;
+
+/// The index of the xattrs of the inodes without xattrs.
+pub(super) const NO_XATTR: u32 = u32::MAX;
+/// If set in the type of an xattr key, the value is stored elsewhere and referred
+/// by a reference following the key.
+const XATTR_VALUE_OOL: u16 = 0x100;
+const XATTR_PREFIXES: [&str; 3] = ["user.", "trusted.", "security."];
+
+/// The header of the xattr id table.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct XattrIdTableHeader {
+    /// The start of the metadata blocks of the xattr keys and values
+    kv_start: u64,
+    count: u32,
+    unused: u32,
+}
+
+/// An entry of the xattr id table, which locates the xattrs of inodes.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct XattrId {
+    /// The reference to the first key, which is relative to `kv_start`
+    xattr: u64,
+    count: u32,
+    size: u32,
+}
+
+/// The tables of the xattrs, which are shared by the inodes with the same xattrs.
+pub(super) struct XattrTable {
+    kv_start: u64,
+    count: u32,
+    /// The positions of the metadata blocks of the xattr id table
+    id_index: Vec<u64>,
+}
+
+impl XattrTable {
+    pub(super) fn read(fs: &SquashFS) -> Result<Self> {
+        let start = fs.super_block().xattr_id_table;
+        let mut buf = [0u8; size_of::<XattrIdTableHeader>()];
+        if start + buf.len() as u64 > fs.super_block().bytes_used {
+            return_errno_with_message!(Errno::EIO, "bogus xattr id table");
+        }
+        fs.block_device()
+            .read_bytes_unaligned(start as usize, &mut buf)?;
+        let header = XattrIdTableHeader::from_bytes(&buf);
+        let id_index = fs.read_table_index(
+            start + buf.len() as u64,
+            header.count as usize * size_of::<XattrId>(),
+        )?;
+        Ok(Self {
+            kv_start: header.kv_start,
+            count: header.count,
+            id_index,
+        })
+    }
+
+    /// Reads the xattrs at `index` of the xattr id table as pairs of the full names
+    /// and the values.
+    pub(super) fn read_xattrs(&self, fs: &SquashFS, index: u32) -> Result<Vec<(String, Vec<u8>)>> {
+        if index >= self.count {
+            return_errno_with_message!(Errno::EIO, "bogus xattr index");
+        }
+        let xattr_id: XattrId = fs.read_table_entry(&self.id_index, index as usize)?;
+        let mut reader =
+            MetaReader::with_ref(fs, self.kv_start, MetaRef::from_raw(xattr_id.xattr))?;
+
+        let mut xattrs = Vec::new();
+        for _ in 0..xattr_id.count {
+            let type_ = reader.read_val::<u16>()?;
+            let name_size = reader.read_val::<u16>()? as usize;
+            let name = reader.read_vec(name_size)?;
+            let Some(prefix) = XATTR_PREFIXES.get((type_ & 0xFF) as usize) else {
+                return_errno_with_message!(Errno::EIO, "unknown xattr prefix");
+            };
+            let full_name = format!("{}{}", prefix, String::from_utf8_lossy(&name));
+
+            let value_size = reader.read_val::<u32>()? as usize;
+            let value = if type_ & XATTR_VALUE_OOL != 0 {
+                if value_size != size_of::<u64>() {
+                    return_errno_with_message!(Errno::EIO, "bogus xattr value reference");
+                }
+                let value_ref = MetaRef::from_raw(reader.read_val::<u64>()?);
+                let mut value_reader = MetaReader::with_ref(fs, self.kv_start, value_ref)?;
+                let value_size = value_reader.read_val::<u32>()? as usize;
+                read_value(&mut value_reader, value_size)?
+            } else {
+                read_value(&mut reader, value_size)?
+            };
+            xattrs.push((full_name, value));
+        }
+        Ok(xattrs)
+    }
+}
+
+fn read_value(reader: &mut MetaReader, size: usize) -> Result<Vec<u8>> {
+    // The same limit as Linux on the size of an xattr value.
+    const XATTR_SIZE_MAX: usize = 65536;
+    if size > XATTR_SIZE_MAX {
+        return_errno_with_message!(Errno::EIO, "too large xattr value");
+    }
+    reader.read_vec(size)
+}
@@ -0,0 +1,641 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! A decoder of the xz format with the LZMA2 filter, which is what `mksquashfs -comp xz`
+//! produces.
+//!
+//! The whole output is available as the dictionary since a block is decompressed at once,
+//! so there is no circular window as a streaming decoder would need. The branch/call/jump
+//! filters are not supported. The CRC32 of the headers and the CRC32 or CRC64 checks
+//! of the blocks are verified, while the other checks, e.g., SHA-256, are skipped.
+
+use aster_util::crc::{crc32, crc64};
+
+use crate::prelude::*;
+
+const STREAM_MAGIC: [u8; 6] = [0xFD, b'7', b'z', b'X', b'Z', 0x00];
+const STREAM_HEADER_LEN: usize = 12;
+const FILTER_LZMA2: u64 = 0x21;
+const CHECK_CRC32: u8 = 0x01;
+const CHECK_CRC64: u8 = 0x04;
+
+/// Decompresses the xz stream in `src` into `dst`, returning the length of the output.
+pub(super) fn decompress(src: &[u8], dst: &mut [u8]) -> Result<usize> {
+    if src.len() < STREAM_HEADER_LEN || src[..6] != STREAM_MAGIC || src[6] != 0 {
+        return_errno_with_message!(Errno::EIO, "invalid xz stream header");
+    }
+    if crc32(&src[6..8]) != read_le_u32(&src[8..12]) {
+        return_errno_with_message!(Errno::EIO, "corrupted xz stream header");
+    }
+    let check_type = src[7] & 0x0F;
+    let check_len = match check_type {
+        0 => 0,
+        check_type => 4 << ((check_type - 1) / 3),
+    };
+
+    let mut input = ByteReader::new(src, STREAM_HEADER_LEN);
+    let mut out_len = 0;
+    loop {
+        // A zero byte in the place of the size of a block header starts the index.
+        let header_start = input.pos;
+        let header_size = input.byte()?;
+        if header_size == 0 {
+            break;
+        }
+        // The size includes the size byte itself and the CRC32 at the end.
+        let header_end = header_start + (header_size as usize + 1) * 4;
+        let Some(header) = src.get(header_start..header_end) else {
+            return_errno_with_message!(Errno::EIO, "truncated xz stream");
+        };
+        let (header, header_crc) = header.split_at(header.len() - 4);
+        if crc32(header) != read_le_u32(header_crc) {
+            return_errno_with_message!(Errno::EIO, "corrupted xz block header");
+        }
+        let flags = input.byte()?;
+        if flags & 0x3C != 0 {
+            return_errno_with_message!(Errno::EIO, "unsupported xz block flags");
+        }
+        if flags & 0x40 != 0 {
+            input.varint()?;
+        }
+        if flags & 0x80 != 0 {
+            input.varint()?;
+        }
+        let num_filters = (flags & 0x03) + 1;
+        let mut dict_size = None;
+        for _ in 0..num_filters {
+            let id = input.varint()?;
+            let props_len = input.varint()? as usize;
+            let props = input.bytes(props_len)?;
+            if id != FILTER_LZMA2 || props_len != 1 || num_filters != 1 {
+                return_errno_with_message!(Errno::EOPNOTSUPP, "unsupported xz filters");
+            }
+            dict_size = Some(lzma2_dict_size(props[0])?);
+        }
+        // Skips the padding and the CRC32 of the block header.
+        input.pos = header_end;
+
+        let Some(dict_size) = dict_size else {
+            return_errno_with_message!(Errno::EIO, "no filter in xz block");
+        };
+        let block_start = out_len;
+        out_len = Lzma2Decoder::new(dict_size).decode(&mut input, dst, out_len)?;
+
+        // Skips the block padding, and then verifies the check.
+        input.pos = input.pos.next_multiple_of(4);
+        let check = input.bytes(check_len)?;
+        let output = &dst[block_start..out_len];
+        let is_valid = match check_type {
+            CHECK_CRC32 => crc32(output) == read_le_u32(check),
+            CHECK_CRC64 => crc64(output) == u64::from_le_bytes(check.try_into().unwrap()),
+            _ => true,
+        };
+        if !is_valid {
+            return_errno_with_message!(Errno::EIO, "xz block check failed");
+        }
+    }
+    Ok(out_len)
+}
+
+fn read_le_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes(bytes.try_into().unwrap())
+}
+
+fn lzma2_dict_size(props: u8) -> Result<usize> {
+    if props > 40 {
+        return_errno_with_message!(Errno::EIO, "invalid LZMA2 dictionary size");
+    }
+    if props == 40 {
+        return Ok(u32::MAX as usize);
+    }
+    Ok((2 | (props as usize & 1)) << (props / 2 + 11))
+}
+
+/// A cursor over the compressed bytes.
+struct ByteReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn new(buf: &'a [u8], pos: usize) -> Self {
+        Self { buf, pos }
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        let Some(byte) = self.buf.get(self.pos) else {
+            return_errno_with_message!(Errno::EIO, "truncated xz stream");
+        };
+        self.pos += 1;
+        Ok(*byte)
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let Some(bytes) = self.buf.get(self.pos..self.pos + len) else {
+            return_errno_with_message!(Errno::EIO, "truncated xz stream");
+        };
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn be_u16(&mut self) -> Result<usize> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]) as usize)
+    }
+
+    /// Reads a multibyte integer, which has 7 bits in each byte.
+    fn varint(&mut self) -> Result<u64> {
+        let mut value = 0u64;
+        for i in 0..9 {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7F) as u64) << (i * 7);
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        return_errno_with_message!(Errno::EIO, "invalid xz multibyte integer")
+    }
+}
+
+/// A decoder of the LZMA2 chunks in a block.
+struct Lzma2Decoder {
+    dict_size: usize,
+    /// The start of the dictionary in the output, which is moved by a dictionary reset
+    dict_start: usize,
+    lzma: Option<LzmaDecoder>,
+}
+
+impl Lzma2Decoder {
+    fn new(dict_size: usize) -> Self {
+        Self {
+            dict_size,
+            dict_start: 0,
+            lzma: None,
+        }
+    }
+
+    /// Decodes the chunks to `dst` from `pos`, returning the end of the output.
+    fn decode(&mut self, input: &mut ByteReader, dst: &mut [u8], mut pos: usize) -> Result<usize> {
+        let mut need_dict_reset = true;
+        loop {
+            let control = input.byte()?;
+            if control == 0x00 {
+                return Ok(pos);
+            }
+
+            if control < 0x80 {
+                // An uncompressed chunk
+                if control > 0x02 {
+                    return_errno_with_message!(Errno::EIO, "invalid LZMA2 control byte");
+                }
+                if control == 0x01 {
+                    self.dict_start = pos;
+                    need_dict_reset = false;
+                } else if need_dict_reset {
+                    return_errno_with_message!(Errno::EIO, "missing LZMA2 dictionary reset");
+                }
+                let len = input.be_u16()? + 1;
+                let data = input.bytes(len)?;
+                let Some(out) = dst.get_mut(pos..pos + len) else {
+                    return_errno_with_message!(Errno::EIO, "too large LZMA2 output");
+                };
+                out.copy_from_slice(data);
+                pos += len;
+                continue;
+            }
+
+            let unpacked_len = (((control & 0x1F) as usize) << 16) + input.be_u16()? + 1;
+            let packed_len = input.be_u16()? + 1;
+            let reset = (control >> 5) & 0x03;
+            if reset == 3 {
+                self.dict_start = pos;
+                need_dict_reset = false;
+            } else if need_dict_reset {
+                return_errno_with_message!(Errno::EIO, "missing LZMA2 dictionary reset");
+            }
+            if reset >= 2 {
+                self.lzma = Some(LzmaDecoder::new(input.byte()?)?);
+            } else if reset == 1
+                && let Some(lzma) = self.lzma.as_mut()
+            {
+                lzma.reset();
+            }
+            let Some(lzma) = self.lzma.as_mut() else {
+                return_errno_with_message!(Errno::EIO, "missing LZMA2 properties");
+            };
+
+            let packed = input.bytes(packed_len)?;
+            let end = pos + unpacked_len;
+            if end > dst.len() {
+                return_errno_with_message!(Errno::EIO, "too large LZMA2 output");
+            }
+            let mut window = Window {
+                buf: &mut dst[..end],
+                start: self.dict_start,
+                pos,
+                max_dist: self.dict_size,
+            };
+            lzma.decode(&mut RangeDecoder::new(packed)?, &mut window)?;
+            pos = end;
+        }
+    }
+}
+
+/// The output written so far, which serves as the dictionary.
+struct Window<'a> {
+    buf: &'a mut [u8],
+    /// The position of the last dictionary reset
+    start: usize,
+    pos: usize,
+    max_dist: usize,
+}
+
+impl Window<'_> {
+    fn is_full(&self) -> bool {
+        self.pos == self.buf.len()
+    }
+
+    /// Returns the number of bytes since the last dictionary reset.
+    fn total_pos(&self) -> usize {
+        self.pos - self.start
+    }
+
+    /// Returns the byte `dist + 1` bytes before the current position.
+    fn get(&self, dist: usize) -> u8 {
+        self.buf[self.pos - dist - 1]
+    }
+
+    fn put(&mut self, byte: u8) {
+        self.buf[self.pos] = byte;
+        self.pos += 1;
+    }
+
+    fn copy_match(&mut self, dist: usize, len: usize) -> Result<()> {
+        if dist >= self.total_pos() || dist >= self.max_dist {
+            return_errno_with_message!(Errno::EIO, "invalid LZMA match distance");
+        }
+        if len > self.buf.len() - self.pos {
+            return_errno_with_message!(Errno::EIO, "LZMA match exceeds the chunk");
+        }
+        // The source may overlap with the destination, so the bytes are copied one by one.
+        for _ in 0..len {
+            let byte = self.get(dist);
+            self.put(byte);
+        }
+        Ok(())
+    }
+}
+
+const NUM_STATES: usize = 12;
+const POS_STATES_MAX: usize = 1 << 4;
+const LEN_TO_POS_STATES: usize = 4;
+const END_POS_MODEL_INDEX: usize = 14;
+const NUM_FULL_DISTANCES: usize = 1 << (END_POS_MODEL_INDEX / 2);
+const NUM_ALIGN_BITS: usize = 4;
+const MATCH_MIN_LEN: usize = 2;
+const PROB_INIT: u16 = 1 << 10;
+
+/// The decoder of the range-coded bits.
+struct RangeDecoder<'a> {
+    input: ByteReader<'a>,
+    range: u32,
+    code: u32,
+}
+
+impl<'a> RangeDecoder<'a> {
+    fn new(buf: &'a [u8]) -> Result<Self> {
+        let mut input = ByteReader::new(buf, 0);
+        if input.byte()? != 0 {
+            return_errno_with_message!(Errno::EIO, "invalid LZMA range coder");
+        }
+        let code = input.bytes(4)?;
+        Ok(Self {
+            input,
+            range: u32::MAX,
+            code: u32::from_be_bytes([code[0], code[1], code[2], code[3]]),
+        })
+    }
+
+    fn normalize(&mut self) -> Result<()> {
+        if self.range < (1 << 24) {
+            self.range <<= 8;
+            self.code = (self.code << 8) | self.input.byte()? as u32;
+        }
+        Ok(())
+    }
+
+    fn bit(&mut self, prob: &mut u16) -> Result<usize> {
+        let bound = (self.range >> 11) * (*prob as u32);
+        let bit = if self.code < bound {
+            self.range = bound;
+            *prob += ((1 << 11) - *prob) >> 5;
+            0
+        } else {
+            self.range -= bound;
+            self.code -= bound;
+            *prob -= *prob >> 5;
+            1
+        };
+        self.normalize()?;
+        Ok(bit)
+    }
+
+    fn direct_bits(&mut self, num_bits: usize) -> Result<usize> {
+        let mut value = 0;
+        for _ in 0..num_bits {
+            self.range >>= 1;
+            let bit = (self.code >= self.range) as usize;
+            if bit == 1 {
+                self.code -= self.range;
+            }
+            value = (value << 1) | bit;
+            self.normalize()?;
+        }
+        Ok(value)
+    }
+
+    fn bit_tree(&mut self, probs: &mut [u16], num_bits: usize) -> Result<usize> {
+        let mut m = 1;
+        for _ in 0..num_bits {
+            m = (m << 1) | self.bit(&mut probs[m])?;
+        }
+        Ok(m - (1 << num_bits))
+    }
+
+    fn reverse_bit_tree(&mut self, probs: &mut [u16], num_bits: usize) -> Result<usize> {
+        let mut m = 1;
+        let mut symbol = 0;
+        for i in 0..num_bits {
+            let bit = self.bit(&mut probs[m])?;
+            m = (m << 1) | bit;
+            symbol |= bit << i;
+        }
+        Ok(symbol)
+    }
+}
+
+/// The probabilities to decode the lengths of the matches.
+struct LenDecoder {
+    choice: u16,
+    choice2: u16,
+    low: [[u16; 1 << 3]; POS_STATES_MAX],
+    mid: [[u16; 1 << 3]; POS_STATES_MAX],
+    high: [u16; 1 << 8],
+}
+
+impl LenDecoder {
+    fn new() -> Self {
+        Self {
+            choice: PROB_INIT,
+            choice2: PROB_INIT,
+            low: [[PROB_INIT; 1 << 3]; POS_STATES_MAX],
+            mid: [[PROB_INIT; 1 << 3]; POS_STATES_MAX],
+            high: [PROB_INIT; 1 << 8],
+        }
+    }
+
+    fn decode(&mut self, rc: &mut RangeDecoder, pos_state: usize) -> Result<usize> {
+        if rc.bit(&mut self.choice)? == 0 {
+            return rc.bit_tree(&mut self.low[pos_state], 3);
+        }
+        if rc.bit(&mut self.choice2)? == 0 {
+            return Ok(8 + rc.bit_tree(&mut self.mid[pos_state], 3)?);
+        }
+        Ok(16 + rc.bit_tree(&mut self.high, 8)?)
+    }
+}
+
+/// The LZMA decoder, whose state is kept across the LZMA2 chunks.
+struct LzmaDecoder {
+    lc: usize,
+    lp: usize,
+    pb: usize,
+    state: usize,
+    reps: [usize; 4],
+    is_match: [u16; NUM_STATES * POS_STATES_MAX],
+    is_rep: [u16; NUM_STATES],
+    is_rep_g0: [u16; NUM_STATES],
+    is_rep_g1: [u16; NUM_STATES],
+    is_rep_g2: [u16; NUM_STATES],
+    is_rep0_long: [u16; NUM_STATES * POS_STATES_MAX],
+    literal: Vec<u16>,
+    pos_slot: [[u16; 1 << 6]; LEN_TO_POS_STATES],
+    pos_special: [u16; 1 + NUM_FULL_DISTANCES - END_POS_MODEL_INDEX],
+    align: [u16; 1 << NUM_ALIGN_BITS],
+    len: LenDecoder,
+    rep_len: LenDecoder,
+}
+
+impl LzmaDecoder {
+    fn new(props: u8) -> Result<Self> {
+        let props = props as usize;
+        let (lc, lp, pb) = (props % 9, props / 9 % 5, props / 45);
+        if props >= 9 * 5 * 5 || lc + lp > 4 {
+            return_errno_with_message!(Errno::EIO, "invalid LZMA properties");
+        }
+        Ok(Self {
+            lc,
+            lp,
+            pb,
+            state: 0,
+            reps: [0; 4],
+            is_match: [PROB_INIT; NUM_STATES * POS_STATES_MAX],
+            is_rep: [PROB_INIT; NUM_STATES],
+            is_rep_g0: [PROB_INIT; NUM_STATES],
+            is_rep_g1: [PROB_INIT; NUM_STATES],
+            is_rep_g2: [PROB_INIT; NUM_STATES],
+            is_rep0_long: [PROB_INIT; NUM_STATES * POS_STATES_MAX],
+            literal: vec![PROB_INIT; 0x300 << (lc + lp)],
+            pos_slot: [[PROB_INIT; 1 << 6]; LEN_TO_POS_STATES],
+            pos_special: [PROB_INIT; 1 + NUM_FULL_DISTANCES - END_POS_MODEL_INDEX],
+            align: [PROB_INIT; 1 << NUM_ALIGN_BITS],
+            len: LenDecoder::new(),
+            rep_len: LenDecoder::new(),
+        })
+    }
+
+    /// Resets the state and the probabilities, keeping the properties.
+    fn reset(&mut self) {
+        let props = (self.pb * 5 + self.lp) * 9 + self.lc;
+        *self = Self::new(props as u8).unwrap();
+    }
+
+    /// Decodes until the window is full.
+    fn decode(&mut self, rc: &mut RangeDecoder, window: &mut Window) -> Result<()> {
+        let pb_mask = (1 << self.pb) - 1;
+        while !window.is_full() {
+            let pos_state = window.total_pos() & pb_mask;
+            let state_index = self.state * POS_STATES_MAX + pos_state;
+
+            if rc.bit(&mut self.is_match[state_index])? == 0 {
+                self.decode_literal(rc, window)?;
+                self.state = match self.state {
+                    0..=3 => 0,
+                    4..=9 => self.state - 3,
+                    _ => self.state - 6,
+                };
+                continue;
+            }
+
+            let len = if rc.bit(&mut self.is_rep[self.state])? == 1 {
+                if window.total_pos() == 0 {
+                    return_errno_with_message!(Errno::EIO, "LZMA repeated match at the start");
+                }
+                if rc.bit(&mut self.is_rep_g0[self.state])? == 0 {
+                    if rc.bit(&mut self.is_rep0_long[state_index])? == 0 {
+                        // A short repeated match of one byte
+                        self.state = if self.state < 7 { 9 } else { 11 };
+                        window.copy_match(self.reps[0], 1)?;
+                        continue;
+                    }
+                } else {
+                    let dist = if rc.bit(&mut self.is_rep_g1[self.state])? == 0 {
+                        self.reps[1]
+                    } else {
+                        let dist = if rc.bit(&mut self.is_rep_g2[self.state])? == 0 {
+                            self.reps[2]
+                        } else {
+                            let dist = self.reps[3];
+                            self.reps[3] = self.reps[2];
+                            dist
+                        };
+                        self.reps[2] = self.reps[1];
+                        dist
+                    };
+                    self.reps[1] = self.reps[0];
+                    self.reps[0] = dist;
+                }
+                self.state = if self.state < 7 { 8 } else { 11 };
+                self.rep_len.decode(rc, pos_state)?
+            } else {
+                self.reps.copy_within(0..3, 1);
+                let len = self.len.decode(rc, pos_state)?;
+                self.state = if self.state < 7 { 7 } else { 10 };
+                self.reps[0] = self.decode_distance(rc, len)?;
+                len
+            };
+            window.copy_match(self.reps[0], len + MATCH_MIN_LEN)?;
+        }
+        Ok(())
+    }
+
+    fn decode_literal(&mut self, rc: &mut RangeDecoder, window: &mut Window) -> Result<()> {
+        let prev_byte = if window.total_pos() > 0 {
+            window.get(0) as usize
+        } else {
+            0
+        };
+        let lit_state =
+            ((window.total_pos() & ((1 << self.lp) - 1)) << self.lc) + (prev_byte >> (8 - self.lc));
+        let probs = &mut self.literal[0x300 * lit_state..0x300 * (lit_state + 1)];
+
+        let mut symbol = 1;
+        if self.state >= 7 {
+            // A literal after a match is decoded with the byte at the last distance.
+            if self.reps[0] >= window.total_pos() {
+                return_errno_with_message!(Errno::EIO, "invalid LZMA match distance");
+            }
+            let mut match_byte = window.get(self.reps[0]) as usize;
+            while symbol < 0x100 {
+                let match_bit = (match_byte >> 7) & 1;
+                match_byte <<= 1;
+                let bit = rc.bit(&mut probs[((1 + match_bit) << 8) + symbol])?;
+                symbol = (symbol << 1) | bit;
+                if match_bit != bit {
+                    break;
+                }
+            }
+        }
+        while symbol < 0x100 {
+            symbol = (symbol << 1) | rc.bit(&mut probs[symbol])?;
+        }
+        window.put((symbol - 0x100) as u8);
+        Ok(())
+    }
+
+    fn decode_distance(&mut self, rc: &mut RangeDecoder, len: usize) -> Result<usize> {
+        let len_state = len.min(LEN_TO_POS_STATES - 1);
+        let pos_slot = rc.bit_tree(&mut self.pos_slot[len_state], 6)?;
+        if pos_slot < 4 {
+            return Ok(pos_slot);
+        }
+
+        let num_direct_bits = (pos_slot >> 1) - 1;
+        let mut dist = (2 | (pos_slot & 1)) << num_direct_bits;
+        if pos_slot < END_POS_MODEL_INDEX {
+            let base = dist - pos_slot;
+            dist += rc.reverse_bit_tree(&mut self.pos_special[base..], num_direct_bits)?;
+        } else {
+            dist += rc.direct_bits(num_direct_bits - NUM_ALIGN_BITS)? << NUM_ALIGN_BITS;
+            dist += rc.reverse_bit_tree(&mut self.align, NUM_ALIGN_BITS)?;
+        }
+        Ok(dist)
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    fn from_hex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    /// `"squashfs " * 40 + "xz\n"` compressed by `xz --check=crc32`.
+    const CRC32_STREAM: &str =
+        "fd377a585a0000016922de360200210116000000742fe5a3e0016a00155d00399c4b\
+        0208682d1c392430218daf4956ca202200000000000092a219a800012deb0200000070fc6a8d3e300d8b02\
+        0000000001595a";
+    /// The offset of the CRC32 check of the block in `CRC32_STREAM`.
+    const CRC32_CHECK_OFFSET: usize = 56;
+
+    /// The bytes from 0 to 199 compressed by `xz --check=crc64`.
+    const CRC64_STREAM: &str = "fd377a585a000004e6d6b4460200210116000000742fe5a3e000c700ba5d000000\
+        52500a84f99bb28021a969d627e03e065a5f048d53d404ba39570509c15524de9db871593160a19ff96f49\
+        73f2c8ea8cba1a8b29692180fe338366af466dec9e898a0b83f03c0e898e3fed5fe79e90d91cff32f4b2e0\
+        3951b2d21415b4c571badb06e3799a9fbb38c1b000ac930baa0619031208155b9bc848f0322efe2da087c8\
+        f0a4e0d251eb8d675692b24d84c5f18631df6a625bc2792dd9f73c73ba747407d83ca9562224a166f85a84\
+        5f3067d2f64b492e77aba8f80000009c8185b8d77f33870001d601c801000081ae6988b1c467fb02000000\
+        0004595a";
+
+    #[ktest]
+    fn decompress_crc32_stream() {
+        let mut expected = b"squashfs ".repeat(40);
+        expected.extend_from_slice(b"xz\n");
+        let mut dst = vec![0u8; 1024];
+        let len = decompress(&from_hex(CRC32_STREAM), &mut dst).unwrap();
+        assert_eq!(dst[..len], expected);
+    }
+
+    #[ktest]
+    fn decompress_crc64_stream() {
+        let expected: Vec<u8> = (0..200).collect();
+        let mut dst = vec![0u8; 1024];
+        let len = decompress(&from_hex(CRC64_STREAM), &mut dst).unwrap();
+        assert_eq!(dst[..len], expected);
+    }
+
+    #[ktest]
+    fn reject_corrupted_streams() {
+        let stream = from_hex(CRC32_STREAM);
+        let mut dst = vec![0u8; 1024];
+
+        let mut corrupted = stream.clone();
+        corrupted[CRC32_CHECK_OFFSET] ^= 1;
+        assert!(decompress(&corrupted, &mut dst).is_err());
+
+        // The padding of the block header
+        let mut corrupted = stream.clone();
+        corrupted[STREAM_HEADER_LEN + 6] ^= 1;
+        assert!(decompress(&corrupted, &mut dst).is_err());
+
+        // The check type in the stream header
+        let mut corrupted = stream;
+        corrupted[7] = CHECK_CRC64;
+        assert!(decompress(&corrupted, &mut dst).is_err());
+    }
+}
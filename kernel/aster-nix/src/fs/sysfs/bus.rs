@@ -0,0 +1,372 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The devices on the PCI bus and the platform (MMIO) bus, and the buses themselves,
+//! i.e., `/sys/devices/pci0000:00`, `/sys/devices/platform` and `/sys/bus`.
+
+use alloc::format;
+use core::fmt::Write;
+
+use ostd::bus::{
+    mmio::{bus::MmioDeviceInfo, MMIO_BUS},
+    pci::{bus::PciDeviceInfo, PciDeviceLocation, PCI_BUS},
+};
+
+use super::{Attribute, KObject};
+use crate::prelude::*;
+
+/// The path of the PCI devices.
+const PCI_DEVICES_PATH: &str = "/devices/pci0000:00";
+/// The path of the platform devices.
+const PLATFORM_DEVICES_PATH: &str = "/devices/platform";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Bus {
+    Pci,
+    Platform,
+}
+
+impl Bus {
+    pub const ALL: [Bus; 2] = [Bus::Pci, Bus::Platform];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Bus::Pci => "pci",
+            Bus::Platform => "platform",
+        }
+    }
+
+    /// Returns the names of the devices on the bus, with the names of their drivers.
+    fn devices(self) -> Vec<(String, Option<&'static str>)> {
+        match self {
+            Bus::Pci => PCI_BUS
+                .lock()
+                .devices()
+                .into_iter()
+                .map(|info| (pci_slot_name(&info.location), pci_driver_name(&info)))
+                .collect(),
+            Bus::Platform => MMIO_BUS
+                .lock()
+                .devices()
+                .into_iter()
+                .map(|info| (platform_device_name(&info), platform_driver_name(&info)))
+                .collect(),
+        }
+    }
+
+    /// Returns the path of the device with the name.
+    fn device_path(self, name: &str) -> String {
+        match self {
+            Bus::Pci => format!("{}/{}", PCI_DEVICES_PATH, name),
+            Bus::Platform => format!("{}/{}", PLATFORM_DEVICES_PATH, name),
+        }
+    }
+
+    /// Returns the names of the drivers that claim devices on the bus.
+    fn drivers(self) -> Vec<&'static str> {
+        let mut drivers: Vec<_> = self
+            .devices()
+            .into_iter()
+            .filter_map(|(_, driver)| driver)
+            .collect();
+        drivers.sort_unstable();
+        drivers.dedup();
+        drivers
+    }
+}
+
+/// Returns the objects at `/sys/devices/pci0000:00/*`.
+pub(super) fn pci_devices(parent: Arc<dyn KObject>) -> Vec<Arc<dyn KObject>> {
+    PCI_BUS
+        .lock()
+        .devices()
+        .into_iter()
+        .map(|info| {
+            Arc::new(PciDeviceObject {
+                info,
+                parent: parent.clone(),
+            }) as _
+        })
+        .collect()
+}
+
+/// Returns the objects at `/sys/devices/platform/*`.
+pub(super) fn platform_devices(parent: Arc<dyn KObject>) -> Vec<Arc<dyn KObject>> {
+    MMIO_BUS
+        .lock()
+        .devices()
+        .into_iter()
+        .map(|info| {
+            Arc::new(PlatformDeviceObject {
+                info,
+                parent: parent.clone(),
+            }) as _
+        })
+        .collect()
+}
+
+/// Returns the objects at `/sys/bus/*`.
+pub(super) fn buses(parent: Arc<dyn KObject>) -> Vec<Arc<dyn KObject>> {
+    Bus::ALL
+        .into_iter()
+        .map(|bus| {
+            Arc::new(BusObject {
+                bus,
+                parent: parent.clone(),
+            }) as _
+        })
+        .collect()
+}
+
+/// Returns the name of the PCI device, e.g., `0000:00:04.0`.
+fn pci_slot_name(location: &PciDeviceLocation) -> String {
+    format!(
+        "0000:{:02x}:{:02x}.{:x}",
+        location.bus, location.device, location.function
+    )
+}
+
+fn pci_driver_name(info: &PciDeviceInfo) -> Option<&'static str> {
+    info.driver.as_ref().map(|driver| driver.name())
+}
+
+/// Returns the name of the platform device, e.g., `feb00000.virtio_mmio`.
+fn platform_device_name(info: &MmioDeviceInfo) -> String {
+    format!("{:x}.virtio_mmio", info.address)
+}
+
+fn platform_driver_name(info: &MmioDeviceInfo) -> Option<&'static str> {
+    info.driver.as_ref().map(|driver| driver.name())
+}
+
+/// Represents the object at `/sys/devices/pci0000:00/<slot>`.
+struct PciDeviceObject {
+    info: PciDeviceInfo,
+    parent: Arc<dyn KObject>,
+}
+
+impl KObject for PciDeviceObject {
+    fn name(&self) -> String {
+        pci_slot_name(&self.info.location)
+    }
+
+    fn parent(&self) -> Option<Arc<dyn KObject>> {
+        Some(self.parent.clone())
+    }
+
+    fn attributes(&self) -> Vec<Attribute> {
+        let id = self.info.device_id;
+        let class = (id.class as u32) << 16 | (id.subclass as u32) << 8 | id.prog_if as u32;
+
+        let mut uevent = String::new();
+        if let Some(driver) = pci_driver_name(&self.info) {
+            let _ = writeln!(uevent, "DRIVER={}", driver);
+        }
+        let _ = writeln!(uevent, "PCI_CLASS={:X}", class);
+        let _ = writeln!(uevent, "PCI_ID={:04X}:{:04X}", id.vendor_id, id.device_id);
+        let _ = writeln!(
+            uevent,
+            "PCI_SUBSYS_ID={:04X}:{:04X}",
+            id.subsystem_vendor_id, id.subsystem_id
+        );
+        let _ = writeln!(uevent, "PCI_SLOT_NAME={}", self.name());
+        let modalias = format!(
+            "pci:v{:08X}d{:08X}sv{:08X}sd{:08X}bc{:02X}sc{:02X}i{:02X}",
+            id.vendor_id,
+            id.device_id,
+            id.subsystem_vendor_id,
+            id.subsystem_id,
+            id.class,
+            id.subclass,
+            id.prog_if
+        );
+        let _ = writeln!(uevent, "MODALIAS={}", modalias);
+
+        vec![
+            Attribute::new_const("vendor", format!("{:#06x}", id.vendor_id)),
+            Attribute::new_const("device", format!("{:#06x}", id.device_id)),
+            Attribute::new_const(
+                "subsystem_vendor",
+                format!("{:#06x}", id.subsystem_vendor_id),
+            ),
+            Attribute::new_const("subsystem_device", format!("{:#06x}", id.subsystem_id)),
+            Attribute::new_const("class", format!("{:#08x}", class)),
+            Attribute::new_const("revision", format!("{:#04x}", id.revision_id)),
+            Attribute::new_const("modalias", modalias),
+            Attribute::new_const("uevent", uevent),
+        ]
+    }
+
+    fn links(&self) -> Vec<(String, String)> {
+        let mut links = vec![(String::from("subsystem"), String::from("/bus/pci"))];
+        if let Some(driver) = pci_driver_name(&self.info) {
+            links.push((
+                String::from("driver"),
+                format!("/bus/pci/drivers/{}", driver),
+            ));
+        }
+        links
+    }
+}
+
+/// Represents the object at `/sys/devices/platform/<address>.virtio_mmio`.
+struct PlatformDeviceObject {
+    info: MmioDeviceInfo,
+    parent: Arc<dyn KObject>,
+}
+
+impl KObject for PlatformDeviceObject {
+    fn name(&self) -> String {
+        platform_device_name(&self.info)
+    }
+
+    fn parent(&self) -> Option<Arc<dyn KObject>> {
+        Some(self.parent.clone())
+    }
+
+    fn attributes(&self) -> Vec<Attribute> {
+        let mut uevent = String::new();
+        if let Some(driver) = platform_driver_name(&self.info) {
+            let _ = writeln!(uevent, "DRIVER={}", driver);
+        }
+        let _ = writeln!(uevent, "MODALIAS=platform:virtio-mmio");
+
+        vec![
+            // The ID of the virtio device type, e.g., 2 for block devices.
+            Attribute::new_const("device", format!("{:#06x}", self.info.device_id)),
+            Attribute::new_const("modalias", "platform:virtio-mmio"),
+            Attribute::new_const("uevent", uevent),
+        ]
+    }
+
+    fn links(&self) -> Vec<(String, String)> {
+        let mut links = vec![(String::from("subsystem"), String::from("/bus/platform"))];
+        if let Some(driver) = platform_driver_name(&self.info) {
+            links.push((
+                String::from("driver"),
+                format!("/bus/platform/drivers/{}", driver),
+            ));
+        }
+        links
+    }
+}
+
+/// Represents the object at `/sys/bus/<bus>`.
+struct BusObject {
+    bus: Bus,
+    parent: Arc<dyn KObject>,
+}
+
+impl KObject for BusObject {
+    fn name(&self) -> String {
+        String::from(self.bus.name())
+    }
+
+    fn parent(&self) -> Option<Arc<dyn KObject>> {
+        Some(self.parent.clone())
+    }
+
+    fn children(self: Arc<Self>) -> Vec<Arc<dyn KObject>> {
+        let bus = self.bus;
+        vec![
+            Arc::new(BusDevicesObject {
+                bus,
+                parent: self.clone(),
+            }) as _,
+            Arc::new(BusDriversObject { bus, parent: self }) as _,
+        ]
+    }
+}
+
+/// Represents the object at `/sys/bus/<bus>/devices`, which links to the devices on the bus.
+struct BusDevicesObject {
+    bus: Bus,
+    parent: Arc<dyn KObject>,
+}
+
+impl KObject for BusDevicesObject {
+    fn name(&self) -> String {
+        String::from("devices")
+    }
+
+    fn parent(&self) -> Option<Arc<dyn KObject>> {
+        Some(self.parent.clone())
+    }
+
+    fn links(&self) -> Vec<(String, String)> {
+        self.bus
+            .devices()
+            .into_iter()
+            .map(|(name, _)| {
+                let path = self.bus.device_path(&name);
+                (name, path)
+            })
+            .collect()
+    }
+}
+
+/// Represents the object at `/sys/bus/<bus>/drivers`.
+struct BusDriversObject {
+    bus: Bus,
+    parent: Arc<dyn KObject>,
+}
+
+impl KObject for BusDriversObject {
+    fn name(&self) -> String {
+        String::from("drivers")
+    }
+
+    fn parent(&self) -> Option<Arc<dyn KObject>> {
+        Some(self.parent.clone())
+    }
+
+    fn children(self: Arc<Self>) -> Vec<Arc<dyn KObject>> {
+        self.bus
+            .drivers()
+            .into_iter()
+            .map(|name| {
+                Arc::new(DriverObject {
+                    bus: self.bus,
+                    name,
+                    parent: self.clone(),
+                }) as _
+            })
+            .collect()
+    }
+}
+
+/// Represents the object at `/sys/bus/<bus>/drivers/<driver>`, which links to
+/// the devices bound to the driver.
+struct DriverObject {
+    bus: Bus,
+    name: &'static str,
+    parent: Arc<dyn KObject>,
+}
+
+impl KObject for DriverObject {
+    fn name(&self) -> String {
+        String::from(self.name)
+    }
+
+    fn parent(&self) -> Option<Arc<dyn KObject>> {
+        Some(self.parent.clone())
+    }
+
+    fn attributes(&self) -> Vec<Attribute> {
+        vec![Attribute::new_const(
+            "uevent",
+            format!("DRIVER={}\n", self.name),
+        )]
+    }
+
+    fn links(&self) -> Vec<(String, String)> {
+        self.bus
+            .devices()
+            .into_iter()
+            .filter(|(_, driver)| *driver == Some(self.name))
+            .map(|(name, _)| {
+                let path = self.bus.device_path(&name);
+                (name, path)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,594 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The devices registered in the device components and the device registry, which are
+//! grouped by their classes, i.e., `/sys/devices/virtual`, `/sys/class`, `/sys/block`
+//! and `/sys/dev`.
+//!
+//! The devices are not associated with the devices on the buses, since the components
+//! do not record which bus device a registered device is probed from. So they are
+//! shown as virtual devices.
+
+use alloc::format;
+use core::fmt::Write;
+
+use aster_block::{partition::Partition, scheduler::SCHEDULER_NAMES, BlockDevice, SECTOR_SIZE};
+use aster_network::AnyNetworkDevice;
+
+use super::{Attribute, KObject};
+use crate::{
+    device::{
+        block,
+        registry::{self, RegisteredDevice},
+    },
+    fs::device::{DeviceId, DeviceType},
+    prelude::*,
+};
+
+/// The path of the virtual devices.
+const VIRTUAL_DEVICES_PATH: &str = "/devices/virtual";
+
+/// The ARP hardware type of Ethernet, see `ARPHRD_ETHER` in Linux.
+const ARPHRD_ETHER: u16 = 1;
+/// The length of the Ethernet header, which is not counted in the MTU.
+const ETHERNET_HEADER_LEN: usize = 14;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Class {
+    Block,
+    Net,
+    Input,
+    Console,
+    /// The character devices in the device registry of a major, e.g., `mem` for `/dev/null`.
+    Char(&'static str),
+}
+
+impl Class {
+    /// Returns the classes, including those of the registered character devices.
+    fn all() -> Vec<Class> {
+        let mut classes = vec![Class::Block, Class::Net, Class::Input, Class::Console];
+        for device in registry::all_devices(DeviceType::CharDevice) {
+            let class = Class::Char(device.major_name());
+            if !classes.iter().any(|other| other.name() == class.name()) {
+                classes.push(class);
+            }
+        }
+        classes
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Class::Block => "block",
+            Class::Net => "net",
+            Class::Input => "input",
+            Class::Console => "console",
+            Class::Char(name) => name,
+        }
+    }
+
+    /// Returns the names of the devices of the class, with their paths.
+    fn device_paths(self) -> Vec<(String, String)> {
+        let names = match self {
+            // The partitions are in the directories of their disks.
+            Class::Block => {
+                return registry::all_devices(DeviceType::BlockDevice)
+                    .into_iter()
+                    .map(|device| (String::from(device.name()), device_path(&device)))
+                    .collect();
+            }
+            Class::Char(_) => {
+                return self
+                    .char_devices()
+                    .into_iter()
+                    .map(|device| (String::from(node_name(&device)), device_path(&device)))
+                    .collect();
+            }
+            Class::Net => names_of(aster_network::all_devices()),
+            Class::Input => names_of(aster_input::all_devices()),
+            Class::Console => names_of(aster_console::all_devices()),
+        };
+        names
+            .into_iter()
+            .map(|name| {
+                let path = format!("{}/{}/{}", VIRTUAL_DEVICES_PATH, self.name(), name);
+                (name, path)
+            })
+            .collect()
+    }
+
+    /// Returns the objects of the devices, except the partitions, which are the
+    /// children of their disks.
+    fn devices(self, parent: Arc<dyn KObject>) -> Vec<Arc<dyn KObject>> {
+        match self {
+            Class::Block => registry::all_devices(DeviceType::BlockDevice)
+                .into_iter()
+                .filter_map(|device| BlockObject::new(&device, parent.clone()))
+                .filter(|object| object.device.downcast_ref::<Partition>().is_none())
+                .map(|object| object as _)
+                .collect(),
+            Class::Net => aster_network::all_devices()
+                .into_iter()
+                .map(|(name, device)| {
+                    Arc::new(NetObject {
+                        name,
+                        device,
+                        parent: parent.clone(),
+                    }) as _
+                })
+                .collect(),
+            Class::Input => aster_input::all_devices()
+                .into_iter()
+                .map(|(name, _)| NamedDeviceObject::new(Class::Input, name, parent.clone()) as _)
+                .collect(),
+            Class::Console => aster_console::all_devices()
+                .into_iter()
+                .map(|(name, _)| NamedDeviceObject::new(Class::Console, name, parent.clone()) as _)
+                .collect(),
+            Class::Char(_) => self
+                .char_devices()
+                .into_iter()
+                .map(|device| {
+                    Arc::new(CharDeviceObject {
+                        device,
+                        parent: parent.clone(),
+                    }) as _
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns the registered character devices of the class.
+    fn char_devices(self) -> Vec<RegisteredDevice> {
+        registry::all_devices(DeviceType::CharDevice)
+            .into_iter()
+            .filter(|device| device.major_name() == self.name())
+            .collect()
+    }
+}
+
+/// Returns the objects at `/sys/devices/virtual/*`.
+pub(super) fn virtual_device_classes(parent: Arc<dyn KObject>) -> Vec<Arc<dyn KObject>> {
+    Class::all()
+        .into_iter()
+        .map(|class| {
+            Arc::new(VirtualClassObject {
+                class,
+                parent: parent.clone(),
+            }) as _
+        })
+        .collect()
+}
+
+/// Returns the objects at `/sys/class/*`.
+pub(super) fn classes(parent: Arc<dyn KObject>) -> Vec<Arc<dyn KObject>> {
+    Class::all()
+        .into_iter()
+        .map(|class| {
+            Arc::new(ClassObject {
+                class,
+                parent: parent.clone(),
+            }) as _
+        })
+        .collect()
+}
+
+/// Returns the links at `/sys/block/*`, which refer to the disks.
+pub(super) fn disk_links() -> Vec<(String, String)> {
+    registry::all_devices(DeviceType::BlockDevice)
+        .into_iter()
+        .filter(|device| {
+            block_device_of(device)
+                .is_some_and(|device| device.downcast_ref::<Partition>().is_none())
+        })
+        .map(|device| (String::from(device.name()), device_path(&device)))
+        .collect()
+}
+
+/// Returns the links at `/sys/dev/char/*`, which are named by the device numbers,
+/// e.g., `1:3` for `/dev/null`.
+pub(super) fn char_device_number_links() -> Vec<(String, String)> {
+    device_number_links(DeviceType::CharDevice)
+}
+
+/// Returns the links at `/sys/dev/block/*`, which are named by the device numbers.
+pub(super) fn block_device_number_links() -> Vec<(String, String)> {
+    device_number_links(DeviceType::BlockDevice)
+}
+
+fn device_number_links(type_: DeviceType) -> Vec<(String, String)> {
+    registry::all_devices(type_)
+        .into_iter()
+        .map(|device| {
+            let id = device.device().id();
+            (
+                format!("{}:{}", id.major(), id.minor()),
+                device_path(&device),
+            )
+        })
+        .collect()
+}
+
+/// Returns the path of the registered device in the sysfs, which is the `DEVPATH`
+/// of its uevents, e.g., `/devices/virtual/mem/null` or `/devices/virtual/block/vda/vda1`.
+pub(super) fn device_path(device: &RegisteredDevice) -> String {
+    if !device.is_block_device() {
+        return format!(
+            "{}/{}/{}",
+            VIRTUAL_DEVICES_PATH,
+            device.major_name(),
+            node_name(device)
+        );
+    }
+
+    let disks_path = format!("{}/block", VIRTUAL_DEVICES_PATH);
+    match block_device_of(device).and_then(|device| disk_of(&device)) {
+        Some(disk_name) => format!("{}/{}/{}", disks_path, disk_name, device.name()),
+        None => format!("{}/{}", disks_path, device.name()),
+    }
+}
+
+/// Returns the subsystem of the registered device, which is its class.
+pub(super) fn subsystem_of(device: &RegisteredDevice) -> &'static str {
+    if device.is_block_device() {
+        Class::Block.name()
+    } else {
+        device.major_name()
+    }
+}
+
+/// Returns the environment variables of the registered device, one per line, which are
+/// shown in its `uevent` attribute and sent in its uevents.
+pub(super) fn device_uevent_vars(device: &RegisteredDevice) -> String {
+    let id = device.device().id();
+    match block_device_of(device) {
+        Some(block_device) => block_uevent_vars(device.name(), id, &block_device),
+        None => char_uevent_vars(device.name(), id),
+    }
+}
+
+fn names_of<T>(devices: Vec<(String, T)>) -> Vec<String> {
+    devices.into_iter().map(|(name, _)| name).collect()
+}
+
+/// Returns the name of the device node without its directories, which names the
+/// object of the device, e.g., `control` for `/dev/mapper/control`.
+fn node_name(device: &RegisteredDevice) -> &str {
+    device.name().rsplit('/').next().unwrap()
+}
+
+/// Returns the underlying block device of the registered device, or `None` if it is
+/// not a block device.
+fn block_device_of(device: &RegisteredDevice) -> Option<Arc<dyn BlockDevice>> {
+    if !device.is_block_device() {
+        return None;
+    }
+    let node = block::get_device(device.device().id())?;
+    Some(node.block_device().clone())
+}
+
+/// Returns the name of the disk that the partition is on, or `None` if the device
+/// is not a partition.
+fn disk_of(device: &Arc<dyn BlockDevice>) -> Option<String> {
+    let partition = device.downcast_ref::<Partition>()?;
+    registry::all_devices(DeviceType::BlockDevice)
+        .into_iter()
+        .find(|disk| {
+            block_device_of(disk).is_some_and(|disk| Arc::ptr_eq(&disk, partition.device()))
+        })
+        .map(|disk| String::from(disk.name()))
+}
+
+fn block_uevent_vars(name: &str, id: DeviceId, device: &Arc<dyn BlockDevice>) -> String {
+    let partition = device.downcast_ref::<Partition>();
+
+    let mut vars = String::new();
+    let _ = writeln!(vars, "MAJOR={}", id.major());
+    let _ = writeln!(vars, "MINOR={}", id.minor());
+    let _ = writeln!(vars, "DEVNAME={}", name);
+    let devtype = if partition.is_some() {
+        "partition"
+    } else {
+        "disk"
+    };
+    let _ = writeln!(vars, "DEVTYPE={}", devtype);
+    if let Some(partition) = partition {
+        let _ = writeln!(vars, "PARTN={}", partition.info().number);
+    }
+    vars
+}
+
+fn char_uevent_vars(name: &str, id: DeviceId) -> String {
+    let mut vars = String::new();
+    let _ = writeln!(vars, "MAJOR={}", id.major());
+    let _ = writeln!(vars, "MINOR={}", id.minor());
+    let _ = writeln!(vars, "DEVNAME={}", name);
+    vars
+}
+
+/// Represents the object at `/sys/devices/virtual/<class>`.
+struct VirtualClassObject {
+    class: Class,
+    parent: Arc<dyn KObject>,
+}
+
+impl KObject for VirtualClassObject {
+    fn name(&self) -> String {
+        String::from(self.class.name())
+    }
+
+    fn parent(&self) -> Option<Arc<dyn KObject>> {
+        Some(self.parent.clone())
+    }
+
+    fn children(self: Arc<Self>) -> Vec<Arc<dyn KObject>> {
+        self.class.devices(self.clone())
+    }
+}
+
+/// Represents the object at `/sys/class/<class>`, which links to the devices of the class.
+struct ClassObject {
+    class: Class,
+    parent: Arc<dyn KObject>,
+}
+
+impl KObject for ClassObject {
+    fn name(&self) -> String {
+        String::from(self.class.name())
+    }
+
+    fn parent(&self) -> Option<Arc<dyn KObject>> {
+        Some(self.parent.clone())
+    }
+
+    fn links(&self) -> Vec<(String, String)> {
+        self.class.device_paths()
+    }
+}
+
+/// Represents the object of a disk or a partition, e.g., `/sys/devices/virtual/block/vda`
+/// and `/sys/devices/virtual/block/vda/vda1`.
+struct BlockObject {
+    name: String,
+    id: DeviceId,
+    device: Arc<dyn BlockDevice>,
+    parent: Arc<dyn KObject>,
+}
+
+impl BlockObject {
+    fn new(registered: &RegisteredDevice, parent: Arc<dyn KObject>) -> Option<Arc<Self>> {
+        Some(Arc::new(Self {
+            name: String::from(registered.name()),
+            id: registered.device().id(),
+            device: block_device_of(registered)?,
+            parent,
+        }))
+    }
+}
+
+impl KObject for BlockObject {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn parent(&self) -> Option<Arc<dyn KObject>> {
+        Some(self.parent.clone())
+    }
+
+    fn children(self: Arc<Self>) -> Vec<Arc<dyn KObject>> {
+        // A partition has neither a request queue nor partitions.
+        if self.device.downcast_ref::<Partition>().is_some() {
+            return Vec::new();
+        }
+
+        let mut children: Vec<Arc<dyn KObject>> = vec![Arc::new(QueueObject {
+            device: self.device.clone(),
+            parent: self.clone(),
+        })];
+        let partitions = registry::all_devices(DeviceType::BlockDevice)
+            .into_iter()
+            .filter_map(|device| BlockObject::new(&device, self.clone()))
+            .filter(|object| {
+                object
+                    .device
+                    .downcast_ref::<Partition>()
+                    .is_some_and(|partition| Arc::ptr_eq(partition.device(), &self.device))
+            });
+        for partition in partitions {
+            children.push(partition);
+        }
+        children
+    }
+
+    fn attributes(&self) -> Vec<Attribute> {
+        let partition = self.device.downcast_ref::<Partition>();
+        let uevent = block_uevent_vars(&self.name, self.id, &self.device);
+
+        let device = self.device.clone();
+        let mut attrs = vec![
+            Attribute::new_ro("size", move || Ok(device.nr_sectors().to_string())),
+            Attribute::new_const("ro", 0),
+            Attribute::new_const("removable", 0),
+            Attribute::new_const("dev", format!("{}:{}", self.id.major(), self.id.minor())),
+            Attribute::new_const("uevent", uevent),
+        ];
+        if let Some(partition) = partition {
+            let info = partition.info();
+            attrs.push(Attribute::new_const("partition", info.number));
+            attrs.push(Attribute::new_const("start", info.start_sid.to_raw()));
+        }
+        attrs
+    }
+
+    fn links(&self) -> Vec<(String, String)> {
+        vec![(String::from("subsystem"), String::from("/class/block"))]
+    }
+}
+
+/// Represents the object at `/sys/devices/virtual/block/<disk>/queue`.
+struct QueueObject {
+    device: Arc<dyn BlockDevice>,
+    parent: Arc<dyn KObject>,
+}
+
+impl KObject for QueueObject {
+    fn name(&self) -> String {
+        String::from("queue")
+    }
+
+    fn parent(&self) -> Option<Arc<dyn KObject>> {
+        Some(self.parent.clone())
+    }
+
+    fn attributes(&self) -> Vec<Attribute> {
+        let show_device = self.device.clone();
+        let store_device = self.device.clone();
+        vec![
+            // The current scheduler is shown in brackets, e.g., `none [mq-deadline] budget-fair`.
+            Attribute::new_rw(
+                "scheduler",
+                move || {
+                    let Some(current) = show_device.io_scheduler() else {
+                        return Ok(String::from("none"));
+                    };
+                    let names: Vec<_> = SCHEDULER_NAMES
+                        .iter()
+                        .map(|name| {
+                            if *name == current {
+                                format!("[{}]", name)
+                            } else {
+                                String::from(*name)
+                            }
+                        })
+                        .collect();
+                    Ok(names.join(" "))
+                },
+                move |name| Ok(store_device.set_io_scheduler(name.trim())?),
+            ),
+            Attribute::new_const("logical_block_size", SECTOR_SIZE),
+            Attribute::new_const("physical_block_size", SECTOR_SIZE),
+            Attribute::new_const("hw_sector_size", SECTOR_SIZE),
+            Attribute::new_const("max_segments", self.device.max_nr_segments_per_bio()),
+            Attribute::new_const("rotational", 0),
+        ]
+    }
+}
+
+/// Represents the object of a network device, e.g., `/sys/devices/virtual/net/Virtio-Net`.
+struct NetObject {
+    name: String,
+    device: Arc<SpinLock<dyn AnyNetworkDevice>>,
+    parent: Arc<dyn KObject>,
+}
+
+impl KObject for NetObject {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn parent(&self) -> Option<Arc<dyn KObject>> {
+        Some(self.parent.clone())
+    }
+
+    fn attributes(&self) -> Vec<Attribute> {
+        let (mac_addr, capabilities) = {
+            let device = self.device.lock();
+            (device.mac_addr(), device.capabilities())
+        };
+        let address = mac_addr
+            .0
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<Vec<_>>()
+            .join(":");
+        let mtu = capabilities
+            .max_transmission_unit
+            .saturating_sub(ETHERNET_HEADER_LEN);
+
+        vec![
+            Attribute::new_const("address", address),
+            Attribute::new_const("addr_len", mac_addr.0.len()),
+            Attribute::new_const("mtu", mtu),
+            Attribute::new_const("type", ARPHRD_ETHER),
+            // TODO: Report the link state once the devices can tell it.
+            Attribute::new_const("operstate", "up"),
+            Attribute::new_const("carrier", 1),
+            Attribute::new_const("uevent", format!("INTERFACE={}\n", self.name)),
+        ]
+    }
+
+    fn links(&self) -> Vec<(String, String)> {
+        vec![(String::from("subsystem"), String::from("/class/net"))]
+    }
+}
+
+/// Represents the object of a device that only has a name, i.e., an input device
+/// or a console device.
+struct NamedDeviceObject {
+    class: Class,
+    name: String,
+    parent: Arc<dyn KObject>,
+}
+
+impl NamedDeviceObject {
+    fn new(class: Class, name: String, parent: Arc<dyn KObject>) -> Arc<Self> {
+        Arc::new(Self {
+            class,
+            name,
+            parent,
+        })
+    }
+}
+
+impl KObject for NamedDeviceObject {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn parent(&self) -> Option<Arc<dyn KObject>> {
+        Some(self.parent.clone())
+    }
+
+    fn attributes(&self) -> Vec<Attribute> {
+        vec![
+            Attribute::new_const("name", &self.name),
+            Attribute::new_const("uevent", format!("NAME=\"{}\"\n", self.name)),
+        ]
+    }
+
+    fn links(&self) -> Vec<(String, String)> {
+        vec![(
+            String::from("subsystem"),
+            format!("/class/{}", self.class.name()),
+        )]
+    }
+}
+
+/// Represents the object of a registered character device, e.g., `/sys/devices/virtual/mem/null`.
+struct CharDeviceObject {
+    device: RegisteredDevice,
+    parent: Arc<dyn KObject>,
+}
+
+impl KObject for CharDeviceObject {
+    fn name(&self) -> String {
+        String::from(node_name(&self.device))
+    }
+
+    fn parent(&self) -> Option<Arc<dyn KObject>> {
+        Some(self.parent.clone())
+    }
+
+    fn attributes(&self) -> Vec<Attribute> {
+        let id = self.device.device().id();
+        vec![
+            Attribute::new_const("dev", format!("{}:{}", id.major(), id.minor())),
+            Attribute::new_const("uevent", device_uevent_vars(&self.device)),
+        ]
+    }
+
+    fn links(&self) -> Vec<(String, String)> {
+        vec![(
+            String::from("subsystem"),
+            format!("/class/{}", self.device.major_name()),
+        )]
+    }
+}
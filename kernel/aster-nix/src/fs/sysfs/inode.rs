@@ -0,0 +1,346 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::format;
+use core::time::Duration;
+
+use super::{Attribute, KObject, SysFS, BLOCK_SIZE};
+use crate::{
+    fs::utils::{DirentVisitor, FileSystem, Inode, InodeMode, InodeType, IoctlCmd, Metadata},
+    prelude::*,
+    process::{Gid, Uid},
+};
+
+/// An inode of the sysfs, which is a kernel object, an attribute of it or a link.
+///
+/// The inodes are created on lookup and are not cached, so the tree always reflects
+/// the current kernel objects. The inode number is derived from the path, so that
+/// an object keeps its inode number across the lookups.
+pub(super) struct SysfsInode {
+    kind: SysfsInodeKind,
+    metadata: Metadata,
+    fs: Weak<SysFS>,
+}
+
+enum SysfsInodeKind {
+    Dir(Arc<dyn KObject>),
+    Attr(Attribute),
+    /// A symbolic link, with the relative path to the target.
+    Link(String),
+}
+
+impl SysfsInode {
+    pub fn new_dir(kobject: Arc<dyn KObject>, fs: Weak<SysFS>) -> Arc<Self> {
+        let metadata = Metadata::new_dir(
+            path_to_ino(&kobject.path()),
+            InodeMode::from_bits_truncate(0o755),
+            BLOCK_SIZE,
+        );
+        Arc::new(Self {
+            kind: SysfsInodeKind::Dir(kobject),
+            metadata,
+            fs,
+        })
+    }
+
+    fn new_attr(path: &str, attr: Attribute, fs: Weak<SysFS>) -> Arc<Self> {
+        let mode = if attr.is_writable() { 0o644 } else { 0o444 };
+        let mut metadata = Metadata::new_file(
+            path_to_ino(path),
+            InodeMode::from_bits_truncate(mode),
+            BLOCK_SIZE,
+        );
+        // The size of the content is unknown until it is read, so the size is reported
+        // as a page like Linux.
+        metadata.size = PAGE_SIZE;
+        Arc::new(Self {
+            kind: SysfsInodeKind::Attr(attr),
+            metadata,
+            fs,
+        })
+    }
+
+    fn new_link(path: &str, target: String, fs: Weak<SysFS>) -> Arc<Self> {
+        let mut metadata = Metadata::new_symlink(
+            path_to_ino(path),
+            InodeMode::from_bits_truncate(0o777),
+            BLOCK_SIZE,
+        );
+        metadata.size = target.len();
+        Arc::new(Self {
+            kind: SysfsInodeKind::Link(target),
+            metadata,
+            fs,
+        })
+    }
+
+    fn kobject(&self) -> Result<&Arc<dyn KObject>> {
+        match &self.kind {
+            SysfsInodeKind::Dir(kobject) => Ok(kobject),
+            _ => return_errno!(Errno::ENOTDIR),
+        }
+    }
+
+    fn attr(&self) -> Result<&Attribute> {
+        match &self.kind {
+            SysfsInodeKind::Attr(attr) => Ok(attr),
+            SysfsInodeKind::Dir(_) => return_errno!(Errno::EISDIR),
+            SysfsInodeKind::Link(_) => return_errno!(Errno::EINVAL),
+        }
+    }
+
+    /// Returns the child inodes of the directory, with their names.
+    fn children(&self) -> Result<Vec<(String, Arc<dyn Inode>)>> {
+        let kobject = self.kobject()?;
+        let dir_path = kobject.path();
+        let mut children: Vec<(String, Arc<dyn Inode>)> = Vec::new();
+        for child in kobject.clone().children() {
+            let inode = Self::new_dir(child.clone(), self.fs.clone());
+            children.push((child.name(), inode));
+        }
+        for attr in kobject.attributes() {
+            let path = format!("{}/{}", dir_path, attr.name());
+            let name = String::from(attr.name());
+            children.push((name, Self::new_attr(&path, attr, self.fs.clone())));
+        }
+        for (name, target) in kobject.links() {
+            let path = format!("{}/{}", dir_path, name);
+            let target = relative_path(&dir_path, &target);
+            children.push((name, Self::new_link(&path, target, self.fs.clone())));
+        }
+        Ok(children)
+    }
+}
+
+impl Inode for SysfsInode {
+    fn size(&self) -> usize {
+        self.metadata.size
+    }
+
+    fn resize(&self, _new_size: usize) -> Result<()> {
+        // Truncating an attribute file, e.g., by `echo > attr`, is a no-op.
+        self.attr().map(|_| ())
+    }
+
+    fn metadata(&self) -> Metadata {
+        self.metadata
+    }
+
+    fn ino(&self) -> u64 {
+        self.metadata.ino
+    }
+
+    fn type_(&self) -> InodeType {
+        self.metadata.type_
+    }
+
+    fn mode(&self) -> Result<InodeMode> {
+        Ok(self.metadata.mode)
+    }
+
+    fn set_mode(&self, _mode: InodeMode) -> Result<()> {
+        return_errno_with_message!(Errno::EPERM, "the mode of sysfs inodes cannot be changed")
+    }
+
+    fn owner(&self) -> Result<Uid> {
+        Ok(self.metadata.uid)
+    }
+
+    fn set_owner(&self, _uid: Uid) -> Result<()> {
+        return_errno_with_message!(Errno::EPERM, "the owner of sysfs inodes cannot be changed")
+    }
+
+    fn group(&self) -> Result<Gid> {
+        Ok(self.metadata.gid)
+    }
+
+    fn set_group(&self, _gid: Gid) -> Result<()> {
+        return_errno_with_message!(Errno::EPERM, "the group of sysfs inodes cannot be changed")
+    }
+
+    fn atime(&self) -> Duration {
+        self.metadata.atime
+    }
+
+    fn set_atime(&self, _time: Duration) {}
+
+    fn mtime(&self) -> Duration {
+        self.metadata.mtime
+    }
+
+    fn set_mtime(&self, _time: Duration) {}
+
+    fn ctime(&self) -> Duration {
+        self.metadata.ctime
+    }
+
+    fn set_ctime(&self, _time: Duration) {}
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        let content = self.attr()?.show()?;
+        let data = content.as_bytes();
+        let start = data.len().min(offset);
+        let end = data.len().min(offset + buf.len());
+        let len = end - start;
+        buf[0..len].copy_from_slice(&data[start..end]);
+        Ok(len)
+    }
+
+    fn read_direct_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        self.read_at(offset, buf)
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize> {
+        let attr = self.attr()?;
+        // An attribute is always stored as a whole.
+        if offset != 0 {
+            return_errno_with_message!(Errno::EINVAL, "the attribute must be written at offset 0");
+        }
+        let content = core::str::from_utf8(buf)
+            .map_err(|_| Error::with_message(Errno::EINVAL, "the content is not UTF-8"))?;
+        attr.store(content)?;
+        Ok(buf.len())
+    }
+
+    fn write_direct_at(&self, offset: usize, buf: &[u8]) -> Result<usize> {
+        self.write_at(offset, buf)
+    }
+
+    fn readdir_at(&self, offset: usize, visitor: &mut dyn DirentVisitor) -> Result<usize> {
+        let kobject = self.kobject()?;
+        let try_readdir = |offset: &mut usize, visitor: &mut dyn DirentVisitor| -> Result<()> {
+            // Read the two special entries.
+            if *offset == 0 {
+                visitor.visit(".", self.ino(), InodeType::Dir, *offset)?;
+                *offset += 1;
+            }
+            if *offset == 1 {
+                let parent_ino = kobject
+                    .parent()
+                    .map(|parent| path_to_ino(&parent.path()))
+                    .unwrap_or(self.ino());
+                visitor.visit("..", parent_ino, InodeType::Dir, *offset)?;
+                *offset += 1;
+            }
+
+            // Read the normal child entries.
+            let start_offset = *offset;
+            for (idx, (name, child)) in self
+                .children()?
+                .into_iter()
+                .enumerate()
+                .map(|(idx, child)| (idx + 2, child))
+                .skip_while(|(idx, _)| idx < &start_offset)
+            {
+                visitor.visit(&name, child.ino(), child.type_(), idx)?;
+                *offset = idx + 1;
+            }
+            Ok(())
+        };
+
+        let mut iterate_offset = offset;
+        match try_readdir(&mut iterate_offset, visitor) {
+            Err(e) if iterate_offset == offset => Err(e),
+            _ => Ok(iterate_offset - offset),
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>> {
+        let kobject = self.kobject()?;
+        let inode: Arc<dyn Inode> = match name {
+            "." => Self::new_dir(kobject.clone(), self.fs.clone()),
+            ".." => {
+                let parent = kobject.parent().unwrap_or_else(|| kobject.clone());
+                Self::new_dir(parent, self.fs.clone())
+            }
+            name => self
+                .children()?
+                .into_iter()
+                .find(|(child_name, _)| child_name == name)
+                .map(|(_, child)| child)
+                .ok_or_else(|| Error::new(Errno::ENOENT))?,
+        };
+        Ok(inode)
+    }
+
+    fn read_link(&self) -> Result<String> {
+        match &self.kind {
+            SysfsInodeKind::Link(target) => Ok(target.clone()),
+            _ => return_errno!(Errno::EINVAL),
+        }
+    }
+
+    fn write_link(&self, _target: &str) -> Result<()> {
+        return_errno!(Errno::EPERM)
+    }
+
+    fn ioctl(&self, _cmd: IoctlCmd, _arg: usize) -> Result<i32> {
+        return_errno!(Errno::ENOTTY)
+    }
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        self.fs.upgrade().unwrap()
+    }
+
+    fn is_dentry_cacheable(&self) -> bool {
+        match &self.kind {
+            SysfsInodeKind::Dir(kobject) => kobject.is_persistent(),
+            _ => false,
+        }
+    }
+}
+
+/// Returns the inode number of the path, which is hashed by FNV-1a.
+fn path_to_ino(path: &str) -> u64 {
+    const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+    if path.is_empty() {
+        return super::SYSFS_ROOT_INO;
+    }
+    let hash = path.bytes().fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+    });
+    // Avoid colliding with the root.
+    hash.max(super::SYSFS_ROOT_INO + 1)
+}
+
+/// Returns the path of the target relative to the directory, e.g., the target
+/// `/devices/virtual/block/vda` is `../../devices/virtual/block/vda` relative to
+/// the directory `/class/block`.
+fn relative_path(dir: &str, target: &str) -> String {
+    let dir_components: Vec<_> = dir.split('/').filter(|c| !c.is_empty()).collect();
+    let target_components: Vec<_> = target.split('/').filter(|c| !c.is_empty()).collect();
+    let nr_common = dir_components
+        .iter()
+        .zip(target_components.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut components = vec![".."; dir_components.len() - nr_common];
+    components.extend_from_slice(&target_components[nr_common..]);
+    if components.is_empty() {
+        return String::from(".");
+    }
+    components.join("/")
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn link_targets() {
+        assert_eq!(
+            relative_path("/class/block", "/devices/virtual/block/vda"),
+            "../../devices/virtual/block/vda"
+        );
+        assert_eq!(
+            relative_path("/devices/pci0000:00/0000:00:04.0", "/bus/pci"),
+            "../../../bus/pci"
+        );
+        assert_eq!(relative_path("/bus/pci", "/bus/pci/drivers"), "drivers");
+        assert_eq!(relative_path("", "/devices"), "devices");
+    }
+}
@@ -0,0 +1,201 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::format;
+
+use crate::prelude::*;
+
+/// A kernel object, which is shown as a directory in the sysfs.
+///
+/// The objects are lightweight views of the kernel states, e.g., the devices on a bus,
+/// which are created when the directories are looked up. So an object should read
+/// the states when its attributes are read, rather than caching them.
+pub trait KObject: Send + Sync + 'static {
+    /// Returns the name of the object, which is the name of its directory.
+    fn name(&self) -> String;
+
+    /// Returns the parent of the object, or `None` if it is the root.
+    fn parent(&self) -> Option<Arc<dyn KObject>>;
+
+    /// Returns the child objects, which are shown as the subdirectories.
+    fn children(self: Arc<Self>) -> Vec<Arc<dyn KObject>> {
+        Vec::new()
+    }
+
+    /// Returns the attributes, which are shown as the regular files.
+    fn attributes(&self) -> Vec<Attribute> {
+        Vec::new()
+    }
+
+    /// Returns the links to the other objects, which are shown as the symbolic links.
+    ///
+    /// Each link is a pair of its name and the absolute path of the target in the sysfs,
+    /// e.g., `("driver", "/bus/pci/drivers/virtio-pci")`.
+    fn links(&self) -> Vec<(String, String)> {
+        Vec::new()
+    }
+
+    /// Returns whether the object lives as long as the sysfs.
+    ///
+    /// The directories of the persistent objects are cached, so that the other file
+    /// systems can be mounted on them, e.g., the cgroup2 on `/sys/fs/cgroup`.
+    fn is_persistent(&self) -> bool {
+        false
+    }
+}
+
+impl dyn KObject {
+    /// Returns the absolute path of the object in the sysfs, e.g., `/block/vda`.
+    ///
+    /// The path of the root is empty.
+    pub fn path(&self) -> String {
+        match self.parent() {
+            Some(parent) => format!("{}/{}", parent.path(), self.name()),
+            None => String::new(),
+        }
+    }
+}
+
+type ShowFn = Box<dyn Fn() -> Result<String> + Send + Sync>;
+type StoreFn = Box<dyn Fn(&str) -> Result<()> + Send + Sync>;
+
+/// An attribute of a kernel object.
+///
+/// An attribute is a regular file, whose content is generated when it is read.
+/// If the attribute can be stored, the written content is passed to the object.
+pub struct Attribute {
+    name: &'static str,
+    show: ShowFn,
+    store: Option<StoreFn>,
+}
+
+impl Attribute {
+    /// Creates a read-only attribute.
+    pub fn new_ro(
+        name: &'static str,
+        show: impl Fn() -> Result<String> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            name,
+            show: Box::new(show),
+            store: None,
+        }
+    }
+
+    /// Creates an attribute that can be both read and written.
+    pub fn new_rw(
+        name: &'static str,
+        show: impl Fn() -> Result<String> + Send + Sync + 'static,
+        store: impl Fn(&str) -> Result<()> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            name,
+            show: Box::new(show),
+            store: Some(Box::new(store)),
+        }
+    }
+
+    /// Creates a read-only attribute whose content is fixed.
+    pub fn new_const(name: &'static str, value: impl ToString) -> Self {
+        let value = value.to_string();
+        Self::new_ro(name, move || Ok(value.clone()))
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn is_writable(&self) -> bool {
+        self.store.is_some()
+    }
+
+    /// Returns the content of the attribute, which ends with a newline.
+    pub fn show(&self) -> Result<String> {
+        let mut content = (self.show)()?;
+        if !content.ends_with('\n') {
+            content.push('\n');
+        }
+        Ok(content)
+    }
+
+    /// Stores the content to the attribute.
+    ///
+    /// The trailing newline, as written by `echo`, is trimmed.
+    pub fn store(&self, content: &str) -> Result<()> {
+        let Some(store) = self.store.as_ref() else {
+            return_errno_with_message!(Errno::EACCES, "the attribute is read-only");
+        };
+        store(content.trim_end_matches('\n'))
+    }
+}
+
+/// A directory that only groups the other objects, e.g., `/sys/bus`.
+///
+/// The children and the links are produced by functions, which are given the directory
+/// itself as the parent of the children.
+pub struct KDir {
+    name: &'static str,
+    parent: Option<Arc<dyn KObject>>,
+    children: fn(&Arc<KDir>) -> Vec<Arc<dyn KObject>>,
+    links: fn() -> Vec<(String, String)>,
+}
+
+impl KDir {
+    pub fn new(
+        name: &'static str,
+        parent: Arc<dyn KObject>,
+        children: fn(&Arc<KDir>) -> Vec<Arc<dyn KObject>>,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            name,
+            parent: Some(parent),
+            children,
+            links: Vec::new,
+        })
+    }
+
+    /// Creates a directory that only contains the links.
+    pub fn new_with_links(
+        name: &'static str,
+        parent: Arc<dyn KObject>,
+        links: fn() -> Vec<(String, String)>,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            name,
+            parent: Some(parent),
+            children: |_| Vec::new(),
+            links,
+        })
+    }
+
+    /// Creates the root directory.
+    pub fn new_root(children: fn(&Arc<KDir>) -> Vec<Arc<dyn KObject>>) -> Arc<Self> {
+        Arc::new(Self {
+            name: "",
+            parent: None,
+            children,
+            links: Vec::new,
+        })
+    }
+}
+
+impl KObject for KDir {
+    fn name(&self) -> String {
+        String::from(self.name)
+    }
+
+    fn parent(&self) -> Option<Arc<dyn KObject>> {
+        self.parent.clone()
+    }
+
+    fn children(self: Arc<Self>) -> Vec<Arc<dyn KObject>> {
+        (self.children)(&self)
+    }
+
+    fn links(&self) -> Vec<(String, String)> {
+        (self.links)()
+    }
+
+    fn is_persistent(&self) -> bool {
+        true
+    }
+}
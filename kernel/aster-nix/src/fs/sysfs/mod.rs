@@ -0,0 +1,112 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The sysfs, which exposes the kernel objects, e.g., the devices, the buses and their
+//! drivers, to the user space.
+//!
+//! Each kernel object is a directory, whose regular files are its attributes and
+//! whose symbolic links refer to the related objects. The tree is laid out like Linux:
+//!
+//! - `/sys/devices` contains the devices on the PCI bus and the platform (MMIO) bus,
+//!   and the devices registered in the device components, e.g., the block devices;
+//! - `/sys/bus/<bus>` links to the devices on the bus and their drivers;
+//! - `/sys/class/<class>` and `/sys/block` link to the devices of the classes;
+//! - `/sys/dev/{char,block}` link to the devices with device numbers, e.g., `1:3`;
+//! - `/sys/fs/cgroup` is the mount point of the cgroup2.
+//!
+//! The objects are created from the buses and the device registries when they are
+//! looked up, so the devices added at runtime, e.g., the loop devices, show up
+//! without notifying the sysfs. The user space is notified by the uevents instead.
+
+pub use self::kobject::{Attribute, KObject};
+use self::{inode::SysfsInode, kobject::KDir};
+use crate::{
+    fs::utils::{FileSystem, FsFlags, Inode, SuperBlock, NAME_MAX},
+    prelude::*,
+};
+
+mod bus;
+mod class;
+mod inode;
+mod kobject;
+pub mod uevent;
+
+/// Magic number.
+const SYSFS_MAGIC: u64 = 0x62656572;
+/// Root Inode ID.
+const SYSFS_ROOT_INO: u64 = 1;
+/// Block size.
+const BLOCK_SIZE: usize = PAGE_SIZE;
+
+pub struct SysFS {
+    sb: SuperBlock,
+    root: Arc<dyn Inode>,
+}
+
+impl SysFS {
+    pub fn new() -> Arc<Self> {
+        Arc::new_cyclic(|weak_fs| Self {
+            sb: SuperBlock::new(SYSFS_MAGIC, BLOCK_SIZE, NAME_MAX),
+            root: SysfsInode::new_dir(root_kobject(), weak_fs.clone()),
+        })
+    }
+}
+
+impl FileSystem for SysFS {
+    fn sync(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn root_inode(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+
+    fn sb(&self) -> SuperBlock {
+        self.sb.clone()
+    }
+
+    fn flags(&self) -> FsFlags {
+        FsFlags::empty()
+    }
+
+    fn name(&self) -> &'static str {
+        "sysfs"
+    }
+}
+
+/// Returns the object at `/sys`.
+fn root_kobject() -> Arc<dyn KObject> {
+    KDir::new_root(|root| {
+        let parent: Arc<dyn KObject> = root.clone();
+        vec![
+            KDir::new("devices", parent.clone(), |devices| {
+                let parent: Arc<dyn KObject> = devices.clone();
+                vec![
+                    KDir::new("pci0000:00", parent.clone(), |dir| {
+                        bus::pci_devices(dir.clone())
+                    }) as _,
+                    KDir::new("platform", parent.clone(), |dir| {
+                        bus::platform_devices(dir.clone())
+                    }) as _,
+                    KDir::new("virtual", parent, |dir| {
+                        class::virtual_device_classes(dir.clone())
+                    }) as _,
+                ]
+            }) as _,
+            KDir::new("bus", parent.clone(), |dir| bus::buses(dir.clone())) as _,
+            KDir::new("class", parent.clone(), |dir| class::classes(dir.clone())) as _,
+            KDir::new_with_links("block", parent.clone(), class::disk_links) as _,
+            KDir::new("dev", parent.clone(), |dev| {
+                let parent: Arc<dyn KObject> = dev.clone();
+                vec![
+                    KDir::new_with_links("char", parent.clone(), class::char_device_number_links)
+                        as _,
+                    KDir::new_with_links("block", parent, class::block_device_number_links) as _,
+                ]
+            }) as _,
+            KDir::new("fs", parent.clone(), |fs| {
+                vec![KDir::new("cgroup", fs.clone(), |_| Vec::new()) as _]
+            }) as _,
+            uevent::kernel_object(parent),
+        ]
+    })
+}
@@ -0,0 +1,185 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The uevents, which notify the user space that the devices are added or removed.
+//!
+//! A uevent is a message of `<action>@<devpath>`, followed by the environment variables
+//! that describe the device, all of which end with NULs, e.g.,
+//! `add@/devices/virtual/mem/null\0ACTION=add\0DEVPATH=/devices/virtual/mem/null\0...`.
+//! The messages are broadcast to the `NETLINK_KOBJECT_UEVENT` sockets, from which
+//! a device manager, e.g., mdev, receives them.
+
+use alloc::format;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use super::{class, Attribute, KObject};
+use crate::{device::registry::RegisteredDevice, net::socket::netlink, prelude::*};
+
+/// The sequence number of the last uevent.
+static SEQNUM: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UeventAction {
+    Add,
+    Remove,
+}
+
+impl UeventAction {
+    fn name(self) -> &'static str {
+        match self {
+            UeventAction::Add => "add",
+            UeventAction::Remove => "remove",
+        }
+    }
+}
+
+/// Sends the uevent of the registered device.
+pub fn send_device_uevent(action: UeventAction, device: &RegisteredDevice) {
+    let devpath = class::device_path(device);
+    let seqnum = SEQNUM.fetch_add(1, Ordering::Relaxed) + 1;
+
+    let mut message = format!("{}@{}\0", action.name(), devpath);
+    message.push_str(&format!("ACTION={}\0", action.name()));
+    message.push_str(&format!("DEVPATH={}\0", devpath));
+    message.push_str(&format!("SUBSYSTEM={}\0", class::subsystem_of(device)));
+    for var in class::device_uevent_vars(device).lines() {
+        message.push_str(var);
+        message.push('\0');
+    }
+    message.push_str(&format!("SEQNUM={}\0", seqnum));
+
+    netlink::broadcast_uevent(message.as_bytes());
+}
+
+/// Returns the object at `/sys/kernel`.
+pub(super) fn kernel_object(parent: Arc<dyn KObject>) -> Arc<dyn KObject> {
+    Arc::new(KernelObject { parent })
+}
+
+/// Represents the object at `/sys/kernel`.
+struct KernelObject {
+    parent: Arc<dyn KObject>,
+}
+
+impl KObject for KernelObject {
+    fn name(&self) -> String {
+        String::from("kernel")
+    }
+
+    fn parent(&self) -> Option<Arc<dyn KObject>> {
+        Some(self.parent.clone())
+    }
+
+    fn attributes(&self) -> Vec<Attribute> {
+        vec![Attribute::new_ro("uevent_seqnum", || {
+            Ok(SEQNUM.load(Ordering::Relaxed).to_string())
+        })]
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+    use crate::{
+        device::registry::{register_device, register_major, unregister_device},
+        events::IoEvents,
+        fs::{
+            device::{Device, DeviceId, DeviceType},
+            devtmpfs,
+            file_handle::FileLike,
+            inode_handle::FileIo,
+            path::{Dentry, MountNode},
+            ramfs::RamFS,
+            sysfs::kobject::KDir,
+            utils::{InodeMode, InodeType},
+        },
+        net::socket::{
+            netlink::{NetlinkSocketAddr, NetlinkUeventSocket},
+            Socket,
+        },
+        process::signal::Poller,
+    };
+
+    struct TestDevice(DeviceId);
+
+    impl Device for TestDevice {
+        fn type_(&self) -> DeviceType {
+            DeviceType::CharDevice
+        }
+
+        fn id(&self) -> DeviceId {
+            self.0
+        }
+    }
+
+    impl FileIo for TestDevice {
+        fn read(&self, _buf: &mut [u8]) -> Result<usize> {
+            Ok(0)
+        }
+
+        fn write(&self, buf: &[u8]) -> Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn poll(&self, mask: IoEvents, _poller: Option<&Poller>) -> IoEvents {
+            (IoEvents::IN | IoEvents::OUT) & mask
+        }
+    }
+
+    fn recv_uevent(socket: &NetlinkUeventSocket) -> Vec<String> {
+        let mut buf = [0u8; 512];
+        let len = socket.read(&mut buf).unwrap();
+        core::str::from_utf8(&buf[..len])
+            .unwrap()
+            .split_terminator('\0')
+            .map(String::from)
+            .collect()
+    }
+
+    #[ktest]
+    fn device_uevents() {
+        crate::time::clocks::init_for_ktest();
+        let root = Dentry::new_fs_root(MountNode::new_root(RamFS::new()));
+        let mode = InodeMode::from_bits_truncate(0o755);
+        let dev = root.new_fs_child("dev", InodeType::Dir, mode).unwrap();
+        devtmpfs::init(&dev).unwrap();
+
+        let socket = NetlinkUeventSocket::new(true);
+        socket.bind(NetlinkSocketAddr::new(200, 1).into()).unwrap();
+        let kernel = kernel_object(KDir::new_root(|_| Vec::new()));
+        let seqnum_attr = kernel.attributes().pop().unwrap();
+        assert_eq!(seqnum_attr.name(), "uevent_seqnum");
+
+        let major = register_major(DeviceType::CharDevice, None, "uevent-test").unwrap();
+        let id = DeviceId::new(major, 0);
+        register_device(Arc::new(TestDevice(id)), "uevent-test").unwrap();
+        let seqnum = seqnum_attr.show().unwrap();
+        let devpath = "/devices/virtual/uevent-test/uevent-test";
+        assert_eq!(
+            recv_uevent(&socket),
+            [
+                format!("add@{}", devpath),
+                String::from("ACTION=add"),
+                format!("DEVPATH={}", devpath),
+                String::from("SUBSYSTEM=uevent-test"),
+                format!("MAJOR={}", major),
+                String::from("MINOR=0"),
+                String::from("DEVNAME=uevent-test"),
+                format!("SEQNUM={}", seqnum),
+            ]
+        );
+
+        unregister_device(DeviceType::CharDevice, id).unwrap();
+        let next_seqnum = seqnum.parse::<u64>().unwrap() + 1;
+        assert_eq!(seqnum_attr.show().unwrap(), next_seqnum.to_string());
+        let uevent = recv_uevent(&socket);
+        assert_eq!(uevent[0], format!("remove@{}", devpath));
+        assert_eq!(uevent[1], "ACTION=remove");
+        assert_eq!(uevent.last().unwrap(), &format!("SEQNUM={}", next_seqnum));
+        assert_eq!(
+            socket.read(&mut [0u8; 8]).unwrap_err().error(),
+            Errno::EAGAIN
+        );
+    }
+}
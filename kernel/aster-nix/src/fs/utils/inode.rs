@@ -7,10 +7,13 @@ use core::time::Duration;
 use aster_rights::Full;
 use core2::io::{Error as IoError, ErrorKind as IoErrorKind, Result as IoResult, Write};
 
-use super::{DirentVisitor, FileSystem, IoctlCmd};
+use super::{AccessMode, DirentVisitor, FileSystem, IoctlCmd, StatusFlags};
 use crate::{
     events::IoEvents,
-    fs::device::{Device, DeviceType},
+    fs::{
+        device::{Device, DeviceType},
+        inode_handle::FileIo,
+    },
     prelude::*,
     process::{signal::Poller, Gid, Uid},
     time::clocks::RealTimeCoarseClock,
@@ -51,6 +54,15 @@ impl InodeType {
     pub fn is_directory(&self) -> bool {
         *self == InodeType::Dir
     }
+
+    /// Returns the type of the device file, or `None` if the file is not a device.
+    pub fn device_type(&self) -> Option<DeviceType> {
+        match self {
+            InodeType::CharDevice => Some(DeviceType::CharDevice),
+            InodeType::BlockDevice => Some(DeviceType::BlockDevice),
+            _ => None,
+        }
+    }
 }
 
 impl From<DeviceType> for InodeType {
@@ -276,6 +288,26 @@ pub trait Inode: Any + Sync + Send {
         None
     }
 
+    /// Prepares the inode to be written through a file or a shared mapping.
+    ///
+    /// For example, an overlay file is copied up here, so that its page cache
+    /// is not that of a lower layer afterwards.
+    fn prepare_write(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Opens the inode with `access_mode` and `status_flags`.
+    ///
+    /// If the filesystem keeps a state for each open file, e.g., the file handle
+    /// of FUSE, the file operations are provided by the returned `FileIo`.
+    fn open(
+        &self,
+        access_mode: AccessMode,
+        status_flags: StatusFlags,
+    ) -> Result<Option<Arc<dyn FileIo>>> {
+        Ok(None)
+    }
+
     fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
         Err(Error::new(Errno::EISDIR))
     }
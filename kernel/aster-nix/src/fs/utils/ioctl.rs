@@ -33,4 +33,42 @@ pub enum IoctlCmd {
     TIOCGPTPEER = 0x40045441,
     /// Get tdx report using TDCALL
     TDXGETREPORT = 0xc4405401,
+    /// Get the size of a block device in 512-byte sectors
+    BLKGETSIZE = 0x1260,
+    /// Write back and invalidate the buffer cache of a block device
+    BLKFLSBUF = 0x1261,
+    /// Get the logical sector size of a block device
+    BLKSSZGET = 0x1268,
+    /// Get the size of a block device in bytes
+    BLKGETSIZE64 = 0x80081272,
+    /// Associate a loop device with a file
+    LOOP_SET_FD = 0x4C00,
+    /// Disassociate a loop device from its file
+    LOOP_CLR_FD = 0x4C01,
+    /// Set the status of a loop device
+    LOOP_SET_STATUS64 = 0x4C04,
+    /// Get the status of a loop device
+    LOOP_GET_STATUS64 = 0x4C05,
+    /// Get the number of a free loop device, which is allocated if none is free
+    LOOP_CTL_GET_FREE = 0x4C82,
+    /// Get the version of the device mapper interface
+    DM_VERSION = 0xC138FD00,
+    /// List the mapped devices
+    DM_LIST_DEVICES = 0xC138FD02,
+    /// Create a mapped device
+    DM_DEV_CREATE = 0xC138FD03,
+    /// Remove a mapped device
+    DM_DEV_REMOVE = 0xC138FD04,
+    /// Suspend or resume a mapped device
+    DM_DEV_SUSPEND = 0xC138FD06,
+    /// Get the status of a mapped device
+    DM_DEV_STATUS = 0xC138FD07,
+    /// Load a table into a mapped device
+    DM_TABLE_LOAD = 0xC138FD09,
+    /// Clear the loaded table of a mapped device
+    DM_TABLE_CLEAR = 0xC138FD0A,
+    /// Get the table of a mapped device
+    DM_TABLE_STATUS = 0xC138FD0C,
+    /// List the versions of the targets
+    DM_LIST_VERSIONS = 0xC138FD0D,
 }
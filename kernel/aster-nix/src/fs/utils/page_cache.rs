@@ -10,6 +10,7 @@ use lru::LruCache;
 use ostd::mm::{Frame, FrameAllocOptions};
 
 use crate::{
+    cgroup::MemoryCharge,
     prelude::*,
     vm::vmo::{get_page_idx_range, Pager, Vmo, VmoFlags, VmoOptions},
 };
@@ -258,7 +259,17 @@ impl ReadaheadState {
             return_errno!(Errno::EINVAL)
         };
         for async_idx in window.readahead_range() {
-            let mut async_page = Page::alloc()?;
+            // Reading ahead is best-effort, so it stops if the backend refuses more pages.
+            if backend.charge_page().is_err() {
+                break;
+            }
+            let mut async_page = match Page::alloc() {
+                Ok(page) => page,
+                Err(e) => {
+                    backend.uncharge_page();
+                    return Err(e);
+                }
+            };
             let pg_waiter = backend.read_page(async_idx, async_page.frame())?;
             self.waiter.concat(pg_waiter);
             async_page.set_state(PageState::Uninit);
@@ -296,7 +307,33 @@ impl PageCacheManager {
     pub fn discard_range(&self, range: Range<usize>) {
         let page_idx_range = get_page_idx_range(&range);
         for idx in page_idx_range {
-            self.pages.lock().pop(&idx);
+            if self.pages.lock().pop(&idx).is_some() {
+                self.uncharge_page();
+            }
+        }
+    }
+
+    /// Allocates a page to be cached, which is charged to the backend.
+    fn alloc_page(&self, backend: &Arc<dyn PageCacheBackend>, zeroed: bool) -> Result<Page> {
+        backend.charge_page()?;
+        let page = if zeroed {
+            Page::alloc_zero()
+        } else {
+            Page::alloc()
+        };
+        if page.is_err() {
+            backend.uncharge_page();
+        }
+        page
+    }
+
+    /// Uncharges a page that is removed from the cache.
+    ///
+    /// The page is not uncharged if the backend is gone, since the backend is
+    /// responsible to return the charges of the remaining pages when it is dropped.
+    fn uncharge_page(&self) {
+        if let Some(backend) = self.backend.upgrade() {
+            backend.uncharge_page();
         }
     }
 
@@ -361,12 +398,15 @@ impl PageCacheManager {
             // Cond 3.
             // Conducts the sync read operation.
             let page = if idx < backend.npages() {
-                let mut page = Page::alloc()?;
-                backend.read_page_sync(idx, page.frame())?;
+                let mut page = self.alloc_page(&backend, false)?;
+                if let Err(e) = backend.read_page_sync(idx, page.frame()) {
+                    backend.uncharge_page();
+                    return Err(e);
+                }
                 page.set_state(PageState::UpToDate);
                 page
             } else {
-                Page::alloc_zero()?
+                self.alloc_page(&backend, true)?
             };
             let frame = page.frame().clone();
             pages.put(idx, page);
@@ -408,10 +448,11 @@ impl Pager for PageCacheManager {
     fn decommit_page(&self, idx: usize) -> Result<()> {
         let page_result = self.pages.lock().pop(&idx);
         if let Some(page) = page_result {
+            let Some(backend) = self.backend.upgrade() else {
+                return Ok(());
+            };
+            backend.uncharge_page();
             if let PageState::Dirty = page.state() {
-                let Some(backend) = self.backend.upgrade() else {
-                    return Ok(());
-                };
                 if idx < backend.npages() {
                     backend.write_page_sync(idx, page.frame())?;
                 }
@@ -426,8 +467,15 @@ impl Pager for PageCacheManager {
             return Ok(page.frame.clone());
         }
 
-        let page = Page::alloc_zero()?;
-        Ok(self.pages.lock().get_or_insert(idx, || page).frame.clone())
+        let backend = self.backend();
+        let page = self.alloc_page(&backend, true)?;
+        let mut pages = self.pages.lock();
+        if let Some(page) = pages.get(&idx) {
+            // Another thread has cached the page, so the new one is dropped.
+            backend.uncharge_page();
+            return Ok(page.frame.clone());
+        }
+        Ok(pages.get_or_insert(idx, || page).frame.clone())
     }
 }
 
@@ -435,22 +483,31 @@ impl Pager for PageCacheManager {
 struct Page {
     frame: Frame,
     state: PageState,
+    /// The charge of the page to the memory cgroup of the task that caches the page,
+    /// which is uncharged when the page is evicted.
+    memory_charge: MemoryCharge,
 }
 
 impl Page {
     pub fn alloc() -> Result<Self> {
+        let memory_charge = MemoryCharge::new();
+        memory_charge.try_charge(1)?;
         let frame = FrameAllocOptions::new(1).uninit(true).alloc_single()?;
         Ok(Self {
             frame,
             state: PageState::Uninit,
+            memory_charge,
         })
     }
 
     pub fn alloc_zero() -> Result<Self> {
+        let memory_charge = MemoryCharge::new();
+        memory_charge.try_charge(1)?;
         let frame = FrameAllocOptions::new(1).alloc_single()?;
         Ok(Self {
             frame,
             state: PageState::Dirty,
+            memory_charge,
         })
     }
 
@@ -488,6 +545,15 @@ pub trait PageCacheBackend: Sync + Send {
     fn write_page(&self, idx: usize, frame: &Frame) -> Result<BioWaiter>;
     /// Returns the number of pages in the backend.
     fn npages(&self) -> usize;
+    /// Charges a page before it is added to the page cache.
+    ///
+    /// Fails if the backend cannot hold more pages, e.g., the size of a tmpfs is
+    /// limited. The charge is returned by `uncharge_page` when the page is removed.
+    fn charge_page(&self) -> Result<()> {
+        Ok(())
+    }
+    /// Uncharges a page that is removed from the page cache.
+    fn uncharge_page(&self) {}
 }
 
 impl dyn PageCacheBackend {
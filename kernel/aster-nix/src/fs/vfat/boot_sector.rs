@@ -0,0 +1,236 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::mem::size_of;
+
+use aster_block::{BlockDevice, SECTOR_SIZE};
+use ostd::mm::VmIo;
+
+use super::{
+    dentry::DENTRY_SIZE,
+    fat::{ClusterId, FatType, FIRST_CLUSTER},
+};
+use crate::prelude::*;
+
+pub(super) const BOOT_SIGNATURE: u16 = 0xAA55;
+
+/// The BIOS parameter block, which is shared by FAT12, FAT16 and FAT32.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Pod)]
+pub(super) struct BiosParameterBlock {
+    pub jump_boot: [u8; 3],
+    pub oem_name: [u8; 8],
+    pub bytes_per_sector: u16,
+    pub sectors_per_cluster: u8,
+    pub reserved_sectors: u16,
+    pub num_fats: u8,
+    pub root_entries: u16,
+    pub total_sectors_16: u16,
+    pub media: u8,
+    pub fat_size_16: u16,
+    pub sectors_per_track: u16,
+    pub num_heads: u16,
+    pub hidden_sectors: u32,
+    pub total_sectors_32: u32,
+}
+
+/// The fields following the BIOS parameter block on FAT32.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Pod)]
+pub(super) struct Fat32Extension {
+    pub fat_size_32: u32,
+    pub ext_flags: u16,
+    pub fs_version: u16,
+    pub root_cluster: u32,
+    pub fs_info_sector: u16,
+    pub backup_boot_sector: u16,
+    pub reserved: [u8; 12],
+}
+
+/// If set in `ext_flags`, only the FAT numbered by the low 4 bits is active.
+const EXT_FLAGS_NO_MIRRORING: u16 = 1 << 7;
+
+/// The FSInfo sector of FAT32, which records the hints of the free clusters.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Pod)]
+pub(super) struct FsInfo {
+    pub lead_signature: u32,
+    pub reserved: [u8; 480],
+    pub struct_signature: u32,
+    pub free_count: u32,
+    pub next_free: u32,
+    pub reserved2: [u8; 12],
+    pub trail_signature: u32,
+}
+
+const FS_INFO_LEAD_SIGNATURE: u32 = 0x41615252;
+const FS_INFO_STRUCT_SIGNATURE: u32 = 0x61417272;
+const FS_INFO_TRAIL_SIGNATURE: u32 = 0xAA550000;
+/// The value of the hints in FSInfo if they are unknown.
+pub(super) const FS_INFO_UNKNOWN: u32 = 0xFFFFFFFF;
+
+/// The root directory.
+#[derive(Clone, Copy, Debug)]
+pub(super) enum RootDir {
+    /// The fixed region following the FATs on FAT12 and FAT16
+    Fixed { offset: usize, size: usize },
+    /// The cluster chain starting from the cluster on FAT32
+    Cluster(ClusterId),
+}
+
+/// The in-memory layout of the volume, which is derived from the boot sector.
+///
+/// All the offsets and the sizes are in bytes.
+#[derive(Clone, Copy, Debug)]
+pub(super) struct VfatSuperBlock {
+    pub fat_type: FatType,
+    pub sector_size: usize,
+    pub cluster_size: usize,
+    /// The offset of the first FAT
+    pub fat_offset: usize,
+    /// The size of each FAT
+    pub fat_size: usize,
+    pub num_fats: usize,
+    /// The only FAT that is used if the FATs are not mirrored
+    pub active_fat: Option<usize>,
+    pub root_dir: RootDir,
+    /// The offset of the first data cluster, i.e., cluster 2
+    pub data_offset: usize,
+    /// The number of data clusters, which are numbered from 2
+    pub num_clusters: u32,
+    /// The offset of the FSInfo sector on FAT32
+    pub fs_info_offset: Option<usize>,
+}
+
+impl VfatSuperBlock {
+    /// Reads the boot sector and checks the layout against the device.
+    pub(super) fn read(block_device: &dyn BlockDevice) -> Result<Self> {
+        let mut boot_sector = [0u8; SECTOR_SIZE];
+        block_device.read_bytes(0, &mut boot_sector)?;
+        if u16::from_le_bytes([boot_sector[510], boot_sector[511]]) != BOOT_SIGNATURE {
+            return_errno_with_message!(Errno::EINVAL, "invalid boot sector signature");
+        }
+        let bpb_size = size_of::<BiosParameterBlock>();
+        let bpb = BiosParameterBlock::from_bytes(&boot_sector[..bpb_size]);
+        let ext = Fat32Extension::from_bytes(
+            &boot_sector[bpb_size..bpb_size + size_of::<Fat32Extension>()],
+        );
+
+        let sector_size = bpb.bytes_per_sector as usize;
+        if !sector_size.is_power_of_two() || !(512..=4096).contains(&sector_size) {
+            return_errno_with_message!(Errno::EINVAL, "bogus sector size");
+        }
+        let sectors_per_cluster = bpb.sectors_per_cluster as usize;
+        if !sectors_per_cluster.is_power_of_two() {
+            return_errno_with_message!(Errno::EINVAL, "bogus sectors per cluster");
+        }
+        if bpb.reserved_sectors == 0 || bpb.num_fats == 0 {
+            return_errno_with_message!(Errno::EINVAL, "bogus reserved sectors or FATs");
+        }
+
+        // FAT32 is identified by the absence of the 16-bit FAT size, as Linux does.
+        let is_fat32 = bpb.fat_size_16 == 0;
+        let fat_sectors = if is_fat32 {
+            ext.fat_size_32 as usize
+        } else {
+            bpb.fat_size_16 as usize
+        };
+        let total_sectors = if bpb.total_sectors_16 != 0 {
+            bpb.total_sectors_16 as usize
+        } else {
+            bpb.total_sectors_32 as usize
+        };
+        if is_fat32 && bpb.root_entries != 0 {
+            return_errno_with_message!(Errno::EINVAL, "bogus root entries on FAT32");
+        }
+        if fat_sectors == 0 {
+            return_errno_with_message!(Errno::EINVAL, "bogus FAT size");
+        }
+
+        let root_dir_sectors = (bpb.root_entries as usize * DENTRY_SIZE).div_ceil(sector_size);
+        let root_dir_start = bpb.reserved_sectors as usize + bpb.num_fats as usize * fat_sectors;
+        let data_start = root_dir_start + root_dir_sectors;
+        if data_start >= total_sectors {
+            return_errno_with_message!(Errno::EINVAL, "bogus total sectors");
+        }
+        if total_sectors * sector_size > block_device.nr_sectors() * SECTOR_SIZE {
+            return_errno_with_message!(Errno::EINVAL, "the volume is larger than the device");
+        }
+
+        let num_clusters = ((total_sectors - data_start) / sectors_per_cluster) as u32;
+        let fat_type = if is_fat32 {
+            FatType::Fat32
+        } else if num_clusters < 4085 {
+            FatType::Fat12
+        } else {
+            FatType::Fat16
+        };
+        if num_clusters == 0 || num_clusters > fat_type.max_clusters() {
+            return_errno_with_message!(Errno::EINVAL, "bogus number of clusters");
+        }
+        let fat_size = fat_sectors * sector_size;
+        if fat_type.fat_offset_of(num_clusters + FIRST_CLUSTER) > fat_size {
+            return_errno_with_message!(Errno::EINVAL, "the FAT is too small");
+        }
+
+        let root_dir = if is_fat32 {
+            if ext.root_cluster < FIRST_CLUSTER || ext.root_cluster >= num_clusters + FIRST_CLUSTER
+            {
+                return_errno_with_message!(Errno::EINVAL, "bogus root cluster");
+            }
+            RootDir::Cluster(ext.root_cluster)
+        } else {
+            RootDir::Fixed {
+                offset: root_dir_start * sector_size,
+                size: bpb.root_entries as usize * DENTRY_SIZE,
+            }
+        };
+        let active_fat = (is_fat32 && ext.ext_flags & EXT_FLAGS_NO_MIRRORING != 0)
+            .then_some((ext.ext_flags & 0xF) as usize)
+            .filter(|index| *index < bpb.num_fats as usize);
+        let fs_info_offset = (is_fat32
+            && ext.fs_info_sector != 0
+            && (ext.fs_info_sector as usize) < bpb.reserved_sectors as usize)
+            .then_some(ext.fs_info_sector as usize * sector_size);
+
+        let super_block = Self {
+            fat_type,
+            sector_size,
+            cluster_size: sectors_per_cluster * sector_size,
+            fat_offset: bpb.reserved_sectors as usize * sector_size,
+            fat_size,
+            num_fats: bpb.num_fats as usize,
+            active_fat,
+            root_dir,
+            data_offset: data_start * sector_size,
+            num_clusters,
+            fs_info_offset,
+        };
+        Ok(super_block)
+    }
+
+    /// Returns the offset of the cluster.
+    pub(super) fn cluster_offset(&self, cluster: ClusterId) -> usize {
+        self.data_offset + (cluster - FIRST_CLUSTER) as usize * self.cluster_size
+    }
+
+    /// Returns whether the cluster is a data cluster.
+    pub(super) fn is_valid_cluster(&self, cluster: ClusterId) -> bool {
+        cluster >= FIRST_CLUSTER && cluster < self.num_clusters + FIRST_CLUSTER
+    }
+
+    /// Returns the offsets of the FATs to be updated.
+    pub(super) fn fat_offsets(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.num_fats)
+            .filter(|index| self.active_fat.map_or(true, |active| active == *index))
+            .map(|index| self.fat_offset + index * self.fat_size)
+    }
+}
+
+impl FsInfo {
+    /// Returns whether the signatures are valid.
+    pub(super) fn is_valid(&self) -> bool {
+        self.lead_signature == FS_INFO_LEAD_SIGNATURE
+            && self.struct_signature == FS_INFO_STRUCT_SIGNATURE
+            && self.trail_signature == FS_INFO_TRAIL_SIGNATURE
+    }
+}
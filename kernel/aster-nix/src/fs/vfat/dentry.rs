@@ -0,0 +1,472 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::collections::BTreeSet;
+use core::{mem::size_of, time::Duration};
+
+use super::fat::ClusterId;
+use crate::{
+    fs::exfat::{DosTimestamp, FatAttr},
+    prelude::*,
+};
+
+pub(super) const DENTRY_SIZE: usize = 32;
+
+/// The maximum length of a long name in UTF-16 code units.
+pub(super) const MAX_NAME_LEN: usize = 255;
+
+/// The number of UTF-16 code units in each long name dentry.
+const LFN_CHARS_PER_DENTRY: usize = 13;
+/// The attributes marking a long name dentry, i.e., READONLY | HIDDEN | SYSTEM | VOLUME.
+const ATTR_LONG_NAME: u8 = 0x0F;
+/// The order of the last long name dentry, i.e., the first one on disk, has this bit set.
+const LFN_LAST: u8 = 0x40;
+
+/// The first byte of the name of a deleted dentry.
+pub(super) const DELETED: u8 = 0xE5;
+/// The first byte of the name of the dentry after the last one in use.
+const END_OF_DIR: u8 = 0x00;
+/// A short name starting with 0xE5 is stored with 0x05 instead.
+const KANJI_LEAD: u8 = 0x05;
+
+/// If set in `case`, the base of the short name is displayed in lower case.
+const CASE_LOWER_BASE: u8 = 0x08;
+/// If set in `case`, the extension of the short name is displayed in lower case.
+const CASE_LOWER_EXT: u8 = 0x10;
+
+/// The characters allowed in short names besides upper case letters and digits.
+const SHORT_NAME_SPECIALS: &str = "$%'-_@~`!(){}^#&";
+/// The characters never allowed in names besides the control characters.
+const INVALID_CHARS: &str = "\"*/:<>?\\|";
+
+pub(super) const DOT_NAME: [u8; 11] = *b".          ";
+pub(super) const DOTDOT_NAME: [u8; 11] = *b"..         ";
+
+/// The short dentry of a file or a directory.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Default, Pod)]
+pub(super) struct RawDentry {
+    pub name: [u8; 11],
+    pub attr: u8,
+    /// The case of the short name, which is used by Windows NT and Linux
+    pub case: u8,
+    pub create_time_10ms: u8,
+    pub create_time: u16,
+    pub create_date: u16,
+    pub access_date: u16,
+    pub cluster_high: u16,
+    pub write_time: u16,
+    pub write_date: u16,
+    pub cluster_low: u16,
+    pub size: u32,
+}
+
+/// A dentry holding 13 UTF-16 code units of a long name.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Default, Pod)]
+struct RawLfnDentry {
+    order: u8,
+    name1: [u16; 5],
+    attr: u8,
+    type_: u8,
+    checksum: u8,
+    name2: [u16; 6],
+    cluster: u16,
+    name3: [u16; 2],
+}
+
+const _: () = assert!(size_of::<RawDentry>() == DENTRY_SIZE);
+const _: () = assert!(size_of::<RawLfnDentry>() == DENTRY_SIZE);
+
+impl RawDentry {
+    pub(super) fn new(name: [u8; 11], case: u8, attr: FatAttr) -> Self {
+        Self {
+            name,
+            case,
+            attr: attr.bits() as u8,
+            ..Default::default()
+        }
+    }
+
+    pub(super) fn attr(&self) -> FatAttr {
+        FatAttr::from_bits_truncate(self.attr as u16)
+    }
+
+    pub(super) fn is_dir(&self) -> bool {
+        self.attr().contains(FatAttr::DIRECTORY)
+    }
+
+    pub(super) fn first_cluster(&self) -> ClusterId {
+        ((self.cluster_high as u32) << 16) | self.cluster_low as u32
+    }
+
+    pub(super) fn set_first_cluster(&mut self, cluster: ClusterId) {
+        self.cluster_high = (cluster >> 16) as u16;
+        self.cluster_low = cluster as u16;
+    }
+
+    pub(super) fn set_create_time(&mut self, time: DosTimestamp) {
+        self.create_time = time.time;
+        self.create_date = time.date;
+        self.create_time_10ms = time.increament_10ms;
+    }
+
+    pub(super) fn write_time(&self) -> Duration {
+        DosTimestamp::new(self.write_time, self.write_date, 0, 0)
+            .and_then(|time| time.as_duration())
+            .unwrap_or_default()
+    }
+
+    pub(super) fn set_write_time(&mut self, time: DosTimestamp) {
+        self.write_time = time.time;
+        self.write_date = time.date;
+    }
+
+    /// Returns the access time, which is recorded at the precision of a day.
+    pub(super) fn access_time(&self) -> Duration {
+        DosTimestamp::new(0, self.access_date, 0, 0)
+            .and_then(|time| time.as_duration())
+            .unwrap_or_default()
+    }
+
+    pub(super) fn set_access_time(&mut self, time: DosTimestamp) {
+        self.access_date = time.date;
+    }
+
+    /// Returns the short name as it is displayed.
+    pub(super) fn short_name(&self) -> String {
+        let mut name = self.name;
+        if name[0] == KANJI_LEAD {
+            name[0] = DELETED;
+        }
+        let convert = |part: &[u8], lower: bool| -> String {
+            let len = part
+                .iter()
+                .rposition(|byte| *byte != b' ')
+                .map_or(0, |index| index + 1);
+            part[..len]
+                .iter()
+                .map(|byte| {
+                    let c = *byte as char;
+                    if lower {
+                        c.to_ascii_lowercase()
+                    } else {
+                        c
+                    }
+                })
+                .collect()
+        };
+        let mut short_name = convert(&name[..8], self.case & CASE_LOWER_BASE != 0);
+        let ext = convert(&name[8..], self.case & CASE_LOWER_EXT != 0);
+        if !ext.is_empty() {
+            short_name.push('.');
+            short_name.push_str(&ext);
+        }
+        short_name
+    }
+}
+
+/// A file or a directory found in a directory.
+#[derive(Clone, Debug)]
+pub(super) struct DirEntry {
+    /// The long name, or the short name if there is no valid long name
+    pub name: String,
+    pub dentry: RawDentry,
+    /// The offset of the first dentry, which is a long name dentry if there is a long name
+    pub start: usize,
+    /// The offset of the short dentry
+    pub pos: usize,
+}
+
+impl DirEntry {
+    /// Returns whether the entry matches the name, which is compared case-insensitively
+    /// against both the long name and the short name.
+    pub(super) fn matches(&self, name: &str) -> bool {
+        name_eq(&self.name, name) || name_eq(&self.dentry.short_name(), name)
+    }
+
+    /// Returns the offsets of all the dentries of the entry.
+    pub(super) fn dentry_offsets(&self) -> impl Iterator<Item = usize> {
+        (self.start..=self.pos).step_by(DENTRY_SIZE)
+    }
+}
+
+/// The long name being collected from the long name dentries.
+struct PendingLongName {
+    units: Vec<u16>,
+    checksum: u8,
+    /// The order of the next long name dentry, which counts down to 1
+    next_order: u8,
+    start: usize,
+}
+
+/// Parses the entries of a directory, skipping the volume label, `.` and `..`.
+pub(super) fn parse_dir(data: &[u8]) -> Vec<DirEntry> {
+    let mut entries = Vec::new();
+    let mut long_name: Option<PendingLongName> = None;
+    for (index, bytes) in data.chunks_exact(DENTRY_SIZE).enumerate() {
+        let pos = index * DENTRY_SIZE;
+        match bytes[0] {
+            END_OF_DIR => break,
+            DELETED => {
+                long_name = None;
+                continue;
+            }
+            _ => {}
+        }
+
+        if bytes[11] & 0x3F == ATTR_LONG_NAME {
+            let lfn = RawLfnDentry::from_bytes(bytes);
+            let order = lfn.order & !LFN_LAST;
+            if lfn.order & LFN_LAST != 0 {
+                long_name = (1..=20).contains(&order).then(|| PendingLongName {
+                    units: vec![0; order as usize * LFN_CHARS_PER_DENTRY],
+                    checksum: lfn.checksum,
+                    next_order: order,
+                    start: pos,
+                });
+            }
+            let Some(pending) = long_name.as_mut() else {
+                continue;
+            };
+            if order != pending.next_order || lfn.checksum != pending.checksum {
+                long_name = None;
+                continue;
+            }
+            let offset = (order as usize - 1) * LFN_CHARS_PER_DENTRY;
+            let (name1, name2, name3) = (lfn.name1, lfn.name2, lfn.name3);
+            let units = name1.iter().chain(name2.iter()).chain(name3.iter());
+            for (dst, src) in pending.units[offset..].iter_mut().zip(units) {
+                *dst = *src;
+            }
+            pending.next_order -= 1;
+            continue;
+        }
+
+        let dentry = RawDentry::from_bytes(bytes);
+        let pending = long_name.take();
+        let attr = dentry.attr();
+        if attr.contains(FatAttr::VOLUME) || dentry.name == DOT_NAME || dentry.name == DOTDOT_NAME {
+            continue;
+        }
+        let pending = pending.filter(|pending| {
+            pending.next_order == 0 && pending.checksum == checksum(&dentry.name)
+        });
+        let (name, start) = match pending {
+            Some(pending) => {
+                let len = pending
+                    .units
+                    .iter()
+                    .position(|unit| *unit == 0)
+                    .unwrap_or(pending.units.len());
+                (
+                    String::from_utf16_lossy(&pending.units[..len]),
+                    pending.start,
+                )
+            }
+            None => (dentry.short_name(), pos),
+        };
+        entries.push(DirEntry {
+            name,
+            dentry,
+            start,
+            pos,
+        });
+    }
+    entries
+}
+
+/// Returns the short names of all the entries in a directory.
+pub(super) fn short_names(entries: &[DirEntry]) -> BTreeSet<[u8; 11]> {
+    entries.iter().map(|entry| entry.dentry.name).collect()
+}
+
+/// Finds `count` contiguous free dentries in a directory and returns the offset of the first one.
+pub(super) fn find_free_dentries(data: &[u8], count: usize) -> Option<usize> {
+    let num_dentries = data.len() / DENTRY_SIZE;
+    let mut run_start = 0;
+    let mut run_len = 0;
+    for index in 0..num_dentries {
+        let first_byte = data[index * DENTRY_SIZE];
+        if first_byte == END_OF_DIR {
+            // All the dentries from here are free.
+            if run_len == 0 {
+                run_start = index;
+            }
+            return (run_len + num_dentries - index >= count).then_some(run_start * DENTRY_SIZE);
+        }
+        if first_byte == DELETED {
+            if run_len == 0 {
+                run_start = index;
+            }
+            run_len += 1;
+            if run_len == count {
+                return Some(run_start * DENTRY_SIZE);
+            }
+        } else {
+            run_len = 0;
+        }
+    }
+    None
+}
+
+/// Returns the offset of the end of the dentries in use.
+pub(super) fn end_of_dir(data: &[u8]) -> usize {
+    data.chunks_exact(DENTRY_SIZE)
+        .position(|bytes| bytes[0] == END_OF_DIR)
+        .map_or(data.len(), |index| index * DENTRY_SIZE)
+}
+
+/// Compares the names case-insensitively, as FAT does with its upcase table.
+pub(super) fn name_eq(a: &str, b: &str) -> bool {
+    a.chars()
+        .flat_map(char::to_uppercase)
+        .eq(b.chars().flat_map(char::to_uppercase))
+}
+
+/// Checks whether the name can be stored and returns it without the trailing dots,
+/// which are ignored as on Windows.
+pub(super) fn check_name(name: &str) -> Result<&str> {
+    let name = name.trim_end_matches('.');
+    if name.is_empty() {
+        return_errno_with_message!(Errno::EINVAL, "the name consists of dots");
+    }
+    if name.encode_utf16().count() > MAX_NAME_LEN {
+        return_errno!(Errno::ENAMETOOLONG);
+    }
+    if name
+        .chars()
+        .any(|c| (c as u32) < 0x20 || INVALID_CHARS.contains(c))
+    {
+        return_errno_with_message!(Errno::EINVAL, "the name contains invalid characters");
+    }
+    Ok(name)
+}
+
+fn is_short_name_char(c: char) -> bool {
+    c.is_ascii_uppercase() || c.is_ascii_digit() || SHORT_NAME_SPECIALS.contains(c)
+}
+
+/// Returns the short name and its case if the name is a valid 8.3 name, so that no
+/// long name is needed.
+///
+/// The base and the extension may be in lower case respectively, as Windows NT does.
+pub(super) fn to_short_name(name: &str) -> Option<([u8; 11], u8)> {
+    let (base, ext) = match name.rfind('.') {
+        Some(index) => (&name[..index], &name[index + 1..]),
+        None => (name, ""),
+    };
+    if base.is_empty() || base.len() > 8 || ext.len() > 3 {
+        return None;
+    }
+
+    let mut short_name = [b' '; 11];
+    let mut case = 0;
+    for (part, dst, lower_flag) in [
+        (base, &mut short_name[..8], CASE_LOWER_BASE),
+        (ext, &mut short_name[8..], CASE_LOWER_EXT),
+    ] {
+        let has_lower = part.chars().any(|c| c.is_ascii_lowercase());
+        let has_upper = part.chars().any(|c| c.is_ascii_uppercase());
+        if has_lower && has_upper {
+            return None;
+        }
+        if !part
+            .chars()
+            .all(|c| is_short_name_char(c.to_ascii_uppercase()))
+        {
+            return None;
+        }
+        if has_lower {
+            case |= lower_flag;
+        }
+        for (dst, src) in dst.iter_mut().zip(part.bytes()) {
+            *dst = src.to_ascii_uppercase();
+        }
+    }
+    Some((short_name, case))
+}
+
+/// Generates a short name for a long name, which does not collide with `existing`.
+///
+/// The short name is derived from the long name with a numeric tail, e.g., `LONGNA~1.TXT`.
+pub(super) fn gen_short_name(name: &str, existing: &BTreeSet<[u8; 11]>) -> Result<[u8; 11]> {
+    let name = name.trim_start_matches('.');
+    let (base, ext) = match name.rfind('.') {
+        Some(index) => (&name[..index], &name[index + 1..]),
+        None => (name, ""),
+    };
+    let convert = |part: &str, max_len: usize| -> Vec<u8> {
+        part.chars()
+            .filter(|c| *c != ' ' && *c != '.')
+            .map(|c| {
+                let c = c.to_ascii_uppercase();
+                if is_short_name_char(c) {
+                    c as u8
+                } else {
+                    b'_'
+                }
+            })
+            .take(max_len)
+            .collect()
+    };
+    let mut base = convert(base, 8);
+    if base.is_empty() {
+        base.push(b'_');
+    }
+    let ext = convert(ext, 3);
+
+    for number in 1..1_000_000 {
+        let tail = format!("~{}", number);
+        let base_len = base.len().min(8 - tail.len());
+        let mut short_name = [b' '; 11];
+        short_name[..base_len].copy_from_slice(&base[..base_len]);
+        short_name[base_len..base_len + tail.len()].copy_from_slice(tail.as_bytes());
+        short_name[8..8 + ext.len()].copy_from_slice(&ext);
+        if !existing.contains(&short_name) {
+            return Ok(short_name);
+        }
+    }
+    return_errno_with_message!(Errno::EEXIST, "too many similar short names")
+}
+
+/// Returns the checksum of the short name, which is recorded in its long name dentries.
+pub(super) fn checksum(short_name: &[u8; 11]) -> u8 {
+    short_name
+        .iter()
+        .fold(0u8, |sum, byte| sum.rotate_right(1).wrapping_add(*byte))
+}
+
+/// Returns the long name dentries in their on-disk order, i.e., from the last part of the name.
+pub(super) fn long_name_dentries(name: &str, checksum: u8) -> Vec<[u8; DENTRY_SIZE]> {
+    let mut units: Vec<u16> = name.encode_utf16().collect();
+    let num_dentries = units.len().div_ceil(LFN_CHARS_PER_DENTRY);
+    // The name is terminated by a NUL unless it fills the dentries, and padded with 0xFFFF.
+    if units.len() % LFN_CHARS_PER_DENTRY != 0 {
+        units.push(0);
+    }
+    units.resize(num_dentries * LFN_CHARS_PER_DENTRY, 0xFFFF);
+
+    (0..num_dentries)
+        .rev()
+        .map(|index| {
+            let part = &units[index * LFN_CHARS_PER_DENTRY..(index + 1) * LFN_CHARS_PER_DENTRY];
+            let mut order = index as u8 + 1;
+            if index == num_dentries - 1 {
+                order |= LFN_LAST;
+            }
+            let lfn = RawLfnDentry {
+                order,
+                name1: part[..5].try_into().unwrap(),
+                attr: ATTR_LONG_NAME,
+                type_: 0,
+                checksum,
+                name2: part[5..11].try_into().unwrap(),
+                cluster: 0,
+                name3: part[11..].try_into().unwrap(),
+            };
+            let mut bytes = [0u8; DENTRY_SIZE];
+            bytes.copy_from_slice(lfn.as_bytes());
+            bytes
+        })
+        .collect()
+}
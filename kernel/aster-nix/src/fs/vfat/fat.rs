@@ -0,0 +1,198 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use bitvec::prelude::*;
+
+use crate::prelude::*;
+
+pub(super) type ClusterId = u32;
+
+/// Cluster 0 and 1 are reserved, so the first data cluster is 2.
+pub(super) const FIRST_CLUSTER: ClusterId = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum FatType {
+    Fat12,
+    Fat16,
+    Fat32,
+}
+
+impl FatType {
+    /// Returns the maximum number of data clusters.
+    pub(super) fn max_clusters(&self) -> u32 {
+        match self {
+            FatType::Fat12 => 4084,
+            FatType::Fat16 => 65524,
+            // The values from 0x0FFFFFF7 are reserved.
+            FatType::Fat32 => 0x0FFFFFF5,
+        }
+    }
+
+    /// Returns the offset of the FAT entry of the cluster in the FAT.
+    pub(super) fn fat_offset_of(&self, cluster: ClusterId) -> usize {
+        let cluster = cluster as usize;
+        match self {
+            FatType::Fat12 => cluster + cluster / 2,
+            FatType::Fat16 => cluster * 2,
+            FatType::Fat32 => cluster * 4,
+        }
+    }
+
+    /// Returns the number of bytes to be accessed for a FAT entry.
+    ///
+    /// An entry of FAT12 is 12-bit, which spans 2 bytes shared with the adjacent entries.
+    pub(super) fn entry_bytes(&self) -> usize {
+        match self {
+            FatType::Fat12 | FatType::Fat16 => 2,
+            FatType::Fat32 => 4,
+        }
+    }
+
+    /// Decodes the FAT entry of the cluster from the bytes at `fat_offset_of(cluster)`.
+    pub(super) fn decode(&self, cluster: ClusterId, bytes: &[u8]) -> FatValue {
+        let raw = match self {
+            FatType::Fat12 => {
+                let value = u16::from_le_bytes([bytes[0], bytes[1]]) as u32;
+                if cluster % 2 == 1 {
+                    value >> 4
+                } else {
+                    value & 0xFFF
+                }
+            }
+            FatType::Fat16 => u16::from_le_bytes([bytes[0], bytes[1]]) as u32,
+            FatType::Fat32 => {
+                u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) & 0x0FFFFFFF
+            }
+        };
+        let bad = self.raw_end_of_chain() - 7;
+        match raw {
+            0 => FatValue::Free,
+            raw if raw == bad => FatValue::Bad,
+            raw if raw > bad => FatValue::EndOfChain,
+            raw => FatValue::Next(raw),
+        }
+    }
+
+    /// Encodes the FAT entry of the cluster into the bytes at `fat_offset_of(cluster)`,
+    /// keeping the bits belonging to the adjacent entries or reserved.
+    pub(super) fn encode(&self, cluster: ClusterId, value: FatValue, bytes: &mut [u8]) {
+        let raw = match value {
+            FatValue::Free => 0,
+            FatValue::Next(next) => next,
+            FatValue::Bad => self.raw_end_of_chain() - 7,
+            FatValue::EndOfChain => self.raw_end_of_chain(),
+        };
+        match self {
+            FatType::Fat12 => {
+                let old = u16::from_le_bytes([bytes[0], bytes[1]]);
+                let new = if cluster % 2 == 1 {
+                    (old & 0x000F) | ((raw as u16) << 4)
+                } else {
+                    (old & 0xF000) | (raw as u16 & 0x0FFF)
+                };
+                bytes[..2].copy_from_slice(&new.to_le_bytes());
+            }
+            FatType::Fat16 => bytes[..2].copy_from_slice(&(raw as u16).to_le_bytes()),
+            FatType::Fat32 => {
+                let old = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                let new = (old & 0xF0000000) | raw;
+                bytes[..4].copy_from_slice(&new.to_le_bytes());
+            }
+        }
+    }
+
+    fn raw_end_of_chain(&self) -> u32 {
+        match self {
+            FatType::Fat12 => 0xFFF,
+            FatType::Fat16 => 0xFFFF,
+            FatType::Fat32 => 0x0FFFFFFF,
+        }
+    }
+}
+
+/// The value of a FAT entry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum FatValue {
+    Free,
+    Next(ClusterId),
+    Bad,
+    EndOfChain,
+}
+
+/// The free clusters, which are found by scanning the FAT when mounting.
+///
+/// FAT has no on-disk bitmap as exFAT does, so the bitmap is kept in memory only.
+#[derive(Debug)]
+pub(super) struct FreeClusters {
+    /// Whether each data cluster is in use, indexed from cluster 2
+    used: BitVec,
+    num_free: u32,
+    /// The cluster from which the next allocation searches
+    next_hint: ClusterId,
+}
+
+impl FreeClusters {
+    /// Creates the bitmap from the FAT values of the data clusters.
+    pub(super) fn new(values: impl Iterator<Item = FatValue>, next_hint: ClusterId) -> Self {
+        let used: BitVec = values.map(|value| value != FatValue::Free).collect();
+        let num_free = used.count_zeros() as u32;
+        let next_hint = if (FIRST_CLUSTER..FIRST_CLUSTER + used.len() as u32).contains(&next_hint) {
+            next_hint
+        } else {
+            FIRST_CLUSTER
+        };
+        Self {
+            used,
+            num_free,
+            next_hint,
+        }
+    }
+
+    pub(super) fn num_free(&self) -> u32 {
+        self.num_free
+    }
+
+    pub(super) fn next_hint(&self) -> ClusterId {
+        self.next_hint
+    }
+
+    /// Marks `count` free clusters as used and returns them, which are searched
+    /// from the hint so that the clusters of a file tend to be contiguous.
+    pub(super) fn alloc(&mut self, count: usize) -> Result<Vec<ClusterId>> {
+        if count > self.num_free as usize {
+            return_errno_with_message!(Errno::ENOSPC, "no free clusters");
+        }
+        if count == 0 {
+            return Ok(Vec::new());
+        }
+        let start = (self.next_hint - FIRST_CLUSTER) as usize % self.used.len();
+        let (tail, head) = (start..self.used.len(), 0..start);
+        let clusters: Vec<ClusterId> = tail
+            .chain(head)
+            .filter(|index| !self.used[*index])
+            .take(count)
+            .map(|index| index as ClusterId + FIRST_CLUSTER)
+            .collect();
+        for cluster in clusters.iter() {
+            self.used.set((*cluster - FIRST_CLUSTER) as usize, true);
+        }
+        self.num_free -= clusters.len() as u32;
+        if let Some(last) = clusters.last() {
+            self.next_hint = last + 1;
+            if self.next_hint - FIRST_CLUSTER >= self.used.len() as u32 {
+                self.next_hint = FIRST_CLUSTER;
+            }
+        }
+        Ok(clusters)
+    }
+
+    /// Marks the clusters as free.
+    pub(super) fn free(&mut self, clusters: &[ClusterId]) {
+        for cluster in clusters {
+            let index = (*cluster - FIRST_CLUSTER) as usize;
+            if self.used[index] {
+                self.used.set(index, false);
+                self.num_free += 1;
+            }
+        }
+    }
+}
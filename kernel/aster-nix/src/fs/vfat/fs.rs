@@ -0,0 +1,331 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::{
+    mem::size_of,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use aster_block::{
+    bio::{Bio, BioSegment, BioStatus, BioType, BioWaiter},
+    id::{BlockId, Sid},
+    BlockDevice,
+};
+use ostd::mm::{Frame, VmIo};
+
+use super::{
+    boot_sector::{FsInfo, VfatSuperBlock, FS_INFO_UNKNOWN},
+    dentry::MAX_NAME_LEN,
+    fat::{ClusterId, FatValue, FreeClusters, FIRST_CLUSTER},
+    inode::{Ino, VfatInode},
+};
+use crate::{
+    fs::utils::{FileSystem, FsFlags, Inode, PageCache, PageCacheBackend, SuperBlock},
+    prelude::*,
+};
+
+/// The magic number reported by `statfs`, which is `MSDOS_SUPER_MAGIC` on Linux.
+const MSDOS_SUPER_MAGIC: u64 = 0x4d44;
+
+pub(super) const ROOT_INO: Ino = 1;
+
+/// The key of the root inode in the opened inodes, which has no dentry.
+const ROOT_KEY: (Ino, usize) = (0, 0);
+
+#[derive(Debug)]
+pub struct VfatFS {
+    block_device: Arc<dyn BlockDevice>,
+    super_block: VfatSuperBlock,
+    /// The free clusters, whose lock is also held when updating the FATs, since
+    /// the entries of FAT12 share bytes with each other.
+    free_clusters: Mutex<FreeClusters>,
+    /// The opened inodes, which are indexed by the inode numbers of their parents and
+    /// the offsets of their short dentries.
+    inodes: Mutex<BTreeMap<(Ino, usize), Arc<VfatInode>>>,
+    /// Used for inode allocation.
+    highest_ino: AtomicU64,
+    /// The cache of the region before the data clusters, i.e., the boot sector,
+    /// the FATs and the fixed root directory of FAT12 and FAT16.
+    meta_cache: PageCache,
+    /// A global lock, which is held when modifying directories or cluster chains.
+    mutex: Mutex<()>,
+}
+
+impl VfatFS {
+    pub fn open(block_device: Arc<dyn BlockDevice>) -> Result<Arc<Self>> {
+        let super_block = VfatSuperBlock::read(block_device.as_ref())?;
+        let vfat_fs = Arc::new_cyclic(|weak_self| VfatFS {
+            block_device,
+            super_block,
+            free_clusters: Mutex::new(FreeClusters::new(core::iter::empty(), FIRST_CLUSTER)),
+            inodes: Mutex::new(BTreeMap::new()),
+            highest_ino: AtomicU64::new(ROOT_INO + 1),
+            meta_cache: PageCache::with_capacity(super_block.data_offset, weak_self.clone() as _)
+                .unwrap(),
+            mutex: Mutex::new(()),
+        });
+
+        *vfat_fs.free_clusters.lock() = vfat_fs.load_free_clusters()?;
+
+        let root = VfatInode::build_root(&vfat_fs)?;
+        vfat_fs.inodes.lock().insert(ROOT_KEY, root);
+
+        Ok(vfat_fs)
+    }
+
+    /// Scans the FAT for the free clusters.
+    fn load_free_clusters(&self) -> Result<FreeClusters> {
+        let sb = &self.super_block;
+        let fat_type = sb.fat_type;
+        let last_cluster = sb.num_clusters + FIRST_CLUSTER - 1;
+        let fat_len = fat_type.fat_offset_of(last_cluster) + fat_type.entry_bytes();
+        let mut fat = vec![0u8; fat_len];
+        let read_len = fat_len.min(sb.fat_size);
+        self.read_meta_at(self.fat_offsets().next().unwrap(), &mut fat[..read_len])?;
+
+        let values = (FIRST_CLUSTER..=last_cluster)
+            .map(|cluster| fat_type.decode(cluster, &fat[fat_type.fat_offset_of(cluster)..]));
+        let next_hint = match self.read_fs_info()? {
+            Some(fs_info) => fs_info.next_free,
+            None => FIRST_CLUSTER,
+        };
+        Ok(FreeClusters::new(values, next_hint))
+    }
+
+    fn read_fs_info(&self) -> Result<Option<FsInfo>> {
+        let Some(offset) = self.super_block.fs_info_offset else {
+            return Ok(None);
+        };
+        let mut buf = [0u8; size_of::<FsInfo>()];
+        self.read_meta_at(offset, &mut buf)?;
+        let fs_info = FsInfo::from_bytes(&buf);
+        Ok(fs_info.is_valid().then_some(fs_info))
+    }
+
+    /// Records the free clusters in FSInfo, which are only hints for other systems.
+    fn write_fs_info(&self) -> Result<()> {
+        let Some(mut fs_info) = self.read_fs_info()? else {
+            return Ok(());
+        };
+        let free_clusters = self.free_clusters.lock();
+        fs_info.free_count = free_clusters.num_free();
+        fs_info.next_free = free_clusters.next_hint();
+        if fs_info.next_free == FIRST_CLUSTER {
+            fs_info.next_free = FS_INFO_UNKNOWN;
+        }
+        self.write_meta_at(self.super_block.fs_info_offset.unwrap(), fs_info.as_bytes())
+    }
+
+    pub(super) fn alloc_ino(&self) -> Ino {
+        self.highest_ino.fetch_add(1, Ordering::SeqCst)
+    }
+
+    pub(super) fn find_opened_inode(&self, key: (Ino, usize)) -> Option<Arc<VfatInode>> {
+        self.inodes.lock().get(&key).cloned()
+    }
+
+    pub(super) fn insert_inode(&self, key: (Ino, usize), inode: Arc<VfatInode>) {
+        self.inodes.lock().insert(key, inode);
+    }
+
+    pub(super) fn remove_inode(&self, key: (Ino, usize)) -> Option<Arc<VfatInode>> {
+        self.inodes.lock().remove(&key)
+    }
+
+    pub(super) fn read_meta_at(&self, offset: usize, buf: &mut [u8]) -> Result<()> {
+        self.meta_cache.pages().read_bytes(offset, buf)?;
+        Ok(())
+    }
+
+    pub(super) fn write_meta_at(&self, offset: usize, buf: &[u8]) -> Result<()> {
+        self.meta_cache.pages().write_bytes(offset, buf)?;
+        Ok(())
+    }
+
+    /// Writes the dirty metadata back to the device.
+    pub(super) fn sync_meta(&self) -> Result<()> {
+        self.write_fs_info()?;
+        self.meta_cache.evict_range(0..self.super_block.data_offset)
+    }
+
+    fn fat_offsets(&self) -> impl Iterator<Item = usize> + '_ {
+        self.super_block.fat_offsets()
+    }
+
+    /// Reads the FAT entry of the cluster.
+    pub(super) fn read_fat(&self, cluster: ClusterId) -> Result<FatValue> {
+        let fat_type = self.super_block.fat_type;
+        let mut buf = [0u8; 4];
+        let bytes = &mut buf[..fat_type.entry_bytes()];
+        let offset = self.fat_offsets().next().unwrap() + fat_type.fat_offset_of(cluster);
+        self.read_meta_at(offset, bytes)?;
+        Ok(fat_type.decode(cluster, bytes))
+    }
+
+    /// Writes the FAT entry of the cluster to all the FATs in use.
+    ///
+    /// The lock of the free clusters must be held by the caller.
+    fn write_fat(&self, cluster: ClusterId, value: FatValue) -> Result<()> {
+        let fat_type = self.super_block.fat_type;
+        let mut buf = [0u8; 4];
+        let bytes = &mut buf[..fat_type.entry_bytes()];
+        for fat_offset in self.fat_offsets() {
+            let offset = fat_offset + fat_type.fat_offset_of(cluster);
+            self.read_meta_at(offset, bytes)?;
+            fat_type.encode(cluster, value, bytes);
+            self.write_meta_at(offset, bytes)?;
+        }
+        Ok(())
+    }
+
+    /// Reads the cluster chain starting from the cluster.
+    pub(super) fn read_chain(&self, start: ClusterId) -> Result<Vec<ClusterId>> {
+        let mut chain = Vec::new();
+        let mut cluster = start;
+        loop {
+            if !self.super_block.is_valid_cluster(cluster) {
+                return_errno_with_message!(Errno::EIO, "invalid cluster in the chain");
+            }
+            if chain.len() >= self.super_block.num_clusters as usize {
+                return_errno_with_message!(Errno::EIO, "the cluster chain has a loop");
+            }
+            chain.push(cluster);
+            match self.read_fat(cluster)? {
+                FatValue::Next(next) => cluster = next,
+                FatValue::EndOfChain => return Ok(chain),
+                FatValue::Free | FatValue::Bad => {
+                    return_errno_with_message!(Errno::EIO, "the cluster chain is broken")
+                }
+            }
+        }
+    }
+
+    /// Allocates `count` clusters and links them after `last`, the last cluster of a chain.
+    pub(super) fn alloc_clusters(
+        &self,
+        last: Option<ClusterId>,
+        count: usize,
+    ) -> Result<Vec<ClusterId>> {
+        let mut free_clusters = self.free_clusters.lock();
+        let clusters = free_clusters.alloc(count)?;
+        for (index, cluster) in clusters.iter().enumerate() {
+            let value = match clusters.get(index + 1) {
+                Some(next) => FatValue::Next(*next),
+                None => FatValue::EndOfChain,
+            };
+            self.write_fat(*cluster, value)?;
+        }
+        if let (Some(last), Some(first)) = (last, clusters.first()) {
+            self.write_fat(last, FatValue::Next(*first))?;
+        }
+        Ok(clusters)
+    }
+
+    /// Frees the clusters at the end of a chain, whose new last cluster is `last`.
+    pub(super) fn dealloc_clusters(
+        &self,
+        last: Option<ClusterId>,
+        clusters: &[ClusterId],
+    ) -> Result<()> {
+        let mut free_clusters = self.free_clusters.lock();
+        if let Some(last) = last {
+            self.write_fat(last, FatValue::EndOfChain)?;
+        }
+        for cluster in clusters {
+            self.write_fat(*cluster, FatValue::Free)?;
+        }
+        free_clusters.free(clusters);
+        Ok(())
+    }
+
+    pub(super) fn block_device(&self) -> &dyn BlockDevice {
+        self.block_device.as_ref()
+    }
+
+    /// Flushes the volatile write cache of the block device.
+    pub(super) fn flush_device(&self) -> Result<()> {
+        let status = self.block_device.flush_sync()?;
+        match status {
+            BioStatus::Complete => Ok(()),
+            err_status => Err(Error::from(err_status)),
+        }
+    }
+
+    pub(super) fn super_block(&self) -> &VfatSuperBlock {
+        &self.super_block
+    }
+
+    pub(super) fn root_inode(&self) -> Arc<VfatInode> {
+        self.inodes.lock().get(&ROOT_KEY).unwrap().clone()
+    }
+
+    pub(super) fn lock(&self) -> MutexGuard<()> {
+        self.mutex.lock()
+    }
+
+    pub(super) fn cluster_size(&self) -> usize {
+        self.super_block.cluster_size
+    }
+
+    pub(super) fn sector_size(&self) -> usize {
+        self.super_block.sector_size
+    }
+}
+
+impl PageCacheBackend for VfatFS {
+    fn read_page(&self, idx: usize, frame: &Frame) -> Result<BioWaiter> {
+        let waiter = self
+            .block_device
+            .read_block(BlockId::new(idx as u64), frame)?;
+        Ok(waiter)
+    }
+
+    fn write_page(&self, idx: usize, frame: &Frame) -> Result<BioWaiter> {
+        // The last page may cover the data clusters, which are cached by the inodes.
+        let offset = idx * PAGE_SIZE;
+        let len = (self.super_block.data_offset - offset).min(PAGE_SIZE);
+        let bio = Bio::new(
+            BioType::Write,
+            Sid::from_offset(offset),
+            vec![BioSegment::from_frame(frame.clone(), 0, len)],
+            None,
+        );
+        Ok(bio.submit(self.block_device())?)
+    }
+
+    fn npages(&self) -> usize {
+        self.super_block.data_offset.div_ceil(PAGE_SIZE)
+    }
+}
+
+impl FileSystem for VfatFS {
+    fn sync(&self) -> Result<()> {
+        let inodes: Vec<_> = self.inodes.lock().values().cloned().collect();
+        for inode in inodes {
+            inode.sync_data_pages()?;
+        }
+        self.sync_meta()?;
+        self.flush_device()?;
+        Ok(())
+    }
+
+    fn root_inode(&self) -> Arc<dyn Inode> {
+        self.root_inode()
+    }
+
+    fn sb(&self) -> SuperBlock {
+        let mut sb = SuperBlock::new(MSDOS_SUPER_MAGIC, self.cluster_size(), MAX_NAME_LEN);
+        let num_free = self.free_clusters.lock().num_free() as usize;
+        sb.blocks = self.super_block.num_clusters as usize;
+        sb.bfree = num_free;
+        sb.bavail = num_free;
+        sb
+    }
+
+    fn flags(&self) -> FsFlags {
+        FsFlags::DENTRY_UNEVICTABLE
+    }
+
+    fn name(&self) -> &'static str {
+        "vfat"
+    }
+}
@@ -0,0 +1,1076 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::{
+    ops::Range,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use align_ext::AlignExt;
+use aster_block::{
+    bio::{Bio, BioSegment, BioStatus, BioType, BioWaiter},
+    id::Sid,
+};
+use aster_rights::Full;
+use ostd::mm::{Frame, FrameAllocOptions, VmIo};
+
+use super::{
+    boot_sector::RootDir,
+    dentry::{
+        check_name, checksum, end_of_dir, find_free_dentries, gen_short_name, long_name_dentries,
+        parse_dir, short_names, to_short_name, DirEntry, RawDentry, DELETED, DENTRY_SIZE,
+        DOTDOT_NAME, DOT_NAME, MAX_NAME_LEN,
+    },
+    fat::ClusterId,
+    fs::{VfatFS, ROOT_INO},
+};
+use crate::{
+    fs::{
+        device::Device,
+        exfat::{DosTimestamp, FatAttr},
+        utils::{
+            DirentVisitor, FileSystem, Inode, InodeMode, InodeType, IoctlCmd, Metadata, PageCache,
+            PageCacheBackend,
+        },
+    },
+    prelude::*,
+    process::{Gid, Uid},
+    vm::vmo::Vmo,
+};
+
+/// Inode number
+pub type Ino = u64;
+
+/// The maximum size of a directory, which holds at most 65536 dentries.
+const MAX_DIR_SIZE: usize = 65536 * DENTRY_SIZE;
+/// The maximum size of a file, which is recorded in 32 bits.
+const MAX_FILE_SIZE: usize = u32::MAX as usize;
+
+/// The earliest time that can be recorded, i.e., 1980-01-01 00:00:00.
+const DOS_TIME_MIN: Duration = Duration::from_secs(315_532_800);
+/// The latest time that can be recorded, i.e., 2107-12-31 23:59:58.
+const DOS_TIME_MAX: Duration = Duration::from_secs(4_354_819_198);
+
+/// A file or a directory on FAT.
+///
+/// FAT has no inode numbers, so they are allocated when the inodes are opened. An opened
+/// inode stays in `VfatFS` until it is removed, so that its inode number never changes.
+#[derive(Debug)]
+pub struct VfatInode {
+    ino: Ino,
+    type_: InodeType,
+    /// The size, which is read by the page cache without locking `inner`
+    size: AtomicUsize,
+    /// The cluster chain, which is empty for an empty file or the fixed root directory
+    clusters: RwMutex<Vec<ClusterId>>,
+    /// The cache of the file contents or the dentries in the directory
+    page_cache: PageCache,
+    inner: RwMutex<InodeInner>,
+    this: Weak<VfatInode>,
+    fs: Weak<VfatFS>,
+}
+
+#[derive(Debug)]
+struct InodeInner {
+    /// The parent directory and the offset of the short dentry in it, which is `None`
+    /// for the root directory or an inode not linked yet
+    location: Option<Location>,
+    /// The short dentry, whose first cluster and size are filled when it is written back
+    dentry: RawDentry,
+    /// Whether the inode has been unlinked, whose clusters are freed when it is dropped
+    is_deleted: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Location {
+    parent: Arc<VfatInode>,
+    pos: usize,
+}
+
+impl VfatInode {
+    fn new(
+        fs: &Arc<VfatFS>,
+        ino: Ino,
+        dentry: RawDentry,
+        location: Option<Location>,
+        clusters: Vec<ClusterId>,
+        size: usize,
+    ) -> Arc<Self> {
+        let type_ = if dentry.is_dir() {
+            InodeType::Dir
+        } else {
+            InodeType::File
+        };
+        Arc::new_cyclic(|weak_self| Self {
+            ino,
+            type_,
+            size: AtomicUsize::new(size),
+            clusters: RwMutex::new(clusters),
+            page_cache: PageCache::with_capacity(size, weak_self.clone() as _).unwrap(),
+            inner: RwMutex::new(InodeInner {
+                location,
+                dentry,
+                is_deleted: false,
+            }),
+            this: weak_self.clone(),
+            fs: Arc::downgrade(fs),
+        })
+    }
+
+    pub(super) fn build_root(fs: &Arc<VfatFS>) -> Result<Arc<Self>> {
+        let dentry = RawDentry::new(DOT_NAME, 0, FatAttr::DIRECTORY);
+        let (clusters, size) = match fs.super_block().root_dir {
+            RootDir::Fixed { size, .. } => (Vec::new(), size),
+            RootDir::Cluster(start) => {
+                let clusters = fs.read_chain(start)?;
+                let size = clusters.len() * fs.cluster_size();
+                (clusters, size)
+            }
+        };
+        Ok(Self::new(fs, ROOT_INO, dentry, None, clusters, size))
+    }
+
+    fn build_from_entry(
+        fs: &Arc<VfatFS>,
+        parent: Arc<Self>,
+        entry: &DirEntry,
+    ) -> Result<Arc<Self>> {
+        let dentry = entry.dentry;
+        let clusters = match dentry.first_cluster() {
+            0 => Vec::new(),
+            start => fs.read_chain(start)?,
+        };
+        let allocated = clusters.len() * fs.cluster_size();
+        let size = if dentry.is_dir() {
+            allocated
+        } else {
+            dentry.size as usize
+        };
+        if size > allocated {
+            return_errno_with_message!(Errno::EIO, "the file is larger than its clusters");
+        }
+        let location = Location {
+            parent,
+            pos: entry.pos,
+        };
+        Ok(Self::new(
+            fs,
+            fs.alloc_ino(),
+            dentry,
+            Some(location),
+            clusters,
+            size,
+        ))
+    }
+
+    fn vfat_fs(&self) -> Arc<VfatFS> {
+        self.fs.upgrade().unwrap()
+    }
+
+    fn this(&self) -> Arc<Self> {
+        self.this.upgrade().unwrap()
+    }
+
+    fn is_dir(&self) -> bool {
+        self.type_ == InodeType::Dir
+    }
+
+    fn first_cluster(&self) -> ClusterId {
+        self.clusters.read().first().copied().unwrap_or(0)
+    }
+
+    /// Returns the cluster recorded in `..` of the subdirectories, which is 0 for the root.
+    fn cluster_for_dotdot(&self) -> ClusterId {
+        if self.ino == ROOT_INO {
+            0
+        } else {
+            self.first_cluster()
+        }
+    }
+
+    /// Returns the offset of the root directory if it is the fixed one of FAT12 and FAT16,
+    /// which is accessed through the metadata cache.
+    fn fixed_root_offset(&self, fs: &VfatFS) -> Option<usize> {
+        match fs.super_block().root_dir {
+            RootDir::Fixed { offset, .. } if self.ino == ROOT_INO => Some(offset),
+            _ => None,
+        }
+    }
+
+    /// Returns the ranges on the device of the bytes in `range`, which are clipped to
+    /// the allocated clusters.
+    fn device_ranges(&self, range: Range<usize>) -> Vec<Range<usize>> {
+        let fs = self.vfat_fs();
+        let sb = fs.super_block();
+        let clusters = self.clusters.read();
+        let end = range.end.min(clusters.len() * sb.cluster_size);
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        let mut offset = range.start;
+        while offset < end {
+            let offset_in_cluster = offset % sb.cluster_size;
+            let len = (sb.cluster_size - offset_in_cluster).min(end - offset);
+            let start = sb.cluster_offset(clusters[offset / sb.cluster_size]) + offset_in_cluster;
+            match ranges.last_mut() {
+                Some(last) if last.end == start => last.end += len,
+                _ => ranges.push(start..start + len),
+            }
+            offset += len;
+        }
+        ranges
+    }
+
+    /// Submits the I/O of a page, which is split where the clusters are discontiguous.
+    fn submit_page_io(&self, type_: BioType, idx: usize, frame: &Frame) -> Result<BioWaiter> {
+        let offset = idx * PAGE_SIZE;
+        let ranges = self.device_ranges(offset..offset + PAGE_SIZE);
+        let len: usize = ranges.iter().map(|range| range.len()).sum();
+        // The part beyond the allocated clusters reads as zeros.
+        if type_ == BioType::Read && len < PAGE_SIZE {
+            frame.write_bytes(len, &vec![0u8; PAGE_SIZE - len])?;
+        }
+
+        let fs = self.vfat_fs();
+        let mut waiter = BioWaiter::new();
+        let mut frame_offset = 0;
+        for range in ranges {
+            let segment = BioSegment::from_frame(frame.clone(), frame_offset, range.len());
+            let bio = Bio::new(type_, Sid::from_offset(range.start), vec![segment], None);
+            waiter.concat(bio.submit(fs.block_device())?);
+            frame_offset += range.len();
+        }
+        Ok(waiter)
+    }
+
+    /// Writes zeros to the bytes in `range` on the device, bypassing the page cache.
+    fn zero_on_disk(&self, range: Range<usize>) -> Result<()> {
+        let fs = self.vfat_fs();
+        let zero_frame = FrameAllocOptions::new(1).alloc_single()?;
+        let max_segments = fs.block_device().max_nr_segments_per_bio();
+        let mut waiter = BioWaiter::new();
+        for device_range in self.device_ranges(range) {
+            let mut offset = device_range.start;
+            while offset < device_range.end {
+                let mut segments = Vec::new();
+                let mut len = 0;
+                while offset + len < device_range.end && segments.len() < max_segments {
+                    let segment_len = (device_range.end - offset - len).min(PAGE_SIZE);
+                    segments.push(BioSegment::from_frame(zero_frame.clone(), 0, segment_len));
+                    len += segment_len;
+                }
+                let bio = Bio::new(BioType::Write, Sid::from_offset(offset), segments, None);
+                waiter.concat(bio.submit(fs.block_device())?);
+                offset += len;
+            }
+        }
+        match waiter.wait() {
+            Some(BioStatus::Complete) => Ok(()),
+            _ => return_errno_with_message!(Errno::EIO, "failed to zero the clusters"),
+        }
+    }
+
+    /// Reads all the dentries in the directory.
+    fn read_dir(&self, fs: &VfatFS) -> Result<Vec<u8>> {
+        let mut data = vec![0u8; self.size()];
+        match self.fixed_root_offset(fs) {
+            Some(root_offset) => fs.read_meta_at(root_offset, &mut data)?,
+            None => self.page_cache.pages().read_bytes(0, &mut data)?,
+        }
+        Ok(data)
+    }
+
+    fn write_dir(&self, fs: &VfatFS, offset: usize, buf: &[u8]) -> Result<()> {
+        match self.fixed_root_offset(fs) {
+            Some(root_offset) => fs.write_meta_at(root_offset + offset, buf)?,
+            None => self.page_cache.pages().write_bytes(offset, buf)?,
+        }
+        Ok(())
+    }
+
+    fn is_empty_dir(&self, fs: &VfatFS) -> Result<bool> {
+        Ok(parse_dir(&self.read_dir(fs)?).is_empty())
+    }
+
+    /// Finds the entry by the name, which is compared case-insensitively.
+    fn find_entry(&self, fs: &VfatFS, name: &str) -> Result<DirEntry> {
+        let name = name.trim_end_matches('.');
+        parse_dir(&self.read_dir(fs)?)
+            .into_iter()
+            .find(|entry| entry.matches(name))
+            .ok_or_else(|| Error::with_message(Errno::ENOENT, "the file does not exist"))
+    }
+
+    /// Returns the inode of the entry, which is built if it has not been opened.
+    fn get_inode(&self, fs: &Arc<VfatFS>, entry: &DirEntry) -> Result<Arc<VfatInode>> {
+        let key = (self.ino, entry.pos);
+        if let Some(inode) = fs.find_opened_inode(key) {
+            return Ok(inode);
+        }
+        let inode = Self::build_from_entry(fs, self.this(), entry)?;
+        fs.insert_inode(key, inode.clone());
+        Ok(inode)
+    }
+
+    /// Appends a zeroed cluster to the directory.
+    fn grow_dir(&self, fs: &Arc<VfatFS>) -> Result<()> {
+        if self.fixed_root_offset(fs).is_some() {
+            return_errno_with_message!(Errno::ENOSPC, "the root directory is full");
+        }
+        let cluster_size = fs.cluster_size();
+        let old_size = self.size();
+        let new_size = old_size + cluster_size;
+        if new_size > MAX_DIR_SIZE {
+            return_errno_with_message!(Errno::ENOSPC, "the directory is full");
+        }
+
+        let last = self.clusters.read().last().copied();
+        let new_clusters = fs.alloc_clusters(last, 1)?;
+        self.clusters.write().extend_from_slice(&new_clusters);
+        self.page_cache.pages().resize(new_size)?;
+        self.page_cache
+            .pages()
+            .write_bytes(old_size, &vec![0u8; cluster_size])?;
+        self.size.store(new_size, Ordering::Release);
+        if last.is_none() {
+            self.write_dentry(&self.inner.read())?;
+        }
+        Ok(())
+    }
+
+    /// Writes the dentries of a new entry into the directory, which grows if there is
+    /// no room for them, and returns the offset of the short dentry.
+    fn insert_dentries(&self, fs: &Arc<VfatFS>, dentries: &[[u8; DENTRY_SIZE]]) -> Result<usize> {
+        let (data, start) = loop {
+            let data = self.read_dir(fs)?;
+            if let Some(start) = find_free_dentries(&data, dentries.len()) {
+                break (data, start);
+            }
+            self.grow_dir(fs)?;
+        };
+        let end = start + dentries.len() * DENTRY_SIZE;
+        self.write_dir(fs, start, &dentries.concat())?;
+        // The dentries after the end of the directory may be garbage, so the end is marked again.
+        if end > end_of_dir(&data) && end < data.len() && data[end] != 0 {
+            self.write_dir(fs, end, &[0])?;
+        }
+        Ok(end - DENTRY_SIZE)
+    }
+
+    /// Removes the entry from the directory.
+    ///
+    /// The clusters of the entry are freed once its inode is no longer used.
+    fn remove_entry(&self, fs: &Arc<VfatFS>, entry: &DirEntry) -> Result<()> {
+        let inode = self.get_inode(fs, entry)?;
+        for offset in entry.dentry_offsets() {
+            self.write_dir(fs, offset, &[DELETED])?;
+        }
+        fs.remove_inode((self.ino, entry.pos));
+        inode.inner.write().is_deleted = true;
+        Ok(())
+    }
+
+    /// Writes `.` and `..` into the new directory.
+    fn init_dir(&self, fs: &VfatFS, dentry: &RawDentry, parent_cluster: ClusterId) -> Result<()> {
+        let mut dot = *dentry;
+        dot.name = DOT_NAME;
+        dot.case = 0;
+        dot.size = 0;
+        dot.set_first_cluster(self.first_cluster());
+        let mut dotdot = dot;
+        dotdot.name = DOTDOT_NAME;
+        dotdot.set_first_cluster(parent_cluster);
+
+        let mut data = vec![0u8; fs.cluster_size()];
+        data[..DENTRY_SIZE].copy_from_slice(dot.as_bytes());
+        data[DENTRY_SIZE..2 * DENTRY_SIZE].copy_from_slice(dotdot.as_bytes());
+        self.page_cache.pages().write_bytes(0, &data)?;
+        Ok(())
+    }
+
+    /// Points `..` of the directory to the new parent directory.
+    fn set_dotdot(&self, parent_cluster: ClusterId) -> Result<()> {
+        if self.size() < 2 * DENTRY_SIZE {
+            return Ok(());
+        }
+        let mut bytes = [0u8; DENTRY_SIZE];
+        self.page_cache
+            .pages()
+            .read_bytes(DENTRY_SIZE, &mut bytes)?;
+        let mut dotdot = RawDentry::from_bytes(&bytes);
+        if dotdot.name != DOTDOT_NAME {
+            return Ok(());
+        }
+        dotdot.set_first_cluster(parent_cluster);
+        self.page_cache
+            .pages()
+            .write_bytes(DENTRY_SIZE, dotdot.as_bytes())?;
+        Ok(())
+    }
+
+    /// Checks that the directory is neither `dir` nor one of its descendants.
+    fn check_not_within(&self, dir: &VfatInode) -> Result<()> {
+        let mut current = self.this();
+        loop {
+            if current.ino == dir.ino {
+                return_errno_with_message!(Errno::EINVAL, "cannot move a directory into itself");
+            }
+            let parent = match current.inner.read().location.as_ref() {
+                Some(location) => location.parent.clone(),
+                None => return Ok(()),
+            };
+            current = parent;
+        }
+    }
+
+    /// Returns the dentry to be written back, with the current first cluster and size.
+    fn current_dentry(&self, inner: &InodeInner) -> RawDentry {
+        let mut dentry = inner.dentry;
+        dentry.set_first_cluster(self.first_cluster());
+        dentry.size = if self.is_dir() { 0 } else { self.size() as u32 };
+        dentry
+    }
+
+    /// Writes the dentry back to the parent directory.
+    fn write_dentry(&self, inner: &InodeInner) -> Result<()> {
+        if inner.is_deleted {
+            return Ok(());
+        }
+        let Some(location) = inner.location.as_ref() else {
+            return Ok(());
+        };
+        let dentry = self.current_dentry(inner);
+        location
+            .parent
+            .write_dir(&self.vfat_fs(), location.pos, dentry.as_bytes())
+    }
+
+    fn update_mtime(&self) -> Result<()> {
+        let now = DosTimestamp::now()?;
+        let mut inner = self.inner.write();
+        inner.dentry.set_write_time(now);
+        self.write_dentry(&inner)
+    }
+
+    /// Updates the access time, which is written back only if the date changes.
+    fn update_atime(&self) -> Result<()> {
+        let now = DosTimestamp::now()?;
+        if self.inner.read().dentry.access_date == now.date {
+            return Ok(());
+        }
+        let mut inner = self.inner.write();
+        inner.dentry.set_access_time(now);
+        self.write_dentry(&inner)
+    }
+
+    fn make_mode(&self, inner: &InodeInner) -> InodeMode {
+        let mode = InodeMode::from_bits_truncate(0o777);
+        if !self.is_dir() && inner.dentry.attr().contains(FatAttr::READONLY) {
+            mode - (InodeMode::S_IWUSR | InodeMode::S_IWGRP | InodeMode::S_IWOTH)
+        } else {
+            mode
+        }
+    }
+
+    /// Expands the file to `new_size`, whose bytes beyond the old size read as zeros,
+    /// except for those in the pages from `overwrite_start`, which are to be overwritten
+    /// by the caller.
+    ///
+    /// The size is left to be updated by the caller.
+    fn expand(&self, fs: &VfatFS, new_size: usize, overwrite_start: usize) -> Result<()> {
+        let old_size = self.size();
+        let num_clusters = new_size.div_ceil(fs.cluster_size());
+        let (num_allocated, last) = {
+            let clusters = self.clusters.read();
+            (clusters.len(), clusters.last().copied())
+        };
+        if num_clusters > num_allocated {
+            let new_clusters = fs.alloc_clusters(last, num_clusters - num_allocated)?;
+            self.clusters.write().extend_from_slice(&new_clusters);
+        }
+        self.page_cache.pages().resize(new_size)?;
+
+        // The tail of the last page is cached, which may hold stale data.
+        let page_end = old_size.align_up(PAGE_SIZE);
+        if page_end > old_size {
+            let len = page_end.min(new_size) - old_size;
+            self.page_cache
+                .pages()
+                .write_bytes(old_size, &vec![0u8; len])?;
+        }
+        // The pages beyond are read from the device once the size is updated.
+        let zero_end = overwrite_start
+            .align_down(PAGE_SIZE)
+            .min(new_size.align_up(PAGE_SIZE));
+        if zero_end > page_end {
+            self.zero_on_disk(page_end..zero_end)?;
+        }
+        Ok(())
+    }
+
+    fn shrink(&self, fs: &VfatFS, new_size: usize) -> Result<()> {
+        let old_size = self.size();
+        // The size is updated first, so that the dropped pages are not written back.
+        self.size.store(new_size, Ordering::Release);
+        self.page_cache.pages().resize(new_size)?;
+        self.page_cache
+            .discard_range(new_size.align_up(PAGE_SIZE)..old_size.align_up(PAGE_SIZE));
+
+        let num_clusters = new_size.div_ceil(fs.cluster_size());
+        let (last, freed) = {
+            let mut clusters = self.clusters.write();
+            let freed = clusters.split_off(num_clusters.min(clusters.len()));
+            (clusters.last().copied(), freed)
+        };
+        if !freed.is_empty() {
+            fs.dealloc_clusters(last, &freed)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the dirty pages back.
+    pub(super) fn sync_data_pages(&self) -> Result<()> {
+        self.page_cache.evict_range(0..self.size())
+    }
+
+    /// Writes the page holding the dentry back.
+    fn sync_dentry(&self) -> Result<()> {
+        let inner = self.inner.read();
+        match inner.location.as_ref() {
+            Some(location) if !inner.is_deleted => location
+                .parent
+                .page_cache
+                .evict_range(location.pos..location.pos + DENTRY_SIZE),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Returns the short name, its case, and the long name dentries of a new entry.
+fn make_names(name: &str, entries: &[DirEntry]) -> Result<([u8; 11], u8, Vec<[u8; DENTRY_SIZE]>)> {
+    if let Some((short_name, case)) = to_short_name(name) {
+        return Ok((short_name, case, Vec::new()));
+    }
+    let short_name = gen_short_name(name, &short_names(entries))?;
+    Ok((
+        short_name,
+        0,
+        long_name_dentries(name, checksum(&short_name)),
+    ))
+}
+
+/// Converts the time to a DOS timestamp, which is clamped to the range that FAT supports.
+fn to_dos_timestamp(time: Duration) -> DosTimestamp {
+    DosTimestamp::from_duration(time.clamp(DOS_TIME_MIN, DOS_TIME_MAX)).unwrap_or_default()
+}
+
+fn to_dentry_bytes(dentry: &RawDentry) -> [u8; DENTRY_SIZE] {
+    let mut bytes = [0u8; DENTRY_SIZE];
+    bytes.copy_from_slice(dentry.as_bytes());
+    bytes
+}
+
+impl PageCacheBackend for VfatInode {
+    fn read_page(&self, idx: usize, frame: &Frame) -> Result<BioWaiter> {
+        self.submit_page_io(BioType::Read, idx, frame)
+    }
+
+    fn write_page(&self, idx: usize, frame: &Frame) -> Result<BioWaiter> {
+        self.submit_page_io(BioType::Write, idx, frame)
+    }
+
+    fn npages(&self) -> usize {
+        self.size().div_ceil(PAGE_SIZE)
+    }
+}
+
+impl Drop for VfatInode {
+    fn drop(&mut self) {
+        if !self.inner.read().is_deleted {
+            return;
+        }
+        let Some(fs) = self.fs.upgrade() else {
+            return;
+        };
+        let clusters = core::mem::take(&mut *self.clusters.write());
+        if clusters.is_empty() {
+            return;
+        }
+        if let Err(err) = fs.dealloc_clusters(None, &clusters) {
+            warn!("failed to free the clusters of a deleted inode: {:?}", err);
+        }
+    }
+}
+
+impl Inode for VfatInode {
+    fn size(&self) -> usize {
+        self.size.load(Ordering::Acquire)
+    }
+
+    fn resize(&self, new_size: usize) -> Result<()> {
+        if self.is_dir() {
+            return_errno!(Errno::EISDIR);
+        }
+        if new_size > MAX_FILE_SIZE {
+            return_errno_with_message!(Errno::EFBIG, "the file is too large for FAT");
+        }
+        let fs = self.vfat_fs();
+        let _fs_guard = fs.lock();
+        let old_size = self.size();
+        if new_size > old_size {
+            self.expand(&fs, new_size, new_size.align_up(PAGE_SIZE))?;
+            self.size.store(new_size, Ordering::Release);
+        } else if new_size < old_size {
+            self.shrink(&fs, new_size)?;
+        }
+        self.update_mtime()
+    }
+
+    fn metadata(&self) -> Metadata {
+        let fs = self.vfat_fs();
+        let inner = self.inner.read();
+        let blk_size = fs.sector_size();
+        let nlinks = if self.is_dir() {
+            // A directory is linked by its parent, `.` and `..` of its subdirectories.
+            let num_subdirs = self.read_dir(&fs).map_or(0, |data| {
+                parse_dir(&data)
+                    .iter()
+                    .filter(|entry| entry.dentry.is_dir())
+                    .count()
+            });
+            num_subdirs + 2
+        } else {
+            1
+        };
+        Metadata {
+            dev: 0,
+            ino: self.ino,
+            size: self.size(),
+            blk_size,
+            blocks: self.clusters.read().len() * fs.cluster_size() / blk_size,
+            atime: inner.dentry.access_time(),
+            mtime: inner.dentry.write_time(),
+            // FAT does not record the change time.
+            ctime: inner.dentry.write_time(),
+            type_: self.type_,
+            mode: self.make_mode(&inner),
+            nlinks,
+            uid: Uid::new_root(),
+            gid: Gid::new_root(),
+            rdev: 0,
+        }
+    }
+
+    fn ino(&self) -> u64 {
+        self.ino
+    }
+
+    fn type_(&self) -> InodeType {
+        self.type_
+    }
+
+    fn mode(&self) -> Result<InodeMode> {
+        Ok(self.make_mode(&self.inner.read()))
+    }
+
+    fn set_mode(&self, mode: InodeMode) -> Result<()> {
+        // Only the write permission of files can be recorded, as the READONLY attribute.
+        if self.is_dir() {
+            return Ok(());
+        }
+        let mut inner = self.inner.write();
+        let mut attr = inner.dentry.attr();
+        attr.set(FatAttr::READONLY, !mode.is_writable());
+        inner.dentry.attr = attr.bits() as u8;
+        self.write_dentry(&inner)
+    }
+
+    fn owner(&self) -> Result<Uid> {
+        Ok(Uid::new_root())
+    }
+
+    fn set_owner(&self, _uid: Uid) -> Result<()> {
+        // Pass through.
+        Ok(())
+    }
+
+    fn group(&self) -> Result<Gid> {
+        Ok(Gid::new_root())
+    }
+
+    fn set_group(&self, _gid: Gid) -> Result<()> {
+        // Pass through.
+        Ok(())
+    }
+
+    fn atime(&self) -> Duration {
+        self.inner.read().dentry.access_time()
+    }
+
+    fn set_atime(&self, time: Duration) {
+        let mut inner = self.inner.write();
+        inner.dentry.set_access_time(to_dos_timestamp(time));
+        let _ = self.write_dentry(&inner);
+    }
+
+    fn mtime(&self) -> Duration {
+        self.inner.read().dentry.write_time()
+    }
+
+    fn set_mtime(&self, time: Duration) {
+        let mut inner = self.inner.write();
+        inner.dentry.set_write_time(to_dos_timestamp(time));
+        let _ = self.write_dentry(&inner);
+    }
+
+    fn ctime(&self) -> Duration {
+        self.inner.read().dentry.write_time()
+    }
+
+    fn set_ctime(&self, _time: Duration) {
+        // FAT does not record the change time.
+    }
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        self.vfat_fs()
+    }
+
+    fn page_cache(&self) -> Option<Vmo<Full>> {
+        (!self.is_dir()).then(|| self.page_cache.pages().dup())
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        if self.is_dir() {
+            return_errno!(Errno::EISDIR);
+        }
+        let size = self.size();
+        let start = offset.min(size);
+        let end = offset.saturating_add(buf.len()).min(size);
+        self.page_cache
+            .pages()
+            .read_bytes(start, &mut buf[..end - start])?;
+        self.update_atime()?;
+        Ok(end - start)
+    }
+
+    // Direct I/O goes through the page cache and writes the pages back immediately,
+    // so that the cached pages never go stale.
+    fn read_direct_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        self.read_at(offset, buf)
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize> {
+        if self.is_dir() {
+            return_errno!(Errno::EISDIR);
+        }
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let new_end = offset
+            .checked_add(buf.len())
+            .filter(|end| *end <= MAX_FILE_SIZE)
+            .ok_or_else(|| Error::with_message(Errno::EFBIG, "the file is too large for FAT"))?;
+
+        // The fs lock is needed only to allocate clusters, so that writes can be parallelized.
+        let fs = self.vfat_fs();
+        let _fs_guard = (new_end > self.size()).then(|| fs.lock());
+        let is_expanding = new_end > self.size();
+        if is_expanding {
+            self.expand(&fs, new_end, offset)?;
+        }
+        self.page_cache.pages().write_bytes(offset, buf)?;
+        if is_expanding {
+            self.size.store(new_end, Ordering::Release);
+        }
+
+        self.update_mtime()?;
+        Ok(buf.len())
+    }
+
+    fn write_direct_at(&self, offset: usize, buf: &[u8]) -> Result<usize> {
+        let len = self.write_at(offset, buf)?;
+        self.page_cache.evict_range(offset..offset + len)?;
+        Ok(len)
+    }
+
+    fn create(&self, name: &str, type_: InodeType, _mode: InodeMode) -> Result<Arc<dyn Inode>> {
+        if !self.is_dir() {
+            return_errno!(Errno::ENOTDIR);
+        }
+        let attr = match type_ {
+            InodeType::File => FatAttr::ARCHIVE,
+            InodeType::Dir => FatAttr::DIRECTORY,
+            _ => {
+                return_errno_with_message!(Errno::EPERM, "FAT only supports files and directories")
+            }
+        };
+        let name = check_name(name)?;
+
+        let fs = self.vfat_fs();
+        let _fs_guard = fs.lock();
+        let entries = parse_dir(&self.read_dir(&fs)?);
+        if entries.iter().any(|entry| entry.matches(name)) {
+            return_errno!(Errno::EEXIST);
+        }
+        let (short_name, case, mut dentries) = make_names(name, &entries)?;
+
+        let now = DosTimestamp::now()?;
+        let mut dentry = RawDentry::new(short_name, case, attr);
+        dentry.set_create_time(now);
+        dentry.set_write_time(now);
+        dentry.set_access_time(now);
+
+        let new_inode = if type_ == InodeType::Dir {
+            let clusters = fs.alloc_clusters(None, 1)?;
+            Self::new(
+                &fs,
+                fs.alloc_ino(),
+                dentry,
+                None,
+                clusters,
+                fs.cluster_size(),
+            )
+        } else {
+            Self::new(&fs, fs.alloc_ino(), dentry, None, Vec::new(), 0)
+        };
+
+        let init_result = if type_ == InodeType::Dir {
+            new_inode.init_dir(&fs, &dentry, self.cluster_for_dotdot())
+        } else {
+            Ok(())
+        };
+        let insert_result = init_result.and_then(|_| {
+            dentries.push(to_dentry_bytes(
+                &new_inode.current_dentry(&new_inode.inner.read()),
+            ));
+            self.insert_dentries(&fs, &dentries)
+        });
+        let pos = match insert_result {
+            Ok(pos) => pos,
+            Err(err) => {
+                // The clusters are freed when the inode is dropped.
+                new_inode.inner.write().is_deleted = true;
+                return Err(err);
+            }
+        };
+        new_inode.inner.write().location = Some(Location {
+            parent: self.this(),
+            pos,
+        });
+        fs.insert_inode((self.ino, pos), new_inode.clone());
+
+        self.update_mtime()?;
+        Ok(new_inode)
+    }
+
+    fn mknod(
+        &self,
+        _name: &str,
+        _mode: InodeMode,
+        _dev: Arc<dyn Device>,
+    ) -> Result<Arc<dyn Inode>> {
+        return_errno_with_message!(Errno::EPERM, "FAT does not support device files")
+    }
+
+    fn readdir_at(&self, offset: usize, visitor: &mut dyn DirentVisitor) -> Result<usize> {
+        if !self.is_dir() {
+            return_errno!(Errno::ENOTDIR);
+        }
+        let fs = self.vfat_fs();
+        let _fs_guard = fs.lock();
+        let parent_ino = self
+            .inner
+            .read()
+            .location
+            .as_ref()
+            .map_or(self.ino, |location| location.parent.ino);
+        let entries = parse_dir(&self.read_dir(&fs)?);
+
+        // The offsets are derived from the positions of the dentries, which are stable
+        // when the entries before are removed.
+        let try_visit = |idx: &mut usize, visitor: &mut dyn DirentVisitor| -> Result<()> {
+            if *idx == 0 {
+                visitor.visit(".", self.ino, InodeType::Dir, *idx)?;
+                *idx += 1;
+            }
+            if *idx == 1 {
+                visitor.visit("..", parent_ino, InodeType::Dir, *idx)?;
+                *idx += 1;
+            }
+            for entry in entries.iter() {
+                let entry_idx = entry.pos / DENTRY_SIZE + 2;
+                if entry_idx < *idx {
+                    continue;
+                }
+                let inode = self.get_inode(&fs, entry)?;
+                visitor.visit(&entry.name, inode.ino, inode.type_, entry_idx)?;
+                *idx = entry_idx + 1;
+            }
+            Ok(())
+        };
+
+        let mut iterate_idx = offset;
+        match try_visit(&mut iterate_idx, visitor) {
+            Err(e) if iterate_idx == offset => Err(e),
+            _ => Ok(iterate_idx - offset),
+        }
+    }
+
+    fn link(&self, _old: &Arc<dyn Inode>, _name: &str) -> Result<()> {
+        return_errno_with_message!(Errno::EPERM, "FAT does not support hard links")
+    }
+
+    fn unlink(&self, name: &str) -> Result<()> {
+        if !self.is_dir() {
+            return_errno!(Errno::ENOTDIR);
+        }
+        if name == "." || name == ".." {
+            return_errno!(Errno::EISDIR);
+        }
+        let fs = self.vfat_fs();
+        let _fs_guard = fs.lock();
+        let entry = self.find_entry(&fs, name)?;
+        if entry.dentry.is_dir() {
+            return_errno!(Errno::EISDIR);
+        }
+        self.remove_entry(&fs, &entry)?;
+        self.update_mtime()
+    }
+
+    fn rmdir(&self, name: &str) -> Result<()> {
+        if !self.is_dir() {
+            return_errno!(Errno::ENOTDIR);
+        }
+        if name == "." {
+            return_errno_with_message!(Errno::EINVAL, "rmdir on .");
+        }
+        if name == ".." {
+            return_errno_with_message!(Errno::ENOTEMPTY, "rmdir on ..");
+        }
+        let fs = self.vfat_fs();
+        let _fs_guard = fs.lock();
+        let entry = self.find_entry(&fs, name)?;
+        if !entry.dentry.is_dir() {
+            return_errno!(Errno::ENOTDIR);
+        }
+        if !self.get_inode(&fs, &entry)?.is_empty_dir(&fs)? {
+            return_errno!(Errno::ENOTEMPTY);
+        }
+        self.remove_entry(&fs, &entry)?;
+        self.update_mtime()
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>> {
+        if !self.is_dir() {
+            return_errno!(Errno::ENOTDIR);
+        }
+        if name.encode_utf16().count() > MAX_NAME_LEN {
+            return_errno!(Errno::ENAMETOOLONG);
+        }
+        let fs = self.vfat_fs();
+        let _fs_guard = fs.lock();
+        let entry = self.find_entry(&fs, name)?;
+        Ok(self.get_inode(&fs, &entry)?)
+    }
+
+    fn rename(&self, old_name: &str, target: &Arc<dyn Inode>, new_name: &str) -> Result<()> {
+        if old_name == "." || old_name == ".." || new_name == "." || new_name == ".." {
+            return_errno!(Errno::EISDIR);
+        }
+        let Some(target) = target.downcast_ref::<VfatInode>() else {
+            return_errno_with_message!(Errno::EXDEV, "not a vfat inode");
+        };
+        if !self.is_dir() || !target.is_dir() {
+            return_errno!(Errno::ENOTDIR);
+        }
+        let new_name = check_name(new_name)?;
+
+        let fs = self.vfat_fs();
+        let _fs_guard = fs.lock();
+        let entry = self.find_entry(&fs, old_name)?;
+        let inode = self.get_inode(&fs, &entry)?;
+        if inode.is_dir() {
+            target.check_not_within(&inode)?;
+        }
+
+        let is_same_dir = self.ino == target.ino;
+        let target_entries = parse_dir(&target.read_dir(&fs)?);
+        if let Some(existing) = target_entries
+            .iter()
+            .find(|existing| existing.matches(new_name))
+        {
+            if is_same_dir && existing.pos == entry.pos {
+                // The file is renamed to itself, with the case of the name changed or not.
+                if existing.name == new_name {
+                    return Ok(());
+                }
+            } else {
+                let existing_inode = target.get_inode(&fs, existing)?;
+                match (inode.is_dir(), existing_inode.is_dir()) {
+                    (true, false) => return_errno!(Errno::ENOTDIR),
+                    (false, true) => return_errno!(Errno::EISDIR),
+                    (true, true) if !existing_inode.is_empty_dir(&fs)? => {
+                        return_errno!(Errno::ENOTEMPTY)
+                    }
+                    _ => {}
+                }
+                target.remove_entry(&fs, existing)?;
+            }
+        }
+
+        let target_entries = parse_dir(&target.read_dir(&fs)?);
+        let (short_name, case, mut dentries) = make_names(new_name, &target_entries)?;
+        {
+            let mut inner = inode.inner.write();
+            let mut dentry = inode.current_dentry(&inner);
+            dentry.name = short_name;
+            dentry.case = case;
+            dentries.push(to_dentry_bytes(&dentry));
+            // The new dentries are written before the old ones are removed.
+            let pos = target.insert_dentries(&fs, &dentries)?;
+            for offset in entry.dentry_offsets() {
+                self.write_dir(&fs, offset, &[DELETED])?;
+            }
+            inner.dentry.name = short_name;
+            inner.dentry.case = case;
+            inner.location = Some(Location {
+                parent: target.this(),
+                pos,
+            });
+            fs.remove_inode((self.ino, entry.pos));
+            fs.insert_inode((target.ino, pos), inode.clone());
+        }
+        if inode.is_dir() && !is_same_dir {
+            inode.set_dotdot(target.cluster_for_dotdot())?;
+        }
+
+        self.update_mtime()?;
+        if !is_same_dir {
+            target.update_mtime()?;
+        }
+        Ok(())
+    }
+
+    fn read_link(&self) -> Result<String> {
+        return_errno_with_message!(Errno::EINVAL, "FAT does not support symbolic links")
+    }
+
+    fn write_link(&self, _target: &str) -> Result<()> {
+        return_errno_with_message!(Errno::EINVAL, "FAT does not support symbolic links")
+    }
+
+    fn ioctl(&self, _cmd: IoctlCmd, _arg: usize) -> Result<i32> {
+        return_errno_with_message!(Errno::EINVAL, "unsupported operation")
+    }
+
+    fn sync_all(&self) -> Result<()> {
+        let fs = self.vfat_fs();
+        self.sync_data_pages()?;
+        self.sync_dentry()?;
+        fs.sync_meta()?;
+        fs.flush_device()
+    }
+
+    fn sync_data(&self) -> Result<()> {
+        // The size and the clusters are needed to read the data back.
+        self.sync_all()
+    }
+}
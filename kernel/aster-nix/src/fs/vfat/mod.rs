@@ -0,0 +1,196 @@
+// SPDX-License-Identifier: MPL-2.0
+
+mod boot_sector;
+mod dentry;
+mod fat;
+mod fs;
+mod inode;
+
+pub use fs::VfatFS;
+pub use inode::VfatInode;
+
+#[cfg(ktest)]
+mod test {
+    use alloc::fmt::Debug;
+
+    use aster_block::{
+        bio::{BioEnqueueError, BioStatus, BioType, SubmittedBio},
+        BlockDevice, SECTOR_SIZE,
+    };
+    use ostd::{
+        mm::{FrameAllocOptions, Segment, VmIo},
+        prelude::*,
+    };
+
+    use crate::{
+        fs::{
+            utils::{Inode, InodeMode, InodeType},
+            vfat::VfatFS,
+        },
+        prelude::*,
+    };
+
+    /// A block device in memory, which is initialized with the vfat disk image.
+    struct VfatMemoryDisk(Segment);
+
+    impl Debug for VfatMemoryDisk {
+        fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+            f.debug_struct("VfatMemoryDisk")
+                .field("sectors_count", &self.nr_sectors())
+                .finish()
+        }
+    }
+
+    impl BlockDevice for VfatMemoryDisk {
+        fn enqueue(&self, bio: SubmittedBio) -> core::result::Result<(), BioEnqueueError> {
+            let mut cur_device_ofs = bio.sid_range().start.to_raw() as usize * SECTOR_SIZE;
+            for seg in bio.segments() {
+                let size = match bio.type_() {
+                    BioType::Read => seg
+                        .writer()
+                        .write(&mut self.0.reader().skip(cur_device_ofs)),
+                    BioType::Write => self
+                        .0
+                        .writer()
+                        .skip(cur_device_ofs)
+                        .write(&mut seg.reader()),
+                    _ => 0,
+                };
+                cur_device_ofs += size;
+            }
+            bio.complete(BioStatus::Complete);
+            Ok(())
+        }
+
+        fn max_nr_segments_per_bio(&self) -> usize {
+            usize::MAX
+        }
+
+        fn nr_sectors(&self) -> usize {
+            self.0.nframes() * (PAGE_SIZE / SECTOR_SIZE)
+        }
+    }
+
+    /// Vfat disk image
+    static VFAT_IMAGE: &[u8] = include_bytes!("../../../../../test/build/vfat.img");
+
+    fn load_vfat() -> Arc<VfatFS> {
+        let segment = FrameAllocOptions::new(VFAT_IMAGE.len() / PAGE_SIZE)
+            .is_contiguous(true)
+            .uninit(true)
+            .alloc_contiguous()
+            .unwrap();
+        segment.write_bytes(0, VFAT_IMAGE).unwrap();
+
+        let fs = VfatFS::open(Arc::new(VfatMemoryDisk(segment)));
+        assert!(fs.is_ok(), "Fs failed to init:{:?}", fs.unwrap_err());
+        fs.unwrap()
+    }
+
+    fn list_dir(dir: &Arc<dyn Inode>) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        dir.readdir_at(0, &mut names).unwrap();
+        names
+    }
+
+    #[ktest]
+    fn new_vfat() {
+        let fs = load_vfat();
+        let root = fs.root_inode() as Arc<dyn Inode>;
+        assert_eq!(list_dir(&root), vec![".", ".."]);
+    }
+
+    #[ktest]
+    fn create_and_lookup() {
+        let fs = load_vfat();
+        let root = fs.root_inode() as Arc<dyn Inode>;
+
+        let long_name = "A File With A Long Name.txt";
+        let file = root
+            .create(long_name, InodeType::File, InodeMode::all())
+            .unwrap();
+        root.create("dir", InodeType::Dir, InodeMode::all())
+            .unwrap();
+
+        // The names are case-insensitive but case-preserving.
+        let found = root.lookup(&long_name.to_lowercase()).unwrap();
+        assert_eq!(found.ino(), file.ino());
+        assert!(root.lookup("DIR").unwrap().type_() == InodeType::Dir);
+        assert_eq!(list_dir(&root), vec![".", "..", long_name, "dir"]);
+
+        assert!(root
+            .create(
+                "a file with a long name.TXT",
+                InodeType::File,
+                InodeMode::all()
+            )
+            .is_err_and(|err| err.error() == Errno::EEXIST));
+        assert!(root
+            .create(&"x".repeat(256), InodeType::File, InodeMode::all())
+            .is_err_and(|err| err.error() == Errno::ENAMETOOLONG));
+        assert!(root
+            .create("a?b", InodeType::File, InodeMode::all())
+            .is_err_and(|err| err.error() == Errno::EINVAL));
+    }
+
+    #[ktest]
+    fn write_and_read() {
+        let fs = load_vfat();
+        let root = fs.root_inode() as Arc<dyn Inode>;
+        let file = root
+            .create("data", InodeType::File, InodeMode::all())
+            .unwrap();
+
+        // Writes across a hole, which should be read as zeros.
+        let hole_size = fs.cluster_size() * 3 + 100;
+        let data: Vec<u8> = (0..PAGE_SIZE * 2).map(|i| i as u8).collect();
+        file.write_at(hole_size, &data).unwrap();
+        assert_eq!(file.size(), hole_size + data.len());
+
+        let mut buf = vec![0xFFu8; hole_size + data.len()];
+        file.read_at(0, &mut buf).unwrap();
+        assert!(buf[..hole_size].iter().all(|byte| *byte == 0));
+        assert_eq!(&buf[hole_size..], &data[..]);
+
+        // Shrinks and expands again, and the truncated part should be zeros.
+        file.resize(hole_size + 10).unwrap();
+        file.resize(hole_size + PAGE_SIZE).unwrap();
+        let mut buf = vec![0xFFu8; PAGE_SIZE];
+        file.read_at(hole_size, &mut buf).unwrap();
+        assert_eq!(&buf[..10], &data[..10]);
+        assert!(buf[10..].iter().all(|byte| *byte == 0));
+    }
+
+    #[ktest]
+    fn rename_and_remove() {
+        let fs = load_vfat();
+        let root = fs.root_inode() as Arc<dyn Inode>;
+        let dir = root
+            .create("dir", InodeType::Dir, InodeMode::all())
+            .unwrap();
+        let file = root
+            .create("file", InodeType::File, InodeMode::all())
+            .unwrap();
+        file.write_at(0, b"hello").unwrap();
+
+        root.rename("file", &dir, "Renamed File").unwrap();
+        assert!(root.lookup("file").is_err());
+        let renamed = dir.lookup("renamed file").unwrap();
+        let mut buf = [0u8; 5];
+        renamed.read_at(0, &mut buf).unwrap();
+        assert_eq!(&buf, b"hello");
+
+        assert!(root
+            .rmdir("dir")
+            .is_err_and(|err| err.error() == Errno::ENOTEMPTY));
+        assert!(dir
+            .rename("Renamed File", &root, "dir")
+            .is_err_and(|err| err.error() == Errno::EISDIR));
+        root.rename("dir", &root, "moved").unwrap();
+        assert_eq!(root.lookup("MOVED").unwrap().ino(), dir.ino());
+
+        dir.unlink("Renamed File").unwrap();
+        root.rmdir("moved").unwrap();
+        assert_eq!(list_dir(&root), vec![".", ".."]);
+    }
+}
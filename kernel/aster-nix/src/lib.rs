@@ -25,7 +25,7 @@
 
 use ostd::{
     arch::qemu::{exit_qemu, QemuExitCode},
-    boot,
+    boot::{self, kcmdline::KCmdlineArg},
 };
 use process::Process;
 
@@ -45,6 +45,7 @@ extern crate controlled;
 extern crate getset;
 
 pub mod arch;
+mod cgroup;
 pub mod console;
 pub mod cpu;
 pub mod device;
@@ -52,6 +53,7 @@ pub mod driver;
 pub mod error;
 pub mod events;
 pub mod fs;
+mod module;
 pub mod net;
 pub mod prelude;
 mod process;
@@ -86,6 +88,7 @@ fn init_thread() {
     // Work queue should be initialized before interrupt is enabled,
     // in case any irq handler uses work queue as bottom half
     thread::work_queue::init();
+    sched::loadavg::init();
     // FIXME: Remove this if we move the step of mounting
     // the filesystems to be done within the init process.
     ostd::trap::enable_local();
@@ -106,14 +109,7 @@ fn init_thread() {
 
     print_banner();
 
-    let karg = boot::kernel_cmdline();
-
-    let initproc = Process::spawn_user_process(
-        karg.get_initproc_path().unwrap(),
-        karg.get_initproc_argv().to_vec(),
-        karg.get_initproc_envp().to_vec(),
-    )
-    .expect("Run init process failed.");
+    let initproc = spawn_init_process(boot::kernel_cmdline());
     // Wait till initproc become zombie.
     while !initproc.is_zombie() {
         // We don't have preemptive scheduler now.
@@ -130,6 +126,31 @@ fn init_thread() {
     exit_qemu(exit_code);
 }
 
+/// The paths of the init process to try if it is not given, like Linux.
+const DEFAULT_INIT_PATHS: &[&str] = &["/sbin/init", "/etc/init", "/bin/init", "/bin/sh"];
+
+/// Spawns the init process given by `rdinit=` or `init=`.
+///
+/// If neither is given, the paths in `DEFAULT_INIT_PATHS` are tried in order,
+/// and the path is prepended to the arguments as `argv[0]`.
+fn spawn_init_process(karg: &KCmdlineArg) -> Arc<Process> {
+    let argv = karg.get_initproc_argv().to_vec();
+    let envp = karg.get_initproc_envp().to_vec();
+    if let Some(path) = karg.get_rdinit_path().or(karg.get_initproc_path()) {
+        return Process::spawn_user_process(path, argv, envp).expect("Run init process failed.");
+    }
+
+    for path in DEFAULT_INIT_PATHS {
+        let mut argv_with_path = vec![CString::new(*path).unwrap()];
+        argv_with_path.extend(argv.iter().cloned());
+        match Process::spawn_user_process(path, argv_with_path, envp.clone()) {
+            Ok(process) => return process,
+            Err(e) => debug!("cannot run {} as the init process: {:?}", path, e),
+        }
+    }
+    panic!("No working init found.");
+}
+
 /// first process never return
 #[controlled]
 pub fn run_first_process() -> ! {
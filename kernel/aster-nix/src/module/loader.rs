@@ -0,0 +1,632 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The loader of the relocatable ELF objects of modules on x86-64.
+//!
+//! The allocated sections of a module are placed in a [`ModuleMemory`] in three groups,
+//! each of which starts at a page boundary: the code followed by a procedure linkage
+//! table (PLT), the read-only data, and the writable data followed by a global offset
+//! table (GOT). The PLT and the GOT have one entry for each symbol. Once the module is
+//! relocated, the groups are mapped as executable, read-only and writable respectively.
+//!
+//! The undefined symbols are resolved against the symbol table of the kernel, so the
+//! modules should be built with the same compiler, flags and dependencies as the kernel,
+//! whose symbols have the same mangled names then.
+//!
+//! Since the module memory is within 2 GiB of the kernel code, the modules can be
+//! built with the same code model as the kernel.
+
+use core::mem::size_of;
+
+use align_ext::AlignExt;
+use ostd::mm::{ModuleMemory, VmIo};
+use xmas_elf::{
+    header,
+    sections::{SectionData, SectionHeader, ShType, SHF_ALLOC, SHF_EXECINSTR, SHF_WRITE},
+    symbol_table::{Binding, Entry},
+    ElfFile,
+};
+
+use crate::prelude::*;
+
+const SHN_UNDEF: u16 = 0;
+const SHN_ABS: u16 = 0xfff1;
+const SHN_COMMON: u16 = 0xfff2;
+
+const R_X86_64_NONE: u32 = 0;
+const R_X86_64_64: u32 = 1;
+const R_X86_64_PC32: u32 = 2;
+const R_X86_64_PLT32: u32 = 4;
+const R_X86_64_GOTPCREL: u32 = 9;
+const R_X86_64_32: u32 = 10;
+const R_X86_64_32S: u32 = 11;
+const R_X86_64_PC64: u32 = 24;
+const R_X86_64_GOTPCRELX: u32 = 41;
+const R_X86_64_REX_GOTPCRELX: u32 = 42;
+
+const GOT_ENTRY_SIZE: usize = 8;
+/// The size of a PLT entry, which is `jmp *0(%rip)` followed by the target address.
+const PLT_ENTRY_SIZE: usize = 16;
+
+/// The groups of the allocated sections, which are mapped with different permissions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SectionGroup {
+    Text,
+    ReadOnly,
+    Writable,
+}
+
+impl SectionGroup {
+    fn of(section: &SectionHeader) -> Self {
+        if section.flags() & SHF_EXECINSTR != 0 {
+            Self::Text
+        } else if section.flags() & SHF_WRITE != 0 {
+            Self::Writable
+        } else {
+            Self::ReadOnly
+        }
+    }
+}
+
+/// A module whose code and data are loaded and relocated, but not initialized yet.
+pub(super) struct LoadedModule {
+    pub memory: ModuleMemory,
+    /// The addresses of the constructors in the `.init_array` sections
+    pub init_functions: Vec<Vaddr>,
+}
+
+/// Returns the name of the component in the `.component_name` section of a module.
+pub(super) fn component_name(elf: &ElfFile) -> Result<String> {
+    for section in elf.section_iter() {
+        if section.get_name(elf) != Ok(".component_name") {
+            continue;
+        }
+        let name = section_data(elf, &section)?;
+        let name = core::str::from_utf8(name)
+            .map_err(|_| Error::with_message(Errno::ENOEXEC, "the component name is invalid"))?;
+        return Ok(name.trim_end_matches('\0').to_string());
+    }
+    return_errno_with_message!(Errno::ENOEXEC, "the module is not a component");
+}
+
+/// Loads a relocatable ELF object into the module memory and relocates it.
+///
+/// The undefined symbols are resolved by `resolve`. The code and the read-only data
+/// of the module are protected once the module is relocated.
+pub(super) fn load(elf: &ElfFile, resolve: impl Fn(&str) -> Option<Vaddr>) -> Result<LoadedModule> {
+    check_header(elf)?;
+
+    let sections: Vec<SectionHeader> = elf.section_iter().collect();
+    let (symtab_index, symbols) = symbol_table(elf, &sections)?;
+
+    // Lay out the code and the PLT, the read-only data, and the writable data and the GOT.
+    let mut section_offsets = vec![None; sections.len()];
+    let text_end = lay_out_group(&sections, SectionGroup::Text, 0, &mut section_offsets)?;
+    let plt_offset = text_end.align_up(PLT_ENTRY_SIZE);
+    let text = 0..(plt_offset + symbols.len() * PLT_ENTRY_SIZE).align_up(PAGE_SIZE);
+    let rodata_end = lay_out_group(
+        &sections,
+        SectionGroup::ReadOnly,
+        text.end,
+        &mut section_offsets,
+    )?;
+    let rodata = text.end..rodata_end.align_up(PAGE_SIZE);
+    let data_end = lay_out_group(
+        &sections,
+        SectionGroup::Writable,
+        rodata.end,
+        &mut section_offsets,
+    )?;
+    let got_offset = data_end.align_up(GOT_ENTRY_SIZE);
+    let size = got_offset + symbols.len() * GOT_ENTRY_SIZE;
+
+    let mut memory = ModuleMemory::alloc(size)?;
+    let base = memory.start_vaddr();
+    for (index, section) in sections.iter().enumerate() {
+        let Some(offset) = section_offsets[index] else {
+            continue;
+        };
+        // The memory is zeroed, so there is nothing to do for `.bss`.
+        if section.get_type() != Ok(ShType::NoBits) {
+            memory.write_bytes(offset, section_data(elf, section)?)?;
+        }
+    }
+
+    let symbol_addresses = symbols
+        .iter()
+        .enumerate()
+        .map(|(index, symbol)| symbol_address(elf, index, symbol, &section_offsets, base, &resolve))
+        .collect::<Result<Vec<_>>>()?;
+
+    let relocator = Relocator {
+        memory: &memory,
+        symbols,
+        symbol_addresses: &symbol_addresses,
+        got_offset,
+        plt_offset,
+    };
+    for section in sections.iter() {
+        match section.get_type() {
+            Ok(ShType::Rela) => {}
+            Ok(ShType::Rel) => {
+                return_errno_with_message!(Errno::ENOEXEC, "relocations without addends")
+            }
+            _ => continue,
+        }
+        if section.link() as usize != symtab_index {
+            return_errno_with_message!(Errno::ENOEXEC, "multiple symbol tables");
+        }
+        // The relocations of the sections that are not loaded, e.g., debug information,
+        // are ignored.
+        let target_index = section.info() as usize;
+        let Some(Some(target_offset)) = section_offsets.get(target_index) else {
+            continue;
+        };
+        let target_size = sections[target_index].size() as usize;
+
+        section_data(elf, section)?;
+        let Ok(SectionData::Rela64(relas)) = section.get_data(elf) else {
+            return_errno_with_message!(Errno::ENOEXEC, "the relocation section is invalid");
+        };
+        for rela in relas {
+            relocator.apply(
+                *target_offset,
+                target_size,
+                rela.get_offset() as usize,
+                rela.get_symbol_table_index() as usize,
+                rela.get_type(),
+                rela.get_addend() as i64,
+            )?;
+        }
+    }
+
+    let mut init_functions = Vec::new();
+    for (index, section) in sections.iter().enumerate() {
+        let Some(offset) = section_offsets[index] else {
+            continue;
+        };
+        let Ok(name) = section.get_name(elf) else {
+            continue;
+        };
+        if name != ".init_array" && !name.starts_with(".init_array.") {
+            continue;
+        }
+        for entry_offset in (0..section.size() as usize).step_by(size_of::<u64>()) {
+            let function = memory.read_val::<u64>(offset + entry_offset)? as Vaddr;
+            if !text.contains(&function.wrapping_sub(base)) {
+                return_errno_with_message!(Errno::ENOEXEC, "the constructor is not in the code");
+            }
+            init_functions.push(function);
+        }
+    }
+
+    memory.make_executable(text, rodata)?;
+    Ok(LoadedModule {
+        memory,
+        init_functions,
+    })
+}
+
+/// Lays out the allocated sections of the group from `start`, which is page-aligned,
+/// and returns the end of the last section.
+fn lay_out_group(
+    sections: &[SectionHeader],
+    group: SectionGroup,
+    start: usize,
+    section_offsets: &mut [Option<usize>],
+) -> Result<usize> {
+    let mut end = start;
+    for (index, section) in sections.iter().enumerate() {
+        if section.flags() & SHF_ALLOC == 0
+            || section.size() == 0
+            || SectionGroup::of(section) != group
+        {
+            continue;
+        }
+        let align = section.align().max(1) as usize;
+        if !align.is_power_of_two() || align > PAGE_SIZE {
+            return_errno_with_message!(Errno::ENOEXEC, "the section alignment is not supported");
+        }
+        end = end.align_up(align);
+        section_offsets[index] = Some(end);
+        end += section.size() as usize;
+    }
+    Ok(end)
+}
+
+fn check_header(elf: &ElfFile) -> Result<()> {
+    if elf.header.pt1.class() != header::Class::SixtyFour {
+        return_errno_with_message!(Errno::ENOEXEC, "the module is not 64-bit");
+    }
+    if elf.header.pt2.type_().as_type() != header::Type::Relocatable {
+        return_errno_with_message!(Errno::ENOEXEC, "the module is not relocatable");
+    }
+    if elf.header.pt2.machine().as_machine() != header::Machine::X86_64 {
+        return_errno_with_message!(Errno::ENOEXEC, "the module is not for x86-64");
+    }
+    Ok(())
+}
+
+/// Returns the section index and the entries of the only symbol table.
+fn symbol_table<'a>(
+    elf: &ElfFile<'a>,
+    sections: &[SectionHeader<'a>],
+) -> Result<(usize, &'a [xmas_elf::symbol_table::Entry64])> {
+    for (index, section) in sections.iter().enumerate() {
+        if section.get_type() != Ok(ShType::SymTab) {
+            continue;
+        }
+        section_data(elf, section)?;
+        let Ok(SectionData::SymbolTable64(symbols)) = section.get_data(elf) else {
+            return_errno_with_message!(Errno::ENOEXEC, "the symbol table is invalid");
+        };
+        return Ok((index, symbols));
+    }
+    return_errno_with_message!(Errno::ENOEXEC, "the module has no symbol table");
+}
+
+/// Returns the data of a section in the file, checking that it is in bounds.
+fn section_data<'a>(elf: &ElfFile<'a>, section: &SectionHeader<'a>) -> Result<&'a [u8]> {
+    let start = section.offset() as usize;
+    let end = start
+        .checked_add(section.size() as usize)
+        .ok_or(Error::with_message(
+            Errno::ENOEXEC,
+            "the section is too large",
+        ))?;
+    if end > elf.input.len() {
+        return_errno_with_message!(Errno::ENOEXEC, "the section is out of the file");
+    }
+    Ok(&elf.input[start..end])
+}
+
+fn symbol_address(
+    elf: &ElfFile,
+    index: usize,
+    symbol: &impl Entry,
+    section_offsets: &[Option<usize>],
+    base: Vaddr,
+    resolve: &impl Fn(&str) -> Option<Vaddr>,
+) -> Result<usize> {
+    let address = match symbol.shndx() {
+        // The first symbol is always the null symbol.
+        SHN_UNDEF if index == 0 => 0,
+        SHN_UNDEF => {
+            let name = symbol
+                .get_name(elf)
+                .map_err(|_| Error::with_message(Errno::ENOEXEC, "the symbol name is invalid"))?;
+            match resolve(name) {
+                Some(address) => address,
+                None if symbol.get_binding() == Ok(Binding::Weak) => 0,
+                None => {
+                    warn!("unknown symbol {} in the module", name);
+                    return_errno_with_message!(Errno::ENOENT, "unknown symbol in the module");
+                }
+            }
+        }
+        SHN_ABS => symbol.value() as usize,
+        SHN_COMMON => {
+            return_errno_with_message!(Errno::ENOEXEC, "common symbols are not supported")
+        }
+        // The symbols in the sections that are not loaded are never referenced by
+        // the loaded sections, so they are left as zero.
+        shndx => match section_offsets.get(shndx as usize) {
+            Some(Some(offset)) => base + offset + symbol.value() as usize,
+            _ => 0,
+        },
+    };
+    Ok(address)
+}
+
+struct Relocator<'a> {
+    memory: &'a ModuleMemory,
+    symbols: &'a [xmas_elf::symbol_table::Entry64],
+    symbol_addresses: &'a [usize],
+    got_offset: usize,
+    plt_offset: usize,
+}
+
+impl Relocator<'_> {
+    /// Applies a relocation at `offset` of the section at `section_offset` in the module memory.
+    fn apply(
+        &self,
+        section_offset: usize,
+        section_size: usize,
+        offset: usize,
+        symbol_index: usize,
+        type_: u32,
+        addend: i64,
+    ) -> Result<()> {
+        let Some(&symbol_address) = self.symbol_addresses.get(symbol_index) else {
+            return_errno_with_message!(Errno::ENOEXEC, "the relocation symbol is invalid");
+        };
+        let width = match type_ {
+            R_X86_64_NONE => return Ok(()),
+            R_X86_64_64 | R_X86_64_PC64 => size_of::<u64>(),
+            _ => size_of::<u32>(),
+        };
+        if offset
+            .checked_add(width)
+            .map_or(true, |end| end > section_size)
+        {
+            return_errno_with_message!(Errno::ENOEXEC, "the relocation is out of the section");
+        }
+
+        let place_offset = section_offset + offset;
+        let place = (self.memory.start_vaddr() + place_offset) as i64;
+        let symbol = symbol_address as i64;
+        match type_ {
+            R_X86_64_64 => self
+                .memory
+                .write_val(place_offset, &(symbol.wrapping_add(addend) as u64))?,
+            R_X86_64_PC64 => self.memory.write_val(
+                place_offset,
+                &(symbol.wrapping_add(addend).wrapping_sub(place) as u64),
+            )?,
+            R_X86_64_PC32 => self.write_i32(place_offset, symbol + addend - place)?,
+            R_X86_64_PLT32 => {
+                let target = if self.is_undefined(symbol_index) {
+                    self.plt_entry(symbol_index)? as i64
+                } else {
+                    symbol
+                };
+                self.write_i32(place_offset, target + addend - place)?
+            }
+            R_X86_64_GOTPCREL | R_X86_64_GOTPCRELX | R_X86_64_REX_GOTPCRELX => {
+                let got_entry = self.got_entry(symbol_index)? as i64;
+                self.write_i32(place_offset, got_entry + addend - place)?
+            }
+            R_X86_64_32 => {
+                let value = u32::try_from(symbol + addend).map_err(|_| {
+                    Error::with_message(Errno::ENOEXEC, "the relocation is out of range")
+                })?;
+                self.memory.write_val(place_offset, &value)?
+            }
+            R_X86_64_32S => self.write_i32(place_offset, symbol + addend)?,
+            _ => {
+                warn!("unsupported relocation type {} in the module", type_);
+                return_errno_with_message!(Errno::ENOEXEC, "unsupported relocation type");
+            }
+        }
+        Ok(())
+    }
+
+    fn write_i32(&self, offset: usize, value: i64) -> Result<()> {
+        let value = i32::try_from(value)
+            .map_err(|_| Error::with_message(Errno::ENOEXEC, "the relocation is out of range"))?;
+        self.memory.write_val(offset, &value)?;
+        Ok(())
+    }
+
+    fn is_undefined(&self, symbol_index: usize) -> bool {
+        self.symbols[symbol_index].shndx() == SHN_UNDEF
+    }
+
+    /// Fills the GOT entry of a symbol and returns its address.
+    fn got_entry(&self, symbol_index: usize) -> Result<Vaddr> {
+        let offset = self.got_offset + symbol_index * GOT_ENTRY_SIZE;
+        self.memory
+            .write_val(offset, &(self.symbol_addresses[symbol_index] as u64))?;
+        Ok(self.memory.start_vaddr() + offset)
+    }
+
+    /// Fills the PLT entry of a symbol and returns its address.
+    fn plt_entry(&self, symbol_index: usize) -> Result<Vaddr> {
+        let offset = self.plt_offset + symbol_index * PLT_ENTRY_SIZE;
+        let mut entry = [0xcc_u8; PLT_ENTRY_SIZE];
+        // jmp *0(%rip)
+        entry[..6].copy_from_slice(&[0xff, 0x25, 0, 0, 0, 0]);
+        entry[6..14].copy_from_slice(&(self.symbol_addresses[symbol_index] as u64).to_le_bytes());
+        self.memory.write_bytes(offset, &entry)?;
+        Ok(self.memory.start_vaddr() + offset)
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    use ostd::prelude::*;
+
+    use super::*;
+
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    static HOOK_CALLED: AtomicBool = AtomicBool::new(false);
+
+    extern "C" fn hook() {
+        HOOK_CALLED.store(true, Ordering::Relaxed);
+    }
+
+    fn resolve(name: &str) -> Option<Vaddr> {
+        match name {
+            "counter" => Some(&COUNTER as *const AtomicUsize as Vaddr),
+            "hook" => Some(hook as extern "C" fn() as Vaddr),
+            _ => None,
+        }
+    }
+
+    struct Section {
+        name: &'static str,
+        type_: u32,
+        flags: u64,
+        data: Vec<u8>,
+        link: u32,
+        info: u32,
+        entsize: u64,
+    }
+
+    impl Section {
+        fn new(name: &'static str, type_: u32, flags: u64, data: &[u8]) -> Self {
+            Self {
+                name,
+                type_,
+                flags,
+                data: data.to_vec(),
+                link: 0,
+                info: 0,
+                entsize: 0,
+            }
+        }
+
+        fn linked(mut self, link: u32, info: u32, entsize: u64) -> Self {
+            self.link = link;
+            self.info = info;
+            self.entsize = entsize;
+            self
+        }
+    }
+
+    /// Builds a relocatable ELF object with the sections, which are indexed from one.
+    fn build_elf(mut sections: Vec<Section>) -> Vec<u8> {
+        const SHT_STRTAB: u32 = 3;
+
+        let mut shstrtab = vec![0u8];
+        let mut names = Vec::new();
+        for name in sections
+            .iter()
+            .map(|section| section.name)
+            .chain([".shstrtab"])
+        {
+            names.push(shstrtab.len() as u32);
+            shstrtab.extend_from_slice(name.as_bytes());
+            shstrtab.push(0);
+        }
+        sections.push(Section::new(".shstrtab", SHT_STRTAB, 0, &shstrtab));
+
+        let mut image = vec![0u8; 64];
+        let mut headers = vec![0u8; 64];
+        for (section, name) in sections.iter().zip(names) {
+            image.resize(image.len().align_up(8), 0);
+            let offset = image.len() as u64;
+            image.extend_from_slice(&section.data);
+            headers.extend_from_slice(&name.to_le_bytes());
+            headers.extend_from_slice(&section.type_.to_le_bytes());
+            headers.extend_from_slice(&section.flags.to_le_bytes());
+            headers.extend_from_slice(&0u64.to_le_bytes());
+            headers.extend_from_slice(&offset.to_le_bytes());
+            headers.extend_from_slice(&(section.data.len() as u64).to_le_bytes());
+            headers.extend_from_slice(&section.link.to_le_bytes());
+            headers.extend_from_slice(&section.info.to_le_bytes());
+            headers.extend_from_slice(&8u64.to_le_bytes());
+            headers.extend_from_slice(&section.entsize.to_le_bytes());
+        }
+        image.resize(image.len().align_up(8), 0);
+        let shoff = image.len() as u64;
+        image.extend_from_slice(&headers);
+
+        image[..7].copy_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1, 1]);
+        image[16..18].copy_from_slice(&1u16.to_le_bytes());
+        image[18..20].copy_from_slice(&62u16.to_le_bytes());
+        image[20..24].copy_from_slice(&1u32.to_le_bytes());
+        image[40..48].copy_from_slice(&shoff.to_le_bytes());
+        image[52..54].copy_from_slice(&64u16.to_le_bytes());
+        image[58..60].copy_from_slice(&64u16.to_le_bytes());
+        image[60..62].copy_from_slice(&(sections.len() as u16 + 1).to_le_bytes());
+        image[62..64].copy_from_slice(&(sections.len() as u16).to_le_bytes());
+        image
+    }
+
+    fn symbol(name: u32, info: u8, shndx: u16) -> Vec<u8> {
+        let mut symbol = Vec::new();
+        symbol.extend_from_slice(&name.to_le_bytes());
+        symbol.extend_from_slice(&[info, 0]);
+        symbol.extend_from_slice(&shndx.to_le_bytes());
+        symbol.extend_from_slice(&[0u8; 16]);
+        symbol
+    }
+
+    fn rela(offset: u64, symbol: u64, type_: u32, addend: i64) -> Vec<u8> {
+        let mut rela = Vec::new();
+        rela.extend_from_slice(&offset.to_le_bytes());
+        rela.extend_from_slice(&((symbol << 32) | type_ as u64).to_le_bytes());
+        rela.extend_from_slice(&addend.to_le_bytes());
+        rela
+    }
+
+    /// Builds a module whose constructor increments its static `local`, copies it to
+    /// `counter` through the GOT, and then jumps to `hook` through the PLT.
+    fn build_module() -> Vec<u8> {
+        const SHT_PROGBITS: u32 = 1;
+        const SHT_SYMTAB: u32 = 2;
+        const SHT_STRTAB: u32 = 3;
+        const SHT_RELA: u32 = 4;
+        const SHT_INIT_ARRAY: u32 = 14;
+        const SHF_WRITE: u64 = 1;
+        const SHF_EXECINSTR: u64 = 4;
+
+        // inc qword ptr [rip + local]
+        // mov rcx, [rip + local]
+        // mov rax, [rip + counter@GOTPCREL]
+        // mov [rax], rcx
+        // jmp hook@PLT
+        let text = [
+            0x48, 0xff, 0x05, 0, 0, 0, 0, 0x48, 0x8b, 0x0d, 0, 0, 0, 0, 0x48, 0x8b, 0x05, 0, 0, 0,
+            0, 0x48, 0x89, 0x08, 0xe9, 0, 0, 0, 0,
+        ];
+        // The symbols are `init` in `.text`, `local` in `.data`, and the undefined
+        // `counter` and `hook`.
+        let strtab = b"\0init\0local\0counter\0hook\0";
+        let symtab = [
+            symbol(0, 0, 0),
+            symbol(1, 0x02, 1),
+            symbol(6, 0x01, 2),
+            symbol(12, 0x10, 0),
+            symbol(20, 0x10, 0),
+        ]
+        .concat();
+        let rela_text = [
+            rela(3, 2, R_X86_64_PC32, -4),
+            rela(10, 2, R_X86_64_PC32, -4),
+            rela(17, 3, R_X86_64_GOTPCREL, -4),
+            rela(25, 4, R_X86_64_PLT32, -4),
+        ]
+        .concat();
+        let rela_init_array = rela(0, 1, R_X86_64_64, 0);
+
+        build_elf(vec![
+            Section::new(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, &text),
+            Section::new(".data", SHT_PROGBITS, SHF_ALLOC | SHF_WRITE, &[0; 8]),
+            Section::new(
+                ".init_array",
+                SHT_INIT_ARRAY,
+                SHF_ALLOC | SHF_WRITE,
+                &[0; 8],
+            ),
+            Section::new(".rela.text", SHT_RELA, 0, &rela_text).linked(6, 1, 24),
+            Section::new(".rela.init_array", SHT_RELA, 0, &rela_init_array).linked(6, 3, 24),
+            Section::new(".symtab", SHT_SYMTAB, 0, &symtab).linked(7, 3, 24),
+            Section::new(".strtab", SHT_STRTAB, 0, strtab),
+            Section::new(".component_name", SHT_PROGBITS, 0, b"aster-test\0"),
+        ])
+    }
+
+    #[ktest]
+    fn load_and_call() {
+        let image = build_module();
+        let elf = ElfFile::new(&image).unwrap();
+        assert_eq!(component_name(&elf).unwrap(), "aster-test");
+
+        let LoadedModule {
+            memory,
+            init_functions,
+        } = load(&elf, resolve).unwrap();
+        assert_eq!(init_functions, vec![memory.start_vaddr()]);
+        // The memory is executable, so it is no longer writable.
+        assert!(memory.write_val(0, &0u8).is_err());
+
+        // The static of the module is writable by the module, so it is incremented
+        // by each call.
+        for count in 1..=2 {
+            memory.run_init_functions(&init_functions).unwrap();
+            assert_eq!(COUNTER.load(Ordering::Relaxed), count);
+        }
+        assert!(HOOK_CALLED.load(Ordering::Relaxed));
+    }
+
+    #[ktest]
+    fn unknown_symbol() {
+        let image = build_module();
+        let elf = ElfFile::new(&image).unwrap();
+        let error = load(&elf, |_| None).err().unwrap();
+        assert_eq!(error.error(), Errno::ENOENT);
+    }
+}
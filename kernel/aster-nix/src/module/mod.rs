@@ -0,0 +1,108 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Loadable kernel modules.
+//!
+//! A module is a component built as a relocatable ELF object with `--cfg component_module`,
+//! which is loaded at runtime by `init_module` or `finit_module`. Once loaded, the
+//! component is initialized through its `#[init_component]` function, after checking
+//! that the components it depends on, as recorded from `Components.toml`, are initialized.
+//!
+//! A module is unloaded by `delete_module` through the `#[exit_component]` function of
+//! its component. Like the modules of Linux that have no exit functions, a module cannot
+//! be unloaded if its component has no such function. Neither can it be unloaded if it
+//! fails to initialize, since the component may have registered something.
+
+mod loader;
+
+use component::{ComponentExitError, ComponentInitError};
+use ostd::mm::ModuleMemory;
+use xmas_elf::ElfFile;
+
+use crate::prelude::*;
+
+/// A module that has been loaded.
+struct Module {
+    /// The memory of the module, which should not be freed until the component exits
+    /// since the component may have registered its code and data to the kernel.
+    _memory: ModuleMemory,
+}
+
+lazy_static! {
+    /// The loaded modules, indexed by the names of their components.
+    static ref MODULES: Mutex<BTreeMap<String, Module>> = Mutex::new(BTreeMap::new());
+}
+
+/// Loads a module from its ELF image and initializes its component.
+pub fn load_module(image: &[u8]) -> Result<()> {
+    if !cfg!(feature = "modules") {
+        return_errno_with_message!(Errno::ENOSYS, "the kernel is built without modules");
+    }
+
+    let elf = ElfFile::new(image)
+        .map_err(|_| Error::with_message(Errno::ENOEXEC, "parse elf header fails"))?;
+    let name = loader::component_name(&elf)?;
+
+    let mut modules = MODULES.lock();
+    if modules.contains_key(&name) {
+        return_errno_with_message!(Errno::EEXIST, "the module is already loaded");
+    }
+    component::check_loadable(&name).map_err(|err| component_init_error(&name, err))?;
+
+    let loader::LoadedModule {
+        memory,
+        init_functions,
+    } = loader::load(&elf, ostd::ksym::lookup)?;
+    // The module, which is loaded with `CAP_SYS_MODULE`, is trusted as a part of the kernel.
+    memory.run_init_functions(&init_functions)?;
+    let result = component::init_loaded(&name);
+
+    // Even if the initialization fails, the component may have registered something.
+    modules.insert(name.clone(), Module { _memory: memory });
+    result.map_err(|err| component_init_error(&name, err))
+}
+
+/// Unloads the module whose component is named `name`.
+pub fn delete_module(name: &str) -> Result<()> {
+    let mut modules = MODULES.lock();
+    if !modules.contains_key(name) {
+        return_errno_with_message!(Errno::ENOENT, "the module is not loaded");
+    }
+    component::exit_loaded(name).map_err(|err| component_exit_error(name, err))?;
+
+    // The component has unregistered everything, so the memory can be freed.
+    modules.remove(name);
+    Ok(())
+}
+
+fn component_init_error(name: &str, err: ComponentInitError) -> Error {
+    warn!("failed to load the component {}: {:?}", name, err);
+    match err {
+        ComponentInitError::UninitializedDependencies(_) => {
+            Error::with_message(Errno::ENOENT, "the dependencies are not initialized")
+        }
+        ComponentInitError::UnknownComponent(_) => {
+            Error::with_message(Errno::EINVAL, "the component is not declared")
+        }
+        ComponentInitError::AlreadyInitialized(_) => {
+            Error::with_message(Errno::EEXIST, "the component is already initialized")
+        }
+        ComponentInitError::Unknown => {
+            Error::with_message(Errno::EINVAL, "the component fails to initialize")
+        }
+    }
+}
+
+fn component_exit_error(name: &str, err: ComponentExitError) -> Error {
+    warn!("failed to unload the component {}: {:?}", name, err);
+    match err {
+        ComponentExitError::InUse(_) => {
+            Error::with_message(Errno::EBUSY, "the module is used by other modules")
+        }
+        ComponentExitError::NoExitFunction(_) | ComponentExitError::NotInitialized(_) => {
+            Error::with_message(Errno::EBUSY, "the module cannot be unloaded")
+        }
+        ComponentExitError::UnknownComponent(_) => {
+            Error::with_message(Errno::ENOENT, "the component is not declared")
+        }
+    }
+}
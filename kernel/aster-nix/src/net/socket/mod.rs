@@ -8,6 +8,7 @@ pub use self::util::{
 use crate::{fs::file_handle::FileLike, prelude::*, util::IoVec};
 
 pub mod ip;
+pub mod netlink;
 pub mod options;
 pub mod unix;
 mod util;
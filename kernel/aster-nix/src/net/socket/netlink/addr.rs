@@ -0,0 +1,35 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use crate::{net::socket::SocketAddr, prelude::*};
+
+/// The address of a netlink socket, i.e., `struct sockaddr_nl` in Linux.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct NetlinkSocketAddr {
+    /// The port ID, which is zero for the kernel
+    pub port: u32,
+    /// The mask of the multicast groups
+    pub groups: u32,
+}
+
+impl NetlinkSocketAddr {
+    pub fn new(port: u32, groups: u32) -> Self {
+        Self { port, groups }
+    }
+}
+
+impl TryFrom<SocketAddr> for NetlinkSocketAddr {
+    type Error = Error;
+
+    fn try_from(value: SocketAddr) -> Result<Self> {
+        let SocketAddr::Netlink(netlink_addr) = value else {
+            return_errno_with_message!(Errno::EINVAL, "invalid netlink socket addr");
+        };
+        Ok(netlink_addr)
+    }
+}
+
+impl From<NetlinkSocketAddr> for SocketAddr {
+    fn from(value: NetlinkSocketAddr) -> Self {
+        SocketAddr::Netlink(value)
+    }
+}
@@ -0,0 +1,365 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use super::NetlinkSocketAddr;
+use crate::{
+    events::{IoEvents, Observer},
+    fs::{file_handle::FileLike, utils::StatusFlags},
+    match_sock_option_mut, match_sock_option_ref,
+    net::socket::{
+        options::{RecvBuf, SocketOption},
+        util::{
+            copy_message_to_user, create_message_buffer, options::MIN_RECVBUF,
+            send_recv_flags::SendRecvFlags, socket_addr::SocketAddr, MessageHeader,
+        },
+        Socket,
+    },
+    prelude::*,
+    process::signal::{Pollee, Poller},
+    util::IoVec,
+};
+
+/// The multicast group of the uevents.
+const UEVENT_GROUP: u32 = 1;
+/// The default size of the receive buffer, which is that of Linux.
+const DEFAULT_RECV_BUF: u32 = 212992;
+/// The maximum size of the receive buffer that can be set, i.e., `rmem_max` in Linux.
+const MAX_RECV_BUF: u32 = 212992;
+
+/// The sockets that may receive the uevents.
+static UEVENT_SOCKETS: SpinLock<Vec<Weak<NetlinkUeventSocket>>> = SpinLock::new(Vec::new());
+
+/// Sends the uevent to the sockets in the uevent group.
+///
+/// A socket whose receive buffer is full misses the uevent, like Linux.
+pub fn broadcast_uevent(message: &[u8]) {
+    let sockets: Vec<_> = {
+        let mut sockets = UEVENT_SOCKETS.lock();
+        sockets.retain(|socket| socket.strong_count() > 0);
+        sockets.iter().filter_map(Weak::upgrade).collect()
+    };
+    for socket in sockets {
+        socket.deliver(message);
+    }
+}
+
+/// A `NETLINK_KOBJECT_UEVENT` socket.
+///
+/// The socket receives the uevents once it is bound to the uevent group. Sending
+/// messages is not supported, since the kernel does not handle any requests.
+pub struct NetlinkUeventSocket {
+    addr: SpinLock<Option<NetlinkSocketAddr>>,
+    receive_queue: SpinLock<ReceiveQueue>,
+    nonblocking: AtomicBool,
+    pollee: Pollee,
+}
+
+struct ReceiveQueue {
+    messages: VecDeque<Vec<u8>>,
+    /// The total length of the messages
+    len: usize,
+    capacity: usize,
+}
+
+impl NetlinkUeventSocket {
+    pub fn new(nonblocking: bool) -> Arc<Self> {
+        let socket = Arc::new(Self {
+            addr: SpinLock::new(None),
+            receive_queue: SpinLock::new(ReceiveQueue {
+                messages: VecDeque::new(),
+                len: 0,
+                capacity: DEFAULT_RECV_BUF as usize,
+            }),
+            nonblocking: AtomicBool::new(nonblocking),
+            pollee: Pollee::new(IoEvents::OUT),
+        });
+        UEVENT_SOCKETS.lock().push(Arc::downgrade(&socket));
+        socket
+    }
+
+    pub fn is_nonblocking(&self) -> bool {
+        self.nonblocking.load(Ordering::SeqCst)
+    }
+
+    pub fn set_nonblocking(&self, nonblocking: bool) {
+        self.nonblocking.store(nonblocking, Ordering::SeqCst);
+    }
+
+    /// Queues the uevent if the socket is bound to the uevent group.
+    fn deliver(&self, message: &[u8]) {
+        let is_in_group = self
+            .addr
+            .lock()
+            .is_some_and(|addr| addr.groups & UEVENT_GROUP != 0);
+        if !is_in_group {
+            return;
+        }
+
+        let mut queue = self.receive_queue.lock();
+        if queue.len + message.len() > queue.capacity {
+            debug!("the receive buffer of the uevent socket is full");
+            return;
+        }
+        queue.len += message.len();
+        queue.messages.push_back(message.to_vec());
+        self.pollee.add_events(IoEvents::IN);
+    }
+
+    fn try_recv(&self, buf: &mut [u8], flags: SendRecvFlags) -> Result<usize> {
+        let mut queue = self.receive_queue.lock();
+        let Some(message) = queue.messages.front() else {
+            return_errno_with_message!(Errno::EAGAIN, "no uevent is received");
+        };
+
+        // The rest of the message is discarded if the buffer is too small.
+        let len = message.len().min(buf.len());
+        buf[..len].copy_from_slice(&message[..len]);
+        if !flags.contains(SendRecvFlags::MSG_PEEK) {
+            let message = queue.messages.pop_front().unwrap();
+            queue.len -= message.len();
+            if queue.messages.is_empty() {
+                self.pollee.del_events(IoEvents::IN);
+            }
+        }
+        Ok(len)
+    }
+
+    fn recv(&self, buf: &mut [u8], flags: SendRecvFlags) -> Result<usize> {
+        if self.is_nonblocking() || flags.contains(SendRecvFlags::MSG_DONTWAIT) {
+            self.try_recv(buf, flags)
+        } else {
+            self.wait_events(IoEvents::IN, || self.try_recv(buf, flags))
+        }
+    }
+
+    // TODO: Support timeout
+    fn wait_events<F, R>(&self, mask: IoEvents, mut cond: F) -> Result<R>
+    where
+        F: FnMut() -> Result<R>,
+    {
+        let poller = Poller::new();
+
+        loop {
+            match cond() {
+                Err(err) if err.error() == Errno::EAGAIN => (),
+                result => return result,
+            };
+
+            let events = self.poll(mask, Some(&poller));
+            if !events.is_empty() {
+                continue;
+            }
+
+            poller.wait()?;
+        }
+    }
+}
+
+impl FileLike for NetlinkUeventSocket {
+    fn read(&self, buf: &mut [u8]) -> Result<usize> {
+        self.recv(buf, SendRecvFlags::empty())
+    }
+
+    fn write(&self, _buf: &[u8]) -> Result<usize> {
+        return_errno_with_message!(Errno::EOPNOTSUPP, "cannot send to the uevent socket");
+    }
+
+    fn poll(&self, mask: IoEvents, poller: Option<&Poller>) -> IoEvents {
+        self.pollee.poll(mask, poller)
+    }
+
+    fn as_socket(self: Arc<Self>) -> Option<Arc<dyn Socket>> {
+        Some(self)
+    }
+
+    fn status_flags(&self) -> StatusFlags {
+        if self.is_nonblocking() {
+            StatusFlags::O_NONBLOCK
+        } else {
+            StatusFlags::empty()
+        }
+    }
+
+    fn set_status_flags(&self, new_flags: StatusFlags) -> Result<()> {
+        if new_flags.contains(StatusFlags::O_NONBLOCK) {
+            self.set_nonblocking(true);
+        } else {
+            self.set_nonblocking(false);
+        }
+        Ok(())
+    }
+
+    fn register_observer(
+        &self,
+        observer: Weak<dyn Observer<IoEvents>>,
+        mask: IoEvents,
+    ) -> Result<()> {
+        self.pollee.register_observer(observer, mask);
+        Ok(())
+    }
+
+    fn unregister_observer(
+        &self,
+        observer: &Weak<dyn Observer<IoEvents>>,
+    ) -> Option<Weak<dyn Observer<IoEvents>>> {
+        self.pollee.unregister_observer(observer)
+    }
+}
+
+impl Socket for NetlinkUeventSocket {
+    fn bind(&self, socket_addr: SocketAddr) -> Result<()> {
+        let mut addr = NetlinkSocketAddr::try_from(socket_addr)?;
+
+        let mut bound_addr = self.addr.lock();
+        if bound_addr.is_some() {
+            return_errno_with_message!(Errno::EINVAL, "the socket is already bound to an address");
+        }
+        // The port is the process ID if it is not specified.
+        if addr.port == 0 {
+            addr.port = current!().pid();
+        }
+        *bound_addr = Some(addr);
+        Ok(())
+    }
+
+    fn addr(&self) -> Result<SocketAddr> {
+        let addr = self.addr.lock().unwrap_or_default();
+        Ok(addr.into())
+    }
+
+    fn get_option(&self, option: &mut dyn SocketOption) -> Result<()> {
+        match_sock_option_mut!(option, {
+            socket_recv_buf: RecvBuf => {
+                let capacity = self.receive_queue.lock().capacity;
+                socket_recv_buf.set(capacity as u32);
+            },
+            _ => return_errno_with_message!(Errno::ENOPROTOOPT, "the socket option to get is unknown")
+        });
+        Ok(())
+    }
+
+    fn set_option(&self, option: &dyn SocketOption) -> Result<()> {
+        match_sock_option_ref!(option, {
+            socket_recv_buf: RecvBuf => {
+                let recv_buf = socket_recv_buf.get().unwrap();
+                let capacity = (*recv_buf).clamp(MIN_RECVBUF, MAX_RECV_BUF);
+                self.receive_queue.lock().capacity = capacity as usize;
+            },
+            _ => return_errno_with_message!(Errno::ENOPROTOOPT, "the socket option to be set is unknown")
+        });
+        Ok(())
+    }
+
+    fn sendmsg(
+        &self,
+        _io_vecs: &[IoVec],
+        _message_header: MessageHeader,
+        _flags: SendRecvFlags,
+    ) -> Result<usize> {
+        return_errno_with_message!(Errno::EOPNOTSUPP, "cannot send to the uevent socket");
+    }
+
+    fn recvmsg(&self, io_vecs: &[IoVec], flags: SendRecvFlags) -> Result<(usize, MessageHeader)> {
+        let mut buf = create_message_buffer(io_vecs);
+
+        let received_bytes = self.recv(&mut buf, flags)?;
+
+        let copied_bytes = {
+            let message = &buf[..received_bytes];
+            copy_message_to_user(io_vecs, message)
+        };
+
+        // The uevents are sent by the kernel to the uevent group.
+        let peer_addr = NetlinkSocketAddr::new(0, UEVENT_GROUP);
+        let message_header = MessageHeader::new(Some(peer_addr.into()), None);
+
+        Ok((copied_bytes, message_header))
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    fn new_bound_socket(port: u32, groups: u32) -> Arc<NetlinkUeventSocket> {
+        let socket = NetlinkUeventSocket::new(true);
+        let addr = NetlinkSocketAddr::new(port, groups);
+        socket.bind(addr.into()).unwrap();
+        socket
+    }
+
+    fn recv_buf_of(socket: &NetlinkUeventSocket) -> u32 {
+        let mut recv_buf = RecvBuf::new();
+        socket.get_option(&mut recv_buf).unwrap();
+        *recv_buf.get().unwrap()
+    }
+
+    fn set_recv_buf(socket: &NetlinkUeventSocket, size: u32) {
+        let mut recv_buf = RecvBuf::new();
+        recv_buf.set(size);
+        socket.set_option(&recv_buf).unwrap();
+    }
+
+    #[ktest]
+    fn deliver_to_group() {
+        let in_group = new_bound_socket(100, UEVENT_GROUP);
+        let out_of_group = new_bound_socket(101, 0);
+        let unbound = NetlinkUeventSocket::new(true);
+
+        broadcast_uevent(b"add@/devices/test\0ACTION=add\0");
+
+        let mut buf = [0u8; 64];
+        let len = in_group.read(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"add@/devices/test\0ACTION=add\0");
+        assert!(!in_group.poll(IoEvents::IN, None).contains(IoEvents::IN));
+        for socket in [out_of_group, unbound] {
+            assert!(!socket.poll(IoEvents::IN, None).contains(IoEvents::IN));
+            assert_eq!(socket.read(&mut buf).unwrap_err().error(), Errno::EAGAIN);
+        }
+    }
+
+    #[ktest]
+    fn peek_and_truncate() {
+        let socket = new_bound_socket(102, UEVENT_GROUP);
+        broadcast_uevent(b"remove@/devices/test\0");
+        broadcast_uevent(b"add@/devices/test\0");
+
+        let mut buf = [0u8; 6];
+        let len = socket.try_recv(&mut buf, SendRecvFlags::MSG_PEEK).unwrap();
+        assert_eq!(&buf[..len], b"remove");
+        // The rest of a truncated message is discarded.
+        let len = socket.read(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"remove");
+        let len = socket.read(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"add@/d");
+        assert_eq!(socket.read(&mut buf).unwrap_err().error(), Errno::EAGAIN);
+    }
+
+    #[ktest]
+    fn clamp_recv_buf() {
+        let socket = new_bound_socket(103, UEVENT_GROUP);
+        assert_eq!(recv_buf_of(&socket), DEFAULT_RECV_BUF);
+
+        set_recv_buf(&socket, u32::MAX);
+        assert_eq!(recv_buf_of(&socket), MAX_RECV_BUF);
+        set_recv_buf(&socket, 1);
+        assert_eq!(recv_buf_of(&socket), MIN_RECVBUF);
+    }
+
+    #[ktest]
+    fn drop_when_full() {
+        let socket = new_bound_socket(104, UEVENT_GROUP);
+        set_recv_buf(&socket, MIN_RECVBUF);
+
+        let message = vec![b'a'; MIN_RECVBUF as usize / 2 + 1];
+        broadcast_uevent(&message);
+        broadcast_uevent(&message);
+
+        let mut buf = vec![0u8; MIN_RECVBUF as usize];
+        assert_eq!(socket.read(&mut buf).unwrap(), message.len());
+        assert_eq!(socket.read(&mut buf).unwrap_err().error(), Errno::EAGAIN);
+    }
+}
@@ -3,7 +3,7 @@
 use crate::{
     net::{
         iface::{IpAddress, IpEndpoint, Ipv4Address},
-        socket::{unix::UnixSocketAddr, vsock::addr::VsockSocketAddr},
+        socket::{netlink::NetlinkSocketAddr, unix::UnixSocketAddr, vsock::addr::VsockSocketAddr},
     },
     prelude::*,
 };
@@ -16,6 +16,7 @@ pub enum SocketAddr {
     IPv4(Ipv4Address, PortNum),
     IPv6,
     Vsock(VsockSocketAddr),
+    Netlink(NetlinkSocketAddr),
 }
 
 impl TryFrom<SocketAddr> for IpEndpoint {
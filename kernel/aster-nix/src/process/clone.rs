@@ -13,11 +13,12 @@ use ostd::{
 
 use super::{
     credentials,
+    credentials::capabilities::CapSet,
     posix_thread::{PosixThread, PosixThreadBuilder, PosixThreadExt, ThreadName},
     process_table,
     process_vm::ProcessVm,
     signal::sig_disposition::SigDispositions,
-    Credentials, Process, ProcessBuilder,
+    Credentials, Process, ProcessBuilder, ResourceType,
 };
 use crate::{
     cpu::LinuxAbi,
@@ -169,8 +170,12 @@ fn clone_child_thread(parent_context: &UserContext, clone_args: CloneArgs) -> Re
         );
         Arc::new(UserSpace::new(child_vm_space, child_cpu_context))
     };
+    check_nproc_limit(&current)?;
     clone_sysvsem(clone_flags)?;
 
+    // The new thread is charged to the cgroup, which fails if the `pids` limit is reached.
+    current.charge_task()?;
+
     // Inherit sigmask from current thread
     let sig_mask = {
         let current_thread = current_thread!();
@@ -192,17 +197,15 @@ fn clone_child_thread(parent_context: &UserContext, clone_args: CloneArgs) -> Re
         thread_builder.build()
     };
 
-    current.threads().lock().push(child_thread.clone());
-
     let child_posix_thread = child_thread.as_posix_thread().unwrap();
-    clone_parent_settid(child_tid, clone_args.parent_tidptr, clone_flags)?;
-    clone_child_cleartid(child_posix_thread, clone_args.child_tidptr, clone_flags)?;
-    clone_child_settid(
-        child_root_vmar,
-        child_tid,
-        clone_args.child_tidptr,
-        clone_flags,
-    )?;
+    if let Err(err) = clone_tids(child_posix_thread, child_root_vmar, child_tid, &clone_args) {
+        // The thread never runs, so it is removed and uncharged from the cgroup.
+        thread_table::remove_thread(child_tid);
+        current.uncharge_task();
+        return Err(err);
+    }
+
+    current.threads().lock().push(child_thread.clone());
     Ok(child_thread)
 }
 
@@ -251,6 +254,8 @@ fn clone_child_process(
     // clone sig dispositions
     let child_sig_dispositions = clone_sighand(current.sig_dispositions(), clone_flags);
 
+    check_nproc_limit(&current)?;
+
     // clone system V semaphore
     clone_sysvsem(clone_flags)?;
 
@@ -265,6 +270,9 @@ fn clone_child_process(
     // inherit parent's nice value
     let child_nice = current.nice().load(Ordering::Relaxed);
 
+    // inherit parent's resource limits
+    let child_resource_limits = current.resource_limits().lock().clone();
+
     let child_tid = allocate_tid();
 
     let child = {
@@ -292,29 +300,51 @@ fn clone_child_process(
             .fs(child_fs)
             .umask(child_umask)
             .sig_dispositions(child_sig_dispositions)
-            .nice(child_nice);
+            .nice(child_nice)
+            .resource_limits(child_resource_limits)
+            .cgroup(current.cgroup());
 
+        // This fails if the `pids` limit of the cgroup is reached.
         process_builder.build()?
     };
 
     // Deals with clone flags
     let child_thread = thread_table::get_thread(child_tid).unwrap();
     let child_posix_thread = child_thread.as_posix_thread().unwrap();
+    if let Err(err) = clone_tids(
+        child_posix_thread,
+        child.root_vmar(),
+        child_tid,
+        &clone_args,
+    ) {
+        // The process never runs, so its main thread is uncharged from the cgroup.
+        thread_table::remove_thread(child_tid);
+        child.uncharge_task();
+        return Err(err);
+    }
+
+    // Sets parent process and group for child process.
+    set_parent_and_group(&current, &child);
+
+    Ok(child)
+}
+
+/// Deals with the clone flags that set or clear the TID of the child.
+fn clone_tids(
+    child_posix_thread: &PosixThread,
+    child_root_vmar: &Vmar<Full>,
+    child_tid: Tid,
+    clone_args: &CloneArgs,
+) -> Result<()> {
+    let clone_flags = clone_args.clone_flags;
     clone_parent_settid(child_tid, clone_args.parent_tidptr, clone_flags)?;
     clone_child_cleartid(child_posix_thread, clone_args.child_tidptr, clone_flags)?;
-
-    let child_root_vmar = child.root_vmar();
     clone_child_settid(
         child_root_vmar,
         child_tid,
         clone_args.child_tidptr,
         clone_flags,
-    )?;
-
-    // Sets parent process and group for child process.
-    set_parent_and_group(&current, &child);
-
-    Ok(child)
+    )
 }
 
 fn clone_child_cleartid(
@@ -431,6 +461,33 @@ fn clone_sighand(
     }
 }
 
+/// Checks the `RLIMIT_NPROC` of the current process, which limits the number of the
+/// threads whose real user ID is that of the current thread.
+///
+/// Like Linux, the root user and the threads with `CAP_SYS_RESOURCE` or `CAP_SYS_ADMIN`
+/// are exempt from the limit.
+fn check_nproc_limit(current: &Process) -> Result<()> {
+    let max_nr_threads = current
+        .resource_limits()
+        .lock()
+        .get_rlimit(ResourceType::RLIMIT_NPROC)
+        .get_cur();
+    let credentials = credentials();
+    let ruid = credentials.ruid();
+    if ruid.is_root()
+        || credentials
+            .effective_capset()
+            .intersects(CapSet::SYS_RESOURCE | CapSet::SYS_ADMIN)
+    {
+        return Ok(());
+    }
+
+    if ruid.nr_tasks() as u64 >= max_nr_threads {
+        return_errno_with_message!(Errno::EAGAIN, "the process number limit is reached");
+    }
+    Ok(())
+}
+
 fn clone_sysvsem(clone_flags: CloneFlags) -> Result<()> {
     if clone_flags.contains(CloneFlags::CLONE_SYSVSEM) {
         warn!("CLONE_SYSVSEM is not supported now");
@@ -0,0 +1,234 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The ELF structures of core files, whose layouts are the same as those of Linux on x86-64.
+
+use core::mem::size_of;
+
+use ostd::cpu::UserContext;
+
+use crate::{prelude::*, time::timeval_t};
+
+pub(super) const ET_CORE: u16 = 4;
+pub(super) const EM_X86_64: u16 = 62;
+
+pub(super) const PT_LOAD: u32 = 1;
+pub(super) const PT_NOTE: u32 = 4;
+
+pub(super) const PF_X: u32 = 1;
+pub(super) const PF_W: u32 = 2;
+pub(super) const PF_R: u32 = 4;
+
+pub(super) const NT_PRSTATUS: u32 = 1;
+pub(super) const NT_PRFPREG: u32 = 2;
+pub(super) const NT_PRPSINFO: u32 = 3;
+pub(super) const NT_AUXV: u32 = 6;
+pub(super) const NT_SIGINFO: u32 = 0x53494749;
+pub(super) const NT_FILE: u32 = 0x46494c45;
+
+/// The selectors of the user code and data segments, which are the same as those of Linux.
+const USER_CS: u64 = 0x33;
+const USER_SS: u64 = 0x2b;
+
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+pub(super) struct ElfHeader {
+    pub e_ident: [u8; 16],
+    pub e_type: u16,
+    pub e_machine: u16,
+    pub e_version: u32,
+    pub e_entry: u64,
+    pub e_phoff: u64,
+    pub e_shoff: u64,
+    pub e_flags: u32,
+    pub e_ehsize: u16,
+    pub e_phentsize: u16,
+    pub e_phnum: u16,
+    pub e_shentsize: u16,
+    pub e_shnum: u16,
+    pub e_shstrndx: u16,
+}
+
+impl ElfHeader {
+    /// Creates the header of a core file with `phnum` program headers,
+    /// which follow the header immediately.
+    pub fn new_core(phnum: u16) -> Self {
+        let mut e_ident = [0u8; 16];
+        e_ident[..4].copy_from_slice(b"\x7fELF");
+        // ELFCLASS64
+        e_ident[4] = 2;
+        // ELFDATA2LSB
+        e_ident[5] = 1;
+        // EV_CURRENT
+        e_ident[6] = 1;
+
+        Self {
+            e_ident,
+            e_type: ET_CORE,
+            e_machine: EM_X86_64,
+            e_version: 1,
+            e_entry: 0,
+            e_phoff: size_of::<ElfHeader>() as u64,
+            e_shoff: 0,
+            e_flags: 0,
+            e_ehsize: size_of::<ElfHeader>() as u16,
+            e_phentsize: size_of::<ProgramHeader>() as u16,
+            e_phnum: phnum,
+            e_shentsize: 0,
+            e_shnum: 0,
+            e_shstrndx: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+pub(super) struct ProgramHeader {
+    pub p_type: u32,
+    pub p_flags: u32,
+    pub p_offset: u64,
+    pub p_vaddr: u64,
+    pub p_paddr: u64,
+    pub p_filesz: u64,
+    pub p_memsz: u64,
+    pub p_align: u64,
+}
+
+/// The signal information in `ElfPrStatus`.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct ElfSigInfo {
+    si_signo: i32,
+    si_code: i32,
+    si_errno: i32,
+}
+
+/// The status of a thread in the `NT_PRSTATUS` note, i.e., `struct elf_prstatus` of Linux.
+#[derive(Clone, Copy, Pod)]
+#[repr(C)]
+pub(super) struct ElfPrStatus {
+    pr_info: ElfSigInfo,
+    pub pr_cursig: u16,
+    _pad0: u16,
+    pub pr_sigpend: u64,
+    pub pr_sighold: u64,
+    pub pr_pid: i32,
+    pub pr_ppid: i32,
+    pub pr_pgrp: i32,
+    pub pr_sid: i32,
+    pub pr_utime: timeval_t,
+    pub pr_stime: timeval_t,
+    pub pr_cutime: timeval_t,
+    pub pr_cstime: timeval_t,
+    pub pr_reg: UserRegs,
+    pub pr_fpvalid: i32,
+    _pad1: i32,
+}
+
+impl ElfPrStatus {
+    pub fn set_signal(&mut self, si_signo: i32, si_code: i32) {
+        self.pr_info.si_signo = si_signo;
+        self.pr_info.si_code = si_code;
+        self.pr_cursig = si_signo as u16;
+    }
+}
+
+/// The general-purpose registers, i.e., `struct user_regs_struct` of Linux.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+pub(super) struct UserRegs {
+    r15: u64,
+    r14: u64,
+    r13: u64,
+    r12: u64,
+    rbp: u64,
+    rbx: u64,
+    r11: u64,
+    r10: u64,
+    r9: u64,
+    r8: u64,
+    rax: u64,
+    rcx: u64,
+    rdx: u64,
+    rsi: u64,
+    rdi: u64,
+    orig_rax: u64,
+    rip: u64,
+    cs: u64,
+    eflags: u64,
+    rsp: u64,
+    ss: u64,
+    fs_base: u64,
+    gs_base: u64,
+    ds: u64,
+    es: u64,
+    fs: u64,
+    gs: u64,
+}
+
+impl From<&UserContext> for UserRegs {
+    fn from(context: &UserContext) -> Self {
+        Self {
+            r15: context.r15() as u64,
+            r14: context.r14() as u64,
+            r13: context.r13() as u64,
+            r12: context.r12() as u64,
+            rbp: context.rbp() as u64,
+            rbx: context.rbx() as u64,
+            r11: context.r11() as u64,
+            r10: context.r10() as u64,
+            r9: context.r9() as u64,
+            r8: context.r8() as u64,
+            rax: context.rax() as u64,
+            rcx: context.rcx() as u64,
+            rdx: context.rdx() as u64,
+            rsi: context.rsi() as u64,
+            rdi: context.rdi() as u64,
+            // The signal is not delivered in a system call.
+            orig_rax: u64::MAX,
+            rip: context.rip() as u64,
+            cs: USER_CS,
+            eflags: context.rflags() as u64,
+            rsp: context.rsp() as u64,
+            ss: USER_SS,
+            fs_base: context.fsbase() as u64,
+            gs_base: context.gsbase() as u64,
+            ds: 0,
+            es: 0,
+            fs: 0,
+            gs: 0,
+        }
+    }
+}
+
+/// The information of a process in the `NT_PRPSINFO` note, i.e., `struct elf_prpsinfo` of Linux.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+pub(super) struct ElfPrPsInfo {
+    pub pr_state: u8,
+    pub pr_sname: u8,
+    pub pr_zomb: u8,
+    pub pr_nice: i8,
+    _pad0: u32,
+    pub pr_flag: u64,
+    pub pr_uid: u32,
+    pub pr_gid: u32,
+    pub pr_pid: i32,
+    pub pr_ppid: i32,
+    pub pr_pgrp: i32,
+    pub pr_sid: i32,
+    pub pr_fname: [u8; 16],
+    pub pr_psargs: [u8; 80],
+}
+
+/// Appends a note named "CORE", whose name and descriptor are padded to 4 bytes.
+pub(super) fn push_note(notes: &mut Vec<u8>, note_type: u32, desc: &[u8]) {
+    const NAME: &[u8] = b"CORE\0";
+
+    notes.extend_from_slice(&(NAME.len() as u32).to_le_bytes());
+    notes.extend_from_slice(&(desc.len() as u32).to_le_bytes());
+    notes.extend_from_slice(&note_type.to_le_bytes());
+    notes.extend_from_slice(NAME);
+    notes.resize(notes.len().next_multiple_of(4), 0);
+    notes.extend_from_slice(desc);
+    notes.resize(notes.len().next_multiple_of(4), 0);
+}
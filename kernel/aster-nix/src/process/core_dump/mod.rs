@@ -0,0 +1,571 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! ELF core dumps.
+//!
+//! When a process is terminated by a signal whose default action is `Core`,
+//! its memory and the state of its threads are written to a core file, which
+//! can be loaded by debuggers such as gdb together with the executable.
+//!
+//! The core file contains a `PT_NOTE` segment with the following notes, and a
+//! `PT_LOAD` segment for each memory mapping of the process:
+//! - `NT_PRSTATUS`, `NT_PRFPREG` and `NT_SIGINFO` of the dumping thread;
+//! - `NT_PRSTATUS` and `NT_PRFPREG` of the other threads;
+//! - `NT_PRPSINFO`, `NT_AUXV` and `NT_FILE` of the process.
+//!
+//! Like the default `coredump_filter` of Linux, the read-only file mappings are not
+//! dumped, since their contents can be found in the mapped files listed in `NT_FILE`.
+//!
+//! Like Linux, the other threads are stopped before the core is dumped. Each of them
+//! records its registers saved on entering the kernel, and waits until the process
+//! exits. A thread that does not stop in time, e.g., one that is blocked in the kernel
+//! and cannot be interrupted, is left out of the core file.
+//!
+//! The file is at the path generated from `/proc/sys/kernel/core_pattern`, and its size
+//! is limited by `RLIMIT_CORE`, beyond which the file is silently truncated.
+
+mod elf;
+mod pattern;
+
+use core::{mem::size_of, sync::atomic::Ordering, time::Duration};
+
+use align_ext::AlignExt;
+use ostd::{cpu::UserContext, sync::WaitQueue};
+pub use pattern::{core_pattern, set_core_pattern};
+
+use self::{
+    elf::{ElfHeader, ElfPrPsInfo, ElfPrStatus, ProgramHeader, UserRegs},
+    pattern::expand_core_pattern,
+};
+use crate::{
+    current_thread,
+    fs::{
+        fs_resolver::FsPath,
+        utils::{AccessMode, CreationFlags, Inode, InodeMode, InodeType},
+    },
+    prelude::*,
+    process::{
+        credentials,
+        posix_thread::{PosixThread, PosixThreadExt},
+        signal::{c_types::siginfo_t, constants::SIGKILL, signals::kernel::KernelSignal},
+        Process, ResourceType,
+    },
+    thread::Tid,
+    time::{wait::WaitTimeout, Clock},
+    vm::{perms::VmPerms, vmar::vm_mapping::VmMapping},
+};
+
+/// How long the dumping thread waits for the other threads to stop.
+const STOP_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// The state of the core dump of a process.
+pub(super) struct CoreDumpState {
+    /// The registers of the stopped threads, keyed by their thread IDs
+    contexts: SpinLock<BTreeMap<Tid, UserContext>>,
+    /// The queue of the dumping thread waiting for the others to stop, and of the stopped
+    /// threads waiting for the process to exit
+    wait_queue: WaitQueue,
+}
+
+impl CoreDumpState {
+    /// Wakes up the stopped threads, which is called once the threads are exited.
+    pub(super) fn wake_stopped_threads(&self) {
+        self.wait_queue.wake_all();
+    }
+}
+
+/// Writes the core file of the current process, which is terminated by a signal.
+///
+/// Returns `false` if core dumps are disabled by `RLIMIT_CORE` or the core pattern,
+/// or if another thread is dumping the core.
+pub fn dump_core(context: &UserContext, siginfo: &siginfo_t) -> Result<bool> {
+    let current = current!();
+    let current_thread = current_thread!();
+    let posix_thread = current_thread.as_posix_thread().unwrap();
+
+    let limit = current
+        .resource_limits()
+        .lock()
+        .get_rlimit(ResourceType::RLIMIT_CORE)
+        .get_cur();
+    let pattern = core_pattern();
+    if limit == 0 || pattern.is_empty() {
+        return Ok(false);
+    }
+    // Like Linux, a core file must be able to hold at least one page.
+    if limit < PAGE_SIZE as u64 {
+        return Ok(false);
+    }
+
+    let mut is_dumping_thread = false;
+    let state = current.core_dump().call_once(|| {
+        is_dumping_thread = true;
+        CoreDumpState {
+            contexts: SpinLock::new(BTreeMap::new()),
+            wait_queue: WaitQueue::new(),
+        }
+    });
+    if !is_dumping_thread {
+        stop_for_core_dump(context);
+        return Ok(false);
+    }
+    let contexts = stop_other_threads(&current, current_thread.tid(), state);
+
+    let comm = comm_of(&current, posix_thread);
+    let path = expand_core_pattern(
+        &pattern,
+        &current,
+        current_thread.tid(),
+        &comm,
+        siginfo.si_signo,
+    );
+    let mut writer = CoreWriter {
+        inode: create_core_file(&current, &path)?,
+        offset: 0,
+        limit: limit.min(usize::MAX as u64) as usize,
+    };
+
+    let segments: Vec<_> = current
+        .root_vmar()
+        .vm_mappings()
+        .into_iter()
+        .map(|vm_mapping| {
+            let dump_size = dump_size_of(&vm_mapping);
+            (vm_mapping, dump_size)
+        })
+        .collect();
+    let notes = build_notes(&current, posix_thread, context, &contexts, siginfo, &comm)?;
+
+    // The layout of the file: the ELF header, the program headers, the notes, and
+    // the page-aligned contents of the mappings.
+    let nr_headers = segments.len() + 1;
+    let notes_offset = size_of::<ElfHeader>() + nr_headers * size_of::<ProgramHeader>();
+    let mut data_offset = (notes_offset + notes.len()).align_up(PAGE_SIZE);
+
+    writer.write(ElfHeader::new_core(nr_headers as u16).as_bytes())?;
+    let note_header = ProgramHeader {
+        p_type: elf::PT_NOTE,
+        p_flags: 0,
+        p_offset: notes_offset as u64,
+        p_vaddr: 0,
+        p_paddr: 0,
+        p_filesz: notes.len() as u64,
+        p_memsz: 0,
+        p_align: 4,
+    };
+    writer.write(note_header.as_bytes())?;
+    for (vm_mapping, dump_size) in segments.iter() {
+        let load_header = ProgramHeader {
+            p_type: elf::PT_LOAD,
+            p_flags: segment_flags(vm_mapping.perms()),
+            p_offset: data_offset as u64,
+            p_vaddr: vm_mapping.map_to_addr() as u64,
+            p_paddr: 0,
+            p_filesz: *dump_size as u64,
+            p_memsz: vm_mapping.map_size() as u64,
+            p_align: PAGE_SIZE as u64,
+        };
+        writer.write(load_header.as_bytes())?;
+        data_offset += dump_size;
+    }
+    writer.write(&notes)?;
+
+    writer.skip_to(writer.offset.align_up(PAGE_SIZE));
+    for (vm_mapping, dump_size) in segments.iter() {
+        if writer.is_full() {
+            break;
+        }
+        dump_mapping(&mut writer, vm_mapping, *dump_size)?;
+    }
+    writer.finish()?;
+
+    Ok(true)
+}
+
+/// Stops the current thread if another thread of the process is dumping the core.
+///
+/// The registers of the current thread are recorded for the core dump, then the thread
+/// waits until it is exited with the process after the core is dumped. Returns `false`
+/// if no core dump is in progress.
+pub fn stop_for_core_dump(context: &UserContext) -> bool {
+    let current = current!();
+    let Some(state) = current.core_dump().get() else {
+        return false;
+    };
+
+    let current_thread = current_thread!();
+    state.contexts.lock().insert(current_thread.tid(), *context);
+    state.wait_queue.wake_all();
+    state
+        .wait_queue
+        .wait_until(|| current_thread.status().is_exited().then_some(()));
+    true
+}
+
+/// Stops the other threads of the process, and returns their registers.
+///
+/// The threads are interrupted by `SIGKILL`, and stop themselves when they handle the
+/// signal, see `stop_for_core_dump`. The threads that exit in the meantime are skipped,
+/// so are those that do not stop within `STOP_TIMEOUT`.
+fn stop_other_threads(
+    process: &Process,
+    dumping_tid: Tid,
+    state: &CoreDumpState,
+) -> BTreeMap<Tid, UserContext> {
+    let threads: Vec<_> = process
+        .threads()
+        .lock()
+        .iter()
+        .filter(|thread| thread.tid() != dumping_tid && !thread.status().is_exited())
+        .cloned()
+        .collect();
+    for thread in threads.iter() {
+        if let Some(posix_thread) = thread.as_posix_thread() {
+            posix_thread.enqueue_signal(Box::new(KernelSignal::new(SIGKILL)));
+        }
+    }
+
+    let are_all_stopped = || {
+        let contexts = state.contexts.lock();
+        threads
+            .iter()
+            .all(|thread| contexts.contains_key(&thread.tid()) || thread.status().is_exited())
+            .then_some(())
+    };
+    if state
+        .wait_queue
+        .wait_until_or_timeout(are_all_stopped, &STOP_TIMEOUT)
+        .is_none()
+    {
+        warn!("some threads are not stopped in time for the core dump");
+    }
+    state.contexts.lock().clone()
+}
+
+/// Returns the name of the thread, or that of the executable if the thread has no name.
+fn comm_of(process: &Process, posix_thread: &PosixThread) -> String {
+    let thread_name = posix_thread.thread_name().lock();
+    if let Some(name) = thread_name
+        .as_ref()
+        .and_then(|thread_name| thread_name.name().ok().flatten())
+    {
+        return name.to_string_lossy().into_owned();
+    }
+
+    let exe_path = process.executable_path();
+    let name = exe_path.rsplit('/').next().unwrap_or(&exe_path);
+    name.to_string()
+}
+
+/// Creates the core file, or truncates it if it exists.
+///
+/// Like Linux, an existing file is only reused if it is a regular file owned by the
+/// current user, and a symbolic link at the path is not followed.
+fn create_core_file(process: &Process, path: &str) -> Result<Arc<dyn Inode>> {
+    let fs_path = FsPath::try_from(path)?;
+    let flags =
+        AccessMode::O_WRONLY as u32 | (CreationFlags::O_CREAT | CreationFlags::O_NOFOLLOW).bits();
+    let mode = (InodeMode::S_IRUSR | InodeMode::S_IWUSR).bits();
+    let inode_handle = process.fs().read().open(&fs_path, flags, mode)?;
+
+    let inode = inode_handle.dentry().inode().clone();
+    if inode.type_() != InodeType::File {
+        return_errno_with_message!(Errno::EISDIR, "the core file is not a regular file");
+    }
+    if inode.owner()? != credentials().fsuid() {
+        return_errno_with_message!(Errno::EPERM, "the core file is owned by another user");
+    }
+    inode.resize(0)?;
+    Ok(inode)
+}
+
+/// Returns the number of bytes of the mapping to be dumped.
+fn dump_size_of(vm_mapping: &VmMapping) -> usize {
+    let perms = vm_mapping.perms();
+    if !perms.contains(VmPerms::READ) {
+        return 0;
+    }
+    if vm_mapping.file().is_some() && !perms.contains(VmPerms::WRITE) {
+        return 0;
+    }
+    vm_mapping.map_size()
+}
+
+fn segment_flags(perms: VmPerms) -> u32 {
+    let mut flags = 0;
+    if perms.contains(VmPerms::READ) {
+        flags |= elf::PF_R;
+    }
+    if perms.contains(VmPerms::WRITE) {
+        flags |= elf::PF_W;
+    }
+    if perms.contains(VmPerms::EXEC) {
+        flags |= elf::PF_X;
+    }
+    flags
+}
+
+/// Writes the contents of the mapping page by page.
+///
+/// The pages of anonymous mappings that have never been touched are left as holes,
+/// so that they are not allocated just to be dumped.
+fn dump_mapping(writer: &mut CoreWriter, vm_mapping: &VmMapping, dump_size: usize) -> Result<()> {
+    let is_anonymous = vm_mapping.file().is_none();
+    let mut page = vec![0u8; PAGE_SIZE];
+    for offset in (0..dump_size).step_by(PAGE_SIZE) {
+        if writer.is_full() {
+            break;
+        }
+        let page_idx = (vm_mapping.vmo_offset() + offset) / PAGE_SIZE;
+        if is_anonymous && !vm_mapping.vmo().is_page_committed(page_idx) {
+            writer.skip_to(writer.offset + PAGE_SIZE);
+            continue;
+        }
+
+        // The pages beyond the end of the mapped file cannot be read.
+        if vm_mapping.read_bytes(offset, &mut page).is_err() {
+            page.fill(0);
+        }
+        writer.write(&page)?;
+    }
+    Ok(())
+}
+
+fn build_notes(
+    process: &Process,
+    dumping_thread: &PosixThread,
+    context: &UserContext,
+    other_contexts: &BTreeMap<Tid, UserContext>,
+    siginfo: &siginfo_t,
+    comm: &str,
+) -> Result<Vec<u8>> {
+    let mut notes = Vec::new();
+
+    let threads = process.threads().lock().clone();
+    let dumping_tid = current_thread!().tid();
+    // Like Linux, the notes of the dumping thread come first, which is regarded as
+    // the current thread by debuggers.
+    let mut prstatus = prstatus_of(process, dumping_thread, dumping_tid);
+    prstatus.set_signal(siginfo.si_signo, siginfo.si_code);
+    prstatus.pr_reg = UserRegs::from(context);
+    let fp_regs = context.fp_regs();
+    prstatus.pr_fpvalid = fp_regs.is_valid() as i32;
+    elf::push_note(&mut notes, elf::NT_PRSTATUS, prstatus.as_bytes());
+    elf::push_note(
+        &mut notes,
+        elf::NT_PRPSINFO,
+        prpsinfo_of(process, comm)?.as_bytes(),
+    );
+    elf::push_note(&mut notes, elf::NT_SIGINFO, siginfo.as_bytes());
+    elf::push_note(&mut notes, elf::NT_AUXV, &auxv_of(process));
+    elf::push_note(&mut notes, elf::NT_FILE, &file_note_of(process));
+    if fp_regs.is_valid() {
+        elf::push_note(&mut notes, elf::NT_PRFPREG, fp_regs.as_slice());
+    }
+
+    // The threads that have exited are not dumped.
+    for thread in threads.iter() {
+        let Some(context) = other_contexts.get(&thread.tid()) else {
+            continue;
+        };
+        let Some(posix_thread) = thread.as_posix_thread() else {
+            continue;
+        };
+        let mut prstatus = prstatus_of(process, posix_thread, thread.tid());
+        prstatus.pr_reg = UserRegs::from(context);
+        let fp_regs = context.fp_regs();
+        prstatus.pr_fpvalid = fp_regs.is_valid() as i32;
+        elf::push_note(&mut notes, elf::NT_PRSTATUS, prstatus.as_bytes());
+        if fp_regs.is_valid() {
+            elf::push_note(&mut notes, elf::NT_PRFPREG, fp_regs.as_slice());
+        }
+    }
+
+    Ok(notes)
+}
+
+fn prstatus_of(process: &Process, posix_thread: &PosixThread, tid: Tid) -> ElfPrStatus {
+    let mut prstatus = ElfPrStatus::new_zeroed();
+    prstatus.pr_sigpend = posix_thread.sig_pending().as_u64();
+    prstatus.pr_sighold = posix_thread.sig_mask().lock().as_u64();
+    prstatus.pr_pid = tid as i32;
+    prstatus.pr_ppid = process.parent().map_or(0, |parent| parent.pid()) as i32;
+    prstatus.pr_pgrp = process.pgid() as i32;
+    prstatus.pr_sid = process.session().map_or(0, |session| session.sid()) as i32;
+
+    let prof_clock = posix_thread.prof_clock();
+    prstatus.pr_utime = prof_clock.user_clock().read_time().into();
+    prstatus.pr_stime = prof_clock.kernel_clock().read_time().into();
+    prstatus
+}
+
+fn prpsinfo_of(process: &Process, comm: &str) -> Result<ElfPrPsInfo> {
+    let mut prpsinfo = ElfPrPsInfo::new_zeroed();
+    // The process is running when the core is dumped.
+    prpsinfo.pr_sname = b'R';
+    prpsinfo.pr_nice = process.nice().load(Ordering::Relaxed).to_raw();
+
+    let credentials = credentials();
+    prpsinfo.pr_uid = credentials.ruid().as_u32();
+    prpsinfo.pr_gid = credentials.rgid().as_u32();
+    prpsinfo.pr_pid = process.pid() as i32;
+    prpsinfo.pr_ppid = process.parent().map_or(0, |parent| parent.pid()) as i32;
+    prpsinfo.pr_pgrp = process.pgid() as i32;
+    prpsinfo.pr_sid = process.session().map_or(0, |session| session.sid()) as i32;
+
+    // The name and the arguments are truncated, keeping the terminating null bytes.
+    let fname_len = comm.len().min(prpsinfo.pr_fname.len() - 1);
+    prpsinfo.pr_fname[..fname_len].copy_from_slice(&comm.as_bytes()[..fname_len]);
+    let argv = process.init_stack_reader().argv()?;
+    let psargs = argv
+        .iter()
+        .map(|arg| arg.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let psargs_len = psargs.len().min(prpsinfo.pr_psargs.len() - 1);
+    prpsinfo.pr_psargs[..psargs_len].copy_from_slice(&psargs.as_bytes()[..psargs_len]);
+
+    Ok(prpsinfo)
+}
+
+/// Returns the auxiliary vector, which is empty if it cannot be read from the init stack.
+fn auxv_of(process: &Process) -> Vec<u8> {
+    let auxv = process.init_stack_reader().auxv().unwrap_or_default();
+    auxv.iter()
+        .flat_map(|(key, val)| [key.to_le_bytes(), val.to_le_bytes()])
+        .flatten()
+        .collect()
+}
+
+/// Returns the descriptor of the `NT_FILE` note, which lists the file mappings as
+///
+/// ```text
+/// count, page_size,
+/// [start, end, file_ofs (in pages)] * count,
+/// [file name with the terminating null byte] * count
+/// ```
+fn file_note_of(process: &Process) -> Vec<u8> {
+    let mut entries = Vec::new();
+    let mut names = Vec::new();
+    let mut count = 0u64;
+    for vm_mapping in process.root_vmar().vm_mappings() {
+        let Some((dentry, file_offset)) = vm_mapping.file() else {
+            continue;
+        };
+        let start = vm_mapping.map_to_addr() as u64;
+        let end = start + vm_mapping.map_size() as u64;
+        let page_offset = (file_offset / PAGE_SIZE) as u64;
+        for val in [start, end, page_offset] {
+            entries.extend_from_slice(&val.to_le_bytes());
+        }
+        names.extend_from_slice(dentry.abs_path().as_bytes());
+        names.push(0);
+        count += 1;
+    }
+
+    let mut desc = Vec::new();
+    desc.extend_from_slice(&count.to_le_bytes());
+    desc.extend_from_slice(&(PAGE_SIZE as u64).to_le_bytes());
+    desc.extend_from_slice(&entries);
+    desc.extend_from_slice(&names);
+    desc
+}
+
+/// A writer of the core file, which silently stops at `RLIMIT_CORE` like Linux.
+///
+/// The file is written through the inode, so `RLIMIT_FSIZE` does not apply.
+struct CoreWriter {
+    inode: Arc<dyn Inode>,
+    offset: usize,
+    limit: usize,
+}
+
+impl CoreWriter {
+    fn write(&mut self, buf: &[u8]) -> Result<()> {
+        let len = buf.len().min(self.limit.saturating_sub(self.offset));
+        let buf = &buf[..len];
+        let mut written = 0;
+        while written < buf.len() {
+            let len = self
+                .inode
+                .write_at(self.offset + written, &buf[written..])?;
+            if len == 0 {
+                return_errno_with_message!(Errno::EIO, "failed to write the core file");
+            }
+            written += len;
+        }
+        self.offset += buf.len();
+        Ok(())
+    }
+
+    /// Moves to `offset`, leaving a hole in the file.
+    fn skip_to(&mut self, offset: usize) {
+        self.offset = offset.min(self.limit);
+    }
+
+    /// Returns whether the file has reached `RLIMIT_CORE`.
+    fn is_full(&self) -> bool {
+        self.offset >= self.limit
+    }
+
+    /// Extends the file over the hole at its end, if any.
+    fn finish(self) -> Result<()> {
+        if self.inode.size() < self.offset {
+            self.inode.resize(self.offset)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+    use crate::fs::{ramfs::RamFS, utils::FileSystem};
+
+    fn new_core_writer(limit: usize) -> CoreWriter {
+        crate::time::clocks::init_for_ktest();
+        let mode = InodeMode::from_bits_truncate(0o600);
+        let inode = RamFS::new()
+            .root_inode()
+            .create("core", InodeType::File, mode)
+            .unwrap();
+        CoreWriter {
+            inode,
+            offset: 0,
+            limit,
+        }
+    }
+
+    #[ktest]
+    fn truncate_at_limit() {
+        let mut writer = new_core_writer(10);
+        let inode = writer.inode.clone();
+        writer.write(b"012345").unwrap();
+        assert!(!writer.is_full());
+        writer.write(b"6789ab").unwrap();
+        assert!(writer.is_full());
+        writer.write(b"cd").unwrap();
+        writer.finish().unwrap();
+
+        let mut buf = [0u8; 16];
+        assert_eq!(inode.read_at(0, &mut buf).unwrap(), 10);
+        assert_eq!(&buf[..10], b"0123456789");
+    }
+
+    #[ktest]
+    fn skip_holes() {
+        let mut writer = new_core_writer(8);
+        let inode = writer.inode.clone();
+        writer.write(b"ab").unwrap();
+        writer.skip_to(6);
+        assert_eq!(writer.offset, 6);
+        writer.skip_to(100);
+        assert!(writer.is_full());
+        writer.finish().unwrap();
+
+        let mut buf = [0xffu8; 8];
+        assert_eq!(inode.read_at(0, &mut buf).unwrap(), 8);
+        assert_eq!(&buf, b"ab\0\0\0\0\0\0");
+    }
+}
@@ -0,0 +1,115 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::fmt::Write;
+
+use crate::{
+    prelude::*,
+    process::{credentials, Process, ResourceType},
+    thread::Tid,
+    time::{clocks::RealTimeClock, Clock},
+};
+
+/// The default pattern, which writes the file `core` in the working directory.
+const DEFAULT_CORE_PATTERN: &str = "core";
+
+/// The maximum length of a pattern, i.e., `CORENAME_MAX_SIZE` of Linux.
+const CORENAME_MAX_SIZE: usize = 128;
+
+lazy_static! {
+    static ref CORE_PATTERN: RwLock<String> = RwLock::new(String::from(DEFAULT_CORE_PATTERN));
+}
+
+/// Returns the pattern of the paths of core files, i.e., `/proc/sys/kernel/core_pattern`.
+pub fn core_pattern() -> String {
+    CORE_PATTERN.read().clone()
+}
+
+/// Sets the pattern of the paths of core files.
+///
+/// An empty pattern disables core dumps. Piping core dumps to a program with
+/// a pattern that starts with `|` is not supported.
+pub fn set_core_pattern(pattern: &str) -> Result<()> {
+    let pattern = pattern.strip_suffix('\n').unwrap_or(pattern);
+    if pattern.len() >= CORENAME_MAX_SIZE {
+        return_errno_with_message!(Errno::EINVAL, "the core pattern is too long");
+    }
+    if pattern.starts_with('|') {
+        return_errno_with_message!(
+            Errno::EINVAL,
+            "piping core dumps to a program is not supported"
+        );
+    }
+
+    *CORE_PATTERN.write() = pattern.to_string();
+    Ok(())
+}
+
+/// Expands the specifiers in the pattern for the core dump of the current thread.
+///
+/// The specifiers are the same as those of Linux, except `%h` and the ones of the
+/// namespaces, which are not supported and are dropped.
+pub(super) fn expand_core_pattern(
+    pattern: &str,
+    process: &Process,
+    tid: Tid,
+    comm: &str,
+    sig_num: i32,
+) -> String {
+    let mut path = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            path.push(c);
+            continue;
+        }
+
+        let Some(specifier) = chars.next() else {
+            break;
+        };
+        let _ = match specifier {
+            '%' => write!(path, "%"),
+            'p' | 'P' => write!(path, "{}", process.pid()),
+            'i' | 'I' => write!(path, "{}", tid),
+            'u' => write!(path, "{}", credentials().ruid().as_u32()),
+            'g' => write!(path, "{}", credentials().rgid().as_u32()),
+            's' => write!(path, "{}", sig_num),
+            't' => write!(path, "{}", RealTimeClock::get().read_time().as_secs()),
+            'e' => write!(path, "{}", comm.replace('/', "!")),
+            'E' => write!(path, "{}", process.executable_path().replace('/', "!")),
+            'c' => {
+                let limit = process
+                    .resource_limits()
+                    .lock()
+                    .get_rlimit(ResourceType::RLIMIT_CORE)
+                    .get_cur();
+                write!(path, "{}", limit)
+            }
+            _ => Ok(()),
+        };
+    }
+
+    // Like Linux, the path is limited in length even after the expansion.
+    if path.len() >= CORENAME_MAX_SIZE {
+        let mut len = CORENAME_MAX_SIZE - 1;
+        while !path.is_char_boundary(len) {
+            len -= 1;
+        }
+        path.truncate(len);
+    }
+    path
+}
+
+#[cfg(ktest)]
+mod test {
+    use alloc::format;
+
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn set_core_pattern_rejects_pipes() {
+        assert!(set_core_pattern("|/usr/bin/handler").is_err());
+        assert!(set_core_pattern(&format!("{:0>1$}", "", CORENAME_MAX_SIZE)).is_err());
+    }
+}
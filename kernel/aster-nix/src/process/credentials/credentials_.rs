@@ -2,7 +2,11 @@
 
 use ostd::sync::{RwLockReadGuard, RwLockWriteGuard};
 
-use super::{group::AtomicGid, user::AtomicUid, Gid, Uid};
+use super::{
+    group::AtomicGid,
+    user::{AtomicUid, RealUid},
+    Gid, Uid,
+};
 use crate::{
     prelude::*,
     process::credentials::capabilities::{AtomicCapSet, CapSet},
@@ -11,7 +15,7 @@ use crate::{
 #[derive(Debug)]
 pub(super) struct Credentials_ {
     /// Real user id. The user to which the process belongs.
-    ruid: AtomicUid,
+    ruid: RealUid,
     /// Effective user id. Used to determine the permissions granted to a process when it tries to perform various operations (i.e., system calls)
     euid: AtomicUid,
     /// Saved-set uid. Used by set_uid elf, the saved_set_uid will be set if the elf has setuid bit
@@ -53,7 +57,7 @@ impl Credentials_ {
         supplementary_gids.insert(gid);
 
         Self {
-            ruid: AtomicUid::new(uid),
+            ruid: RealUid::new(uid),
             euid: AtomicUid::new(uid),
             suid: AtomicUid::new(uid),
             fsuid: AtomicUid::new(uid),
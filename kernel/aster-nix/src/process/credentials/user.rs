@@ -10,6 +10,12 @@ pub struct Uid(u32);
 
 const ROOT_UID: u32 = 0;
 
+/// The numbers of the tasks of the users, keyed by their real user IDs.
+///
+/// The tasks are counted by their `RealUid`s, so that `RLIMIT_NPROC` can be checked
+/// without walking all the threads.
+static NR_TASKS: SpinLock<BTreeMap<u32, usize>> = SpinLock::new(BTreeMap::new());
+
 impl Uid {
     pub const fn new_root() -> Self {
         Self(ROOT_UID)
@@ -26,6 +32,12 @@ impl Uid {
     pub const fn as_u32(&self) -> u32 {
         self.0
     }
+
+    /// Returns the number of the tasks whose real user ID is this one.
+    pub fn nr_tasks(&self) -> usize {
+        let nr_tasks = NR_TASKS.lock_irq_disabled();
+        nr_tasks.get(&self.0).copied().unwrap_or(0)
+    }
 }
 
 #[derive(Debug)]
@@ -54,3 +66,83 @@ impl Clone for AtomicUid {
         Self(AtomicU32::new(self.0.load(Ordering::Acquire)))
     }
 }
+
+/// The real user ID of a task.
+///
+/// A task is counted as one of the tasks of its real user, see `Uid::nr_tasks`,
+/// as long as its credentials are alive.
+#[derive(Debug)]
+pub(super) struct RealUid(AtomicUid);
+
+impl RealUid {
+    pub fn new(uid: Uid) -> Self {
+        let mut nr_tasks = NR_TASKS.lock_irq_disabled();
+        *nr_tasks.entry(uid.0).or_insert(0) += 1;
+        Self(AtomicUid::new(uid))
+    }
+
+    pub fn set(&self, uid: Uid) {
+        let mut nr_tasks = NR_TASKS.lock_irq_disabled();
+        let old_uid = self.0.get();
+        if old_uid == uid {
+            return;
+        }
+        self.0.set(uid);
+        uncount_task(&mut nr_tasks, old_uid);
+        *nr_tasks.entry(uid.0).or_insert(0) += 1;
+    }
+
+    pub fn get(&self) -> Uid {
+        self.0.get()
+    }
+}
+
+impl Clone for RealUid {
+    fn clone(&self) -> Self {
+        Self::new(self.get())
+    }
+}
+
+impl Drop for RealUid {
+    fn drop(&mut self) {
+        let mut nr_tasks = NR_TASKS.lock_irq_disabled();
+        uncount_task(&mut nr_tasks, self.0.get());
+    }
+}
+
+fn uncount_task(nr_tasks: &mut BTreeMap<u32, usize>, uid: Uid) {
+    let Some(count) = nr_tasks.get_mut(&uid.0) else {
+        return;
+    };
+    *count -= 1;
+    if *count == 0 {
+        nr_tasks.remove(&uid.0);
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn count_tasks() {
+        let (uid_a, uid_b) = (Uid::new(60001), Uid::new(60002));
+
+        let ruid = RealUid::new(uid_a);
+        let cloned_ruid = ruid.clone();
+        assert_eq!(uid_a.nr_tasks(), 2);
+        assert_eq!(uid_b.nr_tasks(), 0);
+
+        cloned_ruid.set(uid_b);
+        cloned_ruid.set(uid_b);
+        assert_eq!(uid_a.nr_tasks(), 1);
+        assert_eq!(uid_b.nr_tasks(), 1);
+
+        drop(ruid);
+        drop(cloned_ruid);
+        assert_eq!(uid_a.nr_tasks(), 0);
+        assert_eq!(uid_b.nr_tasks(), 0);
+    }
+}
@@ -24,6 +24,10 @@ pub fn do_exit_group(term_status: TermStatus) {
             debug!("Ignore error when call exit: {:?}", e);
         }
     }
+    // The threads stopped for the core dump wait until they are exited.
+    if let Some(core_dump) = current.core_dump().get() {
+        core_dump.wake_stopped_threads();
+    }
 
     // Sends parent-death signal
     // FIXME: according to linux spec, the signal should be sent when a posix thread which
@@ -1,6 +1,7 @@
 // SPDX-License-Identifier: MPL-2.0
 
 mod clone;
+pub mod core_dump;
 pub mod credentials;
 mod exit;
 mod kill;
@@ -12,6 +13,7 @@ pub mod process_table;
 mod process_vm;
 mod program_loader;
 mod rlimit;
+mod rusage;
 pub mod signal;
 mod status;
 pub mod sync;
@@ -27,13 +29,15 @@ pub use process::{
     Terminal,
 };
 pub use process_filter::ProcessFilter;
-pub use process_vm::{MAX_ARGV_NUMBER, MAX_ARG_LEN, MAX_ENVP_NUMBER, MAX_ENV_LEN};
+pub use process_vm::{MAX_ARGV_NUMBER, MAX_ARG_LEN, MAX_ENVP_NUMBER, MAX_ENV_LEN, USER_HEAP_BASE};
 pub use program_loader::{check_executable_file, load_program_to_vm};
-pub use rlimit::ResourceType;
+pub use rlimit::{RLimit64, ResourceLimits, ResourceType};
+pub use rusage::{account_current, RUsage, ResourceUsage, UsageKind};
 pub use term_status::TermStatus;
 pub use wait::{wait_child_exit, WaitOptions};
 
 pub(super) fn init() {
     process::init();
     posix_thread::futex::init();
+    rusage::init();
 }
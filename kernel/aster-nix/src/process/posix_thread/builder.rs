@@ -2,6 +2,8 @@
 
 #![allow(dead_code)]
 
+use core::sync::atomic::AtomicU64;
+
 use ostd::user::UserSpace;
 
 use super::PosixThread;
@@ -10,7 +12,7 @@ use crate::{
     process::{
         posix_thread::name::ThreadName,
         signal::{sig_mask::SigMask, sig_queues::SigQueues},
-        Credentials, Process,
+        Credentials, Process, ResourceUsage,
     },
     thread::{status::ThreadStatus, task, thread_table, Thread, Tid},
     time::{clocks::ProfClock, TimerManager},
@@ -105,6 +107,8 @@ impl PosixThreadBuilder {
                 sig_stack: Mutex::new(None),
                 robust_list: Mutex::new(None),
                 prof_clock,
+                user_time_start: AtomicU64::new(0),
+                resource_usage: ResourceUsage::new(),
                 virtual_timer_manager,
                 prof_timer_manager,
             };
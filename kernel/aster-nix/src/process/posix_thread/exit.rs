@@ -22,6 +22,7 @@ pub fn do_exit(thread: Arc<Thread>, term_status: TermStatus) -> Result<()> {
     let tid = thread.tid();
 
     let posix_thread = thread.as_posix_thread().unwrap();
+    posix_thread.process().uncharge_task();
 
     let mut clear_ctid = posix_thread.clear_child_tid().lock();
     // If clear_ctid !=0 ,do a futex wake and write zero to the clear_ctid addr.
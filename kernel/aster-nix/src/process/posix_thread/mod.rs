@@ -2,6 +2,11 @@
 
 #![allow(dead_code)]
 
+use core::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
 use aster_rights::{ReadOp, WriteOp};
 
 use super::{
@@ -13,14 +18,17 @@ use super::{
         signals::Signal,
         SigEvents, SigEventsFilter, SigStack,
     },
-    Credentials, Process,
+    Credentials, Process, ResourceUsage,
 };
 use crate::{
     events::Observer,
     prelude::*,
     process::signal::constants::SIGCONT,
     thread::Tid,
-    time::{clocks::ProfClock, Timer, TimerManager},
+    time::{
+        clocks::{system_prof_clock, ProfClock},
+        Timer, TimerManager,
+    },
 };
 
 mod builder;
@@ -65,6 +73,12 @@ pub struct PosixThread {
 
     /// A profiling clock measures the user CPU time and kernel CPU time in the thread.
     prof_clock: Arc<ProfClock>,
+    /// When the thread entered the user mode or its user CPU time was last charged,
+    /// in nanoseconds of the monotonic time, or zero if it is not in the user mode.
+    user_time_start: AtomicU64,
+
+    /// The resources used by the thread.
+    resource_usage: ResourceUsage,
 
     /// A manager that manages timers based on the user CPU time of the current thread.
     virtual_timer_manager: Arc<TimerManager>,
@@ -78,6 +92,10 @@ impl PosixThread {
         self.process.upgrade().unwrap()
     }
 
+    pub fn weak_process(&self) -> Weak<Process> {
+        self.process.clone()
+    }
+
     pub fn thread_name(&self) -> &Mutex<Option<ThreadName>> {
         &self.name
     }
@@ -171,6 +189,54 @@ impl PosixThread {
         &self.prof_clock
     }
 
+    /// Returns the resources used by the thread.
+    pub fn resource_usage(&self) -> &ResourceUsage {
+        &self.resource_usage
+    }
+
+    /// Starts to charge the time as the user CPU time, when the thread is about to
+    /// enter the user mode.
+    pub fn enter_user_mode(&self) {
+        let now = aster_time::read_monotonic_time().as_nanos() as u64;
+        self.user_time_start.store(now, Ordering::Relaxed);
+    }
+
+    /// Charges the user CPU time since the thread entered the user mode,
+    /// when it returns to the kernel mode.
+    pub fn leave_user_mode(&self) {
+        let now = aster_time::read_monotonic_time().as_nanos() as u64;
+        let start = self.user_time_start.swap(0, Ordering::Relaxed);
+        if start != 0 {
+            self.charge_user_time(now.saturating_sub(start));
+        }
+    }
+
+    /// Charges the user CPU time since it was last charged, if the thread is in the user mode.
+    ///
+    /// This method is called at the timer interrupts that interrupt the user mode,
+    /// so that the user CPU clocks advance while the thread stays in the user mode.
+    pub fn update_user_time(&self) {
+        let now = aster_time::read_monotonic_time().as_nanos() as u64;
+        let Ok(start) =
+            self.user_time_start
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |start| {
+                    (start != 0).then_some(now)
+                })
+        else {
+            return;
+        };
+        self.charge_user_time(now.saturating_sub(start));
+    }
+
+    fn charge_user_time(&self, nanos: u64) {
+        let interval = Duration::from_nanos(nanos);
+        self.prof_clock.user_clock().add_time(interval);
+        if let Some(process) = self.process.upgrade() {
+            process.prof_clock().user_clock().add_time(interval);
+        }
+        system_prof_clock().user_clock().add_time(interval);
+    }
+
     /// Creates a timer based on the profiling CPU clock of the current thread.
     pub fn create_prof_timer<F>(&self, func: F) -> Arc<Timer>
     where
@@ -4,6 +4,7 @@
 
 use super::{Pid, Process};
 use crate::{
+    cgroup::{self, Cgroup},
     fs::{file_table::FileTable, fs_resolver::FsResolver, utils::FileCreationMask},
     prelude::*,
     process::{
@@ -35,6 +36,7 @@ pub struct ProcessBuilder<'a> {
     sig_dispositions: Option<Arc<Mutex<SigDispositions>>>,
     credentials: Option<Credentials>,
     nice: Option<Nice>,
+    cgroup: Option<Arc<Cgroup>>,
 }
 
 impl<'a> ProcessBuilder<'a> {
@@ -54,6 +56,7 @@ impl<'a> ProcessBuilder<'a> {
             sig_dispositions: None,
             credentials: None,
             nice: None,
+            cgroup: None,
         }
     }
 
@@ -112,6 +115,12 @@ impl<'a> ProcessBuilder<'a> {
         self
     }
 
+    /// Sets the cgroup of the process, which is the root cgroup by default.
+    pub fn cgroup(&mut self, cgroup: Arc<Cgroup>) -> &mut Self {
+        self.cgroup = Some(cgroup);
+        self
+    }
+
     fn check_build(&self) -> Result<()> {
         if self.main_thread_builder.is_some() {
             debug_assert!(self.parent.upgrade().is_some());
@@ -147,8 +156,14 @@ impl<'a> ProcessBuilder<'a> {
             sig_dispositions,
             credentials,
             nice,
+            cgroup,
         } = self;
 
+        // The main thread is charged to the cgroup before anything is created, so that
+        // the `pids` limit is enforced when a process is cloned.
+        let cgroup = cgroup.unwrap_or_else(|| cgroup::root().clone());
+        cgroup.try_charge_pids(1)?;
+
         let process_vm = process_vm.or_else(|| Some(ProcessVm::alloc())).unwrap();
 
         let file_table = file_table
@@ -186,6 +201,7 @@ impl<'a> ProcessBuilder<'a> {
                 umask,
                 resource_limits,
                 nice,
+                cgroup,
                 sig_dispositions,
             )
         };
@@ -194,7 +210,7 @@ impl<'a> ProcessBuilder<'a> {
             let builder = thread_builder.process(Arc::downgrade(&process));
             builder.build()
         } else {
-            Thread::new_posix_thread_from_executable(
+            let thread = Thread::new_posix_thread_from_executable(
                 pid,
                 credentials.unwrap(),
                 process.vm(),
@@ -203,11 +219,21 @@ impl<'a> ProcessBuilder<'a> {
                 Arc::downgrade(&process),
                 argv.unwrap(),
                 envp.unwrap(),
-            )?
+            );
+            match thread {
+                Ok(thread) => thread,
+                Err(err) => {
+                    process.uncharge_task();
+                    return Err(err);
+                }
+            }
         };
 
         process.threads().lock().push(thread);
 
+        // The process may inherit a `RLIMIT_CPU` from its parent.
+        process.enforce_cpu_limit();
+
         process.set_runnable();
 
         Ok(process)
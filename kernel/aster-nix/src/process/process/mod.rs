@@ -1,29 +1,35 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use core::time::Duration;
+
+use spin::Once;
+
 use self::timer_manager::PosixTimerManager;
 use super::{
+    core_dump::CoreDumpState,
     posix_thread::PosixThreadExt,
     process_table,
     process_vm::{Heap, InitStackReader, ProcessVm},
-    rlimit::ResourceLimits,
+    rlimit::{RLimit64, ResourceLimits, ResourceType},
     signal::{
-        constants::SIGCHLD,
+        constants::{SIGCHLD, SIGKILL, SIGXCPU},
         sig_disposition::SigDispositions,
         sig_mask::SigMask,
         sig_num::{AtomicSigNum, SigNum},
-        signals::Signal,
+        signals::{kernel::KernelSignal, Signal},
         Pauser,
     },
     status::ProcessStatus,
-    Credentials, TermStatus,
+    Credentials, RUsage, ResourceUsage, TermStatus,
 };
 use crate::{
+    cgroup::Cgroup,
     device::tty::open_ntty_as_controlling_terminal,
     fs::{file_table::FileTable, fs_resolver::FsResolver, utils::FileCreationMask},
     prelude::*,
     sched::nice::Nice,
     thread::{allocate_tid, Thread},
-    time::clocks::ProfClock,
+    time::{clocks::ProfClock, timer::Timeout},
     vm::vmar::Vmar,
 };
 
@@ -55,6 +61,13 @@ pub(super) fn init() {
     timer_manager::init();
 }
 
+/// The cgroup that a process belongs to.
+struct CgroupMembership {
+    cgroup: Arc<Cgroup>,
+    /// The number of the threads that are charged to the cgroup
+    nr_tasks: u64,
+}
+
 /// Process stands for a set of threads that shares the same userspace.
 pub struct Process {
     // Immutable Part
@@ -89,21 +102,30 @@ pub struct Process {
     /// According to POSIX.1, the nice value is a per-process attribute,
     /// the threads in a process should share a nice value.
     nice: Atomic<Nice>,
+    /// The cgroup of the process
+    cgroup: SpinLock<CgroupMembership>,
 
     // Signal
     /// Sig dispositions
     sig_dispositions: Arc<Mutex<SigDispositions>>,
     /// The signal that the process should receive when parent process exits.
     parent_death_signal: AtomicSigNum,
+    /// The core dump of the process, which is set when a thread starts to dump the core
+    core_dump: Once<CoreDumpState>,
 
     /// A profiling clock measures the user CPU time and kernel CPU time of the current process.
     prof_clock: Arc<ProfClock>,
+    /// The resources used by the threads of the process.
+    resource_usage: ResourceUsage,
+    /// The resources used by the terminated children that have been waited for.
+    children_rusage: Mutex<RUsage>,
 
     /// A manager that manages timer resources and utilities of the process.
     timer_manager: PosixTimerManager,
 }
 
 impl Process {
+    /// Creates a process, whose main thread has been charged to the `cgroup`.
     #[allow(clippy::too_many_arguments)]
     fn new(
         pid: Pid,
@@ -118,6 +140,7 @@ impl Process {
         umask: Arc<RwLock<FileCreationMask>>,
         resource_limits: ResourceLimits,
         nice: Nice,
+        cgroup: Arc<Cgroup>,
         sig_dispositions: Arc<Mutex<SigDispositions>>,
     ) -> Arc<Self> {
         let children_pauser = {
@@ -129,7 +152,7 @@ impl Process {
 
         let prof_clock = ProfClock::new();
 
-        Arc::new_cyclic(|process_ref: &Weak<Process>| Self {
+        let process = Arc::new_cyclic(|process_ref: &Weak<Process>| Self {
             pid,
             threads: Mutex::new(threads),
             executable_path: RwLock::new(executable_path),
@@ -144,11 +167,20 @@ impl Process {
             umask,
             sig_dispositions,
             parent_death_signal: AtomicSigNum::new_empty(),
+            core_dump: Once::new(),
             resource_limits: Mutex::new(resource_limits),
             nice: Atomic::new(nice),
+            cgroup: SpinLock::new(CgroupMembership {
+                cgroup: cgroup.clone(),
+                nr_tasks: 1,
+            }),
             timer_manager: PosixTimerManager::new(&prof_clock, process_ref),
             prof_clock,
-        })
+            resource_usage: ResourceUsage::new(),
+            children_rusage: Mutex::new(RUsage::default()),
+        });
+        cgroup.add_process(&process);
+        process
     }
 
     /// init a user process and run the process
@@ -229,6 +261,22 @@ impl Process {
         &self.prof_clock
     }
 
+    /// Gets the resources used by the threads of the process.
+    pub fn resource_usage(&self) -> &ResourceUsage {
+        &self.resource_usage
+    }
+
+    /// Returns the resource usage of the process, i.e., that of all its threads.
+    pub fn rusage(&self) -> RUsage {
+        self.resource_usage.snapshot(&self.prof_clock)
+    }
+
+    /// Gets the resource usage of the terminated children that have been waited for,
+    /// which includes that of their waited children in turn.
+    pub fn children_rusage(&self) -> &Mutex<RUsage> {
+        &self.children_rusage
+    }
+
     /// Gets the timer resources and utilities of the process.
     pub fn timer_manager(&self) -> &PosixTimerManager {
         &self.timer_manager
@@ -250,6 +298,42 @@ impl Process {
         &self.resource_limits
     }
 
+    /// Enforces the `RLIMIT_CPU` of the process.
+    ///
+    /// Like Linux, the process receives `SIGXCPU` when its CPU time reaches the soft
+    /// limit and every second after that, and `SIGKILL` when the CPU time reaches the
+    /// hard limit. The CPU limit timer is set to expire at the next of these points.
+    pub fn enforce_cpu_limit(&self) {
+        let rlimit = *self
+            .resource_limits
+            .lock()
+            .get_rlimit(ResourceType::RLIMIT_CPU);
+        let to_duration =
+            |secs: u64| (secs != RLimit64::INFINITY).then(|| Duration::from_secs(secs));
+        let soft_limit = to_duration(rlimit.get_cur());
+        let hard_limit = to_duration(rlimit.get_max());
+
+        let cpu_limit_timer = self.timer_manager.cpu_limit_timer();
+        let cpu_time = self.prof_clock.read_time();
+        if hard_limit.is_some_and(|hard_limit| cpu_time >= hard_limit) {
+            cpu_limit_timer.cancel();
+            self.enqueue_signal(KernelSignal::new(SIGKILL));
+            return;
+        }
+
+        let next_soft_limit = soft_limit.map(|soft_limit| {
+            if cpu_time < soft_limit {
+                return soft_limit;
+            }
+            self.enqueue_signal(KernelSignal::new(SIGXCPU));
+            cpu_time + Duration::from_secs(1)
+        });
+        match [next_soft_limit, hard_limit].into_iter().flatten().min() {
+            Some(expired_time) => cpu_limit_timer.set_timeout(Timeout::When(expired_time)),
+            None => cpu_limit_timer.cancel(),
+        }
+    }
+
     pub fn nice(&self) -> &Atomic<Nice> {
         &self.nice
     }
@@ -283,6 +367,52 @@ impl Process {
         &self.children_pauser
     }
 
+    // *********** Cgroup ***********
+
+    /// Returns the cgroup of the process.
+    pub fn cgroup(&self) -> Arc<Cgroup> {
+        self.cgroup.lock_irq_disabled().cgroup.clone()
+    }
+
+    /// Charges a new thread to the cgroup of the process.
+    ///
+    /// This method returns `EAGAIN` if the `pids` limit of the cgroup is reached.
+    pub(in crate::process) fn charge_task(&self) -> Result<()> {
+        let mut membership = self.cgroup.lock_irq_disabled();
+        membership.cgroup.try_charge_pids(1)?;
+        membership.nr_tasks += 1;
+        Ok(())
+    }
+
+    /// Uncharges an exited thread from the cgroup of the process.
+    ///
+    /// The process leaves its cgroup after all its threads exit.
+    pub(in crate::process) fn uncharge_task(&self) {
+        let mut membership = self.cgroup.lock_irq_disabled();
+        membership.cgroup.uncharge_pids(1);
+        membership.nr_tasks = membership.nr_tasks.saturating_sub(1);
+        if membership.nr_tasks == 0 {
+            membership.cgroup.remove_process(self.pid);
+        }
+    }
+
+    /// Moves the process with its threads to the cgroup.
+    ///
+    /// The threads are charged to the new cgroup regardless of its `pids` limit.
+    pub(crate) fn move_to_cgroup(self: &Arc<Self>, cgroup: &Arc<Cgroup>) {
+        let mut membership = self.cgroup.lock_irq_disabled();
+        if Arc::ptr_eq(&membership.cgroup, cgroup) || membership.nr_tasks == 0 {
+            return;
+        }
+
+        let nr_tasks = membership.nr_tasks;
+        membership.cgroup.uncharge_pids(nr_tasks);
+        membership.cgroup.remove_process(self.pid);
+        cgroup.charge_pids(nr_tasks);
+        cgroup.add_process(self);
+        membership.cgroup = cgroup.clone();
+    }
+
     // *********** Process group & Session***********
 
     /// Returns the process group ID of the process.
@@ -614,6 +744,11 @@ impl Process {
         self.parent_death_signal.as_sig_num()
     }
 
+    /// Returns the core dump of the process, which is set by the thread dumping the core.
+    pub(super) fn core_dump(&self) -> &Once<CoreDumpState> {
+        &self.core_dump
+    }
+
     // ******************* Status ********************
 
     fn set_runnable(&self) {
@@ -650,13 +785,9 @@ pub fn current() -> Arc<Process> {
 }
 
 #[cfg(ktest)]
-mod test {
-
-    use ostd::prelude::*;
-
-    use super::*;
-
-    fn new_process(parent: Option<Arc<Process>>) -> Arc<Process> {
+impl Process {
+    /// Creates a process without any threads for the ktests.
+    pub(crate) fn new_for_ktest(parent: Option<Arc<Process>>) -> Arc<Process> {
         crate::util::random::init();
         crate::fs::rootfs::init_root_mount();
         let pid = allocate_tid();
@@ -676,9 +807,18 @@ mod test {
             Arc::new(RwLock::new(FileCreationMask::default())),
             ResourceLimits::default(),
             Nice::default(),
+            crate::cgroup::root().clone(),
             Arc::new(Mutex::new(SigDispositions::default())),
         )
     }
+}
+
+#[cfg(ktest)]
+mod test {
+
+    use ostd::prelude::*;
+
+    use super::*;
 
     fn new_process_in_session(parent: Option<Arc<Process>>) -> Arc<Process> {
         // Lock order: session table -> group table -> group of process -> group inner
@@ -686,7 +826,7 @@ mod test {
         let mut session_table_mut = process_table::session_table_mut();
         let mut group_table_mut = process_table::group_table_mut();
 
-        let process = new_process(parent);
+        let process = Process::new_for_ktest(parent);
         // Creates new group
         let group = ProcessGroup::new(process.clone());
         *process.process_group.lock() = Arc::downgrade(&group);
@@ -718,7 +858,7 @@ mod test {
     #[ktest]
     fn init_process() {
         crate::time::clocks::init_for_ktest();
-        let process = new_process(None);
+        let process = Process::new_for_ktest(None);
         assert!(process.process_group().is_none());
         assert!(process.session().is_none());
     }
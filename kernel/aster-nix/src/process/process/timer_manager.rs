@@ -27,7 +27,7 @@ use crate::{
         Thread,
     },
     time::{
-        clocks::{ProfClock, RealTimeClock},
+        clocks::{idle_clock, system_prof_clock, ProfClock, RealTimeClock},
         Timer, TimerManager,
     },
 };
@@ -39,13 +39,14 @@ use crate::{
 /// CPU clock.
 fn update_cpu_time() {
     let current_thread = Thread::current();
+    let jiffies_interval = Duration::from_millis(1000 / TIMER_FREQ);
     if let Some(posix_thread) = current_thread.as_posix_thread() {
         let process = posix_thread.process();
         let timer_manager = process.timer_manager();
-        let jiffies_interval = Duration::from_millis(1000 / TIMER_FREQ);
-        // Based on whether the timer interrupt occurs in kernel mode or user mode,
-        // the function will add the duration of one timer interrupt interval to the
-        // corresponding CPU clocks.
+        // The kernel CPU time is sampled: if the timer interrupt occurs in kernel mode,
+        // the duration of one timer interrupt interval is added to the kernel CPU clocks.
+        // The user CPU time is measured when the thread enters and leaves the user mode,
+        // and is charged here as well, so that the user CPU clocks keep advancing.
         if is_kernel_interrupted() {
             posix_thread
                 .prof_clock()
@@ -55,12 +56,11 @@ fn update_cpu_time() {
                 .prof_clock()
                 .kernel_clock()
                 .add_time(jiffies_interval);
-        } else {
-            posix_thread
-                .prof_clock()
-                .user_clock()
+            system_prof_clock()
+                .kernel_clock()
                 .add_time(jiffies_interval);
-            process.prof_clock().user_clock().add_time(jiffies_interval);
+        } else {
+            posix_thread.update_user_time();
             timer_manager
                 .virtual_timer()
                 .timer_manager()
@@ -71,6 +71,10 @@ fn update_cpu_time() {
             .timer_manager()
             .process_expired_timers();
         posix_thread.process_expired_timers();
+    } else {
+        // The time spent in kernel threads (e.g., the idle loop of the init thread
+        // and the workers of the work queues) is counted as idle.
+        idle_clock().add_time(jiffies_interval);
     }
 }
 
@@ -88,6 +92,8 @@ pub struct PosixTimerManager {
     virtual_timer: Arc<Timer>,
     /// A timer based on the profiling clock.
     prof_timer: Arc<Timer>,
+    /// A timer based on the profiling clock, which enforces `RLIMIT_CPU`.
+    cpu_limit_timer: Arc<Timer>,
     /// An ID allocator to allocate unique timer IDs.
     id_allocator: Mutex<IdAlloc>,
     /// A container managing all POSIX timers created by `timer_create()` syscall
@@ -115,6 +121,27 @@ fn create_process_timer_callback(process_ref: &Weak<Process>) -> impl Fn() + Clo
     }
 }
 
+fn create_cpu_limit_timer_callback(process_ref: &Weak<Process>) -> impl Fn() {
+    let current_process = process_ref.clone();
+    let enforce_cpu_limit = move || {
+        if let Some(process) = current_process.upgrade() {
+            process.enforce_cpu_limit();
+        }
+    };
+
+    // Enforcing the limit locks the resource limits, which cannot be done in the
+    // timer interrupt.
+    let work_func = Box::new(enforce_cpu_limit);
+    let work_item = Arc::new(WorkItem::new(work_func));
+
+    move || {
+        submit_work_item(
+            work_item.clone(),
+            crate::thread::work_queue::WorkPriority::High,
+        );
+    }
+}
+
 impl PosixTimerManager {
     pub(super) fn new(prof_clock: &Arc<ProfClock>, process_ref: &Weak<Process>) -> Self {
         const MAX_NUM_OF_POSIX_TIMERS: usize = 10000;
@@ -126,11 +153,15 @@ impl PosixTimerManager {
         let virtual_timer =
             TimerManager::new(prof_clock.user_clock().clone()).create_timer(callback.clone());
         let prof_timer = TimerManager::new(prof_clock.clone()).create_timer(callback);
+        let cpu_limit_timer = prof_timer
+            .timer_manager()
+            .create_timer(create_cpu_limit_timer_callback(process_ref));
 
         Self {
             alarm_timer,
             virtual_timer,
             prof_timer,
+            cpu_limit_timer,
             id_allocator: Mutex::new(IdAlloc::with_capacity(MAX_NUM_OF_POSIX_TIMERS)),
             posix_timers: Mutex::new(Vec::new()),
         }
@@ -151,6 +182,11 @@ impl PosixTimerManager {
         &self.prof_timer
     }
 
+    /// Gets the timer that enforces `RLIMIT_CPU` of the corresponding process.
+    pub fn cpu_limit_timer(&self) -> &Arc<Timer> {
+        &self.cpu_limit_timer
+    }
+
     /// Creates a timer based on the profiling CPU clock of the current process.
     pub fn create_prof_timer<F>(&self, func: F) -> Arc<Timer>
     where
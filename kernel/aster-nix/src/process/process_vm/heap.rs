@@ -58,6 +58,17 @@ impl Heap {
         Ok(())
     }
 
+    /// Returns the lowest address of the heap.
+    pub(super) fn base(&self) -> Vaddr {
+        self.base
+    }
+
+    /// Returns the size of the heap in use, i.e., below the program break.
+    pub(super) fn size(&self) -> usize {
+        let current_heap_end = self.current_heap_end.load(Ordering::Relaxed);
+        (current_heap_end - self.base).align_up(PAGE_SIZE)
+    }
+
     pub fn brk(&self, new_heap_end: Option<Vaddr>) -> Result<Vaddr> {
         let current = current!();
         let root_vmar = current.root_vmar();
@@ -73,6 +84,15 @@ impl Heap {
                     return Ok(current_heap_end);
                 }
                 let new_size = (new_heap_end - self.base).align_up(PAGE_SIZE);
+                let current_size = self.size();
+                if new_size > current_size {
+                    let resource_limits = current.resource_limits().lock();
+                    current.vm().check_map_limits(
+                        &resource_limits,
+                        new_size - current_size,
+                        true,
+                    )?;
+                }
                 let heap_mapping = root_vmar.get_vm_mapping(USER_HEAP_BASE)?;
                 let heap_vmo = heap_mapping.vmo();
                 heap_vmo.resize(new_size)?;
@@ -130,7 +130,7 @@ impl InitStack {
 
         let vmar_map_options = {
             let perms = VmPerms::READ | VmPerms::WRITE;
-            let map_addr = self.initial_top - self.max_size;
+            let map_addr = self.map_addr();
             debug_assert!(map_addr % PAGE_SIZE == 0);
             root_vmar.new_map(vmo, perms)?.offset(map_addr)
         };
@@ -141,6 +141,11 @@ impl InitStack {
         Ok(())
     }
 
+    /// Returns the lowest address of the mapping of the stack.
+    pub(super) fn map_addr(&self) -> Vaddr {
+        self.initial_top - self.max_size
+    }
+
     /// Returns the user stack top(highest address), used to setup rsp.
     ///
     /// This method should only be called after the stack is initialized.
@@ -406,6 +411,30 @@ impl<'a> InitStackReader<'a> {
         Ok(envp)
     }
 
+    /// Read the auxiliary vector from the process, including the terminating `AT_NULL` entry
+    pub fn auxv(&self) -> Result<Vec<(u64, u64)>> {
+        let argc = self.argc()? as usize;
+        let envc = self.envp()?.len();
+        // base = the start of envp
+        // + the size of env pointer(8) * the number of env(envc)
+        // + the size of null pointer(8)
+        let base = self.user_stack_top() + 8 + 8 * argc + 8 + 8 * envc + 8;
+
+        let mut auxv = Vec::new();
+        loop {
+            let offset = base + auxv.len() * 16;
+            let key = self.vmar.read_val::<u64>(offset)?;
+            let val = self.vmar.read_val::<u64>(offset + 8)?;
+            auxv.push((key, val));
+
+            if key == AuxKey::AT_NULL.as_u64() {
+                break;
+            }
+        }
+
+        Ok(auxv)
+    }
+
     pub const fn user_stack_top(&self) -> Vaddr {
         self.base
     }
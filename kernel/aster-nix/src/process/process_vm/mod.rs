@@ -16,14 +16,18 @@ use aster_rights::Full;
 pub use heap::Heap;
 
 pub use self::{
-    heap::USER_HEAP_SIZE_LIMIT,
+    heap::{USER_HEAP_BASE, USER_HEAP_SIZE_LIMIT},
     init_stack::{
         aux_vec::{AuxKey, AuxVec},
         InitStack, InitStackReader, InitStackWriter, INIT_STACK_SIZE, MAX_ARGV_NUMBER, MAX_ARG_LEN,
         MAX_ENVP_NUMBER, MAX_ENV_LEN,
     },
 };
-use crate::{prelude::*, vm::vmar::Vmar};
+use super::{ResourceLimits, ResourceType};
+use crate::{
+    prelude::*,
+    vm::{perms::VmPerms, vmar::Vmar},
+};
 
 /*
  * The user's virtual memory space layout looks like below.
@@ -128,6 +132,58 @@ impl ProcessVm {
         &self.heap
     }
 
+    /// Checks whether `len` more bytes can be mapped without exceeding `RLIMIT_AS`, and,
+    /// if the bytes are data, i.e., private and writable, without exceeding `RLIMIT_DATA`.
+    pub fn check_map_limits(
+        &self,
+        resource_limits: &ResourceLimits,
+        len: usize,
+        is_data: bool,
+    ) -> Result<()> {
+        let (total_size, data_size) = self.mapped_sizes();
+
+        let max_total_size = resource_limits
+            .get_rlimit(ResourceType::RLIMIT_AS)
+            .get_cur();
+        if (total_size + len) as u64 > max_total_size {
+            return_errno_with_message!(Errno::ENOMEM, "the address space limit is reached");
+        }
+
+        let max_data_size = resource_limits
+            .get_rlimit(ResourceType::RLIMIT_DATA)
+            .get_cur();
+        if is_data && (data_size + len) as u64 > max_data_size {
+            return_errno_with_message!(Errno::ENOMEM, "the data segment limit is reached");
+        }
+        Ok(())
+    }
+
+    /// Returns the size of the mapped memory and the size of the data in it.
+    ///
+    /// The heap is counted by its size in use rather than the size of its mapping,
+    /// and the stack is not counted as data, like Linux.
+    fn mapped_sizes(&self) -> (usize, usize) {
+        let heap_size = self.heap.size();
+        let mut total_size = heap_size;
+        let mut data_size = heap_size;
+        for vm_mapping in self.root_vmar.vm_mappings() {
+            let map_addr = vm_mapping.map_to_addr();
+            if map_addr == self.heap.base() {
+                continue;
+            }
+
+            let map_size = vm_mapping.map_size();
+            total_size += map_size;
+            if map_addr != self.init_stack.map_addr()
+                && !vm_mapping.is_shared()
+                && vm_mapping.perms().contains(VmPerms::WRITE)
+            {
+                data_size += map_size;
+            }
+        }
+        (total_size, data_size)
+    }
+
     /// Clears existing mappings and then maps stack and heap vmo.
     pub(super) fn clear_and_map(&self) {
         self.root_vmar.clear().unwrap();
@@ -135,3 +191,51 @@ impl ProcessVm {
         self.heap.alloc_and_map_vmo(&self.root_vmar).unwrap();
     }
 }
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+    use crate::process::RLimit64;
+
+    #[ktest]
+    fn address_space_limit() {
+        crate::util::random::init();
+        let process_vm = ProcessVm::alloc();
+        let mut limits = ResourceLimits::default();
+        let (total_size, _) = process_vm.mapped_sizes();
+
+        *limits.get_rlimit_mut(ResourceType::RLIMIT_AS) =
+            RLimit64::new((total_size + PAGE_SIZE) as u64);
+        assert!(process_vm
+            .check_map_limits(&limits, PAGE_SIZE, false)
+            .is_ok());
+        let err = process_vm
+            .check_map_limits(&limits, 2 * PAGE_SIZE, false)
+            .unwrap_err();
+        assert_eq!(err.error(), Errno::ENOMEM);
+    }
+
+    #[ktest]
+    fn data_limit() {
+        crate::util::random::init();
+        let process_vm = ProcessVm::alloc();
+        let mut limits = ResourceLimits::default();
+        // The heap is empty and the stack is not data.
+        let (_, data_size) = process_vm.mapped_sizes();
+        assert_eq!(data_size, 0);
+
+        *limits.get_rlimit_mut(ResourceType::RLIMIT_DATA) = RLimit64::new(PAGE_SIZE as u64);
+        assert!(process_vm
+            .check_map_limits(&limits, PAGE_SIZE, true)
+            .is_ok());
+        assert!(process_vm
+            .check_map_limits(&limits, 2 * PAGE_SIZE, false)
+            .is_ok());
+        let err = process_vm
+            .check_map_limits(&limits, 2 * PAGE_SIZE, true)
+            .unwrap_err();
+        assert_eq!(err.error(), Errno::ENOMEM);
+    }
+}
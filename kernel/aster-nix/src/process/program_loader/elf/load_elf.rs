@@ -107,7 +107,11 @@ fn lookup_and_parse_ldso(
     Ok(Some((ldso_file, ldso_elf)))
 }
 
-fn load_ldso(root_vmar: &Vmar<Full>, ldso_file: &Dentry, ldso_elf: &Elf) -> Result<LdsoLoadInfo> {
+fn load_ldso(
+    root_vmar: &Vmar<Full>,
+    ldso_file: &Arc<Dentry>,
+    ldso_elf: &Elf,
+) -> Result<LdsoLoadInfo> {
     let map_addr = map_segment_vmos(ldso_elf, root_vmar, ldso_file)?;
     Ok(LdsoLoadInfo::new(
         ldso_elf.entry_point() + map_addr,
@@ -119,7 +123,7 @@ fn init_and_map_vmos(
     process_vm: &ProcessVm,
     ldso: Option<(Arc<Dentry>, Elf)>,
     parsed_elf: &Elf,
-    elf_file: &Dentry,
+    elf_file: &Arc<Dentry>,
 ) -> Result<(Vaddr, AuxVec)> {
     let root_vmar = process_vm.root_vmar();
 
@@ -198,7 +202,11 @@ impl ElfLoadInfo {
 }
 
 /// init vmo for each segment and then map segment to root vmar
-pub fn map_segment_vmos(elf: &Elf, root_vmar: &Vmar<Full>, elf_file: &Dentry) -> Result<Vaddr> {
+pub fn map_segment_vmos(
+    elf: &Elf,
+    root_vmar: &Vmar<Full>,
+    elf_file: &Arc<Dentry>,
+) -> Result<Vaddr> {
     // all segments of the shared object must be mapped to a continuous vm range
     // to ensure the relative offset of each segment not changed.
     let base_addr = if elf.is_shared_object() {
@@ -219,6 +227,7 @@ pub fn map_segment_vmos(elf: &Elf, root_vmar: &Vmar<Full>, elf_file: &Dentry) ->
                 anonymous_map_size,
                 root_vmar,
                 base_addr,
+                elf_file,
             )?;
         }
     }
@@ -257,6 +266,7 @@ fn map_segment_vmo(
     anonymous_map_size: usize,
     root_vmar: &Vmar<Full>,
     base_addr: Vaddr,
+    elf_file: &Arc<Dentry>,
 ) -> Result<()> {
     let perms = parse_segment_perm(program_header.flags);
     let offset = (program_header.virtual_addr as Vaddr).align_down(PAGE_SIZE);
@@ -267,7 +277,11 @@ fn map_segment_vmo(
         perms
     );
     let vmo_size = vmo.size();
-    let mut vm_map_options = root_vmar.new_map(vmo, perms)?.can_overwrite(true);
+    let file_offset = (program_header.offset as usize).align_down(PAGE_SIZE);
+    let mut vm_map_options = root_vmar
+        .new_map(vmo, perms)?
+        .can_overwrite(true)
+        .file(elf_file.clone(), file_offset);
     let offset = base_addr + offset;
     vm_map_options = vm_map_options.offset(offset);
     let map_addr = vm_map_options.build()?;
@@ -2,9 +2,10 @@
 
 #![allow(non_camel_case_types)]
 
-use super::process_vm::{INIT_STACK_SIZE, USER_HEAP_SIZE_LIMIT};
+use super::process_vm::INIT_STACK_SIZE;
 use crate::prelude::*;
 
+#[derive(Clone)]
 pub struct ResourceLimits {
     rlimits: [RLimit64; RLIMIT_COUNT],
 }
@@ -22,15 +23,16 @@ impl ResourceLimits {
 impl Default for ResourceLimits {
     fn default() -> Self {
         let stack_size = RLimit64::new(INIT_STACK_SIZE as u64);
-        let heap_size = RLimit64::new(USER_HEAP_SIZE_LIMIT as u64);
         let open_files = RLimit64::new(1024);
+        // Like Linux, core dumps are disabled unless the soft limit is raised.
+        let core_size = RLimit64::new(0);
 
         let mut rlimits = Self {
             rlimits: [RLimit64::default(); RLIMIT_COUNT],
         };
         *rlimits.get_rlimit_mut(ResourceType::RLIMIT_STACK) = stack_size;
-        *rlimits.get_rlimit_mut(ResourceType::RLIMIT_DATA) = heap_size;
         *rlimits.get_rlimit_mut(ResourceType::RLIMIT_NOFILE) = open_files;
+        *rlimits.get_rlimit_mut(ResourceType::RLIMIT_CORE) = core_size;
         rlimits
     }
 }
@@ -66,8 +68,14 @@ pub struct RLimit64 {
 }
 
 impl RLimit64 {
+    /// The value of an unlimited resource, i.e., `RLIM_INFINITY`.
+    pub const INFINITY: u64 = u64::MAX;
+
     pub fn new(cur: u64) -> Self {
-        Self { cur, max: u64::MAX }
+        Self {
+            cur,
+            max: Self::INFINITY,
+        }
     }
 
     pub fn get_cur(&self) -> u64 {
@@ -77,13 +85,47 @@ impl RLimit64 {
     pub fn get_max(&self) -> u64 {
         self.max
     }
+
+    /// Returns whether the soft limit does not exceed the hard limit.
+    pub fn is_valid(&self) -> bool {
+        self.cur <= self.max
+    }
 }
 
 impl Default for RLimit64 {
     fn default() -> Self {
         Self {
-            cur: u64::MAX,
-            max: u64::MAX,
+            cur: Self::INFINITY,
+            max: Self::INFINITY,
         }
     }
 }
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn default_limits() {
+        let limits = ResourceLimits::default();
+        let nofile = limits.get_rlimit(ResourceType::RLIMIT_NOFILE);
+        assert_eq!(
+            (nofile.get_cur(), nofile.get_max()),
+            (1024, RLimit64::INFINITY)
+        );
+        let core = limits.get_rlimit(ResourceType::RLIMIT_CORE);
+        assert_eq!((core.get_cur(), core.get_max()), (0, RLimit64::INFINITY));
+        let data = limits.get_rlimit(ResourceType::RLIMIT_DATA);
+        assert_eq!(data.get_cur(), RLimit64::INFINITY);
+    }
+
+    #[ktest]
+    fn valid_limits() {
+        assert!(RLimit64::new(0).is_valid());
+        assert!(RLimit64::default().is_valid());
+        assert!(RLimit64 { cur: 1, max: 1 }.is_valid());
+        assert!(!RLimit64 { cur: 2, max: 1 }.is_valid());
+    }
+}
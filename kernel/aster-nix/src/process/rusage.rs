@@ -0,0 +1,219 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::{
+    ops::AddAssign,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use aster_block::bio::{register_io_accounting_callback, BioType};
+use ostd::task::current_task;
+
+use crate::{
+    prelude::*,
+    process::posix_thread::PosixThreadExt,
+    thread::Thread,
+    time::{clocks::ProfClock, Clock},
+};
+
+/// The counters of the resources used by a thread or a process.
+///
+/// The CPU times are not included, which are recorded by `ProfClock`.
+/// The counters of a process include those of all its threads, dead or alive.
+#[derive(Debug, Default)]
+pub struct ResourceUsage {
+    /// The page faults that are handled without I/O
+    minflt: AtomicU64,
+    /// The page faults that need to read the pages from files
+    majflt: AtomicU64,
+    /// The sectors read from the block devices
+    inblock: AtomicU64,
+    /// The sectors written to the block devices
+    oublock: AtomicU64,
+    /// The context switches due to blocking
+    nvcsw: AtomicU64,
+    /// The context switches due to preemption or yielding
+    nivcsw: AtomicU64,
+}
+
+/// The kinds of the resource usage counted by `ResourceUsage`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UsageKind {
+    MinorFault,
+    MajorFault,
+    BlockInput,
+    BlockOutput,
+    VoluntarySwitch,
+    InvoluntarySwitch,
+}
+
+impl ResourceUsage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `count` to the counter of `kind`.
+    pub fn add(&self, kind: UsageKind, count: u64) {
+        self.counter(kind).fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Takes a snapshot of the counters, with the CPU times of the `prof_clock`.
+    pub fn snapshot(&self, prof_clock: &ProfClock) -> RUsage {
+        RUsage {
+            utime: prof_clock.user_clock().read_time(),
+            stime: prof_clock.kernel_clock().read_time(),
+            minflt: self.minflt.load(Ordering::Relaxed),
+            majflt: self.majflt.load(Ordering::Relaxed),
+            inblock: self.inblock.load(Ordering::Relaxed),
+            oublock: self.oublock.load(Ordering::Relaxed),
+            nvcsw: self.nvcsw.load(Ordering::Relaxed),
+            nivcsw: self.nivcsw.load(Ordering::Relaxed),
+        }
+    }
+
+    fn counter(&self, kind: UsageKind) -> &AtomicU64 {
+        match kind {
+            UsageKind::MinorFault => &self.minflt,
+            UsageKind::MajorFault => &self.majflt,
+            UsageKind::BlockInput => &self.inblock,
+            UsageKind::BlockOutput => &self.oublock,
+            UsageKind::VoluntarySwitch => &self.nvcsw,
+            UsageKind::InvoluntarySwitch => &self.nivcsw,
+        }
+    }
+}
+
+/// A snapshot of the resource usage, which is reported by `getrusage`, `wait4` and `times`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RUsage {
+    pub utime: Duration,
+    pub stime: Duration,
+    pub minflt: u64,
+    pub majflt: u64,
+    pub inblock: u64,
+    pub oublock: u64,
+    pub nvcsw: u64,
+    pub nivcsw: u64,
+}
+
+impl AddAssign for RUsage {
+    fn add_assign(&mut self, other: Self) {
+        self.utime += other.utime;
+        self.stime += other.stime;
+        self.minflt += other.minflt;
+        self.majflt += other.majflt;
+        self.inblock += other.inblock;
+        self.oublock += other.oublock;
+        self.nvcsw += other.nvcsw;
+        self.nivcsw += other.nivcsw;
+    }
+}
+
+/// Adds `count` to the resource usage of the current thread and its process.
+///
+/// Nothing is accounted if the current thread is not a POSIX thread.
+pub fn account_current(kind: UsageKind, count: u64) {
+    let Some(current_task) = current_task() else {
+        return;
+    };
+    let Some(thread) = current_task
+        .data()
+        .downcast_ref::<Weak<Thread>>()
+        .and_then(Weak::upgrade)
+    else {
+        return;
+    };
+    let Some(posix_thread) = thread.as_posix_thread() else {
+        return;
+    };
+
+    posix_thread.resource_usage().add(kind, count);
+    if let Some(process) = posix_thread.weak_process().upgrade() {
+        process.resource_usage().add(kind, count);
+    }
+}
+
+pub(super) fn init() {
+    register_io_accounting_callback(account_block_io);
+}
+
+/// Accounts the sectors of a block I/O to the thread that submits it, like Linux.
+fn account_block_io(type_: BioType, nr_sectors: usize) {
+    let kind = match type_ {
+        BioType::Read => UsageKind::BlockInput,
+        BioType::Write => UsageKind::BlockOutput,
+        _ => return,
+    };
+    account_current(kind, nr_sectors as u64);
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+    use crate::process::Process;
+
+    #[ktest]
+    fn count_usage() {
+        crate::time::clocks::init_for_ktest();
+        let usage = ResourceUsage::new();
+        usage.add(UsageKind::MinorFault, 2);
+        usage.add(UsageKind::MajorFault, 1);
+        usage.add(UsageKind::BlockInput, 8);
+        usage.add(UsageKind::BlockOutput, 16);
+        usage.add(UsageKind::VoluntarySwitch, 3);
+        usage.add(UsageKind::InvoluntarySwitch, 4);
+        usage.add(UsageKind::MinorFault, 1);
+
+        let rusage = usage.snapshot(&ProfClock::new());
+        assert_eq!(rusage.utime, Duration::ZERO);
+        assert_eq!(rusage.stime, Duration::ZERO);
+        assert_eq!(
+            (rusage.minflt, rusage.majflt, rusage.inblock, rusage.oublock),
+            (3, 1, 8, 16)
+        );
+        assert_eq!((rusage.nvcsw, rusage.nivcsw), (3, 4));
+    }
+
+    #[ktest]
+    fn accumulate_usage() {
+        let mut rusage = RUsage {
+            utime: Duration::from_millis(1500),
+            minflt: 1,
+            nvcsw: 2,
+            ..Default::default()
+        };
+        rusage += RUsage {
+            utime: Duration::from_millis(500),
+            stime: Duration::from_secs(1),
+            minflt: 2,
+            oublock: 8,
+            ..Default::default()
+        };
+        assert_eq!(rusage.utime, Duration::from_secs(2));
+        assert_eq!(rusage.stime, Duration::from_secs(1));
+        assert_eq!((rusage.minflt, rusage.oublock, rusage.nvcsw), (3, 8, 2));
+    }
+
+    #[ktest]
+    fn process_usage() {
+        crate::time::clocks::init_for_ktest();
+        let process = Process::new_for_ktest(None);
+        process.resource_usage().add(UsageKind::MajorFault, 2);
+        *process.children_rusage().lock() += RUsage {
+            majflt: 1,
+            ..Default::default()
+        };
+
+        assert_eq!(process.rusage().majflt, 2);
+        assert_eq!(process.children_rusage().lock().majflt, 1);
+    }
+
+    #[ktest]
+    fn ignore_non_posix_tasks() {
+        // The ktests do not run in POSIX threads, whose usage cannot be accounted.
+        account_current(UsageKind::MinorFault, 1);
+        account_block_io(BioType::Read, 8);
+    }
+}
@@ -29,7 +29,7 @@ pub use sig_stack::{SigStack, SigStackFlags};
 use super::posix_thread::{PosixThread, PosixThreadExt};
 use crate::{
     prelude::*,
-    process::{do_exit_group, TermStatus},
+    process::{core_dump, do_exit_group, TermStatus},
     thread::{status::ThreadStatus, Thread},
     util::{write_bytes_to_user, write_val_to_user},
 };
@@ -57,6 +57,11 @@ pub fn handle_pending_signal(
         }
     };
 
+    // The thread is stopped instead if another thread is dumping the core.
+    if core_dump::stop_for_core_dump(context) {
+        return Ok(());
+    }
+
     let sig_num = signal.num();
     trace!("sig_num = {:?}, sig_name = {}", sig_num, sig_num.sig_name());
     let current = posix_thread.process();
@@ -85,13 +90,30 @@ pub fn handle_pending_signal(
             trace!("sig_default_action: {:?}", sig_default_action);
             match sig_default_action {
                 SigDefaultAction::Core | SigDefaultAction::Term => {
+                    let is_core_dumped = matches!(sig_default_action, SigDefaultAction::Core)
+                        && match core_dump::dump_core(context, &signal.to_info()) {
+                            Ok(is_core_dumped) => is_core_dumped,
+                            Err(err) => {
+                                warn!(
+                                    "{:?}: failed to dump core: {:?}",
+                                    current.executable_path(),
+                                    err
+                                );
+                                false
+                            }
+                        };
                     warn!(
                         "{:?}: terminating on signal {}",
                         current.executable_path(),
                         sig_num.sig_name()
                     );
+                    let term_status = if is_core_dumped {
+                        TermStatus::Dumped(sig_num)
+                    } else {
+                        TermStatus::Killed(sig_num)
+                    };
                     // We should exit current here, since we cannot restore a valid status from trap now.
-                    do_exit_group(TermStatus::Killed(sig_num));
+                    do_exit_group(term_status);
                 }
                 SigDefaultAction::Ign => {}
                 SigDefaultAction::Stop => {
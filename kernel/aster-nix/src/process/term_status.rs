@@ -2,10 +2,15 @@
 
 use super::signal::sig_num::SigNum;
 
+/// The flag in the wait status that tells a core dump is written.
+const WCOREFLAG: u32 = 0x80;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TermStatus {
     Exited(u8),
     Killed(SigNum),
+    /// Killed by a signal, after a core dump is written.
+    Dumped(SigNum),
 }
 
 impl TermStatus {
@@ -14,6 +19,7 @@ impl TermStatus {
         match self {
             TermStatus::Exited(status) => (*status as u32) << 8,
             TermStatus::Killed(signum) => signum.as_u8() as u32,
+            TermStatus::Dumped(signum) => signum.as_u8() as u32 | WCOREFLAG,
         }
     }
 }
@@ -78,6 +78,12 @@ pub fn wait_child_exit(
 fn reap_zombie_child(process: &Process, pid: Pid) -> ExitCode {
     let child_process = process.children().lock().remove(&pid).unwrap();
     assert!(child_process.is_zombie());
+    let child_rusage = {
+        let mut rusage = child_process.rusage();
+        rusage += *child_process.children_rusage().lock();
+        rusage
+    };
+    *process.children_rusage().lock() += child_rusage;
     child_process.root_vmar().destroy_all().unwrap();
     for thread in &*child_process.threads().lock() {
         thread_table::remove_thread(thread.tid());
@@ -0,0 +1,147 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The load averages of the system.
+//!
+//! Following Linux, the load averages are the exponentially damped moving averages
+//! of the number of runnable user threads, which are sampled every 5 seconds and kept
+//! in fixed-point numbers with 11 fractional bits.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use ostd::arch::timer::{self, TIMER_FREQ};
+use spin::Once;
+
+use crate::{
+    prelude::*,
+    process::process_table,
+    thread::work_queue::{submit_work_item, work_item::WorkItem, WorkPriority},
+};
+
+/// The number of fractional bits of the fixed-point load averages.
+const FSHIFT: u32 = 11;
+const FIXED_1: u64 = 1 << FSHIFT;
+/// The decay factors of 1, 5 and 15 minutes for a 5-second interval, i.e.,
+/// `FIXED_1 / exp(5sec / 1min)` and so on.
+const EXP: [u64; 3] = [1884, 2014, 2037];
+/// The interval between two samples in timer ticks.
+const SAMPLE_INTERVAL: u64 = 5 * TIMER_FREQ;
+
+static LOAD_AVERAGES: [AtomicU64; 3] = [AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0)];
+static TICKS: AtomicU64 = AtomicU64::new(0);
+static SAMPLE_WORK: Once<Arc<WorkItem>> = Once::new();
+
+/// The load averages over the last 1, 5 and 15 minutes, in hundredths.
+#[derive(Debug, Clone, Copy)]
+pub struct LoadAverages(pub [u64; 3]);
+
+impl core::fmt::Display for LoadAverages {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let [one, five, fifteen] = self.0;
+        write!(
+            f,
+            "{}.{:02} {}.{:02} {}.{:02}",
+            one / 100,
+            one % 100,
+            five / 100,
+            five % 100,
+            fifteen / 100,
+            fifteen % 100
+        )
+    }
+}
+
+/// Returns the current load averages.
+pub fn load_averages() -> LoadAverages {
+    LoadAverages(core::array::from_fn(|i| {
+        to_hundredths(LOAD_AVERAGES[i].load(Ordering::Relaxed))
+    }))
+}
+
+/// Converts the fixed-point load to hundredths, rounded to the nearest as Linux does.
+fn to_hundredths(load: u64) -> u64 {
+    let load = load + FIXED_1 / 200;
+    (load >> FSHIFT) * 100 + ((load & (FIXED_1 - 1)) * 100 >> FSHIFT)
+}
+
+/// Returns the numbers of the runnable user threads and all the user threads.
+pub fn nr_threads() -> (usize, usize) {
+    let mut nr_running = 0;
+    let mut nr_total = 0;
+    for process in process_table::process_table().iter() {
+        let threads = process.threads().lock();
+        nr_total += threads.len();
+        nr_running += threads.iter().filter(|thread| thread.is_runnable()).count();
+    }
+    (nr_running, nr_total)
+}
+
+fn sample() {
+    let (nr_running, _) = nr_threads();
+    let active = nr_running as u64 * FIXED_1;
+    for (load_avg, exp) in LOAD_AVERAGES.iter().zip(EXP) {
+        let load = load_avg.load(Ordering::Relaxed);
+        load_avg.store(calc_load(load, exp, active), Ordering::Relaxed);
+    }
+}
+
+/// Moves the fixed-point load towards the fixed-point number of the active threads.
+fn calc_load(load: u64, exp: u64, active: u64) -> u64 {
+    let mut new_load = load * exp + active * (FIXED_1 - exp);
+    // Round up when the load is increasing so that it can reach the number of
+    // runnable threads eventually.
+    if active >= load {
+        new_load += FIXED_1 - 1;
+    }
+    new_load >> FSHIFT
+}
+
+fn on_timer_tick() {
+    if TICKS.fetch_add(1, Ordering::Relaxed) % SAMPLE_INTERVAL != SAMPLE_INTERVAL - 1 {
+        return;
+    }
+    // The process table cannot be locked in the interrupt context, so the sampling
+    // is deferred to the work queue.
+    if let Some(work_item) = SAMPLE_WORK.get() {
+        submit_work_item(work_item.clone(), WorkPriority::High);
+    }
+}
+
+/// Starts to sample the load averages.
+///
+/// This function must be called after the work queues are initialized.
+pub fn init() {
+    SAMPLE_WORK.call_once(|| Arc::new(WorkItem::new(Box::new(sample))));
+    timer::register_callback(on_timer_tick);
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn load_rises_and_decays() {
+        // One runnable thread for one minute, i.e., 12 samples.
+        let mut load = 0;
+        for _ in 0..12 {
+            load = calc_load(load, EXP[0], FIXED_1);
+        }
+        assert_eq!(to_hundredths(load), 63);
+        for _ in 12..180 {
+            load = calc_load(load, EXP[0], FIXED_1);
+        }
+        assert_eq!(load, FIXED_1);
+
+        for _ in 0..180 {
+            load = calc_load(load, EXP[0], 0);
+        }
+        assert_eq!(load, 0);
+    }
+
+    #[ktest]
+    fn format_load_averages() {
+        assert_eq!(to_hundredths(FIXED_1 * 3 / 2), 150);
+        assert_eq!(LoadAverages([5, 105, 1500]).to_string(), "0.05 1.05 15.00");
+    }
+}
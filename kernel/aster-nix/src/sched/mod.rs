@@ -1,5 +1,6 @@
 // SPDX-License-Identifier: MPL-2.0
 
+pub mod loadavg;
 pub mod nice;
 mod priority_scheduler;
 
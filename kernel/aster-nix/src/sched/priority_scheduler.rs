@@ -1,9 +1,15 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use core::time::Duration;
+
 use intrusive_collections::LinkedList;
-use ostd::task::{set_scheduler, Scheduler, Task, TaskAdapter};
+use ostd::task::{current_task, set_scheduler, Scheduler, Task, TaskAdapter, TaskStatus};
 
-use crate::prelude::*;
+use crate::{
+    cgroup::{self, Cgroup, CpuController},
+    prelude::*,
+    process::{account_current, UsageKind},
+};
 
 pub fn init() {
     let preempt_scheduler = Box::new(PreemptScheduler::new());
@@ -15,20 +21,47 @@ pub fn init() {
 ///
 /// Real-time tasks are placed in the `real_time_tasks` queue and
 /// are always prioritized during scheduling.
-/// Normal tasks are placed in the `normal_tasks` queue and are only
+/// Normal tasks are placed in the `normal_tasks` queues and are only
 /// scheduled for execution when there are no real-time tasks.
+///
+/// The normal tasks are queued by their cgroups, and the cgroups share the CPU time
+/// by `cpu.weight`: the queue whose cgroup has used the least CPU time relative to its
+/// weight is picked first. A cgroup that has used up its `cpu.max` quota is not picked
+/// until the next period. Since there is no idle task, a throttled task keeps running
+/// if no other task is runnable, and the quota is only enforced when it is switched out.
 struct PreemptScheduler {
     /// Tasks with a priority of less than 100 are regarded as real-time tasks.
     real_time_tasks: SpinLock<LinkedList<TaskAdapter>>,
     /// Tasks with a priority greater than or equal to 100 are regarded as normal tasks.
-    normal_tasks: SpinLock<LinkedList<TaskAdapter>>,
+    normal_tasks: SpinLock<NormalQueues>,
+}
+
+/// The queues of the normal tasks, one per cgroup.
+struct NormalQueues {
+    /// The queues, indexed by the IDs of the cgroups.
+    groups: BTreeMap<u64, GroupQueue>,
+    /// The smallest virtual runtime of the non-empty queues, which never decreases.
+    min_vruntime: u64,
+    /// The cgroup of the task that was dispatched last, and when its CPU time was charged.
+    running: Option<(Arc<Cgroup>, Duration)>,
+}
+
+struct GroupQueue {
+    cgroup: Arc<Cgroup>,
+    tasks: LinkedList<TaskAdapter>,
+    /// The CPU time used by the cgroup in nanoseconds, scaled by its weight.
+    vruntime: u64,
 }
 
+/// The minimal virtual runtime by which another cgroup must lag behind to preempt
+/// the current task, in nanoseconds.
+const PREEMPT_GRANULARITY_NS: u64 = 4_000_000;
+
 impl PreemptScheduler {
     pub fn new() -> Self {
         Self {
             real_time_tasks: SpinLock::new(LinkedList::new(TaskAdapter::new())),
-            normal_tasks: SpinLock::new(LinkedList::new(TaskAdapter::new())),
+            normal_tasks: SpinLock::new(NormalQueues::new()),
         }
     }
 }
@@ -38,19 +71,141 @@ impl Scheduler for PreemptScheduler {
         if task.is_real_time() {
             self.real_time_tasks.lock_irq_disabled().push_back(task);
         } else {
-            self.normal_tasks.lock_irq_disabled().push_back(task);
+            let cgroup = cgroup::cgroup_of_task(&task);
+            self.normal_tasks
+                .lock_irq_disabled()
+                .push_back(cgroup, task);
         }
     }
 
     fn dequeue(&self) -> Option<Arc<Task>> {
-        if !self.real_time_tasks.lock_irq_disabled().is_empty() {
-            self.real_time_tasks.lock_irq_disabled().pop_front()
-        } else {
-            self.normal_tasks.lock_irq_disabled().pop_front()
+        let now = aster_time::read_monotonic_time();
+        let mut normal_tasks = self.normal_tasks.lock_irq_disabled();
+        normal_tasks.charge_running(now);
+
+        let next_task = {
+            let mut real_time_tasks = self.real_time_tasks.lock_irq_disabled();
+            if !real_time_tasks.is_empty() {
+                real_time_tasks.pop_front()
+            } else {
+                drop(real_time_tasks);
+                normal_tasks.pop_front(now)
+            }
+        };
+        if let Some(task) = next_task.as_ref() {
+            normal_tasks.running = Some((cgroup::cgroup_of_task(task), now));
         }
+        drop(normal_tasks);
+
+        if next_task.is_some() {
+            account_context_switch();
+        }
+        next_task
     }
 
     fn should_preempt(&self, task: &Arc<Task>) -> bool {
-        !task.is_real_time() && !self.real_time_tasks.lock_irq_disabled().is_empty()
+        if task.is_real_time() {
+            return false;
+        }
+        if !self.real_time_tasks.lock_irq_disabled().is_empty() {
+            return true;
+        }
+
+        let now = aster_time::read_monotonic_time();
+        let mut normal_tasks = self.normal_tasks.lock_irq_disabled();
+        normal_tasks.charge_running(now);
+        normal_tasks.should_preempt(&cgroup::cgroup_of_task(task), now)
+    }
+}
+
+/// Accounts the context switch of the current task, which is about to be switched out.
+///
+/// Like Linux, the switch is voluntary if the task blocks, or involuntary if the task
+/// is still runnable, i.e., it is preempted or yields.
+fn account_context_switch() {
+    let Some(task) = current_task() else {
+        return;
+    };
+    let kind = match task.status() {
+        TaskStatus::Runnable => UsageKind::InvoluntarySwitch,
+        TaskStatus::Sleepy | TaskStatus::Sleeping => UsageKind::VoluntarySwitch,
+        TaskStatus::Exited => return,
+    };
+    account_current(kind, 1);
+}
+
+impl NormalQueues {
+    fn new() -> Self {
+        Self {
+            groups: BTreeMap::new(),
+            min_vruntime: 0,
+            running: None,
+        }
+    }
+
+    fn push_back(&mut self, cgroup: Arc<Cgroup>, task: Arc<Task>) {
+        let min_vruntime = self.min_vruntime;
+        let group = self.group_mut(cgroup);
+        // A cgroup that has been idle does not accumulate the CPU time to catch up with.
+        if group.tasks.is_empty() {
+            group.vruntime = group.vruntime.max(min_vruntime);
+        }
+        group.tasks.push_back(task);
+    }
+
+    /// Pops a task of the non-throttled cgroup that has the smallest virtual runtime.
+    fn pop_front(&mut self, now: Duration) -> Option<Arc<Task>> {
+        // Remove the empty queues of the removed cgroups.
+        self.groups
+            .retain(|_, group| !group.tasks.is_empty() || !group.cgroup.is_removed());
+
+        let group = self
+            .groups
+            .values_mut()
+            .filter(|group| !group.tasks.is_empty() && !group.cgroup.is_cpu_throttled(now))
+            .min_by_key(|group| group.vruntime)?;
+        self.min_vruntime = self.min_vruntime.max(group.vruntime);
+        group.tasks.pop_front()
+    }
+
+    fn should_preempt(&self, cgroup: &Arc<Cgroup>, now: Duration) -> bool {
+        let is_throttled = cgroup.is_cpu_throttled(now);
+        let vruntime = self
+            .groups
+            .get(&cgroup.id())
+            .map(|group| group.vruntime)
+            .unwrap_or(self.min_vruntime);
+
+        self.groups.values().any(|group| {
+            !group.tasks.is_empty()
+                && !group.cgroup.is_cpu_throttled(now)
+                && (is_throttled || group.vruntime + PREEMPT_GRANULARITY_NS < vruntime)
+        })
+    }
+
+    /// Charges the CPU time since the last charge to the cgroup of the running task.
+    fn charge_running(&mut self, now: Duration) {
+        let Some((cgroup, last_charged)) = self.running.take() else {
+            return;
+        };
+        let runtime = now.saturating_sub(last_charged);
+        cgroup.charge_cpu_time(now, runtime);
+
+        let delta = runtime.as_nanos() as u64 * CpuController::DEFAULT_WEIGHT as u64
+            / cgroup.cpu_weight() as u64;
+        let group = self.group_mut(cgroup.clone());
+        group.vruntime += delta;
+        self.running = Some((cgroup, now));
+    }
+
+    fn group_mut(&mut self, cgroup: Arc<Cgroup>) -> &mut GroupQueue {
+        let min_vruntime = self.min_vruntime;
+        self.groups
+            .entry(cgroup.id())
+            .or_insert_with(|| GroupQueue {
+                cgroup,
+                tasks: LinkedList::new(TaskAdapter::new()),
+                vruntime: min_vruntime,
+            })
     }
 }
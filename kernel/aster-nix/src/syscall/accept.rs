@@ -66,7 +66,7 @@ fn do_accept(
     let fd = {
         let current = current!();
         let mut file_table = current.file_table().lock();
-        file_table.insert(connected_socket, fd_flags)
+        file_table.insert(connected_socket, fd_flags)?
     };
 
     Ok(fd)
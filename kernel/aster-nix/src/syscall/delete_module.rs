@@ -0,0 +1,16 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::{init_module::check_module_capability, SyscallReturn};
+use crate::{module, prelude::*, util::read_cstring_from_user};
+
+/// The maximum length of the name of a module, i.e., `MODULE_NAME_LEN` of Linux.
+const MODULE_NAME_LEN: usize = 56;
+
+pub fn sys_delete_module(name_addr: Vaddr, flags: u32) -> Result<SyscallReturn> {
+    let name = read_cstring_from_user(name_addr, MODULE_NAME_LEN)?;
+    debug!("name = {:?}, flags = 0x{:x}", name, flags);
+
+    check_module_capability()?;
+    module::delete_module(&name.to_string_lossy())?;
+    Ok(SyscallReturn::Return(0))
+}
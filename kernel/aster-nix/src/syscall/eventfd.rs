@@ -33,7 +33,7 @@ use crate::{
 pub fn sys_eventfd(init_val: u64) -> Result<SyscallReturn> {
     debug!("init_val = 0x{:x}", init_val);
 
-    let fd = do_sys_eventfd2(init_val, Flags::empty());
+    let fd = do_sys_eventfd2(init_val, Flags::empty())?;
 
     Ok(SyscallReturn::Return(fd as _))
 }
@@ -44,12 +44,12 @@ pub fn sys_eventfd2(init_val: u64, flags: u32) -> Result<SyscallReturn> {
         .ok_or_else(|| Error::with_message(Errno::EINVAL, "unknown flags"))?;
     debug!("init_val = 0x{:x}, flags = {:?}", init_val, flags);
 
-    let fd = do_sys_eventfd2(init_val, flags);
+    let fd = do_sys_eventfd2(init_val, flags)?;
 
     Ok(SyscallReturn::Return(fd as _))
 }
 
-fn do_sys_eventfd2(init_val: u64, flags: Flags) -> FileDesc {
+fn do_sys_eventfd2(init_val: u64, flags: Flags) -> Result<FileDesc> {
     let event_file = EventFile::new(init_val, flags);
     let fd = {
         let current = current!();
@@ -59,9 +59,9 @@ fn do_sys_eventfd2(init_val: u64, flags: Flags) -> FileDesc {
         } else {
             FdFlags::empty()
         };
-        file_table.insert(Arc::new(event_file), fd_flags)
+        file_table.insert(Arc::new(event_file), fd_flags)?
     };
-    fd
+    Ok(fd)
 }
 
 bitflags! {
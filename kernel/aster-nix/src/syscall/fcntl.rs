@@ -7,24 +7,15 @@ use crate::{
         utils::StatusFlags,
     },
     prelude::*,
+    process::ResourceType,
 };
 
 pub fn sys_fcntl(fd: FileDesc, cmd: i32, arg: u64) -> Result<SyscallReturn> {
     let fcntl_cmd = FcntlCmd::try_from(cmd)?;
     debug!("fd = {}, cmd = {:?}, arg = {}", fd, fcntl_cmd, arg);
     match fcntl_cmd {
-        FcntlCmd::F_DUPFD => {
-            let current = current!();
-            let mut file_table = current.file_table().lock();
-            let new_fd = file_table.dup(fd, arg as FileDesc, FdFlags::empty())?;
-            Ok(SyscallReturn::Return(new_fd as _))
-        }
-        FcntlCmd::F_DUPFD_CLOEXEC => {
-            let current = current!();
-            let mut file_table = current.file_table().lock();
-            let new_fd = file_table.dup(fd, arg as FileDesc, FdFlags::CLOEXEC)?;
-            Ok(SyscallReturn::Return(new_fd as _))
-        }
+        FcntlCmd::F_DUPFD => dup_fd(fd, arg, FdFlags::empty()),
+        FcntlCmd::F_DUPFD_CLOEXEC => dup_fd(fd, arg, FdFlags::CLOEXEC),
         FcntlCmd::F_GETFD => {
             let current = current!();
             let file_table = current.file_table().lock();
@@ -83,6 +74,26 @@ pub fn sys_fcntl(fd: FileDesc, cmd: i32, arg: u64) -> Result<SyscallReturn> {
     }
 }
 
+/// Duplicates `fd` to the lowest free file descriptor that is not less than `min_fd`.
+fn dup_fd(fd: FileDesc, min_fd: u64, flags: FdFlags) -> Result<SyscallReturn> {
+    let current = current!();
+    // Unlike running out of file descriptors, a lower bound beyond the limit is
+    // an invalid argument.
+    if min_fd
+        >= current
+            .resource_limits()
+            .lock()
+            .get_rlimit(ResourceType::RLIMIT_NOFILE)
+            .get_cur()
+    {
+        return_errno_with_message!(Errno::EINVAL, "the fd is beyond the limit");
+    }
+
+    let mut file_table = current.file_table().lock();
+    let new_fd = file_table.dup(fd, min_fd as FileDesc, flags)?;
+    Ok(SyscallReturn::Return(new_fd as _))
+}
+
 #[repr(i32)]
 #[derive(Debug, Clone, Copy, TryFromInt)]
 #[allow(non_camel_case_types)]
@@ -6,7 +6,10 @@ use int_to_c_enum::TryFromInt;
 
 use super::SyscallReturn;
 use crate::{
-    prelude::*, process::posix_thread::PosixThreadExt, time::timeval_t, util::write_val_to_user,
+    prelude::*,
+    process::{posix_thread::PosixThreadExt, RUsage},
+    time::timeval_t,
+    util::write_val_to_user,
 };
 
 #[derive(Debug, Copy, Clone, TryFromInt, PartialEq)]
@@ -28,32 +31,24 @@ pub fn sys_getrusage(target: i32, rusage_addr: Vaddr) -> Result<SyscallReturn> {
 
     if rusage_addr != 0 {
         let rusage = match rusage_target {
-            RusageTarget::ForSelf => {
-                let process = current!();
-                rusage_t {
-                    ru_utime: process.prof_clock().user_clock().read_time().into(),
-                    ru_stime: process.prof_clock().kernel_clock().read_time().into(),
-                    ..Default::default()
-                }
-            }
+            RusageTarget::ForSelf => current!().rusage(),
             RusageTarget::Thread => {
                 let thread = current_thread!();
                 let posix_thread = thread.as_posix_thread().unwrap();
-                rusage_t {
-                    ru_utime: posix_thread.prof_clock().user_clock().read_time().into(),
-                    ru_stime: posix_thread.prof_clock().kernel_clock().read_time().into(),
-                    ..Default::default()
-                }
+                posix_thread
+                    .resource_usage()
+                    .snapshot(posix_thread.prof_clock())
             }
-            // To support `Children` and `Both` we need to implement the functionality to
-            // accumulate the resources of a child process back to the parent process
-            // upon the child's termination.
-            _ => {
-                return_errno_with_message!(Errno::EINVAL, "the target type is not supported")
+            RusageTarget::Children => *current!().children_rusage().lock(),
+            RusageTarget::Both => {
+                let current = current!();
+                let mut rusage = current.rusage();
+                rusage += *current.children_rusage().lock();
+                rusage
             }
         };
 
-        write_val_to_user(rusage_addr, &rusage)?;
+        write_val_to_user(rusage_addr, &rusage_t::from(rusage))?;
     }
 
     Ok(SyscallReturn::Return(0))
@@ -95,3 +90,20 @@ pub struct rusage_t {
     /// involuntary
     pub ru_nivcsw: u64,
 }
+
+impl From<RUsage> for rusage_t {
+    fn from(rusage: RUsage) -> Self {
+        // Like Linux, the block I/O is counted in sectors.
+        Self {
+            ru_utime: rusage.utime.into(),
+            ru_stime: rusage.stime.into(),
+            ru_minflt: rusage.minflt,
+            ru_majflt: rusage.majflt,
+            ru_inblock: rusage.inblock,
+            ru_oublock: rusage.oublock,
+            ru_nvcsw: rusage.nvcsw,
+            ru_nivcsw: rusage.nivcsw,
+            ..Default::default()
+        }
+    }
+}
@@ -0,0 +1,106 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::SyscallReturn;
+use crate::{
+    fs::{file_table::FileDesc, inode_handle::InodeHandle},
+    module::load_module,
+    prelude::*,
+    process::{credentials, credentials::capabilities::CapSet},
+    util::{read_bytes_from_user, read_cstring_from_user},
+};
+
+/// The maximum length of the parameters of a module.
+const MAX_PARAMS_LEN: usize = 4096;
+/// The maximum size of the image of a module.
+const MAX_IMAGE_SIZE: usize = 64 * 1024 * 1024;
+
+pub fn sys_init_module(image_addr: Vaddr, len: usize, params_addr: Vaddr) -> Result<SyscallReturn> {
+    debug!(
+        "image_addr = 0x{:x}, len = {}, params_addr = 0x{:x}",
+        image_addr, len, params_addr
+    );
+
+    check_module_capability()?;
+    check_params(params_addr)?;
+
+    let mut image = alloc_image(len)?;
+    image.resize(len, 0);
+    read_bytes_from_user(image_addr, &mut VmWriter::from(image.as_mut_slice()))?;
+    load_module(&image)?;
+    Ok(SyscallReturn::Return(0))
+}
+
+pub fn sys_finit_module(fd: FileDesc, params_addr: Vaddr, flags: u32) -> Result<SyscallReturn> {
+    let flags = ModuleInitFlags::from_bits(flags)
+        .ok_or(Error::with_message(Errno::EINVAL, "invalid flags"))?;
+    debug!(
+        "fd = {}, params_addr = 0x{:x}, flags = {:?}",
+        fd, params_addr, flags
+    );
+
+    check_module_capability()?;
+    check_params(params_addr)?;
+    if flags.contains(ModuleInitFlags::COMPRESSED_FILE) {
+        return_errno_with_message!(Errno::EOPNOTSUPP, "compressed modules are not supported");
+    }
+
+    let image = {
+        let current = current!();
+        let file_table = current.file_table().lock();
+        let file = file_table.get_file(fd)?;
+        let inode_handle = file
+            .downcast_ref::<InodeHandle>()
+            .ok_or(Error::with_message(Errno::EBADF, "not inode"))?;
+        let mut image = alloc_image(file.metadata().size)?;
+        inode_handle.read_to_end(&mut image)?;
+        // The file may grow after its size is checked.
+        if image.len() > MAX_IMAGE_SIZE {
+            return_errno_with_message!(Errno::EFBIG, "the module is too large");
+        }
+        image
+    };
+    load_module(&image)?;
+    Ok(SyscallReturn::Return(0))
+}
+
+bitflags! {
+    struct ModuleInitFlags: u32 {
+        /// Ignore the symbol version hashes, which modules do not have.
+        const IGNORE_MODVERSIONS = 1;
+        /// Ignore the kernel version magic, which modules do not have.
+        const IGNORE_VERMAGIC = 2;
+        const COMPRESSED_FILE = 4;
+    }
+}
+
+/// Allocates the buffer for the image of a module of `size` bytes, which is empty.
+fn alloc_image(size: usize) -> Result<Vec<u8>> {
+    if size > MAX_IMAGE_SIZE {
+        return_errno_with_message!(Errno::EFBIG, "the module is too large");
+    }
+    let mut image = Vec::new();
+    image
+        .try_reserve_exact(size)
+        .map_err(|_| Error::with_message(Errno::ENOMEM, "no memory for the module"))?;
+    Ok(image)
+}
+
+pub(super) fn check_module_capability() -> Result<()> {
+    if !credentials()
+        .effective_capset()
+        .contains(CapSet::SYS_MODULE)
+    {
+        return_errno_with_message!(Errno::EPERM, "CAP_SYS_MODULE is required");
+    }
+    Ok(())
+}
+
+/// Checks the parameters of a module, which are not supported since components
+/// take no parameters.
+fn check_params(params_addr: Vaddr) -> Result<()> {
+    let params = read_cstring_from_user(params_addr, MAX_PARAMS_LEN)?;
+    if !params.is_empty() {
+        return_errno_with_message!(Errno::EINVAL, "module parameters are not supported");
+    }
+    Ok(())
+}
@@ -4,6 +4,7 @@ use super::SyscallReturn;
 use crate::{
     device::get_device,
     fs::{
+        device::DeviceType,
         file_table::FileDesc,
         fs_resolver::{FsPath, AT_FDCWD},
         utils::{InodeMode, InodeType},
@@ -48,7 +49,12 @@ pub fn sys_mknodat(
             let _ = dir_dentry.new_fs_child(&name, InodeType::File, inode_mode)?;
         }
         FileType::CharacterDevice | FileType::BlockDevice => {
-            let device_inode = get_device(dev)?;
+            let device_type = if file_type == FileType::CharacterDevice {
+                DeviceType::CharDevice
+            } else {
+                DeviceType::BlockDevice
+            };
+            let device_inode = get_device(device_type, dev)?;
             let _ = dir_dentry.mknod(&name, inode_mode, device_inode)?;
         }
         FileType::Fifo | FileType::Socket => {
@@ -9,7 +9,7 @@ use aster_rights::Rights;
 
 use super::SyscallReturn;
 use crate::{
-    fs::file_table::FileDesc,
+    fs::{file_table::FileDesc, path::Dentry},
     prelude::*,
     vm::{
         perms::VmPerms,
@@ -59,16 +59,25 @@ fn do_sys_mmap(
         return_errno_with_message!(Errno::EINVAL, "mmap only support page-aligned offset");
     }
 
-    let vmo = if option.flags.contains(MMapFlags::MAP_ANONYMOUS) {
+    let (vmo, file) = if option.flags.contains(MMapFlags::MAP_ANONYMOUS) {
         if offset != 0 {
             return_errno_with_message!(Errno::EINVAL, "offset must be zero for anonymous mapping");
         }
-        alloc_anonyous_vmo(len)?
+        (alloc_anonyous_vmo(len)?, None)
     } else {
-        alloc_filebacked_vmo(fd, len, offset, &option)?
+        let (vmo, dentry) = alloc_filebacked_vmo(fd, len, offset, vm_perms, &option)?;
+        (vmo, Some(dentry))
     };
 
     let current = current!();
+    {
+        let is_data = option.typ() == MMapType::Private && vm_perms.contains(VmPerms::WRITE);
+        let resource_limits = current.resource_limits().lock();
+        current
+            .vm()
+            .check_map_limits(&resource_limits, len, is_data)?;
+    }
+
     let root_vmar = current.root_vmar();
     let vm_map_options = {
         let mut options = root_vmar.new_map(vmo.to_dyn(), vm_perms)?;
@@ -83,6 +92,9 @@ fn do_sys_mmap(
         if option.typ() == MMapType::Shared {
             options = options.is_shared(true);
         }
+        if let Some(dentry) = file {
+            options = options.file(dentry, offset);
+        }
 
         options
     };
@@ -101,30 +113,36 @@ fn alloc_filebacked_vmo(
     fd: FileDesc,
     len: usize,
     offset: usize,
+    vm_perms: VmPerms,
     option: &MMapOptions,
-) -> Result<Vmo> {
+) -> Result<(Vmo, Arc<Dentry>)> {
     let current = current!();
-    let page_cache_vmo = {
+    let (page_cache_vmo, dentry) = {
         let fs_resolver = current.fs().read();
         let dentry = fs_resolver.lookup_from_fd(fd)?;
         let inode = dentry.inode();
-        inode
+        if option.typ() == MMapType::Shared && vm_perms.contains(VmPerms::WRITE) {
+            inode.prepare_write()?;
+        }
+        let page_cache_vmo = inode
             .page_cache()
             .ok_or(Error::with_message(
                 Errno::EBADF,
                 "File does not have page cache",
             ))?
-            .to_dyn()
+            .to_dyn();
+        (page_cache_vmo, dentry)
     };
 
-    if option.typ() == MMapType::Private {
+    let vmo = if option.typ() == MMapType::Private {
         // map private
-        VmoChildOptions::new_cow(page_cache_vmo, offset..(offset + len)).alloc()
+        VmoChildOptions::new_cow(page_cache_vmo, offset..(offset + len)).alloc()?
     } else {
         // map shared
         // FIXME: map shared vmo can exceed parent range, but slice child cannot
-        VmoChildOptions::new_slice_rights(page_cache_vmo, offset..(offset + len)).alloc()
-    }
+        VmoChildOptions::new_slice_rights(page_cache_vmo, offset..(offset + len)).alloc()?
+    };
+    Ok((vmo, dentry))
 }
 
 fn check_option(option: &MMapOptions) -> Result<()> {
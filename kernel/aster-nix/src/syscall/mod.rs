@@ -25,6 +25,7 @@ mod clone;
 mod close;
 mod connect;
 mod constants;
+mod delete_module;
 mod dup;
 mod epoll;
 mod eventfd;
@@ -55,6 +56,7 @@ mod getsockopt;
 mod gettid;
 mod gettimeofday;
 mod getuid;
+mod init_module;
 mod ioctl;
 mod kill;
 mod link;
@@ -71,6 +73,7 @@ mod nanosleep;
 mod open;
 mod pause;
 mod pipe;
+mod pivot_root;
 mod poll;
 mod prctl;
 mod pread64;
@@ -124,6 +127,7 @@ mod tgkill;
 mod time;
 mod timer_create;
 mod timer_settime;
+mod times;
 mod truncate;
 mod umask;
 mod umount;
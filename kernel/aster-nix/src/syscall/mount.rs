@@ -1,13 +1,22 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use aster_block::BlockDevice;
+
 use super::SyscallReturn;
 use crate::{
+    device::BlockDeviceNode,
     fs::{
-        exfat::{ExfatFS, ExfatMountOptions},
-        ext2::Ext2,
+        cgroupfs, devtmpfs,
         fs_resolver::{FsPath, AT_FDCWD},
+        fuse::{FuseDevFile, FuseFS, FuseMountOptions},
+        inode_handle::InodeHandle,
+        open_block_fs,
+        overlayfs::{OverlayFS, OverlayMountOptions},
+        p9fs::{P9MountOptions, P9FS},
         path::Dentry,
-        utils::{FileSystem, InodeType},
+        ramfs::{RamFS, RamFsMountOptions},
+        sysfs::SysFS,
+        utils::{FileSystem, Inode, InodeType},
     },
     prelude::*,
     syscall::constants::MAX_FILENAME_LEN,
@@ -16,8 +25,7 @@ use crate::{
 
 /// The `data` argument is interpreted by the different filesystems.
 /// Typically it is a string of comma-separated options understood by
-/// this filesystem. The current implementation only considers the case
-/// where it is `NULL`. Because it should be interpreted by the specific filesystems.
+/// this filesystem. It is read for new mounts and remounts.
 pub fn sys_mount(
     devname_addr: Vaddr,
     dirname_addr: Vaddr,
@@ -43,10 +51,8 @@ pub fn sys_mount(
         current.fs().read().lookup(&fs_path)?
     };
 
-    if mount_flags.contains(MountFlags::MS_REMOUNT) && mount_flags.contains(MountFlags::MS_BIND) {
-        do_reconfigure_mnt()?;
-    } else if mount_flags.contains(MountFlags::MS_REMOUNT) {
-        do_remount()?;
+    if mount_flags.contains(MountFlags::MS_REMOUNT) {
+        do_remount(dst_dentry, mount_flags, data)?;
     } else if mount_flags.contains(MountFlags::MS_BIND) {
         do_bind_mount(
             devname,
@@ -62,18 +68,37 @@ pub fn sys_mount(
     } else if mount_flags.contains(MountFlags::MS_MOVE) {
         do_move_mount_old(devname, dst_dentry)?;
     } else {
-        do_new_mount(devname, fstype_addr, dst_dentry)?;
+        do_new_mount(devname, fstype_addr, data, dst_dentry, mount_flags)?;
     }
 
     Ok(SyscallReturn::Return(0))
 }
 
-fn do_reconfigure_mnt() -> Result<()> {
-    return_errno_with_message!(Errno::EINVAL, "do_reconfigure_mnt is not supported");
-}
-
-fn do_remount() -> Result<()> {
-    return_errno_with_message!(Errno::EINVAL, "do_remount is not supported");
+/// Changes the flags of a mount.
+///
+/// Only `MS_RDONLY` is supported. Since it is a flag of the mount, remounting the
+/// filesystem and reconfiguring the mount with `MS_BIND` are the same.
+///
+/// The `data` of a tmpfs changes its limits. It is ignored by the other filesystems.
+fn do_remount(dst_dentry: Arc<Dentry>, mount_flags: MountFlags, data: Vaddr) -> Result<()> {
+    if !dst_dentry.is_root_of_mount() {
+        return_errno_with_message!(Errno::EINVAL, "dirname is not a mountpoint");
+    }
+    let fs = dst_dentry.mount_node().fs();
+    if let Some(tmpfs) = fs
+        .downcast_ref::<RamFS>()
+        .filter(|ramfs| ramfs.name() == "tmpfs")
+    {
+        let data = read_mount_data(data)?;
+        let data = data
+            .to_str()
+            .map_err(|_| Error::with_message(Errno::EINVAL, "invalid mount data"))?;
+        tmpfs.remount(data)?;
+    }
+    dst_dentry
+        .mount_node()
+        .set_read_only(mount_flags.contains(MountFlags::MS_RDONLY));
+    Ok(())
 }
 
 /// Bind a mount to a dst location.
@@ -128,7 +153,13 @@ fn do_move_mount_old(src_name: CString, dst_dentry: Arc<Dentry>) -> Result<()> {
 }
 
 /// Mount a new filesystem.
-fn do_new_mount(devname: CString, fs_type: Vaddr, target_dentry: Arc<Dentry>) -> Result<()> {
+fn do_new_mount(
+    devname: CString,
+    fs_type: Vaddr,
+    data: Vaddr,
+    target_dentry: Arc<Dentry>,
+    mount_flags: MountFlags,
+) -> Result<()> {
     if target_dentry.type_() != InodeType::Dir {
         return_errno_with_message!(Errno::ENOTDIR, "mountpoint must be directory");
     };
@@ -137,30 +168,129 @@ fn do_new_mount(devname: CString, fs_type: Vaddr, target_dentry: Arc<Dentry>) ->
     if fs_type.is_empty() {
         return_errno_with_message!(Errno::EINVAL, "fs_type is empty");
     }
-    let fs = get_fs(fs_type, devname)?;
-    target_dentry.mount(fs)?;
+    let data = read_mount_data(data)?;
+    let fs = get_fs(fs_type, devname, data)?;
+    let mount_node = target_dentry.mount(fs)?;
+    mount_node.set_read_only(mount_flags.contains(MountFlags::MS_RDONLY));
     Ok(())
 }
 
-/// Get the filesystem by fs_type and devname.
-fn get_fs(fs_type: CString, devname: CString) -> Result<Arc<dyn FileSystem>> {
-    let devname = devname.to_str().unwrap();
-    let device = match aster_block::get_device(devname) {
-        Some(device) => device,
-        None => return_errno_with_message!(Errno::ENOENT, "Device does not exist"),
-    };
+/// Reads the filesystem-specific data, which is empty if `data` is null.
+fn read_mount_data(data: Vaddr) -> Result<CString> {
+    if data == 0 {
+        return Ok(CString::default());
+    }
+    read_cstring_from_user(data, PAGE_SIZE)
+}
+
+/// Get the filesystem by fs_type, devname and the filesystem-specific data.
+fn get_fs(fs_type: CString, devname: CString, data: CString) -> Result<Arc<dyn FileSystem>> {
     let fs_type = fs_type.to_str().unwrap();
+    let data = data
+        .to_str()
+        .map_err(|_| Error::with_message(Errno::EINVAL, "invalid mount data"))?;
+    // Memory-backed filesystems do not need a device.
     match fs_type {
-        "ext2" => {
-            let ext2_fs = Ext2::open(device)?;
-            Ok(ext2_fs)
+        "tmpfs" => {
+            let options = RamFsMountOptions::parse(data)?;
+            return Ok(RamFS::new_with_options(options));
+        }
+        "ramfs" => return Ok(RamFS::new()),
+        "sysfs" => return Ok(SysFS::new()),
+        "cgroup2" => return Ok(cgroupfs::cgroupfs()),
+        "devtmpfs" => return Ok(devtmpfs::devtmpfs()),
+        "overlay" => {
+            let options = OverlayMountOptions::parse(data)?;
+            return get_overlay_fs(options);
+        }
+        "fuse" => {
+            let options = FuseMountOptions::parse(data)?;
+            return get_fuse_fs(options);
         }
-        "exfat" => {
-            let exfat_fs = ExfatFS::open(device, ExfatMountOptions::default())?;
-            Ok(exfat_fs)
+        "9p" => {
+            let options = P9MountOptions::parse(data)?;
+            let Ok(tag) = devname.to_str() else {
+                return_errno_with_message!(Errno::EINVAL, "invalid 9P mount tag");
+            };
+            let Some(device) = aster_virtio::device::p9::get_device(tag) else {
+                return_errno_with_message!(Errno::ENOENT, "no 9P device with the mount tag");
+            };
+            return Ok(P9FS::new(device, options)?);
         }
-        _ => return_errno_with_message!(Errno::EINVAL, "Invalid fs type"),
+        _ => {}
     }
+
+    let devname = devname.to_str().unwrap();
+    let device = match aster_block::get_device(devname) {
+        Some(device) => device,
+        None => lookup_block_device(devname)?,
+    };
+    open_block_fs(fs_type, device, data)
+}
+
+/// Get an overlay filesystem whose layers are the directories in `options`.
+fn get_overlay_fs(options: OverlayMountOptions) -> Result<Arc<dyn FileSystem>> {
+    let current = current!();
+    let lookup_dir = |path: &str| -> Result<Arc<dyn Inode>> {
+        let fs_path = FsPath::new(AT_FDCWD, path)?;
+        let dentry = current.fs().read().lookup(&fs_path)?;
+        if dentry.type_() != InodeType::Dir {
+            return_errno_with_message!(Errno::ENOTDIR, "overlay layer must be directory");
+        }
+        Ok(dentry.inode().clone())
+    };
+
+    let lowers = options
+        .lowerdirs
+        .iter()
+        .map(|lowerdir| lookup_dir(lowerdir))
+        .collect::<Result<Vec<_>>>()?;
+    let upper = options.upperdir.as_deref().map(lookup_dir).transpose()?;
+    let work = options.workdir.as_deref().map(lookup_dir).transpose()?;
+    let overlay_fs = OverlayFS::new(upper, work, lowers)?;
+    Ok(overlay_fs)
+}
+
+/// Gets the block device of the device node at `path`, e.g., `/dev/vda`.
+///
+/// The data cached by the device node are written back, since the filesystem
+/// accesses the block device directly.
+fn lookup_block_device(path: &str) -> Result<Arc<dyn BlockDevice>> {
+    let dentry = {
+        let fs_path = FsPath::new(AT_FDCWD, path)?;
+        current!().fs().read().lookup(&fs_path)?
+    };
+    let file_io = dentry
+        .inode()
+        .as_device()
+        .map(|device| device.open())
+        .transpose()?
+        .flatten();
+    let Some(node) = file_io
+        .as_ref()
+        .and_then(|file_io| file_io.downcast_ref::<BlockDeviceNode>())
+    else {
+        return_errno_with_message!(Errno::ENOTBLK, "not a block device");
+    };
+    node.invalidate()?;
+    Ok(node.block_device().clone())
+}
+
+/// Get a FUSE filesystem served through the opened `/dev/fuse` given in `options`.
+fn get_fuse_fs(options: FuseMountOptions) -> Result<Arc<dyn FileSystem>> {
+    let current = current!();
+    let file_table = current.file_table().lock();
+    let file = file_table.get_file(options.fd)?;
+    let dev_file = file
+        .downcast_ref::<InodeHandle>()
+        .and_then(|inode_handle| inode_handle.file_io())
+        .and_then(|file_io| file_io.downcast_ref::<FuseDevFile>())
+        .ok_or(Error::with_message(
+            Errno::EINVAL,
+            "fd is not a FUSE device",
+        ))?;
+    let fuse_fs = FuseFS::new(dev_file, options)?;
+    Ok(fuse_fs)
 }
 
 bitflags! {
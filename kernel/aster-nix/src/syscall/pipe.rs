@@ -33,8 +33,10 @@ pub fn sys_pipe2(fds: Vaddr, flags: u32) -> Result<SyscallReturn> {
 
     let current = current!();
     let mut file_table = current.file_table().lock();
-    pipe_fds.reader_fd = file_table.insert(pipe_reader, fd_flags);
-    pipe_fds.writer_fd = file_table.insert(pipe_writer, fd_flags);
+    pipe_fds.reader_fd = file_table.insert(pipe_reader, fd_flags)?;
+    pipe_fds.writer_fd = file_table.insert(pipe_writer, fd_flags).inspect_err(|_| {
+        file_table.close_file(pipe_fds.reader_fd);
+    })?;
     debug!("pipe_fds: {:?}", pipe_fds);
     write_val_to_user(fds, &pipe_fds)?;
 
@@ -0,0 +1,47 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::SyscallReturn;
+use crate::{
+    fs::{
+        fs_resolver::{FsPath, AT_FDCWD},
+        rootfs,
+    },
+    prelude::*,
+    process::{credentials, credentials::capabilities::CapSet},
+    syscall::constants::MAX_FILENAME_LEN,
+    util::read_cstring_from_user,
+};
+
+pub fn sys_pivot_root(new_root_addr: Vaddr, put_old_addr: Vaddr) -> Result<SyscallReturn> {
+    let new_root = read_cstring_from_user(new_root_addr, MAX_FILENAME_LEN)?;
+    let put_old = read_cstring_from_user(put_old_addr, MAX_FILENAME_LEN)?;
+    debug!("new_root = {:?}, put_old = {:?}", new_root, put_old);
+
+    if !credentials().effective_capset().contains(CapSet::SYS_ADMIN) {
+        return_errno_with_message!(Errno::EPERM, "CAP_SYS_ADMIN is required");
+    }
+
+    let current = current!();
+    let (new_root_dentry, put_old_dentry) = {
+        let fs = current.fs().read();
+        let new_root = new_root.to_string_lossy();
+        let put_old = put_old.to_string_lossy();
+        if new_root.is_empty() || put_old.is_empty() {
+            return_errno_with_message!(Errno::ENOENT, "path is empty");
+        }
+
+        // The root of the current process must be that of the root mount.
+        let root = fs.root();
+        if !root.is_root_of_mount() || !Arc::ptr_eq(root.mount_node(), &rootfs::root_mount()) {
+            return_errno_with_message!(Errno::EINVAL, "the root is not the root mount");
+        }
+
+        (
+            fs.lookup(&FsPath::new(AT_FDCWD, new_root.as_ref())?)?,
+            fs.lookup(&FsPath::new(AT_FDCWD, put_old.as_ref())?)?,
+        )
+    };
+
+    rootfs::pivot_root(&new_root_dentry, &put_old_dentry)?;
+    Ok(SyscallReturn::Return(0))
+}
@@ -3,30 +3,133 @@
 use super::SyscallReturn;
 use crate::{
     prelude::*,
-    process::{Pid, ResourceType},
+    process::{
+        credentials, credentials::capabilities::CapSet, posix_thread::PosixThreadExt,
+        process_table, Pid, Process, RLimit64, ResourceType,
+    },
     util::{read_val_from_user, write_val_to_user},
 };
 
+/// The maximum number of file descriptors, i.e., `/proc/sys/fs/nr_open` of Linux.
+const NR_OPEN: u64 = 1024 * 1024;
+
+pub fn sys_getrlimit(resource: u32, rlim_addr: Vaddr) -> Result<SyscallReturn> {
+    debug!("resource = {}, rlim_addr = 0x{:x}", resource, rlim_addr);
+    do_prlimit64(0, resource, 0, rlim_addr)
+}
+
+pub fn sys_setrlimit(resource: u32, rlim_addr: Vaddr) -> Result<SyscallReturn> {
+    debug!("resource = {}, rlim_addr = 0x{:x}", resource, rlim_addr);
+    do_prlimit64(0, resource, rlim_addr, 0)
+}
+
 pub fn sys_prlimit64(
     pid: Pid,
     resource: u32,
     new_rlim_addr: Vaddr,
     old_rlim_addr: Vaddr,
 ) -> Result<SyscallReturn> {
-    let resource = ResourceType::try_from(resource)?;
     debug!(
-        "pid = {}, resource = {:?}, new_rlim_addr = 0x{:x}, old_rlim_addr = 0x{:x}",
+        "pid = {}, resource = {}, new_rlim_addr = 0x{:x}, old_rlim_addr = 0x{:x}",
         pid, resource, new_rlim_addr, old_rlim_addr
     );
-    let current = current!();
-    let mut resource_limits = current.resource_limits().lock();
-    if old_rlim_addr != 0 {
-        let rlimit = resource_limits.get_rlimit(resource);
-        write_val_to_user(old_rlim_addr, rlimit)?;
+    do_prlimit64(pid, resource, new_rlim_addr, old_rlim_addr)
+}
+
+fn do_prlimit64(
+    pid: Pid,
+    resource: u32,
+    new_rlim_addr: Vaddr,
+    old_rlim_addr: Vaddr,
+) -> Result<SyscallReturn> {
+    let resource = ResourceType::try_from(resource)?;
+    let new_rlimit: Option<RLimit64> = if new_rlim_addr != 0 {
+        Some(read_val_from_user(new_rlim_addr)?)
+    } else {
+        None
+    };
+
+    let process = if pid == 0 {
+        current!()
+    } else {
+        process_table::get_process(pid)
+            .ok_or_else(|| Error::with_message(Errno::ESRCH, "the process does not exist"))?
+    };
+    check_permission(&process)?;
+
+    {
+        let mut resource_limits = process.resource_limits().lock();
+        let old_rlimit = *resource_limits.get_rlimit(resource);
+        if let Some(new_rlimit) = new_rlimit {
+            check_new_rlimit(resource, &old_rlimit, &new_rlimit)?;
+        }
+        if old_rlim_addr != 0 {
+            write_val_to_user(old_rlim_addr, &old_rlimit)?;
+        }
+        if let Some(new_rlimit) = new_rlimit {
+            *resource_limits.get_rlimit_mut(resource) = new_rlimit;
+        }
     }
-    if new_rlim_addr != 0 {
-        let new_rlimit = read_val_from_user(new_rlim_addr)?;
-        *resource_limits.get_rlimit_mut(resource) = new_rlimit;
+
+    if new_rlimit.is_some() && matches!(resource, ResourceType::RLIMIT_CPU) {
+        process.enforce_cpu_limit();
     }
     Ok(SyscallReturn::Return(0))
 }
+
+/// Checks whether the current process can access the limits of the `process`.
+///
+/// Like Linux, the real, effective and saved user IDs of the current process must all
+/// match the real user ID of the target process, unless it has `CAP_SYS_RESOURCE`.
+fn check_permission(process: &Process) -> Result<()> {
+    let current = current!();
+    if current.pid() == process.pid() {
+        return Ok(());
+    }
+
+    let credentials = credentials();
+    if credentials
+        .effective_capset()
+        .contains(CapSet::SYS_RESOURCE)
+    {
+        return Ok(());
+    }
+
+    let Some(target_ruid) = process.main_thread().and_then(|thread| {
+        thread
+            .as_posix_thread()
+            .map(|posix_thread| posix_thread.credentials().ruid())
+    }) else {
+        return_errno_with_message!(Errno::ESRCH, "the process has exited");
+    };
+    if credentials.ruid() != target_ruid
+        || credentials.euid() != target_ruid
+        || credentials.suid() != target_ruid
+    {
+        return_errno_with_message!(Errno::EPERM, "the limits of the process cannot be accessed");
+    }
+    Ok(())
+}
+
+/// Checks whether the limit can be changed from `old_rlimit` to `new_rlimit`.
+fn check_new_rlimit(
+    resource: ResourceType,
+    old_rlimit: &RLimit64,
+    new_rlimit: &RLimit64,
+) -> Result<()> {
+    if !new_rlimit.is_valid() {
+        return_errno_with_message!(Errno::EINVAL, "the soft limit exceeds the hard limit");
+    }
+
+    let can_raise_max = credentials()
+        .effective_capset()
+        .contains(CapSet::SYS_RESOURCE);
+    if new_rlimit.get_max() > old_rlimit.get_max() && !can_raise_max {
+        return_errno_with_message!(Errno::EPERM, "the hard limit cannot be raised");
+    }
+
+    if matches!(resource, ResourceType::RLIMIT_NOFILE) && new_rlimit.get_max() > NR_OPEN {
+        return_errno_with_message!(Errno::EPERM, "the hard limit exceeds the maximum");
+    }
+    Ok(())
+}
@@ -5,6 +5,7 @@ use crate::{
     fs::{file_handle::FileLike, file_table::FdFlags},
     net::socket::{
         ip::{DatagramSocket, StreamSocket},
+        netlink::{NetlinkProtocol, NetlinkUeventSocket},
         unix::UnixStreamSocket,
         vsock::VsockStreamSocket,
     },
@@ -16,30 +17,42 @@ pub fn sys_socket(domain: i32, type_: i32, protocol: i32) -> Result<SyscallRetur
     let domain = CSocketAddrFamily::try_from(domain)?;
     let sock_type = SockType::try_from(type_ & SOCK_TYPE_MASK)?;
     let sock_flags = SockFlags::from_bits_truncate(type_ & !SOCK_TYPE_MASK);
-    let protocol = Protocol::try_from(protocol)?;
     debug!(
-        "domain = {:?}, sock_type = {:?}, sock_flags = {:?}, protocol = {:?}",
+        "domain = {:?}, sock_type = {:?}, sock_flags = {:?}, protocol = {}",
         domain, sock_type, sock_flags, protocol
     );
     let nonblocking = sock_flags.contains(SockFlags::SOCK_NONBLOCK);
-    let file_like = match (domain, sock_type, protocol) {
-        (CSocketAddrFamily::AF_UNIX, SockType::SOCK_STREAM, _) => {
-            Arc::new(UnixStreamSocket::new(nonblocking)) as Arc<dyn FileLike>
+    let file_like = if domain == CSocketAddrFamily::AF_NETLINK {
+        // The protocols of netlink sockets are not IP protocols.
+        let protocol = NetlinkProtocol::try_from(protocol)?;
+        match (sock_type, protocol) {
+            (
+                SockType::SOCK_DGRAM | SockType::SOCK_RAW,
+                NetlinkProtocol::NETLINK_KOBJECT_UEVENT,
+            ) => NetlinkUeventSocket::new(nonblocking) as Arc<dyn FileLike>,
+            _ => return_errno_with_message!(Errno::EPROTONOSUPPORT, "unsupported netlink protocol"),
         }
-        (
-            CSocketAddrFamily::AF_INET,
-            SockType::SOCK_STREAM,
-            Protocol::IPPROTO_IP | Protocol::IPPROTO_TCP,
-        ) => StreamSocket::new(nonblocking) as Arc<dyn FileLike>,
-        (
-            CSocketAddrFamily::AF_INET,
-            SockType::SOCK_DGRAM,
-            Protocol::IPPROTO_IP | Protocol::IPPROTO_UDP,
-        ) => DatagramSocket::new(nonblocking) as Arc<dyn FileLike>,
-        (CSocketAddrFamily::AF_VSOCK, SockType::SOCK_STREAM, _) => {
-            Arc::new(VsockStreamSocket::new(nonblocking)) as Arc<dyn FileLike>
+    } else {
+        let protocol = Protocol::try_from(protocol)?;
+        match (domain, sock_type, protocol) {
+            (CSocketAddrFamily::AF_UNIX, SockType::SOCK_STREAM, _) => {
+                Arc::new(UnixStreamSocket::new(nonblocking)) as Arc<dyn FileLike>
+            }
+            (
+                CSocketAddrFamily::AF_INET,
+                SockType::SOCK_STREAM,
+                Protocol::IPPROTO_IP | Protocol::IPPROTO_TCP,
+            ) => StreamSocket::new(nonblocking) as Arc<dyn FileLike>,
+            (
+                CSocketAddrFamily::AF_INET,
+                SockType::SOCK_DGRAM,
+                Protocol::IPPROTO_IP | Protocol::IPPROTO_UDP,
+            ) => DatagramSocket::new(nonblocking) as Arc<dyn FileLike>,
+            (CSocketAddrFamily::AF_VSOCK, SockType::SOCK_STREAM, _) => {
+                Arc::new(VsockStreamSocket::new(nonblocking)) as Arc<dyn FileLike>
+            }
+            _ => return_errno_with_message!(Errno::EAFNOSUPPORT, "unsupported domain"),
         }
-        _ => return_errno_with_message!(Errno::EAFNOSUPPORT, "unsupported domain"),
     };
     let fd = {
         let current = current!();
@@ -49,7 +62,7 @@ pub fn sys_socket(domain: i32, type_: i32, protocol: i32) -> Result<SyscallRetur
         } else {
             FdFlags::empty()
         };
-        file_table.insert(file_like, fd_flags)
+        file_table.insert(file_like, fd_flags)?
     };
     Ok(SyscallReturn::Return(fd as _))
 }
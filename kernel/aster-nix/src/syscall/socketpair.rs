@@ -41,8 +41,10 @@ pub fn sys_socketpair(domain: i32, type_: i32, protocol: i32, sv: Vaddr) -> Resu
         } else {
             FdFlags::empty()
         };
-        let fd_a = file_table.insert(socket_a, fd_flags);
-        let fd_b = file_table.insert(socket_b, fd_flags);
+        let fd_a = file_table.insert(socket_a, fd_flags)?;
+        let fd_b = file_table.insert(socket_b, fd_flags).inspect_err(|_| {
+            file_table.close_file(fd_a);
+        })?;
         SocketFds(fd_a, fd_b)
     };
 
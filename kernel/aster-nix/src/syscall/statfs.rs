@@ -6,6 +6,7 @@ use crate::{
         file_table::FileDesc,
         fs_resolver::FsPath,
         inode_handle::InodeHandle,
+        path::Dentry,
         utils::{SuperBlock, PATH_MAX},
     },
     prelude::*,
@@ -22,7 +23,7 @@ pub fn sys_statfs(path_ptr: Vaddr, statfs_buf_ptr: Vaddr) -> Result<SyscallRetur
         let fs_path = FsPath::try_from(path.as_ref())?;
         current.fs().read().lookup(&fs_path)?
     };
-    let statfs = Statfs::from(dentry.fs().sb());
+    let statfs = Statfs::of(&dentry);
     write_val_to_user(statfs_buf_ptr, &statfs)?;
     Ok(SyscallReturn::Return(0))
 }
@@ -37,11 +38,14 @@ pub fn sys_fstatfs(fd: FileDesc, statfs_buf_ptr: Vaddr) -> Result<SyscallReturn>
         .downcast_ref::<InodeHandle>()
         .ok_or(Error::with_message(Errno::EBADF, "not inode"))?;
     let dentry = inode_handle.dentry();
-    let statfs = Statfs::from(dentry.fs().sb());
+    let statfs = Statfs::of(&dentry);
     write_val_to_user(statfs_buf_ptr, &statfs)?;
     Ok(SyscallReturn::Return(0))
 }
 
+/// The flag in `f_flags` for the read-only mounts.
+const ST_RDONLY: u64 = 1;
+
 /// FS Stat
 #[derive(Debug, Clone, Copy, Pod, Default)]
 #[repr(C)]
@@ -72,6 +76,17 @@ struct Statfs {
     f_spare: [u64; 4],
 }
 
+impl Statfs {
+    /// Returns the statistics of the filesystem, with the flags of the mount of `dentry`.
+    fn of(dentry: &Dentry) -> Self {
+        let mut statfs = Self::from(dentry.fs().sb());
+        if dentry.mount_node().is_read_only() {
+            statfs.f_flags |= ST_RDONLY;
+        }
+        statfs
+    }
+}
+
 impl From<SuperBlock> for Statfs {
     fn from(sb: SuperBlock) -> Self {
         Self {
@@ -0,0 +1,67 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::time::Duration;
+
+use super::SyscallReturn;
+use crate::{
+    prelude::*,
+    time::{clocks::BootTimeClock, Clock},
+    util::write_val_to_user,
+};
+
+/// The frequency of the clock ticks in which the times are reported.
+const USER_HZ: u64 = 100;
+
+pub fn sys_times(tms_addr: Vaddr) -> Result<SyscallReturn> {
+    debug!("tms_addr = 0x{:x}", tms_addr);
+
+    if tms_addr != 0 {
+        let current = current!();
+        let rusage = current.rusage();
+        let children_rusage = *current.children_rusage().lock();
+        let tms = tms_t {
+            tms_utime: to_clock_ticks(rusage.utime),
+            tms_stime: to_clock_ticks(rusage.stime),
+            tms_cutime: to_clock_ticks(children_rusage.utime),
+            tms_cstime: to_clock_ticks(children_rusage.stime),
+        };
+        write_val_to_user(tms_addr, &tms)?;
+    }
+
+    // The elapsed time is counted from the boot.
+    let elapsed = to_clock_ticks(BootTimeClock::get().read_time());
+    Ok(SyscallReturn::Return(elapsed as _))
+}
+
+fn to_clock_ticks(time: Duration) -> i64 {
+    (time.as_millis() as u64 * USER_HZ / 1000) as i64
+}
+
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, Pod)]
+struct tms_t {
+    /// user time
+    tms_utime: i64,
+    /// system time
+    tms_stime: i64,
+    /// user time of the waited children
+    tms_cutime: i64,
+    /// system time of the waited children
+    tms_cstime: i64,
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn clock_ticks() {
+        assert_eq!(to_clock_ticks(Duration::ZERO), 0);
+        assert_eq!(to_clock_ticks(Duration::from_millis(9)), 0);
+        assert_eq!(to_clock_ticks(Duration::from_millis(1234)), 123);
+        assert_eq!(to_clock_ticks(Duration::from_secs(60)), 6000);
+    }
+}
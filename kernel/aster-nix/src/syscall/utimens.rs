@@ -137,6 +137,8 @@ fn vfs_utimes(dentry: &Arc<Dentry>, times: Option<TimeSpecPair>) -> Result<Sysca
         }
     };
 
+    dentry.check_writable_mount()?;
+
     // Update times
     dentry.set_atime(atime);
     dentry.set_mtime(mtime);
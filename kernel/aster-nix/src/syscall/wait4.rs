@@ -33,13 +33,11 @@ pub fn sys_wait4(
     }
 
     if rusage_addr != 0 {
-        let rusage = rusage_t {
-            ru_utime: process.prof_clock().user_clock().read_time().into(),
-            ru_stime: process.prof_clock().kernel_clock().read_time().into(),
-            ..Default::default()
-        };
+        // The resource usage of the child includes that of its waited children.
+        let mut rusage = process.rusage();
+        rusage += *process.children_rusage().lock();
 
-        write_val_to_user(rusage_addr, &rusage)?;
+        write_val_to_user(rusage_addr, &rusage_t::from(rusage))?;
     }
 
     Ok(SyscallReturn::Return(return_pid as _))
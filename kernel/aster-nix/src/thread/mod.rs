@@ -4,7 +4,7 @@
 
 use core::sync::atomic::{AtomicU32, Ordering};
 
-use ostd::task::Task;
+use ostd::task::{Task, TaskStatus};
 
 use self::status::{AtomicThreadStatus, ThreadStatus};
 use crate::prelude::*;
@@ -90,6 +90,16 @@ impl Thread {
         self.status.store(new_status, Ordering::Release);
     }
 
+    /// Returns whether the thread is running or ready to run.
+    pub fn is_runnable(&self) -> bool {
+        self.status() == ThreadStatus::Running && self.task.status() == TaskStatus::Runnable
+    }
+
+    /// Returns whether the thread is sleeping, waiting for some events.
+    pub fn is_blocked(&self) -> bool {
+        self.status() == ThreadStatus::Running && self.task.status() == TaskStatus::Sleeping
+    }
+
     pub fn yield_now() {
         Task::yield_now()
     }
@@ -111,3 +121,8 @@ impl Thread {
 pub fn allocate_tid() -> Tid {
     TID_ALLOCATOR.fetch_add(1, Ordering::SeqCst)
 }
+
+/// Returns the tid that is allocated most recently.
+pub fn last_allocated_tid() -> Tid {
+    TID_ALLOCATOR.load(Ordering::SeqCst).saturating_sub(1)
+}
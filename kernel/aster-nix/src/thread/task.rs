@@ -39,7 +39,10 @@ pub fn create_new_user_task(user_space: Arc<UserSpace>, thread_ref: Weak<Thread>
         let posix_thread = current_thread.as_posix_thread().unwrap();
         let has_kernel_event_fn = || posix_thread.has_pending();
         loop {
+            // The time between entering and leaving the user mode is the user CPU time.
+            posix_thread.enter_user_mode();
             let return_reason = user_mode.execute(has_kernel_event_fn);
+            posix_thread.leave_user_mode();
             let context = user_mode.context_mut();
             // handle user event:
             match return_reason {
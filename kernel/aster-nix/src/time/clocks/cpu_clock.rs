@@ -4,6 +4,7 @@ use alloc::sync::Arc;
 use core::time::Duration;
 
 use ostd::sync::SpinLock;
+use spin::Once;
 
 use crate::time::Clock;
 
@@ -65,3 +66,18 @@ impl Clock for ProfClock {
         self.user_clock.read_time() + self.kernel_clock.read_time()
     }
 }
+
+static SYSTEM_PROF_CLOCK: Once<Arc<ProfClock>> = Once::new();
+static IDLE_CLOCK: Once<Arc<CpuClock>> = Once::new();
+
+/// Returns the profiling clock that records the CPU time of all the user threads
+/// in the system.
+pub fn system_prof_clock() -> &'static Arc<ProfClock> {
+    SYSTEM_PROF_CLOCK.call_once(ProfClock::new)
+}
+
+/// Returns the clock that records the CPU time spent out of any user thread,
+/// which is regarded as the idle time of the system.
+pub fn idle_clock() -> &'static Arc<CpuClock> {
+    IDLE_CLOCK.call_once(CpuClock::new)
+}
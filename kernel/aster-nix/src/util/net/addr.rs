@@ -6,16 +6,19 @@
 use crate::{
     net::{
         iface::Ipv4Address,
-        socket::{unix::UnixSocketAddr, vsock::VsockSocketAddr, SocketAddr},
+        socket::{
+            netlink::NetlinkSocketAddr, unix::UnixSocketAddr, vsock::VsockSocketAddr, SocketAddr,
+        },
     },
     prelude::*,
     util::{read_bytes_from_user, read_val_from_user, write_val_to_user},
 };
 
 pub fn read_socket_addr_from_user(addr: Vaddr, addr_len: usize) -> Result<SocketAddr> {
-    debug_assert!(addr_len >= core::mem::size_of::<CSocketAddr>());
-    let sockaddr: CSocketAddr = read_val_from_user(addr)?;
-    let socket_addr = match sockaddr.sa_family()? {
+    debug_assert!(addr_len >= core::mem::size_of::<u16>());
+    // Only the family is read first, since some addresses are shorter than `CSocketAddr`.
+    let sa_family: u16 = read_val_from_user(addr)?;
+    let socket_addr = match CSocketAddrFamily::try_from(sa_family as i32)? {
         CSocketAddrFamily::AF_UNSPEC => {
             return_errno_with_message!(Errno::EINVAL, "the socket addr family is unspecified")
         }
@@ -66,6 +69,14 @@ pub fn read_socket_addr_from_user(addr: Vaddr, addr_len: usize) -> Result<Socket
                 sock_addr_vm.svm_port,
             ))
         }
+        CSocketAddrFamily::AF_NETLINK => {
+            debug_assert!(addr_len >= core::mem::size_of::<CSocketAddrNetlink>());
+            let sock_addr_nl: CSocketAddrNetlink = read_val_from_user(addr)?;
+            SocketAddr::Netlink(NetlinkSocketAddr::new(
+                sock_addr_nl.nl_pid,
+                sock_addr_nl.nl_groups,
+            ))
+        }
         _ => {
             return_errno_with_message!(Errno::EAFNOSUPPORT, "cannot support address for the family")
         }
@@ -124,6 +135,13 @@ pub fn write_socket_addr_with_max_len(
             write_val_to_user(dest, &vm_addr)?;
             write_size as i32
         }
+        SocketAddr::Netlink(addr) => {
+            let nl_addr = CSocketAddrNetlink::new(addr.port, addr.groups);
+            let write_size = core::mem::size_of::<CSocketAddrNetlink>();
+            debug_assert!(max_len >= write_size);
+            write_val_to_user(dest, &nl_addr)?;
+            write_size as i32
+        }
     };
 
     Ok(write_size)
@@ -259,6 +277,31 @@ pub struct CSocketAddrVm {
     svm_zero: [u8; 4],
 }
 
+/// Netlink socket address
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub struct CSocketAddrNetlink {
+    /// always [SaFamily::AF_NETLINK]
+    nl_family: u16,
+    /// always 0
+    nl_pad: u16,
+    /// Port ID
+    nl_pid: u32,
+    /// Multicast groups mask
+    nl_groups: u32,
+}
+
+impl CSocketAddrNetlink {
+    pub fn new(port: u32, groups: u32) -> Self {
+        Self {
+            nl_family: CSocketAddrFamily::AF_NETLINK as _,
+            nl_pad: 0,
+            nl_pid: port,
+            nl_groups: groups,
+        }
+    }
+}
+
 impl CSocketAddrVm {
     pub fn new(cid: u32, port: u32) -> Self {
         Self {
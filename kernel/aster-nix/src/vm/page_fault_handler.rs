@@ -1,6 +1,9 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use crate::prelude::*;
+use crate::{
+    prelude::*,
+    process::{account_current, UsageKind},
+};
 
 /// This trait is implemented by structs which can handle a user space page fault.
 pub trait PageFaultHandler {
@@ -12,3 +15,16 @@ pub trait PageFaultHandler {
     /// Otherwise, this function will return Err.
     fn handle_page_fault(&self, offset: Vaddr, not_present: bool, write: bool) -> Result<()>;
 }
+
+/// Accounts a page fault that is handled for the current thread.
+///
+/// A major page fault is one that may need to read the page from a file,
+/// while a minor one is handled without I/O.
+pub fn account_page_fault(is_major: bool) {
+    let kind = if is_major {
+        UsageKind::MajorFault
+    } else {
+        UsageKind::MinorFault
+    };
+    account_current(kind, 1);
+}
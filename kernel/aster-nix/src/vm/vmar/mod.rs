@@ -763,6 +763,15 @@ impl Vmar_ {
 
         return_errno_with_message!(Errno::EFAULT, "No mapped vmo at this offset");
     }
+
+    /// Collects the mappings in this VMAR and its descendants.
+    fn collect_vm_mappings(&self, vm_mappings: &mut Vec<Arc<VmMapping>>) {
+        let inner = self.inner.lock();
+        vm_mappings.extend(inner.vm_mappings.values().cloned());
+        for child_vmar_ in inner.child_vmar_s.values() {
+            child_vmar_.collect_vm_mappings(vm_mappings);
+        }
+    }
 }
 
 impl<R> Vmar<R> {
@@ -785,6 +794,15 @@ impl<R> Vmar<R> {
         self.check_rights(rights)?;
         self.0.get_vm_mapping(offset)
     }
+
+    /// Returns all the mappings in the VMAR, including those in the child VMARs,
+    /// sorted by their addresses.
+    pub fn vm_mappings(&self) -> Vec<Arc<VmMapping>> {
+        let mut vm_mappings = Vec::new();
+        self.0.collect_vm_mappings(&mut vm_mappings);
+        vm_mappings.sort_by_key(|vm_mapping| vm_mapping.map_to_addr());
+        vm_mappings
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -9,8 +9,10 @@ use ostd::mm::{Frame, FrameVec, PageFlags, VmIo, VmMapOptions, VmSpace};
 
 use super::{interval::Interval, is_intersected, Vmar, Vmar_};
 use crate::{
+    fs::path::Dentry,
     prelude::*,
     vm::{
+        page_fault_handler::account_page_fault,
         perms::VmPerms,
         vmar::Rights,
         vmo::{get_page_idx_range, Vmo, VmoChildOptions, VmoRightsOp},
@@ -31,6 +33,9 @@ pub struct VmMapping {
     /// TODO: support file-backed shared mappings.
     /// only anonyous memory can be mapped shared now.
     is_shared: bool,
+    /// The file that the vmo is created from, and the offset in the file
+    /// where the vmo starts.
+    file: Option<(Arc<Dentry>, usize)>,
 }
 
 impl VmMapping {
@@ -42,6 +47,7 @@ impl VmMapping {
             parent: self.parent.clone(),
             vmo,
             is_shared: self.is_shared,
+            file: self.file.clone(),
         })
     }
 }
@@ -82,6 +88,7 @@ impl VmMapping {
             align,
             can_overwrite,
             is_shared,
+            file,
         } = option;
         let Vmar(parent_vmar, _) = parent;
         let vmo_size = vmo.size();
@@ -112,6 +119,7 @@ impl VmMapping {
             parent: Arc::downgrade(&parent_vmar),
             vmo: vmo.to_dyn(),
             is_shared,
+            file,
         })
     }
 
@@ -173,6 +181,30 @@ impl VmMapping {
         self.inner.lock().vmo_offset
     }
 
+    /// Returns the permissions of the mapping.
+    pub fn perms(&self) -> VmPerms {
+        self.inner.lock().perms
+    }
+
+    /// Returns whether the mapping is shared among processes.
+    pub fn is_shared(&self) -> bool {
+        self.is_shared
+    }
+
+    /// Returns the mapped file and the offset in the file where the mapping starts,
+    /// if the mapping is created from a file.
+    pub fn file(&self) -> Option<(&Arc<Dentry>, usize)> {
+        self.file
+            .as_ref()
+            .map(|(dentry, offset)| (dentry, offset + self.vmo_offset()))
+    }
+
+    /// Returns the number of the pages that have been mapped into the page table,
+    /// i.e., the pages that are resident in memory.
+    pub fn nr_mapped_pages(&self) -> usize {
+        self.inner.lock().mapped_pages.len()
+    }
+
     pub fn read_bytes(&self, offset: usize, buf: &mut [u8]) -> Result<()> {
         let vmo_read_offset = self.vmo_offset() + offset;
 
@@ -247,12 +279,16 @@ impl VmMapping {
         let required_perm = if write { VmPerms::WRITE } else { VmPerms::READ };
         self.check_perms(&required_perm)?;
 
+        // The page of a file mapping that has not been committed may be read from the file.
+        let is_major = self.file.is_some() && !self.vmo.is_page_committed(page_idx);
         let frame = self.vmo.get_committed_frame(page_idx, write)?;
 
         // If read access to cow vmo triggers page fault, the map should be readonly.
         // If user next tries to write to the frame, another page fault will be triggered.
         let is_readonly = self.vmo.is_cow_vmo() && !write;
-        self.map_one_page(page_idx, frame, is_readonly)
+        self.map_one_page(page_idx, frame, is_readonly)?;
+        account_page_fault(is_major);
+        Ok(())
     }
 
     /// Protect a specified range of pages in the mapping to the target perms.
@@ -309,6 +345,7 @@ impl VmMapping {
             parent: Arc::downgrade(new_parent),
             vmo: child_vmo,
             is_shared: self.is_shared,
+            file: self.file.clone(),
         })
     }
 
@@ -628,6 +665,8 @@ pub struct VmarMapOptions<R1, R2> {
     can_overwrite: bool,
     // Whether the mapping is mapped with `MAP_SHARED`
     is_shared: bool,
+    // The file that the VMO is created from, and the offset of the VMO in the file
+    file: Option<(Arc<Dentry>, usize)>,
 }
 
 impl<R1, R2> VmarMapOptions<R1, R2> {
@@ -649,6 +688,7 @@ impl<R1, R2> VmarMapOptions<R1, R2> {
             align: PAGE_SIZE,
             can_overwrite: false,
             is_shared: false,
+            file: None,
         }
     }
 
@@ -729,6 +769,17 @@ impl<R1, R2> VmarMapOptions<R1, R2> {
         self
     }
 
+    /// Sets the file that the VMO is created from, where `file_offset` is the
+    /// offset in the file that the start of the VMO corresponds to.
+    ///
+    /// The file is only used to describe the mapping, e.g., in `/proc/[pid]/maps`.
+    ///
+    /// The default value is `None`.
+    pub fn file(mut self, file: Arc<Dentry>, file_offset: usize) -> Self {
+        self.file = Some((file, file_offset));
+        self
+    }
+
     /// Creates the mapping.
     ///
     /// All options will be checked at this point.
@@ -14,7 +14,7 @@ use ostd::{
     mm::{Frame, FrameAllocOptions, VmReader, VmWriter},
 };
 
-use crate::prelude::*;
+use crate::{cgroup::MemoryCharge, prelude::*};
 
 mod dyn_cap;
 mod options;
@@ -195,12 +195,15 @@ pub(super) struct Vmo_ {
     page_idx_offset: usize,
     /// The virtual pages where the VMO resides.
     pages: Pages,
-}
-
-fn clone_page(page: &Frame) -> Result<Frame> {
-    let new_page = FrameAllocOptions::new(1).alloc_single()?;
-    new_page.copy_from(page);
-    Ok(new_page)
+    /// The pages allocated by the VMO, which are charged to the memory cgroup.
+    ///
+    /// The pages provided by the pager are charged by the pager instead.
+    ///
+    /// The accounting is approximate for the pages shared by COW VMOs. A shared page
+    /// stays charged to the VMO that allocated it until the VMO is dropped, even if it
+    /// is removed from the VMO earlier, or the COW children still map it afterwards.
+    /// The children are charged only for the pages that they copy on write.
+    memory_charge: MemoryCharge,
 }
 
 bitflags! {
@@ -225,6 +228,24 @@ impl CommitFlags {
 }
 
 impl Vmo_ {
+    /// Allocates a new page, which is charged to the memory cgroup of the VMO.
+    fn alloc_page(&self) -> Result<Frame> {
+        self.memory_charge.try_charge(1)?;
+        match FrameAllocOptions::new(1).alloc_single() {
+            Ok(page) => Ok(page),
+            Err(err) => {
+                self.memory_charge.uncharge(1);
+                Err(err.into())
+            }
+        }
+    }
+
+    fn clone_page(&self, page: &Frame) -> Result<Frame> {
+        let new_page = self.alloc_page()?;
+        new_page.copy_from(page);
+        Ok(new_page)
+    }
+
     /// Prepare a new `Frame` for the target index in pages, returning the new page as well as
     /// whether this page needs to be marked as exclusive.
     ///
@@ -245,7 +266,7 @@ impl Vmo_ {
             None => {
                 // Condition 1. The new anonymous page only need to be marked as `ExclusivePage`
                 // when current VMO is a cow VMO, otherwise this mark is meaningless.
-                (FrameAllocOptions::new(1).alloc_single()?, is_cow_vmo)
+                (self.alloc_page()?, is_cow_vmo)
             }
             Some(pager) => {
                 let page = pager.commit_page(page_idx)?;
@@ -256,7 +277,7 @@ impl Vmo_ {
                 let trigger_cow = is_cow_vmo && commit_flags.will_write();
                 if trigger_cow {
                     // Condition 3.
-                    (clone_page(&page)?, true)
+                    (self.clone_page(&page)?, true)
                 } else {
                     // Condition 2.
                     (page, false)
@@ -275,7 +296,7 @@ impl Vmo_ {
         {
             pager.commit_overwrite(page_idx)?
         } else {
-            FrameAllocOptions::new(1).alloc_single()?
+            self.alloc_page()?
         };
         Ok(page)
     }
@@ -299,9 +320,9 @@ impl Vmo_ {
                 }
 
                 if commit_flags.will_overwrite() {
-                    (FrameAllocOptions::new(1).alloc_single()?, true)
+                    (self.alloc_page()?, true)
                 } else {
-                    (clone_page(&committed_page)?, true)
+                    (self.clone_page(&committed_page)?, true)
                 }
             } else if commit_flags.will_overwrite() {
                 // In this case, the page will be completely overwritten. The page only needs to
@@ -345,16 +366,32 @@ impl Vmo_ {
         self.pages.with(|pages, size| {
             let is_cow_vmo = pages.is_marked(VmoMark::CowVmo);
             let mut cursor = pages.cursor_mut(page_idx as u64);
-            if cursor.remove().is_some()
-                && let Some(pager) = &self.pager
-                && !is_cow_vmo
-            {
-                pager.decommit_page(page_idx)?;
-            }
-            Ok(())
+            self.remove_page(&mut cursor, page_idx, is_cow_vmo)
         })
     }
 
+    /// Removes the page at the cursor, which is decommitted from the pager or uncharged
+    /// from the memory cgroup if it is provided by the pager or allocated by the VMO.
+    fn remove_page(
+        &self,
+        cursor: &mut CursorMut<'_, Frame, VmoMark>,
+        page_idx: usize,
+        is_cow_vmo: bool,
+    ) -> Result<()> {
+        let is_exclusive = cursor.is_marked(VmoMark::ExclusivePage);
+        if cursor.remove().is_none() {
+            return Ok(());
+        }
+        match &self.pager {
+            Some(pager) if !is_cow_vmo => pager.decommit_page(page_idx)?,
+            // A page of a COW VMO is allocated by the VMO if it is exclusive.
+            // Otherwise, it is shared with and charged by the parent or the pager.
+            _ if is_cow_vmo && !is_exclusive => (),
+            _ => self.memory_charge.uncharge(1),
+        }
+        Ok(())
+    }
+
     /// Commit a range of pages in the VMO, and perform the operation
     /// on each page in the range in turn.
     pub fn commit_and_operate<F>(
@@ -599,12 +636,7 @@ impl Vmo_ {
         let is_cow_vmo = pages.is_marked(VmoMark::CowVmo);
         let mut cursor = pages.cursor_mut(page_idx_range.start as u64);
         for page_idx in page_idx_range {
-            if cursor.remove().is_some()
-                && let Some(pager) = &self.pager
-                && !is_cow_vmo
-            {
-                pager.decommit_page(page_idx)?;
-            }
+            self.remove_page(&mut cursor, page_idx, is_cow_vmo)?;
             cursor.next();
         }
         Ok(())
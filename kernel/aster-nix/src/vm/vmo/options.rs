@@ -16,7 +16,7 @@ use ostd::{
 use typeflags_util::{SetExtend, SetExtendOp};
 
 use super::{Pager, Pages, Vmo, VmoFlags, VmoMark, VmoRightsOp};
-use crate::{prelude::*, vm::vmo::Vmo_};
+use crate::{cgroup::MemoryCharge, prelude::*, vm::vmo::Vmo_};
 
 /// Options for allocating a root VMO.
 ///
@@ -126,8 +126,9 @@ impl<R: TRights> VmoOptions<TRightSet<R>> {
 
 fn alloc_vmo_(size: usize, flags: VmoFlags, pager: Option<Arc<dyn Pager>>) -> Result<Vmo_> {
     let size = size.align_up(PAGE_SIZE);
+    let memory_charge = MemoryCharge::new();
     let pages = {
-        let pages = committed_pages_if_continuous(flags, size)?;
+        let pages = committed_pages_if_continuous(flags, size, &memory_charge)?;
         if flags.contains(VmoFlags::RESIZABLE) {
             Pages::Resizable(Mutex::new((pages, size)))
         } else {
@@ -139,13 +140,19 @@ fn alloc_vmo_(size: usize, flags: VmoFlags, pager: Option<Arc<dyn Pager>>) -> Re
         flags,
         page_idx_offset: 0,
         pages,
+        memory_charge,
     })
 }
 
-fn committed_pages_if_continuous(flags: VmoFlags, size: usize) -> Result<XArray<Frame, VmoMark>> {
+fn committed_pages_if_continuous(
+    flags: VmoFlags,
+    size: usize,
+    memory_charge: &MemoryCharge,
+) -> Result<XArray<Frame, VmoMark>> {
     if flags.contains(VmoFlags::CONTIGUOUS) {
         // if the vmo is continuous, we need to allocate frames for the vmo
         let frames_num = size / PAGE_SIZE;
+        memory_charge.try_charge(frames_num)?;
         let frames = FrameAllocOptions::new(frames_num)
             .is_contiguous(true)
             .alloc()?;
@@ -457,6 +464,7 @@ fn alloc_child_vmo_(
         flags: child_flags,
         pages: child_pages,
         page_idx_offset: parent_page_idx_offset + parent_vmo_.page_idx_offset(),
+        memory_charge: MemoryCharge::new(),
     };
     Ok(new_vmo)
 }
@@ -559,6 +567,29 @@ mod test {
         assert_eq!(cow_child.read_val::<u32>(2).unwrap(), 0x1234);
     }
 
+    #[ktest]
+    fn cow_child_decommit() {
+        let parent = VmoOptions::<Full>::new(2 * PAGE_SIZE).alloc().unwrap();
+        parent.write_val(0, &1u8).unwrap();
+        parent.write_val(PAGE_SIZE, &2u8).unwrap();
+        assert_eq!(parent.0.memory_charge.nr_pages(), 2);
+        let cow_child = VmoChildOptions::new_cow(parent.dup(), 0..2 * PAGE_SIZE)
+            .alloc()
+            .unwrap();
+        assert_eq!(cow_child.0.memory_charge.nr_pages(), 0);
+
+        // The child is charged for the page copied on write.
+        cow_child.write_val(0, &3u8).unwrap();
+        assert_eq!(cow_child.0.memory_charge.nr_pages(), 1);
+        // The page shared with the parent is not uncharged from the child.
+        cow_child.decommit(PAGE_SIZE..2 * PAGE_SIZE).unwrap();
+        assert_eq!(cow_child.0.memory_charge.nr_pages(), 1);
+        cow_child.decommit(0..PAGE_SIZE).unwrap();
+        assert_eq!(cow_child.0.memory_charge.nr_pages(), 0);
+        assert_eq!(parent.0.memory_charge.nr_pages(), 2);
+        assert_eq!(parent.read_val::<u8>(0).unwrap(), 1);
+    }
+
     #[ktest]
     fn resize() {
         let vmo = VmoOptions::<Full>::new(PAGE_SIZE)
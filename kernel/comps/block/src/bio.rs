@@ -6,10 +6,23 @@ use ostd::{
     mm::{Frame, Segment, VmReader, VmWriter},
     sync::WaitQueue,
 };
+use spin::Once;
 
 use super::{id::Sid, BlockDevice};
 use crate::prelude::*;
 
+/// The callback to account the block I/O, see `register_io_accounting_callback`.
+static IO_ACCOUNTING_CALLBACK: Once<fn(BioType, usize)> = Once::new();
+
+/// Registers the callback that is invoked with the type and the number of sectors
+/// of each read or write `Bio` once it is submitted.
+///
+/// The bios submitted by the stacked devices on behalf of other bios are not
+/// reported, so the I/O is accounted to the task that submits the original one.
+pub fn register_io_accounting_callback(callback: fn(BioType, usize)) {
+    IO_ACCOUNTING_CALLBACK.call_once(|| callback);
+}
+
 /// The unit for block I/O.
 ///
 /// Each `Bio` packs the following information:
@@ -45,10 +58,59 @@ impl Bio {
             complete_fn,
             status: AtomicU32::new(BioStatus::Init as u32),
             wait_queue: WaitQueue::new(),
+            parent: None,
+            private: None,
         });
         Self(inner)
     }
 
+    /// Constructs a new `Bio` which carries no data.
+    ///
+    /// It is used for the I/O types that do not transfer data between the memory
+    /// and the device, i.e., `Flush`, `Discard` and `WriteZeroes`.
+    /// The `sid_range` is the range of target sectors, which is ignored by `Flush`.
+    ///
+    /// # Panics
+    ///
+    /// If the `type_` is `Read` or `Write`, this method will panic.
+    pub fn new_dataless(
+        type_: BioType,
+        sid_range: Range<Sid>,
+        complete_fn: Option<fn(&SubmittedBio)>,
+    ) -> Self {
+        assert!(type_ != BioType::Read && type_ != BioType::Write);
+
+        let sid_range = match type_ {
+            BioType::Flush => Sid::new(0)..Sid::new(0),
+            _ => sid_range,
+        };
+        let inner = Arc::new(BioInner {
+            type_,
+            sid_range,
+            segments: Vec::new(),
+            complete_fn,
+            status: AtomicU32::new(BioStatus::Init as u32),
+            wait_queue: WaitQueue::new(),
+            parent: None,
+            private: None,
+        });
+        Self(inner)
+    }
+
+    /// Attaches the private data, which can be retrieved by the callback function
+    /// through `SubmittedBio::private`.
+    ///
+    /// # Panics
+    ///
+    /// The private data must be attached before the `Bio` is submitted.
+    /// Otherwise, a panic shall be triggered.
+    pub fn with_private(mut self, private: Arc<dyn Any + Send + Sync>) -> Self {
+        Arc::get_mut(&mut self.0)
+            .expect("the bio has been submitted")
+            .private = Some(private);
+        self
+    }
+
     /// Returns the type.
     pub fn type_(&self) -> BioType {
         self.0.type_()
@@ -77,6 +139,30 @@ impl Bio {
     ///
     /// The caller must not submit a `Bio` more than once. Otherwise, a panic shall be triggered.
     pub fn submit(&self, block_device: &dyn BlockDevice) -> Result<BioWaiter, BioEnqueueError> {
+        let waiter = self.submit_nested(block_device)?;
+
+        if matches!(self.type_(), BioType::Read | BioType::Write) {
+            if let Some(callback) = IO_ACCOUNTING_CALLBACK.get() {
+                let sid_range = self.sid_range();
+                let nr_sectors = (sid_range.end.to_raw() - sid_range.start.to_raw()) as usize;
+                callback(self.type_(), nr_sectors);
+            }
+        }
+        Ok(waiter)
+    }
+
+    /// Submits self to the `block_device` asynchronously on behalf of another `Bio`,
+    /// which is used by the stacked devices.
+    ///
+    /// Unlike `submit`, the I/O is not reported to the accounting callback again.
+    ///
+    /// # Panics
+    ///
+    /// The caller must not submit a `Bio` more than once. Otherwise, a panic shall be triggered.
+    pub(crate) fn submit_nested(
+        &self,
+        block_device: &dyn BlockDevice,
+    ) -> Result<BioWaiter, BioEnqueueError> {
         // Change the status from "Init" to "Submit".
         let result = self.0.status.compare_exchange(
             BioStatus::Init as u32,
@@ -260,6 +346,11 @@ impl SubmittedBio {
         self.0.status()
     }
 
+    /// Returns the private data attached by `Bio::with_private`.
+    pub fn private(&self) -> Option<&Arc<dyn Any + Send + Sync>> {
+        self.0.private.as_ref()
+    }
+
     /// Completes the `Bio` with the `status` and invokes the callback function.
     ///
     /// When the driver finishes the request for this `Bio`, it will call this method.
@@ -279,6 +370,37 @@ impl SubmittedBio {
         if let Some(complete_fn) = self.0.complete_fn {
             complete_fn(self);
         }
+        if let Some(parent) = &self.0.parent {
+            parent.complete(status);
+        }
+    }
+
+    /// Creates a `Bio` whose target sectors are shifted by `sid_offset`,
+    /// sharing the memory segments with this bio.
+    ///
+    /// When the new `Bio` is completed, this bio is completed with the same status.
+    /// It is used by the block devices stacked on other devices, e.g., partitions,
+    /// to forward the bios to the underlying devices.
+    pub fn remap(&self, sid_offset: Sid) -> Bio {
+        let sid_range = match self.type_() {
+            // The flush is not targeted to any sector.
+            BioType::Flush => self.sid_range().clone(),
+            _ => {
+                self.sid_range().start + sid_offset.to_raw()
+                    ..self.sid_range().end + sid_offset.to_raw()
+            }
+        };
+        let inner = Arc::new(BioInner {
+            type_: self.type_(),
+            sid_range,
+            segments: self.segments().to_vec(),
+            complete_fn: None,
+            status: AtomicU32::new(BioStatus::Init as u32),
+            wait_queue: WaitQueue::new(),
+            parent: Some(SubmittedBio(self.0.clone())),
+            private: None,
+        });
+        Bio(inner)
     }
 }
 
@@ -296,6 +418,10 @@ struct BioInner {
     status: AtomicU32,
     /// The wait queue for I/O completion
     wait_queue: WaitQueue,
+    /// The bio to be completed along with this bio, see `SubmittedBio::remap`
+    parent: Option<SubmittedBio>,
+    /// The private data for the callback function
+    private: Option<Arc<dyn Any + Send + Sync>>,
 }
 
 impl BioInner {
@@ -340,6 +466,8 @@ pub enum BioType {
     Flush = 2,
     /// Discard sectors.
     Discard = 3,
+    /// Write zeroes into sectors.
+    WriteZeroes = 4,
 }
 
 /// The status of `Bio`.
@@ -422,6 +550,16 @@ impl<'a> BioSegment {
         &self.pages
     }
 
+    /// Returns a `BioSegment` of the `len` bytes starting from the `offset` (in bytes)
+    /// within this segment.
+    ///
+    /// Both the `offset` and the `len` should be aligned to the sector size.
+    pub fn slice(&self, offset: usize, len: usize) -> Self {
+        assert!(offset + len <= self.nbytes());
+
+        Self::from_segment(self.pages.clone(), self.offset() + offset, len)
+    }
+
     /// Returns a reader to read data from it.
     pub fn reader(&'a self) -> VmReader<'a> {
         self.pages
@@ -0,0 +1,281 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The AES block cipher as specified in FIPS 197.
+
+/// The size of an AES block in bytes.
+pub(super) const BLOCK_SIZE: usize = 16;
+
+/// The maximum number of rounds, which is used by AES-256.
+const MAX_ROUNDS: usize = 14;
+
+/// An AES cipher with the expanded round keys.
+#[derive(Clone)]
+pub(super) struct Aes {
+    round_keys: [[u8; BLOCK_SIZE]; MAX_ROUNDS + 1],
+    nr_rounds: usize,
+}
+
+impl Aes {
+    /// Creates the cipher with a key of 16, 24 or 32 bytes.
+    ///
+    /// Returns `None` if the key length is invalid.
+    pub(super) fn new(key: &[u8]) -> Option<Self> {
+        let nk = match key.len() {
+            16 | 24 | 32 => key.len() / 4,
+            _ => return None,
+        };
+        let nr_rounds = nk + 6;
+
+        let nr_words = 4 * (nr_rounds + 1);
+        let mut words = [[0u8; 4]; 4 * (MAX_ROUNDS + 1)];
+        for (word, bytes) in words.iter_mut().zip(key.chunks_exact(4)) {
+            word.copy_from_slice(bytes);
+        }
+        let mut rcon = 1u8;
+        for i in nk..nr_words {
+            let mut temp = words[i - 1];
+            if i % nk == 0 {
+                temp.rotate_left(1);
+                temp = sub_word(temp);
+                temp[0] ^= rcon;
+                rcon = xtime(rcon);
+            } else if nk > 6 && i % nk == 4 {
+                temp = sub_word(temp);
+            }
+            for j in 0..4 {
+                words[i][j] = words[i - nk][j] ^ temp[j];
+            }
+        }
+
+        let mut round_keys = [[0u8; BLOCK_SIZE]; MAX_ROUNDS + 1];
+        for (round, round_key) in round_keys.iter_mut().take(nr_rounds + 1).enumerate() {
+            for column in 0..4 {
+                round_key[column * 4..column * 4 + 4].copy_from_slice(&words[round * 4 + column]);
+            }
+        }
+        // The round keys are the copies of the words.
+        words.fill([0u8; 4]);
+        core::hint::black_box(&words);
+        Some(Self {
+            round_keys,
+            nr_rounds,
+        })
+    }
+
+    pub(super) fn encrypt_block(&self, block: &mut [u8; BLOCK_SIZE]) {
+        add_round_key(block, &self.round_keys[0]);
+        for round in 1..self.nr_rounds {
+            sub_bytes(block);
+            shift_rows(block);
+            mix_columns(block);
+            add_round_key(block, &self.round_keys[round]);
+        }
+        sub_bytes(block);
+        shift_rows(block);
+        add_round_key(block, &self.round_keys[self.nr_rounds]);
+    }
+
+    pub(super) fn decrypt_block(&self, block: &mut [u8; BLOCK_SIZE]) {
+        add_round_key(block, &self.round_keys[self.nr_rounds]);
+        for round in (1..self.nr_rounds).rev() {
+            inv_shift_rows(block);
+            inv_sub_bytes(block);
+            add_round_key(block, &self.round_keys[round]);
+            inv_mix_columns(block);
+        }
+        inv_shift_rows(block);
+        inv_sub_bytes(block);
+        add_round_key(block, &self.round_keys[0]);
+    }
+}
+
+impl Drop for Aes {
+    fn drop(&mut self) {
+        self.round_keys = [[0u8; BLOCK_SIZE]; MAX_ROUNDS + 1];
+    }
+}
+
+// The state is stored column by column, i.e., the byte at row `r` and column `c`
+// is `block[r + 4 * c]`.
+
+fn add_round_key(block: &mut [u8; BLOCK_SIZE], round_key: &[u8; BLOCK_SIZE]) {
+    for (byte, key) in block.iter_mut().zip(round_key) {
+        *byte ^= key;
+    }
+}
+
+fn sub_bytes(block: &mut [u8; BLOCK_SIZE]) {
+    let planes = to_planes(block);
+    *block = from_planes(&affine(&gf_inv(&planes)));
+}
+
+fn inv_sub_bytes(block: &mut [u8; BLOCK_SIZE]) {
+    let planes = to_planes(block);
+    *block = from_planes(&gf_inv(&inv_affine(&planes)));
+}
+
+/// Substitutes the bytes of a word in the key expansion.
+fn sub_word(word: [u8; 4]) -> [u8; 4] {
+    let mut block = [0u8; BLOCK_SIZE];
+    block[..4].copy_from_slice(&word);
+    sub_bytes(&mut block);
+    [block[0], block[1], block[2], block[3]]
+}
+
+fn shift_rows(block: &mut [u8; BLOCK_SIZE]) {
+    let state = *block;
+    for row in 1..4 {
+        for column in 0..4 {
+            block[row + 4 * column] = state[row + 4 * ((column + row) % 4)];
+        }
+    }
+}
+
+fn inv_shift_rows(block: &mut [u8; BLOCK_SIZE]) {
+    let state = *block;
+    for row in 1..4 {
+        for column in 0..4 {
+            block[row + 4 * ((column + row) % 4)] = state[row + 4 * column];
+        }
+    }
+}
+
+fn mix_columns(block: &mut [u8; BLOCK_SIZE]) {
+    for column in block.chunks_exact_mut(4) {
+        let [a0, a1, a2, a3] = [column[0], column[1], column[2], column[3]];
+        let all = a0 ^ a1 ^ a2 ^ a3;
+        column[0] ^= all ^ xtime(a0 ^ a1);
+        column[1] ^= all ^ xtime(a1 ^ a2);
+        column[2] ^= all ^ xtime(a2 ^ a3);
+        column[3] ^= all ^ xtime(a3 ^ a0);
+    }
+}
+
+fn inv_mix_columns(block: &mut [u8; BLOCK_SIZE]) {
+    for column in block.chunks_exact_mut(4) {
+        let [a0, a1, a2, a3] = [column[0], column[1], column[2], column[3]];
+        column[0] = mul(a0, 14) ^ mul(a1, 11) ^ mul(a2, 13) ^ mul(a3, 9);
+        column[1] = mul(a0, 9) ^ mul(a1, 14) ^ mul(a2, 11) ^ mul(a3, 13);
+        column[2] = mul(a0, 13) ^ mul(a1, 9) ^ mul(a2, 14) ^ mul(a3, 11);
+        column[3] = mul(a0, 11) ^ mul(a1, 13) ^ mul(a2, 9) ^ mul(a3, 14);
+    }
+}
+
+/// Multiplies the element of GF(2^8) by `x`.
+const fn xtime(a: u8) -> u8 {
+    (a << 1) ^ ((a >> 7).wrapping_neg() & 0x1B)
+}
+
+/// Multiplies the element of GF(2^8) by a public constant `b`.
+const fn mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+        a = xtime(a);
+        b >>= 1;
+    }
+    product
+}
+
+// The S-box is computed rather than looked up in a table, so that the running
+// time and the memory accesses do not depend on the secret data. The 16 bytes
+// of a block are bitsliced into 8 planes, where bit `j` of `planes[i]` is bit
+// `i` of `block[j]`, and all the bytes are substituted at once with bitwise
+// operations.
+
+type Planes = [u16; 8];
+
+fn to_planes(block: &[u8; BLOCK_SIZE]) -> Planes {
+    let mut planes = [0u16; 8];
+    for (i, plane) in planes.iter_mut().enumerate() {
+        for (j, byte) in block.iter().enumerate() {
+            *plane |= (((byte >> i) & 1) as u16) << j;
+        }
+    }
+    planes
+}
+
+fn from_planes(planes: &Planes) -> [u8; BLOCK_SIZE] {
+    let mut block = [0u8; BLOCK_SIZE];
+    for (i, plane) in planes.iter().enumerate() {
+        for (j, byte) in block.iter_mut().enumerate() {
+            *byte |= (((plane >> j) & 1) as u8) << i;
+        }
+    }
+    block
+}
+
+/// Reduces the polynomial of degree up to 14 modulo `x^8 + x^4 + x^3 + x + 1`.
+fn reduce(mut product: [u16; 15]) -> Planes {
+    for k in (8..15).rev() {
+        let high = product[k];
+        product[k - 4] ^= high;
+        product[k - 5] ^= high;
+        product[k - 7] ^= high;
+        product[k - 8] ^= high;
+    }
+    let mut planes = [0u16; 8];
+    planes.copy_from_slice(&product[..8]);
+    planes
+}
+
+/// Multiplies the bitsliced elements of GF(2^8).
+fn gf_mul(a: &Planes, b: &Planes) -> Planes {
+    let mut product = [0u16; 15];
+    for (i, a) in a.iter().enumerate() {
+        for (j, b) in b.iter().enumerate() {
+            product[i + j] ^= a & b;
+        }
+    }
+    reduce(product)
+}
+
+/// Squares the bitsliced elements of GF(2^8).
+fn gf_square(a: &Planes) -> Planes {
+    let mut product = [0u16; 15];
+    for (i, a) in a.iter().enumerate() {
+        product[2 * i] = *a;
+    }
+    reduce(product)
+}
+
+/// Computes the multiplicative inverses of the bitsliced elements of GF(2^8)
+/// as `a^254`, where the inverse of zero is zero.
+fn gf_inv(a: &Planes) -> Planes {
+    let a2 = gf_square(a);
+    let a3 = gf_mul(&a2, a);
+    let a12 = gf_square(&gf_square(&a3));
+    let a14 = gf_mul(&a12, &a2);
+    let a15 = gf_mul(&a12, &a3);
+    let mut a240 = a15;
+    for _ in 0..4 {
+        a240 = gf_square(&a240);
+    }
+    gf_mul(&a240, &a14)
+}
+
+/// Applies the affine transformation of the S-box.
+fn affine(q: &Planes) -> Planes {
+    let mut planes = [0u16; 8];
+    for (i, plane) in planes.iter_mut().enumerate() {
+        *plane = q[i] ^ q[(i + 7) % 8] ^ q[(i + 6) % 8] ^ q[(i + 5) % 8] ^ q[(i + 4) % 8];
+        if (0x63 >> i) & 1 != 0 {
+            *plane = !*plane;
+        }
+    }
+    planes
+}
+
+/// Applies the inverse of the affine transformation of the S-box.
+fn inv_affine(q: &Planes) -> Planes {
+    let mut planes = [0u16; 8];
+    for (i, plane) in planes.iter_mut().enumerate() {
+        *plane = q[(i + 7) % 8] ^ q[(i + 5) % 8] ^ q[(i + 2) % 8];
+        if (0x05 >> i) & 1 != 0 {
+            *plane = !*plane;
+        }
+    }
+    planes
+}
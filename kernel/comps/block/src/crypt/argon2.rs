@@ -0,0 +1,430 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Argon2 of version 1.3 (RFC 9106), and BLAKE2b (RFC 7693) which it is based on.
+
+use crate::prelude::*;
+
+/// The variant of Argon2.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum Variant {
+    /// Argon2i, which accesses the memory independently of the password.
+    Argon2i = 1,
+    /// Argon2id, which accesses the memory independently of the password
+    /// in the first half of the first pass only.
+    Argon2id = 2,
+}
+
+/// The parameters of Argon2.
+#[derive(Clone, Copy, Debug)]
+pub(super) struct Params {
+    /// The number of passes
+    pub(super) time_cost: u32,
+    /// The size of the memory in KiB
+    pub(super) memory_cost: u32,
+    /// The number of lanes
+    pub(super) parallelism: u32,
+}
+
+const VERSION: u32 = 0x13;
+/// The number of 64-bit words in a 1 KiB block.
+const BLOCK_WORDS: usize = 128;
+/// The number of slices, i.e., synchronization points, of a pass.
+const SYNC_POINTS: u32 = 4;
+
+type Block = [u64; BLOCK_WORDS];
+
+/// Derives the key from the `password` and the `salt`, filling up the `output`.
+///
+/// The `secret` and the associated data are empty as cryptsetup uses.
+pub(super) fn argon2(
+    variant: Variant,
+    params: Params,
+    password: &[u8],
+    salt: &[u8],
+    output: &mut [u8],
+) -> ostd::Result<()> {
+    let Params {
+        time_cost,
+        memory_cost,
+        parallelism,
+    } = params;
+    if time_cost == 0 || parallelism == 0 || memory_cost < 8 * parallelism || output.len() < 4 {
+        return Err(ostd::Error::InvalidArgs);
+    }
+
+    let mut h0_input: Vec<u8> = Vec::new();
+    for value in [
+        parallelism,
+        output.len() as u32,
+        memory_cost,
+        time_cost,
+        VERSION,
+        variant as u32,
+    ] {
+        h0_input.extend_from_slice(&value.to_le_bytes());
+    }
+    for data in [password, salt, &[], &[]] {
+        h0_input.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        h0_input.extend_from_slice(data);
+    }
+    let h0 = blake2b(&h0_input, 64);
+
+    let lane_len = memory_cost / (SYNC_POINTS * parallelism) * SYNC_POINTS;
+    let nr_blocks = (lane_len * parallelism) as usize;
+    let mut memory: Vec<Block> = Vec::new();
+    memory
+        .try_reserve_exact(nr_blocks)
+        .map_err(|_| ostd::Error::NoMemory)?;
+    memory.resize(nr_blocks, [0; BLOCK_WORDS]);
+
+    let mut instance = Instance {
+        memory,
+        variant,
+        time_cost,
+        nr_blocks: nr_blocks as u32,
+        lane_len,
+        segment_len: lane_len / SYNC_POINTS,
+        nr_lanes: parallelism,
+    };
+
+    let mut input = [0u8; 72];
+    input[..64].copy_from_slice(&h0);
+    for lane in 0..parallelism {
+        input[68..72].copy_from_slice(&lane.to_le_bytes());
+        for column in 0..2u32 {
+            input[64..68].copy_from_slice(&column.to_le_bytes());
+            let bytes = blake2b_long(&input, 1024);
+            let block = instance.block_mut(lane, column);
+            for (word, bytes) in block.iter_mut().zip(bytes.chunks_exact(8)) {
+                *word = u64::from_le_bytes(bytes.try_into().unwrap());
+            }
+        }
+    }
+
+    for pass in 0..time_cost {
+        for slice in 0..SYNC_POINTS {
+            for lane in 0..parallelism {
+                instance.fill_segment(pass, slice, lane);
+            }
+        }
+    }
+
+    let mut last = [0u64; BLOCK_WORDS];
+    for lane in 0..parallelism {
+        xor_block(&mut last, instance.block(lane, lane_len - 1));
+    }
+    let bytes: Vec<u8> = last.iter().flat_map(|word| word.to_le_bytes()).collect();
+    output.copy_from_slice(&blake2b_long(&bytes, output.len()));
+    Ok(())
+}
+
+struct Instance {
+    memory: Vec<Block>,
+    variant: Variant,
+    time_cost: u32,
+    nr_blocks: u32,
+    lane_len: u32,
+    segment_len: u32,
+    nr_lanes: u32,
+}
+
+impl Instance {
+    fn block(&self, lane: u32, column: u32) -> &Block {
+        &self.memory[(lane * self.lane_len + column) as usize]
+    }
+
+    fn block_mut(&mut self, lane: u32, column: u32) -> &mut Block {
+        &mut self.memory[(lane * self.lane_len + column) as usize]
+    }
+
+    fn fill_segment(&mut self, pass: u32, slice: u32, lane: u32) {
+        let data_independent = match self.variant {
+            Variant::Argon2i => true,
+            Variant::Argon2id => pass == 0 && slice < SYNC_POINTS / 2,
+        };
+
+        let mut address_input = [0u64; BLOCK_WORDS];
+        let mut addresses = [0u64; BLOCK_WORDS];
+        if data_independent {
+            address_input[..6].copy_from_slice(&[
+                pass as u64,
+                lane as u64,
+                slice as u64,
+                self.nr_blocks as u64,
+                self.time_cost as u64,
+                self.variant as u64,
+            ]);
+        }
+
+        // The first two blocks of each lane are already filled.
+        let start = if pass == 0 && slice == 0 { 2 } else { 0 };
+        if data_independent && start != 0 {
+            next_addresses(&mut address_input, &mut addresses);
+        }
+
+        for index in start..self.segment_len {
+            let column = slice * self.segment_len + index;
+            let prev_column = if column == 0 {
+                self.lane_len - 1
+            } else {
+                column - 1
+            };
+
+            let pseudo_random = if data_independent {
+                if index as usize % BLOCK_WORDS == 0 {
+                    next_addresses(&mut address_input, &mut addresses);
+                }
+                addresses[index as usize % BLOCK_WORDS]
+            } else {
+                self.block(lane, prev_column)[0]
+            };
+
+            let ref_lane = if pass == 0 && slice == 0 {
+                lane
+            } else {
+                ((pseudo_random >> 32) % self.nr_lanes as u64) as u32
+            };
+            let ref_column = self.ref_column(pass, slice, index, ref_lane == lane, pseudo_random);
+
+            let mut block = *self.block(lane, prev_column);
+            xor_block(&mut block, self.block(ref_lane, ref_column));
+            let mut result = block;
+            permute(&mut block);
+            xor_block(&mut result, &block);
+            // Since version 1.3, the blocks are XORed with their old values after the first pass.
+            let target = self.block_mut(lane, column);
+            if pass == 0 {
+                *target = result;
+            } else {
+                xor_block(target, &result);
+            }
+        }
+    }
+
+    /// Maps the pseudo-random value to the column of the reference block.
+    fn ref_column(
+        &self,
+        pass: u32,
+        slice: u32,
+        index: u32,
+        is_same_lane: bool,
+        pseudo_random: u64,
+    ) -> u32 {
+        // The blocks that can be referenced are those of the finished slices,
+        // and those already filled in the same segment if in the same lane.
+        let finished = if pass == 0 {
+            slice * self.segment_len
+        } else {
+            self.lane_len - self.segment_len
+        };
+        let area_size = if is_same_lane {
+            finished + index - 1
+        } else if index == 0 {
+            finished - 1
+        } else {
+            finished
+        };
+
+        let j1 = pseudo_random & 0xFFFF_FFFF;
+        let x = (j1 * j1) >> 32;
+        let y = (area_size as u64 * x) >> 32;
+        let relative = area_size as u64 - 1 - y;
+
+        let start = if pass == 0 || slice == SYNC_POINTS - 1 {
+            0
+        } else {
+            (slice + 1) * self.segment_len
+        };
+        ((start as u64 + relative) % self.lane_len as u64) as u32
+    }
+}
+
+/// Generates the next block of the pseudo-random values for the data-independent addressing.
+fn next_addresses(input: &mut Block, addresses: &mut Block) {
+    input[6] += 1;
+    let zero = [0u64; BLOCK_WORDS];
+    *addresses = compress(&zero, input);
+    *addresses = compress(&zero, addresses);
+}
+
+/// The compression function `G` of Argon2.
+fn compress(x: &Block, y: &Block) -> Block {
+    let mut r = *x;
+    xor_block(&mut r, y);
+    let mut z = r;
+    permute(&mut z);
+    xor_block(&mut z, &r);
+    z
+}
+
+fn xor_block(block: &mut Block, other: &Block) {
+    for (word, other_word) in block.iter_mut().zip(other) {
+        *word ^= other_word;
+    }
+}
+
+/// Applies the permutation `P` to the rows and then the columns of the block,
+/// which is regarded as an 8x8 matrix of 16-byte registers.
+fn permute(block: &mut Block) {
+    for row in 0..8 {
+        let mut v: [u64; 16] = block[row * 16..row * 16 + 16].try_into().unwrap();
+        round_no_msg(&mut v);
+        block[row * 16..row * 16 + 16].copy_from_slice(&v);
+    }
+    for column in 0..8 {
+        let mut v = [0u64; 16];
+        for row in 0..8 {
+            v[row * 2] = block[row * 16 + column * 2];
+            v[row * 2 + 1] = block[row * 16 + column * 2 + 1];
+        }
+        round_no_msg(&mut v);
+        for row in 0..8 {
+            block[row * 16 + column * 2] = v[row * 2];
+            block[row * 16 + column * 2 + 1] = v[row * 2 + 1];
+        }
+    }
+}
+
+fn round_no_msg(v: &mut [u64; 16]) {
+    for [a, b, c, d] in DIAGONALS {
+        let (va, vb, vc, vd) = gb(v[a], v[b], v[c], v[d]);
+        v[a] = va;
+        v[b] = vb;
+        v[c] = vc;
+        v[d] = vd;
+    }
+}
+
+/// The mixing function of Argon2, which is that of BLAKE2b with the additions
+/// replaced by the multiplication-hardened ones.
+fn gb(mut a: u64, mut b: u64, mut c: u64, mut d: u64) -> (u64, u64, u64, u64) {
+    fn add(x: u64, y: u64) -> u64 {
+        let product = (x & 0xFFFF_FFFF).wrapping_mul(y & 0xFFFF_FFFF);
+        x.wrapping_add(y).wrapping_add(product.wrapping_mul(2))
+    }
+    a = add(a, b);
+    d = (d ^ a).rotate_right(32);
+    c = add(c, d);
+    b = (b ^ c).rotate_right(24);
+    a = add(a, b);
+    d = (d ^ a).rotate_right(16);
+    c = add(c, d);
+    b = (b ^ c).rotate_right(63);
+    (a, b, c, d)
+}
+
+/// The indexes of the words mixed in a round of BLAKE2b, i.e., the columns and then the diagonals.
+const DIAGONALS: [[usize; 4]; 8] = [
+    [0, 4, 8, 12],
+    [1, 5, 9, 13],
+    [2, 6, 10, 14],
+    [3, 7, 11, 15],
+    [0, 5, 10, 15],
+    [1, 6, 11, 12],
+    [2, 7, 8, 13],
+    [3, 4, 9, 14],
+];
+
+const BLAKE2B_IV: [u64; 8] = [
+    0x6a09e667f3bcc908,
+    0xbb67ae8584caa73b,
+    0x3c6ef372fe94f82b,
+    0xa54ff53a5f1d36f1,
+    0x510e527fade682d1,
+    0x9b05688c2b3e6c1f,
+    0x1f83d9abfb41bd6b,
+    0x5be0cd19137e2179,
+];
+
+const SIGMA: [[usize; 16]; 10] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+    [11, 8, 12, 0, 5, 2, 15, 13, 10, 14, 3, 6, 7, 1, 9, 4],
+    [7, 9, 3, 1, 13, 12, 11, 14, 2, 6, 5, 10, 4, 0, 15, 8],
+    [9, 0, 5, 7, 2, 4, 10, 15, 14, 1, 11, 12, 6, 8, 3, 13],
+    [2, 12, 6, 10, 0, 11, 8, 3, 4, 13, 7, 5, 15, 14, 1, 9],
+    [12, 5, 1, 15, 14, 13, 4, 10, 0, 7, 6, 3, 9, 2, 8, 11],
+    [13, 11, 7, 14, 12, 1, 3, 9, 5, 0, 15, 4, 8, 6, 2, 10],
+    [6, 15, 14, 9, 11, 3, 0, 8, 12, 2, 13, 7, 1, 4, 10, 5],
+    [10, 2, 8, 4, 7, 6, 1, 5, 15, 11, 9, 14, 3, 12, 13, 0],
+];
+
+/// Computes the unkeyed BLAKE2b digest of `output_len` (at most 64) bytes.
+pub(super) fn blake2b(data: &[u8], output_len: usize) -> Vec<u8> {
+    debug_assert!((1..=64).contains(&output_len));
+
+    let mut h = BLAKE2B_IV;
+    h[0] ^= 0x0101_0000 ^ output_len as u64;
+
+    let nr_blocks = data.len().div_ceil(128).max(1);
+    for index in 0..nr_blocks {
+        let chunk = &data[index * 128..data.len().min(index * 128 + 128)];
+        let mut block = [0u8; 128];
+        block[..chunk.len()].copy_from_slice(chunk);
+        let mut m = [0u64; 16];
+        for (word, bytes) in m.iter_mut().zip(block.chunks_exact(8)) {
+            *word = u64::from_le_bytes(bytes.try_into().unwrap());
+        }
+        let counter = (index * 128 + chunk.len()) as u128;
+        blake2b_compress(&mut h, &m, counter, index == nr_blocks - 1);
+    }
+
+    let mut digest: Vec<u8> = h.iter().flat_map(|word| word.to_le_bytes()).collect();
+    digest.truncate(output_len);
+    digest
+}
+
+fn blake2b_compress(h: &mut [u64; 8], m: &[u64; 16], counter: u128, is_last: bool) {
+    let mut v = [0u64; 16];
+    v[..8].copy_from_slice(h);
+    v[8..].copy_from_slice(&BLAKE2B_IV);
+    v[12] ^= counter as u64;
+    v[13] ^= (counter >> 64) as u64;
+    if is_last {
+        v[14] = !v[14];
+    }
+
+    for round in 0..12 {
+        let s = &SIGMA[round % 10];
+        for (i, [a, b, c, d]) in DIAGONALS.into_iter().enumerate() {
+            let (x, y) = (m[s[2 * i]], m[s[2 * i + 1]]);
+            v[a] = v[a].wrapping_add(v[b]).wrapping_add(x);
+            v[d] = (v[d] ^ v[a]).rotate_right(32);
+            v[c] = v[c].wrapping_add(v[d]);
+            v[b] = (v[b] ^ v[c]).rotate_right(24);
+            v[a] = v[a].wrapping_add(v[b]).wrapping_add(y);
+            v[d] = (v[d] ^ v[a]).rotate_right(16);
+            v[c] = v[c].wrapping_add(v[d]);
+            v[b] = (v[b] ^ v[c]).rotate_right(63);
+        }
+    }
+
+    for i in 0..8 {
+        h[i] ^= v[i] ^ v[i + 8];
+    }
+}
+
+/// The variable-length hash function `H'` of Argon2.
+fn blake2b_long(data: &[u8], output_len: usize) -> Vec<u8> {
+    let mut input = Vec::with_capacity(4 + data.len());
+    input.extend_from_slice(&(output_len as u32).to_le_bytes());
+    input.extend_from_slice(data);
+    if output_len <= 64 {
+        return blake2b(&input, output_len);
+    }
+
+    // The first 32 bytes of each intermediate digest are output,
+    // and the last digest is output in whole.
+    let mut output = Vec::with_capacity(output_len);
+    let mut v = blake2b(&input, 64);
+    loop {
+        output.extend_from_slice(&v[..32]);
+        let remaining = output_len - output.len();
+        v = blake2b(&v, remaining.min(64));
+        if remaining <= 64 {
+            break;
+        }
+    }
+    output.extend_from_slice(&v);
+    output
+}
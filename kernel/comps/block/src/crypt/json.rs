@@ -0,0 +1,222 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! A minimal JSON parser for the metadata of LUKS2.
+
+use crate::prelude::*;
+
+/// A JSON value.
+///
+/// The numbers are kept as their text, since LUKS2 only uses integers,
+/// some of which are 64-bit and thus stored as strings instead.
+#[derive(Debug)]
+pub(super) enum Json {
+    Null,
+    Bool(bool),
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+/// The maximum depth of the nested arrays and objects.
+const MAX_DEPTH: usize = 32;
+
+impl Json {
+    /// Parses the text, returning `None` if it is not a valid JSON value.
+    pub(super) fn parse(text: &str) -> Option<Self> {
+        let mut parser = Parser {
+            bytes: text.as_bytes(),
+            pos: 0,
+        };
+        let value = parser.parse_value(0)?;
+        parser.skip_whitespace();
+        (parser.pos == parser.bytes.len()).then_some(value)
+    }
+
+    /// Returns the member of the object with the `key`.
+    pub(super) fn get(&self, key: &str) -> Option<&Json> {
+        self.as_object()?
+            .iter()
+            .find(|(member_key, _)| member_key == key)
+            .map(|(_, value)| value)
+    }
+
+    pub(super) fn as_object(&self) -> Option<&[(String, Json)]> {
+        match self {
+            Json::Object(members) => Some(members),
+            _ => None,
+        }
+    }
+
+    pub(super) fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(elements) => Some(elements),
+            _ => None,
+        }
+    }
+
+    pub(super) fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    /// Returns the unsigned integer of either a number or a string of digits.
+    pub(super) fn as_u64(&self) -> Option<u64> {
+        match self {
+            Json::Number(text) | Json::String(text) => text.parse().ok(),
+            _ => None,
+        }
+    }
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn parse_value(&mut self, depth: usize) -> Option<Json> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        self.skip_whitespace();
+        match *self.bytes.get(self.pos)? {
+            b'{' => self.parse_object(depth),
+            b'[' => self.parse_array(depth),
+            b'"' => self.parse_string().map(Json::String),
+            b'-' | b'0'..=b'9' => self.parse_number(),
+            b't' => self.parse_literal("true", Json::Bool(true)),
+            b'f' => self.parse_literal("false", Json::Bool(false)),
+            b'n' => self.parse_literal("null", Json::Null),
+            _ => None,
+        }
+    }
+
+    fn parse_object(&mut self, depth: usize) -> Option<Json> {
+        self.pos += 1;
+        let mut members = Vec::new();
+        self.skip_whitespace();
+        if self.eat(b'}') {
+            return Some(Json::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.bytes.get(self.pos) != Some(&b'"') {
+                return None;
+            }
+            let key = self.parse_string()?;
+            self.skip_whitespace();
+            if !self.eat(b':') {
+                return None;
+            }
+            let value = self.parse_value(depth + 1)?;
+            members.push((key, value));
+            self.skip_whitespace();
+            if self.eat(b'}') {
+                return Some(Json::Object(members));
+            }
+            if !self.eat(b',') {
+                return None;
+            }
+        }
+    }
+
+    fn parse_array(&mut self, depth: usize) -> Option<Json> {
+        self.pos += 1;
+        let mut elements = Vec::new();
+        self.skip_whitespace();
+        if self.eat(b']') {
+            return Some(Json::Array(elements));
+        }
+        loop {
+            elements.push(self.parse_value(depth + 1)?);
+            self.skip_whitespace();
+            if self.eat(b']') {
+                return Some(Json::Array(elements));
+            }
+            if !self.eat(b',') {
+                return None;
+            }
+        }
+    }
+
+    fn parse_string(&mut self) -> Option<String> {
+        self.pos += 1;
+        let mut string = String::new();
+        loop {
+            let start = self.pos;
+            while !matches!(*self.bytes.get(self.pos)?, b'"' | b'\\') {
+                self.pos += 1;
+            }
+            string.push_str(core::str::from_utf8(&self.bytes[start..self.pos]).ok()?);
+            if self.eat(b'"') {
+                return Some(string);
+            }
+
+            self.pos += 1;
+            let escaped = *self.bytes.get(self.pos)?;
+            self.pos += 1;
+            let c = match escaped {
+                b'"' => '"',
+                b'\\' => '\\',
+                b'/' => '/',
+                b'b' => '\u{8}',
+                b'f' => '\u{c}',
+                b'n' => '\n',
+                b'r' => '\r',
+                b't' => '\t',
+                b'u' => {
+                    let hex = self.bytes.get(self.pos..self.pos + 4)?;
+                    self.pos += 4;
+                    let code = u32::from_str_radix(core::str::from_utf8(hex).ok()?, 16).ok()?;
+                    // The surrogate pairs are not expected in LUKS2 metadata.
+                    char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER)
+                }
+                _ => return None,
+            };
+            string.push(c);
+        }
+    }
+
+    fn parse_number(&mut self) -> Option<Json> {
+        let start = self.pos;
+        while self
+            .bytes
+            .get(self.pos)
+            .is_some_and(|byte| matches!(byte, b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9'))
+        {
+            self.pos += 1;
+        }
+        let text = core::str::from_utf8(&self.bytes[start..self.pos]).ok()?;
+        Some(Json::Number(text.into()))
+    }
+
+    fn parse_literal(&mut self, literal: &str, value: Json) -> Option<Json> {
+        if !self.bytes[self.pos..].starts_with(literal.as_bytes()) {
+            return None;
+        }
+        self.pos += literal.len();
+        Some(value)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self
+            .bytes
+            .get(self.pos)
+            .is_some_and(|byte| byte.is_ascii_whitespace())
+        {
+            self.pos += 1;
+        }
+    }
+
+    fn eat(&mut self, byte: u8) -> bool {
+        if self.bytes.get(self.pos) == Some(&byte) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+}
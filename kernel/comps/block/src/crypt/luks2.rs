@@ -0,0 +1,435 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The LUKS2 on-disk format, which is compatible with cryptsetup.
+//!
+//! A LUKS2 header consists of a 4 KiB binary header followed by the JSON metadata,
+//! and there are two copies of it for redundancy. The metadata describe the keyslots,
+//! each of which stores the volume key encrypted by a key derived from a passphrase.
+//! The volume key is verified by the digests, and it encrypts the data segment.
+
+use ostd::mm::VmIo;
+
+use super::{
+    argon2::{self, Variant},
+    json::Json,
+    parse_cipher,
+    sha256::{pbkdf2_sha256, sha256, DIGEST_SIZE},
+    CryptConfig, SectorCipher, MAX_SECTOR_SIZE,
+};
+use crate::{id::Sid, prelude::*, BlockDevice, SECTOR_SIZE};
+
+const PRIMARY_MAGIC: &[u8; 6] = b"LUKS\xba\xbe";
+const SECONDARY_MAGIC: &[u8; 6] = b"SKUL\xba\xbe";
+const VERSION: u16 = 2;
+/// The size of the binary header.
+const BINARY_SIZE: usize = 4096;
+/// The possible offsets of the secondary header, which are the possible header sizes.
+const SECONDARY_OFFSETS: [usize; 9] = [
+    0x4000, 0x8000, 0x10000, 0x20000, 0x40000, 0x80000, 0x100000, 0x200000, 0x400000,
+];
+
+const VERSION_OFFSET: usize = 6;
+const HEADER_SIZE_OFFSET: usize = 8;
+const SEQID_OFFSET: usize = 16;
+const CHECKSUM_ALG_OFFSET: usize = 72;
+const HEADER_OFFSET_OFFSET: usize = 256;
+const CHECKSUM_OFFSET: usize = 448;
+const CHECKSUM_LEN: usize = 64;
+
+/// The maximum memory cost of Argon2 in KiB, which is the limit of cryptsetup.
+const MAX_ARGON2_MEMORY: u32 = 4 * 1024 * 1024;
+
+/// The LUKS2 header, i.e., the JSON metadata of the newer valid copy.
+pub(super) struct Header {
+    metadata: Json,
+}
+
+impl Header {
+    /// Reads the header from the `device`.
+    ///
+    /// Returns `InvalidArgs` if neither copy of the header is valid.
+    pub(super) fn read(device: &dyn BlockDevice) -> ostd::Result<Self> {
+        let primary = read_copy(device, 0, PRIMARY_MAGIC)?;
+        let secondary_offsets = match &primary {
+            Some((header_size, _, _)) => vec![*header_size],
+            None => SECONDARY_OFFSETS.to_vec(),
+        };
+        let mut secondary = None;
+        for offset in secondary_offsets {
+            secondary = read_copy(device, offset, SECONDARY_MAGIC)?;
+            if secondary.is_some() {
+                break;
+            }
+        }
+
+        let newer = match (primary, secondary) {
+            (Some(primary), Some(secondary)) if secondary.1 > primary.1 => secondary,
+            (Some(primary), _) => primary,
+            (None, Some(secondary)) => secondary,
+            (None, None) => return Err(ostd::Error::InvalidArgs),
+        };
+        Ok(Self { metadata: newer.2 })
+    }
+
+    /// Unlocks a keyslot with the `passphrase`.
+    ///
+    /// Returns the configuration of the data segment and the volume key.
+    pub(super) fn unlock(
+        &self,
+        device: &dyn BlockDevice,
+        passphrase: &[u8],
+    ) -> ostd::Result<(CryptConfig, Vec<u8>)> {
+        let invalid = || {
+            log::warn!("luks2: invalid or unsupported metadata");
+            ostd::Error::InvalidArgs
+        };
+        let metadata = &self.metadata;
+        let requirements = metadata
+            .get("config")
+            .and_then(|config| config.get("requirements"))
+            .and_then(|requirements| requirements.get("mandatory"))
+            .and_then(|mandatory| mandatory.as_array());
+        if requirements.is_some_and(|requirements| !requirements.is_empty()) {
+            // E.g., the volume is being reencrypted.
+            return Err(invalid());
+        }
+
+        let segments = metadata
+            .get("segments")
+            .and_then(|segments| segments.as_object())
+            .ok_or_else(invalid)?;
+        let [(segment_id, segment)] = segments else {
+            return Err(invalid());
+        };
+        let config = segment_config(segment, device).ok_or_else(invalid)?;
+
+        let digests = metadata
+            .get("digests")
+            .and_then(|digests| digests.as_object())
+            .ok_or_else(invalid)?;
+        let digest = digests
+            .iter()
+            .map(|(_, digest)| digest)
+            .find(|digest| contains_id(digest.get("segments"), segment_id))
+            .ok_or_else(invalid)?;
+
+        let keyslots = metadata
+            .get("keyslots")
+            .and_then(|keyslots| keyslots.as_object())
+            .ok_or_else(invalid)?;
+        // The keyslots of higher priorities are tried first, and those of priority 0 are ignored.
+        let mut candidates: Vec<(u64, &Json)> = keyslots
+            .iter()
+            .filter(|(id, _)| contains_id(digest.get("keyslots"), id))
+            .map(|(_, keyslot)| {
+                let priority = keyslot.get("priority").and_then(|p| p.as_u64());
+                (priority.unwrap_or(1), keyslot)
+            })
+            .filter(|(priority, _)| *priority > 0)
+            .collect();
+        candidates.sort_by_key(|(priority, _)| core::cmp::Reverse(*priority));
+
+        for (_, keyslot) in candidates {
+            let key = match unlock_keyslot(keyslot, device, passphrase) {
+                Ok(key) => key,
+                Err(e) => {
+                    log::warn!("luks2: failed to open a keyslot: {:?}", e);
+                    continue;
+                }
+            };
+            if verify_digest(digest, &key).ok_or_else(invalid)? {
+                return Ok((config, key));
+            }
+        }
+        Err(ostd::Error::AccessDenied)
+    }
+}
+
+/// Reads a copy of the header at the `offset`, returning its size, sequence ID, and metadata.
+///
+/// Returns `None` if the copy is invalid.
+fn read_copy(
+    device: &dyn BlockDevice,
+    offset: usize,
+    magic: &[u8; 6],
+) -> ostd::Result<Option<(usize, u64, Json)>> {
+    let device_size = device.nr_sectors() * SECTOR_SIZE;
+    if device_size < offset + BINARY_SIZE {
+        return Ok(None);
+    }
+    let mut binary = vec![0u8; BINARY_SIZE];
+    device.read_bytes(offset, &mut binary)?;
+    if &binary[..6] != magic
+        || read_u16(&binary, VERSION_OFFSET) != VERSION
+        || read_u64(&binary, HEADER_OFFSET_OFFSET) != offset as u64
+    {
+        return Ok(None);
+    }
+    let header_size = read_u64(&binary, HEADER_SIZE_OFFSET) as usize;
+    if !SECONDARY_OFFSETS.contains(&header_size) || device_size < offset + header_size {
+        return Ok(None);
+    }
+    let checksum_alg = &binary[CHECKSUM_ALG_OFFSET..CHECKSUM_ALG_OFFSET + 7];
+    if checksum_alg != b"sha256\0" {
+        log::warn!("luks2: unsupported checksum algorithm");
+        return Ok(None);
+    }
+
+    let mut bytes = binary;
+    bytes.resize(header_size, 0);
+    device.read_bytes(offset + BINARY_SIZE, &mut bytes[BINARY_SIZE..])?;
+    // The checksum covers the whole header with the checksum field zeroed.
+    let mut checksum = [0u8; CHECKSUM_LEN];
+    checksum.copy_from_slice(&bytes[CHECKSUM_OFFSET..CHECKSUM_OFFSET + CHECKSUM_LEN]);
+    bytes[CHECKSUM_OFFSET..CHECKSUM_OFFSET + CHECKSUM_LEN].fill(0);
+    if sha256(&[&bytes]) != checksum[..DIGEST_SIZE] {
+        log::warn!("luks2: the header at {:#x} has a wrong checksum", offset);
+        return Ok(None);
+    }
+
+    let json_area = &bytes[BINARY_SIZE..];
+    let json_len = json_area
+        .iter()
+        .position(|byte| *byte == 0)
+        .unwrap_or(json_area.len());
+    let Some(metadata) = core::str::from_utf8(&json_area[..json_len])
+        .ok()
+        .and_then(Json::parse)
+    else {
+        log::warn!("luks2: the metadata at {:#x} are malformed", offset);
+        return Ok(None);
+    };
+    let seqid = read_u64(&bytes, SEQID_OFFSET);
+    Ok(Some((header_size, seqid, metadata)))
+}
+
+/// Returns the configuration of the data segment.
+fn segment_config(segment: &Json, device: &dyn BlockDevice) -> Option<CryptConfig> {
+    if segment.get("type")?.as_str()? != "crypt" || segment.get("integrity").is_some() {
+        return None;
+    }
+    let offset = segment.get("offset")?.as_u64()?;
+    let sector_size = segment.get("sector_size")?.as_u64()? as usize;
+    if !sector_size.is_power_of_two() || !(SECTOR_SIZE..=MAX_SECTOR_SIZE).contains(&sector_size) {
+        return None;
+    }
+    let start = offset / SECTOR_SIZE as u64;
+    let nr_sectors = match segment.get("size")?.as_str() {
+        // The segment extends to the end of the device.
+        Some("dynamic") => {
+            let sectors_per_unit = (sector_size / SECTOR_SIZE) as u64;
+            let nr_sectors = (device.nr_sectors() as u64).checked_sub(start)?;
+            nr_sectors / sectors_per_unit * sectors_per_unit
+        }
+        _ => segment.get("size")?.as_u64()? / SECTOR_SIZE as u64,
+    };
+    Some(CryptConfig {
+        cipher: segment.get("encryption")?.as_str()?.into(),
+        start_sid: Sid::new(start),
+        nr_sectors: nr_sectors as usize,
+        iv_offset: segment.get("iv_tweak")?.as_u64()?,
+        sector_size,
+        // The IVs of LUKS2 are always generated from the numbers of the encryption sectors.
+        iv_large_sectors: sector_size > SECTOR_SIZE,
+        allow_discards: false,
+    })
+}
+
+/// Derives the key from the passphrase and decrypts the volume key in the keyslot.
+fn unlock_keyslot(
+    keyslot: &Json,
+    device: &dyn BlockDevice,
+    passphrase: &[u8],
+) -> ostd::Result<Vec<u8>> {
+    let unsupported = ostd::Error::InvalidArgs;
+    if keyslot.get("type").and_then(|type_| type_.as_str()) != Some("luks2") {
+        return Err(unsupported);
+    }
+    let key_size = json_usize(keyslot.get("key_size")).ok_or(unsupported)?;
+
+    let area = keyslot.get("area").ok_or(unsupported)?;
+    if area.get("type").and_then(|type_| type_.as_str()) != Some("raw") {
+        return Err(unsupported);
+    }
+    let area_offset = json_usize(area.get("offset")).ok_or(unsupported)?;
+    let area_size = json_usize(area.get("size")).ok_or(unsupported)?;
+    let area_key_size = json_usize(area.get("key_size")).ok_or(unsupported)?;
+    let area_cipher = area
+        .get("encryption")
+        .and_then(|encryption| encryption.as_str())
+        .ok_or(unsupported)?;
+
+    let af = keyslot.get("af").ok_or(unsupported)?;
+    if af.get("type").and_then(|type_| type_.as_str()) != Some("luks1")
+        || af.get("hash").and_then(|hash| hash.as_str()) != Some("sha256")
+    {
+        return Err(unsupported);
+    }
+    let stripes = json_usize(af.get("stripes")).ok_or(unsupported)?;
+    let af_size = key_size
+        .checked_mul(stripes)
+        .filter(|size| *size > 0)
+        .ok_or(unsupported)?
+        .next_multiple_of(SECTOR_SIZE);
+    if af_size > area_size || area_key_size > 64 {
+        return Err(unsupported);
+    }
+
+    let mut area_key = vec![0u8; area_key_size];
+    derive_key(
+        keyslot.get("kdf").ok_or(unsupported)?,
+        passphrase,
+        &mut area_key,
+    )?;
+
+    let mut af_data = vec![0u8; af_size];
+    device.read_bytes(area_offset, &mut af_data)?;
+    let (xts, iv_mode) = parse_cipher(area_cipher, &area_key).ok_or(unsupported)?;
+    area_key.fill(0);
+    let cipher = SectorCipher {
+        xts,
+        iv_mode,
+        sector_size: SECTOR_SIZE,
+        iv_shift: 0,
+        iv_offset: 0,
+    };
+    for (index, sector) in af_data.chunks_exact_mut(SECTOR_SIZE).enumerate() {
+        let iv = cipher.iv(index as u64);
+        cipher.xts.decrypt(&iv, sector);
+    }
+
+    let key = af_merge(&af_data[..key_size * stripes], key_size, stripes);
+    af_data.fill(0);
+    Ok(key)
+}
+
+/// Derives the key of the keyslot area from the passphrase with the KDF.
+fn derive_key(kdf: &Json, passphrase: &[u8], key: &mut [u8]) -> ostd::Result<()> {
+    let unsupported = ostd::Error::InvalidArgs;
+    let salt = kdf
+        .get("salt")
+        .and_then(|salt| salt.as_str())
+        .and_then(base64_decode)
+        .ok_or(unsupported)?;
+    let kdf_u32 = |name| {
+        kdf.get(name)
+            .and_then(|value| value.as_u64())
+            .and_then(|value| u32::try_from(value).ok())
+            .ok_or(unsupported)
+    };
+
+    match kdf.get("type").and_then(|type_| type_.as_str()) {
+        Some("pbkdf2") => {
+            if kdf.get("hash").and_then(|hash| hash.as_str()) != Some("sha256") {
+                return Err(unsupported);
+            }
+            pbkdf2_sha256(passphrase, &salt, kdf_u32("iterations")?, key);
+            Ok(())
+        }
+        Some(type_ @ ("argon2i" | "argon2id")) => {
+            let variant = if type_ == "argon2i" {
+                Variant::Argon2i
+            } else {
+                Variant::Argon2id
+            };
+            let params = argon2::Params {
+                time_cost: kdf_u32("time")?,
+                memory_cost: kdf_u32("memory")?,
+                parallelism: kdf_u32("cpus")?,
+            };
+            if params.memory_cost > MAX_ARGON2_MEMORY {
+                return Err(unsupported);
+            }
+            argon2::argon2(variant, params, passphrase, &salt, key)
+        }
+        _ => Err(unsupported),
+    }
+}
+
+/// Merges the anti-forensic stripes into the key, see the LUKS1 specification.
+///
+/// Each stripe but the last one is XORed into the buffer, which is then diffused
+/// by the hash. The key is the last stripe XORed with the buffer.
+fn af_merge(stripes_data: &[u8], key_size: usize, stripes: usize) -> Vec<u8> {
+    let mut buf = vec![0u8; key_size];
+    for (index, stripe) in stripes_data.chunks_exact(key_size).enumerate() {
+        for (byte, stripe_byte) in buf.iter_mut().zip(stripe) {
+            *byte ^= stripe_byte;
+        }
+        if index + 1 < stripes {
+            diffuse(&mut buf);
+        }
+    }
+    buf
+}
+
+fn diffuse(buf: &mut [u8]) {
+    for (index, chunk) in buf.chunks_mut(DIGEST_SIZE).enumerate() {
+        let digest = sha256(&[&(index as u32).to_be_bytes(), chunk]);
+        let len = chunk.len();
+        chunk.copy_from_slice(&digest[..len]);
+    }
+}
+
+/// Verifies the volume key with the digest.
+///
+/// Returns `None` if the digest is not supported.
+fn verify_digest(digest: &Json, key: &[u8]) -> Option<bool> {
+    if digest.get("type")?.as_str()? != "pbkdf2" || digest.get("hash")?.as_str()? != "sha256" {
+        return None;
+    }
+    let iterations = u32::try_from(digest.get("iterations")?.as_u64()?).ok()?;
+    let salt = base64_decode(digest.get("salt")?.as_str()?)?;
+    let expected = base64_decode(digest.get("digest")?.as_str()?)?;
+    let mut computed = vec![0u8; expected.len()];
+    pbkdf2_sha256(key, &salt, iterations, &mut computed);
+    Some(computed == expected)
+}
+
+/// Returns whether the array of IDs contains the `id`.
+fn contains_id(ids: Option<&Json>, id: &str) -> bool {
+    ids.and_then(|ids| ids.as_array())
+        .is_some_and(|ids| ids.iter().any(|value| value.as_str() == Some(id)))
+}
+
+fn json_usize(value: Option<&Json>) -> Option<usize> {
+    usize::try_from(value?.as_u64()?).ok()
+}
+
+/// Decodes the standard base64 with padding.
+fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    fn value(c: u8) -> Option<u32> {
+        Some(match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        } as u32)
+    }
+
+    let text = text.trim_end_matches('=').as_bytes();
+    let mut bytes = Vec::with_capacity(text.len() * 3 / 4);
+    for chunk in text.chunks(4) {
+        if chunk.len() == 1 {
+            return None;
+        }
+        let mut bits = 0u32;
+        for c in chunk {
+            bits = (bits << 6) | value(*c)?;
+        }
+        bits <<= 6 * (4 - chunk.len()) as u32;
+        let decoded = bits.to_be_bytes();
+        bytes.extend_from_slice(&decoded[1..chunk.len()]);
+    }
+    Some(bytes)
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes(bytes[offset..offset + 2].try_into().unwrap())
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_be_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
@@ -0,0 +1,557 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The encrypted block devices, i.e., the crypt targets of the device mapper.
+//!
+//! A `CryptDevice` stores its sectors encrypted on a range of the underlying device,
+//! so that the data are only accessible with the key. The sectors are encrypted with
+//! AES-XTS, whose tweaks are derived from the sector numbers as dm-crypt in Linux does.
+//! Thus the devices set up by the same table are interchangeable with Linux.
+//!
+//! The key and the geometry of the device can also be read from the LUKS2 header
+//! on the underlying device, which is unlocked by a passphrase, see `CryptDevice::open_luks2`.
+//! So the volumes formatted by `cryptsetup luksFormat` can be opened.
+//!
+//! The writes are encrypted into the bounce buffers before being submitted to
+//! the underlying device, while the reads are decrypted in place after they complete.
+//! The decryption is done by a worker task rather than in the interrupt context
+//! where the underlying device completes the reads.
+
+mod aes;
+mod argon2;
+mod json;
+mod luks2;
+mod sha256;
+mod xts;
+
+use ostd::{
+    mm::{FrameAllocOptions, VmIo, PAGE_SIZE},
+    sync::{SpinLock, WaitQueue},
+    task::TaskOptions,
+};
+use spin::Once;
+
+use self::xts::Xts;
+use super::{
+    bio::{Bio, BioEnqueueError, BioSegment, BioStatus, BioType, SubmittedBio},
+    id::Sid,
+    BlockDevice, SECTOR_SIZE,
+};
+use crate::prelude::*;
+
+/// The configuration of an encrypted device, which is the table of a crypt target.
+#[derive(Clone, Debug)]
+pub struct CryptConfig {
+    /// The cipher in the form of `<cipher>-<mode>-<iv>`, e.g., `aes-xts-plain64`
+    pub cipher: String,
+    /// The first sector of the encrypted data on the underlying device
+    pub start_sid: Sid,
+    /// The number of sectors of the encrypted device
+    pub nr_sectors: usize,
+    /// The offset (in sectors) added to the sector numbers to generate the IVs
+    pub iv_offset: u64,
+    /// The size in bytes of the sectors that are encrypted as a whole,
+    /// which is a power of two from 512 to 4096
+    pub sector_size: usize,
+    /// Whether the IVs are generated from the numbers of the encryption sectors
+    /// instead of the 512-byte sectors
+    pub iv_large_sectors: bool,
+    /// Whether the discards are passed to the underlying device, which reveals
+    /// the unused sectors
+    pub allow_discards: bool,
+}
+
+/// An encrypted block device.
+#[derive(Debug)]
+pub struct CryptDevice {
+    device: Arc<dyn BlockDevice>,
+    cipher: Arc<SectorCipher>,
+    config: CryptConfig,
+}
+
+impl CryptDevice {
+    /// Creates an encrypted device on the `device` with the `key`.
+    ///
+    /// The key consists of the two AES keys of XTS, so it is 32 or 64 bytes long.
+    pub fn new(
+        device: Arc<dyn BlockDevice>,
+        config: CryptConfig,
+        key: &[u8],
+    ) -> ostd::Result<Self> {
+        let sector_size = config.sector_size;
+        if !sector_size.is_power_of_two()
+            || !(SECTOR_SIZE..=MAX_SECTOR_SIZE).contains(&sector_size)
+            || (config.start_sid.to_raw() as usize * SECTOR_SIZE) % sector_size != 0
+            || (config.nr_sectors * SECTOR_SIZE) % sector_size != 0
+        {
+            return Err(ostd::Error::InvalidArgs);
+        }
+        let end = config
+            .start_sid
+            .to_raw()
+            .checked_add(config.nr_sectors as u64);
+        if end.map_or(true, |end| end > device.nr_sectors() as u64) {
+            return Err(ostd::Error::InvalidArgs);
+        }
+        let cipher = SectorCipher::new(&config, key).ok_or(ostd::Error::InvalidArgs)?;
+        DecryptWorker::get_or_spawn()?;
+        Ok(Self {
+            device,
+            cipher: Arc::new(cipher),
+            config,
+        })
+    }
+
+    /// Opens the LUKS2 volume on the `device` with the `passphrase`.
+    ///
+    /// Returns `AccessDenied` if no keyslot can be unlocked by the passphrase.
+    pub fn open_luks2(device: Arc<dyn BlockDevice>, passphrase: &[u8]) -> ostd::Result<Self> {
+        let header = luks2::Header::read(device.as_ref())?;
+        let (config, mut key) = header.unlock(device.as_ref(), passphrase)?;
+        let crypt_device = Self::new(device, config, &key);
+        key.fill(0);
+        core::hint::black_box(&key);
+        crypt_device
+    }
+
+    /// Returns whether the `device` contains a LUKS2 header.
+    pub fn is_luks2(device: &dyn BlockDevice) -> bool {
+        luks2::Header::read(device).is_ok()
+    }
+
+    /// Returns the underlying device.
+    pub fn device(&self) -> &Arc<dyn BlockDevice> {
+        &self.device
+    }
+
+    pub fn config(&self) -> &CryptConfig {
+        &self.config
+    }
+
+    fn submit_read(&self, bio: SubmittedBio) {
+        let start = Sid::new(self.config.start_sid.to_raw() + bio.sid_range().start.to_raw());
+        let segments = bio.segments().to_vec();
+        let crypt_bio = Bio::new(BioType::Read, start, segments, Some(complete_crypt_bio));
+        self.submit(crypt_bio, bio);
+    }
+
+    fn submit_write(&self, bio: SubmittedBio) {
+        let mut bounce_segments = Vec::with_capacity(bio.segments().len());
+        for segment in bio.segments() {
+            let nr_frames = segment.nbytes().div_ceil(PAGE_SIZE);
+            match FrameAllocOptions::new(nr_frames)
+                .uninit(true)
+                .alloc_contiguous()
+            {
+                Ok(pages) => {
+                    bounce_segments.push(BioSegment::from_segment(pages, 0, segment.nbytes()))
+                }
+                Err(_) => {
+                    bio.complete(BioStatus::IoError);
+                    return;
+                }
+            }
+        }
+        let sid = bio.sid_range().start.to_raw();
+        self.cipher
+            .transform(sid, bio.segments(), &bounce_segments, Direction::Encrypt);
+
+        let start = Sid::new(self.config.start_sid.to_raw() + sid);
+        let crypt_bio = Bio::new(
+            BioType::Write,
+            start,
+            bounce_segments,
+            Some(complete_crypt_bio),
+        );
+        self.submit(crypt_bio, bio);
+    }
+
+    fn submit(&self, crypt_bio: Bio, bio: SubmittedBio) {
+        let private = Arc::new(CryptBio {
+            bio,
+            cipher: self.cipher.clone(),
+        });
+        let crypt_bio = crypt_bio.with_private(private.clone());
+        if crypt_bio.submit_nested(self.device.as_ref()).is_err() {
+            private.bio.complete(BioStatus::IoError);
+        }
+    }
+}
+
+impl BlockDevice for CryptDevice {
+    fn enqueue(&self, bio: SubmittedBio) -> Result<(), BioEnqueueError> {
+        let range = bio.sid_range();
+        if range.end.to_raw() as usize > self.config.nr_sectors {
+            return Err(BioEnqueueError::Refused);
+        }
+        match bio.type_() {
+            BioType::Read | BioType::Write => {
+                // Only the whole encryption sectors can be encrypted or decrypted.
+                let sectors_per_unit = (self.config.sector_size / SECTOR_SIZE) as u64;
+                if range.start.to_raw() % sectors_per_unit != 0
+                    || range.end.to_raw() % sectors_per_unit != 0
+                {
+                    return Err(BioEnqueueError::Refused);
+                }
+                if let BioType::Read = bio.type_() {
+                    self.submit_read(bio);
+                } else {
+                    self.submit_write(bio);
+                }
+            }
+            // The waiter is dropped since the completion is propagated to `bio`.
+            BioType::Flush => {
+                let _ = bio.remap(Sid::new(0)).submit_nested(self.device.as_ref())?;
+            }
+            BioType::Discard if self.config.allow_discards => {
+                let _ = bio
+                    .remap(self.config.start_sid)
+                    .submit_nested(self.device.as_ref())?;
+            }
+            // Writing zeroes to the underlying device does not make the sectors read as zeroes.
+            BioType::Discard | BioType::WriteZeroes => bio.complete(BioStatus::NotSupported),
+        }
+        Ok(())
+    }
+
+    fn max_nr_segments_per_bio(&self) -> usize {
+        self.device.max_nr_segments_per_bio()
+    }
+
+    fn nr_sectors(&self) -> usize {
+        self.config.nr_sectors
+    }
+}
+
+/// The private data of a bio submitted to the underlying device.
+struct CryptBio {
+    /// The bio submitted to the encrypted device
+    bio: SubmittedBio,
+    cipher: Arc<SectorCipher>,
+}
+
+fn complete_crypt_bio(crypt_bio: &SubmittedBio) {
+    let private = crypt_bio
+        .private()
+        .cloned()
+        .and_then(|private| private.downcast::<CryptBio>().ok())
+        .unwrap();
+    let status = crypt_bio.status();
+    if let (BioType::Read, BioStatus::Complete) = (private.bio.type_(), status) {
+        DECRYPT_WORKER.get().unwrap().push(private);
+        return;
+    }
+    private.bio.complete(status);
+}
+
+/// The worker task that decrypts the completed reads, which is shared by all the devices.
+static DECRYPT_WORKER: Once<Arc<DecryptWorker>> = Once::new();
+
+struct DecryptWorker {
+    pending: SpinLock<VecDeque<Arc<CryptBio>>>,
+    wait_queue: WaitQueue,
+}
+
+impl DecryptWorker {
+    /// Returns the worker, which is spawned when the first device is created.
+    fn get_or_spawn() -> ostd::Result<&'static Arc<Self>> {
+        DECRYPT_WORKER.try_call_once(|| {
+            let worker = Arc::new(Self {
+                pending: SpinLock::new(VecDeque::new()),
+                wait_queue: WaitQueue::new(),
+            });
+            let worker_cloned = worker.clone();
+            TaskOptions::new(move || worker_cloned.run())
+                .data(())
+                .spawn()?;
+            Ok(worker)
+        })
+    }
+
+    /// Queues a completed read to be decrypted, which may be called in the interrupt context.
+    fn push(&self, crypt_bio: Arc<CryptBio>) {
+        self.pending.lock_irq_disabled().push_back(crypt_bio);
+        self.wait_queue.wake_one();
+    }
+
+    fn run(&self) {
+        loop {
+            let crypt_bio = self
+                .wait_queue
+                .wait_until(|| self.pending.lock_irq_disabled().pop_front());
+            let bio = &crypt_bio.bio;
+            let sid = bio.sid_range().start.to_raw();
+            crypt_bio
+                .cipher
+                .transform(sid, bio.segments(), bio.segments(), Direction::Decrypt);
+            bio.complete(BioStatus::Complete);
+        }
+    }
+}
+
+/// The maximum size of the encryption sectors.
+const MAX_SECTOR_SIZE: usize = 4096;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Direction {
+    Encrypt,
+    Decrypt,
+}
+
+/// The cipher that encrypts the sectors of a device.
+struct SectorCipher {
+    xts: Xts,
+    iv_mode: IvMode,
+    sector_size: usize,
+    /// The shift from the 512-byte sector numbers to those used by the IVs
+    iv_shift: u32,
+    iv_offset: u64,
+}
+
+/// The generator of the IVs from the sector numbers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum IvMode {
+    /// The low 32 bits of the sector number in little endian
+    Plain,
+    /// The 64-bit sector number in little endian
+    Plain64,
+}
+
+impl SectorCipher {
+    /// Creates the cipher for the configuration.
+    ///
+    /// Returns `None` if the cipher is not supported or the key does not match it.
+    fn new(config: &CryptConfig, key: &[u8]) -> Option<Self> {
+        let (xts, iv_mode) = parse_cipher(&config.cipher, key)?;
+        let iv_shift = if config.iv_large_sectors {
+            (config.sector_size / SECTOR_SIZE).trailing_zeros()
+        } else {
+            0
+        };
+        Some(Self {
+            xts,
+            iv_mode,
+            sector_size: config.sector_size,
+            iv_shift,
+            iv_offset: config.iv_offset,
+        })
+    }
+
+    /// Encrypts or decrypts the data of the `src` segments into the `dst` segments,
+    /// which start from the 512-byte sector `sid` of the encrypted device.
+    ///
+    /// The segments may be the same, and they must contain whole encryption sectors.
+    fn transform(&self, sid: u64, src: &[BioSegment], dst: &[BioSegment], direction: Direction) {
+        let len: usize = src.iter().map(|segment| segment.nbytes()).sum();
+        let mut buf = [0u8; MAX_SECTOR_SIZE];
+        let buf = &mut buf[..self.sector_size];
+        for offset in (0..len).step_by(self.sector_size) {
+            read_segments(src, offset, buf);
+            let sector = sid + (offset / SECTOR_SIZE) as u64;
+            let iv = self.iv(sector);
+            match direction {
+                Direction::Encrypt => self.xts.encrypt(&iv, buf),
+                Direction::Decrypt => self.xts.decrypt(&iv, buf),
+            }
+            write_segments(dst, offset, buf);
+        }
+        buf.fill(0);
+    }
+
+    /// Returns the IV of the encryption sector starting from the 512-byte sector.
+    fn iv(&self, sector: u64) -> [u8; 16] {
+        let iv_sector = (sector + self.iv_offset) >> self.iv_shift;
+        let mut iv = [0u8; 16];
+        match self.iv_mode {
+            IvMode::Plain => iv[..4].copy_from_slice(&(iv_sector as u32).to_le_bytes()),
+            IvMode::Plain64 => iv[..8].copy_from_slice(&iv_sector.to_le_bytes()),
+        }
+        iv
+    }
+}
+
+impl Debug for SectorCipher {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        // The keys are never printed.
+        f.debug_struct("SectorCipher")
+            .field("iv_mode", &self.iv_mode)
+            .field("sector_size", &self.sector_size)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Parses the cipher in the form of `<cipher>-<mode>-<iv>` with the key.
+///
+/// Only AES-XTS with the `plain` or `plain64` IVs is supported, which is the default of cryptsetup.
+fn parse_cipher(cipher: &str, key: &[u8]) -> Option<(Xts, IvMode)> {
+    let iv_mode = match cipher {
+        "aes-xts-plain" => IvMode::Plain,
+        "aes-xts-plain64" => IvMode::Plain64,
+        _ => return None,
+    };
+    // AES-192 is not allowed by XTS.
+    if key.len() != 32 && key.len() != 64 {
+        return None;
+    }
+    Some((Xts::new(key)?, iv_mode))
+}
+
+/// Reads the bytes starting from the `offset` of the concatenated `segments`.
+fn read_segments(segments: &[BioSegment], mut offset: usize, mut buf: &mut [u8]) {
+    for segment in segments {
+        if buf.is_empty() {
+            break;
+        }
+        if offset >= segment.nbytes() {
+            offset -= segment.nbytes();
+            continue;
+        }
+        let len = buf.len().min(segment.nbytes() - offset);
+        segment
+            .pages()
+            .read_bytes(segment.offset() + offset, &mut buf[..len])
+            .unwrap();
+        buf = &mut buf[len..];
+        offset = 0;
+    }
+}
+
+/// Writes the bytes starting from the `offset` of the concatenated `segments`.
+fn write_segments(segments: &[BioSegment], mut offset: usize, mut buf: &[u8]) {
+    for segment in segments {
+        if buf.is_empty() {
+            break;
+        }
+        if offset >= segment.nbytes() {
+            offset -= segment.nbytes();
+            continue;
+        }
+        let len = buf.len().min(segment.nbytes() - offset);
+        segment
+            .pages()
+            .write_bytes(segment.offset() + offset, &buf[..len])
+            .unwrap();
+        buf = &buf[len..];
+        offset = 0;
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+    use crate::test_util::MemDevice;
+
+    fn from_hex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[ktest]
+    fn aes_xts_test_vectors() {
+        // FIPS 197, Appendix C.1
+        let aes = aes::Aes::new(&from_hex("000102030405060708090a0b0c0d0e0f")).unwrap();
+        let mut block: [u8; 16] = from_hex("00112233445566778899aabbccddeeff")
+            .try_into()
+            .unwrap();
+        aes.encrypt_block(&mut block);
+        assert_eq!(block[..], from_hex("69c4e0d86a7b0430d8cdb78070b4c55a"));
+        aes.decrypt_block(&mut block);
+        assert_eq!(block[..], from_hex("00112233445566778899aabbccddeeff"));
+
+        // FIPS 197, Appendix C.3
+        let aes = aes::Aes::new(&from_hex(
+            "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f",
+        ))
+        .unwrap();
+        aes.encrypt_block(&mut block);
+        assert_eq!(block[..], from_hex("8ea2b7ca516745bfeafc49904b496089"));
+        aes.decrypt_block(&mut block);
+        assert_eq!(block[..], from_hex("00112233445566778899aabbccddeeff"));
+
+        // IEEE 1619, Vector 1
+        let xts = Xts::new(&[0u8; 32]).unwrap();
+        let mut sector = [0u8; 32];
+        xts.encrypt(&[0u8; 16], &mut sector);
+        assert_eq!(
+            sector[..],
+            from_hex("917cf69ebd68b2ec9b9fe9a3eadda692cd43d2f59598ed858c02c2652fbf922e")
+        );
+        xts.decrypt(&[0u8; 16], &mut sector);
+        assert_eq!(sector, [0u8; 32]);
+    }
+
+    #[ktest]
+    fn kdf_test_vectors() {
+        assert_eq!(
+            sha256::sha256(&[b"abc"])[..],
+            from_hex("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")
+        );
+
+        // RFC 7914, Section 11
+        let mut key = [0u8; 32];
+        sha256::pbkdf2_sha256(b"password", b"salt", 2, &mut key);
+        assert_eq!(
+            key[..],
+            from_hex("ae4d0c95af6b46d32d0adff928f06dd02a303f8ef3c251dfd6e2d85a95474c43")
+        );
+
+        // The parameters of RFC 9106, Section 5.3, without the secret and the associated data.
+        let params = argon2::Params {
+            time_cost: 3,
+            memory_cost: 32,
+            parallelism: 4,
+        };
+        argon2::argon2(
+            argon2::Variant::Argon2id,
+            params,
+            &[1; 32],
+            &[2; 16],
+            &mut key,
+        )
+        .unwrap();
+        assert_eq!(
+            key[..],
+            from_hex("03aab965c12001c9d7d0d2de33192c0494b684bb148196d73c1df1acaf6d0c2e")
+        );
+    }
+
+    #[ktest]
+    fn crypt_device_round_trip() {
+        let device = MemDevice::new(vec![0u8; 64 * SECTOR_SIZE]);
+        let config = CryptConfig {
+            cipher: "aes-xts-plain64".into(),
+            start_sid: Sid::new(8),
+            nr_sectors: 48,
+            iv_offset: 0,
+            sector_size: 4096,
+            iv_large_sectors: true,
+            allow_discards: false,
+        };
+        let key = [0x11u8; 64];
+        let crypt: Arc<dyn BlockDevice> =
+            Arc::new(CryptDevice::new(device.clone(), config, &key).unwrap());
+        assert_eq!(crypt.nr_sectors(), 48);
+
+        let expected: Vec<u8> = (0..2 * 4096).map(|i| i as u8).collect();
+        crypt.write_bytes(4096, &expected).unwrap();
+        let mut buf = vec![0u8; expected.len()];
+        crypt.read_bytes(4096, &mut buf).unwrap();
+        assert_eq!(buf, expected);
+
+        // The second encryption sector is stored at sector 16 with the IV of 1.
+        let mut stored = device.data.lock()[16 * SECTOR_SIZE..24 * SECTOR_SIZE].to_vec();
+        assert_ne!(stored[..], expected[..4096]);
+        let mut iv = [0u8; 16];
+        iv[0] = 1;
+        Xts::new(&key).unwrap().decrypt(&iv, &mut stored);
+        assert_eq!(stored[..], expected[..4096]);
+
+        // The sectors smaller than the encryption sectors cannot be accessed.
+        assert!(crypt
+            .read_bytes(SECTOR_SIZE, &mut buf[..SECTOR_SIZE])
+            .is_err());
+    }
+}
@@ -0,0 +1,82 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The XTS mode of AES as specified in IEEE 1619.
+
+use super::aes::{Aes, BLOCK_SIZE};
+
+/// An AES-XTS cipher, which encrypts the sectors independently with their tweaks.
+#[derive(Clone)]
+pub(super) struct Xts {
+    data_cipher: Aes,
+    tweak_cipher: Aes,
+}
+
+impl Xts {
+    /// Creates the cipher with a key of 32, 48 or 64 bytes, whose first half
+    /// is the data key and the second half is the tweak key.
+    ///
+    /// Returns `None` if the key length is invalid.
+    pub(super) fn new(key: &[u8]) -> Option<Self> {
+        if key.len() % 2 != 0 {
+            return None;
+        }
+        let (data_key, tweak_key) = key.split_at(key.len() / 2);
+        Some(Self {
+            data_cipher: Aes::new(data_key)?,
+            tweak_cipher: Aes::new(tweak_key)?,
+        })
+    }
+
+    /// Encrypts the sector in place with the 16-byte `iv`.
+    ///
+    /// The length of the sector must be a multiple of the AES block size.
+    pub(super) fn encrypt(&self, iv: &[u8; BLOCK_SIZE], sector: &mut [u8]) {
+        self.process(iv, sector, |block| self.data_cipher.encrypt_block(block));
+    }
+
+    /// Decrypts the sector in place with the 16-byte `iv`.
+    ///
+    /// The length of the sector must be a multiple of the AES block size.
+    pub(super) fn decrypt(&self, iv: &[u8; BLOCK_SIZE], sector: &mut [u8]) {
+        self.process(iv, sector, |block| self.data_cipher.decrypt_block(block));
+    }
+
+    fn process(
+        &self,
+        iv: &[u8; BLOCK_SIZE],
+        sector: &mut [u8],
+        cipher: impl Fn(&mut [u8; BLOCK_SIZE]),
+    ) {
+        debug_assert!(sector.len() % BLOCK_SIZE == 0);
+
+        let mut tweak = *iv;
+        self.tweak_cipher.encrypt_block(&mut tweak);
+        for chunk in sector.chunks_exact_mut(BLOCK_SIZE) {
+            let block: &mut [u8; BLOCK_SIZE] = chunk.try_into().unwrap();
+            xor(block, &tweak);
+            cipher(block);
+            xor(block, &tweak);
+            mul_alpha(&mut tweak);
+        }
+    }
+}
+
+fn xor(block: &mut [u8; BLOCK_SIZE], tweak: &[u8; BLOCK_SIZE]) {
+    for (byte, tweak_byte) in block.iter_mut().zip(tweak) {
+        *byte ^= tweak_byte;
+    }
+}
+
+/// Multiplies the tweak by the primitive element in GF(2^128),
+/// where the tweak is a little-endian polynomial.
+fn mul_alpha(tweak: &mut [u8; BLOCK_SIZE]) {
+    let mut carry = 0;
+    for byte in tweak.iter_mut() {
+        let next_carry = *byte >> 7;
+        *byte = (*byte << 1) | carry;
+        carry = next_carry;
+    }
+    if carry != 0 {
+        tweak[0] ^= 0x87;
+    }
+}
@@ -1,5 +1,6 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use align_ext::AlignExt;
 use ostd::mm::{Frame, FrameAllocOptions, Segment, VmIo};
 
 use super::{
@@ -71,6 +72,41 @@ impl dyn BlockDevice {
         let bio = create_bio_from_frame(BioType::Write, bid, frame);
         bio.submit(self)
     }
+
+    /// Synchronously flushes the volatile write cache of the device.
+    ///
+    /// The data of the completed writes are durable after the flush.
+    pub fn flush_sync(&self) -> Result<BioStatus, BioEnqueueError> {
+        let bio = Bio::new_dataless(
+            BioType::Flush,
+            Sid::new(0)..Sid::new(0),
+            Some(general_complete_fn),
+        );
+        let status = bio.submit_sync(self)?;
+        Ok(status)
+    }
+
+    /// Synchronously discards the blocks in the `bid_range`.
+    ///
+    /// The discarded blocks are no longer in use, so that the device is free to
+    /// deallocate them. Their contents are undefined afterwards.
+    pub fn discard_blocks_sync(&self, bid_range: Range<Bid>) -> Result<BioStatus, BioEnqueueError> {
+        let bio = create_dataless_bio(BioType::Discard, bid_range);
+        let status = bio.submit_sync(self)?;
+        Ok(status)
+    }
+
+    /// Synchronously fills the blocks in the `bid_range` with zeroes.
+    ///
+    /// Unlike writing zeroed pages, no data is transferred to the device.
+    pub fn write_zeroes_blocks_sync(
+        &self,
+        bid_range: Range<Bid>,
+    ) -> Result<BioStatus, BioEnqueueError> {
+        let bio = create_dataless_bio(BioType::WriteZeroes, bid_range);
+        let status = bio.submit_sync(self)?;
+        Ok(status)
+    }
 }
 
 impl VmIo for dyn BlockDevice {
@@ -156,6 +192,23 @@ impl VmIo for dyn BlockDevice {
 }
 
 impl dyn BlockDevice {
+    /// Reads consecutive bytes at any offset and of any length.
+    ///
+    /// The sectors covering the bytes are read, so this is for the file systems
+    /// whose structures are not aligned to sectors, e.g., SquashFS.
+    pub fn read_bytes_unaligned(&self, offset: usize, buf: &mut [u8]) -> ostd::Result<()> {
+        let start = offset.align_down(SECTOR_SIZE);
+        let end = (offset + buf.len()).align_up(SECTOR_SIZE);
+        if start == offset && end == offset + buf.len() {
+            return self.read_bytes(offset, buf);
+        }
+
+        let mut sectors = vec![0u8; end - start];
+        self.read_bytes(start, &mut sectors)?;
+        buf.copy_from_slice(&sectors[offset - start..offset - start + buf.len()]);
+        Ok(())
+    }
+
     /// Asynchronously writes consecutive bytes of several sectors in size.
     pub fn write_bytes_async(&self, offset: usize, buf: &[u8]) -> ostd::Result<BioWaiter> {
         if offset % SECTOR_SIZE != 0 || buf.len() % SECTOR_SIZE != 0 {
@@ -215,6 +268,16 @@ fn create_bio_from_frame(type_: BioType, bid: Bid, frame: &Frame) -> Bio {
     )
 }
 
+fn create_dataless_bio(type_: BioType, bid_range: Range<Bid>) -> Bio {
+    // Failures are expected if the device does not support the operation,
+    // so they are left to the callers instead of being logged.
+    Bio::new_dataless(
+        type_,
+        Sid::from(bid_range.start)..Sid::from(bid_range.end),
+        None,
+    )
+}
+
 fn general_complete_fn(bio: &SubmittedBio) {
     match bio.status() {
         BioStatus::Complete => (),
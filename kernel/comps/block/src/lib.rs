@@ -36,10 +36,16 @@
 extern crate alloc;
 
 pub mod bio;
+pub mod crypt;
 pub mod id;
 mod impl_block_device;
+pub mod md;
+pub mod partition;
 mod prelude;
 pub mod request_queue;
+pub mod scheduler;
+#[cfg(ktest)]
+mod test_util;
 
 use component::{init_component, ComponentInitError};
 use ostd::sync::SpinLock;
@@ -58,6 +64,17 @@ pub trait BlockDevice: Send + Sync + Any + Debug {
     fn enqueue(&self, bio: SubmittedBio) -> Result<(), BioEnqueueError>;
     /// Returns the upper limit for the number of segments per bio.
     fn max_nr_segments_per_bio(&self) -> usize;
+    /// Returns the number of sectors of the device.
+    fn nr_sectors(&self) -> usize;
+    /// Returns the name of the I/O scheduler, or `None` if the device does not
+    /// schedule the requests, e.g., a device stacked on other devices.
+    fn io_scheduler(&self) -> Option<&'static str> {
+        None
+    }
+    /// Switches the I/O scheduler by its name, see `scheduler::SCHEDULER_NAMES`.
+    fn set_io_scheduler(&self, _name: &str) -> ostd::Result<()> {
+        Err(ostd::Error::InvalidArgs)
+    }
 }
 
 impl dyn BlockDevice {
@@ -75,6 +92,16 @@ pub fn register_device(name: String, device: Arc<dyn BlockDevice>) {
         .insert(name, device);
 }
 
+/// Unregisters the device of the name, returning the device if it is registered.
+pub fn unregister_device(name: &str) -> Option<Arc<dyn BlockDevice>> {
+    COMPONENT
+        .get()
+        .unwrap()
+        .block_device_table
+        .lock()
+        .remove(name)
+}
+
 pub fn get_device(str: &str) -> Option<Arc<dyn BlockDevice>> {
     COMPONENT
         .get()
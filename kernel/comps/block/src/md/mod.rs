@@ -0,0 +1,663 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The software RAID (md) arrays.
+//!
+//! An md array composes several block devices, called members, into a new block device.
+//! The following RAID levels are supported:
+//! - RAID0, which stripes the data over the members in chunks;
+//! - RAID1, which mirrors the data on all the members. The reads are balanced among
+//!   the members, and the array keeps working as long as one member is in sync.
+//!
+//! The arrays are described by the md superblocks of version 1.2 on the members, which
+//! are compatible with Linux. Thus the arrays created by `mdadm` can be found by `assemble`.
+//!
+//! The superblocks are only updated when a member of a RAID1 array fails, so that
+//! the failed member is not used after the array is assembled again. The array is
+//! not marked as dirty during writes, and the write-intent bitmap is not maintained.
+
+mod raid0;
+mod raid1;
+mod superblock;
+
+use core::sync::atomic::AtomicBool;
+
+use ostd::sync::SpinLock;
+
+use self::{
+    raid0::Raid0,
+    raid1::Raid1,
+    superblock::{
+        Superblock, FEATURE_RECOVERY_OFFSET, FEATURE_REPLACEMENT, MAX_DEVICES, ROLE_FAULTY,
+    },
+};
+use super::{
+    bio::{Bio, BioEnqueueError, BioSegment, BioStatus, BioType, SubmittedBio},
+    id::Sid,
+    BlockDevice, SECTOR_SIZE,
+};
+use crate::prelude::*;
+
+/// The RAID level of an md array.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RaidLevel {
+    Raid0,
+    Raid1,
+}
+
+/// An md array.
+#[derive(Debug)]
+pub struct MdDevice {
+    array: Arc<MdArray>,
+}
+
+impl MdDevice {
+    /// Returns the name of the array recorded in the superblocks, e.g., `host:0`.
+    pub fn name(&self) -> &str {
+        &self.array.name
+    }
+
+    /// Returns the RAID level.
+    pub fn level(&self) -> RaidLevel {
+        match self.array.personality {
+            Personality::Raid0(_) => RaidLevel::Raid0,
+            Personality::Raid1(_) => RaidLevel::Raid1,
+        }
+    }
+
+    /// Returns whether some members of the array are missing or faulty.
+    pub fn is_degraded(&self) -> bool {
+        (0..self.array.members.len()).any(|index| !self.array.is_available(index))
+    }
+}
+
+impl BlockDevice for MdDevice {
+    fn enqueue(&self, bio: SubmittedBio) -> Result<(), BioEnqueueError> {
+        if bio.sid_range().end.to_raw() as usize > self.array.nr_sectors {
+            return Err(BioEnqueueError::Refused);
+        }
+        let io = Arc::new(MdIo::new(self.array.clone(), bio));
+        match &self.array.personality {
+            Personality::Raid0(raid0) => io.submit_raid0(raid0),
+            Personality::Raid1(raid1) => io.submit_raid1(raid1),
+        }
+        io.put();
+        Ok(())
+    }
+
+    fn max_nr_segments_per_bio(&self) -> usize {
+        self.array.max_nr_segments_per_bio
+    }
+
+    fn nr_sectors(&self) -> usize {
+        self.array.nr_sectors
+    }
+}
+
+/// Assembles the md arrays from the members among the `devices`.
+///
+/// The members are recognized by their superblocks and grouped into arrays by the
+/// UUIDs of the arrays. The stale members, whose event counts are behind the others,
+/// are not used. An array is assembled as long as its data are available, e.g.,
+/// a RAID1 array with only one member left.
+pub fn assemble(devices: impl IntoIterator<Item = Arc<dyn BlockDevice>>) -> Vec<Arc<MdDevice>> {
+    let mut groups: Vec<Vec<(Arc<dyn BlockDevice>, Superblock)>> = Vec::new();
+    for device in devices {
+        let superblock = match Superblock::read(device.as_ref()) {
+            Ok(Some(superblock)) => superblock,
+            Ok(None) => continue,
+            Err(e) => {
+                log::warn!("failed to read the md superblock: {:?}", e);
+                continue;
+            }
+        };
+        let group = groups
+            .iter_mut()
+            .find(|group| group[0].1.set_uuid() == superblock.set_uuid());
+        match group {
+            Some(group) => group.push((device, superblock)),
+            None => groups.push(vec![(device, superblock)]),
+        }
+    }
+
+    groups
+        .into_iter()
+        .filter_map(MdArray::assemble)
+        .map(|array| {
+            Arc::new(MdDevice {
+                array: Arc::new(array),
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug)]
+struct MdArray {
+    name: String,
+    personality: Personality,
+    /// The members indexed by their roles, which are `None` if missing
+    members: Vec<Option<Member>>,
+    nr_sectors: usize,
+    max_nr_segments_per_bio: usize,
+    /// The lock that serializes the failures of the members
+    fail_lock: SpinLock<()>,
+}
+
+#[derive(Debug)]
+enum Personality {
+    Raid0(Raid0),
+    Raid1(Raid1),
+}
+
+#[derive(Debug)]
+struct Member {
+    device: Arc<dyn BlockDevice>,
+    superblock: SpinLock<Superblock>,
+    dev_number: u32,
+    /// The first sector of the data on the device
+    data_offset: u64,
+    /// The number of sectors of the data on the device
+    data_size: u64,
+    faulty: AtomicBool,
+}
+
+impl Member {
+    fn new(device: Arc<dyn BlockDevice>, superblock: Superblock) -> Self {
+        Self {
+            device,
+            dev_number: superblock.dev_number(),
+            data_offset: superblock.data_offset(),
+            data_size: superblock.data_size(),
+            superblock: SpinLock::new(superblock),
+            faulty: AtomicBool::new(false),
+        }
+    }
+}
+
+impl MdArray {
+    /// Assembles the array from the members with the same array UUID.
+    ///
+    /// Returns `None` if the array cannot work with the members.
+    fn assemble(members: Vec<(Arc<dyn BlockDevice>, Superblock)>) -> Option<Self> {
+        let max_events = members.iter().map(|(_, sb)| sb.events()).max()?;
+        let (_, reference) = members.iter().find(|(_, sb)| sb.events() == max_events)?;
+        let reference = reference.clone();
+        let name = reference.set_name();
+        if reference.has_unsupported_features() {
+            log::warn!(
+                "md array {}: unsupported features {:#x}",
+                name,
+                reference.feature_map()
+            );
+            return None;
+        }
+        let raid_disks = reference.raid_disks() as usize;
+        if raid_disks == 0 || raid_disks > MAX_DEVICES {
+            log::warn!("md array {}: invalid number of members", name);
+            return None;
+        }
+
+        let mut slots: Vec<Option<Member>> = (0..raid_disks).map(|_| None).collect();
+        for (device, superblock) in members {
+            if superblock.events() < max_events {
+                log::warn!(
+                    "md array {}: member {} is stale, ignored",
+                    name,
+                    superblock.dev_number()
+                );
+                continue;
+            }
+            // The spare and faulty members are not used, nor are those out of sync.
+            let role = reference.role(superblock.dev_number()) as usize;
+            let is_in_sync =
+                superblock.feature_map() & (FEATURE_RECOVERY_OFFSET | FEATURE_REPLACEMENT) == 0;
+            if role >= raid_disks || !is_in_sync || slots[role].is_some() {
+                continue;
+            }
+            slots[role] = Some(Member::new(device, superblock));
+        }
+
+        let nr_members = slots.iter().flatten().count();
+        let (personality, nr_sectors) = match reference.level() {
+            0 => {
+                if nr_members < raid_disks {
+                    log::warn!(
+                        "md array {}: {} of {} members are missing",
+                        name,
+                        raid_disks - nr_members,
+                        raid_disks
+                    );
+                    return None;
+                }
+                let member_sectors: Vec<u64> = slots
+                    .iter()
+                    .flatten()
+                    .map(|member| member.data_size)
+                    .collect();
+                let Some(raid0) = Raid0::new(
+                    reference.chunk_sectors(),
+                    reference.layout(),
+                    &member_sectors,
+                ) else {
+                    log::warn!("md array {}: invalid RAID0 geometry", name);
+                    return None;
+                };
+                let nr_sectors = raid0.nr_sectors();
+                (Personality::Raid0(raid0), nr_sectors)
+            }
+            1 => {
+                if nr_members == 0 {
+                    log::warn!("md array {}: no member is in sync", name);
+                    return None;
+                }
+                let size = reference.size();
+                if slots.iter().flatten().any(|member| member.data_size < size) {
+                    log::warn!("md array {}: the members are too small", name);
+                    return None;
+                }
+                if nr_members < raid_disks {
+                    log::warn!(
+                        "md array {}: degraded with {} of {} members",
+                        name,
+                        nr_members,
+                        raid_disks
+                    );
+                }
+                if !reference.is_clean() {
+                    log::warn!(
+                        "md array {}: not shut down cleanly, the mirrors may differ",
+                        name
+                    );
+                }
+                (Personality::Raid1(Raid1::new(raid_disks)), size)
+            }
+            level => {
+                log::warn!("md array {}: RAID level {} is not supported", name, level);
+                return None;
+            }
+        };
+
+        let max_nr_segments_per_bio = slots
+            .iter()
+            .flatten()
+            .map(|member| member.device.max_nr_segments_per_bio())
+            .min()
+            .unwrap();
+        log::info!(
+            "md array {}: assembled with {} members, {} sectors",
+            name,
+            nr_members,
+            nr_sectors
+        );
+        Some(Self {
+            name,
+            personality,
+            members: slots,
+            nr_sectors: nr_sectors as usize,
+            max_nr_segments_per_bio,
+            fail_lock: SpinLock::new(()),
+        })
+    }
+
+    fn member(&self, index: usize) -> &Member {
+        self.members[index].as_ref().unwrap()
+    }
+
+    /// Returns whether the member exists and is not faulty.
+    fn is_available(&self, index: usize) -> bool {
+        self.members[index]
+            .as_ref()
+            .is_some_and(|member| !member.faulty.load(Ordering::Relaxed))
+    }
+
+    /// Marks the member as faulty, returning whether it is faulty afterwards.
+    ///
+    /// The failure is recorded in the superblocks of the other members,
+    /// so that the member is not used after the array is assembled again.
+    /// Like Linux, the last available member is never marked as faulty,
+    /// so that the array keeps working in the degraded mode.
+    fn fail_member(&self, index: usize) -> bool {
+        let failed = self.member(index);
+        {
+            let _guard = self.fail_lock.lock_irq_disabled();
+            if failed.faulty.load(Ordering::Relaxed) {
+                return true;
+            }
+            if !(0..self.members.len()).any(|other| other != index && self.is_available(other)) {
+                log::warn!(
+                    "md array {}: member {} failed, but it is the last one",
+                    self.name,
+                    index
+                );
+                return false;
+            }
+            failed.faulty.store(true, Ordering::Relaxed);
+        }
+        log::warn!("md array {}: member {} failed", self.name, index);
+
+        let offset = Superblock::SID as usize * SECTOR_SIZE;
+        for (other_index, other) in self.members.iter().enumerate() {
+            if !self.is_available(other_index) {
+                continue;
+            }
+            let other = other.as_ref().unwrap();
+            let bytes = {
+                let mut superblock = other.superblock.lock_irq_disabled();
+                superblock.set_role(failed.dev_number, ROLE_FAULTY);
+                let events = superblock.events() + 1;
+                superblock.set_events(events);
+                superblock.to_bytes()
+            };
+            // The superblock may be updated in the interrupt context, so it is not waited.
+            if let Err(e) = other.device.write_bytes_async(offset, &bytes) {
+                log::warn!(
+                    "md array {}: failed to update the superblock: {:?}",
+                    self.name,
+                    e
+                );
+            }
+        }
+        true
+    }
+}
+
+/// An I/O on the array.
+///
+/// The bio submitted to the array is split into the bios to the members, and it is
+/// completed after all of them are completed.
+struct MdIo {
+    array: Arc<MdArray>,
+    bio: SubmittedBio,
+    /// The number of the member bios in flight, plus one held by the submitter
+    nr_pending: AtomicUsize,
+    /// The number of the member bios completed successfully
+    nr_succeeded: AtomicUsize,
+    /// The status of the failed member bios
+    error: AtomicU32,
+}
+
+/// The private data of a member bio.
+struct MemberBio {
+    io: Arc<MdIo>,
+    member: usize,
+}
+
+impl MdIo {
+    fn new(array: Arc<MdArray>, bio: SubmittedBio) -> Self {
+        Self {
+            array,
+            bio,
+            nr_pending: AtomicUsize::new(1),
+            nr_succeeded: AtomicUsize::new(0),
+            error: AtomicU32::new(BioStatus::Complete as u32),
+        }
+    }
+
+    fn sid_range(&self) -> Range<u64> {
+        self.bio.sid_range().start.to_raw()..self.bio.sid_range().end.to_raw()
+    }
+
+    fn submit_raid0(self: &Arc<Self>, raid0: &Raid0) {
+        if let BioType::Flush = self.bio.type_() {
+            for member in 0..self.array.members.len() {
+                self.submit_range(member, 0..0, 0);
+            }
+            return;
+        }
+
+        // The pieces of the bio on the members, each of which is the index of the member,
+        // the sectors on the member, and the offset (in sectors) within the bio.
+        let has_data = matches!(self.bio.type_(), BioType::Read | BioType::Write);
+        let range = self.sid_range();
+        let mut pieces: Vec<(usize, Range<u64>, u64)> = Vec::new();
+        let mut sid = range.start;
+        while sid < range.end {
+            let (member, member_sid, nr_sectors) = raid0.map(sid);
+            let nr_sectors = nr_sectors.min(range.end - sid);
+            // The dataless pieces are merged if they are contiguous on the member.
+            let last = pieces.iter_mut().rev().find(|piece| piece.0 == member);
+            match last {
+                Some(piece) if !has_data && piece.1.end == member_sid => {
+                    piece.1.end += nr_sectors;
+                }
+                _ => pieces.push((
+                    member,
+                    member_sid..member_sid + nr_sectors,
+                    sid - range.start,
+                )),
+            }
+            sid += nr_sectors;
+        }
+
+        for (member, member_range, bio_offset) in pieces {
+            self.submit_range(member, member_range, bio_offset);
+        }
+    }
+
+    fn submit_raid1(self: &Arc<Self>, raid1: &Raid1) {
+        if let BioType::Read = self.bio.type_() {
+            self.read_raid1(raid1);
+            return;
+        }
+
+        let mut nr_submitted = 0;
+        for member in 0..self.array.members.len() {
+            if self.array.is_available(member) {
+                self.submit_range(member, self.sid_range(), 0);
+                nr_submitted += 1;
+            }
+        }
+        if nr_submitted == 0 {
+            self.record_error(BioStatus::IoError);
+        }
+    }
+
+    fn read_raid1(self: &Arc<Self>, raid1: &Raid1) {
+        let range = self.sid_range();
+        match raid1.read_balance(&range, |member| self.array.is_available(member)) {
+            Some(member) => self.submit_range(member, range, 0),
+            None => self.record_error(BioStatus::IoError),
+        }
+    }
+
+    /// Submits the bio of the `range` of sectors on the member, which corresponds to
+    /// the sectors starting from `bio_offset` within the bio.
+    fn submit_range(self: &Arc<Self>, member: usize, range: Range<u64>, bio_offset: u64) {
+        let start = Sid::new(self.array.member(member).data_offset + range.start);
+        let nr_sectors = range.end - range.start;
+        let bio = match self.bio.type_() {
+            type_ @ (BioType::Read | BioType::Write) => {
+                let offset = bio_offset as usize * SECTOR_SIZE;
+                let len = nr_sectors as usize * SECTOR_SIZE;
+                let segments = slice_segments(self.bio.segments(), offset..offset + len);
+                Bio::new(type_, start, segments, Some(complete_member_bio))
+            }
+            type_ => Bio::new_dataless(type_, start..start + nr_sectors, Some(complete_member_bio)),
+        };
+
+        self.nr_pending.fetch_add(1, Ordering::Relaxed);
+        let bio = bio.with_private(Arc::new(MemberBio {
+            io: self.clone(),
+            member,
+        }));
+        let device = &self.array.member(member).device;
+        if bio.submit_nested(device.as_ref()).is_err() {
+            self.end_member_bio(member, BioStatus::IoError);
+        }
+    }
+
+    fn end_member_bio(self: &Arc<Self>, member: usize, status: BioStatus) {
+        match status {
+            BioStatus::Complete => {
+                self.nr_succeeded.fetch_add(1, Ordering::Relaxed);
+            }
+            BioStatus::NotSupported => self.record_error(status),
+            _ => {
+                self.record_error(status);
+                // A mirror cannot be trusted once an I/O on it fails,
+                // and the failed read is retried on another mirror.
+                // The error is returned if there is no other mirror.
+                if let Personality::Raid1(raid1) = &self.array.personality {
+                    if self.array.fail_member(member) {
+                        if let BioType::Read = self.bio.type_() {
+                            self.read_raid1(raid1);
+                        }
+                    }
+                }
+            }
+        }
+        self.put();
+    }
+
+    fn record_error(&self, status: BioStatus) {
+        // `NotSupported` takes precedence, so that the submitter can fall back
+        // to other ways and keep the mirrors identical.
+        let _ = self
+            .error
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |error| {
+                (error == BioStatus::Complete as u32 || status == BioStatus::NotSupported)
+                    .then_some(status as u32)
+            });
+    }
+
+    /// Drops a pending reference, and completes the bio if it is the last one.
+    fn put(&self) {
+        if self.nr_pending.fetch_sub(1, Ordering::AcqRel) != 1 {
+            return;
+        }
+        let error = BioStatus::try_from(self.error.load(Ordering::Relaxed)).unwrap();
+        let status = match error {
+            BioStatus::Complete | BioStatus::NotSupported => error,
+            // A mirrored I/O succeeds as long as it succeeds on one member.
+            _ if matches!(self.array.personality, Personality::Raid1(_))
+                && self.nr_succeeded.load(Ordering::Relaxed) > 0 =>
+            {
+                BioStatus::Complete
+            }
+            _ => error,
+        };
+        self.bio.complete(status);
+    }
+}
+
+fn complete_member_bio(bio: &SubmittedBio) {
+    let member_bio = bio
+        .private()
+        .and_then(|private| private.downcast_ref::<MemberBio>())
+        .unwrap();
+    member_bio
+        .io
+        .end_member_bio(member_bio.member, bio.status());
+}
+
+/// Returns the segments of the bytes in the `range` of the concatenated `segments`.
+fn slice_segments(segments: &[BioSegment], range: Range<usize>) -> Vec<BioSegment> {
+    let mut sliced = Vec::new();
+    let mut offset = 0;
+    for segment in segments {
+        let start = range.start.max(offset);
+        let end = range.end.min(offset + segment.nbytes());
+        if start < end {
+            sliced.push(segment.slice(start - offset, end - start));
+        }
+        offset += segment.nbytes();
+    }
+    sliced
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::{mm::VmIo, prelude::*};
+
+    use super::*;
+    use crate::test_util::MemDevice;
+
+    /// Creates a member device with the superblock.
+    fn new_member(nr_sectors: usize, superblock: &[u8]) -> Arc<MemDevice> {
+        let mut data = vec![0u8; nr_sectors * SECTOR_SIZE];
+        let offset = Superblock::SID as usize * SECTOR_SIZE;
+        data[offset..offset + superblock.len()].copy_from_slice(superblock);
+        MemDevice::new(data)
+    }
+
+    #[ktest]
+    fn raid0_map_multiple_zones() {
+        // The first zone stripes over all the members up to 8 sectors,
+        // and the second one stripes over the last two members.
+        let raid0 = Raid0::new(4, 2, &[8, 16, 18]).unwrap();
+        assert_eq!(raid0.nr_sectors(), 40);
+        assert_eq!(raid0.map(0), (0, 0, 4));
+        assert_eq!(raid0.map(5), (1, 1, 3));
+        assert_eq!(raid0.map(8), (2, 0, 4));
+        assert_eq!(raid0.map(12), (0, 4, 4));
+        assert_eq!(raid0.map(24), (1, 8, 4));
+        assert_eq!(raid0.map(30), (2, 10, 2));
+        assert_eq!(raid0.map(32), (1, 12, 4));
+    }
+
+    #[ktest]
+    fn raid1_survives_member_failure() {
+        const NR_SECTORS: usize = 80;
+        let members: Vec<Arc<MemDevice>> = (0..2)
+            .map(|dev_number| {
+                new_member(NR_SECTORS, &superblock::build(1, 2, 0, 64, dev_number, 64))
+            })
+            .collect();
+        let arrays = assemble(
+            members
+                .iter()
+                .map(|member| member.clone() as Arc<dyn BlockDevice>),
+        );
+        assert_eq!(arrays.len(), 1);
+        assert_eq!(arrays[0].level(), RaidLevel::Raid1);
+        assert!(!arrays[0].is_degraded());
+        let array: Arc<dyn BlockDevice> = arrays[0].clone();
+        assert_eq!(array.nr_sectors(), 64);
+
+        let expected = [0x5Au8; SECTOR_SIZE];
+        array.write_bytes(2 * SECTOR_SIZE, &expected).unwrap();
+        // The data of the members start from sector 16.
+        for member in members.iter() {
+            let offset = 18 * SECTOR_SIZE;
+            assert_eq!(member.data.lock()[offset..offset + SECTOR_SIZE], expected);
+        }
+
+        // The read is retried on the other member.
+        members[0].is_broken.store(true, Ordering::Relaxed);
+        let mut buf = [0u8; SECTOR_SIZE];
+        array.read_bytes(2 * SECTOR_SIZE, &mut buf).unwrap();
+        assert_eq!(buf, expected);
+        assert!(arrays[0].is_degraded());
+
+        let superblock = Superblock::read(members[1].as_ref()).unwrap().unwrap();
+        assert_eq!(superblock.role(0), ROLE_FAULTY);
+        assert_eq!(superblock.events(), 2);
+    }
+
+    #[ktest]
+    fn raid1_keeps_last_member() {
+        const NR_SECTORS: usize = 80;
+        // The second member of the array is missing.
+        let member = new_member(NR_SECTORS, &superblock::build(1, 2, 0, 64, 0, 64));
+        let arrays = assemble([member.clone() as Arc<dyn BlockDevice>]);
+        assert_eq!(arrays.len(), 1);
+        assert!(arrays[0].is_degraded());
+        let array: Arc<dyn BlockDevice> = arrays[0].clone();
+
+        let expected = [0x5Au8; SECTOR_SIZE];
+        array.write_bytes(2 * SECTOR_SIZE, &expected).unwrap();
+
+        // The error is returned, but the last member is not failed.
+        member.is_broken.store(true, Ordering::Relaxed);
+        let mut buf = [0u8; SECTOR_SIZE];
+        assert!(array.read_bytes(2 * SECTOR_SIZE, &mut buf).is_err());
+        assert!(array.write_bytes(2 * SECTOR_SIZE, &buf).is_err());
+        assert!(arrays[0].array.is_available(0));
+
+        // The array works again once the member recovers.
+        member.is_broken.store(false, Ordering::Relaxed);
+        array.read_bytes(2 * SECTOR_SIZE, &mut buf).unwrap();
+        assert_eq!(buf, expected);
+        let superblock = Superblock::read(member.as_ref()).unwrap().unwrap();
+        assert_ne!(superblock.role(0), ROLE_FAULTY);
+    }
+}
@@ -0,0 +1,121 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use crate::prelude::*;
+
+/// The striping of a RAID0 array.
+///
+/// The array is divided into chunks, which are distributed over the members in turn.
+/// If the members have different sizes, the array consists of several zones as Linux
+/// does. The first zone stripes over all the members up to the size of the smallest one,
+/// and each following zone stripes over the remaining space of the larger members.
+#[derive(Debug)]
+pub(super) struct Raid0 {
+    chunk_sectors: u64,
+    layout: Layout,
+    zones: Vec<Zone>,
+}
+
+#[derive(Debug)]
+struct Zone {
+    /// The indexes of the members that the zone stripes over
+    members: Vec<usize>,
+    /// The first sector of the zone on each member
+    dev_start: u64,
+    /// The sector of the array following the zone
+    zone_end: u64,
+}
+
+/// The layout of the zones other than the first one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Layout {
+    /// The chunks are mapped by the sectors of the array, which is the layout
+    /// of Linux before 3.14.
+    Original,
+    /// The chunks are mapped by the sectors within the zone.
+    Alternate,
+}
+
+impl Raid0 {
+    /// Creates the striping over the members of `member_sectors` sectors.
+    ///
+    /// Returns `None` if the chunk size is zero, some member is smaller than a chunk,
+    /// or the layout of multiple zones is unknown.
+    pub(super) fn new(chunk_sectors: u32, layout: u32, member_sectors: &[u64]) -> Option<Self> {
+        let chunk_sectors = chunk_sectors as u64;
+        if chunk_sectors == 0 {
+            return None;
+        }
+        // The space of each member is used in whole chunks.
+        let sectors: Vec<u64> = member_sectors
+            .iter()
+            .map(|sectors| sectors / chunk_sectors * chunk_sectors)
+            .collect();
+        if sectors.iter().any(|sectors| *sectors == 0) {
+            return None;
+        }
+
+        let mut zones = Vec::new();
+        let mut dev_start = 0;
+        let mut zone_end = 0;
+        loop {
+            let members: Vec<usize> = (0..sectors.len())
+                .filter(|index| sectors[*index] > dev_start)
+                .collect();
+            let Some(smallest) = members.iter().map(|index| sectors[*index]).min() else {
+                break;
+            };
+            zone_end += (smallest - dev_start) * members.len() as u64;
+            zones.push(Zone {
+                members,
+                dev_start,
+                zone_end,
+            });
+            dev_start = smallest;
+        }
+
+        let layout = match layout {
+            _ if zones.len() == 1 => Layout::Alternate,
+            1 => Layout::Original,
+            2 => Layout::Alternate,
+            _ => return None,
+        };
+        Some(Self {
+            chunk_sectors,
+            layout,
+            zones,
+        })
+    }
+
+    /// Returns the number of sectors of the array.
+    pub(super) fn nr_sectors(&self) -> u64 {
+        self.zones.last().unwrap().zone_end
+    }
+
+    /// Maps the sector of the array to the member.
+    ///
+    /// Returns the index of the member, the sector within the data of the member, and
+    /// the number of the following sectors in the same chunk.
+    pub(super) fn map(&self, sid: u64) -> (usize, u64, u64) {
+        let index = self
+            .zones
+            .iter()
+            .position(|zone| sid < zone.zone_end)
+            .unwrap();
+        let zone = &self.zones[index];
+        let zone_offset = match index {
+            0 => sid,
+            _ => sid - self.zones[index - 1].zone_end,
+        };
+        let nr_members = zone.members.len() as u64;
+
+        let sector = match self.layout {
+            Layout::Original => sid,
+            Layout::Alternate => zone_offset,
+        };
+        let sector_in_chunk = sector % self.chunk_sectors;
+        let member = zone.members[((sector / self.chunk_sectors) % nr_members) as usize];
+        let chunk_in_member = zone_offset / (self.chunk_sectors * nr_members);
+        let member_sid = zone.dev_start + chunk_in_member * self.chunk_sectors + sector_in_chunk;
+        (member, member_sid, self.chunk_sectors - sector_in_chunk)
+    }
+}
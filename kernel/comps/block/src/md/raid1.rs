@@ -0,0 +1,42 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::sync::atomic::AtomicU64;
+
+use crate::prelude::*;
+
+/// The mirroring of a RAID1 array.
+///
+/// The writes go to all the members that are in sync. Each read goes to the member
+/// whose last read ends nearest to it, so that the sequential reads stay on the same
+/// member while the random reads are spread over the members.
+#[derive(Debug)]
+pub(super) struct Raid1 {
+    /// The sector following the last read of each member
+    head_positions: Vec<AtomicU64>,
+}
+
+impl Raid1 {
+    pub(super) fn new(nr_members: usize) -> Self {
+        Self {
+            head_positions: (0..nr_members).map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
+
+    /// Selects the member to read the sectors of `range` from, among which
+    /// `is_available` returns `true`.
+    pub(super) fn read_balance(
+        &self,
+        range: &Range<u64>,
+        is_available: impl Fn(usize) -> bool,
+    ) -> Option<usize> {
+        let member = (0..self.head_positions.len())
+            .filter(|index| is_available(*index))
+            .min_by_key(|index| {
+                self.head_positions[*index]
+                    .load(Ordering::Relaxed)
+                    .abs_diff(range.start)
+            })?;
+        self.head_positions[member].store(range.end, Ordering::Relaxed);
+        Some(member)
+    }
+}
@@ -0,0 +1,276 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use ostd::mm::VmIo;
+
+use crate::{prelude::*, BlockDevice, SECTOR_SIZE};
+
+/// The md superblock of version 1.2, which is compatible with Linux.
+///
+/// The superblock resides at 4 KiB from the start of each member. It consists of
+/// a 256-byte header followed by the roles of the members, each of which is
+/// a little-endian `u16` indexed by the device number.
+#[derive(Clone, Debug)]
+pub(super) struct Superblock {
+    bytes: Vec<u8>,
+}
+
+/// The role of a spare member.
+pub(super) const ROLE_SPARE: u16 = 0xFFFF;
+/// The role of a faulty member.
+pub(super) const ROLE_FAULTY: u16 = 0xFFFE;
+
+const FEATURE_BITMAP_OFFSET: u32 = 1 << 0;
+/// The member is being recovered, i.e., only the sectors below the recovery offset are in sync.
+pub(super) const FEATURE_RECOVERY_OFFSET: u32 = 1 << 1;
+const FEATURE_BAD_BLOCKS: u32 = 1 << 3;
+/// The member is replacing another member.
+pub(super) const FEATURE_REPLACEMENT: u32 = 1 << 4;
+const FEATURE_RECOVERY_BITMAP: u32 = 1 << 7;
+/// The features that can be assembled. The write-intent bitmap and the bad block log
+/// are ignored, and the members being recovered or replacing others are not used.
+const SUPPORTED_FEATURES: u32 = FEATURE_BITMAP_OFFSET
+    | FEATURE_RECOVERY_OFFSET
+    | FEATURE_BAD_BLOCKS
+    | FEATURE_REPLACEMENT
+    | FEATURE_RECOVERY_BITMAP;
+
+const MAGIC: u32 = 0xA92B_4EFC;
+const MAJOR_VERSION: u32 = 1;
+/// The size of the header before the roles.
+const HEADER_SIZE: usize = 256;
+/// The maximum size of the superblock.
+const MAX_SIZE: usize = 4096;
+/// The maximum number of the members, whose roles fill up the superblock.
+pub(super) const MAX_DEVICES: usize = (MAX_SIZE - HEADER_SIZE) / 2;
+
+const MAGIC_OFFSET: usize = 0;
+const MAJOR_VERSION_OFFSET: usize = 4;
+const FEATURE_MAP_OFFSET: usize = 8;
+const SET_UUID_OFFSET: usize = 16;
+const SET_NAME_OFFSET: usize = 32;
+const SET_NAME_LEN: usize = 32;
+const LEVEL_OFFSET: usize = 72;
+const LAYOUT_OFFSET: usize = 76;
+const SIZE_OFFSET: usize = 80;
+const CHUNK_SIZE_OFFSET: usize = 88;
+const RAID_DISKS_OFFSET: usize = 92;
+const DATA_OFFSET_OFFSET: usize = 128;
+const DATA_SIZE_OFFSET: usize = 136;
+const SUPER_OFFSET_OFFSET: usize = 144;
+const DEV_NUMBER_OFFSET: usize = 160;
+const EVENTS_OFFSET: usize = 200;
+const RESYNC_OFFSET_OFFSET: usize = 208;
+const CHECKSUM_OFFSET: usize = 216;
+const MAX_DEV_OFFSET: usize = 220;
+
+impl Superblock {
+    /// The sector of the superblock on a member.
+    pub(super) const SID: u64 = 8;
+
+    /// Reads the superblock from the `device`.
+    ///
+    /// Returns `None` if the device has no valid superblock of version 1.2.
+    pub(super) fn read(device: &dyn BlockDevice) -> ostd::Result<Option<Self>> {
+        let offset = Self::SID as usize * SECTOR_SIZE;
+        if device.nr_sectors() * SECTOR_SIZE < offset + MAX_SIZE {
+            return Ok(None);
+        }
+        let mut bytes = vec![0u8; MAX_SIZE];
+        device.read_bytes(offset, &mut bytes)?;
+        let Some(superblock) = Self::parse(bytes) else {
+            return Ok(None);
+        };
+        // The data must lie within the device.
+        let data_end = superblock.data_offset().checked_add(superblock.data_size());
+        if data_end.map_or(true, |end| end > device.nr_sectors() as u64) {
+            log::warn!("the data of the md member are beyond the device");
+            return Ok(None);
+        }
+        Ok(Some(superblock))
+    }
+
+    fn parse(mut bytes: Vec<u8>) -> Option<Self> {
+        if read_u32(&bytes, MAGIC_OFFSET) != MAGIC
+            || read_u32(&bytes, MAJOR_VERSION_OFFSET) != MAJOR_VERSION
+            || read_u64(&bytes, SUPER_OFFSET_OFFSET) != Self::SID
+        {
+            return None;
+        }
+        let max_dev = read_u32(&bytes, MAX_DEV_OFFSET) as usize;
+        if max_dev > MAX_DEVICES {
+            return None;
+        }
+        bytes.truncate(HEADER_SIZE + max_dev * 2);
+        let superblock = Self { bytes };
+        if superblock.checksum() != read_u32(&superblock.bytes, CHECKSUM_OFFSET) {
+            log::warn!("the md superblock has a wrong checksum");
+            return None;
+        }
+        Some(superblock)
+    }
+
+    /// Returns whether the superblock contains any feature that cannot be assembled.
+    pub(super) fn has_unsupported_features(&self) -> bool {
+        self.feature_map() & !SUPPORTED_FEATURES != 0
+    }
+
+    pub(super) fn feature_map(&self) -> u32 {
+        read_u32(&self.bytes, FEATURE_MAP_OFFSET)
+    }
+
+    pub(super) fn set_uuid(&self) -> [u8; 16] {
+        self.bytes[SET_UUID_OFFSET..SET_UUID_OFFSET + 16]
+            .try_into()
+            .unwrap()
+    }
+
+    /// Returns the name of the array, e.g., `host:0`.
+    pub(super) fn set_name(&self) -> String {
+        let name = &self.bytes[SET_NAME_OFFSET..SET_NAME_OFFSET + SET_NAME_LEN];
+        let len = name
+            .iter()
+            .position(|byte| *byte == 0)
+            .unwrap_or(name.len());
+        String::from_utf8_lossy(&name[..len]).into()
+    }
+
+    pub(super) fn level(&self) -> i32 {
+        read_u32(&self.bytes, LEVEL_OFFSET) as i32
+    }
+
+    pub(super) fn layout(&self) -> u32 {
+        read_u32(&self.bytes, LAYOUT_OFFSET)
+    }
+
+    /// Returns the number of sectors used on each member, which is the size of RAID1 arrays.
+    pub(super) fn size(&self) -> u64 {
+        read_u64(&self.bytes, SIZE_OFFSET)
+    }
+
+    /// Returns the chunk size in sectors.
+    pub(super) fn chunk_sectors(&self) -> u32 {
+        read_u32(&self.bytes, CHUNK_SIZE_OFFSET)
+    }
+
+    pub(super) fn raid_disks(&self) -> u32 {
+        read_u32(&self.bytes, RAID_DISKS_OFFSET)
+    }
+
+    /// Returns the first sector of the data on the member.
+    pub(super) fn data_offset(&self) -> u64 {
+        read_u64(&self.bytes, DATA_OFFSET_OFFSET)
+    }
+
+    /// Returns the number of sectors of the data on the member.
+    pub(super) fn data_size(&self) -> u64 {
+        read_u64(&self.bytes, DATA_SIZE_OFFSET)
+    }
+
+    /// Returns the device number of the member, which indexes the roles.
+    pub(super) fn dev_number(&self) -> u32 {
+        read_u32(&self.bytes, DEV_NUMBER_OFFSET)
+    }
+
+    /// Returns the event count, which is increased whenever the array changes.
+    pub(super) fn events(&self) -> u64 {
+        read_u64(&self.bytes, EVENTS_OFFSET)
+    }
+
+    pub(super) fn set_events(&mut self, events: u64) {
+        self.bytes[EVENTS_OFFSET..EVENTS_OFFSET + 8].copy_from_slice(&events.to_le_bytes());
+    }
+
+    /// Returns whether the array was shut down cleanly, i.e., the members are in sync.
+    pub(super) fn is_clean(&self) -> bool {
+        read_u64(&self.bytes, RESYNC_OFFSET_OFFSET) == u64::MAX
+    }
+
+    /// Returns the role of the member with the `dev_number`, which is the index of
+    /// the member in the array, `ROLE_SPARE`, or `ROLE_FAULTY`.
+    pub(super) fn role(&self, dev_number: u32) -> u16 {
+        let offset = HEADER_SIZE + dev_number as usize * 2;
+        if offset + 2 > self.bytes.len() {
+            return ROLE_SPARE;
+        }
+        u16::from_le_bytes(self.bytes[offset..offset + 2].try_into().unwrap())
+    }
+
+    pub(super) fn set_role(&mut self, dev_number: u32, role: u16) {
+        let offset = HEADER_SIZE + dev_number as usize * 2;
+        if offset + 2 <= self.bytes.len() {
+            self.bytes[offset..offset + 2].copy_from_slice(&role.to_le_bytes());
+        }
+    }
+
+    /// Returns the bytes to be written to the member, with the checksum updated.
+    pub(super) fn to_bytes(&mut self) -> Vec<u8> {
+        let checksum = self.checksum();
+        self.bytes[CHECKSUM_OFFSET..CHECKSUM_OFFSET + 4].copy_from_slice(&checksum.to_le_bytes());
+        let mut bytes = self.bytes.clone();
+        bytes.resize(self.bytes.len().next_multiple_of(SECTOR_SIZE), 0);
+        bytes
+    }
+
+    /// Computes the checksum, in which the checksum field itself is regarded as zero.
+    fn checksum(&self) -> u32 {
+        let mut sum: u64 = 0;
+        let mut chunks = self.bytes.chunks_exact(4);
+        for (index, chunk) in chunks.by_ref().enumerate() {
+            if index * 4 != CHECKSUM_OFFSET {
+                sum += u32::from_le_bytes(chunk.try_into().unwrap()) as u64;
+            }
+        }
+        if let [low, high] = chunks.remainder() {
+            sum += u16::from_le_bytes([*low, *high]) as u64;
+        }
+        ((sum & 0xFFFF_FFFF) + (sum >> 32)) as u32
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+/// Builds the superblock of a member, which is used to set up the arrays in tests.
+#[cfg(ktest)]
+pub(super) fn build(
+    level: i32,
+    raid_disks: u32,
+    chunk_sectors: u32,
+    size: u64,
+    dev_number: u32,
+    data_size: u64,
+) -> Vec<u8> {
+    const DATA_OFFSET: u64 = 16;
+
+    let mut bytes = vec![0u8; HEADER_SIZE + raid_disks as usize * 2];
+    let mut write = |offset: usize, value: &[u8]| {
+        bytes[offset..offset + value.len()].copy_from_slice(value);
+    };
+    write(MAGIC_OFFSET, &MAGIC.to_le_bytes());
+    write(MAJOR_VERSION_OFFSET, &MAJOR_VERSION.to_le_bytes());
+    write(SET_UUID_OFFSET, &[0xAB; 16]);
+    write(SET_NAME_OFFSET, b"test:0");
+    write(LEVEL_OFFSET, &level.to_le_bytes());
+    write(SIZE_OFFSET, &size.to_le_bytes());
+    write(CHUNK_SIZE_OFFSET, &chunk_sectors.to_le_bytes());
+    write(RAID_DISKS_OFFSET, &raid_disks.to_le_bytes());
+    write(DATA_OFFSET_OFFSET, &DATA_OFFSET.to_le_bytes());
+    write(DATA_SIZE_OFFSET, &data_size.to_le_bytes());
+    write(SUPER_OFFSET_OFFSET, &Superblock::SID.to_le_bytes());
+    write(DEV_NUMBER_OFFSET, &dev_number.to_le_bytes());
+    write(EVENTS_OFFSET, &1u64.to_le_bytes());
+    write(RESYNC_OFFSET_OFFSET, &u64::MAX.to_le_bytes());
+    write(MAX_DEV_OFFSET, &raid_disks.to_le_bytes());
+    for role in 0..raid_disks {
+        write(
+            HEADER_SIZE + role as usize * 2,
+            &(role as u16).to_le_bytes(),
+        );
+    }
+    Superblock { bytes }.to_bytes()
+}
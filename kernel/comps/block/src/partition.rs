@@ -0,0 +1,448 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The partitions of block devices.
+//!
+//! The partition table of a block device is scanned by `scan`, which supports
+//! MBR (including the logical partitions in extended partitions) and GPT.
+//! Each partition can then be used as a standalone block device via `Partition`,
+//! which forwards its bios to the underlying device with the sectors translated.
+
+use aster_util::crc::crc32;
+use ostd::mm::VmIo;
+
+use super::{
+    bio::{BioEnqueueError, SubmittedBio},
+    id::Sid,
+    BlockDevice, SECTOR_SIZE,
+};
+use crate::prelude::*;
+
+/// The information of a partition.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PartitionInfo {
+    /// The partition number, which starts from 1
+    pub number: u32,
+    /// The first sector of the partition on the device
+    pub start_sid: Sid,
+    /// The number of sectors of the partition
+    pub nr_sectors: usize,
+}
+
+/// A partition of a block device.
+#[derive(Debug)]
+pub struct Partition {
+    device: Arc<dyn BlockDevice>,
+    info: PartitionInfo,
+}
+
+impl Partition {
+    /// Creates a partition on the `device`.
+    ///
+    /// The partition must lie within the device.
+    pub fn new(device: Arc<dyn BlockDevice>, info: PartitionInfo) -> Self {
+        assert!(info.start_sid.to_raw() as usize + info.nr_sectors <= device.nr_sectors());
+        Self { device, info }
+    }
+
+    /// Returns the information of the partition.
+    pub fn info(&self) -> &PartitionInfo {
+        &self.info
+    }
+
+    /// Returns the underlying block device.
+    pub fn device(&self) -> &Arc<dyn BlockDevice> {
+        &self.device
+    }
+}
+
+impl BlockDevice for Partition {
+    fn enqueue(&self, bio: SubmittedBio) -> Result<(), BioEnqueueError> {
+        if bio.sid_range().end.to_raw() as usize > self.info.nr_sectors {
+            return Err(BioEnqueueError::Refused);
+        }
+        // The waiter is dropped since the completion is propagated to `bio`.
+        let _ = bio
+            .remap(self.info.start_sid)
+            .submit_nested(self.device.as_ref())?;
+        Ok(())
+    }
+
+    fn max_nr_segments_per_bio(&self) -> usize {
+        self.device.max_nr_segments_per_bio()
+    }
+
+    fn nr_sectors(&self) -> usize {
+        self.info.nr_sectors
+    }
+}
+
+/// Scans the partition table of the `device`.
+///
+/// Returns an empty vector if the device has no recognized partition table.
+pub fn scan(device: &dyn BlockDevice) -> ostd::Result<Vec<PartitionInfo>> {
+    let read_sectors =
+        |sid: u64, buf: &mut [u8]| device.read_bytes(sid as usize * SECTOR_SIZE, buf);
+    scan_with(read_sectors, device.nr_sectors() as u64)
+}
+
+fn scan_with<F>(read_sectors: F, nr_sectors: u64) -> ostd::Result<Vec<PartitionInfo>>
+where
+    F: Fn(u64, &mut [u8]) -> ostd::Result<()>,
+{
+    if nr_sectors == 0 {
+        return Ok(Vec::new());
+    }
+    let mut mbr = [0u8; SECTOR_SIZE];
+    read_sectors(0, &mut mbr)?;
+    let Some(entries) = parse_mbr(&mbr) else {
+        return Ok(Vec::new());
+    };
+
+    // A protective MBR indicates that the device is partitioned by GPT.
+    if entries
+        .iter()
+        .any(|entry| entry.type_ == MBR_TYPE_GPT_PROTECTIVE)
+    {
+        return scan_gpt(&read_sectors, nr_sectors);
+    }
+
+    let mut partitions = Vec::new();
+    for (index, entry) in entries.iter().enumerate() {
+        if entry.is_empty() {
+            continue;
+        }
+        if entry.is_extended() {
+            scan_extended(&read_sectors, nr_sectors, entry, &mut partitions)?;
+            continue;
+        }
+        push_partition(
+            &mut partitions,
+            index as u32 + 1,
+            entry.start as u64,
+            entry.nr_sectors as u64,
+            nr_sectors,
+        );
+    }
+    Ok(partitions)
+}
+
+/// Pushes the partition if it lies within the device.
+fn push_partition(
+    partitions: &mut Vec<PartitionInfo>,
+    number: u32,
+    start: u64,
+    len: u64,
+    nr_sectors: u64,
+) {
+    if len == 0 || start.checked_add(len).map_or(true, |end| end > nr_sectors) {
+        log::warn!("partition {} is beyond the device, ignored", number);
+        return;
+    }
+    partitions.push(PartitionInfo {
+        number,
+        start_sid: Sid::new(start),
+        nr_sectors: len as usize,
+    });
+}
+
+const MBR_SIGNATURE_OFFSET: usize = 510;
+const MBR_ENTRIES_OFFSET: usize = 446;
+const MBR_ENTRY_SIZE: usize = 16;
+const MBR_NR_ENTRIES: usize = 4;
+const MBR_TYPE_GPT_PROTECTIVE: u8 = 0xEE;
+/// The first partition number of the logical partitions.
+const MBR_FIRST_LOGICAL_NUMBER: u32 = 5;
+/// The maximum number of the logical partitions, which guards against cyclic EBR chains.
+const MBR_MAX_LOGICAL_PARTITIONS: u32 = 128;
+
+#[derive(Clone, Copy, Debug)]
+struct MbrEntry {
+    type_: u8,
+    start: u32,
+    nr_sectors: u32,
+}
+
+impl MbrEntry {
+    fn is_empty(&self) -> bool {
+        self.type_ == 0 || self.nr_sectors == 0
+    }
+
+    fn is_extended(&self) -> bool {
+        matches!(self.type_, 0x05 | 0x0F | 0x85)
+    }
+}
+
+/// Parses the partition entries of an MBR or an EBR.
+///
+/// Returns `None` if the sector is not an MBR, e.g., the boot sector of a filesystem.
+fn parse_mbr(sector: &[u8; SECTOR_SIZE]) -> Option<[MbrEntry; MBR_NR_ENTRIES]> {
+    if sector[MBR_SIGNATURE_OFFSET..MBR_SIGNATURE_OFFSET + 2] != [0x55, 0xAA] {
+        return None;
+    }
+    let mut entries = [MbrEntry {
+        type_: 0,
+        start: 0,
+        nr_sectors: 0,
+    }; MBR_NR_ENTRIES];
+    for (index, entry) in entries.iter_mut().enumerate() {
+        let raw = &sector[MBR_ENTRIES_OFFSET + index * MBR_ENTRY_SIZE..][..MBR_ENTRY_SIZE];
+        // The boot indicator of a valid entry is either 0x00 or 0x80.
+        if raw[0] & 0x7F != 0 {
+            return None;
+        }
+        *entry = MbrEntry {
+            type_: raw[4],
+            start: read_u32(raw, 8),
+            nr_sectors: read_u32(raw, 12),
+        };
+    }
+    Some(entries)
+}
+
+/// Scans the logical partitions in the extended partition by following the EBR chain.
+///
+/// The start of a logical partition is relative to its EBR, while the start of
+/// the next EBR is relative to the extended partition.
+fn scan_extended<F>(
+    read_sectors: &F,
+    nr_sectors: u64,
+    extended: &MbrEntry,
+    partitions: &mut Vec<PartitionInfo>,
+) -> ostd::Result<()>
+where
+    F: Fn(u64, &mut [u8]) -> ostd::Result<()>,
+{
+    let extended_start = extended.start as u64;
+    let extended_end = extended_start + extended.nr_sectors as u64;
+    let mut ebr_sid = extended_start;
+    let mut number = MBR_FIRST_LOGICAL_NUMBER;
+    while number < MBR_FIRST_LOGICAL_NUMBER + MBR_MAX_LOGICAL_PARTITIONS {
+        if ebr_sid >= extended_end.min(nr_sectors) {
+            log::warn!("the EBR is beyond the extended partition");
+            break;
+        }
+        let mut ebr = [0u8; SECTOR_SIZE];
+        read_sectors(ebr_sid, &mut ebr)?;
+        let Some(entries) = parse_mbr(&ebr) else {
+            break;
+        };
+
+        let logical = &entries[0];
+        if !logical.is_empty() {
+            push_partition(
+                partitions,
+                number,
+                ebr_sid + logical.start as u64,
+                logical.nr_sectors as u64,
+                extended_end.min(nr_sectors),
+            );
+            number += 1;
+        }
+
+        let next = &entries[1];
+        if next.is_empty() || !next.is_extended() {
+            break;
+        }
+        let next_sid = extended_start + next.start as u64;
+        if next_sid <= ebr_sid {
+            log::warn!("the EBR chain goes backwards, stop scanning");
+            break;
+        }
+        ebr_sid = next_sid;
+    }
+    Ok(())
+}
+
+const GPT_SIGNATURE: &[u8; 8] = b"EFI PART";
+const GPT_MIN_HEADER_SIZE: usize = 92;
+const GPT_MIN_ENTRY_SIZE: usize = 128;
+/// The maximum size of the partition entry array to read.
+const GPT_MAX_ENTRIES_SIZE: usize = 1024 * 1024;
+
+/// Scans the GPT, falling back to the backup GPT at the last sector
+/// if the primary one is corrupted.
+fn scan_gpt<F>(read_sectors: &F, nr_sectors: u64) -> ostd::Result<Vec<PartitionInfo>>
+where
+    F: Fn(u64, &mut [u8]) -> ostd::Result<()>,
+{
+    for header_sid in [1, nr_sectors - 1] {
+        if let Some(partitions) = parse_gpt(read_sectors, nr_sectors, header_sid)? {
+            return Ok(partitions);
+        }
+        log::warn!("the GPT header at sector {} is invalid", header_sid);
+    }
+    Ok(Vec::new())
+}
+
+/// Parses the GPT whose header is at `header_sid`.
+///
+/// Returns `None` if the header or the partition entries are invalid.
+fn parse_gpt<F>(
+    read_sectors: &F,
+    nr_sectors: u64,
+    header_sid: u64,
+) -> ostd::Result<Option<Vec<PartitionInfo>>>
+where
+    F: Fn(u64, &mut [u8]) -> ostd::Result<()>,
+{
+    let mut header = [0u8; SECTOR_SIZE];
+    read_sectors(header_sid, &mut header)?;
+    if &header[0..8] != GPT_SIGNATURE {
+        return Ok(None);
+    }
+    let header_size = read_u32(&header, 12) as usize;
+    if !(GPT_MIN_HEADER_SIZE..=SECTOR_SIZE).contains(&header_size) {
+        return Ok(None);
+    }
+    let header_crc = read_u32(&header, 16);
+    header[16..20].fill(0);
+    if crc32(&header[..header_size]) != header_crc {
+        return Ok(None);
+    }
+    if read_u64(&header, 24) != header_sid {
+        return Ok(None);
+    }
+
+    let entries_sid = read_u64(&header, 72);
+    let nr_entries = read_u32(&header, 80) as usize;
+    let entry_size = read_u32(&header, 84) as usize;
+    let entries_crc = read_u32(&header, 88);
+    if entry_size < GPT_MIN_ENTRY_SIZE || entry_size % 8 != 0 {
+        return Ok(None);
+    }
+    let Some(entries_size) = nr_entries
+        .checked_mul(entry_size)
+        .filter(|size| *size <= GPT_MAX_ENTRIES_SIZE)
+    else {
+        return Ok(None);
+    };
+    let entries_nr_sectors = entries_size.div_ceil(SECTOR_SIZE) as u64;
+    if entries_sid
+        .checked_add(entries_nr_sectors)
+        .map_or(true, |end| end > nr_sectors)
+    {
+        return Ok(None);
+    }
+
+    let mut entries = vec![0u8; entries_nr_sectors as usize * SECTOR_SIZE];
+    read_sectors(entries_sid, &mut entries)?;
+    let entries = &entries[..entries_size];
+    if crc32(entries) != entries_crc {
+        return Ok(None);
+    }
+
+    let mut partitions = Vec::new();
+    for (index, entry) in entries.chunks_exact(entry_size).enumerate() {
+        // An unused entry has a zeroed partition type GUID.
+        if entry[0..16].iter().all(|byte| *byte == 0) {
+            continue;
+        }
+        let first = read_u64(entry, 32);
+        let last = read_u64(entry, 40);
+        if first > last {
+            log::warn!("the GPT partition {} is invalid, ignored", index + 1);
+            continue;
+        }
+        push_partition(
+            &mut partitions,
+            index as u32 + 1,
+            first,
+            last - first + 1,
+            nr_sectors,
+        );
+    }
+    Ok(Some(partitions))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    fn scan_image(image: &[u8]) -> Vec<PartitionInfo> {
+        let read_sectors = |sid: u64, buf: &mut [u8]| {
+            let offset = sid as usize * SECTOR_SIZE;
+            buf.copy_from_slice(&image[offset..offset + buf.len()]);
+            Ok(())
+        };
+        scan_with(read_sectors, (image.len() / SECTOR_SIZE) as u64).unwrap()
+    }
+
+    fn write_mbr_entry(sector: &mut [u8], index: usize, type_: u8, start: u32, len: u32) {
+        let raw = &mut sector[MBR_ENTRIES_OFFSET + index * MBR_ENTRY_SIZE..][..MBR_ENTRY_SIZE];
+        raw[4] = type_;
+        raw[8..12].copy_from_slice(&start.to_le_bytes());
+        raw[12..16].copy_from_slice(&len.to_le_bytes());
+        sector[MBR_SIGNATURE_OFFSET] = 0x55;
+        sector[MBR_SIGNATURE_OFFSET + 1] = 0xAA;
+    }
+
+    fn info(number: u32, start: u64, nr_sectors: usize) -> PartitionInfo {
+        PartitionInfo {
+            number,
+            start_sid: Sid::new(start),
+            nr_sectors,
+        }
+    }
+
+    #[ktest]
+    fn mbr_with_logical_partitions() {
+        let mut image = vec![0u8; 64 * SECTOR_SIZE];
+        write_mbr_entry(&mut image[..SECTOR_SIZE], 0, 0x83, 2, 8);
+        write_mbr_entry(&mut image[..SECTOR_SIZE], 1, 0x05, 16, 48);
+        // The first EBR at sector 16 and the second one at sector 32.
+        let ebr = &mut image[16 * SECTOR_SIZE..17 * SECTOR_SIZE];
+        write_mbr_entry(ebr, 0, 0x83, 1, 8);
+        write_mbr_entry(ebr, 1, 0x05, 16, 16);
+        let ebr = &mut image[32 * SECTOR_SIZE..33 * SECTOR_SIZE];
+        write_mbr_entry(ebr, 0, 0x83, 2, 14);
+
+        let partitions = scan_image(&image);
+        assert_eq!(
+            partitions,
+            vec![info(1, 2, 8), info(5, 17, 8), info(6, 34, 14)]
+        );
+    }
+
+    #[ktest]
+    fn gpt_with_corrupted_primary_header() {
+        const NR_SECTORS: usize = 64;
+        let mut image = vec![0u8; NR_SECTORS * SECTOR_SIZE];
+        write_mbr_entry(&mut image[..SECTOR_SIZE], 0, 0xEE, 1, NR_SECTORS as u32 - 1);
+
+        // One used entry among four in the backup entry array at sector 60.
+        let mut entries = vec![0u8; 4 * 128];
+        entries[128..144].fill(0xAB);
+        entries[128 + 32..128 + 40].copy_from_slice(&10u64.to_le_bytes());
+        entries[128 + 40..128 + 48].copy_from_slice(&19u64.to_le_bytes());
+        image[60 * SECTOR_SIZE..61 * SECTOR_SIZE].copy_from_slice(&entries);
+
+        let mut header = [0u8; SECTOR_SIZE];
+        header[0..8].copy_from_slice(GPT_SIGNATURE);
+        header[12..16].copy_from_slice(&92u32.to_le_bytes());
+        header[24..32].copy_from_slice(&(NR_SECTORS as u64 - 1).to_le_bytes());
+        header[72..80].copy_from_slice(&60u64.to_le_bytes());
+        header[80..84].copy_from_slice(&4u32.to_le_bytes());
+        header[84..88].copy_from_slice(&128u32.to_le_bytes());
+        header[88..92].copy_from_slice(&crc32(&entries).to_le_bytes());
+        let header_crc = crc32(&header[..92]);
+        header[16..20].copy_from_slice(&header_crc.to_le_bytes());
+        image[(NR_SECTORS - 1) * SECTOR_SIZE..].copy_from_slice(&header);
+
+        // The primary header has a wrong checksum.
+        image[SECTOR_SIZE..2 * SECTOR_SIZE].copy_from_slice(&header);
+        image[SECTOR_SIZE + 16] ^= 1;
+
+        let partitions = scan_image(&image);
+        assert_eq!(partitions, vec![info(2, 10, 10)]);
+    }
+}
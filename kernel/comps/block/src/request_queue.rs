@@ -1,23 +1,27 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use ostd::sync::{Mutex, WaitQueue};
+use ostd::sync::{SpinLock, WaitQueue};
 
 use super::{
     bio::{BioEnqueueError, BioType, SubmittedBio},
     id::Sid,
+    scheduler::{IoScheduler, NoopScheduler},
 };
 use crate::prelude::*;
 
-/// A simple block I/O request queue backed by one internal FIFO queue.
+/// A simple block I/O request queue, whose requests are managed by an I/O scheduler.
 ///
-/// It is a FIFO producer-consumer queue, where the producer (e.g., filesystem)
+/// It is a producer-consumer queue, where the producer (e.g., filesystem)
 /// submits requests to the queue, and the consumer (e.g., block device driver)
 /// continuously consumes and processes these requests from the queue.
 ///
-/// It supports merging the new request with the front request if if the type
-/// is same and the sector range is contiguous.
+/// The scheduler decides how the submitted bios are merged into the requests and
+/// the order to dispatch the requests. It can be switched at runtime.
+///
+/// The requests can be dequeued in the interrupt context by `try_dequeue`,
+/// e.g., to refill the device queue when the previous requests are completed.
 pub struct BioRequestSingleQueue {
-    queue: Mutex<VecDeque<BioRequest>>,
+    scheduler: SpinLock<Box<dyn IoScheduler>>,
     num_requests: AtomicUsize,
     wait_queue: WaitQueue,
     max_nr_segments_per_bio: usize,
@@ -31,8 +35,14 @@ impl BioRequestSingleQueue {
 
     /// Creates an empty queue with the upper bound for the number of segments in a bio.
     pub fn with_max_nr_segments_per_bio(max_nr_segments_per_bio: usize) -> Self {
+        Self::with_scheduler(max_nr_segments_per_bio, Box::new(NoopScheduler::new()))
+    }
+
+    /// Creates an empty queue with the upper bound for the number of segments in a bio
+    /// and the I/O scheduler.
+    pub fn with_scheduler(max_nr_segments_per_bio: usize, scheduler: Box<dyn IoScheduler>) -> Self {
         Self {
-            queue: Mutex::new(VecDeque::new()),
+            scheduler: SpinLock::new(scheduler),
             num_requests: AtomicUsize::new(0),
             wait_queue: WaitQueue::new(),
             max_nr_segments_per_bio,
@@ -49,11 +59,30 @@ impl BioRequestSingleQueue {
         self.num_requests.load(Ordering::Relaxed)
     }
 
+    /// Returns the name of the I/O scheduler.
+    pub fn scheduler_name(&self) -> &'static str {
+        self.scheduler.lock_irq_disabled().name()
+    }
+
+    /// Switches to the new I/O scheduler.
+    ///
+    /// The pending requests are moved to the new scheduler.
+    pub fn set_scheduler(&self, mut new_scheduler: Box<dyn IoScheduler>) {
+        let mut scheduler = self.scheduler.lock_irq_disabled();
+        while let Some(request) = scheduler.dispatch() {
+            for bio in request.bios {
+                new_scheduler.insert(bio, self.max_nr_segments_per_bio);
+            }
+        }
+        *scheduler = new_scheduler;
+        self.num_requests
+            .store(scheduler.nr_requests(), Ordering::Relaxed);
+    }
+
     /// Enqueues a `SubmittedBio` to this queue.
     ///
-    /// When enqueueing the `SubmittedBio`, try to insert it into the last request if the
-    /// type is same and the sector range is contiguous.
-    /// Otherwise, creates and inserts a new request for the `SubmittedBio`.
+    /// The `SubmittedBio` is merged into a pending request by the scheduler if possible.
+    /// Otherwise, a new request is created for the `SubmittedBio`.
     ///
     /// This method will wake up the waiter if a new `BioRequest` is enqueued.
     pub fn enqueue(&self, bio: SubmittedBio) -> Result<(), BioEnqueueError> {
@@ -61,25 +90,28 @@ impl BioRequestSingleQueue {
             return Err(BioEnqueueError::TooBig);
         }
 
-        let mut queue = self.queue.lock();
-        if let Some(request) = queue.front_mut() {
-            if request.can_merge(&bio)
-                && request.num_segments() + bio.segments().len() <= self.max_nr_segments_per_bio
-            {
-                request.merge_bio(bio);
-                return Ok(());
-            }
-        }
-
-        let new_request = BioRequest::from(bio);
-        queue.push_front(new_request);
-        self.inc_num_requests();
-        drop(queue);
+        let mut scheduler = self.scheduler.lock_irq_disabled();
+        let old_num_requests = scheduler.nr_requests();
+        scheduler.insert(bio, self.max_nr_segments_per_bio);
+        let num_requests = scheduler.nr_requests();
+        self.num_requests.store(num_requests, Ordering::Relaxed);
+        drop(scheduler);
 
-        self.wait_queue.wake_all();
+        if num_requests > old_num_requests {
+            self.wait_queue.wake_all();
+        }
         Ok(())
     }
 
+    /// Dequeues a `BioRequest` from this queue without waiting.
+    pub fn try_dequeue(&self) -> Option<BioRequest> {
+        let mut scheduler = self.scheduler.lock_irq_disabled();
+        let request = scheduler.dispatch()?;
+        self.num_requests
+            .store(scheduler.nr_requests(), Ordering::Relaxed);
+        Some(request)
+    }
+
     /// Dequeues a `BioRequest` from this queue.
     ///
     /// This method will wait until one request can be retrieved.
@@ -88,9 +120,7 @@ impl BioRequestSingleQueue {
 
         loop {
             if num_requests > 0 {
-                let mut queue = self.queue.lock();
-                if let Some(request) = queue.pop_back() {
-                    self.dec_num_requests();
+                if let Some(request) = self.try_dequeue() {
                     return request;
                 }
             }
@@ -105,14 +135,6 @@ impl BioRequestSingleQueue {
             });
         }
     }
-
-    fn dec_num_requests(&self) {
-        self.num_requests.fetch_sub(1, Ordering::Relaxed);
-    }
-
-    fn inc_num_requests(&self) {
-        self.num_requests.fetch_add(1, Ordering::Relaxed);
-    }
 }
 
 impl Default for BioRequestSingleQueue {
@@ -125,7 +147,7 @@ impl Debug for BioRequestSingleQueue {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         f.debug_struct("BioRequestSingleQueue")
             .field("num_requests", &self.num_requests())
-            .field("queue", &self.queue.lock())
+            .field("scheduler", &self.scheduler_name())
             .finish()
     }
 }
@@ -216,3 +238,62 @@ impl From<SubmittedBio> for BioRequest {
         }
     }
 }
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+    use crate::{bio::Bio, scheduler::DeadlineScheduler, BlockDevice};
+
+    #[derive(Debug)]
+    struct QueueDevice(BioRequestSingleQueue);
+
+    impl BlockDevice for QueueDevice {
+        fn enqueue(&self, bio: SubmittedBio) -> Result<(), BioEnqueueError> {
+            self.0.enqueue(bio)
+        }
+
+        fn max_nr_segments_per_bio(&self) -> usize {
+            usize::MAX
+        }
+
+        fn nr_sectors(&self) -> usize {
+            usize::MAX
+        }
+    }
+
+    fn submit_discard(device: &QueueDevice, start: u64) {
+        let bio = Bio::new_dataless(BioType::Discard, Sid::new(start)..Sid::new(start + 8), None);
+        bio.submit(device).unwrap();
+    }
+
+    #[ktest]
+    fn try_dequeue_without_waiting() {
+        let device = QueueDevice(BioRequestSingleQueue::new());
+        assert!(device.0.try_dequeue().is_none());
+
+        submit_discard(&device, 0);
+        assert_eq!(device.0.num_requests(), 1);
+        assert!(device.0.try_dequeue().is_some());
+        assert_eq!(device.0.num_requests(), 0);
+        assert!(device.0.try_dequeue().is_none());
+    }
+
+    #[ktest]
+    fn switch_scheduler_with_pending_requests() {
+        let device = QueueDevice(BioRequestSingleQueue::new());
+        submit_discard(&device, 200);
+        submit_discard(&device, 100);
+
+        device.0.set_scheduler(Box::new(DeadlineScheduler::new()));
+        assert_eq!(device.0.scheduler_name(), DeadlineScheduler::NAME);
+        assert_eq!(device.0.num_requests(), 2);
+
+        // The requests are now dispatched in the sector order.
+        let starts: Vec<u64> = core::iter::from_fn(|| device.0.try_dequeue())
+            .map(|request| request.sid_range().start.to_raw())
+            .collect();
+        assert_eq!(starts, vec![100, 200]);
+    }
+}
@@ -0,0 +1,121 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use ostd::{task::current_task, trap::in_interrupt_context};
+
+use super::{try_merge, IoScheduler};
+use crate::{bio::SubmittedBio, prelude::*, request_queue::BioRequest};
+
+/// A scheduler that shares the device fairly among the submitting tasks.
+///
+/// The requests are queued per submitting task, which is called a flow.
+/// The flows are served in the round-robin order, and each flow dispatches its
+/// requests until it runs out of its budget of sectors. Thus a task issuing
+/// lots of I/O, e.g., a writeback task, cannot starve the other tasks.
+///
+/// Unlike the BFQ scheduler of Linux, the budgets are fixed and are not
+/// weighted, and an idle flow is not waited for.
+#[derive(Debug, Default)]
+pub struct BudgetFairScheduler {
+    /// The flows with pending requests, in which the front one is being served
+    flows: VecDeque<Flow>,
+    nr_requests: usize,
+}
+
+#[derive(Debug)]
+struct Flow {
+    /// The ID of the submitting task
+    id: u64,
+    requests: VecDeque<BioRequest>,
+    /// The number of sectors that the flow can still dispatch in its turn
+    budget: usize,
+}
+
+impl Flow {
+    fn new(id: u64) -> Self {
+        Self {
+            id,
+            requests: VecDeque::new(),
+            budget: BudgetFairScheduler::BUDGET,
+        }
+    }
+}
+
+impl BudgetFairScheduler {
+    pub const NAME: &'static str = "budget-fair";
+
+    /// The number of sectors that a flow can dispatch in its turn.
+    const BUDGET: usize = 2048;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the flow ID of the current task.
+    ///
+    /// The bios submitted in the interrupt context, e.g., by the completion
+    /// of other bios, do not belong to the interrupted task and share flow 0.
+    fn current_flow_id() -> u64 {
+        if in_interrupt_context() {
+            return 0;
+        }
+        current_task().map_or(0, |task| task.id())
+    }
+}
+
+impl IoScheduler for BudgetFairScheduler {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn insert(&mut self, bio: SubmittedBio, max_nr_segments: usize) {
+        // The bio can be merged into the requests of any flow.
+        let requests = self
+            .flows
+            .iter_mut()
+            .flat_map(|flow| flow.requests.iter_mut());
+        let Err(bio) = try_merge(requests, bio, max_nr_segments) else {
+            return;
+        };
+
+        let id = Self::current_flow_id();
+        let flow = match self.flows.iter().position(|flow| flow.id == id) {
+            Some(index) => &mut self.flows[index],
+            None => {
+                self.flows.push_back(Flow::new(id));
+                self.flows.back_mut().unwrap()
+            }
+        };
+        flow.requests.push_back(BioRequest::from(bio));
+        self.nr_requests += 1;
+    }
+
+    fn dispatch(&mut self) -> Option<BioRequest> {
+        loop {
+            let flow = self.flows.front_mut()?;
+            if flow.budget == 0 {
+                // Move to the next flow, and recharge the budget for the next turn.
+                flow.budget = Self::BUDGET;
+                self.flows.rotate_left(1);
+                continue;
+            }
+            let Some(request) = flow.requests.pop_front() else {
+                self.flows.pop_front();
+                continue;
+            };
+
+            // A request costs at least one sector, e.g., a flush.
+            let range = request.sid_range();
+            let cost = (range.end.to_raw() - range.start.to_raw()).max(1) as usize;
+            flow.budget = flow.budget.saturating_sub(cost);
+            if flow.requests.is_empty() {
+                self.flows.pop_front();
+            }
+            self.nr_requests -= 1;
+            return Some(request);
+        }
+    }
+
+    fn nr_requests(&self) -> usize {
+        self.nr_requests
+    }
+}
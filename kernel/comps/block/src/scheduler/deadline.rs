@@ -0,0 +1,187 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::time::Duration;
+
+use ostd::arch::timer::Jiffies;
+
+use super::{try_merge, IoScheduler};
+use crate::{
+    bio::{BioType, SubmittedBio},
+    id::Sid,
+    prelude::*,
+    request_queue::BioRequest,
+};
+
+/// A scheduler that dispatches the requests in the sector order with deadlines.
+///
+/// The reads and writes are kept in separate FIFO queues. The requests are dispatched
+/// in batches, each of which sweeps one of the queues in the ascending sector order.
+/// A batch starts from the oldest request if it has expired. Reads are preferred over
+/// writes since the readers are usually waiting, but the writes are not starved.
+#[derive(Debug)]
+pub struct DeadlineScheduler {
+    queues: [VecDeque<PendingRequest>; 2],
+    /// The direction of the current batch
+    batch_direction: Option<Direction>,
+    /// The number of requests dispatched in the current batch
+    batch_count: usize,
+    /// The number of batches of reads dispatched while there are pending writes
+    starved: usize,
+    /// The sector following the last dispatched request
+    next_sid: Sid,
+}
+
+#[derive(Debug)]
+struct PendingRequest {
+    request: BioRequest,
+    deadline: Duration,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Direction {
+    Read = 0,
+    Write = 1,
+}
+
+impl Direction {
+    fn of(type_: BioType) -> Self {
+        match type_ {
+            BioType::Read => Self::Read,
+            _ => Self::Write,
+        }
+    }
+
+    fn expire(self) -> Duration {
+        match self {
+            Self::Read => DeadlineScheduler::READ_EXPIRE,
+            Self::Write => DeadlineScheduler::WRITE_EXPIRE,
+        }
+    }
+}
+
+impl DeadlineScheduler {
+    pub const NAME: &'static str = "deadline";
+
+    /// The maximum time that a read waits before being dispatched.
+    const READ_EXPIRE: Duration = Duration::from_millis(500);
+    /// The maximum time that a write waits before being dispatched.
+    const WRITE_EXPIRE: Duration = Duration::from_secs(5);
+    /// The maximum number of requests in a batch.
+    const FIFO_BATCH: usize = 16;
+    /// The maximum number of read batches before a write batch.
+    const WRITES_STARVED: usize = 2;
+
+    pub fn new() -> Self {
+        Self {
+            queues: [VecDeque::new(), VecDeque::new()],
+            batch_direction: None,
+            batch_count: 0,
+            starved: 0,
+            next_sid: Sid::new(0),
+        }
+    }
+
+    fn queue(&self, direction: Direction) -> &VecDeque<PendingRequest> {
+        &self.queues[direction as usize]
+    }
+
+    /// Returns whether the oldest request in the direction has expired.
+    fn is_expired(&self, direction: Direction, now: Duration) -> bool {
+        self.queue(direction)
+            .front()
+            .is_some_and(|pending| pending.deadline <= now)
+    }
+
+    /// Returns the index of the request that follows the last dispatched one
+    /// in the sector order.
+    fn next_in_order(&self, direction: Direction) -> Option<usize> {
+        self.queue(direction)
+            .iter()
+            .enumerate()
+            .filter(|(_, pending)| pending.request.sid_range().start >= self.next_sid)
+            .min_by_key(|(_, pending)| pending.request.sid_range().start)
+            .map(|(index, _)| index)
+    }
+
+    /// Selects the direction of a new batch.
+    fn select_direction(&mut self, now: Duration) -> Option<Direction> {
+        let has_reads = !self.queue(Direction::Read).is_empty();
+        let has_writes = !self.queue(Direction::Write).is_empty();
+        if has_reads
+            && (!has_writes
+                || (self.starved < Self::WRITES_STARVED && !self.is_expired(Direction::Write, now)))
+        {
+            if has_writes {
+                self.starved += 1;
+            }
+            return Some(Direction::Read);
+        }
+        if has_writes {
+            self.starved = 0;
+            return Some(Direction::Write);
+        }
+        None
+    }
+
+    fn take(&mut self, direction: Direction, index: usize) -> BioRequest {
+        let pending = self.queues[direction as usize].remove(index).unwrap();
+        self.next_sid = pending.request.sid_range().end;
+        self.batch_count += 1;
+        pending.request
+    }
+}
+
+impl Default for DeadlineScheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IoScheduler for DeadlineScheduler {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn insert(&mut self, bio: SubmittedBio, max_nr_segments: usize) {
+        let direction = Direction::of(bio.type_());
+        let queue = &mut self.queues[direction as usize];
+        let requests = queue.iter_mut().map(|pending| &mut pending.request);
+        if let Err(bio) = try_merge(requests, bio, max_nr_segments) {
+            queue.push_back(PendingRequest {
+                request: BioRequest::from(bio),
+                deadline: Jiffies::elapsed().as_duration() + direction.expire(),
+            });
+        }
+    }
+
+    fn dispatch(&mut self) -> Option<BioRequest> {
+        // Continue the current batch if possible.
+        let batch_direction = self
+            .batch_direction
+            .filter(|_| self.batch_count < Self::FIFO_BATCH);
+        if let Some(direction) = batch_direction {
+            if let Some(index) = self.next_in_order(direction) {
+                return Some(self.take(direction, index));
+            }
+        }
+
+        let now = Jiffies::elapsed().as_duration();
+        let direction = self.select_direction(now)?;
+        let index = if self.is_expired(direction, now) {
+            0
+        } else {
+            // Wrap around to the lowest sector if no request follows the last one.
+            self.next_in_order(direction).unwrap_or_else(|| {
+                self.next_sid = Sid::new(0);
+                self.next_in_order(direction).unwrap()
+            })
+        };
+        self.batch_direction = Some(direction);
+        self.batch_count = 0;
+        Some(self.take(direction, index))
+    }
+
+    fn nr_requests(&self) -> usize {
+        self.queues.iter().map(|queue| queue.len()).sum()
+    }
+}
@@ -0,0 +1,158 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The I/O schedulers of the block request queues.
+//!
+//! An I/O scheduler holds the pending requests of a block device. It decides how the
+//! submitted bios are merged into the requests and in which order the requests are
+//! dispatched to the device driver.
+//!
+//! The following schedulers are provided:
+//! - `NoopScheduler`: dispatches the requests in the FIFO order.
+//! - `DeadlineScheduler`: dispatches the requests in the sector order, while preferring
+//!   reads and ensuring that no request waits longer than its deadline.
+//! - `BudgetFairScheduler`: shares the device among the submitting tasks, each of which
+//!   is allowed to dispatch a budget of sectors in turn.
+
+mod budget_fair;
+mod deadline;
+mod noop;
+
+pub use self::{
+    budget_fair::BudgetFairScheduler, deadline::DeadlineScheduler, noop::NoopScheduler,
+};
+use crate::{bio::SubmittedBio, prelude::*, request_queue::BioRequest};
+
+/// An I/O scheduler.
+pub trait IoScheduler: Send + Debug {
+    /// Returns the name of the scheduler.
+    fn name(&self) -> &'static str;
+
+    /// Inserts the bio, which is merged into a pending request if possible.
+    ///
+    /// The merged request must not have more than `max_nr_segments` segments.
+    fn insert(&mut self, bio: SubmittedBio, max_nr_segments: usize);
+
+    /// Removes and returns the next request to be dispatched.
+    fn dispatch(&mut self) -> Option<BioRequest>;
+
+    /// Returns the number of the pending requests.
+    fn nr_requests(&self) -> usize;
+}
+
+/// The names of the available schedulers.
+pub const SCHEDULER_NAMES: [&str; 3] = [
+    NoopScheduler::NAME,
+    DeadlineScheduler::NAME,
+    BudgetFairScheduler::NAME,
+];
+
+/// Creates a scheduler by its name.
+pub fn new_scheduler(name: &str) -> Option<Box<dyn IoScheduler>> {
+    let scheduler: Box<dyn IoScheduler> = match name {
+        NoopScheduler::NAME => Box::new(NoopScheduler::new()),
+        DeadlineScheduler::NAME => Box::new(DeadlineScheduler::new()),
+        BudgetFairScheduler::NAME => Box::new(BudgetFairScheduler::new()),
+        _ => return None,
+    };
+    Some(scheduler)
+}
+
+/// Tries to merge the bio into one of the `requests`, which need not be adjacent
+/// to each other in the queue.
+///
+/// Returns the bio back if it cannot be merged.
+fn try_merge<'a>(
+    requests: impl Iterator<Item = &'a mut BioRequest>,
+    bio: SubmittedBio,
+    max_nr_segments: usize,
+) -> Result<(), SubmittedBio> {
+    for request in requests {
+        if request.can_merge(&bio)
+            && request.num_segments() + bio.segments().len() <= max_nr_segments
+        {
+            request.merge_bio(bio);
+            return Ok(());
+        }
+    }
+    Err(bio)
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+    use crate::{
+        bio::{Bio, BioEnqueueError, BioType},
+        id::Sid,
+        BlockDevice,
+    };
+
+    /// A device that inserts the submitted bios into the scheduler.
+    #[derive(Debug)]
+    struct SchedulerDevice(ostd::sync::Mutex<Box<dyn IoScheduler>>);
+
+    impl BlockDevice for SchedulerDevice {
+        fn enqueue(&self, bio: SubmittedBio) -> Result<(), BioEnqueueError> {
+            self.0.lock().insert(bio, usize::MAX);
+            Ok(())
+        }
+
+        fn max_nr_segments_per_bio(&self) -> usize {
+            usize::MAX
+        }
+
+        fn nr_sectors(&self) -> usize {
+            usize::MAX
+        }
+    }
+
+    fn submit_discard(device: &SchedulerDevice, range: Range<u64>) {
+        let bio = Bio::new_dataless(
+            BioType::Discard,
+            Sid::new(range.start)..Sid::new(range.end),
+            None,
+        );
+        bio.submit(device).unwrap();
+    }
+
+    fn dispatched_ranges(device: &SchedulerDevice) -> Vec<Range<u64>> {
+        let mut scheduler = device.0.lock();
+        let mut ranges = Vec::new();
+        while let Some(request) = scheduler.dispatch() {
+            let range = request.sid_range();
+            ranges.push(range.start.to_raw()..range.end.to_raw());
+        }
+        assert_eq!(scheduler.nr_requests(), 0);
+        ranges
+    }
+
+    #[ktest]
+    fn merge_non_adjacent_requests() {
+        for name in SCHEDULER_NAMES {
+            let device = SchedulerDevice(ostd::sync::Mutex::new(new_scheduler(name).unwrap()));
+            submit_discard(&device, 8..16);
+            submit_discard(&device, 100..108);
+            // Merged into the first request at the back and then at the front.
+            submit_discard(&device, 16..24);
+            submit_discard(&device, 0..8);
+            assert_eq!(device.0.lock().nr_requests(), 2);
+
+            let mut ranges = dispatched_ranges(&device);
+            ranges.sort_by_key(|range| range.start);
+            assert_eq!(ranges, vec![0..24, 100..108]);
+        }
+    }
+
+    #[ktest]
+    fn deadline_dispatch_in_sector_order() {
+        let device = SchedulerDevice(ostd::sync::Mutex::new(Box::new(DeadlineScheduler::new())));
+        submit_discard(&device, 300..308);
+        submit_discard(&device, 100..108);
+        submit_discard(&device, 200..208);
+        assert_eq!(
+            dispatched_ranges(&device),
+            vec![100..108, 200..208, 300..308]
+        );
+    }
+}
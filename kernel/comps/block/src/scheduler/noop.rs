@@ -0,0 +1,39 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::{try_merge, IoScheduler};
+use crate::{bio::SubmittedBio, prelude::*, request_queue::BioRequest};
+
+/// A scheduler that dispatches the requests in the FIFO order.
+#[derive(Debug, Default)]
+pub struct NoopScheduler {
+    requests: VecDeque<BioRequest>,
+}
+
+impl NoopScheduler {
+    pub const NAME: &'static str = "none";
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl IoScheduler for NoopScheduler {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn insert(&mut self, bio: SubmittedBio, max_nr_segments: usize) {
+        // Try the most recent requests first, which are the most likely to be merged.
+        if let Err(bio) = try_merge(self.requests.iter_mut().rev(), bio, max_nr_segments) {
+            self.requests.push_back(BioRequest::from(bio));
+        }
+    }
+
+    fn dispatch(&mut self) -> Option<BioRequest> {
+        self.requests.pop_front()
+    }
+
+    fn nr_requests(&self) -> usize {
+        self.requests.len()
+    }
+}
@@ -0,0 +1,64 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The utilities shared by the tests of the block devices.
+
+use core::sync::atomic::AtomicBool;
+
+use ostd::sync::SpinLock;
+
+use crate::{
+    bio::{BioEnqueueError, BioStatus, BioType, SubmittedBio},
+    prelude::*,
+    BlockDevice, SECTOR_SIZE,
+};
+
+/// A device in the memory, which can be broken to fail all the I/O.
+#[derive(Debug)]
+pub(crate) struct MemDevice {
+    pub(crate) data: SpinLock<Vec<u8>>,
+    pub(crate) is_broken: AtomicBool,
+}
+
+impl MemDevice {
+    /// Creates a device with the data, whose length is a multiple of the sector size.
+    pub(crate) fn new(data: Vec<u8>) -> Arc<Self> {
+        Arc::new(Self {
+            data: SpinLock::new(data),
+            is_broken: AtomicBool::new(false),
+        })
+    }
+}
+
+impl BlockDevice for MemDevice {
+    fn enqueue(&self, bio: SubmittedBio) -> Result<(), BioEnqueueError> {
+        if self.is_broken.load(Ordering::Relaxed) {
+            bio.complete(BioStatus::IoError);
+            return Ok(());
+        }
+        let mut offset = bio.sid_range().start.to_raw() as usize * SECTOR_SIZE;
+        let mut data = self.data.lock();
+        for segment in bio.segments() {
+            let bytes = &mut data[offset..offset + segment.nbytes()];
+            match bio.type_() {
+                BioType::Read => {
+                    segment.writer().write(&mut (&*bytes).into());
+                }
+                _ => {
+                    segment.reader().read(&mut bytes.into());
+                }
+            }
+            offset += segment.nbytes();
+        }
+        drop(data);
+        bio.complete(BioStatus::Complete);
+        Ok(())
+    }
+
+    fn max_nr_segments_per_bio(&self) -> usize {
+        usize::MAX
+    }
+
+    fn nr_sectors(&self) -> usize {
+        self.data.lock().len() / SECTOR_SIZE
+    }
+}
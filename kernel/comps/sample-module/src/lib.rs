@@ -0,0 +1,32 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! A sample component that can be loaded and unloaded at runtime.
+//!
+//! The component is not linked into the kernel. Instead, it is built as a
+//! relocatable object with the same compiler, flags and dependencies as the
+//! kernel, e.g.,
+//!
+//! ```text
+//! RUSTFLAGS="--cfg component_module" cargo rustc -p aster-sample-module \
+//!     --target x86_64-unknown-none -- --emit=obj -C codegen-units=1
+//! ```
+//!
+//! The kernel should be built with `MODULES=1`, which reserves the kernel
+//! symbol table to resolve the symbols of the module. The object is then
+//! loaded by `insmod` and unloaded by `rmmod`, which call the `init_module`
+//! and `delete_module` system calls.
+#![no_std]
+#![deny(unsafe_code)]
+
+use component::{exit_component, init_component, ComponentInitError};
+
+#[init_component]
+fn init() -> Result<(), ComponentInitError> {
+    log::info!("The sample module is loaded");
+    Ok(())
+}
+
+#[exit_component]
+fn exit() {
+    log::info!("The sample module is unloaded");
+}
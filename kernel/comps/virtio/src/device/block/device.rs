@@ -1,18 +1,20 @@
 // SPDX-License-Identifier: MPL-2.0
 
 use alloc::{boxed::Box, collections::BTreeMap, string::String, sync::Arc, vec, vec::Vec};
-use core::{fmt::Debug, hint::spin_loop, mem::size_of};
+use core::{fmt::Debug, hint::spin_loop, mem::size_of, ops::Range};
 
 use aster_block::{
     bio::{BioEnqueueError, BioStatus, BioType, SubmittedBio},
     request_queue::{BioRequest, BioRequestSingleQueue},
+    scheduler::{self, DeadlineScheduler},
 };
-use aster_util::safe_ptr::SafePtr;
+use aster_util::{field_ptr, safe_ptr::SafePtr};
 use id_alloc::IdAlloc;
 use log::info;
 use ostd::{
+    cpu::{num_cpus, this_cpu},
     io_mem::IoMem,
-    mm::{DmaDirection, DmaStream, DmaStreamSlice, FrameAllocOptions, VmIo},
+    mm::{DmaDirection, DmaStream, DmaStreamSlice, FrameAllocOptions, VmIo, PAGE_SIZE},
     sync::SpinLock,
     trap::TrapFrame,
 };
@@ -31,8 +33,6 @@ use crate::{
 #[derive(Debug)]
 pub struct BlockDevice {
     device: Arc<DeviceInner>,
-    /// The software staging queue.
-    queue: BioRequestSingleQueue,
 }
 
 impl BlockDevice {
@@ -41,97 +41,206 @@ impl BlockDevice {
         let device = DeviceInner::init(transport)?;
         let device_id = device.request_device_id();
 
-        let block_device = Arc::new(Self {
-            device,
-            // Each bio request includes an additional 1 request and 1 response descriptor,
-            // therefore this upper bound is set to (QUEUE_SIZE - 2).
-            queue: BioRequestSingleQueue::with_max_nr_segments_per_bio(
-                (DeviceInner::QUEUE_SIZE - 2) as usize,
-            ),
-        });
+        let block_device = Arc::new(Self { device });
 
         aster_block::register_device(device_id, block_device);
         Ok(())
     }
 
-    /// Dequeues a `BioRequest` from the software staging queue and
-    /// processes the request.
-    pub fn handle_requests(&self) {
-        let request = self.queue.dequeue();
-        info!("Handle Request: {:?}", request);
-        match request.type_() {
-            BioType::Read => self.device.read(request),
-            BioType::Write => self.device.write(request),
-            BioType::Flush | BioType::Discard => todo!(),
-        }
-    }
-
     /// Negotiate features for the device specified bits 0~23
     pub(crate) fn negotiate_features(features: u64) -> u64 {
-        let feature = BlockFeatures::from_bits(features).unwrap();
-        let support_features = BlockFeatures::from_bits(features).unwrap();
-        (feature & support_features).bits
+        let device_features = BlockFeatures::from_bits_truncate(features);
+        let supported_features = BlockFeatures::SIZE_MAX
+            | BlockFeatures::SEG_MAX
+            | BlockFeatures::GEOMETRY
+            | BlockFeatures::RO
+            | BlockFeatures::BLK_SIZE
+            | BlockFeatures::TOPOLOGY
+            | BlockFeatures::FLUSH
+            | BlockFeatures::MQ
+            | BlockFeatures::DISCARD
+            | BlockFeatures::WRITE_ZEROES;
+        (device_features & supported_features).bits
     }
 }
 
 impl aster_block::BlockDevice for BlockDevice {
+    /// Enqueues the bio and dispatches the requests to the virtqueue of the current CPU.
+    ///
+    /// The bio is completed by the interrupt handler of the virtqueue.
     fn enqueue(&self, bio: SubmittedBio) -> Result<(), BioEnqueueError> {
-        self.queue.enqueue(bio)
+        self.device.staging_queue.enqueue(bio)?;
+        self.device.dispatch(self.device.current_queue());
+        Ok(())
     }
 
     fn max_nr_segments_per_bio(&self) -> usize {
-        self.queue.max_nr_segments_per_bio()
+        self.device.staging_queue.max_nr_segments_per_bio()
+    }
+
+    fn nr_sectors(&self) -> usize {
+        field_ptr!(&self.device.config, VirtioBlockConfig, capacity)
+            .read()
+            .unwrap() as usize
+    }
+
+    fn io_scheduler(&self) -> Option<&'static str> {
+        Some(self.device.staging_queue.scheduler_name())
+    }
+
+    fn set_io_scheduler(&self, name: &str) -> ostd::Result<()> {
+        let scheduler = scheduler::new_scheduler(name).ok_or(ostd::Error::InvalidArgs)?;
+        self.device.staging_queue.set_scheduler(scheduler);
+        Ok(())
     }
 }
 
 #[derive(Debug)]
 struct DeviceInner {
     config: SafePtr<VirtioBlockConfig, IoMem>,
-    queue: SpinLock<VirtQueue>,
+    features: BlockFeatures,
+    /// The maximum number of sectors and segments in a discard request
+    discard_limits: (u32, u32),
+    /// The maximum number of sectors and segments in a write zeroes request
+    write_zeroes_limits: (u32, u32),
+    /// The software staging queue, whose requests are dispatched to the virtqueues
+    staging_queue: BioRequestSingleQueue,
+    /// The request virtqueues, one for each CPU if the device supports multiple queues
+    queues: Vec<RequestQueue>,
     transport: SpinLock<Box<dyn VirtioTransport>>,
+}
+
+/// A virtqueue for the block requests.
+#[derive(Debug)]
+struct RequestQueue {
     block_requests: DmaStream,
     block_responses: DmaStream,
-    id_allocator: SpinLock<IdAlloc>,
-    submitted_requests: SpinLock<BTreeMap<u16, SubmittedRequest>>,
+    inner: SpinLock<RequestQueueInner>,
 }
 
-impl DeviceInner {
-    const QUEUE_SIZE: u16 = 64;
+#[derive(Debug)]
+struct RequestQueueInner {
+    queue: VirtQueue,
+    id_allocator: IdAlloc,
+    submitted_requests: BTreeMap<u16, SubmittedRequest>,
+    /// The request dequeued from the staging queue, which waits for free descriptors
+    pending_request: Option<BioRequest>,
+}
 
-    /// Creates and inits the device.
-    pub fn init(mut transport: Box<dyn VirtioTransport>) -> Result<Arc<Self>, VirtioDeviceError> {
-        let config = VirtioBlockConfig::new(transport.as_mut());
-        let num_queues = transport.num_queues();
-        if num_queues != 1 {
-            return Err(VirtioDeviceError::QueuesAmountDoNotMatch(num_queues, 1));
-        }
-        let queue = VirtQueue::new(0, Self::QUEUE_SIZE, transport.as_mut())
-            .expect("create virtqueue failed");
+impl RequestQueue {
+    fn new(index: u16, transport: &mut dyn VirtioTransport) -> Result<Self, VirtioDeviceError> {
+        let queue = VirtQueue::new(index, DeviceInner::QUEUE_SIZE, transport)?;
         let block_requests = {
             let vm_segment = FrameAllocOptions::new(1).alloc_contiguous().unwrap();
             DmaStream::map(vm_segment, DmaDirection::Bidirectional, false).unwrap()
         };
-        assert!(Self::QUEUE_SIZE as usize * REQ_SIZE <= block_requests.nbytes());
+        assert!(DeviceInner::QUEUE_SIZE as usize * REQ_SIZE <= block_requests.nbytes());
         let block_responses = {
             let vm_segment = FrameAllocOptions::new(1).alloc_contiguous().unwrap();
             DmaStream::map(vm_segment, DmaDirection::Bidirectional, false).unwrap()
         };
-        assert!(Self::QUEUE_SIZE as usize * RESP_SIZE <= block_responses.nbytes());
+        assert!(DeviceInner::QUEUE_SIZE as usize * RESP_SIZE <= block_responses.nbytes());
 
-        let device = Arc::new(Self {
-            config,
-            queue: SpinLock::new(queue),
-            transport: SpinLock::new(transport),
+        Ok(Self {
             block_requests,
             block_responses,
-            id_allocator: SpinLock::new(IdAlloc::with_capacity(Self::QUEUE_SIZE as usize)),
-            submitted_requests: SpinLock::new(BTreeMap::new()),
-        });
+            inner: SpinLock::new(RequestQueueInner {
+                queue,
+                id_allocator: IdAlloc::with_capacity(DeviceInner::QUEUE_SIZE as usize),
+                submitted_requests: BTreeMap::new(),
+                pending_request: None,
+            }),
+        })
+    }
 
-        let cloned_device = device.clone();
-        let handle_irq = move |_: &TrapFrame| {
-            cloned_device.handle_irq();
+    /// Writes the request header of `id` and returns its slice.
+    fn req_slice(&self, id: usize, type_: ReqType, sector: u64) -> DmaStreamSlice<'_> {
+        let req_slice = DmaStreamSlice::new(&self.block_requests, id * REQ_SIZE, REQ_SIZE);
+        let req = BlockReq {
+            type_: type_ as _,
+            reserved: 0,
+            sector,
         };
+        req_slice.write_val(0, &req).unwrap();
+        req_slice.sync().unwrap();
+        req_slice
+    }
+
+    /// Resets the response of `id` and returns its slice.
+    fn resp_slice(&self, id: usize) -> DmaStreamSlice<'_> {
+        let resp_slice = DmaStreamSlice::new(&self.block_responses, id * RESP_SIZE, RESP_SIZE);
+        resp_slice.write_val(0, &BlockResp::default()).unwrap();
+        resp_slice
+    }
+}
+
+impl DeviceInner {
+    const QUEUE_SIZE: u16 = 64;
+
+    /// Creates and inits the device.
+    pub fn init(mut transport: Box<dyn VirtioTransport>) -> Result<Arc<Self>, VirtioDeviceError> {
+        let config = VirtioBlockConfig::new(transport.as_mut());
+        let features = BlockFeatures::from_bits_truncate(BlockDevice::negotiate_features(
+            transport.device_features(),
+        ));
+        let discard_limits = if features.contains(BlockFeatures::DISCARD) {
+            (
+                field_ptr!(&config, VirtioBlockConfig, max_discard_sectors)
+                    .read()
+                    .unwrap(),
+                field_ptr!(&config, VirtioBlockConfig, max_discard_seg)
+                    .read()
+                    .unwrap(),
+            )
+        } else {
+            (0, 0)
+        };
+        let write_zeroes_limits = if features.contains(BlockFeatures::WRITE_ZEROES) {
+            (
+                field_ptr!(&config, VirtioBlockConfig, max_write_zeroes_sectors)
+                    .read()
+                    .unwrap(),
+                field_ptr!(&config, VirtioBlockConfig, max_write_zeroes_seg)
+                    .read()
+                    .unwrap(),
+            )
+        } else {
+            (0, 0)
+        };
+
+        // Uses one virtqueue for each CPU at most.
+        let nr_queues = if features.contains(BlockFeatures::MQ) {
+            field_ptr!(&config, VirtioBlockConfig, num_queues)
+                .read()
+                .unwrap()
+                .clamp(1, num_cpus() as u16)
+        } else {
+            1
+        };
+        let num_queues = transport.num_queues();
+        if num_queues < nr_queues {
+            return Err(VirtioDeviceError::QueuesAmountDoNotMatch(
+                num_queues, nr_queues,
+            ));
+        }
+        let queues = (0..nr_queues)
+            .map(|index| RequestQueue::new(index, transport.as_mut()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let device = Arc::new(Self {
+            config,
+            features,
+            discard_limits,
+            write_zeroes_limits,
+            // Each bio request includes an additional 1 request and 1 response descriptor,
+            // therefore this upper bound is set to (QUEUE_SIZE - 2).
+            staging_queue: BioRequestSingleQueue::with_scheduler(
+                (Self::QUEUE_SIZE - 2) as usize,
+                Box::new(DeadlineScheduler::new()),
+            ),
+            queues,
+            transport: SpinLock::new(transport),
+        });
 
         let cloned_device = device.clone();
         let handle_config_change = move |_: &TrapFrame| {
@@ -143,41 +252,61 @@ impl DeviceInner {
             transport
                 .register_cfg_callback(Box::new(handle_config_change))
                 .unwrap();
-            transport
-                .register_queue_callback(0, Box::new(handle_irq), false)
-                .unwrap();
+            for index in 0..nr_queues {
+                let cloned_device = device.clone();
+                let handle_irq = move |_: &TrapFrame| {
+                    cloned_device.handle_irq(index as usize);
+                };
+                // Each virtqueue uses its own MSI-X vector if there are enough vectors.
+                if transport
+                    .register_queue_callback(index, Box::new(handle_irq.clone()), true)
+                    .is_err()
+                {
+                    transport
+                        .register_queue_callback(index, Box::new(handle_irq), false)
+                        .unwrap();
+                }
+            }
             transport.finish_init();
         }
 
         Ok(device)
     }
 
-    /// Handles the irq issued from the device
-    fn handle_irq(&self) {
+    /// Returns the virtqueue of the current CPU.
+    fn current_queue(&self) -> &RequestQueue {
+        &self.queues[this_cpu() as usize % self.queues.len()]
+    }
+
+    /// Handles the irq issued from the virtqueue at `index`.
+    fn handle_irq(&self, index: usize) {
         info!("Virtio block device handle irq");
+        let request_queue = &self.queues[index];
         // When we enter the IRQs handling function,
         // IRQs have already been disabled,
         // so there is no need to call `lock_irq_disabled`.
         loop {
-            // Pops the complete request
-            let complete_request = {
-                let mut queue = self.queue.lock();
-                let Ok((token, _)) = queue.pop_used() else {
-                    return;
+            // Pops the complete request and its response
+            let (complete_request, resp) = {
+                let mut inner = request_queue.inner.lock();
+                let Ok((token, _)) = inner.queue.pop_used() else {
+                    break;
                 };
-                self.submitted_requests.lock().remove(&token).unwrap()
+                let complete_request = inner.submitted_requests.remove(&token).unwrap();
+                let id = complete_request.id as usize;
+                let resp_slice =
+                    DmaStreamSlice::new(&request_queue.block_responses, id * RESP_SIZE, RESP_SIZE);
+                resp_slice.sync().unwrap();
+                let resp: BlockResp = resp_slice.read_val(0).unwrap();
+                inner.id_allocator.free(id);
+                (complete_request, resp)
             };
 
             // Handles the response
-            let id = complete_request.id as usize;
-            let resp_slice = DmaStreamSlice::new(&self.block_responses, id * RESP_SIZE, RESP_SIZE);
-            resp_slice.sync().unwrap();
-            let resp: BlockResp = resp_slice.read_val(0).unwrap();
-            self.id_allocator.lock().free(id);
-            match RespStatus::try_from(resp.status).unwrap() {
-                RespStatus::Ok => {}
-                // FIXME: Return an error instead of triggering a kernel panic
-                _ => panic!("io error in block device"),
+            let status = match RespStatus::try_from(resp.status) {
+                Ok(RespStatus::Ok) => BioStatus::Complete,
+                Ok(RespStatus::Unsupported) => BioStatus::NotSupported,
+                _ => BioStatus::IoError,
             };
 
             // Synchronize DMA mapping if read from the device
@@ -191,37 +320,77 @@ impl DeviceInner {
             }
 
             // Completes the bio request
-            complete_request.bio_request.bios().for_each(|bio| {
-                bio.complete(BioStatus::Complete);
-            });
+            complete_bio_request(complete_request.bio_request, status);
         }
+
+        // Refills the virtqueue with the staged requests.
+        self.dispatch(request_queue);
     }
 
     fn handle_config_change(&self) {
         info!("Virtio block device config space change");
     }
 
-    // TODO: Most logic is the same as read and write, there should be a refactor.
+    /// Dispatches the requests in the staging queue to the virtqueue
+    /// until the virtqueue runs out of descriptors.
+    fn dispatch(&self, request_queue: &RequestQueue) {
+        // The requests completed without the device, which are completed
+        // after the lock is released since the callbacks may submit new bios.
+        let mut completed_requests = Vec::new();
+        {
+            let mut inner = request_queue.inner.lock_irq_disabled();
+            let mut is_submitted = false;
+            loop {
+                let nr_free_descs = inner.queue.available_desc();
+                let Some(bio_request) = next_request(
+                    &mut inner.pending_request,
+                    &self.staging_queue,
+                    nr_free_descs,
+                ) else {
+                    break;
+                };
+
+                let result = match bio_request.type_() {
+                    BioType::Read | BioType::Write => {
+                        self.read_or_write(request_queue, &mut inner, bio_request)
+                    }
+                    BioType::Flush => self.flush(request_queue, &mut inner, bio_request),
+                    BioType::Discard | BioType::WriteZeroes => {
+                        self.discard_or_write_zeroes(request_queue, &mut inner, bio_request)
+                    }
+                };
+                match result {
+                    Ok(()) => is_submitted = true,
+                    Err(completed_request) => completed_requests.push(completed_request),
+                }
+            }
+            if is_submitted && inner.queue.should_notify() {
+                inner.queue.notify();
+            }
+        }
+
+        for (bio_request, status) in completed_requests {
+            complete_bio_request(bio_request, status);
+        }
+    }
+
+    /// Returns the number of descriptors used by the request.
+    fn nr_descs(bio_request: &BioRequest) -> usize {
+        match bio_request.type_() {
+            BioType::Read | BioType::Write => bio_request.num_segments() + 2,
+            BioType::Flush => 2,
+            BioType::Discard | BioType::WriteZeroes => 3,
+        }
+    }
+
     // TODO: Should return an Err instead of panic if the device fails.
     fn request_device_id(&self) -> String {
-        let id = self.id_allocator.lock_irq_disabled().alloc().unwrap();
-        let req_slice = {
-            let req_slice = DmaStreamSlice::new(&self.block_requests, id * REQ_SIZE, REQ_SIZE);
-            let req = BlockReq {
-                type_: ReqType::GetId as _,
-                reserved: 0,
-                sector: 0,
-            };
-            req_slice.write_val(0, &req).unwrap();
-            req_slice.sync().unwrap();
-            req_slice
-        };
+        let request_queue = &self.queues[0];
+        let mut inner = request_queue.inner.lock_irq_disabled();
+        let id = inner.id_allocator.alloc().unwrap();
+        let req_slice = request_queue.req_slice(id, ReqType::GetId, 0);
+        let resp_slice = request_queue.resp_slice(id);
 
-        let resp_slice = {
-            let resp_slice = DmaStreamSlice::new(&self.block_responses, id * RESP_SIZE, RESP_SIZE);
-            resp_slice.write_val(0, &BlockResp::default()).unwrap();
-            resp_slice
-        };
         const MAX_ID_LENGTH: usize = 20;
         let device_id_stream = {
             let segment = FrameAllocOptions::new(1)
@@ -233,20 +402,25 @@ impl DeviceInner {
         let device_id_slice = DmaStreamSlice::new(&device_id_stream, 0, MAX_ID_LENGTH);
         let outputs = vec![&device_id_slice, &resp_slice];
 
-        let mut queue = self.queue.lock_irq_disabled();
-        let token = queue
+        // The request is polled with the lock held, so the IRQ handler never sees it.
+        let token = inner
+            .queue
             .add_dma_buf(&[&req_slice], outputs.as_slice())
             .expect("add queue failed");
-        if queue.should_notify() {
-            queue.notify();
+        if inner.queue.should_notify() {
+            inner.queue.notify();
         }
-        while !queue.can_pop() {
+        while !inner.queue.can_pop() {
             spin_loop();
         }
-        queue.pop_used_with_token(token).expect("pop used failed");
+        inner
+            .queue
+            .pop_used_with_token(token)
+            .expect("pop used failed");
 
         resp_slice.sync().unwrap();
-        self.id_allocator.lock_irq_disabled().free(id);
+        inner.id_allocator.free(id);
+        drop(inner);
         let resp: BlockResp = resp_slice.read_val(0).unwrap();
         match RespStatus::try_from(resp.status).unwrap() {
             RespStatus::Ok => {}
@@ -267,125 +441,136 @@ impl DeviceInner {
         String::from_utf8(device_id).unwrap()
     }
 
-    /// Reads data from the device, this function is non-blocking.
-    fn read(&self, bio_request: BioRequest) {
+    /// Reads data from or writes data to the device, this function is non-blocking.
+    fn read_or_write(
+        &self,
+        request_queue: &RequestQueue,
+        inner: &mut RequestQueueInner,
+        bio_request: BioRequest,
+    ) -> Result<(), (BioRequest, BioStatus)> {
         let dma_streams = Self::dma_stream_map(&bio_request);
-
-        let id = self.id_allocator.lock_irq_disabled().alloc().unwrap();
-        let req_slice = {
-            let req_slice = DmaStreamSlice::new(&self.block_requests, id * REQ_SIZE, REQ_SIZE);
-            let req = BlockReq {
-                type_: ReqType::In as _,
-                reserved: 0,
-                sector: bio_request.sid_range().start.to_raw(),
-            };
-            req_slice.write_val(0, &req).unwrap();
-            req_slice.sync().unwrap();
-            req_slice
-        };
-
-        let resp_slice = {
-            let resp_slice = DmaStreamSlice::new(&self.block_responses, id * RESP_SIZE, RESP_SIZE);
-            resp_slice.write_val(0, &BlockResp::default()).unwrap();
-            resp_slice
-        };
-
         let dma_slices: Vec<DmaStreamSlice> = dma_streams
             .iter()
             .map(|(stream, offset, len)| DmaStreamSlice::new(stream, *offset, *len))
             .collect();
-        let outputs = {
-            let mut outputs: Vec<&DmaStreamSlice> = Vec::with_capacity(dma_streams.len() + 1);
+
+        let id = inner.id_allocator.alloc().unwrap();
+        let sector = bio_request.sid_range().start.to_raw();
+        let resp_slice = request_queue.resp_slice(id);
+        let token = if let BioType::Read = bio_request.type_() {
+            let req_slice = request_queue.req_slice(id, ReqType::In, sector);
+            let mut outputs: Vec<&DmaStreamSlice> = Vec::with_capacity(dma_slices.len() + 1);
             outputs.extend(dma_slices.iter());
             outputs.push(&resp_slice);
-            outputs
-        };
-
-        let num_used_descs = outputs.len() + 1;
-        // FIXME: Split the request if it is too big
-        if num_used_descs > Self::QUEUE_SIZE as usize {
-            panic!("The request size surpasses the queue size");
+            inner.queue.add_dma_buf(&[&req_slice], outputs.as_slice())
+        } else {
+            let req_slice = request_queue.req_slice(id, ReqType::Out, sector);
+            let mut inputs: Vec<&DmaStreamSlice> = Vec::with_capacity(dma_slices.len() + 1);
+            inputs.push(&req_slice);
+            inputs.extend(dma_slices.iter());
+            inner.queue.add_dma_buf(inputs.as_slice(), &[&resp_slice])
         }
+        .expect("add queue failed");
 
-        loop {
-            let mut queue = self.queue.lock_irq_disabled();
-            if num_used_descs > queue.available_desc() {
-                continue;
-            }
-            let token = queue
-                .add_dma_buf(&[&req_slice], outputs.as_slice())
-                .expect("add queue failed");
-            if queue.should_notify() {
-                queue.notify();
-            }
+        // Records the submitted request
+        let submitted_request = SubmittedRequest::new(id as u16, bio_request, dma_streams);
+        inner.submitted_requests.insert(token, submitted_request);
+        Ok(())
+    }
 
-            // Records the submitted request
-            let submitted_request = SubmittedRequest::new(id as u16, bio_request, dma_streams);
-            self.submitted_requests
-                .lock_irq_disabled()
-                .insert(token, submitted_request);
-            return;
+    /// Flushes the volatile write cache of the device, this function is non-blocking.
+    fn flush(
+        &self,
+        request_queue: &RequestQueue,
+        inner: &mut RequestQueueInner,
+        bio_request: BioRequest,
+    ) -> Result<(), (BioRequest, BioStatus)> {
+        if !self.features.contains(BlockFeatures::FLUSH) {
+            // Without the feature, the device has no volatile write cache,
+            // i.e., each completed write is already durable.
+            return Err((bio_request, BioStatus::Complete));
         }
-    }
 
-    /// Writes data to the device, this function is non-blocking.
-    fn write(&self, bio_request: BioRequest) {
-        let dma_streams = Self::dma_stream_map(&bio_request);
+        let id = inner.id_allocator.alloc().unwrap();
+        let req_slice = request_queue.req_slice(id, ReqType::Flush, 0);
+        let resp_slice = request_queue.resp_slice(id);
+        let token = inner
+            .queue
+            .add_dma_buf(&[&req_slice], &[&resp_slice])
+            .expect("add queue failed");
 
-        let id = self.id_allocator.lock_irq_disabled().alloc().unwrap();
-        let req_slice = {
-            let req_slice = DmaStreamSlice::new(&self.block_requests, id * REQ_SIZE, REQ_SIZE);
-            let req = BlockReq {
-                type_: ReqType::Out as _,
-                reserved: 0,
-                sector: bio_request.sid_range().start.to_raw(),
-            };
-            req_slice.write_val(0, &req).unwrap();
-            req_slice.sync().unwrap();
-            req_slice
-        };
+        let submitted_request = SubmittedRequest::new(id as u16, bio_request, Vec::new());
+        inner.submitted_requests.insert(token, submitted_request);
+        Ok(())
+    }
 
-        let resp_slice = {
-            let resp_slice = DmaStreamSlice::new(&self.block_responses, id * RESP_SIZE, RESP_SIZE);
-            resp_slice.write_val(0, &BlockResp::default()).unwrap();
-            resp_slice
+    /// Discards or writes zeroes to the sectors of the request,
+    /// this function is non-blocking.
+    ///
+    /// The sector range is split into segments that respect the limits of the device,
+    /// see [`split_sectors`]. If the range is too large for one request, the request
+    /// fails with `BioStatus::NotSupported` rather than covering only a part of the range.
+    fn discard_or_write_zeroes(
+        &self,
+        request_queue: &RequestQueue,
+        inner: &mut RequestQueueInner,
+        bio_request: BioRequest,
+    ) -> Result<(), (BioRequest, BioStatus)> {
+        let (feature, req_type, limits) = match bio_request.type_() {
+            BioType::Discard => (
+                BlockFeatures::DISCARD,
+                ReqType::Discard,
+                self.discard_limits,
+            ),
+            BioType::WriteZeroes => (
+                BlockFeatures::WRITE_ZEROES,
+                ReqType::WriteZeroes,
+                self.write_zeroes_limits,
+            ),
+            _ => unreachable!(),
         };
+        if !self.features.contains(feature) {
+            return Err((bio_request, BioStatus::NotSupported));
+        }
 
-        let dma_slices: Vec<DmaStreamSlice> = dma_streams
-            .iter()
-            .map(|(stream, offset, len)| DmaStreamSlice::new(stream, *offset, *len))
-            .collect();
-        let inputs = {
-            let mut inputs: Vec<&DmaStreamSlice> = Vec::with_capacity(dma_streams.len() + 1);
-            inputs.push(&req_slice);
-            inputs.extend(dma_slices.iter());
-            inputs
+        let sid_range = bio_request.sid_range();
+        let Some(segs) = split_sectors(sid_range.start.to_raw()..sid_range.end.to_raw(), limits)
+        else {
+            return Err((bio_request, BioStatus::NotSupported));
         };
-
-        let num_used_descs = inputs.len() + 1;
-        // FIXME: Split the request if it is too big
-        if num_used_descs > Self::QUEUE_SIZE as usize {
-            panic!("The request size surpasses the queue size");
+        if segs.is_empty() {
+            return Err((bio_request, BioStatus::Complete));
         }
-        loop {
-            let mut queue = self.queue.lock_irq_disabled();
-            if num_used_descs > queue.available_desc() {
-                continue;
-            }
-            let token = queue
-                .add_dma_buf(inputs.as_slice(), &[&resp_slice])
-                .expect("add queue failed");
-            if queue.should_notify() {
-                queue.notify();
-            }
 
-            // Records the submitted request
-            let submitted_request = SubmittedRequest::new(id as u16, bio_request, dma_streams);
-            self.submitted_requests
-                .lock_irq_disabled()
-                .insert(token, submitted_request);
-            return;
+        let segs_stream = {
+            let segment = FrameAllocOptions::new(1)
+                .uninit(true)
+                .alloc_contiguous()
+                .unwrap();
+            DmaStream::map(segment, DmaDirection::ToDevice, false).unwrap()
+        };
+        for (index, seg) in segs.iter().enumerate() {
+            segs_stream
+                .write_val(index * DISCARD_SEG_SIZE, seg)
+                .unwrap();
         }
+        let segs_len = segs.len() * DISCARD_SEG_SIZE;
+        let segs_slice = DmaStreamSlice::new(&segs_stream, 0, segs_len);
+        segs_slice.sync().unwrap();
+
+        let id = inner.id_allocator.alloc().unwrap();
+        let req_slice = request_queue.req_slice(id, req_type, 0);
+        let resp_slice = request_queue.resp_slice(id);
+        let token = inner
+            .queue
+            .add_dma_buf(&[&req_slice, &segs_slice], &[&resp_slice])
+            .expect("add queue failed");
+
+        // Keeps the segments alive until the request is completed.
+        let dma_bufs = vec![(segs_stream.clone(), 0, segs_len)];
+        let submitted_request = SubmittedRequest::new(id as u16, bio_request, dma_bufs);
+        inner.submitted_requests.insert(token, submitted_request);
+        Ok(())
     }
 
     /// Performs DMA mapping for the segments in bio request.
@@ -409,6 +594,69 @@ impl DeviceInner {
     }
 }
 
+/// Takes the next request to be dispatched to a virtqueue with `nr_free_descs`
+/// free descriptors.
+///
+/// A request that does not fit in the virtqueue is kept in `pending_request`
+/// and is taken first later, so the requests are dispatched in the order of the
+/// scheduler of the staging queue.
+fn next_request(
+    pending_request: &mut Option<BioRequest>,
+    staging_queue: &BioRequestSingleQueue,
+    nr_free_descs: usize,
+) -> Option<BioRequest> {
+    let bio_request = pending_request
+        .take()
+        .or_else(|| staging_queue.try_dequeue())?;
+    if DeviceInner::nr_descs(&bio_request) > nr_free_descs {
+        *pending_request = Some(bio_request);
+        return None;
+    }
+    Some(bio_request)
+}
+
+/// Splits the sectors of a discard or write zeroes request into the segments, each of
+/// which covers at most the maximum number of sectors in `limits`.
+///
+/// `limits` are the maximum number of sectors in a segment and the maximum number of
+/// the segments, as read from the configuration space, where zero sectors means no
+/// limit. At most one page of segments is used. Returns `None` if the sectors need
+/// more segments.
+fn split_sectors(
+    sectors: Range<u64>,
+    (max_sectors, max_seg): (u32, u32),
+) -> Option<Vec<DiscardWriteZeroesSeg>> {
+    let max_sectors = match max_sectors {
+        0 => u32::MAX as u64,
+        max_sectors => max_sectors as u64,
+    };
+    let max_seg = (max_seg as usize).clamp(1, PAGE_SIZE / DISCARD_SEG_SIZE);
+    let nsectors = sectors.end.saturating_sub(sectors.start);
+    if nsectors.div_ceil(max_sectors) > max_seg as u64 {
+        return None;
+    }
+
+    let mut segs = Vec::new();
+    let mut sector = sectors.start;
+    while sector < sectors.end {
+        let num_sectors = (sectors.end - sector).min(max_sectors);
+        segs.push(DiscardWriteZeroesSeg {
+            sector,
+            num_sectors: num_sectors as u32,
+            flags: 0,
+        });
+        sector += num_sectors;
+    }
+    Some(segs)
+}
+
+/// Completes all the bios in the request without submitting it to the device.
+fn complete_bio_request(bio_request: BioRequest, status: BioStatus) {
+    bio_request.bios().for_each(|bio| {
+        bio.complete(status);
+    });
+}
+
 /// A submitted bio request for callback.
 #[derive(Debug)]
 struct SubmittedRequest {
@@ -447,6 +695,17 @@ struct BlockResp {
 
 const RESP_SIZE: usize = size_of::<BlockResp>();
 
+/// A range of sectors in a discard or write zeroes request.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod)]
+struct DiscardWriteZeroesSeg {
+    pub sector: u64,
+    pub num_sectors: u32,
+    pub flags: u32,
+}
+
+const DISCARD_SEG_SIZE: usize = size_of::<DiscardWriteZeroesSeg>();
+
 impl Default for BlockResp {
     fn default() -> Self {
         Self {
@@ -454,3 +713,131 @@ impl Default for BlockResp {
         }
     }
 }
+
+#[cfg(ktest)]
+mod test {
+    use aster_block::{
+        bio::{Bio, BioEnqueueError},
+        id::Sid,
+    };
+    use ostd::prelude::*;
+
+    use super::*;
+
+    /// A device that stages the submitted bios like the virtio block device.
+    #[derive(Debug)]
+    struct StagingDevice(BioRequestSingleQueue);
+
+    impl aster_block::BlockDevice for StagingDevice {
+        fn enqueue(&self, bio: SubmittedBio) -> Result<(), BioEnqueueError> {
+            self.0.enqueue(bio)
+        }
+
+        fn max_nr_segments_per_bio(&self) -> usize {
+            usize::MAX
+        }
+
+        fn nr_sectors(&self) -> usize {
+            usize::MAX
+        }
+    }
+
+    /// Submits a discard, whose request takes 3 descriptors.
+    fn submit_discard(device: &StagingDevice, start: u64) {
+        let bio = Bio::new_dataless(BioType::Discard, Sid::new(start)..Sid::new(start + 8), None);
+        bio.submit(device).unwrap();
+    }
+
+    fn start_of(bio_request: &BioRequest) -> u64 {
+        bio_request.sid_range().start.to_raw()
+    }
+
+    /// Returns the sectors and the numbers of sectors of the segments.
+    fn split(sectors: Range<u64>, limits: (u32, u32)) -> Option<Vec<(u64, u32)>> {
+        let segs = split_sectors(sectors, limits)?;
+        Some(
+            segs.iter()
+                .map(|seg| (seg.sector, seg.num_sectors))
+                .collect(),
+        )
+    }
+
+    #[ktest]
+    fn split_by_device_limits() {
+        assert_eq!(split(8..8, (16, 4)), Some(vec![]));
+        assert_eq!(split(8..24, (16, 4)), Some(vec![(8, 16)]));
+        assert_eq!(
+            split(8..48, (16, 4)),
+            Some(vec![(8, 16), (24, 16), (40, 8)])
+        );
+        // The range is refused rather than partly covered.
+        assert_eq!(split(0..65, (16, 4)), None);
+        // Zero sectors means no limit, and at least one segment is allowed.
+        assert_eq!(split(0..1 << 20, (0, 0)), Some(vec![(0, 1 << 20)]));
+        assert_eq!(split(0..32, (16, 0)), None);
+        // The segments fit in one page.
+        let max_seg = (PAGE_SIZE / DISCARD_SEG_SIZE) as u64;
+        assert!(split(0..max_seg, (1, u32::MAX)).is_some());
+        assert_eq!(split(0..max_seg + 1, (1, u32::MAX)), None);
+    }
+
+    #[ktest]
+    fn descs_of_requests() {
+        let device = StagingDevice(BioRequestSingleQueue::new());
+        let flush = Bio::new_dataless(BioType::Flush, Sid::new(0)..Sid::new(0), None);
+        flush.submit(&device).unwrap();
+        submit_discard(&device, 0);
+        let bio = Bio::new_dataless(BioType::WriteZeroes, Sid::new(100)..Sid::new(108), None);
+        bio.submit(&device).unwrap();
+
+        let mut nr_descs = Vec::new();
+        while let Some(request) = device.0.try_dequeue() {
+            nr_descs.push((request.type_(), DeviceInner::nr_descs(&request)));
+        }
+        // The requests may be reordered by the scheduler.
+        nr_descs.sort_by_key(|(type_, _)| *type_ as u8);
+        // The header and the status, plus the segments of a discard or write zeroes.
+        assert_eq!(
+            nr_descs,
+            vec![
+                (BioType::Flush, 2),
+                (BioType::Discard, 3),
+                (BioType::WriteZeroes, 3)
+            ]
+        );
+    }
+
+    #[ktest]
+    fn dispatch_in_staging_order() {
+        let device = StagingDevice(BioRequestSingleQueue::new());
+        submit_discard(&device, 100);
+        submit_discard(&device, 0);
+        let mut pending_request = None;
+
+        let request = next_request(&mut pending_request, &device.0, 6).unwrap();
+        assert_eq!(start_of(&request), 100);
+        let request = next_request(&mut pending_request, &device.0, 3).unwrap();
+        assert_eq!(start_of(&request), 0);
+        assert!(next_request(&mut pending_request, &device.0, 3).is_none());
+        assert!(pending_request.is_none());
+    }
+
+    #[ktest]
+    fn keep_request_without_free_descs() {
+        let device = StagingDevice(BioRequestSingleQueue::new());
+        submit_discard(&device, 100);
+        let mut pending_request = None;
+
+        // The request waits for the descriptors freed by the completed requests.
+        assert!(next_request(&mut pending_request, &device.0, 2).is_none());
+        assert_eq!(start_of(pending_request.as_ref().unwrap()), 100);
+        assert_eq!(device.0.num_requests(), 0);
+
+        // The pending request goes before the ones staged later.
+        submit_discard(&device, 0);
+        let request = next_request(&mut pending_request, &device.0, 3).unwrap();
+        assert_eq!(start_of(&request), 100);
+        let request = next_request(&mut pending_request, &device.0, 3).unwrap();
+        assert_eq!(start_of(&request), 0);
+    }
+}
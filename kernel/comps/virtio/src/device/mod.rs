@@ -8,6 +8,7 @@ pub mod block;
 pub mod console;
 pub mod input;
 pub mod network;
+pub mod p9;
 pub mod socket;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, TryFromInt)]
@@ -47,6 +48,8 @@ pub enum VirtioDeviceError {
     QueueUnknownError,
     /// The input virtio capability list contains invalid element
     CapabilityListError,
+    /// There is no memory for the DMA buffers of a request
+    NoMemory,
 }
 
 impl From<QueueError> for VirtioDeviceError {
@@ -0,0 +1,42 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::string::String;
+
+use ostd::{io_mem::IoMem, mm::VmIo};
+
+use crate::transport::VirtioTransport;
+
+bitflags::bitflags! {
+    pub struct P9Features: u64 {
+        /// The mount tag is available in the configuration space.
+        const VIRTIO_9P_MOUNT_TAG = 1 << 0;
+    }
+}
+
+/// The configuration space of a 9P transport device.
+///
+/// The layout is a `u16` length followed by the mount tag, which is not
+/// NUL-terminated, so it cannot be described by a fixed-size `Pod` struct.
+#[derive(Debug)]
+pub struct VirtioP9Config {
+    memory: IoMem,
+}
+
+impl VirtioP9Config {
+    /// The maximum length of a mount tag accepted by QEMU.
+    const MAX_TAG_LEN: usize = 255;
+
+    pub(super) fn new(transport: &dyn VirtioTransport) -> Self {
+        Self {
+            memory: transport.device_config_memory(),
+        }
+    }
+
+    /// Reads the mount tag, which identifies the shared directory.
+    pub fn mount_tag(&self) -> String {
+        let tag_len = self.memory.read_val::<u16>(0).unwrap() as usize;
+        let mut tag = alloc::vec![0u8; tag_len.min(Self::MAX_TAG_LEN)];
+        self.memory.read_bytes(2, &mut tag).unwrap();
+        String::from_utf8_lossy(&tag).into_owned()
+    }
+}
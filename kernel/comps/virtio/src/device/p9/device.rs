@@ -0,0 +1,157 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::{boxed::Box, collections::BTreeMap, sync::Arc};
+use core::fmt::Debug;
+
+use log::{debug, info};
+use ostd::{
+    mm::{DmaDirection, DmaStream, DmaStreamSlice, FrameAllocOptions, VmIo, PAGE_SIZE},
+    sync::{SpinLock, WaitQueue},
+    trap::TrapFrame,
+};
+
+use super::{
+    config::{P9Features, VirtioP9Config},
+    register_device, DEVICE_NAME,
+};
+use crate::{device::VirtioDeviceError, queue::VirtQueue, transport::VirtioTransport};
+
+/// A virtio 9P transport, which carries 9P messages to a shared directory of the host.
+///
+/// The device does not interpret the messages. Each request is a 9P T-message
+/// and is completed when the device writes the matching R-message back.
+pub struct P9Device {
+    config: VirtioP9Config,
+    queue: SpinLock<VirtQueue>,
+    transport: SpinLock<Box<dyn VirtioTransport>>,
+    /// The lengths of the completed replies, indexed by the tokens of the requests
+    completed: SpinLock<BTreeMap<u16, u32>>,
+    wait_queue: WaitQueue,
+}
+
+impl P9Device {
+    const QUEUE_SIZE: u16 = 64;
+    const REQUEST_QUEUE_INDEX: u16 = 0;
+
+    pub(crate) fn negotiate_features(features: u64) -> u64 {
+        let features = P9Features::from_bits_truncate(features);
+        features.bits()
+    }
+
+    pub(crate) fn init(mut transport: Box<dyn VirtioTransport>) -> Result<(), VirtioDeviceError> {
+        let config = VirtioP9Config::new(transport.as_ref());
+        let queue = VirtQueue::new(
+            Self::REQUEST_QUEUE_INDEX,
+            Self::QUEUE_SIZE,
+            transport.as_mut(),
+        )?;
+
+        let device = Arc::new(Self {
+            config,
+            queue: SpinLock::new(queue),
+            transport: SpinLock::new(transport),
+            completed: SpinLock::new(BTreeMap::new()),
+            wait_queue: WaitQueue::new(),
+        });
+
+        let mut transport = device.transport.lock_irq_disabled();
+        let handle_irq = {
+            let device = device.clone();
+            move |_: &TrapFrame| device.handle_irq()
+        };
+        transport
+            .register_queue_callback(Self::REQUEST_QUEUE_INDEX, Box::new(handle_irq), false)
+            .unwrap();
+        transport
+            .register_cfg_callback(Box::new(config_space_change))
+            .unwrap();
+        transport.finish_init();
+        drop(transport);
+
+        let tag = device.config.mount_tag();
+        info!(
+            "[{}]: found shared directory with tag {:?}",
+            DEVICE_NAME, tag
+        );
+        register_device(tag, device);
+
+        Ok(())
+    }
+
+    /// Returns the mount tag of the shared directory.
+    pub fn mount_tag(&self) -> alloc::string::String {
+        self.config.mount_tag()
+    }
+
+    /// Sends a 9P request and waits for the reply.
+    ///
+    /// The reply is written to `reply`, whose length limits the size of the reply.
+    /// The length of the reply is returned.
+    pub fn request(&self, request: &[u8], reply: &mut [u8]) -> Result<usize, VirtioDeviceError> {
+        let request_stream = alloc_dma_stream(request.len(), DmaDirection::ToDevice)?;
+        request_stream.write_bytes(0, request).unwrap();
+        let request_slice = DmaStreamSlice::new(&request_stream, 0, request.len());
+        request_slice.sync().unwrap();
+
+        let reply_stream = alloc_dma_stream(reply.len(), DmaDirection::FromDevice)?;
+        let reply_slice = DmaStreamSlice::new(&reply_stream, 0, reply.len());
+
+        // The descriptors are freed by the IRQ handler, which wakes up the waiters.
+        let token = self.wait_queue.wait_until(|| {
+            let mut queue = self.queue.lock_irq_disabled();
+            if queue.available_desc() < 2 {
+                return None;
+            }
+            let result = queue.add_dma_buf(&[&request_slice], &[&reply_slice]);
+            if result.is_ok() && queue.should_notify() {
+                queue.notify();
+            }
+            Some(result)
+        })?;
+
+        let len = self
+            .wait_queue
+            .wait_until(|| self.completed.lock_irq_disabled().remove(&token));
+        let len = (len as usize).min(reply.len());
+        reply_slice.sync().unwrap();
+        reply_stream.read_bytes(0, &mut reply[..len]).unwrap();
+        Ok(len)
+    }
+
+    fn handle_irq(&self) {
+        // When we enter the IRQs handling function,
+        // IRQs have already been disabled,
+        // so there is no need to call `lock_irq_disabled`.
+        let mut queue = self.queue.lock();
+        let mut completed = self.completed.lock();
+        while let Ok((token, len)) = queue.pop_used() {
+            completed.insert(token, len);
+        }
+        drop(completed);
+        drop(queue);
+        self.wait_queue.wake_all();
+    }
+}
+
+impl Debug for P9Device {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("P9Device")
+            .field("config", &self.config)
+            .field("transport", &self.transport)
+            .field("queue", &self.queue)
+            .finish()
+    }
+}
+
+fn alloc_dma_stream(len: usize, direction: DmaDirection) -> Result<DmaStream, VirtioDeviceError> {
+    let nframes = len.div_ceil(PAGE_SIZE).max(1);
+    let segment = FrameAllocOptions::new(nframes)
+        .uninit(true)
+        .alloc_contiguous()
+        .map_err(|_| VirtioDeviceError::NoMemory)?;
+    DmaStream::map(segment, direction, false).map_err(|_| VirtioDeviceError::NoMemory)
+}
+
+fn config_space_change(_: &TrapFrame) {
+    debug!("Virtio-9P device configuration space change");
+}
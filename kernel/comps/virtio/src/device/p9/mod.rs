@@ -0,0 +1,42 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::{collections::BTreeMap, string::String, sync::Arc, vec::Vec};
+
+use ostd::sync::SpinLock;
+use spin::Once;
+
+use self::device::P9Device;
+
+pub mod config;
+pub mod device;
+
+pub static DEVICE_NAME: &str = "Virtio-9P";
+
+/// Registers a 9P transport device with its mount tag.
+pub fn register_device(tag: String, device: Arc<P9Device>) {
+    P9_DEVICE_TABLE
+        .get()
+        .unwrap()
+        .lock_irq_disabled()
+        .insert(tag, device);
+}
+
+/// Gets the 9P transport device with the mount tag.
+pub fn get_device(tag: &str) -> Option<Arc<P9Device>> {
+    let lock = P9_DEVICE_TABLE.get().unwrap().lock_irq_disabled();
+    lock.get(tag).cloned()
+}
+
+pub fn all_devices() -> Vec<(String, Arc<P9Device>)> {
+    let p9_devs = P9_DEVICE_TABLE.get().unwrap().lock_irq_disabled();
+    p9_devs
+        .iter()
+        .map(|(tag, device)| (tag.clone(), device.clone()))
+        .collect()
+}
+
+pub fn init() {
+    P9_DEVICE_TABLE.call_once(|| SpinLock::new(BTreeMap::new()));
+}
+
+static P9_DEVICE_TABLE: Once<SpinLock<BTreeMap<String, Arc<P9Device>>>> = Once::new();
@@ -19,6 +19,7 @@ use device::{
     console::device::ConsoleDevice,
     input::device::InputDevice,
     network::device::NetworkDevice,
+    p9::{self, device::P9Device},
     socket::{self, device::SocketDevice},
     VirtioDeviceType,
 };
@@ -38,6 +39,7 @@ fn virtio_component_init() -> Result<(), ComponentInitError> {
     transport::init();
     // For vsock table static init
     socket::init();
+    p9::init();
     while let Some(mut transport) = pop_device_transport() {
         // Reset device
         transport.set_device_status(DeviceStatus::empty()).unwrap();
@@ -61,6 +63,7 @@ fn virtio_component_init() -> Result<(), ComponentInitError> {
             VirtioDeviceType::Network => NetworkDevice::init(transport),
             VirtioDeviceType::Console => ConsoleDevice::init(transport),
             VirtioDeviceType::Socket => SocketDevice::init(transport),
+            VirtioDeviceType::Transport9P => P9Device::init(transport),
             _ => {
                 warn!("[Virtio]: Found unimplemented device:{:?}", device_type);
                 Ok(())
@@ -96,6 +99,7 @@ fn negotiate_features(transport: &mut Box<dyn VirtioTransport>) {
         VirtioDeviceType::Input => InputDevice::negotiate_features(device_specified_features),
         VirtioDeviceType::Console => ConsoleDevice::negotiate_features(device_specified_features),
         VirtioDeviceType::Socket => SocketDevice::negotiate_features(device_specified_features),
+        VirtioDeviceType::Transport9P => P9Device::negotiate_features(device_specified_features),
         _ => device_specified_features,
     };
     let mut support_feature = Feature::from_bits_truncate(features);
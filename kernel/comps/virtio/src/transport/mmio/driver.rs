@@ -37,6 +37,10 @@ impl VirtioMmioDriver {
 }
 
 impl MmioDriver for VirtioMmioDriver {
+    fn name(&self) -> &'static str {
+        "virtio-mmio"
+    }
+
     fn probe(
         &self,
         device: MmioCommonDevice,
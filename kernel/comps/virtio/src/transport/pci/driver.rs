@@ -37,6 +37,10 @@ impl VirtioPciDriver {
 }
 
 impl PciDriver for VirtioPciDriver {
+    fn name(&self) -> &'static str {
+        "virtio-pci"
+    }
+
     fn probe(
         &self,
         device: PciCommonDevice,
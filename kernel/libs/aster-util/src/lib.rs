@@ -8,6 +8,7 @@
 extern crate alloc;
 
 pub mod coeff;
+pub mod crc;
 pub mod dup;
 pub mod safe_ptr;
 pub mod slot_vec;
@@ -11,6 +11,7 @@ mod priority;
 
 use init_comp::ComponentInitFunction;
 use proc_macro::TokenStream;
+use proc_macro2::Literal;
 use quote::quote;
 use syn::parse_macro_input;
 
@@ -37,14 +38,23 @@ pub(crate) const COMPONENT_FILE_NAME: &str = "Components.toml";
 ///     file!()
 /// }
 ///
+/// #[cfg(not(component_module))]
 /// component::submit!(component::ComponentRegistry::new(&init,file()));
 /// ```
 /// The priority will calculate automatically
 ///
+/// If the component is built as a module that is loaded at runtime, i.e., with
+/// `--cfg component_module`, the function is instead registered by a constructor
+/// of the module with `component::register_loaded`, and the name of the component
+/// is recorded in the `.component_name` section of the module.
+///
 #[proc_macro_attribute]
 pub fn init_component(_: TokenStream, input: TokenStream) -> proc_macro::TokenStream {
     let function = parse_macro_input!(input as ComponentInitFunction);
     let function_name = &function.function_name;
+    let component_name = std::env::var("CARGO_PKG_NAME").unwrap();
+    let component_name_len = component_name.len();
+    let component_name = Literal::byte_string(component_name.as_bytes());
     quote! {
         #function
 
@@ -52,7 +62,89 @@ pub fn init_component(_: TokenStream, input: TokenStream) -> proc_macro::TokenSt
             file!()
         }
 
+        #[cfg(not(component_module))]
         component::submit!(component::ComponentRegistry::new(&#function_name,file()));
+
+        #[cfg(component_module)]
+        #[doc(hidden)]
+        #[allow(unsafe_code)]
+        #[used]
+        #[link_section = ".component_name"]
+        static __COMPONENT_NAME: [u8; #component_name_len] = *#component_name;
+
+        #[cfg(component_module)]
+        #[doc(hidden)]
+        #[allow(unsafe_code)]
+        #[used]
+        #[link_section = ".init_array"]
+        static __COMPONENT_REGISTER: extern "C" fn() = {
+            extern "C" fn register() {
+                static REGISTRY: component::ComponentRegistry =
+                    component::ComponentRegistry::new(&#function_name, file());
+                component::register_loaded(&REGISTRY);
+            }
+            register
+        };
+    }
+    .into()
+}
+
+/// Register a function to undo the initialization of the component when the module
+/// of the component is unloaded. The function should not public.
+///
+/// The function should unregister everything that the component has registered to
+/// the kernel, since the code and the data of the module are freed after the call.
+/// A module cannot be unloaded if its component has no such function.
+///
+/// Example:
+/// ```rust
+/// #[exit_component]
+/// fn exit() {
+/// }
+///
+/// ```
+///
+/// It will expand to
+/// ```rust
+/// #[cfg_attr(not(component_module), allow(dead_code))]
+/// fn exit() {
+/// }
+///
+/// #[cfg(component_module)]
+/// #[used]
+/// #[link_section = ".init_array"]
+/// static __COMPONENT_REGISTER_EXIT: extern "C" fn() = {
+///     extern "C" fn register() {
+///         static REGISTRY: component::ComponentExitRegistry =
+///             component::ComponentExitRegistry::new(&exit, file!());
+///         component::register_loaded_exit(&REGISTRY);
+///     }
+///     register
+/// };
+/// ```
+/// The function is never called if the component is not built as a module.
+///
+#[proc_macro_attribute]
+pub fn exit_component(_: TokenStream, input: TokenStream) -> proc_macro::TokenStream {
+    let function = parse_macro_input!(input as ComponentInitFunction);
+    let function_name = &function.function_name;
+    quote! {
+        #[cfg_attr(not(component_module), allow(dead_code))]
+        #function
+
+        #[cfg(component_module)]
+        #[doc(hidden)]
+        #[allow(unsafe_code)]
+        #[used]
+        #[link_section = ".init_array"]
+        static __COMPONENT_REGISTER_EXIT: extern "C" fn() = {
+            extern "C" fn register() {
+                static REGISTRY: component::ComponentExitRegistry =
+                    component::ComponentExitRegistry::new(&#function_name, file!());
+                component::register_loaded_exit(&REGISTRY);
+            }
+            register
+        };
     }
     .into()
 }
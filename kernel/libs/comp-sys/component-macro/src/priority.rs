@@ -17,12 +17,24 @@ pub struct ComponentInfo {
     /// The absolute path to the component
     path: String,
     priority: u16,
+    /// The names of the components that the component depends on
+    dependencies: Vec<String>,
 }
 
 impl ToTokens for ComponentInfo {
     fn to_tokens(&self, tokens: &mut TokenStream) {
+        let dependencies = self
+            .dependencies
+            .iter()
+            .map(|name| format!("\"{}\"", name))
+            .collect::<Vec<_>>()
+            .join(",");
         let token = TokenStream::from_str(
-            format!("\"{}\",\"{}\",{}", self.name, self.path, self.priority).as_str(),
+            format!(
+                "\"{}\",\"{}\",{},&[{}]",
+                self.name, self.path, self.priority, dependencies
+            )
+            .as_str(),
         )
         .unwrap();
         tokens.append(Group::new(proc_macro2::Delimiter::Parenthesis, token));
@@ -98,6 +110,10 @@ pub fn component_generate() -> Vec<ComponentInfo> {
                     .unwrap()
                     .to_string(),
                 priority: *mapping.get(&package_name).unwrap(),
+                dependencies: package["dependencies"]
+                    .members()
+                    .map(|depend| depend["name"].as_str().unwrap().to_string())
+                    .collect(),
             }
         };
         components_info.push(component_info)
@@ -20,13 +20,25 @@ use alloc::{
 pub use component_macro::*;
 pub use inventory::submit;
 use log::{debug, error, info};
+use spin::Mutex;
 
 #[derive(Debug)]
 pub enum ComponentInitError {
     UninitializedDependencies(String),
+    UnknownComponent(String),
+    AlreadyInitialized(String),
     Unknown,
 }
 
+#[derive(Debug)]
+pub enum ComponentExitError {
+    UnknownComponent(String),
+    NotInitialized(String),
+    /// The component is depended on by the component of the name, which is initialized.
+    InUse(String),
+    NoExitFunction(String),
+}
+
 pub struct ComponentRegistry {
     function: &'static (dyn Fn() -> Result<(), ComponentInitError> + Sync),
     path: &'static str,
@@ -51,19 +63,42 @@ impl Debug for ComponentRegistry {
     }
 }
 
+/// The function to undo the initialization of a component that is loaded at runtime,
+/// which is registered by `exit_component` macro.
+pub struct ComponentExitRegistry {
+    function: &'static (dyn Fn() + Sync),
+    path: &'static str,
+}
+
+impl ComponentExitRegistry {
+    pub const fn new(function: &'static (dyn Fn() + Sync), path: &'static str) -> Self {
+        Self { function, path }
+    }
+}
+
+impl Debug for ComponentExitRegistry {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ComponentExitRegistry")
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
 pub struct ComponentInfo {
     name: String,
     path: String,
     priority: u32,
+    dependencies: Vec<String>,
     function: Option<&'static (dyn Fn() -> Result<(), ComponentInitError> + Sync)>,
 }
 
 impl ComponentInfo {
-    pub fn new(name: &str, path: &str, priority: u32) -> Self {
+    pub fn new(name: &str, path: &str, priority: u32, dependencies: &[&str]) -> Self {
         Self {
             name: name.to_string(),
             path: path.to_string(),
             priority,
+            dependencies: dependencies.iter().map(|name| name.to_string()).collect(),
             function: None,
         }
     }
@@ -95,6 +130,7 @@ impl Debug for ComponentInfo {
             .field("name", &self.name)
             .field("path", &self.path)
             .field("priority", &self.priority)
+            .field("dependencies", &self.dependencies)
             .finish()
     }
 }
@@ -105,6 +141,37 @@ pub enum ComponentSystemInitError {
     NotIncludeAllComponent(String),
 }
 
+/// The state of a component, which is recorded by the component system
+/// to initialize the components that are loaded at runtime.
+#[derive(Debug)]
+struct ComponentState {
+    name: String,
+    dependencies: Vec<String>,
+    initialized: bool,
+    /// The exit function of the component, if it is loaded at runtime and has one.
+    exit: Option<&'static ComponentExitRegistry>,
+}
+
+impl ComponentState {
+    fn new(name: String, dependencies: Vec<String>, initialized: bool) -> Self {
+        Self {
+            name,
+            dependencies,
+            initialized,
+            exit: None,
+        }
+    }
+}
+
+/// The states of all components, indexed by the paths of the components.
+static COMPONENTS: Mutex<BTreeMap<String, ComponentState>> = Mutex::new(BTreeMap::new());
+
+/// The components registered by the modules that are loaded but not initialized yet.
+static LOADED_REGISTRIES: Mutex<Vec<&'static ComponentRegistry>> = Mutex::new(Vec::new());
+
+/// The exit functions registered by the modules that are loaded but not initialized yet.
+static LOADED_EXIT_REGISTRIES: Mutex<Vec<&'static ComponentExitRegistry>> = Mutex::new(Vec::new());
+
 /// Component system initialization. It will collect invoke all functions that are marked by init_component based on dependencies between crates.
 ///
 /// The collection of ComponentInfo usually generate by `parse_metadata` macro.
@@ -134,25 +201,7 @@ fn match_and_call(
 ) -> Result<(), ComponentSystemInitError> {
     let mut infos = Vec::new();
     for registry in inventory::iter::<ComponentRegistry> {
-        // relative/path/to/comps/pci/src/lib.rs
-        let mut str: String = registry.path.to_owned();
-        str = str.replace('\\', "/");
-        // relative/path/to/comps/pci
-        // There are two cases, one in the test folder and one in the src folder.
-        // There may be multiple directories within the folder.
-        // There we assume it will not have such directories: 'comp1/src/comp2/src/lib.rs' so that we can split by tests or src string
-        if str.contains("src/") {
-            str = str
-                .trim_end_matches(str.get(str.find("src/").unwrap()..str.len()).unwrap())
-                .to_string();
-        } else if str.contains("tests/") {
-            str = str
-                .trim_end_matches(str.get(str.find("tests/").unwrap()..str.len()).unwrap())
-                .to_string();
-        } else {
-            panic!("The path of {} cannot recognized by component system", str);
-        }
-        let str = str.trim_end_matches('/').to_owned();
+        let str = component_path(registry.path);
 
         let mut info = components
             .remove(&str)
@@ -173,12 +222,173 @@ fn match_and_call(
 
     for i in infos {
         info!("Component initializing:{:?}", i);
-        if let Err(res) = i.function.unwrap().call(()) {
+        let initialized = if let Err(res) = i.function.unwrap().call(()) {
             error!("Component initalize error:{:?}", res);
+            false
         } else {
             info!("Component initalize complete");
-        }
+            true
+        };
+        COMPONENTS.lock().insert(
+            i.path,
+            ComponentState::new(i.name, i.dependencies, initialized),
+        );
+    }
+    // The remaining components may be loaded at runtime.
+    let mut states = COMPONENTS.lock();
+    for (path, i) in components {
+        states.insert(path, ComponentState::new(i.name, i.dependencies, false));
     }
     info!("All components initalization completed");
     Ok(())
 }
+
+/// Returns the path of the component that a registered function is defined in.
+fn component_path(file_path: &str) -> String {
+    // relative/path/to/comps/pci/src/lib.rs
+    let mut str: String = file_path.to_owned();
+    str = str.replace('\\', "/");
+    // relative/path/to/comps/pci
+    // There are two cases, one in the test folder and one in the src folder.
+    // There may be multiple directories within the folder.
+    // There we assume it will not have such directories: 'comp1/src/comp2/src/lib.rs' so that we can split by tests or src string
+    if str.contains("src/") {
+        str = str
+            .trim_end_matches(str.get(str.find("src/").unwrap()..str.len()).unwrap())
+            .to_string();
+    } else if str.contains("tests/") {
+        str = str
+            .trim_end_matches(str.get(str.find("tests/").unwrap()..str.len()).unwrap())
+            .to_string();
+    } else {
+        panic!("The path of {} cannot recognized by component system", str);
+    }
+    str.trim_end_matches('/').to_owned()
+}
+
+/// Returns whether the component named `name` is initialized.
+pub fn is_initialized(name: &str) -> bool {
+    COMPONENTS
+        .lock()
+        .values()
+        .any(|state| state.name == name && state.initialized)
+}
+
+/// Checks whether the component named `name` can be loaded at runtime.
+///
+/// The component should be declared in `Components.toml` and not be initialized,
+/// and all the components that it depends on should have been initialized.
+pub fn check_loadable(name: &str) -> Result<(), ComponentInitError> {
+    let states = COMPONENTS.lock();
+    let Some(state) = states.values().find(|state| state.name == name) else {
+        return Err(ComponentInitError::UnknownComponent(name.to_string()));
+    };
+    if state.initialized {
+        return Err(ComponentInitError::AlreadyInitialized(name.to_string()));
+    }
+    for dependency in state.dependencies.iter() {
+        let dependency_initialized = states
+            .values()
+            .any(|state| &state.name == dependency && state.initialized);
+        if !dependency_initialized {
+            return Err(ComponentInitError::UninitializedDependencies(
+                dependency.clone(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Registers a component of a module that is loaded at runtime.
+///
+/// It is called by the constructor of the module that is generated by `init_component` macro.
+pub fn register_loaded(registry: &'static ComponentRegistry) {
+    LOADED_REGISTRIES.lock().push(registry);
+}
+
+/// Registers the exit function of a component of a module that is loaded at runtime.
+///
+/// It is called by the constructor of the module that is generated by `exit_component` macro.
+pub fn register_loaded_exit(registry: &'static ComponentExitRegistry) {
+    LOADED_EXIT_REGISTRIES.lock().push(registry);
+}
+
+/// Returns whether the component at `path` is named `name`.
+fn is_component(path: &str, name: &str) -> bool {
+    COMPONENTS
+        .lock()
+        .get(path)
+        .is_some_and(|state| state.name == name)
+}
+
+/// Initializes the component named `name`, which is registered by a module that is just loaded.
+///
+/// The registered functions of the component are taken, and the component is checked
+/// by [`check_loadable`] again before it is initialized.
+pub fn init_loaded(name: &str) -> Result<(), ComponentInitError> {
+    let mut registry = None;
+    LOADED_REGISTRIES.lock().retain(|loaded| {
+        if !is_component(&component_path(loaded.path), name) {
+            return true;
+        }
+        registry = Some(*loaded);
+        false
+    });
+    let mut exit = None;
+    LOADED_EXIT_REGISTRIES.lock().retain(|loaded| {
+        if !is_component(&component_path(loaded.path), name) {
+            return true;
+        }
+        exit = Some(*loaded);
+        false
+    });
+    check_loadable(name)?;
+    let registry =
+        registry.ok_or_else(|| ComponentInitError::UnknownComponent(name.to_string()))?;
+
+    info!("Component initializing:{}", name);
+    registry.function.call(())?;
+    info!("Component initalize complete");
+
+    let mut states = COMPONENTS.lock();
+    if let Some(state) = states.values_mut().find(|state| state.name == name) {
+        state.initialized = true;
+        state.exit = exit;
+    }
+    Ok(())
+}
+
+/// Undoes the initialization of the component named `name`, which is loaded at runtime.
+///
+/// The component should be initialized and have an exit function, and no initialized
+/// component should depend on it.
+pub fn exit_loaded(name: &str) -> Result<(), ComponentExitError> {
+    let exit = {
+        let states = COMPONENTS.lock();
+        let Some(state) = states.values().find(|state| state.name == name) else {
+            return Err(ComponentExitError::UnknownComponent(name.to_string()));
+        };
+        if !state.initialized {
+            return Err(ComponentExitError::NotInitialized(name.to_string()));
+        }
+        if let Some(dependent) = states.values().find(|dependent| {
+            dependent.initialized && dependent.dependencies.iter().any(|dep| dep == name)
+        }) {
+            return Err(ComponentExitError::InUse(dependent.name.clone()));
+        }
+        state
+            .exit
+            .ok_or_else(|| ComponentExitError::NoExitFunction(name.to_string()))?
+    };
+
+    info!("Component exiting:{}", name);
+    exit.function.call(());
+    info!("Component exit complete");
+
+    let mut states = COMPONENTS.lock();
+    if let Some(state) = states.values_mut().find(|state| state.name == name) {
+        state.initialized = false;
+        state.exit = None;
+    }
+    Ok(())
+}
@@ -0,0 +1,182 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Fills the kernel symbol table in the `.ksymtab` section of the kernel ELF,
+//! which is used by the kernel to resolve the symbols of the kernel modules.
+//!
+//! The format of the table is described in `ostd/src/ksym.rs`.
+
+use std::{collections::BTreeMap, path::Path, process};
+
+use crate::{error::Errno, error_msg};
+
+const KSYMTAB_SECTION_NAME: &[u8] = b".ksymtab";
+
+const SHT_SYMTAB: u32 = 2;
+const SHN_UNDEF: u16 = 0;
+const STB_LOCAL: u8 = 0;
+const STT_NOTYPE: u8 = 0;
+const STT_OBJECT: u8 = 1;
+const STT_FUNC: u8 = 2;
+
+const SECTION_HEADER_SIZE: usize = 64;
+const SYMBOL_SIZE: usize = 24;
+const ENTRY_SIZE: usize = 16;
+
+/// Fills the kernel symbol table with the symbols in the symbol table of the ELF.
+///
+/// The global symbols are preferred if the space is not large enough for all the
+/// symbols. Nothing is done if the ELF has no `.ksymtab` section.
+pub fn fill_kernel_symbol_table(elf_path: &Path) {
+    let mut elf = std::fs::read(elf_path).unwrap();
+    let sections = section_headers(&elf);
+    let Some(ksymtab) = sections
+        .iter()
+        .find(|section| section_name(&elf, &sections, section) == KSYMTAB_SECTION_NAME)
+    else {
+        return;
+    };
+    let Some(symtab) = sections.iter().find(|section| section.type_ == SHT_SYMTAB) else {
+        error_msg!("The kernel ELF has no symbol table");
+        process::exit(Errno::BuildCrate as _);
+    };
+
+    let strtab = &sections[symtab.link as usize];
+    let mut global_symbols = BTreeMap::new();
+    let mut local_symbols = BTreeMap::new();
+    for symbol in elf[symtab.range()].chunks_exact(SYMBOL_SIZE).skip(1) {
+        let name_offset = read_u32(symbol, 0) as usize;
+        let info = symbol[4];
+        let shndx = read_u16(symbol, 6);
+        let value = read_u64(symbol, 8);
+        let (binding, type_) = (info >> 4, info & 0xf);
+        if shndx == SHN_UNDEF || !matches!(type_, STT_NOTYPE | STT_OBJECT | STT_FUNC) {
+            continue;
+        }
+        let name = c_str(&elf[strtab.range()][name_offset..]);
+        if name.is_empty() {
+            continue;
+        }
+        if binding != STB_LOCAL {
+            global_symbols.insert(name.to_vec(), value);
+        } else if type_ != STT_NOTYPE {
+            // The local symbols with the same name cannot be told apart.
+            local_symbols
+                .entry(name.to_vec())
+                .and_modify(|local_value: &mut Option<u64>| {
+                    if *local_value != Some(value) {
+                        *local_value = None;
+                    }
+                })
+                .or_insert(Some(value));
+        }
+    }
+    let local_symbols = local_symbols
+        .into_iter()
+        .filter_map(|(name, value)| Some((name, value?)))
+        .collect();
+
+    let table = encode_table(&global_symbols, &local_symbols, ksymtab.size)
+        .or_else(|| encode_table(&global_symbols, &BTreeMap::new(), ksymtab.size))
+        .unwrap_or_else(|| {
+            error_msg!(
+                "The kernel symbol table is larger than the space of {} bytes",
+                ksymtab.size
+            );
+            process::exit(Errno::BuildCrate as _);
+        });
+    let start = ksymtab.offset;
+    elf[start..start + table.len()].copy_from_slice(&table);
+    std::fs::write(elf_path, elf).unwrap();
+}
+
+/// Encodes the symbols into the table, or returns `None` if the table is larger than `size`.
+///
+/// If a symbol is both global and local, the global one is taken.
+fn encode_table(
+    global_symbols: &BTreeMap<Vec<u8>, u64>,
+    local_symbols: &BTreeMap<Vec<u8>, u64>,
+    size: usize,
+) -> Option<Vec<u8>> {
+    let mut symbols = local_symbols.clone();
+    symbols.extend(
+        global_symbols
+            .iter()
+            .map(|(name, value)| (name.clone(), *value)),
+    );
+
+    let mut entries = Vec::new();
+    let mut names = Vec::new();
+    for (name, value) in symbols.iter() {
+        entries.extend_from_slice(&value.to_le_bytes());
+        entries.extend_from_slice(&u32::try_from(names.len()).ok()?.to_le_bytes());
+        entries.extend_from_slice(&u32::try_from(name.len()).ok()?.to_le_bytes());
+        names.extend_from_slice(name);
+    }
+    debug_assert_eq!(entries.len(), symbols.len() * ENTRY_SIZE);
+
+    let mut table = (symbols.len() as u64).to_le_bytes().to_vec();
+    table.extend(entries);
+    table.extend(names);
+    (table.len() <= size).then_some(table)
+}
+
+struct SectionHeader {
+    name: u32,
+    type_: u32,
+    offset: usize,
+    size: usize,
+    link: u32,
+}
+
+impl SectionHeader {
+    fn range(&self) -> std::ops::Range<usize> {
+        self.offset..self.offset + self.size
+    }
+}
+
+fn section_headers(elf: &[u8]) -> Vec<SectionHeader> {
+    let shoff = read_u64(elf, 0x28) as usize;
+    let shnum = read_u16(elf, 0x3c) as usize;
+    (0..shnum)
+        .map(|index| {
+            let header = &elf[shoff + index * SECTION_HEADER_SIZE..][..SECTION_HEADER_SIZE];
+            SectionHeader {
+                name: read_u32(header, 0),
+                type_: read_u32(header, 4),
+                offset: read_u64(header, 24) as usize,
+                size: read_u64(header, 32) as usize,
+                link: read_u32(header, 40),
+            }
+        })
+        .collect()
+}
+
+fn section_name<'a>(
+    elf: &'a [u8],
+    sections: &[SectionHeader],
+    section: &SectionHeader,
+) -> &'a [u8] {
+    let shstrndx = read_u16(elf, 0x3e) as usize;
+    let shstrtab = &sections[shstrndx];
+    c_str(&elf[shstrtab.range()][section.name as usize..])
+}
+
+fn c_str(bytes: &[u8]) -> &[u8] {
+    let len = bytes
+        .iter()
+        .position(|&byte| byte == 0)
+        .unwrap_or(bytes.len());
+    &bytes[..len]
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(bytes[offset..offset + 2].try_into().unwrap())
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
@@ -2,6 +2,7 @@
 
 mod bin;
 mod grub;
+mod ksymtab;
 mod qcow2;
 
 use std::{
@@ -12,6 +13,7 @@ use std::{
 };
 
 use bin::make_elf_for_qemu;
+use ksymtab::fill_kernel_symbol_table;
 
 use super::util::{cargo, profile_name_adapter, COMMON_CARGO_ARGS, DEFAULT_TARGET_RELPATH};
 use crate::{
@@ -209,9 +211,11 @@ fn build_kernel_elf(
         // We do not really allow unwinding except for kernel testing. However, we need to specify
         // this to show backtraces when panicking.
         "-C panic=unwind",
-        // This is to let rustc know that "cfg(ktest)" is our well-known configuration.
+        // This is to let rustc know that "cfg(ktest)" and "cfg(component_module)" are our
+        // well-known configurations.
         // See the [Rust Blog](https://blog.rust-lang.org/2024/05/06/check-cfg.html) for details.
         "--check-cfg cfg(ktest)",
+        "--check-cfg cfg(component_module)",
     ]);
 
     if matches!(arch, Arch::X86_64) {
@@ -252,6 +256,7 @@ fn build_kernel_elf(
         .join(&target_os_string)
         .join(profile_name_adapter(profile))
         .join(get_current_crate_info().name);
+    fill_kernel_symbol_table(&aster_bin_path);
 
     AsterBin::new(
         aster_bin_path,
@@ -26,7 +26,7 @@ pub fn execute_forwarded_command(subcommand: &str, args: &Vec<String>) -> ! {
     cargo.args(args);
 
     let env_rustflags = std::env::var("RUSTFLAGS").unwrap_or_default();
-    let rustflags = env_rustflags + " --check-cfg cfg(ktest)";
+    let rustflags = env_rustflags + " --check-cfg cfg(ktest) --check-cfg cfg(component_module)";
 
     cargo.env("RUSTFLAGS", rustflags);
 
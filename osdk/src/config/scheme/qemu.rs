@@ -93,7 +93,7 @@ impl QemuScheme {
 
 /// Keys with multiple values
 const MULTI_VALUE_KEYS: &[&str] = &[
-    "-device", "-chardev", "-object", "-netdev", "-drive", "-cdrom",
+    "-device", "-chardev", "-object", "-netdev", "-drive", "-cdrom", "-fsdev", "-virtfs",
 ];
 /// Keys with only single value
 const SINGLE_VALUE_KEYS: &[&str] = &["-cpu", "-machine", "-m", "-serial", "-monitor", "-display"];
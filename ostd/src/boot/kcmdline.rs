@@ -23,10 +23,20 @@ use crate::early_println;
 #[derive(PartialEq, Debug)]
 struct InitprocArgs {
     path: Option<String>,
+    rdinit_path: Option<String>,
     argv: Vec<CString>,
     envp: Vec<CString>,
 }
 
+/// The arguments to mount the root filesystem.
+#[derive(PartialEq, Debug)]
+struct RootArgs {
+    device: Option<String>,
+    fstype: Option<String>,
+    flags: Option<String>,
+    read_only: bool,
+}
+
 /// Kernel module arguments
 #[derive(PartialEq, Debug, Clone)]
 pub enum ModuleArg {
@@ -40,6 +50,7 @@ pub enum ModuleArg {
 #[derive(Debug)]
 pub struct KCmdlineArg {
     initproc: InitprocArgs,
+    root: RootArgs,
     module_args: BTreeMap<String, Vec<ModuleArg>>,
 }
 
@@ -49,6 +60,10 @@ impl KCmdlineArg {
     pub fn get_initproc_path(&self) -> Option<&str> {
         self.initproc.path.as_deref()
     }
+    /// Gets the path of the initprocess in the initramfs, which is given by `rdinit=`.
+    pub fn get_rdinit_path(&self) -> Option<&str> {
+        self.initproc.rdinit_path.as_deref()
+    }
     /// Gets the argument vector(argv) of the initprocess.
     pub fn get_initproc_argv(&self) -> &Vec<CString> {
         &self.initproc.argv
@@ -57,6 +72,24 @@ impl KCmdlineArg {
     pub fn get_initproc_envp(&self) -> &Vec<CString> {
         &self.initproc.envp
     }
+    /// Gets the root device, which is given by `root=`.
+    pub fn get_root_device(&self) -> Option<&str> {
+        self.root.device.as_deref()
+    }
+    /// Gets the filesystem type of the root device, which is given by `rootfstype=`.
+    pub fn get_root_fstype(&self) -> Option<&str> {
+        self.root.fstype.as_deref()
+    }
+    /// Gets the mount options of the root device, which are given by `rootflags=`.
+    pub fn get_root_flags(&self) -> Option<&str> {
+        self.root.flags.as_deref()
+    }
+    /// Returns whether the root device is mounted read-only.
+    ///
+    /// Like Linux, it is read-only unless `rw` is given.
+    pub fn is_root_read_only(&self) -> bool {
+        self.root.read_only
+    }
     /// Gets the argument vector of a kernel module.
     pub fn get_module_args(&self, module: &str) -> Option<&Vec<ModuleArg>> {
         self.module_args.get(module)
@@ -84,9 +117,16 @@ impl From<&str> for KCmdlineArg {
         let mut result: KCmdlineArg = KCmdlineArg {
             initproc: InitprocArgs {
                 path: None,
+                rdinit_path: None,
                 argv: Vec::new(),
                 envp: Vec::new(),
             },
+            root: RootArgs {
+                device: None,
+                fstype: None,
+                flags: None,
+                read_only: true,
+            },
             module_args: BTreeMap::new(),
         };
 
@@ -100,7 +140,10 @@ impl From<&str> for KCmdlineArg {
             // KernelArg => Arg "\s+" KernelArg | %empty
             // InitArg => Arg "\s+" InitArg | %empty
             if kcmdline_end {
-                if result.initproc.path.is_none() {
+                if result.initproc.path.is_none()
+                    && result.initproc.rdinit_path.is_none()
+                    && result.root.device.is_none()
+                {
                     panic!("Initproc arguments provided but no initproc path specified!");
                 }
                 result.initproc.argv.push(CString::new(arg).unwrap());
@@ -163,6 +206,18 @@ impl From<&str> for KCmdlineArg {
                         }
                         result.initproc.path = Some(value.to_string());
                     }
+                    "rdinit" => {
+                        result.initproc.rdinit_path = Some(value.to_string());
+                    }
+                    "root" => {
+                        result.root.device = Some(value.to_string());
+                    }
+                    "rootfstype" => {
+                        result.root.fstype = Some(value.to_string());
+                    }
+                    "rootflags" => {
+                        result.root.flags = Some(value.trim_matches('"').to_string());
+                    }
                     _ => {
                         // If the option is not recognized, it is passed to the initproc.
                         // Pattern 'option=value' is treated as the init environment.
@@ -172,14 +227,69 @@ impl From<&str> for KCmdlineArg {
                 }
             } else {
                 // There is no value, the entry is only a option.
-
-                // If the option is not recognized, it is passed to the initproc.
-                // Pattern 'option' without value is treated as the init argument.
-                let argv_entry = CString::new(option.to_string()).unwrap();
-                result.initproc.argv.push(argv_entry);
+                match option {
+                    "ro" => result.root.read_only = true,
+                    "rw" => result.root.read_only = false,
+                    _ => {
+                        // If the option is not recognized, it is passed to the initproc.
+                        // Pattern 'option' without value is treated as the init argument.
+                        let argv_entry = CString::new(option.to_string()).unwrap();
+                        result.initproc.argv.push(argv_entry);
+                    }
+                }
             }
         }
 
         result
     }
 }
+
+#[cfg(ktest)]
+mod test {
+    use super::*;
+    use crate::prelude::*;
+
+    #[ktest]
+    fn root_args() {
+        let kcmdline = KCmdlineArg::from(
+            "console=hvc0 root=/dev/vda rootfstype=ext2 rootflags=\"ro,noatime\" rw",
+        );
+        assert_eq!(kcmdline.get_root_device(), Some("/dev/vda"));
+        assert_eq!(kcmdline.get_root_fstype(), Some("ext2"));
+        assert_eq!(kcmdline.get_root_flags(), Some("ro,noatime"));
+        assert!(!kcmdline.is_root_read_only());
+        assert_eq!(kcmdline.get_initproc_path(), None);
+        assert_eq!(
+            kcmdline.get_initproc_envp(),
+            &vec![CString::new("console=hvc0").unwrap()]
+        );
+    }
+
+    #[ktest]
+    fn root_read_only() {
+        let kcmdline = KCmdlineArg::from("root=8:1");
+        assert!(kcmdline.is_root_read_only());
+        assert_eq!(kcmdline.get_root_fstype(), None);
+        assert_eq!(kcmdline.get_root_flags(), None);
+
+        let kcmdline = KCmdlineArg::from("root=8:1 rw ro");
+        assert!(kcmdline.is_root_read_only());
+    }
+
+    #[ktest]
+    fn initproc_args_after_root() {
+        let kcmdline = KCmdlineArg::from("rdinit=/init root=/dev/vda -- -v quiet");
+        assert_eq!(kcmdline.get_rdinit_path(), Some("/init"));
+        assert_eq!(kcmdline.get_root_device(), Some("/dev/vda"));
+        assert_eq!(
+            kcmdline.get_initproc_argv(),
+            &vec![CString::new("-v").unwrap(), CString::new("quiet").unwrap()]
+        );
+    }
+
+    #[ktest]
+    #[should_panic]
+    fn initproc_args_without_init() {
+        let _ = KCmdlineArg::from("rw -- -v");
+    }
+}
@@ -7,7 +7,7 @@ use alloc::{collections::VecDeque, fmt::Debug, sync::Arc, vec::Vec};
 use log::{debug, error};
 
 use super::device::MmioCommonDevice;
-use crate::bus::BusProbeError;
+use crate::{bus::BusProbeError, mm::Paddr};
 
 pub trait MmioDevice: Sync + Send + Debug {
     fn device_id(&self) -> u32;
@@ -15,6 +15,17 @@ pub trait MmioDevice: Sync + Send + Debug {
 
 /// MMIO device driver.
 pub trait MmioDriver: Sync + Send + Debug {
+    /// Returns the name of the driver.
+    ///
+    /// The default implementation returns the name of the implementing type without
+    /// its module path and generic arguments. Drivers should override it to report
+    /// a stable name.
+    fn name(&self) -> &'static str {
+        let type_name = core::any::type_name::<Self>();
+        let path = type_name.split('<').next().unwrap_or(type_name);
+        path.rsplit("::").next().unwrap_or(path)
+    }
+
     /// Probe an unclaimed mmio device.
     ///
     /// If the driver matches and succeeds in initializing the unclaimed device,
@@ -29,24 +40,59 @@ pub trait MmioDriver: Sync + Send + Debug {
     ) -> Result<Arc<dyn MmioDevice>, (BusProbeError, MmioCommonDevice)>;
 }
 
+/// The information of a device on the MMIO bus.
+#[derive(Debug, Clone)]
+pub struct MmioDeviceInfo {
+    /// The physical address of the device registers
+    pub address: Paddr,
+    pub device_id: u32,
+    /// The driver that the device is bound to, or `None` if it is not claimed
+    pub driver: Option<Arc<dyn MmioDriver>>,
+}
+
 /// MMIO bus
 pub struct MmioBus {
     common_devices: VecDeque<MmioCommonDevice>,
     devices: Vec<Arc<dyn MmioDevice>>,
     drivers: Vec<Arc<dyn MmioDriver>>,
+    /// The claimed devices with their drivers
+    bound_devices: Vec<MmioDeviceInfo>,
 }
 
 impl MmioBus {
+    /// Returns the information of all the devices on the bus, including the unclaimed ones.
+    pub fn devices(&self) -> Vec<MmioDeviceInfo> {
+        let unbound_devices = self.common_devices.iter().map(|device| MmioDeviceInfo {
+            address: device.address(),
+            device_id: device.device_id(),
+            driver: None,
+        });
+        let mut devices: Vec<_> = self
+            .bound_devices
+            .iter()
+            .cloned()
+            .chain(unbound_devices)
+            .collect();
+        devices.sort_by_key(|device| device.address);
+        devices
+    }
+
     pub fn register_driver(&mut self, driver: Arc<dyn MmioDriver>) {
         debug!("Register driver:{:#x?}", driver);
         let length = self.common_devices.len();
         for i in (0..length).rev() {
             let common_device = self.common_devices.pop_front().unwrap();
             let device_id = common_device.device_id();
+            let address = common_device.address();
             let device = match driver.probe(common_device) {
                 Ok(device) => {
                     debug_assert!(device_id == device.device_id());
                     self.devices.push(device);
+                    self.bound_devices.push(MmioDeviceInfo {
+                        address,
+                        device_id,
+                        driver: Some(driver.clone()),
+                    });
                     continue;
                 }
                 Err((err, device)) => {
@@ -64,11 +110,17 @@ impl MmioBus {
 
     pub(super) fn register_mmio_device(&mut self, mut mmio_device: MmioCommonDevice) {
         let device_id = mmio_device.device_id();
+        let address = mmio_device.address();
         for driver in self.drivers.iter() {
             mmio_device = match driver.probe(mmio_device) {
                 Ok(device) => {
                     debug_assert!(device_id == device.device_id());
                     self.devices.push(device);
+                    self.bound_devices.push(MmioDeviceInfo {
+                        address,
+                        device_id,
+                        driver: Some(driver.clone()),
+                    });
                     return;
                 }
                 Err((err, common_device)) => {
@@ -88,6 +140,7 @@ impl MmioBus {
             common_devices: VecDeque::new(),
             devices: Vec::new(),
             drivers: Vec::new(),
+            bound_devices: Vec::new(),
         }
     }
 }
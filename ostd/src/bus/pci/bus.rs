@@ -7,7 +7,10 @@ use core::fmt::Debug;
 
 use log::{debug, error};
 
-use super::{device_info::PciDeviceId, PciCommonDevice};
+use super::{
+    device_info::{PciDeviceId, PciDeviceLocation},
+    PciCommonDevice,
+};
 use crate::bus::BusProbeError;
 
 pub trait PciDevice: Sync + Send + Debug {
@@ -16,6 +19,17 @@ pub trait PciDevice: Sync + Send + Debug {
 
 /// PCI device driver, PCI bus will pass the device through the `probe` function when a new device is registered.
 pub trait PciDriver: Sync + Send + Debug {
+    /// Returns the name of the driver.
+    ///
+    /// The default implementation returns the name of the implementing type without
+    /// its module path and generic arguments. Drivers should override it to report
+    /// a stable name.
+    fn name(&self) -> &'static str {
+        let type_name = core::any::type_name::<Self>();
+        let path = type_name.split('<').next().unwrap_or(type_name);
+        path.rsplit("::").next().unwrap_or(path)
+    }
+
     /// Probe an unclaimed PCI device.
     ///
     /// If the driver matches and succeeds in initializing the unclaimed device,
@@ -31,6 +45,15 @@ pub trait PciDriver: Sync + Send + Debug {
     ) -> Result<Arc<dyn PciDevice>, (BusProbeError, PciCommonDevice)>;
 }
 
+/// The information of a device on the PCI bus.
+#[derive(Debug, Clone)]
+pub struct PciDeviceInfo {
+    pub location: PciDeviceLocation,
+    pub device_id: PciDeviceId,
+    /// The driver that the device is bound to, or `None` if it is not claimed
+    pub driver: Option<Arc<dyn PciDriver>>,
+}
+
 /// The PCI bus used to register PCI devices. If a component wishes to drive a PCI device, it needs to provide the following:
 ///
 /// 1. The structure that implements the PciDevice trait.
@@ -39,19 +62,44 @@ pub struct PciBus {
     common_devices: VecDeque<PciCommonDevice>,
     devices: Vec<Arc<dyn PciDevice>>,
     drivers: Vec<Arc<dyn PciDriver>>,
+    /// The claimed devices with their drivers
+    bound_devices: Vec<PciDeviceInfo>,
 }
 
 impl PciBus {
+    /// Returns the information of all the devices on the bus, including the unclaimed ones.
+    pub fn devices(&self) -> Vec<PciDeviceInfo> {
+        let unbound_devices = self.common_devices.iter().map(|device| PciDeviceInfo {
+            location: *device.location(),
+            device_id: *device.device_id(),
+            driver: None,
+        });
+        let mut devices: Vec<_> = self
+            .bound_devices
+            .iter()
+            .cloned()
+            .chain(unbound_devices)
+            .collect();
+        devices.sort_by_key(|device| device.location);
+        devices
+    }
+
     pub fn register_driver(&mut self, driver: Arc<dyn PciDriver>) {
         debug!("Register driver:{:#x?}", driver);
         let length = self.common_devices.len();
         for i in (0..length).rev() {
             let common_device = self.common_devices.pop_front().unwrap();
             let device_id = *common_device.device_id();
+            let location = *common_device.location();
             let device = match driver.probe(common_device) {
                 Ok(device) => {
                     debug_assert!(device_id == device.device_id());
                     self.devices.push(device);
+                    self.bound_devices.push(PciDeviceInfo {
+                        location,
+                        device_id,
+                        driver: Some(driver.clone()),
+                    });
                     continue;
                 }
                 Err((err, common_device)) => {
@@ -70,11 +118,17 @@ impl PciBus {
     pub(super) fn register_common_device(&mut self, mut common_device: PciCommonDevice) {
         debug!("Find pci common devices:{:x?}", common_device);
         let device_id = *common_device.device_id();
+        let location = *common_device.location();
         for driver in self.drivers.iter() {
             common_device = match driver.probe(common_device) {
                 Ok(device) => {
                     debug_assert!(device_id == device.device_id());
                     self.devices.push(device);
+                    self.bound_devices.push(PciDeviceInfo {
+                        location,
+                        device_id,
+                        driver: Some(driver.clone()),
+                    });
                     return;
                 }
                 Err((err, common_device)) => {
@@ -94,6 +148,7 @@ impl PciBus {
             common_devices: VecDeque::new(),
             devices: Vec::new(),
             drivers: Vec::new(),
+            bound_devices: Vec::new(),
         }
     }
 }
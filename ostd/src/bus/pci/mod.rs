@@ -27,6 +27,10 @@
 //! }
 //!
 //! impl PciDriver for PciDriverA {
+//!     fn name(&self) -> &'static str {
+//!         "driver-a"
+//!     }
+//!
 //!     fn probe(
 //!         &self,
 //!         device: PciCommonDevice,
@@ -0,0 +1,96 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The symbol table of the kernel.
+//!
+//! The space of the table is reserved in the `.ksymtab` section of the kernel
+//! image, and the table is filled by OSDK with the function and the data symbols
+//! of the kernel after the kernel is linked. It is used to resolve the undefined
+//! symbols of the kernel modules that are loaded at runtime.
+//!
+//! The table starts with the number of the symbols, followed by the entries of
+//! the symbols sorted by their names, and then the names. Each entry consists of
+//! the address of the symbol, the offset of its name from the start of the names
+//! and the length of its name. The number and the addresses are little-endian
+//! `u64`s, and the offsets and the lengths are little-endian `u32`s.
+//!
+//! The space is only reserved with the `modules` feature, so that the kernels
+//! that never load modules do not carry it. The table is empty without the
+//! feature or if the kernel is not built by OSDK.
+
+use crate::mm::Vaddr;
+
+/// The size of the space reserved for the table, which should be large
+/// enough for OSDK to fill the table.
+#[cfg(feature = "modules")]
+const KSYMTAB_SIZE: usize = 8 * 1024 * 1024;
+
+/// The size of an entry in the table.
+const ENTRY_SIZE: usize = 16;
+
+#[cfg(feature = "modules")]
+#[used]
+#[link_section = ".ksymtab"]
+static KSYMTAB_SPACE: [u8; KSYMTAB_SIZE] = [0; KSYMTAB_SIZE];
+
+extern "C" {
+    fn __ksymtab();
+    fn __ksymtab_end();
+}
+
+/// Returns the address of the symbol named `name` in the kernel.
+pub fn lookup(name: &str) -> Option<Vaddr> {
+    let table = ksymtab();
+    let nr_symbols = usize::try_from(read_u64(table, 0)?).ok()?;
+    let names_offset = nr_symbols.checked_mul(ENTRY_SIZE)?.checked_add(8)?;
+    let names = table.get(names_offset..)?;
+
+    let (mut low, mut high) = (0, nr_symbols);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        let entry_offset = 8 + mid * ENTRY_SIZE;
+        let name_offset = read_u32(table, entry_offset + 8)? as usize;
+        let name_len = read_u32(table, entry_offset + 12)? as usize;
+        let symbol_name = names.get(name_offset..name_offset.checked_add(name_len)?)?;
+        match symbol_name.cmp(name.as_bytes()) {
+            core::cmp::Ordering::Less => low = mid + 1,
+            core::cmp::Ordering::Greater => high = mid,
+            core::cmp::Ordering::Equal => {
+                return usize::try_from(read_u64(table, entry_offset)?).ok();
+            }
+        }
+    }
+    None
+}
+
+fn ksymtab() -> &'static [u8] {
+    let len = __ksymtab_end as usize - __ksymtab as usize;
+    // SAFETY: `__ksymtab` is the start of the `.ksymtab` section, which is
+    // readable and is never written after the kernel is loaded.
+    unsafe { core::slice::from_raw_parts(__ksymtab as *const u8, len) }
+}
+
+fn read_u64(table: &[u8], offset: usize) -> Option<u64> {
+    let bytes = table.get(offset..offset.checked_add(8)?)?;
+    Some(u64::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+fn read_u32(table: &[u8], offset: usize) -> Option<u32> {
+    let bytes = table.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+#[cfg(ktest)]
+mod test {
+    use crate::prelude::*;
+
+    #[ktest]
+    fn lookup_kernel_symbol() {
+        // OSDK fills the table when building the kernel for the tests.
+        if cfg!(feature = "modules") {
+            assert_eq!(super::lookup("__ksymtab"), Some(super::__ksymtab as usize));
+        } else {
+            assert_eq!(super::lookup("__ksymtab"), None);
+        }
+        assert_eq!(super::lookup("__ksymtab_not_a_symbol"), None);
+    }
+}
@@ -34,6 +34,7 @@ pub mod console;
 pub mod cpu;
 mod error;
 pub mod io_mem;
+pub mod ksym;
 pub mod logger;
 pub mod mm;
 pub mod panicking;
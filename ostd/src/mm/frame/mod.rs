@@ -155,6 +155,20 @@ impl VmIo for Frame {
     }
 }
 
+/// Returns the total number of the physical frames managed by the allocator, which
+/// excludes the memory reserved by the firmware or occupied by the kernel image.
+pub fn total_frames() -> usize {
+    super::page::allocator::stats().0
+}
+
+/// Returns the number of the physical frames that are not allocated.
+///
+/// The frames allocated for the kernel heap and the page metadata are counted as
+/// allocated as well.
+pub fn free_frames() -> usize {
+    super::page::allocator::stats().1
+}
+
 impl PageMeta for FrameMeta {
     const USAGE: PageUsage = PageUsage::Frame;
 
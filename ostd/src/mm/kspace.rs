@@ -13,6 +13,9 @@
 //!
 //! ```text
 //! +-+ <- the highest used address (0xffff_ffff_ffff_0000)
+//! | |         For the kernel modules, 1 GiB.
+//! | |         Mapped frames are tracked with handles.
+//! +-+ <- 0xffff_ffff_c000_0000
 //! | |         For the kernel code, 1 GiB. Mapped frames are untracked.
 //! +-+ <- 0xffff_ffff_8000_0000
 //! | |
@@ -82,6 +85,11 @@ pub fn kernel_loaded_offset() -> usize {
 
 const KERNEL_CODE_BASE_VADDR: usize = 0xffff_ffff_8000_0000 << ADDR_WIDTH_SHIFT;
 
+/// The kernel modules are mapped right after the kernel code, so that the code of the
+/// modules can refer to the kernel with 32-bit relative or sign-extended addresses.
+const MODULE_BASE_VADDR: Vaddr = 0xffff_ffff_c000_0000 << ADDR_WIDTH_SHIFT;
+pub(in crate::mm) const MODULE_VADDR_RANGE: Range<Vaddr> = MODULE_BASE_VADDR..KERNEL_END_VADDR;
+
 const FRAME_METADATA_CAP_VADDR: Vaddr = 0xffff_ff00_0000_0000 << ADDR_WIDTH_SHIFT;
 const FRAME_METADATA_BASE_VADDR: Vaddr = 0xffff_fe00_0000_0000 << ADDR_WIDTH_SHIFT;
 pub(in crate::mm) const FRAME_METADATA_RANGE: Range<Vaddr> =
@@ -13,6 +13,7 @@ pub mod frame;
 pub(crate) mod heap_allocator;
 mod io;
 pub(crate) mod kspace;
+mod module_memory;
 mod offset;
 pub(crate) mod page;
 pub(crate) mod page_prop;
@@ -28,6 +29,7 @@ pub use self::{
     dma::{Daddr, DmaCoherent, DmaDirection, DmaStream, DmaStreamSlice, HasDaddr},
     frame::{options::FrameAllocOptions, Frame, FrameVec, FrameVecIter, Segment},
     io::{KernelSpace, UserSpace, VmIo, VmReader, VmWriter},
+    module_memory::ModuleMemory,
     page_prop::{CachePolicy, PageFlags, PageProperty},
     space::{VmMapOptions, VmSpace},
 };
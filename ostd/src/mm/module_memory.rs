@@ -0,0 +1,284 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The memory of the kernel modules that are loaded at runtime.
+
+use alloc::collections::BTreeMap;
+use core::ops::Range;
+
+use crate::{
+    arch::mm::tlb_flush_addr_range,
+    mm::{
+        kspace::{KERNEL_PAGE_TABLE, MODULE_VADDR_RANGE},
+        CachePolicy, FrameAllocOptions, PageFlags, PageProperty, PrivilegedPageFlags, Vaddr, VmIo,
+        VmReader, VmWriter, PAGE_SIZE,
+    },
+    prelude::*,
+    sync::SpinLock,
+};
+
+/// The memory that holds the code and the data of a kernel module.
+///
+/// The memory is mapped in the area of the kernel modules, which is right after
+/// the kernel code. It is zeroed and mapped as readable and writable once
+/// allocated, so that the loader of the module can write the sections of the
+/// module into it with the [`VmIo`] methods. Then the loader should protect
+/// the code and the read-only data with [`ModuleMemory::make_executable`],
+/// before running the constructors in the code with
+/// [`ModuleMemory::run_init_functions`]. The rest of the memory, e.g., the
+/// writable data, stays writable but not executable.
+///
+/// Since the code of a module runs with the privilege of the kernel, loading a
+/// module means trusting it as much as the kernel itself. The code should be
+/// built from the source of the kernel with the same compiler, so that it does
+/// not violate the memory safety of the kernel. The trust is the reason why
+/// running the code is not `unsafe`, just like running the code of the kernel.
+pub struct ModuleMemory {
+    vaddr_range: Range<Vaddr>,
+    /// The range of the code, which is set once the memory is executable
+    text_range: Option<Range<Vaddr>>,
+}
+
+impl ModuleMemory {
+    /// Allocates the memory of `size` bytes for a module.
+    pub fn alloc(size: usize) -> Result<Self> {
+        let nframes = size.div_ceil(PAGE_SIZE).max(1);
+        let frames = FrameAllocOptions::new(nframes).alloc()?;
+        let module_memory = Self {
+            vaddr_range: alloc_vaddr_range(nframes * PAGE_SIZE)?,
+            text_range: None,
+        };
+
+        let prop = PageProperty {
+            flags: PageFlags::RW,
+            cache: CachePolicy::Writeback,
+            priv_flags: PrivilegedPageFlags::GLOBAL,
+        };
+        let mut cursor = KERNEL_PAGE_TABLE
+            .get()
+            .unwrap()
+            .cursor_mut(&module_memory.vaddr_range)?;
+        for frame in frames.into_iter() {
+            // SAFETY: the range is reserved for the memory, so mapping the frames
+            // in it does not affect other mappings.
+            unsafe {
+                cursor.map(frame.into(), prop);
+            }
+        }
+        Ok(module_memory)
+    }
+
+    /// Returns the starting virtual address of the memory.
+    pub fn start_vaddr(&self) -> Vaddr {
+        self.vaddr_range.start
+    }
+
+    /// Returns the size of the memory in bytes.
+    pub fn nbytes(&self) -> usize {
+        self.vaddr_range.len()
+    }
+
+    /// Makes the code readable and executable, and the read-only data readable.
+    ///
+    /// `text` and `rodata` are the page-aligned ranges of the offsets of the code and
+    /// the read-only data in the memory, which must not overlap. Neither of them is
+    /// writable afterwards, while the rest of the memory stays readable and writable
+    /// but not executable.
+    ///
+    /// The memory can no longer be written with the [`VmIo`] methods afterwards.
+    pub fn make_executable(&mut self, text: Range<usize>, rodata: Range<usize>) -> Result<()> {
+        if self.text_range.is_some() {
+            return Err(Error::AccessDenied);
+        }
+        for range in [&text, &rodata] {
+            if range.start % PAGE_SIZE != 0
+                || range.end % PAGE_SIZE != 0
+                || range.start > range.end
+                || range.end > self.nbytes()
+            {
+                return Err(Error::InvalidArgs);
+            }
+        }
+        if text.start < rodata.end && rodata.start < text.end {
+            return Err(Error::InvalidArgs);
+        }
+
+        self.protect(&text, PageFlags::RX)?;
+        self.protect(&rodata, PageFlags::R)?;
+
+        self.text_range = Some(self.start_vaddr() + text.start..self.start_vaddr() + text.end);
+        Ok(())
+    }
+
+    /// Runs the constructors of the module at `functions` in order.
+    ///
+    /// Each constructor is a function in the code of the module, e.g., an entry of
+    /// the `.init_array` sections, which takes no arguments and returns nothing with
+    /// the C calling convention.
+    ///
+    /// Nothing is run if the memory is not executable or one of `functions` is not
+    /// in the code.
+    pub fn run_init_functions(&self, functions: &[Vaddr]) -> Result<()> {
+        let Some(text_range) = self.text_range.as_ref() else {
+            return Err(Error::AccessDenied);
+        };
+        if !functions
+            .iter()
+            .all(|function| text_range.contains(function))
+        {
+            return Err(Error::InvalidArgs);
+        }
+
+        for &function in functions {
+            // SAFETY: the function is in the code of the module, which is executable.
+            // The module is trusted as a part of the kernel, so its constructors take
+            // no arguments and return nothing with the C calling convention, and do
+            // not violate the memory safety of the kernel.
+            let function: extern "C" fn() = unsafe { core::mem::transmute(function) };
+            function();
+        }
+        Ok(())
+    }
+
+    /// Changes the flags of the pages in the range of the offsets.
+    fn protect(&self, range: &Range<usize>, flags: PageFlags) -> Result<()> {
+        if range.is_empty() {
+            return Ok(());
+        }
+
+        let vaddr_range = self.start_vaddr() + range.start..self.start_vaddr() + range.end;
+        let mut cursor = KERNEL_PAGE_TABLE.get().unwrap().cursor_mut(&vaddr_range)?;
+        // SAFETY: the range is in the memory, and the code and the data of the module
+        // are not used until the memory is executable, so it is fine to make it read-only.
+        unsafe {
+            cursor.protect(vaddr_range.len(), |p| p.flags = flags, false)?;
+        }
+        drop(cursor);
+        tlb_flush_addr_range(&vaddr_range);
+        Ok(())
+    }
+
+    fn check_range(&self, offset: usize, len: usize) -> Result<()> {
+        // Do bound check with potential integer overflow in mind
+        let max_offset = offset.checked_add(len).ok_or(Error::Overflow)?;
+        if max_offset > self.nbytes() {
+            return Err(Error::InvalidArgs);
+        }
+        Ok(())
+    }
+}
+
+impl VmIo for ModuleMemory {
+    fn read_bytes(&self, offset: usize, buf: &mut [u8]) -> Result<()> {
+        self.check_range(offset, buf.len())?;
+        // SAFETY: the range is in the memory, which is mapped and readable.
+        let mut reader = unsafe {
+            VmReader::from_kernel_space((self.start_vaddr() + offset) as *const u8, buf.len())
+        };
+        let len = reader.read(&mut buf.into());
+        debug_assert!(len == buf.len());
+        Ok(())
+    }
+
+    fn write_bytes(&self, offset: usize, buf: &[u8]) -> Result<()> {
+        if self.text_range.is_some() {
+            return Err(Error::AccessDenied);
+        }
+        self.check_range(offset, buf.len())?;
+        // SAFETY: the range is in the memory, which is mapped and writable since
+        // it is not executable yet. The code and the data of the module are not
+        // used until the memory is executable.
+        let mut writer = unsafe {
+            VmWriter::from_kernel_space((self.start_vaddr() + offset) as *mut u8, buf.len())
+        };
+        let len = writer.write(&mut buf.into());
+        debug_assert!(len == buf.len());
+        Ok(())
+    }
+}
+
+impl Drop for ModuleMemory {
+    fn drop(&mut self) {
+        let kpt = KERNEL_PAGE_TABLE.get().unwrap();
+        // SAFETY: the range is reserved for the memory, which is no longer used.
+        // The frames mapped in it are freed once unmapped.
+        if unsafe { kpt.unmap(&self.vaddr_range) }.is_ok() {
+            tlb_flush_addr_range(&self.vaddr_range);
+        }
+        free_vaddr_range(&self.vaddr_range);
+    }
+}
+
+/// The allocated ranges in the area of the kernel modules, which are indexed by
+/// their starting addresses and map to their ending addresses.
+static ALLOCATED_VADDR_RANGES: SpinLock<BTreeMap<Vaddr, Vaddr>> = SpinLock::new(BTreeMap::new());
+
+/// Allocates a range of `size` bytes in the area of the kernel modules with the first fit.
+fn alloc_vaddr_range(size: usize) -> Result<Range<Vaddr>> {
+    let mut allocated = ALLOCATED_VADDR_RANGES.lock();
+    let mut start = MODULE_VADDR_RANGE.start;
+    for (&allocated_start, &allocated_end) in allocated.iter() {
+        if allocated_start - start >= size {
+            break;
+        }
+        start = allocated_end;
+    }
+    if MODULE_VADDR_RANGE.end - start < size {
+        return Err(Error::NoMemory);
+    }
+    allocated.insert(start, start + size);
+    Ok(start..start + size)
+}
+
+fn free_vaddr_range(range: &Range<Vaddr>) {
+    ALLOCATED_VADDR_RANGES.lock().remove(&range.start);
+}
+
+#[cfg(ktest)]
+mod test {
+    use super::*;
+
+    #[ktest]
+    fn alloc_and_call() {
+        let mut memory = ModuleMemory::alloc(2 * PAGE_SIZE + 1).unwrap();
+        assert_eq!(memory.nbytes(), 3 * PAGE_SIZE);
+        assert!(MODULE_VADDR_RANGE.contains(&memory.start_vaddr()));
+
+        // ret
+        memory.write_val(PAGE_SIZE, &0xc3u8).unwrap();
+        memory.write_val(0, &0x2au8).unwrap();
+        let function = memory.start_vaddr() + PAGE_SIZE;
+        assert!(memory.run_init_functions(&[function]).is_err());
+        assert!(memory
+            .make_executable(PAGE_SIZE..2 * PAGE_SIZE, PAGE_SIZE..2 * PAGE_SIZE)
+            .is_err());
+        assert!(memory
+            .make_executable(PAGE_SIZE..2 * PAGE_SIZE, 1..PAGE_SIZE)
+            .is_err());
+        memory
+            .make_executable(PAGE_SIZE..2 * PAGE_SIZE, 0..PAGE_SIZE)
+            .unwrap();
+        assert_eq!(memory.read_val::<u8>(0).unwrap(), 0x2a);
+        assert_eq!(memory.read_val::<u8>(PAGE_SIZE).unwrap(), 0xc3);
+        assert!(memory.write_val(0, &0u8).is_err());
+        // The read-only data are not executable.
+        assert!(memory
+            .run_init_functions(&[function, memory.start_vaddr()])
+            .is_err());
+        // The code at the address only returns.
+        memory.run_init_functions(&[function]).unwrap();
+
+        // The data after the code are still writable.
+        let data = (memory.start_vaddr() + 2 * PAGE_SIZE) as *mut u64;
+        // SAFETY: The last page of the memory is mapped as writable and used by nothing.
+        unsafe {
+            data.write_volatile(42);
+            assert_eq!(data.read_volatile(), 42);
+        }
+
+        // The virtual addresses are reused once the memory is freed.
+        let start_vaddr = memory.start_vaddr();
+        drop(memory);
+        let memory = ModuleMemory::alloc(3 * PAGE_SIZE).unwrap();
+        assert_eq!(memory.start_vaddr(), start_vaddr);
+    }
+}
@@ -15,7 +15,37 @@ use spin::Once;
 use super::{cont_pages::ContPages, meta::PageMeta, Page};
 use crate::{boot::memory_region::MemoryRegionType, mm::PAGE_SIZE, sync::SpinLock};
 
-pub(in crate::mm) static PAGE_ALLOCATOR: Once<SpinLock<FrameAllocator>> = Once::new();
+pub(in crate::mm) static PAGE_ALLOCATOR: Once<SpinLock<CountingFrameAllocator>> = Once::new();
+
+/// A frame allocator that counts the allocated frames, so that the usage of the
+/// physical memory can be reported.
+pub(in crate::mm) struct CountingFrameAllocator {
+    allocator: FrameAllocator,
+    total: usize,
+    allocated: usize,
+}
+
+impl CountingFrameAllocator {
+    /// Allocates `count` contiguous frames, returning the index of the first one.
+    pub(in crate::mm) fn alloc(&mut self, count: usize) -> Option<usize> {
+        let start = self.allocator.alloc(count)?;
+        self.allocated += count;
+        Some(start)
+    }
+
+    /// Deallocates `count` contiguous frames starting from the index `start`.
+    pub(in crate::mm) fn dealloc(&mut self, start: usize, count: usize) {
+        self.allocator.dealloc(start, count);
+        self.allocated -= count;
+    }
+}
+
+/// Returns the total number of the frames that the allocator manages and the number
+/// of the free ones.
+pub(in crate::mm) fn stats() -> (usize, usize) {
+    let allocator = PAGE_ALLOCATOR.get().unwrap().lock();
+    (allocator.total, allocator.total - allocator.allocated)
+}
 
 /// Allocate a single page.
 pub(crate) fn alloc_single<M: PageMeta>() -> Option<Page<M>> {
@@ -64,6 +94,7 @@ pub(crate) fn alloc<M: PageMeta>(len: usize) -> Option<Vec<Page<M>>> {
 pub(crate) fn init() {
     let regions = crate::boot::memory_regions();
     let mut allocator = FrameAllocator::<32>::new();
+    let mut total = 0;
     for region in regions.iter() {
         if region.typ() == MemoryRegionType::Usable {
             // Make the memory region page-aligned, and skip if it is too small.
@@ -75,6 +106,7 @@ pub(crate) fn init() {
             }
             // Add global free pages to the frame allocator.
             allocator.add_frame(start, end);
+            total += end - start;
             info!(
                 "Found usable region, start:{:x}, end:{:x}",
                 region.base(),
@@ -82,5 +114,11 @@ pub(crate) fn init() {
             );
         }
     }
-    PAGE_ALLOCATOR.call_once(|| SpinLock::new(allocator));
+    PAGE_ALLOCATOR.call_once(|| {
+        SpinLock::new(CountingFrameAllocator {
+            allocator,
+            total,
+            allocated: 0,
+        })
+    });
 }
@@ -5,7 +5,10 @@
 #![allow(missing_docs)]
 #![allow(dead_code)]
 
-use core::cell::UnsafeCell;
+use core::{
+    cell::UnsafeCell,
+    sync::atomic::{AtomicU64, Ordering},
+};
 
 use intrusive_collections::{intrusive_adapter, LinkedListAtomicLink};
 
@@ -111,6 +114,8 @@ impl Drop for KernelStack {
 /// If having a user space, the task can switch to the user space to
 /// execute user code. Multiple tasks can share a single user space.
 pub struct Task {
+    /// The ID of the task, which is never reused
+    id: u64,
     func: Box<dyn Fn() + Send + Sync>,
     data: Box<dyn Any + Send + Sync>,
     user_space: Option<Arc<UserSpace>>,
@@ -124,6 +129,9 @@ pub struct Task {
     cpu_affinity: CpuSet,
 }
 
+/// The ID of the next task to build.
+static NEXT_TASK_ID: AtomicU64 = AtomicU64::new(1);
+
 // TaskAdapter struct is implemented for building relationships between doubly linked list and Task struct
 intrusive_adapter!(pub TaskAdapter = Arc<Task>: Task { link: LinkedListAtomicLink });
 
@@ -164,6 +172,11 @@ impl Task {
         schedule();
     }
 
+    /// Returns the ID of the task, which is unique among all the tasks ever built.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
     /// Returns the task status.
     pub fn status(&self) -> TaskStatus {
         self.task_inner.lock_irq_disabled().task_status
@@ -295,6 +308,7 @@ impl TaskOptions {
         }
 
         let mut new_task = Task {
+            id: NEXT_TASK_ID.fetch_add(1, Ordering::Relaxed),
             func: self.func.unwrap(),
             data: self.data.unwrap(),
             user_space: self.user_space,
@@ -0,0 +1,560 @@
+// SPDX-License-Identifier: MPL-2.0
+
+// A FUSE passthrough daemon that speaks the raw protocol on /dev/fuse without
+// libfuse. The parent process mounts the filesystem and exercises it, while the
+// child process serves the requests by forwarding them to a backing directory.
+
+#include <dirent.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <limits.h>
+#include <linux/fuse.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/mount.h>
+#include <sys/stat.h>
+#include <sys/statfs.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define MAX_NODES 1024
+#define BUF_SIZE (FUSE_MIN_READ_BUFFER + 128 * 1024)
+
+#define CHECK(cond)                                                      \
+	do {                                                             \
+		if (!(cond)) {                                           \
+			fprintf(stderr, "%s:%d: check failed: %s (%s)\n", \
+				__FILE__, __LINE__, #cond,               \
+				strerror(errno));                        \
+			exit(EXIT_FAILURE);                              \
+		}                                                        \
+	} while (0)
+
+static char *node_paths[MAX_NODES];
+static char in_buf[BUF_SIZE];
+static char out_buf[BUF_SIZE];
+
+// ============================================================================
+// The daemon
+// ============================================================================
+
+static const char *node_path(uint64_t nodeid)
+{
+	if (nodeid >= MAX_NODES || node_paths[nodeid] == NULL)
+		return NULL;
+	return node_paths[nodeid];
+}
+
+static uint64_t node_get(const char *path)
+{
+	uint64_t free_id = 0;
+
+	for (uint64_t id = FUSE_ROOT_ID; id < MAX_NODES; id++) {
+		if (node_paths[id] == NULL) {
+			if (free_id == 0)
+				free_id = id;
+		} else if (strcmp(node_paths[id], path) == 0) {
+			return id;
+		}
+	}
+	if (free_id != 0)
+		node_paths[free_id] = strdup(path);
+	return free_id;
+}
+
+static void node_rename(const char *old_path, const char *new_path)
+{
+	size_t old_len = strlen(old_path);
+	char buf[PATH_MAX];
+
+	for (uint64_t id = FUSE_ROOT_ID + 1; id < MAX_NODES; id++) {
+		char *path = node_paths[id];
+
+		if (path == NULL || strncmp(path, old_path, old_len) != 0 ||
+		    (path[old_len] != '\0' && path[old_len] != '/'))
+			continue;
+		snprintf(buf, sizeof(buf), "%s%s", new_path, path + old_len);
+		free(path);
+		node_paths[id] = strdup(buf);
+	}
+}
+
+static int child_path(char *buf, uint64_t parent, const char *name)
+{
+	const char *parent_path = node_path(parent);
+
+	if (parent_path == NULL)
+		return -ENOENT;
+	snprintf(buf, PATH_MAX, "%s/%s", parent_path, name);
+	return 0;
+}
+
+static void fill_attr(struct fuse_attr *attr, const struct stat *st,
+		      uint64_t nodeid)
+{
+	memset(attr, 0, sizeof(*attr));
+	attr->ino = nodeid;
+	attr->size = st->st_size;
+	attr->blocks = st->st_blocks;
+	attr->atime = st->st_atim.tv_sec;
+	attr->mtime = st->st_mtim.tv_sec;
+	attr->ctime = st->st_ctim.tv_sec;
+	attr->atimensec = st->st_atim.tv_nsec;
+	attr->mtimensec = st->st_mtim.tv_nsec;
+	attr->ctimensec = st->st_ctim.tv_nsec;
+	attr->mode = st->st_mode;
+	attr->nlink = st->st_nlink;
+	attr->uid = st->st_uid;
+	attr->gid = st->st_gid;
+	attr->rdev = st->st_rdev;
+	attr->blksize = st->st_blksize;
+}
+
+// Replies with the entry of `path`, returning the length of the reply.
+static int reply_entry(const char *path)
+{
+	struct fuse_entry_out *out = (void *)out_buf;
+	struct stat st;
+	uint64_t nodeid;
+
+	if (lstat(path, &st) < 0)
+		return -errno;
+	nodeid = node_get(path);
+	if (nodeid == 0)
+		return -ENOMEM;
+	memset(out, 0, sizeof(*out));
+	out->nodeid = nodeid;
+	fill_attr(&out->attr, &st, nodeid);
+	return sizeof(*out);
+}
+
+static int reply_attr(const char *path, uint64_t nodeid)
+{
+	struct fuse_attr_out *out = (void *)out_buf;
+	struct stat st;
+
+	if (lstat(path, &st) < 0)
+		return -errno;
+	memset(out, 0, sizeof(*out));
+	fill_attr(&out->attr, &st, nodeid);
+	return sizeof(*out);
+}
+
+static int do_setattr(const char *path, const struct fuse_setattr_in *in)
+{
+	if ((in->valid & FATTR_MODE) && chmod(path, in->mode & 07777) < 0)
+		return -errno;
+	if ((in->valid & (FATTR_UID | FATTR_GID)) &&
+	    lchown(path, (in->valid & FATTR_UID) ? in->uid : (uid_t)-1,
+		   (in->valid & FATTR_GID) ? in->gid : (gid_t)-1) < 0)
+		return -errno;
+	if ((in->valid & FATTR_SIZE) && truncate(path, in->size) < 0)
+		return -errno;
+	if (in->valid & (FATTR_ATIME | FATTR_MTIME)) {
+		struct timespec times[2] = {
+			{ .tv_sec = in->atime, .tv_nsec = in->atimensec },
+			{ .tv_sec = in->mtime, .tv_nsec = in->mtimensec },
+		};
+
+		if (!(in->valid & FATTR_ATIME))
+			times[0].tv_nsec = UTIME_OMIT;
+		if (!(in->valid & FATTR_MTIME))
+			times[1].tv_nsec = UTIME_OMIT;
+		if (utimensat(AT_FDCWD, path, times, AT_SYMLINK_NOFOLLOW) < 0)
+			return -errno;
+	}
+	return 0;
+}
+
+static int do_readdir(const struct fuse_read_in *in)
+{
+	DIR *dir = (DIR *)(uintptr_t)in->fh;
+	struct dirent *dirent;
+	uint64_t index = 0;
+	size_t len = 0;
+
+	// The offset of an entry is the index of the next one.
+	rewinddir(dir);
+	while ((dirent = readdir(dir)) != NULL) {
+		struct fuse_dirent *out = (void *)(out_buf + len);
+		size_t name_len = strlen(dirent->d_name);
+		size_t entry_len =
+			FUSE_DIRENT_ALIGN(FUSE_NAME_OFFSET + name_len);
+
+		index++;
+		if (index <= in->offset)
+			continue;
+		if (len + entry_len > in->size)
+			break;
+		out->ino = dirent->d_ino;
+		out->off = index;
+		out->namelen = name_len;
+		out->type = dirent->d_type;
+		memcpy(out->name, dirent->d_name, name_len);
+		memset(out->name + name_len, 0,
+		       entry_len - FUSE_NAME_OFFSET - name_len);
+		len += entry_len;
+	}
+	return len;
+}
+
+// Handles a request, returning the length of the reply or a negative errno.
+static int handle_request(const struct fuse_in_header *header, const void *arg)
+{
+	const char *path = node_path(header->nodeid);
+	const char *name = arg;
+	char new_path[PATH_MAX];
+	int ret;
+
+	if (header->opcode != FUSE_INIT && path == NULL)
+		return -ENOENT;
+
+	switch (header->opcode) {
+	case FUSE_INIT: {
+		const struct fuse_init_in *in = arg;
+		struct fuse_init_out *out = (void *)out_buf;
+
+		memset(out, 0, sizeof(*out));
+		out->major = FUSE_KERNEL_VERSION;
+		out->minor = in->minor;
+		out->max_write = 128 * 1024;
+		return sizeof(*out);
+	}
+	case FUSE_LOOKUP:
+		if ((ret = child_path(new_path, header->nodeid, name)) < 0)
+			return ret;
+		return reply_entry(new_path);
+	case FUSE_GETATTR:
+		return reply_attr(path, header->nodeid);
+	case FUSE_SETATTR:
+		if ((ret = do_setattr(path, arg)) < 0)
+			return ret;
+		return reply_attr(path, header->nodeid);
+	case FUSE_READLINK:
+		ret = readlink(path, out_buf, sizeof(out_buf));
+		return ret < 0 ? -errno : ret;
+	case FUSE_SYMLINK: {
+		const char *target = name + strlen(name) + 1;
+
+		if ((ret = child_path(new_path, header->nodeid, name)) < 0)
+			return ret;
+		if (symlink(target, new_path) < 0)
+			return -errno;
+		return reply_entry(new_path);
+	}
+	case FUSE_MKNOD: {
+		const struct fuse_mknod_in *in = arg;
+
+		if ((ret = child_path(new_path, header->nodeid,
+				      (const char *)(in + 1))) < 0)
+			return ret;
+		if (mknod(new_path, in->mode, in->rdev) < 0)
+			return -errno;
+		return reply_entry(new_path);
+	}
+	case FUSE_MKDIR: {
+		const struct fuse_mkdir_in *in = arg;
+
+		if ((ret = child_path(new_path, header->nodeid,
+				      (const char *)(in + 1))) < 0)
+			return ret;
+		if (mkdir(new_path, in->mode & 07777) < 0)
+			return -errno;
+		return reply_entry(new_path);
+	}
+	case FUSE_UNLINK:
+	case FUSE_RMDIR:
+		if ((ret = child_path(new_path, header->nodeid, name)) < 0)
+			return ret;
+		ret = header->opcode == FUSE_UNLINK ? unlink(new_path) :
+						      rmdir(new_path);
+		return ret < 0 ? -errno : 0;
+	case FUSE_RENAME: {
+		const struct fuse_rename_in *in = arg;
+		const char *old_name = (const char *)(in + 1);
+		const char *new_name = old_name + strlen(old_name) + 1;
+		char old_path[PATH_MAX];
+
+		if ((ret = child_path(old_path, header->nodeid, old_name)) < 0 ||
+		    (ret = child_path(new_path, in->newdir, new_name)) < 0)
+			return ret;
+		if (rename(old_path, new_path) < 0)
+			return -errno;
+		node_rename(old_path, new_path);
+		return 0;
+	}
+	case FUSE_LINK: {
+		const struct fuse_link_in *in = arg;
+		const char *old_path = node_path(in->oldnodeid);
+
+		if (old_path == NULL)
+			return -ENOENT;
+		if ((ret = child_path(new_path, header->nodeid,
+				      (const char *)(in + 1))) < 0)
+			return ret;
+		if (link(old_path, new_path) < 0)
+			return -errno;
+		return reply_entry(new_path);
+	}
+	case FUSE_OPEN: {
+		const struct fuse_open_in *in = arg;
+		struct fuse_open_out *out = (void *)out_buf;
+		int fd = open(path, in->flags & ~(O_CREAT | O_EXCL | O_TRUNC));
+
+		if (fd < 0)
+			return -errno;
+		memset(out, 0, sizeof(*out));
+		out->fh = fd;
+		return sizeof(*out);
+	}
+	case FUSE_CREATE: {
+		const struct fuse_create_in *in = arg;
+		struct fuse_open_out *open_out;
+		int fd;
+
+		if ((ret = child_path(new_path, header->nodeid,
+				      (const char *)(in + 1))) < 0)
+			return ret;
+		fd = open(new_path, in->flags, in->mode & 07777);
+		if (fd < 0)
+			return -errno;
+		if ((ret = reply_entry(new_path)) < 0) {
+			close(fd);
+			return ret;
+		}
+		open_out = (void *)(out_buf + ret);
+		memset(open_out, 0, sizeof(*open_out));
+		open_out->fh = fd;
+		return ret + sizeof(*open_out);
+	}
+	case FUSE_READ: {
+		const struct fuse_read_in *in = arg;
+
+		ret = pread(in->fh, out_buf, in->size, in->offset);
+		return ret < 0 ? -errno : ret;
+	}
+	case FUSE_WRITE: {
+		const struct fuse_write_in *in = arg;
+		struct fuse_write_out *out = (void *)out_buf;
+
+		ret = pwrite(in->fh, in + 1, in->size, in->offset);
+		if (ret < 0)
+			return -errno;
+		memset(out, 0, sizeof(*out));
+		out->size = ret;
+		return sizeof(*out);
+	}
+	case FUSE_STATFS: {
+		struct fuse_statfs_out *out = (void *)out_buf;
+		struct statfs st;
+
+		if (statfs(path, &st) < 0)
+			return -errno;
+		memset(out, 0, sizeof(*out));
+		out->st.blocks = st.f_blocks;
+		out->st.bfree = st.f_bfree;
+		out->st.bavail = st.f_bavail;
+		out->st.files = st.f_files;
+		out->st.ffree = st.f_ffree;
+		out->st.bsize = st.f_bsize;
+		out->st.namelen = st.f_namelen;
+		out->st.frsize = st.f_frsize;
+		return sizeof(*out);
+	}
+	case FUSE_RELEASE:
+		close(((const struct fuse_release_in *)arg)->fh);
+		return 0;
+	case FUSE_FSYNC:
+		ret = fsync(((const struct fuse_fsync_in *)arg)->fh);
+		return ret < 0 ? -errno : 0;
+	case FUSE_OPENDIR: {
+		struct fuse_open_out *out = (void *)out_buf;
+		DIR *dir = opendir(path);
+
+		if (dir == NULL)
+			return -errno;
+		memset(out, 0, sizeof(*out));
+		out->fh = (uintptr_t)dir;
+		return sizeof(*out);
+	}
+	case FUSE_READDIR:
+		return do_readdir(arg);
+	case FUSE_RELEASEDIR:
+		closedir((DIR *)(uintptr_t)((const struct fuse_release_in *)arg)
+				 ->fh);
+		return 0;
+	default:
+		return -ENOSYS;
+	}
+}
+
+static void serve(int fuse_fd, const char *backing_dir)
+{
+	node_paths[FUSE_ROOT_ID] = strdup(backing_dir);
+
+	for (;;) {
+		struct fuse_in_header *header = (void *)in_buf;
+		struct fuse_out_header out_header;
+		ssize_t len = read(fuse_fd, in_buf, sizeof(in_buf));
+		int ret;
+
+		if (len < 0 && errno == ENODEV)
+			// The filesystem is unmounted.
+			exit(EXIT_SUCCESS);
+		CHECK(len >= (ssize_t)sizeof(*header));
+
+		ret = handle_request(header, header + 1);
+		if (header->opcode == FUSE_FORGET)
+			continue;
+
+		out_header.unique = header->unique;
+		out_header.error = ret < 0 ? ret : 0;
+		out_header.len = sizeof(out_header) + (ret < 0 ? 0 : ret);
+		memmove(out_buf + sizeof(out_header), out_buf, ret < 0 ? 0 : ret);
+		memcpy(out_buf, &out_header, sizeof(out_header));
+		CHECK(write(fuse_fd, out_buf, out_header.len) == out_header.len);
+	}
+}
+
+// ============================================================================
+// The tests on the mounted filesystem
+// ============================================================================
+
+static void read_file(const char *path, char *buf, size_t size)
+{
+	int fd = open(path, O_RDONLY);
+	ssize_t len;
+
+	CHECK(fd >= 0);
+	len = read(fd, buf, size - 1);
+	CHECK(len >= 0);
+	buf[len] = '\0';
+	CHECK(close(fd) == 0);
+}
+
+static void write_file(const char *path, const char *content)
+{
+	int fd = open(path, O_WRONLY | O_CREAT | O_TRUNC, 0644);
+
+	CHECK(fd >= 0);
+	CHECK(write(fd, content, strlen(content)) == (ssize_t)strlen(content));
+	CHECK(fsync(fd) == 0);
+	CHECK(close(fd) == 0);
+}
+
+static int count_entries(const char *path)
+{
+	DIR *dir = opendir(path);
+	struct dirent *dirent;
+	int count = 0;
+
+	CHECK(dir != NULL);
+	while ((dirent = readdir(dir)) != NULL) {
+		if (strcmp(dirent->d_name, ".") != 0 &&
+		    strcmp(dirent->d_name, "..") != 0)
+			count++;
+	}
+	CHECK(closedir(dir) == 0);
+	return count;
+}
+
+static void run_tests(const char *mnt, const char *backing_dir)
+{
+	char path[PATH_MAX], path2[PATH_MAX], buf[256];
+	struct stat st;
+	struct statfs stfs;
+
+	// Files are written through the daemon into the backing directory
+	snprintf(path, sizeof(path), "%s/file", mnt);
+	write_file(path, "hello fuse");
+	read_file(path, buf, sizeof(buf));
+	CHECK(strcmp(buf, "hello fuse") == 0);
+	snprintf(path2, sizeof(path2), "%s/file", backing_dir);
+	read_file(path2, buf, sizeof(buf));
+	CHECK(strcmp(buf, "hello fuse") == 0);
+
+	// Attributes
+	CHECK(stat(path, &st) == 0);
+	CHECK(S_ISREG(st.st_mode) && st.st_size == strlen("hello fuse"));
+	CHECK(chmod(path, 0600) == 0);
+	CHECK(stat(path2, &st) == 0 && (st.st_mode & 07777) == 0600);
+	CHECK(truncate(path, 5) == 0);
+	read_file(path, buf, sizeof(buf));
+	CHECK(strcmp(buf, "hello") == 0);
+	CHECK(statfs(mnt, &stfs) == 0);
+
+	// Directories
+	snprintf(path, sizeof(path), "%s/dir", mnt);
+	CHECK(mkdir(path, 0755) == 0);
+	snprintf(path, sizeof(path), "%s/dir/nested", mnt);
+	write_file(path, "nested");
+	snprintf(path, sizeof(path), "%s/dir", mnt);
+	CHECK(count_entries(path) == 1);
+	CHECK(count_entries(mnt) == 2);
+
+	// Renames and links
+	snprintf(path, sizeof(path), "%s/dir", mnt);
+	snprintf(path2, sizeof(path2), "%s/renamed", mnt);
+	CHECK(rename(path, path2) == 0);
+	snprintf(path, sizeof(path), "%s/renamed/nested", mnt);
+	read_file(path, buf, sizeof(buf));
+	CHECK(strcmp(buf, "nested") == 0);
+	snprintf(path2, sizeof(path2), "%s/hardlink", mnt);
+	CHECK(link(path, path2) == 0);
+	CHECK(stat(path2, &st) == 0 && st.st_nlink == 2);
+
+	// Symlinks
+	snprintf(path, sizeof(path), "%s/symlink", mnt);
+	CHECK(symlink("renamed/nested", path) == 0);
+	CHECK(readlink(path, buf, sizeof(buf)) ==
+	      (ssize_t)strlen("renamed/nested"));
+	read_file(path, buf, sizeof(buf));
+	CHECK(strcmp(buf, "nested") == 0);
+
+	// Removals
+	CHECK(unlink(path) == 0);
+	CHECK(unlink(path2) == 0);
+	snprintf(path, sizeof(path), "%s/renamed/nested", mnt);
+	CHECK(unlink(path) == 0);
+	snprintf(path, sizeof(path), "%s/renamed", mnt);
+	CHECK(rmdir(path) == 0);
+	snprintf(path, sizeof(path), "%s/file", mnt);
+	CHECK(unlink(path) == 0);
+	CHECK(stat(path, &st) < 0 && errno == ENOENT);
+	CHECK(count_entries(backing_dir) == 0);
+}
+
+int main(int argc, char *argv[])
+{
+	char options[128];
+	int fuse_fd, status;
+	pid_t pid;
+
+	if (argc != 3) {
+		fprintf(stderr, "usage: %s <backing dir> <mountpoint>\n",
+			argv[0]);
+		return EXIT_FAILURE;
+	}
+
+	fuse_fd = open("/dev/fuse", O_RDWR);
+	CHECK(fuse_fd >= 0);
+	snprintf(options, sizeof(options),
+		 "fd=%d,rootmode=40000,user_id=0,group_id=0", fuse_fd);
+	CHECK(mount("passthrough", argv[2], "fuse", 0, options) == 0);
+
+	pid = fork();
+	CHECK(pid >= 0);
+	if (pid == 0)
+		serve(fuse_fd, argv[1]);
+	CHECK(close(fuse_fd) == 0);
+
+	run_tests(argv[2], argv[1]);
+
+	CHECK(umount(argv[2]) == 0);
+	CHECK(waitpid(pid, &status, 0) == pid);
+	CHECK(WIFEXITED(status) && WEXITSTATUS(status) == 0);
+
+	printf("FUSE passthrough test passed\n");
+	return EXIT_SUCCESS;
+}
@@ -0,0 +1,95 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include <fcntl.h>
+#include <stdint.h>
+#include <sys/ioctl.h>
+#include <linux/fs.h>
+#include <linux/loop.h>
+
+#define FILE_SIZE (64 * 1024)
+#define OFFSET 4096
+
+#define CHECK(cond)                                                     \
+	do {                                                            \
+		if (!(cond)) {                                          \
+			fprintf(stderr, "%s:%d: check failed: %s\n",    \
+				__FILE__, __LINE__, #cond);             \
+			perror("errno");                                \
+			exit(EXIT_FAILURE);                             \
+		}                                                       \
+	} while (0)
+
+int main(int argc, char **argv)
+{
+	char backing_path[256], loop_path[64], buf[512], expected[512];
+	struct loop_info64 info;
+	uint64_t size;
+	int control_fd, backing_fd, loop_fd, number;
+
+	if (argc != 2) {
+		printf("Usage: %s <directory>\n", argv[0]);
+		return EXIT_FAILURE;
+	}
+	snprintf(backing_path, sizeof(backing_path), "%s/loop_backing.img",
+		 argv[1]);
+
+	backing_fd = open(backing_path, O_RDWR | O_CREAT | O_TRUNC, 0644);
+	CHECK(backing_fd >= 0);
+	CHECK(ftruncate(backing_fd, FILE_SIZE) == 0);
+
+	control_fd = open("/dev/loop-control", O_RDWR);
+	CHECK(control_fd >= 0);
+	number = ioctl(control_fd, LOOP_CTL_GET_FREE);
+	CHECK(number >= 0);
+	snprintf(loop_path, sizeof(loop_path), "/dev/loop%d", number);
+
+	loop_fd = open(loop_path, O_RDWR);
+	CHECK(loop_fd >= 0);
+	CHECK(ioctl(loop_fd, LOOP_SET_FD, backing_fd) == 0);
+	CHECK(ioctl(loop_fd, LOOP_SET_FD, backing_fd) == -1);
+	CHECK(ioctl(loop_fd, BLKGETSIZE64, &size) == 0);
+	CHECK(size == FILE_SIZE);
+
+	// The data written to the loop device reach the backing file
+	memset(expected, 'a', sizeof(expected));
+	CHECK(pwrite(loop_fd, expected, sizeof(expected), OFFSET) ==
+	      sizeof(expected));
+	CHECK(fsync(loop_fd) == 0);
+	CHECK(pread(backing_fd, buf, sizeof(buf), OFFSET) == sizeof(buf));
+	CHECK(memcmp(buf, expected, sizeof(buf)) == 0);
+
+	// The device starts at the offset of the backing file
+	memset(&info, 0, sizeof(info));
+	info.lo_offset = OFFSET;
+	CHECK(ioctl(loop_fd, LOOP_SET_STATUS64, &info) == 0);
+	CHECK(ioctl(loop_fd, BLKGETSIZE64, &size) == 0);
+	CHECK(size == FILE_SIZE - OFFSET);
+	CHECK(pread(loop_fd, buf, sizeof(buf), 0) == sizeof(buf));
+	CHECK(memcmp(buf, expected, sizeof(buf)) == 0);
+
+	memset(&info, 0, sizeof(info));
+	CHECK(ioctl(loop_fd, LOOP_GET_STATUS64, &info) == 0);
+	CHECK(info.lo_number == number);
+	CHECK(info.lo_offset == OFFSET);
+	CHECK(strstr((char *)info.lo_file_name, "loop_backing.img") != NULL);
+
+	// A bound loop device is not free
+	CHECK(ioctl(control_fd, LOOP_CTL_GET_FREE) != number);
+
+	CHECK(ioctl(loop_fd, LOOP_CLR_FD) == 0);
+	CHECK(ioctl(loop_fd, BLKGETSIZE64, &size) == 0);
+	CHECK(size == 0);
+	CHECK(ioctl(loop_fd, LOOP_CLR_FD) == -1);
+
+	close(loop_fd);
+	close(control_fd);
+	close(backing_fd);
+	CHECK(unlink(backing_path) == 0);
+
+	printf("Loop device test passed on %s\n", argv[1]);
+	return EXIT_SUCCESS;
+}
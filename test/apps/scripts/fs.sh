@@ -51,12 +51,120 @@ test_fdatasync() {
     rm -f /ext2/test_fdatasync.txt
     fdatasync/fdatasync /exfat
     rm -f /exfat/test_fdatasync.txt
+    fdatasync/fdatasync /vfat
+    rm -f /vfat/test_fdatasync.txt
+}
+
+test_vfat() {
+    local vfat_dir="$1"
+
+    cd ${vfat_dir}
+    # Long file names keep their case, while the lookup ignores it
+    echo "vfat" > "A Long File Name.txt"
+    [ "$(cat "a long file name.TXT")" = "vfat" ]
+    [ "$(ls)" = "A Long File Name.txt" ]
+    mkdir dir
+    mv "A Long File Name.txt" dir/short.txt
+    [ "$(ls dir)" = "short.txt" ]
+    rm -r dir
+    [ -z "$(ls)" ]
+    # Test case for the big file feature
+    truncate -s 32M test_file.txt
+    check_file_size test_file.txt $((32 * 1024 * 1024))
+    truncate -s 2K test_file.txt
+    check_file_size test_file.txt $((2 * 1024))
+
+    # Clean up
+    rm -f test_file.txt
+    sync
+    cd -
+}
+
+test_overlayfs() {
+    local test_dir="/overlay_test"
+
+    mkdir -p ${test_dir}
+    mount -t tmpfs tmpfs ${test_dir}
+    mkdir -p ${test_dir}/lower/dir ${test_dir}/upper ${test_dir}/work ${test_dir}/merged
+    echo "lower" > ${test_dir}/lower/file
+    echo "lower" > ${test_dir}/lower/dir/removed
+    mount -t overlay overlay \
+        -o lowerdir=${test_dir}/lower,upperdir=${test_dir}/upper,workdir=${test_dir}/work \
+        ${test_dir}/merged
+
+    cd ${test_dir}/merged
+    # Copy-up on write leaves the lower layer untouched
+    echo "upper" >> file
+    [ "$(cat file)" = "$(printf 'lower\nupper')" ]
+    [ "$(cat ${test_dir}/lower/file)" = "lower" ]
+    # Removing a lower file leaves a whiteout in the upper layer
+    rm dir/removed
+    [ ! -e dir/removed ]
+    [ -c ${test_dir}/upper/dir/removed ]
+    [ -e ${test_dir}/lower/dir/removed ]
+    # A recreated directory does not show the lower entries
+    echo "lower" > ${test_dir}/lower/dir/hidden
+    rm dir/hidden
+    rmdir dir
+    mkdir dir
+    [ -z "$(ls dir)" ]
+    # Entries of all the layers are merged
+    touch new_file
+    [ "$(ls | sort | tr '\n' ' ')" = "dir file new_file " ]
+    cd -
+
+    umount ${test_dir}/merged
+    umount ${test_dir}
+    rm -rf ${test_dir}
+}
+
+test_fuse() {
+    local test_dir="/fuse_test"
+
+    mkdir -p ${test_dir}/backing ${test_dir}/mnt
+    fuse/fuse_passthrough ${test_dir}/backing ${test_dir}/mnt
+    rm -rf ${test_dir}
+}
+
+test_block_device() {
+    # The virtio block devices are named by the sorted serials: vexfat, vext2, vvfat
+    [ -b /dev/vda ]
+    [ -b /dev/vdb ]
+    [ -b /dev/vdc ]
+    [ "$(dd if=/dev/vdb bs=512 count=8 2>/dev/null | wc -c)" -eq 4096 ]
+    # The magic number of ext2 is at the offset 1080
+    [ "$(dd if=/dev/vdb bs=1 skip=1080 count=2 2>/dev/null | od -An -tx2 | tr -d ' ')" = "ef53" ]
+}
+
+test_loop_device() {
+    loop/loop_device /ext2
+    loop/loop_device /
 }
 
 echo "Start ext2 fs test......"
 test_ext2 "/ext2" "test_file.txt"
 echo "All ext2 fs test passed."
 
+echo "Start vfat fs test......"
+test_vfat "/vfat"
+echo "All vfat fs test passed."
+
 echo "Start fdatasync test......"
 test_fdatasync
-echo "All fdatasync test passed."
\ No newline at end of file
+echo "All fdatasync test passed."
+
+echo "Start overlayfs test......"
+test_overlayfs
+echo "All overlayfs test passed."
+
+echo "Start fuse test......"
+test_fuse
+echo "All fuse test passed."
+
+echo "Start block device test......"
+test_block_device
+echo "All block device test passed."
+
+echo "Start loop device test......"
+test_loop_device
+echo "All loop device test passed."
\ No newline at end of file
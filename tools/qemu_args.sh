@@ -6,6 +6,8 @@
 # The positional argument $1 is the scheme.
 # A switch "-ovmf" can be passed as an argument to enable OVMF.
 # The enrivonmental variable VSOCK can be passed as 1 to trigger vsock module.
+# The enrivonmental variable VIRTFS can be passed as a host directory to share it
+# with the guest through virtio-9p, which can be mounted with the tag "hostshare".
 
 RAND_PORT_NUM1=$(shuf -i 1024-65535 -n 1)
 RAND_PORT_NUM2=$(shuf -i 1024-65535 -n 1)
@@ -25,8 +27,9 @@ COMMON_QEMU_ARGS="\
     -netdev user,id=net01,hostfwd=tcp::$RAND_PORT_NUM1-:22,hostfwd=tcp::$RAND_PORT_NUM2-:8080 \
     -object filter-dump,id=filter0,netdev=net01,file=virtio-net.pcap \
     -device isa-debug-exit,iobase=0xf4,iosize=0x04 \
-    -drive if=none,format=raw,id=x0,file=./test/build/ext2.img \
-    -drive if=none,format=raw,id=x1,file=./test/build/exfat.img \
+    -drive if=none,format=raw,id=x0,file=./test/build/ext2.img,discard=unmap \
+    -drive if=none,format=raw,id=x1,file=./test/build/exfat.img,discard=unmap \
+    -drive if=none,format=raw,id=x2,file=./test/build/vfat.img,discard=unmap \
 "
 
 if [ "$1" = "iommu" ]; then
@@ -42,6 +45,7 @@ QEMU_ARGS="\
     -machine q35,kernel-irqchip=split \
     -device virtio-blk-pci,bus=pcie.0,addr=0x6,drive=x0,serial=vext2,disable-legacy=on,disable-modern=off$IOMMU_DEV_EXTRA \
     -device virtio-blk-pci,bus=pcie.0,addr=0x7,drive=x1,serial=vexfat,disable-legacy=on,disable-modern=off$IOMMU_DEV_EXTRA \
+    -device virtio-blk-pci,bus=pcie.0,addr=0x8,drive=x2,serial=vvfat,disable-legacy=on,disable-modern=off$IOMMU_DEV_EXTRA \
     -device virtio-keyboard-pci,disable-legacy=on,disable-modern=off$IOMMU_DEV_EXTRA \
     -device virtio-net-pci,netdev=net01,disable-legacy=on,disable-modern=off$IOMMU_DEV_EXTRA \
     -device virtio-serial-pci,disable-legacy=on,disable-modern=off$IOMMU_DEV_EXTRA \
@@ -56,6 +60,7 @@ MICROVM_QEMU_ARGS="\
     -no-user-config \
     -device virtio-blk-device,drive=x0,serial=vext2 \
     -device virtio-blk-device,drive=x1,serial=vexfat \
+    -device virtio-blk-device,drive=x2,serial=vvfat \
     -device virtio-keyboard-device \
     -device virtio-net-device,netdev=net01 \
     -device virtio-serial-device \
@@ -79,6 +84,22 @@ if [ "$VSOCK" = "1" ]; then
     fi
 fi
 
+if [ -n "$VIRTFS" ]; then
+    echo "[$1] Shared host directory $VIRTFS with tag hostshare" 1>&2
+    if [ "$1" = "microvm" ]; then
+        MICROVM_QEMU_ARGS="
+            $MICROVM_QEMU_ARGS \
+            -fsdev local,path=$VIRTFS,security_model=none,id=fs0 \
+            -device virtio-9p-device,fsdev=fs0,mount_tag=hostshare \
+        "
+    else
+        QEMU_ARGS="
+            $QEMU_ARGS \
+            -fsdev local,path=$VIRTFS,security_model=none,id=fs0 \
+            -device virtio-9p-pci,fsdev=fs0,mount_tag=hostshare,disable-legacy=on,disable-modern=off$IOMMU_DEV_EXTRA \
+        "
+    fi
+fi
 
 if [ "$1" = "microvm" ]; then
     QEMU_ARGS=$MICROVM_QEMU_ARGS